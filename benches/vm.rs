@@ -0,0 +1,98 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use dcbor::prelude::*;
+use dcbor_pattern::{Matcher, Pattern, Program};
+
+const ELEMENTS: u64 = 10_000;
+
+/// An array of `ELEMENTS` numbers.
+fn large_array() -> CBOR {
+    (0..ELEMENTS).map(CBOR::from).collect::<Vec<_>>().into()
+}
+
+/// Checks that `pattern` matches the whole array, capturing each element
+/// once and in order, so that a faster run is never a wrong one.
+fn assert_captures_every_element(pattern: &Pattern, haystack: &CBOR) {
+    let (paths, captures) = pattern.paths_with_captures(haystack);
+    assert_eq!(paths, vec![vec![haystack.clone()]]);
+    let expected: Vec<_> = (0..ELEMENTS)
+        .map(|n| vec![haystack.clone(), CBOR::from(n)])
+        .collect();
+    assert_eq!(captures["n"], expected);
+}
+
+fn bench_captures(c: &mut Criterion) {
+    let haystack = large_array();
+    for source in ["[(@n(number))*]", "[(@n(number))*] | text"] {
+        let pattern = Pattern::parse(source).unwrap();
+        assert_captures_every_element(&pattern, &haystack);
+        c.bench_function(source, |b| {
+            b.iter(|| pattern.paths_with_captures(&haystack))
+        });
+    }
+}
+
+fn bench_search(c: &mut Criterion) {
+    let haystack = large_array();
+    let pattern = Pattern::parse("search(@n(number))").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_eq!(paths.len(), ELEMENTS as usize);
+    assert_eq!(captures["n"], paths);
+    c.bench_function("search(@n(number))", |b| {
+        b.iter(|| pattern.paths_with_captures(&haystack))
+    });
+}
+
+fn bench_nested_array_captures(c: &mut Criterion) {
+    const ROWS: u64 = ELEMENTS / 10;
+    let haystack: CBOR = (0..ROWS)
+        .map(|n| CBOR::from(vec![CBOR::from(n)]))
+        .collect::<Vec<_>>()
+        .into();
+    let source = "[(@row([@n(number)]))*]";
+    let pattern = Pattern::parse(source).unwrap();
+    let expected = pattern.paths_with_captures(&haystack);
+    assert_eq!(expected.1["row"].len(), ROWS as usize);
+    assert_eq!(expected.1["n"].len(), ROWS as usize);
+
+    // Each row is matched with the inner array's own program, which is
+    // compiled once and reused for every later row and run
+    let program = Program::compile(&pattern);
+    assert_eq!(program.run(&haystack), expected);
+    c.bench_function(source, |b| {
+        b.iter(|| pattern.paths_with_captures(&haystack))
+    });
+    c.bench_function(&format!("{} compiled", source), |b| {
+        b.iter(|| program.run(&haystack))
+    });
+    c.bench_function(&format!("{} recompiled", source), |b| {
+        b.iter(|| Program::compile(&pattern).run(&haystack))
+    });
+}
+
+/// Maps nested `depth` deep under the key `"a"`, each also holding an array
+/// under `"b"`, with `42` at the bottom.
+fn chain(depth: u64) -> CBOR {
+    let mut node = CBOR::from(42);
+    for i in 0..depth {
+        let mut map = Map::new();
+        map.insert("a", node);
+        map.insert("b", vec![i, 1, 2]);
+        node = map.into();
+    }
+    node
+}
+
+fn bench_nested_search(c: &mut Criterion) {
+    let haystack = chain(250);
+    let pattern = Pattern::parse(r#"search({"a": search(42)})"#).unwrap();
+    assert_eq!(pattern.paths(&haystack).len(), 250);
+    c.bench_function("nested search", |b| b.iter(|| pattern.paths(&haystack)));
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_captures, bench_search, bench_nested_array_captures,
+        bench_nested_search
+}
+criterion_main!(benches);
@@ -0,0 +1,132 @@
+/// A small grep for dCBOR: prints every node of a document that matches a
+/// pattern.
+///
+/// ```text
+/// dgrep [--json] [--count] [--first] PATTERN [FILE]
+/// ```
+///
+/// The document is read from FILE, or from standard input if FILE is
+/// omitted or `-`. Input that is UTF-8 text in dCBOR diagnostic notation
+/// is parsed as such; anything else is decoded as binary dCBOR.
+///
+/// The exit status is 0 if anything matched, 1 if nothing did, and 2 on
+/// errors, like grep's.
+use std::{
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Captures, FormatPathsOpts, MatchResult, Pattern};
+
+const USAGE: &str = "usage: dgrep [--json] [--count] [--first] PATTERN [FILE]";
+
+#[derive(Default)]
+struct Args {
+    json: bool,
+    count: bool,
+    first: bool,
+    pattern: String,
+    file: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut positional = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => args.json = true,
+            "--count" => args.count = true,
+            "--first" => args.first = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => {
+                return Err(format!("unknown option {}\n{}", arg, USAGE));
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let mut positional = positional.into_iter();
+    args.pattern = positional.next().ok_or(USAGE)?;
+    args.file = positional.next().filter(|file| file != "-");
+    if positional.next().is_some() {
+        return Err(USAGE.to_string());
+    }
+    Ok(args)
+}
+
+/// Reads the document, as diagnostic notation if it is text that parses as
+/// such, and as binary dCBOR otherwise.
+fn read_document(file: Option<&str>) -> Result<CBOR, String> {
+    let data = match file {
+        Some(file) => std::fs::read(file)
+            .map_err(|e| format!("cannot read {}: {}", file, e))?,
+        None => {
+            let mut data = Vec::new();
+            io::stdin()
+                .read_to_end(&mut data)
+                .map_err(|e| format!("cannot read standard input: {}", e))?;
+            data
+        }
+    };
+
+    if let Ok(text) = std::str::from_utf8(&data)
+        && let Ok(cbor) = parse_dcbor_item(text)
+    {
+        return Ok(cbor);
+    }
+    CBOR::try_from_data(&data).map_err(|e| {
+        format!("input is neither diagnostic notation nor dCBOR: {}", e)
+    })
+}
+
+fn run(args: &Args) -> Result<bool, String> {
+    let pattern = Pattern::parse(&args.pattern)
+        .map_err(|e| e.to_display(&args.pattern))?;
+    let document = read_document(args.file.as_deref())?;
+
+    let limit = if args.first { 1 } else { usize::MAX };
+    let matches = pattern.find_iter(&document).take(limit);
+
+    if args.count {
+        let count = matches.count();
+        println!("{}", count);
+        return Ok(count > 0);
+    }
+
+    // Gather the matches into one set of paths and captures
+    let mut paths = Vec::new();
+    let mut captures = Captures::new();
+    for m in matches {
+        let (path, match_captures) = m.into_parts();
+        paths.push(path);
+        for (name, capture_paths) in match_captures {
+            captures.entry(name).or_default().extend(capture_paths);
+        }
+    }
+
+    let result = MatchResult::new(pattern.to_string(), paths, captures);
+    let output = result.format_opt(FormatPathsOpts::new().json(args.json));
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    Ok(!result.is_empty())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::from(2)
+        }
+    }
+}
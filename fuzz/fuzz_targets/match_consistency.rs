@@ -0,0 +1,20 @@
+#![no_main]
+
+//! Matching a generated pattern against a generated value never panics, and
+//! `matches`, `paths`, `paths_with_captures`, and the compiled program agree.
+
+use dcbor_pattern::{ArbitraryCBOR, Matcher, Pattern, Program};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (Pattern, ArbitraryCBOR)| {
+    let (pattern, ArbitraryCBOR(haystack)) = input;
+    let matches = pattern.matches(&haystack);
+    let paths = pattern.paths(&haystack);
+    let (captured_paths, _) = pattern.paths_with_captures(&haystack);
+    assert_eq!(paths, captured_paths, "{} on {}", pattern, haystack);
+    assert_eq!(matches, !paths.is_empty(), "{} on {}", pattern, haystack);
+
+    let program = Program::compile(&pattern);
+    let (vm_paths, _) = program.run(&haystack);
+    assert_eq!(matches, !vm_paths.is_empty(), "{} on {}", pattern, haystack);
+});
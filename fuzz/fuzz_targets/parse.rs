@@ -0,0 +1,10 @@
+#![no_main]
+
+//! Parsing arbitrary text never panics.
+
+use dcbor_pattern::Pattern;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let _ = Pattern::parse(source);
+});
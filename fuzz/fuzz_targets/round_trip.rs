@@ -0,0 +1,26 @@
+#![no_main]
+
+//! A pattern displays as text that parses back to the same pattern, whether
+//! it was parsed from arbitrary text or generated.
+
+use dcbor_pattern::Pattern;
+use libfuzzer_sys::fuzz_target;
+
+fn check(pattern: &Pattern) {
+    let displayed = pattern.to_string();
+    assert_eq!(
+        Pattern::parse(&displayed).as_ref(),
+        Ok(pattern),
+        "{} displayed as {}",
+        pattern,
+        displayed
+    );
+}
+
+fuzz_target!(|input: (&str, Pattern)| {
+    let (source, pattern) = input;
+    if let Ok(parsed) = Pattern::parse(source) {
+        check(&parsed);
+    }
+    check(&pattern);
+});
@@ -0,0 +1,193 @@
+//! [`Arbitrary`] implementations for fuzzing and property tests.
+//!
+//! Generated patterns come from a bounded subset of the syntax, built with
+//! the [`dsl`](crate::dsl) builders so that each is a pattern the parser
+//! could produce, with a distinct name for every capture and none inside a
+//! negation. Generated haystacks are small trees over a handful of values
+//! that the patterns' literals also draw on, so that matches are common.
+
+use std::ops::Bound;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use dcbor::prelude::*;
+use known_values::KnownValue;
+
+use crate::{Pattern, dsl::*};
+
+/// How deeply generated patterns and haystacks nest.
+const MAX_DEPTH: usize = 3;
+
+/// The most elements, entries, or alternatives a generated node holds.
+const MAX_LEN: usize = 3;
+
+const NUMBERS: &[i64] = &[-1, 0, 1, 2, 42];
+const TEXTS: &[&str] = &["", "a", "b", "ab"];
+const BYTES: &[&[u8]] = &[&[], &[0x00], &[0x01, 0x02]];
+const TAGS: &[u64] = &[1, 24, 100];
+const KNOWN_VALUES: &[u64] = &[1, 4];
+
+impl<'a> Arbitrary<'a> for Pattern {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        PatternBuilder { captures: 0 }.pattern(u, MAX_DEPTH, true)
+    }
+}
+
+/// Builds a pattern, numbering its captures so that their names differ.
+struct PatternBuilder {
+    captures: usize,
+}
+
+impl PatternBuilder {
+    fn pattern(
+        &mut self,
+        u: &mut Unstructured<'_>,
+        depth: usize,
+        may_capture: bool,
+    ) -> Result<Pattern> {
+        if depth == 0 || u.ratio(1, 3)? {
+            return leaf(u);
+        }
+        let depth = depth - 1;
+        Ok(match u.int_in_range(0..=11)? {
+            0 => {
+                let mut alternatives = self.patterns(u, depth, may_capture)?;
+                let first = alternatives.remove(0);
+                alternatives.into_iter().fold(first, PatternExt::or)
+            }
+            1 => {
+                let mut conjuncts = self.patterns(u, depth, may_capture)?;
+                let first = conjuncts.remove(0);
+                conjuncts.into_iter().fold(first, PatternExt::and)
+            }
+            2 => not(self.pattern(u, depth, false)?),
+            3 if may_capture => {
+                self.captures += 1;
+                let name = format!("c{}", self.captures);
+                capture(name, self.pattern(u, depth, may_capture)?)
+            }
+            4 => search(self.pattern(u, depth, may_capture)?),
+            5 => {
+                let mut elements = Vec::new();
+                for _ in 0..u.int_in_range(0..=MAX_LEN)? {
+                    let element = self.pattern(u, depth, may_capture)?;
+                    elements.push(match u.int_in_range(0..=3)? {
+                        0 => element.repeat(..),
+                        1 => element.repeat_lazy(1..),
+                        2 => element.repeat(0..=1),
+                        _ => element,
+                    });
+                }
+                array(elements)
+            }
+            6 => {
+                let mut entries = Vec::new();
+                for _ in 0..u.int_in_range(1..=MAX_LEN)? {
+                    entries.push((
+                        self.pattern(u, depth, may_capture)?,
+                        self.pattern(u, depth, may_capture)?,
+                    ));
+                }
+                map(entries)
+            }
+            7 => map_value(
+                self.pattern(u, depth, may_capture)?,
+                self.pattern(u, depth, may_capture)?,
+            ),
+            8 => {
+                let content = self.pattern(u, depth, may_capture)?;
+                if u.arbitrary()? {
+                    any_tagged_with_content(content)
+                } else {
+                    tagged(*u.choose(TAGS)?, content)
+                }
+            }
+            9 => child(self.pattern(u, depth, may_capture)?),
+            10 => untag(self.pattern(u, depth, may_capture)?),
+            _ => bstr_cbor(self.pattern(u, depth, may_capture)?),
+        })
+    }
+
+    /// Builds between one and [`MAX_LEN`] patterns.
+    fn patterns(
+        &mut self,
+        u: &mut Unstructured<'_>,
+        depth: usize,
+        may_capture: bool,
+    ) -> Result<Vec<Pattern>> {
+        let mut patterns = Vec::new();
+        for _ in 0..u.int_in_range(1..=MAX_LEN)? {
+            patterns.push(self.pattern(u, depth, may_capture)?);
+        }
+        Ok(patterns)
+    }
+}
+
+/// Builds a pattern with no other patterns inside it.
+fn leaf(u: &mut Unstructured<'_>) -> Result<Pattern> {
+    Ok(match u.int_in_range(0..=15)? {
+        0 => any(),
+        1 => bool(),
+        2 => bool_value(u.arbitrary()?),
+        3 => number(),
+        4 => number_value(*u.choose(NUMBERS)? as f64),
+        5 => {
+            let low = *u.choose(NUMBERS)?;
+            let high = *u.choose(NUMBERS)?;
+            let (low, high) = (low.min(high) as f64, low.max(high) as f64);
+            match u.int_in_range(0..=2)? {
+                0 => number_range(low..=high),
+                1 => number_bounded(low..high),
+                _ => number_bounded((Bound::Excluded(low), Bound::Unbounded)),
+            }
+        }
+        6 => text(),
+        7 => text_value(*u.choose(TEXTS)?),
+        8 => bstr(),
+        9 => bstr_value(u.choose(BYTES)?),
+        10 => null(),
+        11 => known(),
+        12 => Pattern::known_value(KnownValue::new(*u.choose(KNOWN_VALUES)?)),
+        13 => any_tagged(),
+        14 => array_len(0..=u.int_in_range(0..=MAX_LEN)?),
+        _ => map_len(u.int_in_range(0..=MAX_LEN)?..),
+    })
+}
+
+/// A small CBOR value to match arbitrary patterns against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArbitraryCBOR(pub CBOR);
+
+impl<'a> Arbitrary<'a> for ArbitraryCBOR {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        cbor(u, MAX_DEPTH).map(ArbitraryCBOR)
+    }
+}
+
+fn cbor(u: &mut Unstructured<'_>, depth: usize) -> Result<CBOR> {
+    let kinds = if depth == 0 { 0..=5 } else { 0..=9 };
+    let depth = depth.saturating_sub(1);
+    Ok(match u.int_in_range(kinds)? {
+        0 => CBOR::from(*u.choose(NUMBERS)?),
+        1 => CBOR::from(*u.choose(TEXTS)?),
+        2 => CBOR::to_byte_string(*u.choose(BYTES)?),
+        3 => CBOR::from(u.arbitrary::<bool>()?),
+        4 => CBOR::null(),
+        5 => CBOR::from(KnownValue::new(*u.choose(KNOWN_VALUES)?)),
+        6 => {
+            let mut elements = Vec::new();
+            for _ in 0..u.int_in_range(0..=MAX_LEN)? {
+                elements.push(cbor(u, depth)?);
+            }
+            CBOR::from(elements)
+        }
+        7 => {
+            let mut map = Map::new();
+            for _ in 0..u.int_in_range(0..=MAX_LEN)? {
+                map.insert(cbor(u, depth)?, cbor(u, depth)?);
+            }
+            CBOR::from(map)
+        }
+        8 => CBOR::to_tagged_value(*u.choose(TAGS)?, cbor(u, depth)?),
+        _ => CBOR::to_byte_string(cbor(u, depth)?.to_cbor_data()),
+    })
+}
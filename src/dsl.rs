@@ -0,0 +1,281 @@
+//! A small combinator layer for building patterns in Rust.
+//!
+//! The functions here are named after the keywords of the pattern syntax, and
+//! [`PatternExt`] adds the operators as methods, so patterns can be written
+//! much as they would be in text:
+//!
+//! ```
+//! use dcbor_pattern::dsl::*;
+//!
+//! let built = array([
+//!     any().repeat(0..),
+//!     capture("item", number_value(42)),
+//!     any().repeat(0..),
+//! ]);
+//! assert_eq!(built, Pattern::parse("[(*)*, @item(42), (*)*]").unwrap());
+//! ```
+//!
+//! Every builder produces the same [`Pattern`] the parser produces for the
+//! equivalent text. In particular, [`PatternExt::repeat`] and its variants
+//! stand for a parenthesized group with a quantifier, so
+//! `number().repeat(1..)` is `(number)+`. A group without a quantifier
+//! needs no builder, since parentheses only group.
+
+use std::ops::RangeBounds;
+
+use dcbor::prelude::*;
+
+use crate::{
+    AndPattern, ArrayPattern, MapPattern, MetaPattern, OrPattern, PathStep,
+    StructurePattern, parse::meta::merge_lengths,
+};
+pub use crate::{Matcher, Pattern, Quantifier, Reluctance};
+
+/// Creates a pattern that matches any value: `*`.
+pub fn any() -> Pattern { Pattern::any() }
+
+/// Creates a pattern that matches any boolean: `bool`.
+pub fn bool() -> Pattern { Pattern::any_bool() }
+
+/// Creates a pattern that matches the given boolean: `true` or `false`.
+pub fn bool_value(value: bool) -> Pattern { Pattern::bool(value) }
+
+/// Creates a pattern that matches any number: `number`.
+pub fn number() -> Pattern { Pattern::any_number() }
+
+/// Creates a pattern that matches the given number, e.g. `42`.
+pub fn number_value(value: impl Into<f64>) -> Pattern { Pattern::number(value) }
+
+/// Creates a pattern that matches numbers in the given inclusive range, e.g.
+/// `1...10`.
+pub fn number_range<A>(range: std::ops::RangeInclusive<A>) -> Pattern
+where
+    A: Into<f64> + Copy,
+{
+    Pattern::number_range(range)
+}
+
+/// Creates a pattern that matches numbers within bounds that may be exclusive
+/// or open, e.g. `10<...<20` for
+/// `(Bound::Excluded(10.0), Bound::Excluded(20.0))`.
+pub fn number_bounded(bounds: impl std::ops::RangeBounds<f64>) -> Pattern {
+    Pattern::number_bounded(bounds)
+}
+
+/// Creates a pattern that matches any text: `text`.
+pub fn text() -> Pattern { Pattern::any_text() }
+
+/// Creates a pattern that matches the given text, e.g. `"hello"`.
+pub fn text_value(value: impl Into<String>) -> Pattern { Pattern::text(value) }
+
+/// Creates a pattern that matches text against a regex, e.g. `/h.*o/`.
+pub fn text_regex(regex: regex::Regex) -> Pattern { Pattern::text_regex(regex) }
+
+/// Creates a pattern that matches any byte string: `bstr`.
+pub fn bstr() -> Pattern { Pattern::any_byte_string() }
+
+/// Creates a pattern that matches the given bytes, e.g. `h'cafe'`.
+pub fn bstr_value(value: impl AsRef<[u8]>) -> Pattern {
+    Pattern::byte_string(value)
+}
+
+/// Creates a pattern that matches byte strings holding an encoded dCBOR item
+/// that `pattern` matches: `bstr(cbor: pattern)`.
+pub fn bstr_cbor(pattern: Pattern) -> Pattern {
+    Pattern::byte_string_decoded(pattern)
+}
+
+/// Creates a pattern that matches any date: `date`.
+pub fn date() -> Pattern { Pattern::any_date() }
+
+/// Creates a pattern that matches null: `null`.
+pub fn null() -> Pattern { Pattern::null() }
+
+/// Creates a pattern that matches any known value: `known`.
+pub fn known() -> Pattern { Pattern::any_known_value() }
+
+/// Creates a pattern that matches any digest: `digest`.
+pub fn digest() -> Pattern { Pattern::any_digest() }
+
+/// Creates a pattern that matches exactly the given item, e.g. `cbor'[1]'`.
+pub fn cbor(value: impl Into<CBOR>) -> Pattern { Pattern::cbor(value) }
+
+/// Creates a pattern that matches any array: `array`.
+pub fn any_array() -> Pattern { Pattern::any_array() }
+
+/// Creates a pattern that matches an array whose elements match the given
+/// patterns in order, e.g. `[number, (text)*]`.
+///
+/// An empty list matches only the empty array, like `[{0}]`.
+pub fn array(elements: impl IntoIterator<Item = Pattern>) -> Pattern {
+    let mut elements: Vec<Pattern> = elements.into_iter().collect();
+    let pattern = match elements.len() {
+        0 => ArrayPattern::with_length_range(0..=0),
+        1 => ArrayPattern::with_elements(elements.remove(0)),
+        _ => ArrayPattern::with_elements(Pattern::sequence(elements)),
+    };
+    Pattern::Structure(StructurePattern::Array(pattern))
+}
+
+/// Creates a pattern that matches arrays with a number of elements in the
+/// given range, e.g. `[{2,5}]`.
+pub fn array_len(range: impl RangeBounds<usize>) -> Pattern {
+    Pattern::Structure(StructurePattern::Array(
+        ArrayPattern::with_length_range(range),
+    ))
+}
+
+/// Creates a pattern that matches any map: `map`.
+pub fn any_map() -> Pattern { Pattern::any_map() }
+
+/// Creates a pattern that matches a map satisfying every key-value
+/// constraint, e.g. `{"id": number, "name": text}`.
+pub fn map(entries: impl IntoIterator<Item = (Pattern, Pattern)>) -> Pattern {
+    Pattern::Structure(StructurePattern::Map(
+        MapPattern::with_key_value_constraints(entries.into_iter().collect()),
+    ))
+}
+
+/// Creates a pattern that matches maps with a number of entries in the given
+/// range, e.g. `{{1,}}`.
+pub fn map_len(range: impl RangeBounds<usize>) -> Pattern {
+    Pattern::Structure(StructurePattern::Map(MapPattern::with_length_range(
+        range,
+    )))
+}
+
+/// Creates a pattern that matches a map with an entry whose key and value
+/// match, with paths continuing into the value, e.g. `{"a"}: number`.
+pub fn map_value(key: Pattern, value: Pattern) -> Pattern {
+    Pattern::map_value(key, value)
+}
+
+/// Creates a pattern that matches any tagged value: `tagged`.
+pub fn any_tagged() -> Pattern { Pattern::any_tagged() }
+
+/// Creates a pattern that matches a tagged value with any tag and the given
+/// content, e.g. `tagged(*, text)`.
+pub fn any_tagged_with_content(content: Pattern) -> Pattern {
+    Pattern::any_tagged_with_content(content)
+}
+
+/// Creates a pattern that matches a tagged value with the given tag and
+/// content, e.g. `tagged(1, number)`.
+pub fn tagged(tag: impl Into<Tag>, content: Pattern) -> Pattern {
+    Pattern::tagged(tag, content)
+}
+
+/// Creates a pattern that captures whatever the inner pattern matches, e.g.
+/// `@name(text)`.
+pub fn capture(name: impl AsRef<str>, pattern: Pattern) -> Pattern {
+    Pattern::capture(name, pattern)
+}
+
+/// Creates a pattern that matches the value captured earlier under the given
+/// name, e.g. `=name`.
+pub fn backref(name: impl AsRef<str>) -> Pattern { Pattern::backref(name) }
+
+/// Creates a pattern that searches the whole tree, e.g. `search(number)`.
+pub fn search(pattern: Pattern) -> Pattern { Pattern::search(pattern) }
+
+/// Creates a pattern that matches the direct children of a node, e.g.
+/// `child(text)`.
+pub fn child(pattern: Pattern) -> Pattern { Pattern::child(pattern) }
+
+/// Creates a pattern that matches through any tags around a node, e.g.
+/// `untag(42)`.
+pub fn untag(pattern: Pattern) -> Pattern { Pattern::untag(pattern) }
+
+/// Creates a pattern that matches at the end of a path of map keys and array
+/// indexes, e.g. `at("items", 2, number)`.
+pub fn at(
+    steps: impl IntoIterator<Item = PathStep>,
+    pattern: Pattern,
+) -> Pattern {
+    Pattern::at(steps, pattern)
+}
+
+/// Creates a pattern that matches nodes whose subtree has no match, e.g.
+/// `none(null)`.
+pub fn none(pattern: Pattern) -> Pattern { Pattern::none(pattern) }
+
+/// Creates a pattern that matches when the inner pattern doesn't, e.g.
+/// `!text`.
+pub fn not(pattern: Pattern) -> Pattern { Pattern::not_matching(pattern) }
+
+/// The pattern operators as methods, for chaining builders.
+pub trait PatternExt: Sized {
+    /// Matches if either pattern matches: `self | other`.
+    ///
+    /// Alternatives are flattened as the parser does, so
+    /// `a.or(b).or(c)` is `a | b | c`.
+    fn or(self, other: Pattern) -> Pattern;
+
+    /// Matches if both patterns match: `self & other`.
+    ///
+    /// Conjuncts are flattened, and length constraints on the same kind of
+    /// value merged, as the parser does.
+    fn and(self, other: Pattern) -> Pattern;
+
+    /// Captures whatever this pattern matches: `@name(self)`.
+    fn capture(self, name: impl AsRef<str>) -> Pattern;
+
+    /// Searches the whole tree for this pattern: `search(self)`.
+    fn search(self) -> Pattern;
+
+    /// Returns this pattern unchanged, as `(self)` only groups.
+    #[deprecated(note = "parentheses only group, so use the pattern itself")]
+    fn group(self) -> Pattern;
+
+    /// Repeats this pattern greedily, e.g. `(self){2,5}` for `2..=5`.
+    fn repeat(self, range: impl RangeBounds<usize>) -> Pattern;
+
+    /// Repeats this pattern lazily, e.g. `(self)*?` for `0..`.
+    fn repeat_lazy(self, range: impl RangeBounds<usize>) -> Pattern;
+
+    /// Repeats this pattern possessively, e.g. `(self)++` for `1..`.
+    fn repeat_possessive(self, range: impl RangeBounds<usize>) -> Pattern;
+
+    /// Repeats this pattern with the given quantifier.
+    fn quantified(self, quantifier: Quantifier) -> Pattern;
+}
+
+impl PatternExt for Pattern {
+    fn or(self, other: Pattern) -> Pattern {
+        Pattern::Meta(MetaPattern::Or(OrPattern::from_iter([self, other])))
+    }
+
+    fn and(self, other: Pattern) -> Pattern {
+        let and = AndPattern::from_iter([self, other]);
+        let mut patterns = merge_lengths(and.patterns().to_vec());
+        if patterns.len() == 1 {
+            patterns.remove(0)
+        } else {
+            Pattern::and(patterns)
+        }
+    }
+
+    fn capture(self, name: impl AsRef<str>) -> Pattern {
+        Pattern::capture(name, self)
+    }
+
+    fn search(self) -> Pattern { Pattern::search(self) }
+
+    fn group(self) -> Pattern { self }
+
+    fn repeat(self, range: impl RangeBounds<usize>) -> Pattern {
+        self.quantified(Quantifier::new(range, Reluctance::Greedy))
+    }
+
+    fn repeat_lazy(self, range: impl RangeBounds<usize>) -> Pattern {
+        self.quantified(Quantifier::new(range, Reluctance::Lazy))
+    }
+
+    fn repeat_possessive(self, range: impl RangeBounds<usize>) -> Pattern {
+        self.quantified(Quantifier::new(range, Reluctance::Possessive))
+    }
+
+    fn quantified(self, quantifier: Quantifier) -> Pattern {
+        Pattern::repeat(self, quantifier)
+    }
+}
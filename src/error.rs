@@ -4,6 +4,11 @@ use thiserror::Error;
 use crate::parse::Token;
 
 /// Errors that can occur during parsing of dCBOR patterns.
+///
+/// Errors found in the source of a pattern carry the span of the source they
+/// refer to, and syntax errors also carry the text of that span, their
+/// lexeme, so that it can be shown without the source. [`Error::span`] and
+/// [`Error::lexeme`] return these for any variant.
 #[derive(Debug, Clone, Error, PartialEq, Default)]
 pub enum Error {
     #[error("Empty input")]
@@ -12,83 +17,180 @@ pub enum Error {
     #[error("Unexpected end of input")]
     UnexpectedEndOfInput,
 
-    #[error("Extra data at end of input")]
-    ExtraData(Span),
+    #[error("Extra data at {1:?}")]
+    ExtraData(String, Span),
 
-    #[error("Unexpected token {0:?}")]
-    UnexpectedToken(Box<Token>, Span),
+    #[error("Unexpected {0} at {2:?}")]
+    UnexpectedToken(Box<Token>, String, Span),
 
-    #[error("Unrecognized token at position {0:?}")]
-    UnrecognizedToken(Span),
+    #[error("Unrecognized token at position {1:?}")]
+    UnrecognizedToken(String, Span),
 
-    #[error("Invalid regex pattern at {0:?}")]
-    InvalidRegex(Span),
+    #[error("Invalid regex pattern at {1:?}")]
+    InvalidRegex(String, Span),
 
-    #[error("Unterminated regex pattern at {0:?}")]
-    UnterminatedRegex(Span),
+    #[error("Unterminated regex pattern at {1:?}")]
+    UnterminatedRegex(String, Span),
 
-    #[error("Unterminated string literal at {0:?}")]
-    UnterminatedString(Span),
+    #[error("Unterminated string literal at {1:?}")]
+    UnterminatedString(String, Span),
 
-    #[error("Invalid range at {0:?}")]
-    InvalidRange(Span),
+    #[error("Invalid range at {1:?}")]
+    InvalidRange(String, Span),
 
-    #[error("Invalid hex string at {0:?}")]
-    InvalidHexString(Span),
+    #[error("Invalid hex string at {1:?}")]
+    InvalidHexString(String, Span),
 
-    #[error("Unterminated hex string at {0:?}")]
-    UnterminatedHexString(Span),
+    #[error("Unterminated hex string at {1:?}")]
+    UnterminatedHexString(String, Span),
 
-    #[error("Invalid date format at {0:?}")]
-    InvalidDateFormat(Span),
+    #[error("Invalid date format at {1:?}")]
+    InvalidDateFormat(String, Span),
 
-    #[error("Invalid number format at {0:?}")]
-    InvalidNumberFormat(Span),
+    #[error("Invalid number format at {1:?}")]
+    InvalidNumberFormat(String, Span),
 
     #[error("Invalid UR: {0} at {1:?}")]
     InvalidUr(String, Span),
 
-    #[error("Expected opening parenthesis")]
-    ExpectedOpenParen(Span),
+    #[error("Expected opening parenthesis at {1:?}")]
+    ExpectedOpenParen(String, Span),
 
-    #[error("Expected closing parenthesis")]
-    ExpectedCloseParen(Span),
+    #[error("Expected closing parenthesis at {1:?}")]
+    ExpectedCloseParen(String, Span),
 
-    #[error("Expected closing bracket")]
-    ExpectedCloseBracket(Span),
+    #[error("Expected closing bracket at {1:?}")]
+    ExpectedCloseBracket(String, Span),
 
-    #[error("Expected closing brace")]
-    ExpectedCloseBrace(Span),
+    #[error("Expected closing brace at {1:?}")]
+    ExpectedCloseBrace(String, Span),
 
-    #[error("Expected colon")]
-    ExpectedColon(Span),
+    #[error("Expected colon at {1:?}")]
+    ExpectedColon(String, Span),
 
-    #[error("Expected pattern after operator")]
-    ExpectedPattern(Span),
+    #[error("Expected pattern after operator at {1:?}")]
+    ExpectedPattern(String, Span),
 
-    #[error("Unmatched parentheses")]
-    UnmatchedParentheses(Span),
+    #[error("Unmatched parentheses at {1:?}")]
+    UnmatchedParentheses(String, Span),
 
-    #[error("Unmatched braces")]
-    UnmatchedBraces(Span),
+    #[error("Unmatched braces at {1:?}")]
+    UnmatchedBraces(String, Span),
 
-    #[error("Invalid capture group name")]
+    #[error("Invalid capture group name: {0} at {1:?}")]
     InvalidCaptureGroupName(String, Span),
 
     #[error("Invalid digest pattern: {0} at {1:?}")]
     InvalidDigestPattern(String, Span),
 
-    #[error("Unterminated digest quoted pattern at {0:?}")]
-    UnterminatedDigestQuoted(Span),
+    #[error("Unterminated digest quoted pattern at {1:?}")]
+    UnterminatedDigestQuoted(String, Span),
 
-    #[error("Unterminated date quoted pattern at {0:?}")]
-    UnterminatedDateQuoted(Span),
+    #[error("Unterminated date quoted pattern at {1:?}")]
+    UnterminatedDateQuoted(String, Span),
+
+    #[error("Invalid dCBOR literal: {0} at {1:?}")]
+    InvalidCbor(String, Span),
+
+    #[error("Unterminated dCBOR quoted pattern at {1:?}")]
+    UnterminatedCborQuoted(String, Span),
+
+    #[error("Invalid search option: {0} at {1:?}")]
+    InvalidSearchOption(String, Span),
+
+    #[error("Unknown known value name: {0} at {1:?}")]
+    UnknownKnownValue(String, Span),
+
+    #[error("Duplicate capture name: {0} at {1:?}")]
+    DuplicateCaptureName(String, Span),
+
+    /// A capture in a negated pattern, holding its name and, for one parsed
+    /// from source, its span.
+    #[error("Capture inside negation: {0}{at}", at = at(.1))]
+    CaptureInsideNegation(String, Option<Span>),
+
+    #[error("Pattern nested too deeply at {1:?}")]
+    NestingTooDeep(String, Span),
+
+    #[error("Quantifier has nothing to repeat at {1:?}")]
+    NothingToRepeat(String, Span),
+
+    /// A repeat among the patterns of an unordered array, holding the repeat
+    /// and, for one parsed from source, its span.
+    #[error(
+        "Repeat in unordered array pattern{at}; each of its patterns must \
+         match a single element",
+        at = at(.1)
+    )]
+    RepeatInUnorderedArray(String, Option<Span>),
+
+    #[error(
+        "Sequence outside an array pattern at {1:?}; wrap it in [...] or use \
+         Pattern::match_sequence"
+    )]
+    SequenceOutsideArray(String, Span),
+
+    #[error("Undefined reference: ${0} at {1:?}")]
+    UndefinedReference(String, Span),
+
+    #[error("Recursive definition: {0} refers to itself at {1:?}")]
+    RecursiveDefinition(String, Span),
+
+    #[error("Duplicate definition: {0}")]
+    DuplicateDefinition(String),
+
+    #[error("Invalid definition name: {0}")]
+    InvalidDefinitionName(String),
+
+    #[error("Unknown custom matcher: {0} at {1:?}")]
+    UnknownCustomMatcher(String, Span),
+
+    #[error("Invalid custom matcher name: {0}")]
+    InvalidCustomMatcherName(String),
+
+    #[error("Path is not consistent with the haystack at element {0}")]
+    InconsistentPath(usize),
+
+    #[error("Match budget exceeded")]
+    BudgetExceeded,
+
+    #[error("Pattern has {0} nodes, more than the limit of {1}")]
+    TooManyNodes(usize, usize),
+
+    #[error("Pattern is nested {0} deep, more than the limit of {1}")]
+    DepthLimitExceeded(usize, usize),
+
+    #[error(
+        "Pattern has {0} unbounded quantifiers, more than the limit of {1}"
+    )]
+    TooManyUnboundedQuantifiers(usize, usize),
+
+    #[error("Pattern has {0} searches, more than the limit of {1}")]
+    TooManySearches(usize, usize),
+
+    #[error("Pattern complexity score {0} exceeds the limit of {1}")]
+    ComplexityScoreExceeded(u64, u64),
+
+    #[error("Invalid dCBOR data: {0}")]
+    InvalidCborData(String),
+
+    #[error("Invalid quantifier: {0}")]
+    InvalidQuantifier(String),
+
+    #[error("Pattern can't be matched against a stream: {0}")]
+    UnsupportedForStreaming(String),
 
     #[error("Unknown error")]
     #[default]
     Unknown,
 }
 
+/// Formats the span of an error that may have none as ` at <span>`.
+fn at(span: &Option<Span>) -> String {
+    span.as_ref()
+        .map_or(String::new(), |span| format!(" at {:?}", span))
+}
+
 /// A Result type specialized for dCBOR pattern parsing.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -97,92 +199,298 @@ impl Error {
     /// and end positions. Returns a new error with adjusted span, or the
     /// original error if it has no span.
     pub fn adjust_span(self, offset: usize) -> Self {
+        let adjust = |span: Span| offset + span.start..offset + span.end;
         match self {
-            Error::UnrecognizedToken(span) => {
-                Error::UnrecognizedToken(offset + span.start..offset + span.end)
-            }
-            Error::ExtraData(span) => {
-                Error::ExtraData(offset + span.start..offset + span.end)
-            }
-            Error::UnexpectedToken(token, span) => Error::UnexpectedToken(
-                token,
-                offset + span.start..offset + span.end,
-            ),
-            Error::InvalidRegex(span) => {
-                Error::InvalidRegex(offset + span.start..offset + span.end)
-            }
-            Error::UnterminatedRegex(span) => {
-                Error::UnterminatedRegex(offset + span.start..offset + span.end)
-            }
-            Error::UnterminatedString(span) => Error::UnterminatedString(
-                offset + span.start..offset + span.end,
-            ),
-            Error::InvalidRange(span) => {
-                Error::InvalidRange(offset + span.start..offset + span.end)
-            }
-            Error::InvalidHexString(span) => {
-                Error::InvalidHexString(offset + span.start..offset + span.end)
-            }
-            Error::UnterminatedHexString(span) => Error::UnterminatedHexString(
-                offset + span.start..offset + span.end,
-            ),
-            Error::InvalidDateFormat(span) => {
-                Error::InvalidDateFormat(offset + span.start..offset + span.end)
-            }
-            Error::InvalidNumberFormat(span) => Error::InvalidNumberFormat(
-                offset + span.start..offset + span.end,
-            ),
-            Error::InvalidUr(msg, span) => {
-                Error::InvalidUr(msg, offset + span.start..offset + span.end)
-            }
-            Error::ExpectedOpenParen(span) => {
-                Error::ExpectedOpenParen(offset + span.start..offset + span.end)
-            }
-            Error::ExpectedCloseParen(span) => Error::ExpectedCloseParen(
-                offset + span.start..offset + span.end,
-            ),
-            Error::ExpectedCloseBracket(span) => Error::ExpectedCloseBracket(
-                offset + span.start..offset + span.end,
-            ),
-            Error::ExpectedCloseBrace(span) => Error::ExpectedCloseBrace(
-                offset + span.start..offset + span.end,
-            ),
-            Error::ExpectedColon(span) => {
-                Error::ExpectedColon(offset + span.start..offset + span.end)
-            }
-            Error::ExpectedPattern(span) => {
-                Error::ExpectedPattern(offset + span.start..offset + span.end)
-            }
-            Error::UnmatchedParentheses(span) => Error::UnmatchedParentheses(
-                offset + span.start..offset + span.end,
-            ),
-            Error::UnmatchedBraces(span) => {
-                Error::UnmatchedBraces(offset + span.start..offset + span.end)
-            }
-            Error::InvalidCaptureGroupName(name, span) => {
-                Error::InvalidCaptureGroupName(
-                    name,
-                    offset + span.start..offset + span.end,
-                )
-            }
-            Error::InvalidDigestPattern(msg, span) => {
-                Error::InvalidDigestPattern(
-                    msg,
-                    offset + span.start..offset + span.end,
-                )
-            }
-            Error::UnterminatedDigestQuoted(span) => {
-                Error::UnterminatedDigestQuoted(
-                    offset + span.start..offset + span.end,
-                )
-            }
-            Error::UnterminatedDateQuoted(span) => {
-                Error::UnterminatedDateQuoted(
-                    offset + span.start..offset + span.end,
-                )
+            Error::UnexpectedToken(token, lexeme, span) => {
+                Error::UnexpectedToken(token, lexeme, adjust(span))
+            }
+            Error::ExtraData(lexeme, span) => {
+                Error::ExtraData(lexeme, adjust(span))
+            }
+            Error::UnrecognizedToken(lexeme, span) => {
+                Error::UnrecognizedToken(lexeme, adjust(span))
+            }
+            Error::InvalidRegex(lexeme, span) => {
+                Error::InvalidRegex(lexeme, adjust(span))
+            }
+            Error::UnterminatedRegex(lexeme, span) => {
+                Error::UnterminatedRegex(lexeme, adjust(span))
+            }
+            Error::UnterminatedString(lexeme, span) => {
+                Error::UnterminatedString(lexeme, adjust(span))
+            }
+            Error::InvalidRange(lexeme, span) => {
+                Error::InvalidRange(lexeme, adjust(span))
+            }
+            Error::InvalidHexString(lexeme, span) => {
+                Error::InvalidHexString(lexeme, adjust(span))
+            }
+            Error::UnterminatedHexString(lexeme, span) => {
+                Error::UnterminatedHexString(lexeme, adjust(span))
+            }
+            Error::InvalidDateFormat(lexeme, span) => {
+                Error::InvalidDateFormat(lexeme, adjust(span))
+            }
+            Error::InvalidNumberFormat(lexeme, span) => {
+                Error::InvalidNumberFormat(lexeme, adjust(span))
+            }
+            Error::ExpectedOpenParen(lexeme, span) => {
+                Error::ExpectedOpenParen(lexeme, adjust(span))
+            }
+            Error::ExpectedCloseParen(lexeme, span) => {
+                Error::ExpectedCloseParen(lexeme, adjust(span))
+            }
+            Error::ExpectedCloseBracket(lexeme, span) => {
+                Error::ExpectedCloseBracket(lexeme, adjust(span))
+            }
+            Error::ExpectedCloseBrace(lexeme, span) => {
+                Error::ExpectedCloseBrace(lexeme, adjust(span))
+            }
+            Error::ExpectedColon(lexeme, span) => {
+                Error::ExpectedColon(lexeme, adjust(span))
+            }
+            Error::ExpectedPattern(lexeme, span) => {
+                Error::ExpectedPattern(lexeme, adjust(span))
+            }
+            Error::UnmatchedParentheses(lexeme, span) => {
+                Error::UnmatchedParentheses(lexeme, adjust(span))
+            }
+            Error::UnmatchedBraces(lexeme, span) => {
+                Error::UnmatchedBraces(lexeme, adjust(span))
+            }
+            Error::UnterminatedDigestQuoted(lexeme, span) => {
+                Error::UnterminatedDigestQuoted(lexeme, adjust(span))
+            }
+            Error::UnterminatedDateQuoted(lexeme, span) => {
+                Error::UnterminatedDateQuoted(lexeme, adjust(span))
+            }
+            Error::UnterminatedCborQuoted(lexeme, span) => {
+                Error::UnterminatedCborQuoted(lexeme, adjust(span))
+            }
+            Error::NestingTooDeep(lexeme, span) => {
+                Error::NestingTooDeep(lexeme, adjust(span))
+            }
+            Error::NothingToRepeat(lexeme, span) => {
+                Error::NothingToRepeat(lexeme, adjust(span))
+            }
+            Error::RepeatInUnorderedArray(lexeme, span) => {
+                Error::RepeatInUnorderedArray(lexeme, span.map(adjust))
+            }
+            Error::SequenceOutsideArray(lexeme, span) => {
+                Error::SequenceOutsideArray(lexeme, adjust(span))
+            }
+            Error::InvalidUr(text, span) => {
+                Error::InvalidUr(text, adjust(span))
+            }
+            Error::InvalidCaptureGroupName(text, span) => {
+                Error::InvalidCaptureGroupName(text, adjust(span))
+            }
+            Error::InvalidDigestPattern(text, span) => {
+                Error::InvalidDigestPattern(text, adjust(span))
+            }
+            Error::InvalidCbor(text, span) => {
+                Error::InvalidCbor(text, adjust(span))
+            }
+            Error::InvalidSearchOption(text, span) => {
+                Error::InvalidSearchOption(text, adjust(span))
+            }
+            Error::UnknownKnownValue(text, span) => {
+                Error::UnknownKnownValue(text, adjust(span))
+            }
+            Error::DuplicateCaptureName(text, span) => {
+                Error::DuplicateCaptureName(text, adjust(span))
+            }
+            Error::CaptureInsideNegation(text, span) => {
+                Error::CaptureInsideNegation(text, span.map(adjust))
+            }
+            Error::UndefinedReference(text, span) => {
+                Error::UndefinedReference(text, adjust(span))
+            }
+            Error::RecursiveDefinition(text, span) => {
+                Error::RecursiveDefinition(text, adjust(span))
+            }
+            Error::UnknownCustomMatcher(text, span) => {
+                Error::UnknownCustomMatcher(text, adjust(span))
             }
             // For errors without spans, return them as-is
             _ => self,
         }
     }
+
+    /// Returns the byte range of the source the error refers to, or `None`
+    /// if the error has no span, as for errors found while building a
+    /// pattern in code rather than parsing it.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::ExtraData(_, span)
+            | Error::UnexpectedToken(_, _, span)
+            | Error::UnrecognizedToken(_, span)
+            | Error::InvalidRegex(_, span)
+            | Error::UnterminatedRegex(_, span)
+            | Error::UnterminatedString(_, span)
+            | Error::InvalidRange(_, span)
+            | Error::InvalidHexString(_, span)
+            | Error::UnterminatedHexString(_, span)
+            | Error::InvalidDateFormat(_, span)
+            | Error::InvalidNumberFormat(_, span)
+            | Error::InvalidUr(_, span)
+            | Error::ExpectedOpenParen(_, span)
+            | Error::ExpectedCloseParen(_, span)
+            | Error::ExpectedCloseBracket(_, span)
+            | Error::ExpectedCloseBrace(_, span)
+            | Error::ExpectedColon(_, span)
+            | Error::ExpectedPattern(_, span)
+            | Error::UnmatchedParentheses(_, span)
+            | Error::UnmatchedBraces(_, span)
+            | Error::InvalidCaptureGroupName(_, span)
+            | Error::InvalidDigestPattern(_, span)
+            | Error::UnterminatedDigestQuoted(_, span)
+            | Error::UnterminatedDateQuoted(_, span)
+            | Error::InvalidCbor(_, span)
+            | Error::UnterminatedCborQuoted(_, span)
+            | Error::InvalidSearchOption(_, span)
+            | Error::UnknownKnownValue(_, span)
+            | Error::DuplicateCaptureName(_, span)
+            | Error::NestingTooDeep(_, span)
+            | Error::NothingToRepeat(_, span)
+            | Error::SequenceOutsideArray(_, span)
+            | Error::UndefinedReference(_, span)
+            | Error::RecursiveDefinition(_, span)
+            | Error::UnknownCustomMatcher(_, span) => Some(span.clone()),
+            // Errors found by building a pattern in code have no source
+            Error::CaptureInsideNegation(_, span)
+            | Error::RepeatInUnorderedArray(_, span) => span.clone(),
+            Error::EmptyInput
+            | Error::UnexpectedEndOfInput
+            | Error::DuplicateDefinition(_)
+            | Error::InvalidDefinitionName(_)
+            | Error::InvalidCustomMatcherName(_)
+            | Error::InconsistentPath(_)
+            | Error::BudgetExceeded
+            | Error::TooManyNodes(..)
+            | Error::DepthLimitExceeded(..)
+            | Error::TooManyUnboundedQuantifiers(..)
+            | Error::TooManySearches(..)
+            | Error::ComplexityScoreExceeded(..)
+            | Error::InvalidCborData(_)
+            | Error::InvalidQuantifier(_)
+            | Error::UnsupportedForStreaming(_)
+            | Error::Unknown => None,
+        }
+    }
+
+    /// Returns the text of the source the error refers to, for syntax
+    /// errors, or `None` for other errors, including those that name what
+    /// they refer to, such as [`Error::DuplicateCaptureName`].
+    ///
+    /// ```rust
+    /// # use dcbor_pattern::Pattern;
+    /// // Hex digits come in pairs
+    /// let error = Pattern::parse("[number, h'abc']").unwrap_err();
+    /// assert_eq!(error.lexeme(), Some("h'abc'"));
+    /// assert_eq!(error.span(), Some(9..15));
+    /// ```
+    pub fn lexeme(&self) -> Option<&str> {
+        match self {
+            Error::UnexpectedToken(_, lexeme, _)
+            | Error::ExtraData(lexeme, _)
+            | Error::UnrecognizedToken(lexeme, _)
+            | Error::InvalidRegex(lexeme, _)
+            | Error::UnterminatedRegex(lexeme, _)
+            | Error::UnterminatedString(lexeme, _)
+            | Error::InvalidRange(lexeme, _)
+            | Error::InvalidHexString(lexeme, _)
+            | Error::UnterminatedHexString(lexeme, _)
+            | Error::InvalidDateFormat(lexeme, _)
+            | Error::InvalidNumberFormat(lexeme, _)
+            | Error::ExpectedOpenParen(lexeme, _)
+            | Error::ExpectedCloseParen(lexeme, _)
+            | Error::ExpectedCloseBracket(lexeme, _)
+            | Error::ExpectedCloseBrace(lexeme, _)
+            | Error::ExpectedColon(lexeme, _)
+            | Error::ExpectedPattern(lexeme, _)
+            | Error::UnmatchedParentheses(lexeme, _)
+            | Error::UnmatchedBraces(lexeme, _)
+            | Error::UnterminatedDigestQuoted(lexeme, _)
+            | Error::UnterminatedDateQuoted(lexeme, _)
+            | Error::UnterminatedCborQuoted(lexeme, _)
+            | Error::NestingTooDeep(lexeme, _)
+            | Error::NothingToRepeat(lexeme, _)
+            | Error::RepeatInUnorderedArray(lexeme, Some(_))
+            | Error::SequenceOutsideArray(lexeme, _) => Some(lexeme),
+            _ => None,
+        }
+    }
+
+    /// Renders the error against the pattern source it was produced from.
+    ///
+    /// The rendering gives the 1-based line and column of the error and
+    /// underlines the offending part of the source line with carets:
+    ///
+    /// ```text
+    /// error: Unterminated string literal at 8..12
+    ///  --> 2:3
+    ///   |
+    /// 2 |   "abc
+    ///   |   ^^^^
+    /// ```
+    ///
+    /// Errors at the end of the input point just past the last character.
+    /// `source` must be the full text passed to [`Pattern::parse`] for the
+    /// location to be meaningful.
+    ///
+    /// [`Pattern::parse`]: crate::Pattern::parse
+    pub fn to_display(&self, source: &str) -> String {
+        let span = match self {
+            Error::EmptyInput | Error::UnexpectedEndOfInput => {
+                Some(source.len()..source.len())
+            }
+            _ => self.span(),
+        };
+        let Some(span) = span else {
+            return format!("error: {}", self);
+        };
+
+        // Clamp the span to the source and to character boundaries
+        let floor = |mut i: usize| {
+            i = i.min(source.len());
+            while !source.is_char_boundary(i) {
+                i -= 1;
+            }
+            i
+        };
+        let start = floor(span.start);
+        let end = floor(span.end).max(start);
+
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+        let text = source[line_start..line_end].trim_end_matches('\r');
+
+        // Keep tabs in the padding so the carets line up with the source,
+        // and underline at most to the end of the first line
+        let padding: String = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = source[start..end.min(line_end)].chars().count().max(1);
+
+        let gutter = " ".repeat(line.to_string().len());
+        format!(
+            "error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}",
+            self,
+            gutter,
+            line,
+            column,
+            gutter,
+            line,
+            text,
+            gutter,
+            padding,
+            "^".repeat(width)
+        )
+    }
 }
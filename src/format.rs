@@ -10,9 +10,13 @@
 //!   flat diagnostic notation
 //! - **Path indentation**: Automatically indent nested path elements
 //! - **Truncation support**: Optionally truncate long representations with
-//!   ellipsis
+//!   ellipsis, or summarize large arrays and maps by their size
 //! - **Flexible options**: Choose whether to show all elements or just the
 //!   final destination
+//! - **JSON output**: Emit paths and captures as a JSON document for
+//!   machine-readable consumption
+//! - **Custom rendering**: Render each node with your own function, such as one
+//!   that redacts sensitive values, while keeping the tree layout
 //!
 //! ## Usage
 //!
@@ -43,7 +47,7 @@
 
 use dcbor::prelude::*;
 
-use crate::Path;
+use crate::{Captures, Path, pattern::with_root_capture};
 
 /// A builder that provides formatting options for each path element.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -53,6 +57,8 @@ pub enum PathElementFormat {
     /// Flat diagnostic format (single line), with optional maximum length for
     /// truncation.
     DiagnosticFlat(Option<usize>),
+    /// Hex-encoded CBOR bytes, with optional maximum length for truncation.
+    Hex(Option<usize>),
 }
 
 impl Default for PathElementFormat {
@@ -74,6 +80,32 @@ pub struct FormatPathsOpts {
     /// This is useful for displaying only the final destination of a path.
     /// If false, all elements will be formatted.
     last_element_only: bool,
+
+    /// If true, paths and captures are emitted as a JSON document instead of
+    /// an indented text tree.
+    json: bool,
+
+    /// The maximum length of each formatted node, beyond which it is
+    /// truncated with an ellipsis. Applies in addition to any maximum length
+    /// of the element format.
+    max_node_length: Option<usize>,
+
+    /// If true, arrays and maps with more than `container_threshold` elements
+    /// or entries are printed as a summary of their size, such as
+    /// `[… 10000 elements]`, instead of their full body.
+    summarize_containers: bool,
+
+    /// The number of elements or entries above which containers are
+    /// summarized.
+    container_threshold: usize,
+
+    /// If true, the last element of each path, which is the matched node, is
+    /// always printed in full.
+    exempt_last_element: bool,
+
+    /// If true, the paths are also listed as a capture named
+    /// [`ROOT_CAPTURE`](crate::ROOT_CAPTURE) when formatting with captures.
+    root_capture: bool,
 }
 
 impl Default for FormatPathsOpts {
@@ -81,11 +113,23 @@ impl Default for FormatPathsOpts {
     /// - `indent`: true
     /// - `element_format`: PathElementFormat::Diagnostic(None)
     /// - `last_element_only`: false
+    /// - `json`: false
+    /// - `max_node_length`: None
+    /// - `summarize_containers`: false
+    /// - `container_threshold`: 16
+    /// - `exempt_last_element`: false
+    /// - `root_capture`: false
     fn default() -> Self {
         Self {
             indent: true,
             element_format: PathElementFormat::default(),
             last_element_only: false,
+            json: false,
+            max_node_length: None,
+            summarize_containers: false,
+            container_threshold: 16,
+            exempt_last_element: false,
+            root_capture: false,
         }
     }
 }
@@ -115,14 +159,71 @@ impl FormatPathsOpts {
         self.last_element_only = last_element_only;
         self
     }
+
+    /// Sets whether to emit JSON instead of text.
+    ///
+    /// The JSON document has the shape
+    /// `{"paths": [[...], ...], "captures": {"name": [[...], ...]}}`, where
+    /// each path is an array of strings formatted according to the element
+    /// format. Indentation does not apply in JSON mode.
+    pub fn json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Sets the maximum length of each formatted node.
+    /// Longer nodes are truncated with an ellipsis.
+    pub fn max_node_length(mut self, max_node_length: Option<usize>) -> Self {
+        self.max_node_length = max_node_length;
+        self
+    }
+
+    /// Sets whether to summarize large arrays and maps.
+    /// If true, containers with more elements or entries than the container
+    /// threshold are printed as `[… N elements]` or `{… N entries}`.
+    pub fn summarize_containers(mut self, summarize_containers: bool) -> Self {
+        self.summarize_containers = summarize_containers;
+        self
+    }
+
+    /// Sets the number of elements or entries above which containers are
+    /// summarized. Default is 16.
+    pub fn container_threshold(mut self, container_threshold: usize) -> Self {
+        self.container_threshold = container_threshold;
+        self
+    }
+
+    /// Sets whether the last element of each path is exempt from truncation
+    /// and summarizing, so that the matched node is always shown in full.
+    pub fn exempt_last_element(mut self, exempt_last_element: bool) -> Self {
+        self.exempt_last_element = exempt_last_element;
+        self
+    }
+
+    /// Sets whether formatting with captures also lists the paths as a
+    /// capture named [`ROOT_CAPTURE`](crate::ROOT_CAPTURE), which sorts before
+    /// every name a pattern can capture. Nothing is added when there are no
+    /// paths.
+    pub fn root_capture(mut self, root_capture: bool) -> Self {
+        self.root_capture = root_capture;
+        self
+    }
 }
 
 impl AsRef<FormatPathsOpts> for FormatPathsOpts {
     fn as_ref(&self) -> &FormatPathsOpts { self }
 }
 
-/// Format a single CBOR element according to the specified format.
-fn format_cbor_element(cbor: &CBOR, format: PathElementFormat) -> String {
+/// A function that renders a single path node, in place of the element
+/// format of [`FormatPathsOpts`].
+pub type NodeRenderer<'a> = &'a dyn Fn(&CBOR) -> String;
+
+/// Formats a single path node according to `format`.
+///
+/// This is the renderer used when none is given, so a custom renderer passed
+/// to [`format_paths_with`] can delegate to it for the nodes it doesn't
+/// handle itself.
+pub fn format_path_element(cbor: &CBOR, format: PathElementFormat) -> String {
     match format {
         PathElementFormat::DiagnosticSummary(max_length) => {
             let diagnostic = cbor.summary();
@@ -132,16 +233,59 @@ fn format_cbor_element(cbor: &CBOR, format: PathElementFormat) -> String {
             let diagnostic = cbor.diagnostic_flat();
             truncate_with_ellipsis(&diagnostic, max_length)
         }
+        PathElementFormat::Hex(max_length) => {
+            let hex = hex::encode(cbor.to_cbor_data());
+            truncate_hex(&hex, max_length)
+        }
     }
 }
 
-/// Truncates a string to the specified maximum length, appending an ellipsis if
-/// truncated. If `max_length` is None, returns the original string.
+/// Format a path node with `render`, applying the eliding options unless
+/// `is_last` and the last element is exempt.
+fn format_node(
+    cbor: &CBOR,
+    opts: &FormatPathsOpts,
+    is_last: bool,
+    render: NodeRenderer,
+) -> String {
+    if is_last && opts.exempt_last_element {
+        return render(cbor);
+    }
+    let summary = if opts.summarize_containers {
+        summarize_container(cbor, opts.container_threshold)
+    } else {
+        None
+    };
+    let formatted = summary.unwrap_or_else(|| render(cbor));
+    truncate_with_ellipsis(&formatted, opts.max_node_length)
+}
+
+/// Returns a summary of the size of an array or map with more than
+/// `threshold` elements or entries, or `None` for other values.
+fn summarize_container(cbor: &CBOR, threshold: usize) -> Option<String> {
+    let (len, open, close, one, many) = match cbor.as_case() {
+        CBORCase::Array(array) => {
+            (array.len(), '[', ']', "element", "elements")
+        }
+        CBORCase::Map(map) => (map.len(), '{', '}', "entry", "entries"),
+        _ => return None,
+    };
+    if len <= threshold {
+        return None;
+    }
+    let noun = if len == 1 { one } else { many };
+    Some(format!("{}… {} {}{}", open, len, noun, close))
+}
+
+/// Truncates a string to the specified maximum length in characters,
+/// appending an ellipsis if truncated. If `max_length` is None, returns the
+/// original string.
 fn truncate_with_ellipsis(s: &str, max_length: Option<usize>) -> String {
     match max_length {
-        Some(max_len) if s.len() > max_len => {
+        Some(max_len) if s.chars().count() > max_len => {
             if max_len > 1 {
-                format!("{}…", &s[0..(max_len - 1)])
+                let kept: String = s.chars().take(max_len - 1).collect();
+                format!("{}…", kept)
             } else {
                 "…".to_string()
             }
@@ -150,6 +294,19 @@ fn truncate_with_ellipsis(s: &str, max_length: Option<usize>) -> String {
     }
 }
 
+/// Truncates hex to at most `max_length` characters as
+/// [`truncate_with_ellipsis`] does, but only between whole bytes, so that
+/// the digits kept are still valid hex.
+fn truncate_hex(hex: &str, max_length: Option<usize>) -> String {
+    match max_length {
+        Some(max_len) if hex.len() > max_len => {
+            let kept = max_len.saturating_sub(1) & !1;
+            format!("{}…", &hex[..kept])
+        }
+        _ => hex.to_string(),
+    }
+}
+
 /// Format each path element on its own line, each line successively indented by
 /// 4 spaces. Options can be provided to customize the formatting.
 pub fn format_path_opt(
@@ -157,18 +314,30 @@ pub fn format_path_opt(
     opts: impl AsRef<FormatPathsOpts>,
 ) -> String {
     let opts = opts.as_ref();
+    format_path_rendered(path, opts, &|cbor| {
+        format_path_element(cbor, opts.element_format)
+    })
+}
 
+/// Format a path as [`format_path_opt`] does, rendering each node with
+/// `render`.
+fn format_path_rendered(
+    path: &Path,
+    opts: &FormatPathsOpts,
+    render: NodeRenderer,
+) -> String {
     if opts.last_element_only {
         // Only format the last element, no indentation.
         if let Some(element) = path.iter().last() {
-            format_cbor_element(element, opts.element_format)
+            format_node(element, opts, true, render)
         } else {
             String::new()
         }
     } else {
         match opts.element_format {
             PathElementFormat::DiagnosticSummary(_)
-            | PathElementFormat::DiagnosticFlat(_) => {
+            | PathElementFormat::DiagnosticFlat(_)
+            | PathElementFormat::Hex(_) => {
                 // Multi-line output with indentation for diagnostic formats.
                 let mut lines = Vec::new();
                 for (index, element) in path.iter().enumerate() {
@@ -178,8 +347,12 @@ pub fn format_path_opt(
                         String::new()
                     };
 
-                    let content =
-                        format_cbor_element(element, opts.element_format);
+                    let content = format_node(
+                        element,
+                        opts,
+                        index + 1 == path.len(),
+                        render,
+                    );
                     lines.push(format!("{}{}", indent, content));
                 }
                 lines.join("\n")
@@ -194,15 +367,139 @@ pub fn format_path(path: &Path) -> String {
     format_path_opt(path, FormatPathsOpts::default())
 }
 
+/// Quote and escape a string for inclusion in a JSON document.
+#[cfg(feature = "serde")]
+fn json_string(s: &str) -> String { serde_json::Value::from(s).to_string() }
+
+/// Quote and escape a string for inclusion in a JSON document.
+#[cfg(not(feature = "serde"))]
+fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                result.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Format a path as a JSON array of formatted elements.
+fn format_path_json(
+    path: &Path,
+    opts: &FormatPathsOpts,
+    render: NodeRenderer,
+) -> String {
+    let first = if opts.last_element_only {
+        path.len().saturating_sub(1)
+    } else {
+        0
+    };
+    let elements: Vec<String> = path
+        .iter()
+        .enumerate()
+        .skip(first)
+        .map(|(index, element)| {
+            json_string(&format_node(
+                element,
+                opts,
+                index + 1 == path.len(),
+                render,
+            ))
+        })
+        .collect();
+    format!("[{}]", elements.join(", "))
+}
+
+/// Format paths and captures as a JSON document. Capture names are sorted
+/// lexicographically, matching the text output.
+fn format_paths_with_captures_json(
+    paths: &[Path],
+    captures: &Captures,
+    opts: &FormatPathsOpts,
+    render: NodeRenderer,
+) -> String {
+    let format_paths_json = |paths: &[Path]| {
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|path| format_path_json(path, opts, render))
+            .collect();
+        format!("[{}]", paths.join(", "))
+    };
+
+    let mut capture_names: Vec<&String> = captures.keys().collect();
+    capture_names.sort();
+    let captures: Vec<String> = capture_names
+        .into_iter()
+        .map(|name| {
+            format!(
+                "{}: {}",
+                json_string(name),
+                format_paths_json(&captures[name])
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"paths\": {}, \"captures\": {{{}}}}}",
+        format_paths_json(paths),
+        captures.join(", ")
+    )
+}
+
 /// Format multiple paths with captures in a structured way.
 /// Captures come first, sorted lexicographically by name, with their name
 /// prefixed by '@'. Regular paths follow after all captures.
+///
+/// If the options request JSON output, a JSON document is returned instead;
+/// see [`FormatPathsOpts::json`].
 pub fn format_paths_with_captures(
     paths: &[Path],
-    captures: &std::collections::HashMap<String, Vec<Path>>,
+    captures: &Captures,
+    opts: impl AsRef<FormatPathsOpts>,
+) -> String {
+    let opts = opts.as_ref();
+    format_paths_with_captures_with(opts, paths, captures, &|cbor| {
+        format_path_element(cbor, opts.element_format)
+    })
+}
+
+/// Format multiple paths with captures as [`format_paths_with_captures`]
+/// does, rendering each node with `renderer` instead of the element format.
+///
+/// The renderer is given every node of every path, whole, and the options
+/// other than the element format still apply: indentation, last element
+/// only, JSON output, and the eliding options, so that a summarized
+/// container is never passed to the renderer and a node longer than the
+/// maximum length is truncated after rendering.
+pub fn format_paths_with_captures_with(
     opts: impl AsRef<FormatPathsOpts>,
+    paths: &[Path],
+    captures: &Captures,
+    renderer: NodeRenderer,
 ) -> String {
     let opts = opts.as_ref();
+    let with_root;
+    let captures = if opts.root_capture {
+        with_root = with_root_capture(paths, captures.clone());
+        &with_root
+    } else {
+        captures
+    };
+    if opts.json {
+        return format_paths_with_captures_json(
+            paths, captures, opts, renderer,
+        );
+    }
     let mut result = Vec::new();
 
     // First, format all captures, sorted lexicographically by name
@@ -213,7 +510,7 @@ pub fn format_paths_with_captures(
         if let Some(capture_paths) = captures.get(capture_name) {
             result.push(format!("@{}", capture_name));
             for path in capture_paths {
-                let formatted_path = format_path_opt(path, opts);
+                let formatted_path = format_path_rendered(path, opts, renderer);
                 // Add indentation to each line of the formatted path
                 for line in formatted_path.split('\n') {
                     if !line.is_empty() {
@@ -226,7 +523,7 @@ pub fn format_paths_with_captures(
 
     // Then, format all regular paths
     for path in paths {
-        let formatted_path = format_path_opt(path, opts);
+        let formatted_path = format_path_rendered(path, opts, renderer);
         for line in formatted_path.split('\n') {
             if !line.is_empty() {
                 result.push(line.to_string());
@@ -243,7 +540,19 @@ pub fn format_paths_opt(
     opts: impl AsRef<FormatPathsOpts>,
 ) -> String {
     // Call format_paths_with_captures with empty captures
-    format_paths_with_captures(paths, &std::collections::HashMap::new(), opts)
+    format_paths_with_captures(paths, &Captures::new(), opts)
+}
+
+/// Format multiple paths as [`format_paths_opt`] does, rendering each node
+/// with `renderer` instead of the element format. See
+/// [`format_paths_with_captures_with`] for how the renderer and the options
+/// combine.
+pub fn format_paths_with(
+    opts: impl AsRef<FormatPathsOpts>,
+    paths: &[Path],
+    renderer: NodeRenderer,
+) -> String {
+    format_paths_with_captures_with(opts, paths, &Captures::new(), renderer)
 }
 
 /// Format multiple paths with default options.
@@ -307,6 +616,76 @@ mod tests {
         assert_eq!(truncate_with_ellipsis("hello", Some(10)), "hello");
         assert_eq!(truncate_with_ellipsis("hello world", Some(5)), "hell…");
         assert_eq!(truncate_with_ellipsis("hello", Some(1)), "…");
+        assert_eq!(truncate_with_ellipsis("héllo wörld", Some(4)), "hél…");
+    }
+
+    #[test]
+    fn test_truncate_hex() {
+        assert_eq!(truncate_hex("1903e8", None), "1903e8");
+        assert_eq!(truncate_hex("1903e8", Some(6)), "1903e8");
+        assert_eq!(truncate_hex("1903e8", Some(5)), "1903…");
+        assert_eq!(truncate_hex("1903e8", Some(4)), "19…");
+        assert_eq!(truncate_hex("1903e8", Some(1)), "…");
+
+        let cbor = CBOR::from(1000);
+        assert_eq!(
+            format_path_element(&cbor, PathElementFormat::Hex(Some(4))),
+            "19…"
+        );
+    }
+
+    #[test]
+    fn test_summarize_container() {
+        let array = CBOR::from(vec![1, 2, 3]);
+        assert_eq!(summarize_container(&array, 3), None);
+        assert_eq!(
+            summarize_container(&array, 2).as_deref(),
+            Some("[… 3 elements]")
+        );
+        assert_eq!(
+            summarize_container(&CBOR::from(vec![1]), 0).as_deref(),
+            Some("[… 1 element]")
+        );
+
+        let mut map = Map::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+        let map = CBOR::from(map);
+        assert_eq!(
+            summarize_container(&map, 1).as_deref(),
+            Some("{… 2 entries}")
+        );
+        assert_eq!(summarize_container(&CBOR::from("text"), 0), None);
+    }
+
+    #[test]
+    fn test_format_path_eliding() {
+        let path = vec![
+            CBOR::from(vec![10, 20, 30]),
+            CBOR::from(vec![10, 20]),
+            CBOR::from(20),
+        ];
+
+        let opts = FormatPathsOpts::new()
+            .summarize_containers(true)
+            .container_threshold(2);
+        assert_eq!(
+            format_path_opt(&path, opts),
+            "[… 3 elements]\n    [10, 20]\n        20"
+        );
+
+        let opts = FormatPathsOpts::new().max_node_length(Some(5));
+        assert_eq!(
+            format_path_opt(&path, opts),
+            "[10,…\n    [10,…\n        20"
+        );
+
+        // The matched node can be exempted
+        let path = vec![CBOR::from(vec![10, 20]), CBOR::from(vec![10, 20])];
+        let opts = FormatPathsOpts::new()
+            .max_node_length(Some(5))
+            .exempt_last_element(true);
+        assert_eq!(format_path_opt(&path, opts), "[10,…\n    [10, 20]");
     }
 
     #[test]
@@ -325,13 +704,11 @@ mod tests {
 
     #[test]
     fn test_format_paths_with_captures() {
-        use std::collections::HashMap;
-
         let path1 = vec![CBOR::from(1)];
         let path2 = vec![CBOR::from(2)];
         let paths = vec![path1.clone(), path2.clone()];
 
-        let mut captures = HashMap::new();
+        let mut captures = Captures::new();
         captures.insert("capture1".to_string(), vec![path1]);
         captures.insert("capture2".to_string(), vec![path2]);
 
@@ -353,13 +730,11 @@ mod tests {
 
     #[test]
     fn test_format_paths_with_empty_captures() {
-        use std::collections::HashMap;
-
         let path1 = vec![CBOR::from(1)];
         let path2 = vec![CBOR::from(2)];
         let paths = vec![path1, path2];
 
-        let captures = HashMap::new();
+        let captures = Captures::new();
         let formatted = format_paths_with_captures(
             &paths,
             &captures,
@@ -373,14 +748,12 @@ mod tests {
 
     #[test]
     fn test_capture_names_sorted() {
-        use std::collections::HashMap;
-
         let path1 = vec![CBOR::from(1)];
         let path2 = vec![CBOR::from(2)];
         let path3 = vec![CBOR::from(3)];
         let paths = vec![];
 
-        let mut captures = HashMap::new();
+        let mut captures = Captures::new();
         captures.insert("zebra".to_string(), vec![path1]);
         captures.insert("alpha".to_string(), vec![path2]);
         captures.insert("beta".to_string(), vec![path3]);
@@ -397,4 +770,40 @@ mod tests {
         assert!(lines[2] == "@beta");
         assert!(lines[4] == "@zebra");
     }
+
+    #[test]
+    fn test_json_string_escaping() {
+        assert_eq!(json_string("plain"), r#""plain""#);
+        assert_eq!(json_string(r#""quoted""#), r#""\"quoted\"""#);
+        assert_eq!(json_string("a\\b"), r#""a\\b""#);
+        assert_eq!(json_string("line\nbreak"), r#""line\nbreak""#);
+        assert_eq!(json_string("\u{1}"), r#""\u0001""#);
+    }
+
+    #[test]
+    fn test_format_paths_json() {
+        let paths = vec![vec![CBOR::from(vec![1, 2]), CBOR::from(2)]];
+        let mut captures = Captures::new();
+        captures.insert("b".to_string(), vec![vec![CBOR::from("x")]]);
+        captures.insert("a".to_string(), vec![]);
+
+        let formatted = format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::new().json(true),
+        );
+        assert_eq!(
+            formatted,
+            r#"{"paths": [["[1, 2]", "2"]], "captures": {"a": [], "b": [["\"x\""]]}}"#
+        );
+
+        let formatted = format_paths_opt(
+            &paths,
+            FormatPathsOpts::new()
+                .json(true)
+                .last_element_only(true)
+                .element_format(PathElementFormat::Hex(None)),
+        );
+        assert_eq!(formatted, r#"{"paths": [["02"]], "captures": {}}"#);
+    }
 }
@@ -2,8 +2,8 @@
 /// minimum and optional maximum.
 ///
 /// This module is used in the context of pattern matching for dCBOR items
-/// to represent cardinality specifications like `{n}`, `{n,m}`, or `{n,}`
-/// in pattern expressions.
+/// to represent cardinality specifications like `{n}`, `{n,m}`, `{n,}`, or
+/// `{,m}` in pattern expressions.
 use std::ops::{Bound, RangeBounds};
 
 /// Represents an inclusive interval with a minimum value and an optional
@@ -11,6 +11,7 @@ use std::ops::{Bound, RangeBounds};
 ///
 /// When the maximum is `None`, the interval is considered unbounded above.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Interval {
     min: usize,
     max: Option<usize>, // None == unbounded
@@ -22,20 +23,48 @@ impl Interval {
     ///
     /// This allows creating intervals from Rust's range expressions like
     /// `1..5`, `0..=10`, or `2..`.
+    ///
+    /// A range with no values, such as `3..=1` or `..0`, has its maximum
+    /// raised to its minimum, giving `{3}` or `{0}`, as there is no way to
+    /// write an empty interval in a pattern. Use [`Interval::try_new`] to
+    /// reject such ranges instead.
     pub fn new(range: impl RangeBounds<usize>) -> Self {
         let min = match range.start_bound() {
             Bound::Included(&start) => start,
-            Bound::Excluded(&start) => start + 1,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(&end) => Some(end),
+            Bound::Excluded(&end) => Some(end.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+        Self { min, max: max.map(|max| max.max(min)) }
+    }
+
+    /// Creates a new `Interval` from a range as [`Interval::new`] does, or
+    /// returns `None` if the range contains no values.
+    pub fn try_new(range: impl RangeBounds<usize>) -> Option<Self> {
+        let min = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1)?,
             Bound::Unbounded => 0,
         };
         let max = match range.end_bound() {
             Bound::Included(&end) => Some(end),
-            Bound::Excluded(&end) => Some(end - 1),
+            Bound::Excluded(&end) => Some(end.checked_sub(1)?),
             Bound::Unbounded => None,
         };
-        Self { min, max }
+        match max {
+            Some(max) if max < min => None,
+            _ => Some(Self { min, max }),
+        }
     }
 
+    /// Creates a new `Interval` containing the values from 0 to `max`
+    /// inclusive, written `{,max}`.
+    pub fn at_most(max: usize) -> Self { Self { min: 0, max: Some(max) } }
+
     /// Returns the minimum value of the interval.
     pub fn min(&self) -> usize { self.min }
 
@@ -54,16 +83,53 @@ impl Interval {
     /// Checks if the interval is unbounded (i.e., has no maximum value).
     pub fn is_unbounded(&self) -> bool { self.max.is_none() }
 
+    /// Returns the interval of values contained in both this interval and
+    /// `other`, or `None` if they have no values in common.
+    pub fn intersect(&self, other: &Interval) -> Option<Interval> {
+        let min = self.min.max(other.min);
+        let max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        match max {
+            Some(max) if max < min => None,
+            _ => Some(Interval { min, max }),
+        }
+    }
+
+    /// Returns the interval of values contained in either this interval or
+    /// `other`, or `None` if the values don't form a single interval because
+    /// there is a gap between the two.
+    pub fn union(&self, other: &Interval) -> Option<Interval> {
+        let (low, high) = if self.min <= other.min {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        if let Some(low_max) = low.max
+            && low_max.saturating_add(1) < high.min
+        {
+            return None;
+        }
+        let max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            _ => None,
+        };
+        Some(Interval { min: low.min, max })
+    }
+
     /// Returns a string representation of the interval using standard range
-    /// notation.
+    /// notation, omitting the minimum when it is zero.
     ///
     /// Examples:
     /// - `{3}` for the single value 3
     /// - `{1,5}` for the range 1 to 5 inclusive
+    /// - `{,5}` for 5 or fewer
     /// - `{2,}` for 2 or more
     pub fn range_notation(&self) -> String {
         match (self.min, self.max) {
             (min, Some(max)) if min == max => format!("{{{}}}", min),
+            (0, Some(max)) => format!("{{,{}}}", max),
             (min, Some(max)) => format!("{{{},{}}}", min, max),
             (min, None) => format!("{{{},}}", min),
         }
@@ -79,11 +145,9 @@ impl Interval {
     pub fn shorthand_notation(&self) -> String {
         match (self.min, self.max) {
             (0, Some(1)) => "?".to_string(),
-            (min, Some(max)) if min == max => format!("{{{}}}", min),
-            (min, Some(max)) => format!("{{{},{}}}", min, max),
             (0, None) => "*".to_string(),
             (1, None) => "+".to_string(),
-            (min, None) => format!("{{{},}}", min),
+            _ => self.range_notation(),
         }
     }
 }
@@ -135,6 +199,91 @@ mod tests {
         assert_eq!(format!("{}", Interval::new(2..)), "{2,}");
         assert_eq!(format!("{}", Interval::new(0..)), "{0,}");
         assert_eq!(format!("{}", Interval::new(1..)), "{1,}");
-        assert_eq!(format!("{}", Interval::new(0..=1)), "{0,1}");
+        assert_eq!(format!("{}", Interval::new(0..=1)), "{,1}");
+        assert_eq!(format!("{}", Interval::new(0..=0)), "{0}");
+        assert_eq!(format!("{}", Interval::at_most(4)), "{,4}");
+        assert_eq!(format!("{}", Interval::new(..5)), "{,4}");
+    }
+
+    #[test]
+    fn test_interval_try_new() {
+        assert_eq!(Interval::try_new(2..5), Some(Interval::new(2..=4)));
+        assert_eq!(Interval::try_new(..1), Some(Interval::new(0..=0)));
+        let (min, max) = (3, 1);
+        assert_eq!(Interval::try_new(min..=max), None);
+        assert_eq!(Interval::try_new(3..3), None);
+        assert_eq!(Interval::try_new(..0), None);
+    }
+
+    #[test]
+    fn test_interval_new_empty() {
+        let (min, max) = (5, 2);
+        assert_eq!(Interval::new(min..=max), Interval::new(5..=5));
+        assert_eq!(Interval::new(3..3), Interval::new(3..=3));
+        assert_eq!(Interval::new(..0), Interval::new(0..=0));
+        assert_eq!(
+            Interval::new((Bound::Excluded(usize::MAX), Bound::Unbounded)),
+            Interval::new(usize::MAX..)
+        );
+    }
+
+    #[test]
+    fn test_interval_at_most() {
+        let interval = Interval::at_most(3);
+        assert_eq!(interval, Interval::new(0..=3));
+        assert!(interval.contains(0));
+        assert!(interval.contains(3));
+        assert!(!interval.contains(4));
+    }
+
+    #[test]
+    fn test_interval_intersect() {
+        let a = Interval::new(2..);
+        let b = Interval::at_most(5);
+        assert_eq!(a.intersect(&b), Some(Interval::new(2..=5)));
+        assert_eq!(b.intersect(&a), Some(Interval::new(2..=5)));
+
+        // Touching at a single value
+        let c = Interval::new(5..=9);
+        assert_eq!(b.intersect(&c), Some(Interval::new(5..=5)));
+
+        // Disjoint
+        let d = Interval::new(6..);
+        assert_eq!(b.intersect(&d), None);
+
+        // Both unbounded
+        assert_eq!(a.intersect(&d), Some(Interval::new(6..)));
+
+        // Every value of the intersection is in both intervals
+        for (a, b) in [(a, b), (b, c), (a, c)] {
+            let i = a.intersect(&b).unwrap();
+            for n in 0..12 {
+                assert_eq!(i.contains(n), a.contains(n) && b.contains(n));
+            }
+        }
+    }
+
+    #[test]
+    fn test_interval_union() {
+        let a = Interval::new(1..=3);
+        assert_eq!(a.union(&Interval::new(2..=6)), Some(Interval::new(1..=6)));
+        // Adjacent intervals merge
+        assert_eq!(a.union(&Interval::new(4..=6)), Some(Interval::new(1..=6)));
+        assert_eq!(Interval::new(4..=6).union(&a), Some(Interval::new(1..=6)));
+        // Intervals with a gap do not
+        assert_eq!(a.union(&Interval::new(5..=6)), None);
+        // An unbounded side makes the union unbounded
+        assert_eq!(a.union(&Interval::new(3..)), Some(Interval::new(1..)));
+        assert_eq!(
+            Interval::at_most(usize::MAX).union(&Interval::new(usize::MAX..)),
+            Some(Interval::new(0..))
+        );
+
+        // Every value of either interval is in the union
+        let b = Interval::new(3..=8);
+        let u = a.union(&b).unwrap();
+        for n in 0..12 {
+            assert_eq!(u.contains(n), a.contains(n) || b.contains(n));
+        }
     }
 }
@@ -1,5 +1,8 @@
 //! # dCBOR Pattern Matching
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+pub mod dsl;
 mod error;
 mod format;
 mod interval;
@@ -7,7 +10,12 @@ mod parse;
 mod pattern;
 mod quantifier;
 mod reluctance;
+#[cfg(feature = "serde")]
+mod serde_support;
+pub mod syntax;
 
+#[cfg(feature = "arbitrary")]
+pub use arbitrary::ArbitraryCBOR;
 pub use error::*;
 pub use format::*;
 pub use interval::*;
@@ -0,0 +1,44 @@
+use super::Token;
+use crate::{
+    Error, Pattern, Result, parse::LexerErrors, pattern::registered_custom,
+};
+
+/// Parse a custom pattern of the form `custom(name)`, which matches with the
+/// matcher registered as `name` by [`Pattern::register_custom`].
+pub(crate) fn parse_custom(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    match lexer.next() {
+        Some(Ok(Token::ParenOpen)) => {}
+        Some(Ok(token)) => {
+            return Err(lexer.unexpected(token));
+        }
+        Some(Err(e)) => return Err(e),
+        None => return Err(Error::UnexpectedEndOfInput),
+    }
+
+    // Matcher names aren't tokens, so read the name from the source
+    let src = lexer.remainder();
+    let start = src.len() - src.trim_start().len();
+    let len = src[start..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(src.len() - start);
+    if len == 0 {
+        return Err(match lexer.next() {
+            Some(Ok(token)) => lexer.unexpected(token),
+            Some(Err(e)) => e,
+            None => Error::UnexpectedEndOfInput,
+        });
+    }
+    let name = &src[start..start + len];
+    let offset = lexer.span().end + start;
+    let span = offset..offset + len;
+    let pattern = registered_custom(name)
+        .ok_or_else(|| Error::UnknownCustomMatcher(name.to_string(), span))?;
+    lexer.bump(start + len);
+
+    match lexer.next() {
+        Some(Ok(Token::ParenClose)) => Ok(Pattern::Custom(pattern)),
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(lexer.error(Error::ExpectedCloseParen)),
+    }
+}
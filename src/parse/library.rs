@@ -0,0 +1,219 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use logos::Span;
+
+use super::{ParseOpts, parse_unchecked};
+use crate::{Error, Pattern, Result};
+
+/// A set of named pattern definitions that patterns parsed through the
+/// library can refer to as `$name`.
+///
+/// References are resolved when a pattern is parsed, by substituting the
+/// definition in place, so the parsed pattern is an ordinary [`Pattern`] and
+/// displays with each reference expanded. A definition may only refer to
+/// definitions made before it, which rules out recursive definitions.
+///
+/// A capture inside a definition is renamed for each reference, so that the
+/// references do not share it: `@name` in the `n`th reference to `$user`
+/// becomes `@user_n_name`, counting references from 1 in source order.
+/// References inside definitions nest these prefixes, so `@name` in the
+/// first `$user` of the second `$team` becomes `@team_2_user_1_name`.
+/// Back-references inside the definition are renamed to match.
+///
+/// # Example
+///
+/// ```
+/// use dcbor_parse::parse_dcbor_item;
+/// use dcbor_pattern::{Matcher, PatternLibrary};
+///
+/// let mut library = PatternLibrary::new();
+/// library.define("user", r#"{"name": @name(text), "id": number}"#)?;
+///
+/// let pattern = library.parse("[$user, $user]")?;
+/// let haystack = parse_dcbor_item(
+///     r#"[{"name": "Alice", "id": 1}, {"name": "Bob", "id": 2}]"#,
+/// )
+/// .unwrap();
+/// let (_, captures) = pattern.paths_with_captures(&haystack);
+/// assert!(captures.contains_key("user_1_name"));
+/// assert!(captures.contains_key("user_2_name"));
+/// # Ok::<(), dcbor_pattern::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatternLibrary {
+    definitions: BTreeMap<String, String>,
+}
+
+impl PatternLibrary {
+    /// Creates an empty library.
+    pub fn new() -> Self { Self::default() }
+
+    /// Defines `name` as the pattern in `source`, which may refer to the
+    /// library's earlier definitions.
+    ///
+    /// Fails with [`Error::DuplicateDefinition`] if `name` is already
+    /// defined, with [`Error::RecursiveDefinition`] if `source` refers to
+    /// `name` itself, and with any error parsing `source`, whose spans are
+    /// relative to `source`.
+    pub fn define(
+        &mut self,
+        name: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Result<&mut Self> {
+        let name = name.into();
+        let source = source.into();
+        if self.definitions.contains_key(&name) {
+            return Err(Error::DuplicateDefinition(name));
+        }
+        if !is_identifier(&name) {
+            return Err(Error::InvalidDefinitionName(name));
+        }
+        with_scope(Scope::new(&self.definitions, Some(name.clone())), || {
+            Pattern::parse(&source)
+        })?;
+        self.definitions.insert(name, source);
+        Ok(self)
+    }
+
+    /// Returns the source of the definition of `name`, if there is one.
+    pub fn definition(&self, name: &str) -> Option<&str> {
+        self.definitions.get(name).map(String::as_str)
+    }
+
+    /// Returns the names of the definitions, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.definitions.keys().map(String::as_str)
+    }
+
+    /// Parses a pattern that may refer to the library's definitions, as
+    /// [`Pattern::parse`] does.
+    pub fn parse(&self, source: &str) -> Result<Pattern> {
+        self.parse_with_opts(source, ParseOpts::default())
+    }
+
+    /// Parses a pattern that may refer to the library's definitions, as
+    /// [`Pattern::parse_with_opts`] does.
+    pub fn parse_with_opts(
+        &self,
+        source: &str,
+        opts: ParseOpts,
+    ) -> Result<Pattern> {
+        with_scope(Scope::new(&self.definitions, None), || {
+            Pattern::parse_with_opts(source, opts)
+        })
+    }
+}
+
+/// Returns whether `name` can follow `$` in a reference, or name a custom
+/// matcher.
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The definitions in scope for the parse in progress, and the renaming of
+/// captures in the reference being expanded.
+#[derive(Default)]
+struct Scope {
+    definitions: Rc<BTreeMap<String, String>>,
+    /// The definition being checked by [`PatternLibrary::define`], which its
+    /// own source may not refer to.
+    defining: Option<String>,
+    /// The prefix added to capture names in the reference being expanded.
+    prefix: String,
+    /// How many references to each definition have been expanded so far at
+    /// this level.
+    counts: BTreeMap<String, usize>,
+}
+
+impl Scope {
+    fn new(
+        definitions: &BTreeMap<String, String>,
+        defining: Option<String>,
+    ) -> Self {
+        Scope {
+            definitions: Rc::new(definitions.clone()),
+            defining,
+            ..Default::default()
+        }
+    }
+}
+
+thread_local! {
+    static SCOPE: RefCell<Scope> = RefCell::new(Scope::default());
+}
+
+/// Restores the scope of the enclosing parse when dropped, even on
+/// unwinding.
+struct RestoreScope(Scope);
+
+impl Drop for RestoreScope {
+    fn drop(&mut self) {
+        SCOPE.with_borrow_mut(|scope| {
+            std::mem::swap(scope, &mut self.0);
+        });
+    }
+}
+
+/// Runs the parse `f` with `scope`, restoring the enclosing scope afterwards.
+fn with_scope<R>(scope: Scope, f: impl FnOnce() -> R) -> R {
+    let _restore = RestoreScope(SCOPE.replace(scope));
+    f()
+}
+
+/// Returns the name a capture or back-reference written as `name` has in the
+/// parse in progress.
+pub(crate) fn scoped_capture_name(name: String) -> String {
+    SCOPE.with_borrow(|scope| {
+        if scope.prefix.is_empty() {
+            name
+        } else {
+            format!("{}{}", scope.prefix, name)
+        }
+    })
+}
+
+/// Parses the definition the reference `$name` at `span` refers to, with its
+/// captures renamed for this reference.
+pub(crate) fn resolve_reference(name: &str, span: Span) -> Result<Pattern> {
+    let scope = SCOPE.with_borrow_mut(|scope| {
+        let Some(source) = scope.definitions.get(name) else {
+            return Err(if scope.defining.as_deref() == Some(name) {
+                Error::RecursiveDefinition(name.to_string(), span)
+            } else {
+                Error::UndefinedReference(name.to_string(), span)
+            });
+        };
+        let count = scope.counts.entry(name.to_string()).or_default();
+        *count += 1;
+        let prefix = format!("{}{}_{}_", scope.prefix, name, count);
+        Ok((
+            source.clone(),
+            Scope {
+                definitions: scope.definitions.clone(),
+                defining: scope.defining.clone(),
+                prefix,
+                counts: BTreeMap::new(),
+            },
+        ))
+    });
+    let (source, scope) = scope?;
+    with_scope(scope, || parse_unchecked(&source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("user"));
+        assert!(is_identifier("_user_2"));
+        assert!(!is_identifier(""));
+        assert!(!is_identifier("2user"));
+        assert!(!is_identifier("user-name"));
+    }
+}
@@ -1,5 +1,8 @@
 use super::{super::Token, parse_not};
-use crate::{Pattern, Result};
+use crate::{
+    AndPattern, ArrayPattern, ByteStringPattern, MapPattern, Pattern, Result,
+    StructurePattern, ValuePattern,
+};
 
 /// Parse an AND pattern.
 ///
@@ -13,6 +16,11 @@ use crate::{Pattern, Result};
 /// - `number & (>= 0)` - matches numbers that are also >= 0
 /// - `[*] & map` - matches values that are both arrays AND maps (impossible,
 ///   always fails)
+///
+/// Length constraints on the same kind of value are merged, so
+/// `[{2,}] & [{,5}]` parses as `[{2,5}]`. An array length constraint is also
+/// merged with an array element pattern, so `[{3,}] & [(number)*]` parses as
+/// `[{3,} & (number)*]`.
 pub(crate) fn parse_and(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
     let mut patterns = vec![parse_not(lexer)?];
 
@@ -27,9 +35,126 @@ pub(crate) fn parse_and(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
         }
     }
 
+    // A parenthesized AND among the operands is flattened into them
+    let flattened: AndPattern = patterns.into_iter().collect();
+    let mut patterns = merge_lengths(flattened.patterns().to_vec());
     if patterns.len() == 1 {
         Ok(patterns.remove(0))
     } else {
         Ok(Pattern::and(patterns))
     }
 }
+
+/// Merges each length constraint into an earlier one on the same kind of
+/// value by intersecting their intervals, or into an earlier array element
+/// pattern. Constraints whose intervals don't intersect are left as they
+/// are.
+pub(crate) fn merge_lengths(patterns: Vec<Pattern>) -> Vec<Pattern> {
+    let mut merged: Vec<Pattern> = Vec::with_capacity(patterns.len());
+    for pattern in patterns {
+        let combined = merged.iter_mut().find_map(|earlier| {
+            Some((intersect_lengths(earlier, &pattern)?, earlier))
+        });
+        match combined {
+            Some((intersection, earlier)) => *earlier = intersection,
+            None => merged.push(pattern),
+        }
+    }
+    merged
+}
+
+/// Returns the single length constraint equivalent to both `a` and `b`, if
+/// they constrain the length of the same kind of value and can both hold.
+fn intersect_lengths(a: &Pattern, b: &Pattern) -> Option<Pattern> {
+    match (a, b) {
+        (
+            Pattern::Structure(StructurePattern::Array(a)),
+            Pattern::Structure(StructurePattern::Array(b)),
+        ) => Some(Pattern::Structure(StructurePattern::Array(
+            intersect_array_lengths(a, b)?,
+        ))),
+        (
+            Pattern::Structure(StructurePattern::Map(MapPattern::Length(a))),
+            Pattern::Structure(StructurePattern::Map(MapPattern::Length(b))),
+        ) => Some(Pattern::Structure(StructurePattern::Map(
+            MapPattern::Length(a.intersect(b)?),
+        ))),
+        (
+            Pattern::Value(ValuePattern::ByteString(
+                ByteStringPattern::Length(a),
+            )),
+            Pattern::Value(ValuePattern::ByteString(
+                ByteStringPattern::Length(b),
+            )),
+        ) => Some(Pattern::byte_string_with_length(a.intersect(b)?)),
+        _ => None,
+    }
+}
+
+/// Returns the single array pattern equivalent to both `a` and `b`, if at
+/// most one of them has an element pattern and their lengths can both hold.
+fn intersect_array_lengths(
+    a: &ArrayPattern,
+    b: &ArrayPattern,
+) -> Option<ArrayPattern> {
+    let (interval, elements) = match (a, b) {
+        (ArrayPattern::Length(a), ArrayPattern::Length(b)) => {
+            return Some(ArrayPattern::Length(a.intersect(b)?));
+        }
+        (ArrayPattern::Length(a), ArrayPattern::Elements(elements))
+        | (ArrayPattern::Elements(elements), ArrayPattern::Length(a)) => {
+            (*a, elements)
+        }
+        (
+            ArrayPattern::Length(a),
+            ArrayPattern::LengthAndElements(b, elements),
+        )
+        | (
+            ArrayPattern::LengthAndElements(b, elements),
+            ArrayPattern::Length(a),
+        ) => (a.intersect(b)?, elements),
+        _ => return None,
+    };
+    Some(ArrayPattern::with_length_and_elements(
+        interval,
+        elements.as_ref().clone(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_merges_lengths() {
+        let pattern = Pattern::parse("[{2,}] & [{,5}]").unwrap();
+        assert_eq!(
+            pattern,
+            Pattern::Structure(StructurePattern::Array(
+                ArrayPattern::with_length_range(2..=5)
+            ))
+        );
+        assert_eq!(pattern.to_string(), "[{2,5}]");
+
+        let pattern = Pattern::parse("{{1,}} & text & {{,3}}").unwrap();
+        assert_eq!(pattern.to_string(), "{{1,3}} & text");
+
+        let pattern = Pattern::parse("bstr({4,}) & bstr({,4})").unwrap();
+        assert_eq!(pattern.to_string(), "bstr({4})");
+
+        // Disjoint lengths can't be merged and are kept as written
+        let pattern = Pattern::parse("[{,1}] & [{3,}]").unwrap();
+        assert_eq!(pattern.to_string(), "[{,1}] & [{3,}]");
+
+        // Different kinds of values are not merged
+        let pattern = Pattern::parse("[{2,}] & {{,5}}").unwrap();
+        assert_eq!(pattern.to_string(), "[{2,}] & {{,5}}");
+
+        // Array lengths merge with element patterns, but element patterns
+        // don't merge with each other
+        let pattern = Pattern::parse("[(number)*] & [{3,}] & [{,5}]").unwrap();
+        assert_eq!(pattern, Pattern::parse("[{3,5} & (number)*]").unwrap());
+        let pattern = Pattern::parse("[number, *] & [*, text]").unwrap();
+        assert_eq!(pattern.to_string(), "[number, *] & [*, text]");
+    }
+}
@@ -0,0 +1,61 @@
+use super::{super::Token, parse_or};
+use crate::{Error, PathStep, Pattern, Result, parse::LexerErrors};
+
+/// Parse a path query of the form `at(step, step, ..., pattern)`, where each
+/// step is a string literal naming a map key or a number literal giving an
+/// array index.
+///
+/// Examples:
+/// - `at("items", 2, number)` - matches a map whose value under `"items"` is an
+///   array with a number at index 2
+/// - `at("name", @name(text))` - captures the text value under `"name"`
+pub(crate) fn parse_at(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    match lexer.next() {
+        Some(Ok(Token::ParenOpen)) => {}
+        Some(Ok(token)) => {
+            return Err(lexer.unexpected(token));
+        }
+        Some(Err(e)) => return Err(e),
+        None => return Err(Error::UnexpectedEndOfInput),
+    }
+
+    let mut steps = Vec::new();
+    while let Some(step) = parse_step(lexer)? {
+        steps.push(step);
+    }
+
+    let pattern = parse_or(lexer)?;
+    match lexer.next() {
+        Some(Ok(Token::ParenClose)) => Ok(Pattern::at(steps, pattern)),
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(lexer.error(Error::ExpectedCloseParen)),
+    }
+}
+
+/// Parse the next step and the comma after it, or return `None`, consuming
+/// nothing, if a literal followed by a comma doesn't come next.
+fn parse_step(lexer: &mut logos::Lexer<Token>) -> Result<Option<PathStep>> {
+    let mut lookahead = lexer.clone();
+    let token = lookahead.next();
+    let span = lookahead.span();
+    if !matches!(lookahead.next(), Some(Ok(Token::Comma))) {
+        return Ok(None);
+    }
+    let step = match token {
+        Some(Ok(Token::StringLiteral(key))) => PathStep::Key(key?),
+        Some(Ok(Token::NumberLiteral(index))) => {
+            let index = index?;
+            if index < 0.0 || index.fract() != 0.0 || index >= usize::MAX as f64
+            {
+                return Err(
+                    lookahead.error_at(Error::InvalidNumberFormat, span)
+                );
+            }
+            PathStep::Index(index as usize)
+        }
+        _ => return Ok(None),
+    };
+    *lexer = lookahead;
+    Ok(Some(step))
+}
@@ -1,5 +1,8 @@
 use super::{super::Token, parse_or};
-use crate::{Error, Pattern, Result};
+use crate::{
+    Error, Pattern, Result,
+    parse::{LexerErrors, scoped_capture_name},
+};
 
 /// Parse a capture pattern of the form `@name(pattern)`.
 ///
@@ -20,18 +23,14 @@ pub(crate) fn parse_capture(
             let pattern = parse_or(lexer)?;
             match lexer.next() {
                 Some(Ok(Token::ParenClose)) => {
-                    Ok(Pattern::capture(name, pattern))
-                }
-                Some(Ok(token)) => {
-                    Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
+                    Ok(Pattern::capture(scoped_capture_name(name), pattern))
                 }
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
                 Some(Err(e)) => Err(e),
-                None => Err(Error::ExpectedCloseParen(lexer.span())),
+                None => Err(lexer.error(Error::ExpectedCloseParen)),
             }
         }
-        Some(Ok(token)) => {
-            Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-        }
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
         Some(Err(e)) => Err(e),
         None => Err(Error::UnexpectedEndOfInput),
     }
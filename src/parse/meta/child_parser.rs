@@ -0,0 +1,24 @@
+use super::{super::Token, parse_or};
+use crate::{Error, Pattern, Result, parse::LexerErrors};
+
+/// Parse a child pattern of the form `child(pattern)`.
+///
+/// Examples:
+/// - `child(number)` - matches any direct child that is a number
+/// - `child(@item(text))` - captures each direct child that is text
+pub(crate) fn parse_child(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    match lexer.next() {
+        Some(Ok(Token::ParenOpen)) => {
+            let pattern = parse_or(lexer)?;
+            match lexer.next() {
+                Some(Ok(Token::ParenClose)) => Ok(Pattern::child(pattern)),
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
+                Some(Err(e)) => Err(e),
+                None => Err(lexer.error(Error::ExpectedCloseParen)),
+            }
+        }
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(Error::UnexpectedEndOfInput),
+    }
+}
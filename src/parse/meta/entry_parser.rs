@@ -0,0 +1,31 @@
+use super::{super::Token, parse_or};
+use crate::{
+    Error, Pattern, Result,
+    parse::{LexerErrors, structure::expect_colon},
+};
+
+/// Parse an entry pattern of the form `entry(key: value)`.
+///
+/// Examples:
+/// - `entry(text: number)` - matches each map entry with a text key and a
+///   number value
+/// - `search(entry(@key(text): [(*)*]))` - captures the key of every entry with
+///   an array value anywhere in the tree
+pub(crate) fn parse_entry(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    match lexer.next() {
+        Some(Ok(Token::ParenOpen)) => {
+            let key = parse_or(lexer)?;
+            expect_colon(lexer)?;
+            let value = parse_or(lexer)?;
+            match lexer.next() {
+                Some(Ok(Token::ParenClose)) => Ok(Pattern::entry(key, value)),
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
+                Some(Err(e)) => Err(e),
+                None => Err(lexer.error(Error::ExpectedCloseParen)),
+            }
+        }
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(Error::UnexpectedEndOfInput),
+    }
+}
@@ -1,15 +1,27 @@
 mod and_parser;
+mod at_parser;
 mod capture_parser;
+mod child_parser;
+mod entry_parser;
+mod none_parser;
 mod not_parser;
 mod or_parser;
 mod primary_parser;
 mod repeat_parser;
 mod search_parser;
+mod sequence_parser;
+mod untag_parser;
 
 pub(crate) use and_parser::*;
+pub(crate) use at_parser::*;
 pub(crate) use capture_parser::*;
+pub(crate) use child_parser::*;
+pub(crate) use entry_parser::*;
+pub(crate) use none_parser::*;
 pub(crate) use not_parser::*;
 pub(crate) use or_parser::*;
 pub(crate) use primary_parser::*;
 pub(crate) use repeat_parser::*;
 pub(crate) use search_parser::*;
+pub(crate) use sequence_parser::*;
+pub(crate) use untag_parser::*;
@@ -0,0 +1,31 @@
+use super::{super::Token, parse_or};
+use crate::{
+    Error, Pattern, Result,
+    parse::{LexerErrors, check_negated_captures},
+};
+
+/// Parse a none pattern of the form `none(pattern)`.
+///
+/// Examples:
+/// - `none(number)` - matches nodes with no number anywhere inside them
+/// - `search(none(text))` - finds every subtree that contains no text
+///
+/// As with `!`, captures inside the pattern are rejected.
+pub(crate) fn parse_none(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    match lexer.next() {
+        Some(Ok(Token::ParenOpen)) => {
+            let start = lexer.span().end;
+            let pattern = parse_or(lexer)?;
+            check_negated_captures(lexer, start, &pattern)?;
+            match lexer.next() {
+                Some(Ok(Token::ParenClose)) => Ok(Pattern::none(pattern)),
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
+                Some(Err(e)) => Err(e),
+                None => Err(lexer.error(Error::ExpectedCloseParen)),
+            }
+        }
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(Error::UnexpectedEndOfInput),
+    }
+}
@@ -1,10 +1,14 @@
 use super::{super::Token, parse_primary};
-use crate::{Pattern, Result};
+use crate::{
+    Pattern, Result,
+    parse::{NestingGuard, check_negated_captures},
+};
 
 /// Parse a NOT pattern or delegate to primary parser.
 ///
 /// This parser handles the NOT operator (!) with right associativity.
-/// If no NOT token is found, it delegates to the primary parser.
+/// If no NOT token is found, it delegates to the primary parser. Captures
+/// inside the negated pattern are rejected.
 ///
 /// Examples:
 /// - `!bool` - matches anything that is not a boolean
@@ -15,7 +19,10 @@ pub(crate) fn parse_not(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
     match lookahead.next() {
         Some(Ok(Token::Not)) => {
             lexer.next(); // consume the NOT token
+            let _nesting = NestingGuard::enter(lexer)?;
+            let start = lexer.span().end;
             let pattern = parse_not(lexer)?; // right associative recursion
+            check_negated_captures(lexer, start, &pattern)?;
             Ok(Pattern::not_matching(pattern))
         }
         _ => parse_primary(lexer),
@@ -1,5 +1,5 @@
 use super::{super::Token, parse_and};
-use crate::{Pattern, Result};
+use crate::{MetaPattern, Pattern, Result};
 
 /// Parse an OR pattern - the top-level pattern parser.
 ///
@@ -31,6 +31,9 @@ pub(crate) fn parse_or(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
     if patterns.len() == 1 {
         Ok(patterns.remove(0))
     } else {
-        Ok(Pattern::or(patterns))
+        // A parenthesized OR among the alternatives is flattened into them
+        Ok(Pattern::Meta(MetaPattern::Or(
+            patterns.into_iter().collect(),
+        )))
     }
 }
@@ -3,23 +3,33 @@ use super::super::{
     value::{
         parse_bool, parse_bool_false, parse_bool_true, parse_bytestring,
         parse_date, parse_digest, parse_known_value, parse_null, parse_number,
-        parse_text,
+        parse_number_from, parse_number_up_to, parse_text,
     },
 };
 use crate::{
-    Error, MapPattern, Pattern, Result,
-    parse::structure::{parse_bracket_array, parse_bracket_map, parse_tagged},
+    Error, Pattern, Result,
+    parse::{
+        LexerErrors, NestingGuard, parse_custom, resolve_reference,
+        scoped_capture_name,
+        structure::{parse_bracket_array, parse_bracket_map, parse_tagged},
+    },
     value::{parse_hex_regex_token, parse_hex_string_token},
 };
 
 /// Parse a primary pattern - the most basic unit of pattern matching.
 ///
 /// This parser handles:
-/// - * and search meta patterns
+/// - *, search, child, entry, and none meta patterns
 /// - Parenthesized group patterns
 /// - Capture patterns (@name(...))
+/// - Back-references (=name)
+/// - References to [`PatternLibrary`](crate::PatternLibrary) definitions
+///   ($name)
 /// - All atomic value patterns (bool, text, number, etc.)
 /// - All structure patterns (Array, Map, Tagged)
+///
+/// A quantifier may follow any primary pattern, so `number{3}` is the same as
+/// `(number){3}`.
 pub(crate) fn parse_primary(
     lexer: &mut logos::Lexer<Token>,
 ) -> Result<Pattern> {
@@ -29,7 +39,7 @@ pub(crate) fn parse_primary(
             // Convert Unknown errors to UnrecognizedToken with span information
             match e {
                 Error::Unknown => {
-                    return Err(Error::UnrecognizedToken(lexer.span()));
+                    return Err(lexer.error(Error::UnrecognizedToken));
                 }
                 _ => return Err(e),
             }
@@ -37,40 +47,80 @@ pub(crate) fn parse_primary(
         None => return Err(Error::UnexpectedEndOfInput),
     };
 
+    // Patterns that contain other patterns count towards the nesting limit
+    // until they are fully parsed
+    let _nesting = match token {
+        Token::Search
+        | Token::Child
+        | Token::Untag
+        | Token::At
+        | Token::ByteString
+        | Token::Entry
+        | Token::None
+        | Token::ParenOpen
+        | Token::GroupName(_)
+        | Token::Reference(_)
+        | Token::Tagged
+        | Token::BracketOpen
+        | Token::BraceOpen => Some(NestingGuard::enter(lexer)?),
+        _ => None,
+    };
+
+    let pattern = if matches!(token, Token::ParenOpen) {
+        parse_group(lexer)?
+    } else {
+        parse_unquantified(token, lexer)?
+    };
+    super::parse_quantifier(pattern, lexer)
+}
+
+/// Parse a parenthesized group, which is just the pattern it contains, or the
+/// sequence if it contains several separated by commas.
+fn parse_group(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    let pattern = super::parse_sequence(lexer)?;
+    match lexer.next() {
+        Some(Ok(Token::ParenClose)) => Ok(pattern),
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(Error::UnexpectedEndOfInput),
+    }
+}
+
+/// Parse the primary pattern that starts with `token`, other than a group.
+fn parse_unquantified(
+    token: Token,
+    lexer: &mut logos::Lexer<Token>,
+) -> Result<Pattern> {
     match token {
         // Meta patterns
         Token::RepeatZeroOrMore => Ok(Pattern::any()), /* '*' as standalone */
         // pattern means
         // "any"
         Token::Search => super::parse_search(lexer),
-
-        // Parenthesized groups - parse the inner pattern and check for
-        // quantifiers
-        Token::ParenOpen => {
-            let pattern = super::parse_or(lexer)?;
-            match lexer.next() {
-                Some(Ok(Token::ParenClose)) => {
-                    // After closing parenthesis, check for quantifiers
-                    // Always force RepeatPattern creation for parentheses
-                    super::parse_quantifier(pattern, lexer, true)
-                }
-                Some(Ok(token)) => {
-                    Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-                }
-                Some(Err(e)) => Err(e),
-                None => Err(Error::UnexpectedEndOfInput),
-            }
-        }
+        Token::Child => super::parse_child(lexer),
+        Token::Untag => super::parse_untag(lexer),
+        Token::At => super::parse_at(lexer),
+        Token::Entry => super::parse_entry(lexer),
+        Token::None => super::parse_none(lexer),
 
         // Capture patterns (@name(...))
         Token::GroupName(name) => super::parse_capture(lexer, name),
 
+        // Back-references (=name)
+        Token::Backref(name) => Ok(Pattern::backref(scoped_capture_name(name))),
+
+        // References to library definitions ($name)
+        Token::Reference(name) => resolve_reference(&name, lexer.span()),
+
         // Value patterns
         Token::Bool => parse_bool(lexer),
         Token::BoolTrue => parse_bool_true(lexer),
         Token::BoolFalse => parse_bool_false(lexer),
         Token::ByteString => parse_bytestring(lexer),
         Token::Date => parse_date(lexer),
+        Token::DateAny => Ok(Pattern::date_any_encoding(
+            crate::pattern::DatePattern::any(),
+        )),
         Token::Digest => parse_digest(lexer),
         Token::DigestQuoted(res) => {
             let digest_pattern = res?;
@@ -78,12 +128,21 @@ pub(crate) fn parse_primary(
                 digest_pattern,
             )))
         }
+        Token::EncodedDigestQuoted(res) => {
+            let encoded_digest_pattern =
+                crate::pattern::EncodedDigestPattern::from_digest_pattern(res?);
+            Ok(Pattern::Value(crate::pattern::ValuePattern::EncodedDigest(
+                encoded_digest_pattern,
+            )))
+        }
+        Token::CborQuoted(res) => Ok(Pattern::cbor(res?)),
         Token::DateQuoted(res) => {
             let date_pattern = res?;
             Ok(Pattern::Value(crate::pattern::ValuePattern::Date(
                 date_pattern,
             )))
         }
+        Token::DateAnyQuoted(res) => Ok(Pattern::date_any_encoding(res?)),
         Token::Known => parse_known_value(lexer),
         Token::Null => parse_null(lexer),
         Token::Number => parse_number(lexer),
@@ -98,14 +157,14 @@ pub(crate) fn parse_primary(
         // Single-quoted pattern (non-prefixed known value)
         Token::SingleQuoted(res) => {
             let value = res?;
-            parse_single_quoted_as_known_value(value)
+            parse_single_quoted_as_known_value(value, lexer)
         }
 
         // Direct regex literal
         Token::Regex(res) => {
             let regex_str = res?;
             let regex = regex::Regex::new(&regex_str)
-                .map_err(|_| Error::InvalidRegex(lexer.span()))?;
+                .map_err(|_| lexer.error(Error::InvalidRegex))?;
             Ok(Pattern::text_regex(regex))
         }
 
@@ -113,7 +172,10 @@ pub(crate) fn parse_primary(
         Token::HexString(res) => parse_hex_string_token(res),
 
         // Direct hex regex literal
-        Token::HexRegex(res) => parse_hex_regex_token(res),
+        Token::HexRegex(res) => parse_hex_regex_token(res, lexer),
+
+        // Custom patterns (custom(name))
+        Token::Custom => parse_custom(lexer),
 
         // Structure patterns
         Token::Tagged => parse_tagged(lexer),
@@ -132,43 +194,9 @@ pub(crate) fn parse_primary(
         // Brace syntax for maps
         Token::BraceOpen => parse_bracket_map(lexer),
 
-        // Range tokens that represent map length constraints (e.g., {3}, {2,5})
-        Token::Range(res) => {
-            // Range tokens at the top level represent map length constraints
-            let quantifier = res?;
-
-            // Convert quantifier to appropriate MapPattern
-            let pattern = MapPattern::with_length_interval(quantifier.into());
-
-            Ok(Pattern::Structure(crate::pattern::StructurePattern::Map(
-                pattern,
-            )))
-        }
-
-        // New simplified number syntax
-        Token::NumberLiteral(res) => {
-            let value = res?;
-
-            // Look ahead for range operator
-            match lexer.clone().next() {
-                Some(Ok(Token::Ellipsis)) => {
-                    lexer.next(); // consume the ellipsis
-                    match lexer.next() {
-                        Some(Ok(Token::NumberLiteral(Ok(end_value)))) => {
-                            Ok(Pattern::number_range(value..=end_value))
-                        }
-                        Some(Ok(Token::NumberLiteral(Err(e)))) => Err(e),
-                        Some(Ok(token)) => Err(Error::UnexpectedToken(
-                            Box::new(token),
-                            lexer.span(),
-                        )),
-                        Some(Err(e)) => Err(e),
-                        None => Err(Error::UnexpectedEndOfInput),
-                    }
-                }
-                _ => Ok(Pattern::number(value)),
-            }
-        }
+        // New simplified number syntax, including ranges
+        Token::NumberLiteral(res) => parse_number_from(lexer, res?),
+        Token::Ellipsis => parse_number_up_to(lexer),
 
         Token::NaN => Ok(Pattern::number_nan()),
         Token::Infinity => Ok(Pattern::number_infinity()),
@@ -179,9 +207,7 @@ pub(crate) fn parse_primary(
                 Ok(Pattern::number_greater_than_or_equal(value))
             }
             Some(Ok(Token::NumberLiteral(Err(e)))) => Err(e),
-            Some(Ok(token)) => {
-                Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-            }
+            Some(Ok(token)) => Err(lexer.unexpected(token)),
             Some(Err(e)) => Err(e),
             None => Err(Error::UnexpectedEndOfInput),
         },
@@ -191,9 +217,7 @@ pub(crate) fn parse_primary(
                 Ok(Pattern::number_less_than_or_equal(value))
             }
             Some(Ok(Token::NumberLiteral(Err(e)))) => Err(e),
-            Some(Ok(token)) => {
-                Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-            }
+            Some(Ok(token)) => Err(lexer.unexpected(token)),
             Some(Err(e)) => Err(e),
             None => Err(Error::UnexpectedEndOfInput),
         },
@@ -203,9 +227,7 @@ pub(crate) fn parse_primary(
                 Ok(Pattern::number_greater_than(value))
             }
             Some(Ok(Token::NumberLiteral(Err(e)))) => Err(e),
-            Some(Ok(token)) => {
-                Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-            }
+            Some(Ok(token)) => Err(lexer.unexpected(token)),
             Some(Err(e)) => Err(e),
             None => Err(Error::UnexpectedEndOfInput),
         },
@@ -215,15 +237,18 @@ pub(crate) fn parse_primary(
                 Ok(Pattern::number_less_than(value))
             }
             Some(Ok(Token::NumberLiteral(Err(e)))) => Err(e),
-            Some(Ok(token)) => {
-                Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-            }
+            Some(Ok(token)) => Err(lexer.unexpected(token)),
             Some(Err(e)) => Err(e),
             None => Err(Error::UnexpectedEndOfInput),
         },
 
+        // Any other quantifier here has no pattern before it to repeat
+        _ if super::is_quantifier(&token) => {
+            Err(lexer.error(Error::NothingToRepeat))
+        }
+
         // Unexpected tokens
-        _ => Err(Error::UnexpectedToken(Box::new(token), lexer.span())),
+        _ => Err(lexer.unexpected(token)),
     }
 }
 
@@ -232,12 +257,15 @@ pub(crate) fn parse_primary(
 /// - 'value' -> known value by numeric ID
 /// - 'name' -> known value by name
 /// - '/regex/' -> known value by regex
-fn parse_single_quoted_as_known_value(value: String) -> Result<Pattern> {
+fn parse_single_quoted_as_known_value(
+    value: String,
+    lexer: &logos::Lexer<Token>,
+) -> Result<Pattern> {
     // Check if it's a regex pattern (starts and ends with /)
     if value.starts_with('/') && value.ends_with('/') && value.len() > 2 {
         let regex_str = &value[1..value.len() - 1];
         let regex = regex::Regex::new(regex_str)
-            .map_err(|_| Error::InvalidRegex(0..value.len()))?;
+            .map_err(|_| lexer.error(Error::InvalidRegex))?;
         return Ok(Pattern::known_value_regex(regex));
     }
 
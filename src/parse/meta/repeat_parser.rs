@@ -1,135 +1,97 @@
 //! Parser for repeat patterns (quantifiers).
 //!
 //! This module handles parsing of quantifier syntax like *, +, ?, {n,m}
-//! that can follow grouped patterns in parentheses.
+//! that can follow a primary pattern, such as a group in parentheses.
 
-use super::super::Token;
-use crate::{Pattern, Quantifier, Reluctance, Result};
+use super::super::{Token, parse_range};
+use crate::{
+    Error, Pattern, Quantifier, Reluctance, Result, parse::LexerErrors,
+};
 
-/// Parse quantifier tokens that follow a grouped pattern.
+/// Parse a quantifier that follows a primary pattern.
 ///
 /// This function assumes that a pattern has been parsed and we're now
-/// looking for quantifier operators like *, +, ?, or {n,m}.
+/// looking for quantifier operators like *, +, ?, or {n,m}. A quantifier
+/// may not itself be followed by another one, as in `(a)*{2}`.
 ///
 /// # Arguments
 /// * `pattern` - The pattern to apply the quantifier to
 /// * `lexer` - The lexer positioned after the pattern
-/// * `force_repeat` - If true, always wrap in RepeatPattern even without
-///   explicit quantifier
 ///
 /// # Returns
-/// * `Ok(Pattern)` - The pattern wrapped with the appropriate quantifier
+/// * `Ok(Pattern)` - The pattern wrapped with the appropriate quantifier, or
+///   unchanged if no quantifier follows
 /// * `Err(Error)` - If quantifier parsing fails
 pub(crate) fn parse_quantifier(
     pattern: Pattern,
     lexer: &mut logos::Lexer<Token>,
-    force_repeat: bool,
 ) -> Result<Pattern> {
-    // Look ahead to see if there's a quantifier
+    let quantifier = match next_quantifier(lexer) {
+        Some(res) => res?,
+        None => return Ok(pattern),
+    };
+
     let mut lookahead = lexer.clone();
-    match lookahead.next() {
-        Some(Ok(tok)) => match tok {
-            Token::RepeatZeroOrMore => {
-                lexer.next(); // consume the token
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(0.., Reluctance::Greedy),
-                ))
-            }
-            Token::RepeatZeroOrMoreLazy => {
-                lexer.next();
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(0.., Reluctance::Lazy),
-                ))
-            }
-            Token::RepeatZeroOrMorePossessive => {
-                lexer.next();
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(0.., Reluctance::Possessive),
-                ))
-            }
-            Token::RepeatOneOrMore => {
-                lexer.next();
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(1.., Reluctance::Greedy),
-                ))
-            }
-            Token::RepeatOneOrMoreLazy => {
-                lexer.next();
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(1.., Reluctance::Lazy),
-                ))
-            }
-            Token::RepeatOneOrMorePossessive => {
-                lexer.next();
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(1.., Reluctance::Possessive),
-                ))
-            }
-            Token::RepeatZeroOrOne => {
-                lexer.next();
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(0..=1, Reluctance::Greedy),
-                ))
-            }
-            Token::RepeatZeroOrOneLazy => {
-                lexer.next();
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(0..=1, Reluctance::Lazy),
-                ))
-            }
-            Token::RepeatZeroOrOnePossessive => {
-                lexer.next();
-                Ok(Pattern::repeat(
-                    pattern,
-                    Quantifier::new(0..=1, Reluctance::Possessive),
-                ))
-            }
-            Token::Range(res) => {
-                lexer.next(); // consume the token
-                let q = res?;
-                let pat = if let Some(max) = q.max() {
-                    Pattern::repeat(
-                        pattern,
-                        Quantifier::new(q.min()..=max, q.reluctance()),
-                    )
-                } else {
-                    Pattern::repeat(
-                        pattern,
-                        Quantifier::new(q.min().., q.reluctance()),
-                    )
-                };
-                Ok(pat)
-            }
-            _ => {
-                // No quantifier found - behavior depends on force_repeat flag
-                if force_repeat {
-                    // Parentheses always create a RepeatPattern with "exactly
-                    // one"
-                    Ok(Pattern::repeat(pattern, Quantifier::default()))
-                } else {
-                    // Return pattern unchanged for general use
-                    Ok(pattern)
-                }
-            }
-        },
-        _ => {
-            // No quantifier found - behavior depends on force_repeat flag
-            if force_repeat {
-                // Parentheses always create a RepeatPattern with "exactly one"
-                Ok(Pattern::repeat(pattern, Quantifier::default()))
-            } else {
-                // Return pattern unchanged for general use
-                Ok(pattern)
-            }
+    if next_quantifier(&mut lookahead).is_some() {
+        return Err(lookahead.error(Error::NothingToRepeat));
+    }
+
+    Ok(Pattern::repeat(pattern, quantifier))
+}
+
+/// Consumes the quantifier that comes next, if any. After a pattern, a `{`
+/// always opens a range rather than a map.
+fn next_quantifier(
+    lexer: &mut logos::Lexer<Token>,
+) -> Option<Result<Quantifier>> {
+    if let Some(res) = parse_range(lexer, true) {
+        return Some(res);
+    }
+    match lexer.clone().next() {
+        Some(Ok(tok)) if is_quantifier(&tok) => {
+            lexer.next(); // consume the token
+            Some(Ok(to_quantifier(tok)))
         }
+        _ => None,
+    }
+}
+
+/// Returns true if the token is a quantifier: `*`, `+`, or `?`, with an
+/// optional reluctance suffix.
+pub(crate) fn is_quantifier(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::RepeatZeroOrMore
+            | Token::RepeatZeroOrMoreLazy
+            | Token::RepeatZeroOrMorePossessive
+            | Token::RepeatOneOrMore
+            | Token::RepeatOneOrMoreLazy
+            | Token::RepeatOneOrMorePossessive
+            | Token::RepeatZeroOrOne
+            | Token::RepeatZeroOrOneLazy
+            | Token::RepeatZeroOrOnePossessive
+    )
+}
+
+/// Converts a quantifier token into the quantifier it denotes.
+fn to_quantifier(token: Token) -> Quantifier {
+    match token {
+        Token::RepeatZeroOrMore => Quantifier::new(0.., Reluctance::Greedy),
+        Token::RepeatZeroOrMoreLazy => Quantifier::new(0.., Reluctance::Lazy),
+        Token::RepeatZeroOrMorePossessive => {
+            Quantifier::new(0.., Reluctance::Possessive)
+        }
+        Token::RepeatOneOrMore => Quantifier::new(1.., Reluctance::Greedy),
+        Token::RepeatOneOrMoreLazy => Quantifier::new(1.., Reluctance::Lazy),
+        Token::RepeatOneOrMorePossessive => {
+            Quantifier::new(1.., Reluctance::Possessive)
+        }
+        Token::RepeatZeroOrOne => Quantifier::new(0..=1, Reluctance::Greedy),
+        Token::RepeatZeroOrOneLazy => Quantifier::new(0..=1, Reluctance::Lazy),
+        Token::RepeatZeroOrOnePossessive => {
+            Quantifier::new(0..=1, Reluctance::Possessive)
+        }
+        _ => unreachable!("not a quantifier token"),
     }
 }
 
@@ -143,7 +105,7 @@ mod tests {
     fn test_parse_quantifier_star() {
         let mut lexer = Token::lexer("*");
         let pattern = Pattern::number(42);
-        let result = parse_quantifier(pattern, &mut lexer, false).unwrap();
+        let result = parse_quantifier(pattern, &mut lexer).unwrap();
 
         // Should be a repeat pattern with 0.. quantifier
         assert_eq!(result.to_string(), "(42)*");
@@ -153,7 +115,7 @@ mod tests {
     fn test_parse_quantifier_plus() {
         let mut lexer = Token::lexer("+");
         let pattern = Pattern::number(42);
-        let result = parse_quantifier(pattern, &mut lexer, false).unwrap();
+        let result = parse_quantifier(pattern, &mut lexer).unwrap();
 
         assert_eq!(result.to_string(), "(42)+");
     }
@@ -162,7 +124,7 @@ mod tests {
     fn test_parse_quantifier_question() {
         let mut lexer = Token::lexer("?");
         let pattern = Pattern::number(42);
-        let result = parse_quantifier(pattern, &mut lexer, false).unwrap();
+        let result = parse_quantifier(pattern, &mut lexer).unwrap();
 
         assert_eq!(result.to_string(), "(42)?");
     }
@@ -171,7 +133,7 @@ mod tests {
     fn test_parse_quantifier_lazy() {
         let mut lexer = Token::lexer("*?");
         let pattern = Pattern::number(42);
-        let result = parse_quantifier(pattern, &mut lexer, false).unwrap();
+        let result = parse_quantifier(pattern, &mut lexer).unwrap();
 
         assert_eq!(result.to_string(), "(42)*?");
     }
@@ -180,7 +142,7 @@ mod tests {
     fn test_parse_quantifier_possessive() {
         let mut lexer = Token::lexer("++");
         let pattern = Pattern::number(42);
-        let result = parse_quantifier(pattern, &mut lexer, false).unwrap();
+        let result = parse_quantifier(pattern, &mut lexer).unwrap();
 
         assert_eq!(result.to_string(), "(42)++");
     }
@@ -189,7 +151,7 @@ mod tests {
     fn test_parse_quantifier_range() {
         let mut lexer = Token::lexer("{3,5}");
         let pattern = Pattern::number(42);
-        let result = parse_quantifier(pattern, &mut lexer, false).unwrap();
+        let result = parse_quantifier(pattern, &mut lexer).unwrap();
 
         assert_eq!(result.to_string(), "(42){3,5}");
     }
@@ -198,19 +160,30 @@ mod tests {
     fn test_parse_quantifier_no_quantifier() {
         let mut lexer = Token::lexer("OTHER");
         let pattern = Pattern::number(42);
-        let result = parse_quantifier(pattern, &mut lexer, false).unwrap();
+        let result = parse_quantifier(pattern, &mut lexer).unwrap();
 
         // Should return the pattern unchanged
         assert_eq!(result.to_string(), "42");
     }
 
     #[test]
-    fn test_parse_quantifier_force_repeat() {
-        let mut lexer = Token::lexer("OTHER");
+    fn test_parse_quantifier_exactly_once() {
+        let mut lexer = Token::lexer("{1}");
         let pattern = Pattern::number(42);
-        let result = parse_quantifier(pattern, &mut lexer, true).unwrap();
+        let result = parse_quantifier(pattern, &mut lexer).unwrap();
 
-        // Should always create a RepeatPattern when force_repeat is true
+        // An explicit {1} still creates a RepeatPattern
         assert_eq!(result.to_string(), "(42){1}");
     }
+
+    #[test]
+    fn test_parse_quantifier_stacked() {
+        let mut lexer = Token::lexer("*{2}");
+        let pattern = Pattern::number(42);
+        let result = parse_quantifier(pattern, &mut lexer);
+
+        assert!(
+            matches!(result, Err(Error::NothingToRepeat(_, span)) if span == (1..4))
+        );
+    }
 }
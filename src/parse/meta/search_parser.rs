@@ -1,21 +1,176 @@
-use super::{super::Token, or_parser::parse_or};
-use crate::{Error, Pattern, Result};
+use logos::Lexer;
 
-pub(crate) fn parse_search(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+use super::super::Token;
+use crate::{
+    Error, Pattern, Result, SearchOpts,
+    parse::{LexerErrors, meta::parse_or, option_error, parse_option_name},
+};
+
+/// Parse a search pattern.
+///
+/// Supports the following syntax:
+/// - `search(pattern)` - searches the whole tree
+/// - `search(pattern, depth: n, keys: bool, tagged: bool)` - searches with
+///   traversal options; each option is optional and may appear in any order
+pub(crate) fn parse_search(lexer: &mut Lexer<Token>) -> Result<Pattern> {
     match lexer.next() {
         Some(Ok(Token::ParenOpen)) => {
             let pat = parse_or(lexer)?;
-            match lexer.next() {
-                Some(Ok(Token::ParenClose)) => Ok(Pattern::search(pat)),
-                Some(Ok(t)) => {
-                    Err(Error::UnexpectedToken(Box::new(t), lexer.span()))
+            let mut opts = SearchOpts::default();
+            let mut token = lexer.next();
+            if let Some(Ok(Token::Comma)) = token {
+                parse_search_opts(lexer, &mut opts)?;
+                token = lexer.next();
+            }
+            match token {
+                Some(Ok(Token::ParenClose)) => {
+                    Ok(Pattern::search_with(pat, opts))
                 }
+                Some(Ok(t)) => Err(lexer.unexpected(t)),
                 Some(Err(e)) => Err(e),
-                None => Err(Error::ExpectedCloseParen(lexer.span())),
+                None => Err(lexer.error(Error::ExpectedCloseParen)),
             }
         }
-        Some(Ok(t)) => Err(Error::UnexpectedToken(Box::new(t), lexer.span())),
+        Some(Ok(t)) => Err(lexer.unexpected(t)),
         Some(Err(e)) => Err(e),
         None => Err(Error::UnexpectedEndOfInput),
     }
 }
+
+/// Parse a comma-separated list of `name: value` search options, stopping
+/// before the token that follows the last one.
+fn parse_search_opts(
+    lexer: &mut Lexer<Token>,
+    opts: &mut SearchOpts,
+) -> Result<()> {
+    loop {
+        let name = parse_option_name(lexer, |token| {
+            matches!(token, Token::Depth | Token::Keys | Token::Tagged)
+        });
+        match name {
+            Some(Ok(Token::Depth)) => {
+                let depth = match lexer.next() {
+                    Some(Ok(Token::NumberLiteral(_))) => {
+                        lexer.slice().parse::<usize>().ok()
+                    }
+                    _ => None,
+                };
+                let depth = depth.ok_or_else(|| {
+                    option_error(lexer, Error::InvalidNumberFormat)
+                })?;
+                opts.max_depth = Some(depth);
+            }
+            Some(Ok(Token::Keys)) => opts.visit_map_keys = parse_bool(lexer)?,
+            Some(Ok(_)) => opts.visit_tagged_content = parse_bool(lexer)?,
+            Some(Err(e)) => return Err(e),
+            None => {
+                lexer.next();
+                return Err(option_error(lexer, Error::InvalidSearchOption));
+            }
+        }
+
+        let mut lookahead = lexer.clone();
+        match lookahead.next() {
+            Some(Ok(Token::Comma)) => {
+                lexer.next(); // consume the comma
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn parse_bool(lexer: &mut Lexer<Token>) -> Result<bool> {
+    match lexer.next() {
+        Some(Ok(Token::BoolTrue)) => Ok(true),
+        Some(Ok(Token::BoolFalse)) => Ok(false),
+        _ => Err(option_error(lexer, Error::InvalidSearchOption)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_search_opts() {
+        let pattern =
+            Pattern::parse("search(number, depth: 2, keys: false)").unwrap();
+        assert_eq!(
+            pattern,
+            Pattern::search_with(
+                Pattern::any_number(),
+                SearchOpts {
+                    max_depth: Some(2),
+                    visit_map_keys: false,
+                    ..Default::default()
+                }
+            )
+        );
+        assert_eq!(
+            pattern.to_string(),
+            "search(number, depth: 2, keys: false)"
+        );
+
+        let pattern = Pattern::parse("search(text,tagged:false)").unwrap();
+        assert_eq!(pattern.to_string(), "search(text, tagged: false)");
+
+        assert_eq!(
+            Pattern::parse("search(number)").unwrap(),
+            Pattern::search(Pattern::any_number())
+        );
+
+        // Options are tokens, with whitespace and comments between them
+        let pattern = Pattern::parse(
+            "search(number, # options\n depth # levels\n: 2, keys:false)",
+        )
+        .unwrap();
+        assert_eq!(
+            pattern.to_string(),
+            "search(number, depth: 2, keys: false)"
+        );
+    }
+
+    #[test]
+    fn test_parse_search_opts_errors() {
+        assert!(matches!(
+            Pattern::parse("search(number, width: 2)"),
+            Err(Error::InvalidSearchOption(name, span))
+                if name == "width" && span == (15..20)
+        ));
+        assert!(matches!(
+            Pattern::parse("search(number, depth: x)"),
+            Err(Error::InvalidNumberFormat(_, _))
+        ));
+        assert!(matches!(
+            Pattern::parse("search(number, keys: maybe)"),
+            Err(Error::InvalidSearchOption(_, _))
+        ));
+        assert!(matches!(
+            Pattern::parse("search(number, depth 2)"),
+            Err(Error::ExpectedColon(_, _))
+        ));
+        assert!(Pattern::parse("search(number, depth: 2").is_err());
+
+        // Each error points at the token where the option went wrong
+        for (source, error) in [
+            (
+                "search(number, depth 2)",
+                Error::ExpectedColon("2".to_string(), 21..22),
+            ),
+            (
+                "search(number, depth: x)",
+                Error::InvalidNumberFormat("x".to_string(), 22..23),
+            ),
+            (
+                "search(number, # no option\n)",
+                Error::InvalidSearchOption(")".to_string(), 27..28),
+            ),
+            (
+                "search(number, tagged: # a comment\n maybe)",
+                Error::InvalidSearchOption("maybe".to_string(), 36..41),
+            ),
+        ] {
+            assert_eq!(Pattern::parse(source), Err(error), "{}", source);
+        }
+    }
+}
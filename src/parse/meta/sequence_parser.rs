@@ -0,0 +1,37 @@
+use super::{super::Token, parse_or};
+use crate::{Pattern, Result};
+
+/// Parse a comma-separated sequence of patterns, as found between array
+/// brackets and between parentheses. If only one pattern is found, it
+/// returns that pattern directly.
+///
+/// Each element binds more loosely than `|`, so `1, 2 | 3` is `1` followed
+/// by `2 | 3`; parentheses make a sequence an alternative, as in
+/// `(1, 2) | 3`.
+///
+/// Examples:
+/// - `"a", "b"` - matches "a" followed by "b" in sequence
+/// - `1, 2, 3` - matches 1, 2, 3 in exact sequence
+/// - `(*)*, 42, (*)*` - matches 42 anywhere within the array
+pub(crate) fn parse_sequence(
+    lexer: &mut logos::Lexer<Token>,
+) -> Result<Pattern> {
+    let mut patterns = vec![parse_or(lexer)?];
+
+    loop {
+        let mut lookahead = lexer.clone();
+        match lookahead.next() {
+            Some(Ok(Token::Comma)) => {
+                lexer.next(); // consume the comma token (,)
+                patterns.push(parse_or(lexer)?);
+            }
+            _ => break,
+        }
+    }
+
+    if patterns.len() == 1 {
+        Ok(patterns.remove(0))
+    } else {
+        Ok(Pattern::sequence(patterns))
+    }
+}
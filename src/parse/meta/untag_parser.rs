@@ -0,0 +1,25 @@
+use super::{super::Token, parse_or};
+use crate::{Error, Pattern, Result, parse::LexerErrors};
+
+/// Parse an untag pattern of the form `untag(pattern)`.
+///
+/// Examples:
+/// - `untag(42)` - matches `42`, `100(42)`, `100(200(42))`, and so on
+/// - `untag(@date(date))` - captures a date whether or not it is wrapped in
+///   further tags
+pub(crate) fn parse_untag(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    match lexer.next() {
+        Some(Ok(Token::ParenOpen)) => {
+            let pattern = parse_or(lexer)?;
+            match lexer.next() {
+                Some(Ok(Token::ParenClose)) => Ok(Pattern::untag(pattern)),
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
+                Some(Err(e)) => Err(e),
+                None => Err(lexer.error(Error::ExpectedCloseParen)),
+            }
+        }
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(Error::UnexpectedEndOfInput),
+    }
+}
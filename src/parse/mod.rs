@@ -1,32 +1,96 @@
+mod custom_parser;
+mod library;
 pub mod meta;
 mod structure;
 mod token;
 pub mod value;
+mod warning;
 
 // pub use meta::*;
 // pub use structure::*;
+// pub use value::*;
+use std::cell::Cell;
+
+pub(crate) use custom_parser::parse_custom;
+pub use library::PatternLibrary;
+pub(crate) use library::{
+    is_identifier, resolve_reference, scoped_capture_name,
+};
+use logos::Span;
 pub use token::*;
+pub use warning::Warning;
 
-// pub use value::*;
 use crate::{Error, Pattern, Result};
 
+/// The default for [`ParseOpts::max_nesting_depth`].
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 64;
+
+/// Options controlling how [`Pattern::parse_with_opts`] parses a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOpts {
+    /// Whether more than one capture may use the same name. When `false`,
+    /// the default, a repeated name is rejected with
+    /// [`Error::DuplicateCaptureName`].
+    pub allow_duplicate_captures: bool,
+    /// The deepest nesting of groups, captures, arrays, maps, tagged values,
+    /// `!` operators, and keyword patterns such as `search(...)` allowed.
+    /// Deeper patterns are rejected with [`Error::NestingTooDeep`] rather than
+    /// exhausting the stack. Defaults to [`DEFAULT_MAX_NESTING_DEPTH`].
+    pub max_nesting_depth: usize,
+}
+
+impl Default for ParseOpts {
+    fn default() -> Self {
+        Self {
+            allow_duplicate_captures: false,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+        }
+    }
+}
+
 impl Pattern {
     /// Parse a pattern expression from a string.
+    ///
+    /// Each capture name may be used only once; see
+    /// [`Pattern::parse_with_opts`] to allow repeated names.
     pub fn parse(input: &str) -> Result<Self> {
-        let (pattern, consumed) = Self::parse_partial(input)?;
-        if consumed < input.len() {
-            // Find where we stopped to provide accurate error span
-            return Err(Error::ExtraData(consumed..input.len()));
+        Self::parse_with_opts(input, ParseOpts::default())
+    }
+
+    /// Parse a pattern expression from a string using the given options.
+    pub fn parse_with_opts(input: &str, opts: ParseOpts) -> Result<Self> {
+        let (pattern, consumed) = Self::parse_partial_with_opts(input, opts)?;
+        if !is_blank(&input[consumed..]) {
+            return Err(extra_data_error(input, consumed));
         }
         Ok(pattern)
     }
 
+    /// Parse a pattern expression from a string, as [`Pattern::parse`] does,
+    /// along with a [`Warning`] for each part of it that is valid but
+    /// probably a mistake.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use dcbor_pattern::{Pattern, Warning};
+    /// let (pattern, warnings) = Pattern::parse_with_warnings("[*]").unwrap();
+    /// assert_eq!(pattern, Pattern::parse("[*]").unwrap());
+    /// assert_eq!(warnings, [Warning::SingleElementArray(0..3)]);
+    /// ```
+    pub fn parse_with_warnings(input: &str) -> Result<(Self, Vec<Warning>)> {
+        let pattern = Self::parse(input)?;
+        Ok((pattern, warning::warnings(input)))
+    }
+
     /// Parses a pattern from the beginning of a string and returns both
     /// the parsed Pattern and the number of bytes consumed.
     ///
     /// Unlike `parse()`, this function succeeds even if additional
-    /// characters follow the first pattern. The returned index points to the
-    /// first unparsed character after the pattern.
+    /// characters follow the first pattern. The number of bytes consumed is
+    /// the end of the pattern's last token, so it never includes whitespace
+    /// or comments after the pattern, and `&input[consumed..]` starts with
+    /// whatever followed the pattern.
     ///
     /// # Example
     ///
@@ -34,22 +98,221 @@ impl Pattern {
     /// # use dcbor_pattern::Pattern;
     /// let (pattern, consumed) = Pattern::parse_partial("true rest").unwrap();
     /// assert_eq!(pattern, Pattern::bool(true));
-    /// assert_eq!(consumed, 5); // "true ".len() - includes whitespace
+    /// assert_eq!(consumed, 4); // "true".len()
     /// ```
     pub fn parse_partial(input: &str) -> Result<(Self, usize)> {
-        use logos::Logos;
+        Self::parse_partial_with_opts(input, ParseOpts::default())
+    }
 
-        use crate::parse::{Token, meta::parse_or};
+    /// Parses a pattern from the beginning of a string using the given
+    /// options, as [`Pattern::parse_partial`] does.
+    pub fn parse_partial_with_opts(
+        input: &str,
+        opts: ParseOpts,
+    ) -> Result<(Self, usize)> {
+        let (pattern, consumed) =
+            with_nesting_limit(opts.max_nesting_depth, || {
+                parse_partial_unchecked(input)
+            })?;
+        if !opts.allow_duplicate_captures {
+            check_duplicate_captures(&input[..consumed])?;
+        }
+        Ok((pattern, consumed))
+    }
+}
 
-        let mut lexer = Token::lexer(input);
-        let pattern = parse_or(&mut lexer)?;
+/// Parses a whole pattern without the checks that apply to the source as a
+/// whole, for patterns embedded in a larger source.
+pub(crate) fn parse_unchecked(input: &str) -> Result<Pattern> {
+    let (pattern, consumed) = parse_partial_unchecked(input)?;
+    if !is_blank(&input[consumed..]) {
+        return Err(extra_data_error(input, consumed));
+    }
+    Ok(pattern)
+}
 
-        // Calculate consumed bytes - much simpler than current approach!
-        let consumed = match lexer.next() {
-            Some(_) => lexer.span().start,
-            None => input.len(),
-        };
+/// Returns `true` if `source` holds no tokens, only whitespace and comments.
+fn is_blank(source: &str) -> bool {
+    use logos::Logos;
 
-        Ok((pattern, consumed))
+    Token::lexer(source).next().is_none()
+}
+
+/// Returns the error for input left over after a whole pattern ending at
+/// `consumed`.
+///
+/// A comma there means the source is a sequence such as `number, text`,
+/// which only has a meaning between array brackets, so that gets its own
+/// error rather than a bare complaint about the rest of the input.
+fn extra_data_error(input: &str, consumed: usize) -> Error {
+    use logos::Logos;
+
+    let mut lexer = Token::lexer(&input[consumed..]);
+    let token = lexer.next();
+    let span = consumed + lexer.span().start..consumed + lexer.span().end;
+    if let Some(Ok(Token::Comma)) = token {
+        return error_in(Error::SequenceOutsideArray, input, span);
+    }
+    error_in(Error::ExtraData, input, span.start..input.len())
+}
+
+/// Builds the error `kind` for the part of `source` at `span`, carrying the
+/// text there as its lexeme.
+pub(crate) fn error_in(
+    kind: fn(String, Span) -> Error,
+    source: &str,
+    span: Span,
+) -> Error {
+    let lexeme = source.get(span.clone()).unwrap_or_default();
+    kind(lexeme.to_string(), span)
+}
+
+/// Builds errors located by a lexer, for the parsers.
+pub(crate) trait LexerErrors {
+    /// Builds the error `kind` for the lexer's current token.
+    fn error(&self, kind: fn(String, Span) -> Error) -> Error;
+
+    /// Builds the error `kind` for the part of the lexer's source at `span`.
+    fn error_at(&self, kind: fn(String, Span) -> Error, span: Span) -> Error;
+
+    /// Builds [`Error::UnexpectedToken`] for `token`, the lexer's current
+    /// token.
+    fn unexpected(&self, token: Token) -> Error;
+}
+
+impl LexerErrors for logos::Lexer<'_, Token> {
+    fn error(&self, kind: fn(String, Span) -> Error) -> Error {
+        self.error_at(kind, self.span())
+    }
+
+    fn error_at(&self, kind: fn(String, Span) -> Error, span: Span) -> Error {
+        error_in(kind, self.source(), span)
+    }
+
+    fn unexpected(&self, token: Token) -> Error {
+        Error::UnexpectedToken(
+            Box::new(token),
+            self.slice().to_string(),
+            self.span(),
+        )
+    }
+}
+
+fn parse_partial_unchecked(input: &str) -> Result<(Pattern, usize)> {
+    use logos::Logos;
+
+    use crate::parse::meta::parse_or;
+
+    let mut lexer = Token::lexer(input);
+    let pattern = parse_or(&mut lexer).map_err(|e| match e {
+        // The lexer reports unrecognized input without a span; the
+        // offending token is the last one it produced
+        Error::Unknown => lexer.error(Error::UnrecognizedToken),
+        e => e,
+    })?;
+
+    // The pattern ends with the last token it consumed
+    Ok((pattern, lexer.span().end))
+}
+
+/// Rejects the second use of any capture name in `source`, pointing at the
+/// repeated `@name`.
+///
+/// This works on the tokens rather than the parsed pattern so that the error
+/// can carry a span. Every `@name` token in a parsed source starts a capture,
+/// including those inside `tagged(...)` content.
+fn check_duplicate_captures(source: &str) -> Result<()> {
+    use logos::Logos;
+
+    let mut seen = std::collections::HashSet::new();
+    let mut lexer = Token::lexer(source);
+    while let Some(token) = lexer.next() {
+        if let Ok(Token::GroupName(name)) = token
+            && !seen.insert(name.clone())
+        {
+            return Err(Error::DuplicateCaptureName(name, lexer.span()));
+        }
+    }
+    Ok(())
+}
+
+/// Rejects a capture in `operand`, the pattern negated by a `!` or `none(...)`
+/// whose source starts at `start` and ends with the lexer's last token.
+///
+/// A negated pattern only matches where its operand doesn't, so a capture in
+/// it could never capture anything. The error points at the capture's
+/// `@name`, or at the whole operand if the capture comes from a reference to
+/// a library definition or a regex group.
+pub(crate) fn check_negated_captures(
+    lexer: &logos::Lexer<Token>,
+    start: usize,
+    operand: &Pattern,
+) -> Result<()> {
+    use logos::Logos;
+
+    let Some(name) = operand.capture_names().into_iter().next() else {
+        return Ok(());
+    };
+    let end = lexer.span().end;
+    let mut tokens = Token::lexer(&lexer.source()[start..end]);
+    let mut span = start..end;
+    while let Some(token) = tokens.next() {
+        if let Ok(Token::GroupName(group)) = token
+            && scoped_capture_name(group) == name
+        {
+            span = start + tokens.span().start..start + tokens.span().end;
+            break;
+        }
+    }
+    Err(Error::CaptureInsideNegation(name, Some(span)))
+}
+
+thread_local! {
+    /// The nesting depth of the parse in progress, and the deepest allowed.
+    static NESTING: Cell<(usize, usize)> =
+        const { Cell::new((0, DEFAULT_MAX_NESTING_DEPTH)) };
+}
+
+/// Runs the parse `f` with a fresh nesting depth and the given limit,
+/// restoring the state of any enclosing parse afterwards.
+fn with_nesting_limit<R>(limit: usize, f: impl FnOnce() -> R) -> R {
+    let _restore = RestoreNesting(NESTING.replace((0, limit)));
+    f()
+}
+
+/// Restores the nesting state of the enclosing parse when dropped, even on
+/// unwinding.
+struct RestoreNesting((usize, usize));
+
+impl Drop for RestoreNesting {
+    fn drop(&mut self) { NESTING.set(self.0); }
+}
+
+/// Returns how many more levels of nesting the parse in progress allows.
+pub(crate) fn nesting_budget() -> usize {
+    let (depth, limit) = NESTING.get();
+    limit.saturating_sub(depth)
+}
+
+/// One level of nesting in the parse in progress, left when dropped.
+pub(crate) struct NestingGuard;
+
+impl NestingGuard {
+    /// Enters a nested pattern opened by the lexer's current token, failing
+    /// with [`Error::NestingTooDeep`] if that exceeds the parse's limit.
+    pub(crate) fn enter(lexer: &logos::Lexer<Token>) -> Result<Self> {
+        let (depth, limit) = NESTING.get();
+        if depth >= limit {
+            return Err(lexer.error(Error::NestingTooDeep));
+        }
+        NESTING.set((depth + 1, limit));
+        Ok(NestingGuard)
+    }
+}
+
+impl Drop for NestingGuard {
+    fn drop(&mut self) {
+        let (depth, limit) = NESTING.get();
+        NESTING.set((depth - 1, limit));
     }
 }
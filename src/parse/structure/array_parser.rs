@@ -1,4 +1,8 @@
-use crate::{ArrayPattern, Error, Interval, Pattern, Result, parse::Token};
+use crate::{
+    ArrayPattern, Error, Interval, Pattern, Result,
+    parse::{LexerErrors, Token, parse_range},
+    pattern::is_single_element,
+};
 
 /// Parse bracket-style array patterns: [pattern] or [{n}] etc.
 ///
@@ -8,8 +12,17 @@ use crate::{ArrayPattern, Error, Interval, Pattern, Result, parse::Token};
 /// - `[{n}]` - matches array with exactly n elements
 /// - `[{n,m}]` - matches array with n to m elements (inclusive)
 /// - `[{n,}]` - matches array with at least n elements
+/// - `[{n,m} & pattern]` - matches array with n to m elements that match the
+///   given pattern, which may be a sequence
+/// - `[pattern{n}]` - matches array with n elements matching the pattern
 /// - `[pattern, pattern, ...]` - matches array with elements matching the given
 ///   patterns in order
+/// - `[~ pattern, pattern, ...]` - matches array with exactly one element
+///   matching each of the given patterns, in any order
+///
+/// A range is a length only when `]` or `&` follows it; otherwise it is a
+/// map length pattern for an element, so `[{0}, {5: *}]` is a sequence of
+/// two map patterns.
 ///
 /// `[]` is not a valid array pattern and will return an error.
 /// Use `array` keyword for "any array" pattern.
@@ -18,27 +31,47 @@ pub(crate) fn parse_bracket_array(
 ) -> Result<Pattern> {
     // We expect the opening bracket to already be consumed by the caller
 
-    // Peek at the next token to determine what we're parsing
+    // This is a quantifier syntax: [{n}], [{n,m}], etc.
     let mut lookahead = lexer.clone();
-    match lookahead.next() {
-        Some(Ok(Token::Range(res))) => {
-            // This is a quantifier syntax: [{n}], [{n,m}], etc.
-            let quantifier = res?;
-            lexer.next(); // consume the Range token
+    if let Some(res) = parse_range(&mut lookahead, false).filter(|_| {
+        matches!(
+            lookahead.clone().next(),
+            Some(Ok(Token::BracketClose | Token::And))
+        )
+    }) {
+        *lexer = lookahead;
+        let quantifier = res?;
 
-            let pattern = ArrayPattern::with_length_interval(quantifier.into());
+        let mut pattern = ArrayPattern::with_length_interval(quantifier.into());
 
-            // Expect closing bracket
-            match lexer.next() {
-                Some(Ok(Token::BracketClose)) => Ok(Pattern::Structure(
-                    crate::pattern::StructurePattern::Array(pattern),
-                )),
-                Some(Ok(token)) => {
-                    Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-                }
-                Some(Err(e)) => Err(e),
-                None => Err(Error::ExpectedCloseBracket(lexer.span())),
-            }
+        // A length may be followed by `&` and the element pattern
+        let mut lookahead = lexer.clone();
+        if let Some(Ok(Token::And)) = lookahead.next() {
+            lexer.next(); // consume the AND token
+            let element_pattern = parse_array_or(lexer)?;
+            pattern = ArrayPattern::with_length_and_elements(
+                quantifier.into(),
+                element_pattern,
+            );
+        }
+
+        // Expect closing bracket
+        return match lexer.next() {
+            Some(Ok(Token::BracketClose)) => Ok(Pattern::Structure(
+                crate::pattern::StructurePattern::Array(pattern),
+            )),
+            Some(Ok(token)) => Err(lexer.unexpected(token)),
+            Some(Err(e)) => Err(e),
+            None => Err(lexer.error(Error::ExpectedCloseBracket)),
+        };
+    }
+
+    // Peek at the next token to determine what we're parsing
+    let mut lookahead = lexer.clone();
+    match lookahead.next() {
+        Some(Ok(Token::Tilde)) => {
+            lexer.next(); // consume the tilde
+            parse_unordered_array(lexer)
         }
         Some(Ok(Token::BracketClose)) => {
             // This is [] - empty array (no elements)
@@ -59,13 +92,57 @@ pub(crate) fn parse_bracket_array(
                 Some(Ok(Token::BracketClose)) => Ok(Pattern::Structure(
                     crate::pattern::StructurePattern::Array(pattern),
                 )),
-                Some(Ok(token)) => {
-                    Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-                }
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
                 Some(Err(e)) => Err(e),
-                None => Err(Error::ExpectedCloseBracket(lexer.span())),
+                None => Err(lexer.error(Error::ExpectedCloseBracket)),
+            }
+        }
+    }
+}
+
+/// Parse the comma-separated element patterns of an unordered array, after
+/// the `[~`, and the closing bracket.
+///
+/// Each element pattern must match a single element, so a repeat is an
+/// error pointing at it.
+fn parse_unordered_array(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    let mut patterns = Vec::new();
+    let mut lookahead = lexer.clone();
+    if !matches!(lookahead.next(), Some(Ok(Token::BracketClose))) {
+        loop {
+            let mut lookahead = lexer.clone();
+            lookahead.next();
+            let start = lookahead.span().start;
+            let pattern = super::super::meta::parse_or(lexer)?;
+            if !is_single_element(&pattern) {
+                return Err(lexer.error_at(
+                    |lexeme, span| {
+                        Error::RepeatInUnorderedArray(lexeme, Some(span))
+                    },
+                    start..lexer.span().end,
+                ));
             }
+            patterns.push(pattern);
+
+            let mut lookahead = lexer.clone();
+            match lookahead.next() {
+                Some(Ok(Token::Comma)) => {
+                    lexer.next(); // consume the comma token (,)
+                }
+                _ => break,
+            }
+        }
+    }
+
+    match lexer.next() {
+        Some(Ok(Token::BracketClose)) => {
+            Ok(Pattern::Structure(crate::pattern::StructurePattern::Array(
+                ArrayPattern::Unordered(patterns),
+            )))
         }
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(lexer.error(Error::ExpectedCloseBracket)),
     }
 }
 
@@ -73,8 +150,8 @@ pub(crate) fn parse_bracket_array(
 ///
 /// This parser handles the comma operator (,) for array element sequences.
 /// It follows the same precedence hierarchy as the global pattern parser,
-/// but uses commas instead of `>` for sequences:
-/// OR -> AND -> NOT -> ARRAY_SEQUENCE -> PRIMARY
+/// with sequences below negation:
+/// OR -> AND -> NOT -> SEQUENCE -> PRIMARY
 ///
 /// Examples:
 /// - `"a", "b"` - matches "a" followed by "b" in sequence
@@ -136,34 +213,12 @@ pub(crate) fn parse_array_not(
     match lookahead.next() {
         Some(Ok(Token::Not)) => {
             lexer.next(); // consume the NOT token
+            let start = lexer.span().end;
             let pattern = parse_array_not(lexer)?; // right associative recursion
+            crate::parse::check_negated_captures(lexer, start, &pattern)?;
             Ok(Pattern::not_matching(pattern))
         }
-        _ => parse_array_sequence(lexer),
-    }
-}
-
-/// Parse a sequence pattern specifically for array contents using commas.
-pub(crate) fn parse_array_sequence(
-    lexer: &mut logos::Lexer<Token>,
-) -> Result<Pattern> {
-    let mut patterns = vec![super::super::meta::parse_or(lexer)?];
-
-    loop {
-        let mut lookahead = lexer.clone();
-        match lookahead.next() {
-            Some(Ok(Token::Comma)) => {
-                lexer.next(); // consume the comma token (,)
-                patterns.push(super::super::meta::parse_or(lexer)?);
-            }
-            _ => break,
-        }
-    }
-
-    if patterns.len() == 1 {
-        Ok(patterns.remove(0))
-    } else {
-        Ok(Pattern::sequence(patterns))
+        _ => super::super::meta::parse_sequence(lexer),
     }
 }
 
@@ -221,7 +276,7 @@ mod tests {
 
         // Should be an array with elements pattern
         if let Pattern::Structure(crate::pattern::StructurePattern::Array(
-            ArrayPattern::Elements(_),
+            ArrayPattern::Elements(..),
         )) = pattern
         {
             // Test passes
@@ -229,4 +284,27 @@ mod tests {
             panic!("Expected ArrayPattern::WithElements");
         }
     }
+
+    #[test]
+    fn test_parse_bracket_array_length_and_elements() {
+        let pattern = Pattern::parse("[{3,} & (number)*]").unwrap();
+        assert_eq!(
+            pattern,
+            Pattern::Structure(crate::pattern::StructurePattern::Array(
+                ArrayPattern::with_length_and_elements(
+                    Interval::new(3..),
+                    Pattern::parse("(number)*").unwrap()
+                )
+            ))
+        );
+        assert_eq!(pattern.to_string(), "[{3,} & (number)*]");
+
+        // The element pattern runs to the closing bracket
+        let pattern =
+            Pattern::parse("[{2,4} & text, (number)* | null]").unwrap();
+        assert_eq!(pattern.to_string(), "[{2,4} & text, (number)* | null]");
+
+        assert!(Pattern::parse("[{3} &]").is_err());
+        assert!(Pattern::parse("[{3} & number").is_err());
+    }
 }
@@ -1,17 +1,39 @@
 use crate::{
-    Error, MapPattern, Pattern, Result, StructurePattern,
-    parse::{Token, meta::parse_or},
+    Error, Interval, MapConstraint, MapPattern, MetaPattern, Pattern,
+    Reluctance, Result, StructurePattern,
+    parse::{
+        LexerErrors, Token, meta::parse_or, parse_range, parse_range_body,
+        parse_word, skip_ws, starts_range,
+    },
 };
 
 /// Parse a bracket map pattern: { ... }
 ///
 /// Supports the following syntax:
+/// - `{n}`, `{n,m}`, `{n,}`, `{,m}` - matches map with n to m key-value pairs,
+///   the same as `{{n,m}}`; after a pattern, such a range is a quantifier
+///   instead
 /// - `{{0}}` - matches map with exactly 0 key-value pairs (empty map)
 /// - `{{n}}` - matches map with exactly n key-value pairs
 /// - `{{n,m}}` - matches map with n to m key-value pairs
 /// - `{{n,}}` - matches map with at least n key-value pairs
 /// - `{pattern:pattern, pattern:pattern, ...}` - matches map with specified
 ///   key-value constraints
+/// - `{{n,m}, pattern:pattern, ...}` - matches map with n to m key-value pairs
+///   that also satisfies the key-value constraints
+/// - `{pattern}: pattern` - matches map with an entry whose key matches the
+///   first pattern and whose value matches the second, with paths continuing
+///   into the value
+/// - `{(pattern:pattern){n,m}, ...}` - matches map with n to m entries
+///   satisfying the key-value constraint, alongside any other constraints
+/// - `{pattern:pattern, ..., @name(pattern)}` - matches map with specified
+///   key-value constraints whose remaining entries, as a map, match the
+///   captured pattern, which may also follow a length interval or stand alone
+/// - `{keys: pattern}`, `{values: pattern}`, `{keys: pattern, values: pattern}`
+///   - matches map whose every key, every value, or both match the patterns
+///
+/// Keys are parsed as full patterns, so keywords work as keys: `{bool: *}`
+/// matches any boolean key and `{null: *}` the `null` key.
 ///
 /// `{}` is not a valid map pattern and will return an error.
 /// Use `map` keyword for "any map" pattern.
@@ -20,19 +42,32 @@ pub(crate) fn parse_bracket_map(
 ) -> Result<Pattern> {
     // We expect the opening brace to already be consumed by the caller
 
+    // A range in place of the map's contents is a length: {n,m}
+    if starts_range(lexer.remainder()) {
+        let quantifier = parse_range_body(lexer, false)?;
+        return Ok(Pattern::Structure(StructurePattern::Map(
+            MapPattern::with_length_interval(quantifier.interval()),
+        )));
+    }
+
     // We need to look ahead to distinguish between:
-    // 1. {interval} - length constraints (interval {n}, {n,m}, {n,})
-    // 2. {pattern:pattern} - key-value constraints
+    // 1. {{interval}, ...} - length constraints (interval {n}, {n,m}, {n,})
+    // 2. {pattern:pattern} - key-value constraints, where the key may itself be
+    //    a map length, as in {{0}: text}
 
     let mut lookahead = lexer.clone();
-    match lookahead.next() {
-        Some(Ok(Token::Range(quantifier_result))) => {
-            // This is {interval} - map length constraint
-            lexer.next(); // consume the Range token
+    let length = parse_range(&mut lookahead, false);
+    match length {
+        Some(quantifier_result)
+            if !matches!(lookahead.clone().next(), Some(Ok(Token::Colon))) =>
+        {
+            // This is {{interval}, ...} - map length constraint
+            *lexer = lookahead;
 
             let quantifier = quantifier_result?;
 
-            // Expect closing brace for the map
+            // Expect closing brace for the map, or a comma introducing
+            // key-value constraints
             match lexer.next() {
                 Some(Ok(Token::BraceClose)) => {
                     let pattern =
@@ -40,47 +75,141 @@ pub(crate) fn parse_bracket_map(
 
                     Ok(Pattern::Structure(StructurePattern::Map(pattern)))
                 }
-                Some(Ok(token)) => {
-                    Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
+                Some(Ok(Token::Comma)) => {
+                    let (constraints, rest) =
+                        parse_key_value_constraints(lexer, None)?;
+                    let pattern = MapPattern::with_counted_constraints(
+                        quantifier.interval(),
+                        constraints,
+                        rest,
+                    );
+
+                    Ok(Pattern::Structure(StructurePattern::Map(pattern)))
                 }
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
                 Some(Err(e)) => Err(e),
-                None => Err(Error::ExpectedCloseBrace(lexer.span())),
+                None => Err(lexer.error(Error::ExpectedCloseBrace)),
             }
         }
         _ => {
-            // This should be key-value constraints: {pattern:pattern, ...}
-            parse_key_value_constraints(lexer)
+            if let Some(side) = parse_entries_side(lexer) {
+                return parse_all_entries(lexer, side);
+            }
+
+            if starts_counted_constraint(lexer) {
+                let (constraints, rest) =
+                    parse_key_value_constraints(lexer, None)?;
+                return Ok(Pattern::Structure(StructurePattern::Map(
+                    unsized_constraints(constraints, rest),
+                )));
+            }
+
+            let key_pattern = parse_or(lexer)?;
+
+            // A lone key closed by the brace continues into its value:
+            // {pattern}: pattern
+            // unless it is a capture with no colon after the brace, which
+            // binds the whole map as the rest: {@name(pattern)}
+            let mut lookahead = lexer.clone();
+            if let Some(Ok(Token::BraceClose)) = lookahead.next() {
+                lexer.next(); // consume the closing brace
+                if is_capture(&key_pattern)
+                    && !matches!(lookahead.next(), Some(Ok(Token::Colon)))
+                {
+                    return Ok(Pattern::Structure(StructurePattern::Map(
+                        MapPattern::with_rest(
+                            Interval::new(0..),
+                            vec![],
+                            key_pattern,
+                        ),
+                    )));
+                }
+                expect_colon(lexer)?;
+                let value_pattern = parse_or(lexer)?;
+                return Ok(Pattern::map_value(key_pattern, value_pattern));
+            }
+
+            // Otherwise these are key-value constraints: {pattern:pattern, ...}
+            let (constraints, rest) =
+                parse_key_value_constraints(lexer, Some(key_pattern))?;
+            let pattern = unsized_constraints(constraints, rest);
+            Ok(Pattern::Structure(StructurePattern::Map(pattern)))
         }
     }
 }
 
-/// Parse key-value constraints for bracket map patterns.
+/// Returns the map pattern for constraints with no length interval.
+fn unsized_constraints(
+    constraints: Vec<MapConstraint>,
+    rest: Option<Pattern>,
+) -> MapPattern {
+    if rest.is_none() && constraints.iter().all(MapConstraint::is_uncounted) {
+        MapPattern::with_key_value_constraints(
+            constraints
+                .into_iter()
+                .map(MapConstraint::into_key_value)
+                .collect(),
+        )
+    } else {
+        MapPattern::with_counted_constraints(
+            Interval::new(0..),
+            constraints,
+            rest,
+        )
+    }
+}
+
+/// Consume the colon separating a key pattern from its value pattern.
+pub(crate) fn expect_colon(lexer: &mut logos::Lexer<Token>) -> Result<()> {
+    match lexer.next() {
+        Some(Ok(Token::Colon)) => Ok(()),
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(lexer.error(Error::ExpectedColon)),
+    }
+}
+
+/// Parse key-value constraints for bracket map patterns, starting after the
+/// key of the first constraint if it is already parsed, up to and including
+/// the closing brace.
+///
+/// A constraint in parentheses followed by a range, as in
+/// `(text: number){2,}`, is counted. A capture with no colon after it just
+/// before the closing brace is the rest pattern, returned separately.
 fn parse_key_value_constraints(
     lexer: &mut logos::Lexer<Token>,
-) -> Result<Pattern> {
+    first_key: Option<Pattern>,
+) -> Result<(Vec<MapConstraint>, Option<Pattern>)> {
     let mut constraints = Vec::new();
+    let mut next_key = first_key;
 
     loop {
-        // Parse the key pattern
-        let key_pattern = parse_or(lexer)?;
+        // Parse the key pattern, unless this is a counted constraint
+        let key_pattern = match next_key.take() {
+            Some(key_pattern) => Some(key_pattern),
+            None if starts_counted_constraint(lexer) => {
+                constraints.push(parse_counted_constraint(lexer)?);
+                None
+            }
+            None => Some(parse_or(lexer)?),
+        };
 
-        // Expect colon
-        match lexer.next() {
-            Some(Ok(Token::Colon)) => {}
-            Some(Ok(token)) => {
-                return Err(Error::UnexpectedToken(
-                    Box::new(token),
-                    lexer.span(),
-                ));
+        if let Some(key_pattern) = key_pattern {
+            if is_capture(&key_pattern) {
+                let mut lookahead = lexer.clone();
+                if let Some(Ok(Token::BraceClose)) = lookahead.next() {
+                    lexer.next(); // consume the closing brace
+                    return Ok((constraints, Some(key_pattern)));
+                }
             }
-            Some(Err(e)) => return Err(e),
-            None => return Err(Error::ExpectedColon(lexer.span())),
-        }
 
-        // Parse the value pattern
-        let value_pattern = parse_or(lexer)?;
+            expect_colon(lexer)?;
 
-        constraints.push((key_pattern, value_pattern));
+            // Parse the value pattern
+            let value_pattern = parse_or(lexer)?;
+
+            constraints.push(MapPattern::entry(key_pattern, value_pattern));
+        }
 
         // Check what comes next
         match lexer.next() {
@@ -93,21 +222,140 @@ fn parse_key_value_constraints(
                 break;
             }
             Some(Ok(token)) => {
-                return Err(Error::UnexpectedToken(
-                    Box::new(token),
-                    lexer.span(),
-                ));
+                return Err(lexer.unexpected(token));
             }
             Some(Err(e)) => return Err(e),
-            None => return Err(Error::ExpectedCloseBrace(lexer.span())),
+            None => return Err(lexer.error(Error::ExpectedCloseBrace)),
+        }
+    }
+
+    Ok((constraints, None))
+}
+
+/// Returns `true` if the source ahead is a parenthesized key-value
+/// constraint, `(pattern: ...`, rather than a key pattern in parentheses.
+fn starts_counted_constraint(lexer: &logos::Lexer<Token>) -> bool {
+    let mut lookahead = lexer.clone();
+    if !matches!(lookahead.next(), Some(Ok(Token::ParenOpen))) {
+        return false;
+    }
+    parse_or(&mut lookahead).is_ok()
+        && matches!(lookahead.next(), Some(Ok(Token::Colon)))
+}
+
+/// Parse a counted key-value constraint, `(pattern: pattern){n,m}`.
+fn parse_counted_constraint(
+    lexer: &mut logos::Lexer<Token>,
+) -> Result<MapConstraint> {
+    lexer.next(); // consume the opening parenthesis
+    let key_pattern = parse_or(lexer)?;
+    expect_colon(lexer)?;
+    let value_pattern = parse_or(lexer)?;
+    match lexer.next() {
+        Some(Ok(Token::ParenClose)) => {}
+        Some(Ok(token)) => {
+            return Err(lexer.unexpected(token));
+        }
+        Some(Err(e)) => return Err(e),
+        None => return Err(lexer.error(Error::ExpectedCloseParen)),
+    }
+
+    // The count is a range; counting entries has no notion of reluctance
+    let count = match parse_range(lexer, true) {
+        Some(res) => {
+            let quantifier = res?;
+            if quantifier.reluctance() != Reluctance::Greedy {
+                return Err(lexer.error(Error::InvalidRange));
+            }
+            quantifier.interval()
         }
+        None => match lexer.next() {
+            Some(Ok(token)) => {
+                return Err(lexer.unexpected(token));
+            }
+            Some(Err(e)) => return Err(e),
+            None => return Err(Error::UnexpectedEndOfInput),
+        },
+    };
+    Ok(MapPattern::entry(key_pattern, value_pattern).with_count(count))
+}
+
+/// Which side of every entry a `keys:` or `values:` constraint applies to.
+#[derive(PartialEq)]
+enum EntriesSide {
+    Keys,
+    Values,
+}
+
+/// Consumes the word `keys` or `values` if the source ahead is that word
+/// followed by a colon, which introduces a constraint on every entry rather
+/// than a key pattern.
+fn parse_entries_side(lexer: &mut logos::Lexer<Token>) -> Option<EntriesSide> {
+    let src = lexer.remainder();
+    let mut pos = 0;
+    skip_ws(src, &mut pos);
+    let side = match parse_word(src, &mut pos) {
+        "keys" => EntriesSide::Keys,
+        "values" => EntriesSide::Values,
+        _ => return None,
+    };
+    skip_ws(src, &mut pos);
+    if !src[pos..].starts_with(':') {
+        return None;
     }
+    lexer.bump(pos);
+    Some(side)
+}
 
+/// Parse the constraints on every entry of a map, starting after the first
+/// `keys` or `values` word, up to and including the closing brace. `keys`
+/// comes first when both are given.
+fn parse_all_entries(
+    lexer: &mut logos::Lexer<Token>,
+    first: EntriesSide,
+) -> Result<Pattern> {
+    let mut key_pattern = Pattern::any();
+    let mut value_pattern = Pattern::any();
+    let mut side = first;
+    loop {
+        expect_colon(lexer)?;
+        let pattern = parse_or(lexer)?;
+        let is_keys = side == EntriesSide::Keys;
+        if is_keys {
+            key_pattern = pattern;
+        } else {
+            value_pattern = pattern;
+        }
+        match lexer.next() {
+            Some(Ok(Token::BraceClose)) => break,
+            Some(Ok(Token::Comma)) if is_keys => {
+                match parse_entries_side(lexer) {
+                    Some(EntriesSide::Values) => side = EntriesSide::Values,
+                    _ => {
+                        return Err(match lexer.next() {
+                            Some(Ok(token)) => lexer.unexpected(token),
+                            Some(Err(e)) => e,
+                            None => lexer.error(Error::ExpectedCloseBrace),
+                        });
+                    }
+                }
+            }
+            Some(Ok(token)) => {
+                return Err(lexer.unexpected(token));
+            }
+            Some(Err(e)) => return Err(e),
+            None => return Err(lexer.error(Error::ExpectedCloseBrace)),
+        }
+    }
     Ok(Pattern::Structure(StructurePattern::Map(
-        MapPattern::with_key_value_constraints(constraints),
+        MapPattern::with_all_entries(key_pattern, value_pattern),
     )))
 }
 
+fn is_capture(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Meta(MetaPattern::Capture(_)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -165,4 +413,49 @@ mod tests {
             )))
         ));
     }
+
+    #[test]
+    fn test_parse_bracket_map_length_and_constraints() {
+        let pattern = Pattern::parse(r#"{{2,}, "type": text}"#).unwrap();
+        if let Pattern::Structure(StructurePattern::Map(
+            MapPattern::LengthAndConstraints(interval, constraints),
+        )) = &pattern
+        {
+            assert_eq!(*interval, Interval::new(2..));
+            assert_eq!(constraints.len(), 1);
+        } else {
+            panic!("expected LengthAndConstraints, got {:?}", pattern);
+        }
+        assert_eq!(pattern.to_string(), r#"{{2,}, "type": text}"#);
+
+        assert!(Pattern::parse(r#"{{2,}, "type"}"#).is_err());
+        assert!(Pattern::parse("{{2,},}").is_err());
+    }
+
+    #[test]
+    fn test_parse_bracket_map_value_at_key() {
+        let pattern = Pattern::parse(r#"{"a"}: {"b"}: number | text"#).unwrap();
+        assert_eq!(
+            pattern,
+            Pattern::map_value(
+                Pattern::text("a"),
+                Pattern::map_value(
+                    Pattern::text("b"),
+                    Pattern::or(vec![
+                        Pattern::any_number(),
+                        Pattern::any_text()
+                    ]),
+                ),
+            )
+        );
+        assert_eq!(pattern.to_string(), r#"{"a"}: {"b"}: number | text"#);
+
+        // The continuation ends at a comma inside enclosing brackets
+        let pattern = Pattern::parse(r#"[{"a"}: number, text]"#).unwrap();
+        assert_eq!(pattern.to_string(), r#"[{"a"}: number, text]"#);
+
+        assert!(Pattern::parse(r#"{"a"}"#).is_err());
+        assert!(Pattern::parse(r#"{"a"} number"#).is_err());
+        assert!(Pattern::parse(r#"{{1}, "a"}: number"#).is_err());
+    }
 }
@@ -1,13 +1,26 @@
+use std::{collections::BTreeSet, ops::RangeInclusive};
+
 use dcbor::prelude::*;
 
-use crate::{Error, Pattern, Result, TaggedPattern, parse::Token};
+use crate::{
+    Error, Pattern, Result, TaggedPattern,
+    parse::{
+        LexerErrors, Token, error_in, nesting_budget, parse_unchecked, skip_ws,
+    },
+};
 
 /// Parse a tagged pattern.
 ///
 /// Supports the following syntax:
 /// - `tagged` - matches any tagged value
+/// - `tagged(*, pattern)` - matches tagged value with any tag and content
+///   pattern
 /// - `tagged(value, pattern)` - matches tagged value with specific u64 tag and
 ///   content pattern
+/// - `tagged(start...end, pattern)` - matches tagged value with a tag number in
+///   the inclusive range and content pattern
+/// - `tagged(value | value | ..., pattern)` - matches tagged value with any of
+///   the tag numbers and content pattern
 /// - `tagged(name, pattern)` - matches tagged value with named tag and content
 ///   pattern
 /// - `tagged(/regex/, pattern)` - matches tagged value with tag name matching
@@ -30,6 +43,9 @@ pub(crate) fn parse_tagged(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
             match lexer.next() {
                 Some(Ok(Token::ParenClose)) => {
                     let pattern = match tag_pattern {
+                        TagSelector::Any => {
+                            TaggedPattern::any_with_content(content_pattern)
+                        }
                         TagSelector::Value(tag_val) => {
                             let tag = Tag::new(tag_val, "");
                             TaggedPattern::with_tag(tag, content_pattern)
@@ -41,17 +57,24 @@ pub(crate) fn parse_tagged(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
                         TagSelector::Regex(regex) => {
                             TaggedPattern::with_regex(regex, content_pattern)
                         }
+                        TagSelector::Range(range) => {
+                            TaggedPattern::with_tag_range(
+                                range,
+                                content_pattern,
+                            )
+                        }
+                        TagSelector::Set(tags) => {
+                            TaggedPattern::with_tag_set(tags, content_pattern)
+                        }
                     };
 
                     Ok(Pattern::Structure(
                         crate::pattern::StructurePattern::Tagged(pattern),
                     ))
                 }
-                Some(Ok(token)) => {
-                    Err(Error::UnexpectedToken(Box::new(token), lexer.span()))
-                }
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
                 Some(Err(e)) => Err(e),
-                None => Err(Error::ExpectedCloseParen(lexer.span())),
+                None => Err(lexer.error(Error::ExpectedCloseParen)),
             }
         }
         _ => {
@@ -65,9 +88,12 @@ pub(crate) fn parse_tagged(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
 
 #[derive(Debug)]
 enum TagSelector {
+    Any,
     Value(u64),
     Name(String),
     Regex(regex::Regex),
+    Range(RangeInclusive<u64>),
+    Set(BTreeSet<u64>),
 }
 
 fn parse_tagged_inner(
@@ -78,11 +104,22 @@ fn parse_tagged_inner(
     skip_ws(src, &mut pos);
 
     // Parse the tag selector (first parameter)
-    let tag_selector = if src[pos..].starts_with('/') {
+    let tag_selector = if src[pos..].starts_with('*') {
+        // Any tag, written like the `*` pattern rather than as a bare word
+        pos += 1;
+        TagSelector::Any
+    } else if src[pos..].starts_with('/') {
         // Regex pattern
-        let (regex, used) = parse_text_regex(&src[pos..])?;
+        let (regex, used) = parse_text_regex(&src[pos..])
+            .map_err(|e| e.adjust_span(remainder_start + pos))?;
         pos += used;
         TagSelector::Regex(regex)
+    } else if src[pos..].starts_with(|c: char| c.is_ascii_digit()) {
+        // A tag number, range of tag numbers, or set of tag numbers
+        let (selector, used) = parse_tag_numbers(&src[pos..])
+            .map_err(|e| e.adjust_span(remainder_start + pos))?;
+        pos += used;
+        selector
     } else {
         // Could be a number or a name
         let (word, used) = parse_bare_word(&src[pos..])?;
@@ -102,30 +139,16 @@ fn parse_tagged_inner(
     pos += 1;
     skip_ws(src, &mut pos);
 
-    // Parse the content pattern (second parameter)
-    // For now, we'll parse the rest as a simple pattern string
-    // This is a simplified approach - a more robust implementation would
-    // need to handle nested parentheses and complex patterns
+    // Parse the content pattern (second parameter), which runs to the `)`
+    // closing the tagged pattern
     let pattern_start = pos;
-    let mut paren_depth = 0;
-    while pos < src.len() {
-        let ch = src.as_bytes()[pos];
-        if ch == b'(' {
-            paren_depth += 1;
-        } else if ch == b')' {
-            if paren_depth == 0 {
-                break; // This is the closing paren for our tagged()
-            }
-            paren_depth -= 1;
-        }
-        pos += 1;
-    }
+    pos += content_len(&src[pos..]);
 
     let pattern_src = &src[pattern_start..pos];
     let trimmed_pattern = pattern_src.trim();
     let trim_offset = pattern_src.len() - pattern_src.trim_start().len();
 
-    let content_pattern = Pattern::parse(trimmed_pattern).map_err(|e| {
+    let content_pattern = parse_unchecked(trimmed_pattern).map_err(|e| {
         // Adjust error spans to be relative to the original input
         let offset = remainder_start + pattern_start + trim_offset;
         e.adjust_span(offset)
@@ -134,13 +157,89 @@ fn parse_tagged_inner(
     Ok((tag_selector, content_pattern, pos))
 }
 
+/// Parses a tag number, a range `start...end` of tag numbers, or a set
+/// `a | b | ...` of tag numbers, returning the selector and the length of
+/// source it used. A set of one distinct number selects that number.
+fn parse_tag_numbers(src: &str) -> Result<(TagSelector, usize)> {
+    use logos::Logos;
+
+    let mut lexer = Token::lexer(src);
+    let start = parse_tag_number(&mut lexer)?;
+    let mut lookahead = lexer.clone();
+    let selector = match lookahead.next() {
+        Some(Ok(Token::Ellipsis)) => {
+            lexer.next();
+            let end = parse_tag_number(&mut lexer)?;
+            if start > end {
+                return Err(lexer.error(Error::InvalidRange));
+            }
+            TagSelector::Range(start..=end)
+        }
+        Some(Ok(Token::Or)) => {
+            let mut tags = BTreeSet::from([start]);
+            while let Some(Ok(Token::Or)) = lexer.clone().next() {
+                lexer.next();
+                tags.insert(parse_tag_number(&mut lexer)?);
+            }
+            if tags.len() == 1 {
+                TagSelector::Value(start)
+            } else {
+                TagSelector::Set(tags)
+            }
+        }
+        _ => TagSelector::Value(start),
+    };
+    Ok((selector, lexer.span().end))
+}
+
+/// Parses a tag number, which must be a non-negative integer written in
+/// decimal.
+fn parse_tag_number(lexer: &mut logos::Lexer<Token>) -> Result<u64> {
+    match lexer.next() {
+        Some(Ok(Token::NumberLiteral(_))) => lexer
+            .slice()
+            .parse::<u64>()
+            .map_err(|_| lexer.error(Error::InvalidNumberFormat)),
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(Error::UnexpectedEndOfInput),
+    }
+}
+
+/// Returns the length of `src` up to the unmatched `)` that ends it.
+///
+/// The source is scanned by token, so parentheses inside nested patterns,
+/// quoted literals, regexes, and comments are accounted for.
+///
+/// Once the parentheses nest more deeply than the parse allows, the content
+/// cannot parse, so the scan stops there rather than running on through
+/// arbitrarily long input; parsing the content then reports the error.
+fn content_len(src: &str) -> usize {
+    use logos::Logos;
+
+    let budget = nesting_budget();
+    let mut lexer = Token::lexer(src);
+    let mut depth = 0usize;
+    while let Some(token) = lexer.next() {
+        match token {
+            Ok(Token::ParenOpen) if depth >= budget => return src.len(),
+            Ok(Token::ParenOpen) => depth += 1,
+            Ok(Token::ParenClose) if depth == 0 => return lexer.span().start,
+            Ok(Token::ParenClose) => depth -= 1,
+            _ => {}
+        }
+    }
+    src.len()
+}
+
 /// Parse a text regex from the input string starting with /
 fn parse_text_regex(src: &str) -> Result<(regex::Regex, usize)> {
     let mut pos = 0;
     skip_ws(src, &mut pos);
     if pos >= src.len() || src.as_bytes()[pos] != b'/' {
-        return Err(Error::UnterminatedRegex(pos..pos));
+        return Err(error_in(Error::UnterminatedRegex, src, pos..src.len()));
     }
+    let open = pos;
     pos += 1;
     let start = pos;
     let mut escape = false;
@@ -158,12 +257,12 @@ fn parse_text_regex(src: &str) -> Result<(regex::Regex, usize)> {
         if b == b'/' {
             let inner = &src[start..pos - 1];
             let regex = regex::Regex::new(inner)
-                .map_err(|_| Error::InvalidRegex(pos..pos))?;
+                .map_err(|_| error_in(Error::InvalidRegex, src, open..pos))?;
             skip_ws(src, &mut pos);
             return Ok((regex, pos));
         }
     }
-    Err(Error::UnterminatedRegex(pos..pos))
+    Err(error_in(Error::UnterminatedRegex, src, open..src.len()))
 }
 
 /// Parse a bare word (alphanumeric with hyphens and underscores)
@@ -173,7 +272,7 @@ fn parse_bare_word(src: &str) -> Result<(String, usize)> {
     let start = pos;
     while pos < src.len() {
         let ch = src[pos..].chars().next().unwrap();
-        if matches!(ch, ' ' | '\t' | '\n' | '\r' | '\u{0c}' | ',' | ')') {
+        if matches!(ch, ' ' | '\t' | '\n' | '\r' | '\u{0c}' | ',' | ')' | '|') {
             break;
         }
         pos += ch.len_utf8();
@@ -186,17 +285,6 @@ fn parse_bare_word(src: &str) -> Result<(String, usize)> {
     Ok((word, pos))
 }
 
-/// Skip whitespace characters.
-fn skip_ws(src: &str, pos: &mut usize) {
-    while let Some(ch) = src[*pos..].chars().next() {
-        if matches!(ch, ' ' | '\t' | '\n' | '\r' | '\u{0c}') {
-            *pos += ch.len_utf8();
-        } else {
-            break;
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -274,4 +362,52 @@ mod tests {
             _ => panic!("Expected TaggedPattern with tag value 0"),
         }
     }
+
+    #[test]
+    fn test_parse_tagged_range_and_set() {
+        let pattern = Pattern::parse("tagged( 100 ... 199 , text)").unwrap();
+        assert_eq!(
+            pattern,
+            Pattern::tagged_range(100..=199, Pattern::any_text())
+        );
+        assert_eq!(pattern.to_string(), "tagged(100...199, text)");
+
+        let pattern = Pattern::parse("tagged(200|1 | 100, *)").unwrap();
+        assert_eq!(pattern, Pattern::tagged_set([1, 100, 200], Pattern::any()));
+        assert_eq!(pattern.to_string(), "tagged(1 | 100 | 200, *)");
+    }
+
+    #[test]
+    fn test_parse_tagged_any_tag() {
+        let pattern = Pattern::parse("tagged(*, [(*)*])").unwrap();
+        match &pattern {
+            Pattern::Structure(crate::pattern::StructurePattern::Tagged(
+                TaggedPattern::AnyTag { .. },
+            )) => {}
+            _ => panic!("Expected TaggedPattern with any tag"),
+        }
+        assert_eq!(pattern.to_string(), "tagged(*, [(*)*])");
+
+        // The `*` stands alone, so it isn't read as a name or a repeat
+        assert!(Pattern::parse("tagged(*)").is_err());
+        assert!(Pattern::parse("tagged(*?, text)").is_err());
+        assert!(Pattern::parse("tagged(**, text)").is_err());
+    }
+
+    #[test]
+    fn test_parse_tagged_range_and_set_errors() {
+        assert_eq!(
+            Pattern::parse("tagged(199...100, *)").unwrap_err(),
+            Error::InvalidRange("100".to_string(), 13..16)
+        );
+        assert_eq!(
+            Pattern::parse("tagged(1 | 2.5, *)").unwrap_err(),
+            Error::InvalidNumberFormat("2.5".to_string(), 11..14)
+        );
+        assert!(matches!(
+            Pattern::parse("tagged(1 | text, *)").unwrap_err(),
+            Error::UnexpectedToken(_, lexeme, span)
+                if lexeme == "text" && span == (11..15)
+        ));
+    }
 }
@@ -1,14 +1,17 @@
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item_partial;
-use logos::{Lexer, Logos};
+use logos::{Lexer, Logos, Span};
 
-use crate::{DigestPattern, Error, Quantifier, Reluctance, Result};
+use crate::{
+    DigestPattern, Error, Quantifier, Reluctance, Result, parse::LexerErrors,
+};
 
 /// Tokens for the Gordian Envelope pattern syntax.
 #[derive(Debug, Clone, Logos, PartialEq)]
 #[rustfmt::skip]
 #[logos(error = Error)]
 #[logos(skip r"[ \t\r\n\f]+")]
+#[logos(skip r"#[^\n]*")]
 pub enum Token {
     #[token("&")]
     And,
@@ -48,54 +51,128 @@ pub enum Token {
 
     // Structure Pattern Keywords
     #[token("tagged")]
+    #[token("TAGGED")]
     Tagged,
 
     #[token("array")]
+    #[token("ARRAY")]
     Array,
 
     #[token("map")]
+    #[token("MAP")]
     Map,
 
     // Value Pattern Keywords
     #[token("bool")]
+    #[token("BOOL")]
     Bool,
 
     #[token("bstr")]
+    #[token("BSTR")]
     ByteString,
 
     #[token("date")]
+    #[token("DATE")]
     Date,
 
     #[token("date'", parse_date_quoted)]
+    #[token("DATE'", parse_date_quoted)]
     DateQuoted(Result<crate::pattern::DatePattern>),
 
+    #[token("date-any")]
+    #[token("DATE-ANY")]
+    DateAny,
+
+    #[token("date-any'", parse_date_quoted)]
+    #[token("DATE-ANY'", parse_date_quoted)]
+    DateAnyQuoted(Result<crate::pattern::DatePattern>),
+
     #[token("known")]
+    #[token("KNOWN")]
     Known,
 
     #[token("null")]
+    #[token("NULL")]
     Null,
 
     #[token("number")]
+    #[token("NUMBER")]
     Number,
 
     #[token("text")]
+    #[token("TEXT")]
     Text,
 
     #[token("digest")]
+    #[token("DIGEST")]
     Digest,
 
     #[token("digest'", parse_digest_quoted)]
+    #[token("DIGEST'", parse_digest_quoted)]
     DigestQuoted(Result<DigestPattern>),
 
+    #[token("encoded-digest'", parse_digest_quoted)]
+    #[token("ENCODED-DIGEST'", parse_digest_quoted)]
+    EncodedDigestQuoted(Result<DigestPattern>),
+
+    #[token("cbor'", parse_cbor_quoted)]
+    #[token("CBOR'", parse_cbor_quoted)]
+    CborQuoted(Result<CBOR>),
+
     // Meta Pattern Keywords
     #[token("search")]
+    #[token("SEARCH")]
     Search,
 
+    #[token("child")]
+    #[token("CHILD")]
+    Child,
+
+    #[token("untag")]
+    #[token("UNTAG")]
+    Untag,
+
+    #[token("at")]
+    #[token("AT")]
+    At,
+
+    #[token("custom")]
+    #[token("CUSTOM")]
+    Custom,
+
+    #[token("entry")]
+    #[token("ENTRY")]
+    Entry,
+
+    #[token("none")]
+    #[token("NONE")]
+    None,
+
+    // Option names, followed by `:` inside the parentheses of `search(...)`
+    // and `bstr(...)`
+    #[token("depth")]
+    #[token("DEPTH")]
+    Depth,
+
+    #[token("keys")]
+    #[token("KEYS")]
+    Keys,
+
+    #[token("prefix")]
+    #[token("PREFIX")]
+    Prefix,
+
+    #[token("cbor")]
+    #[token("CBOR")]
+    Cbor,
+
     // Special literals
     #[token("true")]
+    #[token("TRUE")]
     BoolTrue,
 
     #[token("false")]
+    #[token("FALSE")]
     BoolFalse,
 
     #[token("NaN")]
@@ -120,7 +197,7 @@ pub enum Token {
     #[token("]")]
     BracketClose,
 
-    #[token("{", parse_brace_open)]
+    #[token("{")]
     BraceOpen,
 
     #[token("}")]
@@ -135,6 +212,15 @@ pub enum Token {
     #[token("...")]
     Ellipsis,
 
+    #[token("^")]
+    Caret,
+
+    #[token("$")]
+    Dollar,
+
+    #[token("~")]
+    Tilde,
+
     #[token(">=")]
     GreaterThanOrEqual,
 
@@ -156,6 +242,16 @@ pub enum Token {
     )]
     GroupName(String),
 
+    #[regex(r"=[a-zA-Z_][a-zA-Z0-9_]*", |lex|
+        lex.slice()[1..].to_string()
+    )]
+    Backref(String),
+
+    #[regex(r"\$[a-zA-Z_][a-zA-Z0-9_]*", |lex|
+        lex.slice()[1..].to_string()
+    )]
+    Reference(String),
+
     #[token("\"", parse_string)]
     StringLiteral(Result<String>),
 
@@ -170,8 +266,6 @@ pub enum Token {
 
     #[token("h'/", parse_hex_regex)]
     HexRegex(Result<String>),
-
-    Range(Result<Quantifier>),
 }
 
 /// Callback to parse numbers using dcbor-parse for consistency with dCBOR
@@ -180,12 +274,18 @@ fn parse_number(lex: &mut Lexer<Token>) -> Result<f64> {
     match parse_dcbor_item_partial(number_str) {
         Ok((cbor, _)) => match f64::try_from_cbor(&cbor) {
             Ok(value) => Ok(value),
-            Err(_) => Err(Error::InvalidNumberFormat(lex.span())),
+            Err(_) => Err(lex.error(Error::InvalidNumberFormat)),
         },
-        Err(_) => Err(Error::InvalidNumberFormat(lex.span())),
+        Err(_) => Err(lex.error(Error::InvalidNumberFormat)),
     }
 }
 
+/// Returns the span from the start of the current token to the end of the
+/// input, used for literals that are never closed.
+fn span_to_end(lex: &Lexer<Token>) -> Span {
+    lex.span().start..lex.source().len()
+}
+
 /// Callback used by the `Regex` variant above.
 fn parse_regex(lex: &mut Lexer<Token>) -> Result<String> {
     let src = lex.remainder(); // everything after the first '/'
@@ -200,7 +300,7 @@ fn parse_regex(lex: &mut Lexer<Token>) -> Result<String> {
                 let content = src[..i].to_owned();
                 match regex::Regex::new(&content) {
                     Ok(_) => return Ok(content),
-                    Err(_) => return Err(Error::InvalidRegex(lex.span())),
+                    Err(_) => return Err(lex.error(Error::InvalidRegex)),
                 }
             }
             _ => escape = false, // any other char ends an escape
@@ -208,7 +308,7 @@ fn parse_regex(lex: &mut Lexer<Token>) -> Result<String> {
     }
 
     // Unterminated literal – treat as lexing error
-    Err(Error::UnterminatedRegex(lex.span()))
+    Err(lex.error_at(Error::UnterminatedRegex, span_to_end(lex)))
 }
 
 /// Callback used by the `StringLiteral` variant above.
@@ -248,7 +348,7 @@ fn parse_string(lex: &mut Lexer<Token>) -> Result<String> {
     }
 
     // Unterminated literal – treat as lexing error
-    Err(Error::UnterminatedString(lex.span()))
+    Err(lex.error_at(Error::UnterminatedString, span_to_end(lex)))
 }
 
 /// Callback used by the `HexString` variant above.
@@ -261,26 +361,26 @@ fn parse_hex_string(lex: &mut Lexer<Token>) -> Result<Vec<u8>> {
             '\'' => {
                 // Found the closing delimiter
                 let hex_content = &src[..i];
+                lex.bump(i + 1); // +1 to also eat the '\''
                 match hex::decode(hex_content) {
-                    Ok(bytes) => {
-                        lex.bump(i + 1); // +1 to also eat the '\''
-                        return Ok(bytes);
-                    }
-                    Err(_) => return Err(Error::InvalidHexString(lex.span())),
+                    Ok(bytes) => return Ok(bytes),
+                    Err(_) => return Err(lex.error(Error::InvalidHexString)),
                 }
             }
             c if c.is_ascii_hexdigit() => {
                 // Valid hex character, continue
             }
-            _ => {
-                // Invalid character in hex string
-                return Err(Error::InvalidHexString(lex.span()));
+            c => {
+                // Invalid character in hex string; point at the character
+                let at = lex.span().end + i;
+                return Err(lex
+                    .error_at(Error::InvalidHexString, at..at + c.len_utf8()));
             }
         }
     }
 
     // Unterminated literal – treat as lexing error
-    Err(Error::UnterminatedHexString(lex.span()))
+    Err(lex.error_at(Error::UnterminatedHexString, span_to_end(lex)))
 }
 
 /// Callback used by the `HexRegex` variant above.
@@ -300,7 +400,7 @@ fn parse_hex_regex(lex: &mut Lexer<Token>) -> Result<String> {
                     let content = src[..i].to_owned();
                     match regex::bytes::Regex::new(&content) {
                         Ok(_) => return Ok(content),
-                        Err(_) => return Err(Error::InvalidRegex(lex.span())),
+                        Err(_) => return Err(lex.error(Error::InvalidRegex)),
                     }
                 }
                 // Not the end, continue
@@ -311,10 +411,11 @@ fn parse_hex_regex(lex: &mut Lexer<Token>) -> Result<String> {
     }
 
     // Unterminated literal – treat as lexing error
-    Err(Error::UnterminatedRegex(lex.span()))
+    Err(lex.error_at(Error::UnterminatedRegex, span_to_end(lex)))
 }
 
-/// Callback used by the `DigestQuoted` variant above.
+/// Callback used by the `DigestQuoted` and `EncodedDigestQuoted` variants
+/// above.
 fn parse_digest_quoted(lex: &mut Lexer<Token>) -> Result<DigestPattern> {
     use bc_components::Digest;
     use bc_ur::URDecodable;
@@ -337,6 +438,7 @@ fn parse_digest_quoted(lex: &mut Lexer<Token>) -> Result<DigestPattern> {
 
             // Check if it's a UR string
             if content.starts_with("ur:") {
+                crate::pattern::register_digest_tags();
                 match Digest::from_ur_string(content) {
                     Ok(digest) => return Ok(DigestPattern::digest(digest)),
                     Err(_) => {
@@ -356,7 +458,7 @@ fn parse_digest_quoted(lex: &mut Lexer<Token>) -> Result<DigestPattern> {
                 let regex_content = &content[1..content.len() - 1];
                 match regex::bytes::Regex::new(regex_content) {
                     Ok(regex) => return Ok(DigestPattern::binary_regex(regex)),
-                    Err(_) => return Err(Error::InvalidRegex(lex.span())),
+                    Err(_) => return Err(lex.error(Error::InvalidRegex)),
                 }
             }
 
@@ -368,17 +470,15 @@ fn parse_digest_quoted(lex: &mut Lexer<Token>) -> Result<DigestPattern> {
                             if bytes.len() <= Digest::DIGEST_SIZE {
                                 return Ok(DigestPattern::prefix(bytes));
                             } else {
-                                return Err(Error::InvalidHexString(
-                                    lex.span(),
-                                ));
+                                return Err(lex.error(Error::InvalidHexString));
                             }
                         }
                         Err(_) => {
-                            return Err(Error::InvalidHexString(lex.span()));
+                            return Err(lex.error(Error::InvalidHexString));
                         }
                     }
                 } else {
-                    return Err(Error::InvalidHexString(lex.span()));
+                    return Err(lex.error(Error::InvalidHexString));
                 }
             }
 
@@ -391,10 +491,10 @@ fn parse_digest_quoted(lex: &mut Lexer<Token>) -> Result<DigestPattern> {
     }
 
     // Unterminated literal
-    Err(Error::UnterminatedDigestQuoted(lex.span()))
+    Err(lex.error_at(Error::UnterminatedDigestQuoted, span_to_end(lex)))
 }
 
-/// Callback used by the `DateQuoted` variant above.
+/// Callback used by the `DateQuoted` and `DateAnyQuoted` variants above.
 fn parse_date_quoted(
     lex: &mut Lexer<Token>,
 ) -> Result<crate::pattern::DatePattern> {
@@ -410,7 +510,7 @@ fn parse_date_quoted(
 
             // Check for empty content
             if content.is_empty() {
-                return Err(Error::InvalidDateFormat(lex.span()));
+                return Err(lex.error(Error::InvalidDateFormat));
             }
 
             // Check if it's a regex pattern /.../
@@ -423,7 +523,28 @@ fn parse_date_quoted(
                     Ok(regex) => {
                         return Ok(crate::pattern::DatePattern::regex(regex));
                     }
-                    Err(_) => return Err(Error::InvalidRegex(lex.span())),
+                    Err(_) => return Err(lex.error(Error::InvalidRegex)),
+                }
+            }
+
+            // Check for relative patterns: "past", "future", optionally
+            // followed by a duration such as "30d"
+            let relative = [
+                ("past", crate::pattern::DatePattern::Past as fn(_) -> _),
+                ("future", crate::pattern::DatePattern::Future),
+            ];
+            for (keyword, variant) in relative {
+                if let Some(rest) = content.strip_prefix(keyword) {
+                    if rest.is_empty() {
+                        return Ok(variant(None));
+                    }
+                    if let Some(duration) = rest
+                        .strip_prefix(' ')
+                        .and_then(crate::pattern::parse_duration)
+                    {
+                        return Ok(variant(Some(duration)));
+                    }
+                    return Err(lex.error(Error::InvalidDateFormat));
                 }
             }
 
@@ -439,13 +560,11 @@ fn parse_date_quoted(
                                 );
                             }
                             Err(_) => {
-                                return Err(Error::InvalidDateFormat(
-                                    lex.span(),
-                                ));
+                                return Err(lex.error(Error::InvalidDateFormat));
                             }
                         },
                         Err(_) => {
-                            return Err(Error::InvalidDateFormat(lex.span()));
+                            return Err(lex.error(Error::InvalidDateFormat));
                         }
                     }
                 } else if let Some(iso_str) = content.strip_suffix("...") {
@@ -458,13 +577,11 @@ fn parse_date_quoted(
                                 );
                             }
                             Err(_) => {
-                                return Err(Error::InvalidDateFormat(
-                                    lex.span(),
-                                ));
+                                return Err(lex.error(Error::InvalidDateFormat));
                             }
                         },
                         Err(_) => {
-                            return Err(Error::InvalidDateFormat(lex.span()));
+                            return Err(lex.error(Error::InvalidDateFormat));
                         }
                     }
                 } else {
@@ -475,37 +592,33 @@ fn parse_date_quoted(
                             Ok(cbor) => match Date::try_from(cbor) {
                                 Ok(date) => date,
                                 Err(_) => {
-                                    return Err(Error::InvalidDateFormat(
-                                        lex.span(),
-                                    ));
+                                    return Err(
+                                        lex.error(Error::InvalidDateFormat)
+                                    );
                                 }
                             },
                             Err(_) => {
-                                return Err(Error::InvalidDateFormat(
-                                    lex.span(),
-                                ));
+                                return Err(lex.error(Error::InvalidDateFormat));
                             }
                         };
                         let end_date = match parse_dcbor_item(parts[1]) {
                             Ok(cbor) => match Date::try_from(cbor) {
                                 Ok(date) => date,
                                 Err(_) => {
-                                    return Err(Error::InvalidDateFormat(
-                                        lex.span(),
-                                    ));
+                                    return Err(
+                                        lex.error(Error::InvalidDateFormat)
+                                    );
                                 }
                             },
                             Err(_) => {
-                                return Err(Error::InvalidDateFormat(
-                                    lex.span(),
-                                ));
+                                return Err(lex.error(Error::InvalidDateFormat));
                             }
                         };
                         return Ok(crate::pattern::DatePattern::range(
                             start_date..=end_date,
                         ));
                     } else {
-                        return Err(Error::InvalidDateFormat(lex.span()));
+                        return Err(lex.error(Error::InvalidDateFormat));
                     }
                 }
             }
@@ -516,45 +629,143 @@ fn parse_date_quoted(
                     Ok(date) => {
                         return Ok(crate::pattern::DatePattern::value(date));
                     }
-                    Err(_) => return Err(Error::InvalidDateFormat(lex.span())),
+                    Err(_) => return Err(lex.error(Error::InvalidDateFormat)),
                 },
-                Err(_) => return Err(Error::InvalidDateFormat(lex.span())),
+                Err(_) => return Err(lex.error(Error::InvalidDateFormat)),
             }
         }
     }
 
     // Unterminated literal
-    Err(Error::UnterminatedDateQuoted(lex.span()))
+    Err(lex.error_at(Error::UnterminatedDateQuoted, span_to_end(lex)))
 }
 
-/// Callback to handle `{` token - determines if it's a Range or BraceOpen
-fn parse_brace_open(lex: &mut Lexer<Token>) -> Token {
-    let remainder = lex.remainder();
+/// Callback used by the `CborQuoted` variant above.
+fn parse_cbor_quoted(lex: &mut Lexer<Token>) -> Result<CBOR> {
+    use dcbor_parse::parse_dcbor_item;
 
-    // Skip whitespace and see if we have a digit pattern
-    let chars = remainder.chars();
-    let mut pos = 0;
+    let src = lex.remainder(); // everything after "cbor'"
+    let mut escape = false;
+    let mut content = String::new();
 
-    // Skip whitespace
-    for ch in chars {
-        if !matches!(ch, ' ' | '\t' | '\n' | '\r' | '\u{0c}') {
-            // If the first non-whitespace character is a digit, we need to look
-            // ahead further to determine if this is really a range
-            // pattern or a map key-value constraint
-            if ch.is_ascii_digit() {
-                // Look ahead to see if this looks like a range pattern
-                if looks_like_range_pattern(&remainder[pos..]) {
-                    let quantifier_result = parse_range_from_remainder(lex);
-                    return Token::Range(quantifier_result);
+    for (i, ch) in src.char_indices() {
+        match (ch, escape) {
+            ('\\', false) => escape = true, // start of an escape
+            ('\'', false) => {
+                // Found the closing delimiter
+                lex.bump(i + 1); // +1 to also eat the '\''
+                return parse_dcbor_item(&content).map_err(|e| {
+                    Error::InvalidCbor(e.to_string(), lex.span())
+                });
+            }
+            (c, true) => {
+                // Only quotes and backslashes are escaped; any other escape
+                // belongs to the diagnostic notation itself
+                if !matches!(c, '\'' | '\\') {
+                    content.push('\\');
                 }
+                content.push(c);
+                escape = false;
             }
-            // Otherwise, it's just a regular BraceOpen
+            (c, false) => content.push(c),
+        }
+    }
+
+    // Unterminated literal
+    Err(lex.error_at(Error::UnterminatedCborQuoted, span_to_end(lex)))
+}
+
+/// Skips the whitespace and `#` comments at `pos` in `src`, as the lexer does
+/// between tokens. For parsers that scan the source directly.
+pub(crate) fn skip_ws(src: &str, pos: &mut usize) {
+    while let Some(ch) = src[*pos..].chars().next() {
+        if matches!(ch, ' ' | '\t' | '\n' | '\r' | '\u{0c}') {
+            *pos += ch.len_utf8();
+        } else if ch == '#' {
+            *pos = src[*pos..].find('\n').map_or(src.len(), |i| *pos + i);
+        } else {
             break;
         }
-        pos += ch.len_utf8();
     }
+}
+
+/// Consumes the `name:` that starts a keyword option inside parentheses,
+/// such as `depth:` in `search(number, depth: 2)` or `prefix:` in
+/// `bstr(prefix: h'01')`, returning the name's token if `is_option` accepts
+/// the next token. Consumes nothing and returns `None` if it doesn't.
+pub(crate) fn parse_option_name(
+    lexer: &mut Lexer<Token>,
+    is_option: impl Fn(&Token) -> bool,
+) -> Option<Result<Token>> {
+    let mut lookahead = lexer.clone();
+    let name = match lookahead.next() {
+        Some(Ok(token)) if is_option(&token) => token,
+        _ => return None,
+    };
+    *lexer = lookahead;
+    Some(match lexer.next() {
+        Some(Ok(Token::Colon)) => Ok(name),
+        Some(_) => Err(option_error(lexer, Error::ExpectedColon)),
+        None => Err(Error::UnexpectedEndOfInput),
+    })
+}
 
-    Token::BraceOpen
+/// Builds the error `kind` for the lexer's current token in a keyword
+/// option. A word the lexer has no token for fails partway through, so the
+/// error then spans the whole word, such as `width` rather than its `w`.
+pub(crate) fn option_error(
+    lexer: &Lexer<Token>,
+    kind: fn(String, Span) -> Error,
+) -> Error {
+    let span = lexer.span();
+    let mut end = span.start;
+    parse_word(lexer.source(), &mut end);
+    lexer.error_at(kind, span.start..span.end.max(end))
+}
+
+/// Parses the word of ASCII alphanumerics and underscores at `pos` in `src`,
+/// which may be empty. For parsers that scan the source directly.
+pub(crate) fn parse_word<'a>(src: &'a str, pos: &mut usize) -> &'a str {
+    let start = *pos;
+    while let Some(ch) = src[*pos..].chars().next() {
+        if ch.is_ascii_alphanumeric() || ch == '_' {
+            *pos += ch.len_utf8();
+        } else {
+            break;
+        }
+    }
+    &src[start..*pos]
+}
+
+/// Consumes a range such as `{2}`, `{2,5}`, `{2,}` or `{,5}` if one comes
+/// next, returning `None` and consuming nothing otherwise.
+///
+/// The lexer produces a plain `{` for every brace, since only the parser
+/// knows whether one there opens a range or a map: `(*){5}` repeats a group,
+/// while `{5}` on its own is a map with five entries. A `?` or `+` after the
+/// range sets its reluctance only if `reluctance` is true, and is otherwise
+/// left for the parser.
+pub(crate) fn parse_range(
+    lexer: &mut Lexer<Token>,
+    reluctance: bool,
+) -> Option<Result<Quantifier>> {
+    let mut lookahead = lexer.clone();
+    if !matches!(lookahead.next(), Some(Ok(Token::BraceOpen)))
+        || !starts_range(lookahead.remainder())
+    {
+        return None;
+    }
+    *lexer = lookahead;
+    Some(parse_range_body(lexer, reluctance))
+}
+
+/// Returns true if `src`, the source after a `{`, is the body of a range
+/// rather than of a map pattern.
+pub(crate) fn starts_range(src: &str) -> bool {
+    let mut pos = 0;
+    skip_ws(src, &mut pos);
+    // A map can't start with a comma, so this is an `{,m}` range
+    src[pos..].starts_with(',') || looks_like_range_pattern(&src[pos..])
 }
 
 /// Helper function to determine if the content after `{` looks like a range
@@ -610,49 +821,48 @@ fn looks_like_range_pattern(content: &str) -> bool {
     false
 }
 
-/// Helper function to parse a range pattern from the current position
-fn parse_range_from_remainder(lex: &mut Lexer<Token>) -> Result<Quantifier> {
+/// Parses the body of a range after its `{`, which
+/// [`starts_range`] has found to be one, extending the span of the `{` token
+/// to cover it.
+pub(crate) fn parse_range_body(
+    lex: &mut Lexer<Token>,
+    reluctance: bool,
+) -> Result<Quantifier> {
     let remainder = lex.remainder(); // everything after the '{'
 
-    // Helper to skip whitespace inside the range specification
-    fn skip_ws(s: &str, pos: &mut usize) {
-        while let Some(ch) = s[*pos..].chars().next() {
-            if matches!(ch, ' ' | '\t' | '\n' | '\r' | '\u{0c}') {
-                *pos += ch.len_utf8();
-            } else {
-                break;
-            }
-        }
-    }
-
     let mut pos = 0;
 
     // Skip initial whitespace
     skip_ws(remainder, &mut pos);
 
-    // Parse the first number
-    if !remainder[pos..]
-        .chars()
-        .next()
-        .is_some_and(|c| c.is_ascii_digit())
-    {
-        return Err(Error::InvalidRange(lex.span()));
-    }
-
-    let start = pos;
-    while let Some(ch) = remainder[pos..].chars().next() {
-        if ch.is_ascii_digit() {
-            pos += ch.len_utf8();
-        } else {
-            break;
+    // Parse the first number, which may be omitted in `{,m}`
+    let has_min = !remainder[pos..].starts_with(',');
+    let min: usize = if has_min {
+        if !remainder[pos..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_digit())
+        {
+            return Err(lex.error(Error::InvalidRange));
         }
-    }
 
-    let min: usize = remainder[start..pos]
-        .parse()
-        .map_err(|_| Error::InvalidRange(lex.span()))?;
+        let start = pos;
+        while let Some(ch) = remainder[pos..].chars().next() {
+            if ch.is_ascii_digit() {
+                pos += ch.len_utf8();
+            } else {
+                break;
+            }
+        }
 
-    skip_ws(remainder, &mut pos);
+        let min = remainder[start..pos]
+            .parse()
+            .map_err(|_| lex.error(Error::InvalidRange))?;
+        skip_ws(remainder, &mut pos);
+        min
+    } else {
+        0
+    };
 
     // Parse optional comma and maximum value
     let max: Option<usize>;
@@ -664,7 +874,7 @@ fn parse_range_from_remainder(lex: &mut Lexer<Token>) -> Result<Quantifier> {
 
             // If the next non-space char is '}', the range is open ended
             match remainder[pos..].chars().next() {
-                Some('}') => {
+                Some('}') if has_min => {
                     pos += 1;
                     max = None;
                 }
@@ -678,30 +888,31 @@ fn parse_range_from_remainder(lex: &mut Lexer<Token>) -> Result<Quantifier> {
                         }
                     }
                     if start == pos {
-                        return Err(Error::InvalidRange(lex.span()));
+                        return Err(lex.error(Error::InvalidRange));
                     }
                     let m: usize = remainder[start..pos]
                         .parse()
-                        .map_err(|_| Error::InvalidRange(lex.span()))?;
+                        .map_err(|_| lex.error(Error::InvalidRange))?;
                     skip_ws(remainder, &mut pos);
                     if !matches!(remainder[pos..].chars().next(), Some('}')) {
-                        return Err(Error::InvalidRange(lex.span()));
+                        return Err(lex.error(Error::InvalidRange));
                     }
                     pos += 1;
                     max = Some(m);
                 }
-                _ => return Err(Error::InvalidRange(lex.span())),
+                _ => return Err(lex.error(Error::InvalidRange)),
             }
         }
         Some('}') => {
             pos += 1;
             max = Some(min);
         }
-        _ => return Err(Error::InvalidRange(lex.span())),
+        _ => return Err(lex.error(Error::InvalidRange)),
     }
 
     // Determine greediness
     let mode = match remainder[pos..].chars().next() {
+        _ if !reluctance => Reluctance::Greedy,
         Some('?') => {
             pos += 1;
             Reluctance::Lazy
@@ -718,7 +929,7 @@ fn parse_range_from_remainder(lex: &mut Lexer<Token>) -> Result<Quantifier> {
 
     if let Some(max) = max {
         if min > max {
-            return Err(Error::InvalidRange(lex.span()));
+            return Err(lex.error(Error::InvalidRange));
         }
         Ok(Quantifier::new(min..=max, mode))
     } else {
@@ -763,7 +974,94 @@ fn parse_single_quoted(lex: &mut Lexer<Token>) -> Result<String> {
     }
 
     // Unterminated literal – treat as lexing error
-    Err(Error::UnterminatedString(lex.span()))
+    Err(lex.error_at(Error::UnterminatedString, span_to_end(lex)))
+}
+
+impl std::fmt::Display for Token {
+    /// Writes the token as it appears in error messages: punctuation and
+    /// keywords quoted as written, and literals by kind.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Token::And => "&",
+            Token::Or => "|",
+            Token::Not => "!",
+            Token::RepeatZeroOrMore => "*",
+            Token::RepeatZeroOrMoreLazy => "*?",
+            Token::RepeatZeroOrMorePossessive => "*+",
+            Token::RepeatOneOrMore => "+",
+            Token::RepeatOneOrMoreLazy => "+?",
+            Token::RepeatOneOrMorePossessive => "++",
+            Token::RepeatZeroOrOne => "?",
+            Token::RepeatZeroOrOneLazy => "??",
+            Token::RepeatZeroOrOnePossessive => "?+",
+            Token::Tagged => "tagged",
+            Token::Array => "array",
+            Token::Map => "map",
+            Token::Bool => "bool",
+            Token::ByteString => "bstr",
+            Token::Date => "date",
+            Token::DateAny => "date-any",
+            Token::Known => "known",
+            Token::Null => "null",
+            Token::Number => "number",
+            Token::Text => "text",
+            Token::Digest => "digest",
+            Token::Search => "search",
+            Token::Child => "child",
+            Token::Untag => "untag",
+            Token::At => "at",
+            Token::Custom => "custom",
+            Token::Entry => "entry",
+            Token::None => "none",
+            Token::Depth => "depth",
+            Token::Keys => "keys",
+            Token::Prefix => "prefix",
+            Token::Cbor => "cbor",
+            Token::BoolTrue => "true",
+            Token::BoolFalse => "false",
+            Token::NaN => "NaN",
+            Token::Infinity => "Infinity",
+            Token::NegInfinity => "-Infinity",
+            Token::ParenOpen => "(",
+            Token::ParenClose => ")",
+            Token::BracketOpen => "[",
+            Token::BracketClose => "]",
+            Token::BraceOpen => "{",
+            Token::BraceClose => "}",
+            Token::Comma => ",",
+            Token::Colon => ":",
+            Token::Ellipsis => "...",
+            Token::Caret => "^",
+            Token::Dollar => "$",
+            Token::Tilde => "~",
+            Token::GreaterThanOrEqual => ">=",
+            Token::LessThanOrEqual => "<=",
+            Token::GreaterThan => ">",
+            Token::LessThan => "<",
+            Token::GroupName(name) => return write!(f, "capture '@{}'", name),
+            Token::Reference(name) => {
+                return write!(f, "reference '${}'", name);
+            }
+            Token::Backref(name) => {
+                return write!(f, "back-reference '={}'", name);
+            }
+            Token::NumberLiteral(_) => return write!(f, "number literal"),
+            Token::StringLiteral(_) => return write!(f, "string literal"),
+            Token::SingleQuoted(_) => return write!(f, "quoted literal"),
+            Token::Regex(_) => return write!(f, "regex"),
+            Token::HexString(_) => return write!(f, "hex string"),
+            Token::HexRegex(_) => return write!(f, "hex regex"),
+            Token::DateQuoted(_) | Token::DateAnyQuoted(_) => {
+                return write!(f, "date literal");
+            }
+            Token::DigestQuoted(_) => return write!(f, "digest literal"),
+            Token::EncodedDigestQuoted(_) => {
+                return write!(f, "encoded digest literal");
+            }
+            Token::CborQuoted(_) => return write!(f, "dCBOR literal"),
+        };
+        write!(f, "'{}'", text)
+    }
 }
 
 #[cfg(test)]
@@ -951,7 +1249,7 @@ mod tests {
 
         for test_case in test_cases {
             let mut lexer = Token::lexer(test_case.input);
-            if let Some(Ok(Token::Range(Ok(range)))) = lexer.next() {
+            if let Some(Ok(range)) = parse_range(&mut lexer, true) {
                 assert_eq!(range, test_case.expected);
             } else {
                 failed_cases.push(test_case.input);
@@ -962,4 +1260,67 @@ mod tests {
             panic!("Failed to parse ranges: {:?}", failed_cases);
         }
     }
+
+    #[test]
+    fn test_range_position() {
+        // The lexer leaves every brace to the parser
+        let mut lexer = Token::lexer("{5}");
+        assert_eq!(lexer.next(), Some(Ok(Token::BraceOpen)));
+
+        // A map pattern isn't a range, and is left unconsumed
+        let mut lexer = Token::lexer("{5: *}");
+        assert!(parse_range(&mut lexer, true).is_none());
+        assert_eq!(lexer.next(), Some(Ok(Token::BraceOpen)));
+
+        // Without reluctance, a `?` after the range is left for the parser
+        let mut lexer = Token::lexer("{5}?");
+        assert_eq!(
+            parse_range(&mut lexer, false),
+            Some(Ok(Quantifier::new(5..=5, Reluctance::Greedy)))
+        );
+        assert_eq!(lexer.next(), Some(Ok(Token::RepeatZeroOrOne)));
+    }
+
+    #[test]
+    fn test_uppercase_keywords() {
+        // Each keyword lexes the same in either case, but not in mixed case
+        for keyword in [
+            "tagged", "array", "map", "bool", "bstr", "date", "date-any",
+            "known", "null", "number", "text", "digest", "search", "child",
+            "untag", "custom", "entry", "none", "true", "false",
+        ] {
+            let upper = keyword.to_uppercase();
+            assert_eq!(
+                Token::lexer(&upper).next(),
+                Token::lexer(keyword).next(),
+                "{}",
+                upper
+            );
+        }
+        assert_eq!(
+            Token::lexer("DATE'2023-12-25'").next(),
+            Token::lexer("date'2023-12-25'").next()
+        );
+        assert_ne!(Token::lexer("Number").next(), Some(Ok(Token::Number)));
+
+        // Keywords display in lowercase however they were written
+        let token = Token::lexer("NUMBER").next().unwrap().unwrap();
+        assert_eq!(token.to_string(), "'number'");
+    }
+
+    #[test]
+    fn test_token_display() {
+        let display = |source: &str| {
+            Token::lexer(source).next().unwrap().unwrap().to_string()
+        };
+        assert_eq!(display("|"), "'|'");
+        assert_eq!(display("*?"), "'*?'");
+        assert_eq!(display("number"), "'number'");
+        assert_eq!(display("-Infinity"), "'-Infinity'");
+        assert_eq!(display("@name"), "capture '@name'");
+        assert_eq!(display("=name"), "back-reference '=name'");
+        assert_eq!(display("42"), "number literal");
+        assert_eq!(display("\"a\""), "string literal");
+        assert_eq!(display("/a/"), "regex");
+    }
 }
@@ -1,10 +1,77 @@
-use crate::{Error, Pattern, Result, parse::Token};
+use super::parse_set_items;
+use crate::{
+    Error, Pattern, Result,
+    parse::{LexerErrors, Token, parse_option_name, parse_range},
+};
 
+/// Parse a byte string pattern after the `bstr` keyword.
+///
+/// Supports the following syntax:
+/// - `bstr` - matches any byte string
+/// - `bstr({n})`, `bstr({n,m})`, `bstr({n,})` - matches byte strings whose
+///   length is within the interval
+/// - `bstr(prefix: h'0102')` - matches byte strings starting with the bytes
+/// - `bstr(h'00', h'01', ...)` - matches any of the listed byte strings
+/// - `bstr(cbor: pattern)` - matches byte strings holding an encoded dCBOR item
+///   the pattern matches
 pub(crate) fn parse_bytestring(
-    _lexer: &mut logos::Lexer<Token>,
+    lexer: &mut logos::Lexer<Token>,
 ) -> Result<Pattern> {
-    // The bstr token only matches any byte string
-    Ok(Pattern::any_byte_string())
+    if !matches!(lexer.clone().next(), Some(Ok(Token::ParenOpen))) {
+        return Ok(Pattern::any_byte_string());
+    }
+    lexer.next(); // consume the opening parenthesis
+
+    let option = parse_option_name(lexer, |token| {
+        matches!(token, Token::Cbor | Token::Prefix)
+    })
+    .transpose()?;
+    let pattern = if option == Some(Token::Cbor) {
+        Pattern::byte_string_decoded(super::super::meta::parse_or(lexer)?)
+    } else if option == Some(Token::Prefix) {
+        match lexer.next() {
+            Some(Ok(Token::HexString(res))) => {
+                Pattern::byte_string_prefix(res?)
+            }
+            Some(Ok(token)) => {
+                return Err(lexer.unexpected(token));
+            }
+            Some(Err(e)) => return Err(e),
+            None => return Err(Error::UnexpectedEndOfInput),
+        }
+    } else {
+        match parse_range(lexer, false) {
+            Some(res) => Pattern::byte_string_with_length(res?.interval()),
+            None => match lexer.next() {
+                Some(Ok(Token::HexString(res))) => {
+                    let mut values =
+                        parse_set_items(lexer, res?, |token| match token {
+                            Token::HexString(res) => Some(res),
+                            _ => None,
+                        })?;
+                    values.sort();
+                    values.dedup();
+                    return Ok(if values.len() == 1 {
+                        Pattern::byte_string(values.pop().unwrap())
+                    } else {
+                        Pattern::byte_string_in(values)
+                    });
+                }
+                Some(Ok(token)) => {
+                    return Err(lexer.unexpected(token));
+                }
+                Some(Err(e)) => return Err(e),
+                None => return Err(Error::UnexpectedEndOfInput),
+            },
+        }
+    };
+
+    match lexer.next() {
+        Some(Ok(Token::ParenClose)) => Ok(pattern),
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(lexer.error(Error::ExpectedCloseParen)),
+    }
 }
 
 pub(crate) fn parse_hex_string_token(
@@ -16,11 +83,14 @@ pub(crate) fn parse_hex_string_token(
     }
 }
 
-pub(crate) fn parse_hex_regex_token(token: Result<String>) -> Result<Pattern> {
+pub(crate) fn parse_hex_regex_token(
+    token: Result<String>,
+    lexer: &logos::Lexer<Token>,
+) -> Result<Pattern> {
     match token {
         Ok(regex_str) => {
             let regex = regex::bytes::Regex::new(&regex_str)
-                .map_err(|_| Error::InvalidRegex(0..0))?;
+                .map_err(|_| lexer.error(Error::InvalidRegex))?;
             Ok(Pattern::byte_string_regex(regex))
         }
         Err(e) => Err(e),
@@ -32,6 +102,7 @@ mod tests {
     use logos::Logos;
 
     use super::*;
+    use crate::Interval;
 
     #[test]
     fn test_parse_bytestring_any() {
@@ -42,4 +113,63 @@ mod tests {
         assert_eq!(result, Pattern::any_byte_string());
         assert_eq!(result.to_string(), "bstr");
     }
+
+    #[test]
+    fn test_parse_bytestring_length() {
+        let cases = [
+            ("bstr({32})", Interval::new(32..=32)),
+            ("bstr({16,64})", Interval::new(16..=64)),
+            ("bstr({1,})", Interval::new(1..)),
+            ("bstr({0})", Interval::new(0..=0)),
+        ];
+        for (src, interval) in cases {
+            let pattern = Pattern::parse(src).unwrap();
+            assert_eq!(pattern, Pattern::byte_string_with_length(interval));
+            assert_eq!(pattern.to_string(), src);
+        }
+    }
+
+    #[test]
+    fn test_parse_bytestring_prefix() {
+        let pattern = Pattern::parse("bstr(prefix: h'0102')").unwrap();
+        assert_eq!(pattern, Pattern::byte_string_prefix([1, 2]));
+        assert_eq!(pattern.to_string(), "bstr(prefix: h'0102')");
+
+        let pattern = Pattern::parse("bstr( prefix:h'' )").unwrap();
+        assert_eq!(pattern, Pattern::byte_string_prefix([]));
+
+        // Comments may come before the keyword, as before any token
+        let pattern =
+            Pattern::parse("bstr( # the first bytes\n prefix: h'01')").unwrap();
+        assert_eq!(pattern, Pattern::byte_string_prefix([1]));
+        let pattern = Pattern::parse("bstr(# decoded\ncbor: 1)").unwrap();
+        assert_eq!(pattern, Pattern::byte_string_decoded(Pattern::number(1)));
+        assert_eq!(
+            Pattern::parse("bstr(# c\nprefix h'01')").unwrap_err(),
+            Error::ExpectedColon("h'01'".to_string(), 16..21)
+        );
+
+        assert!(Pattern::parse("bstr(prefix h'01')").is_err());
+        assert!(Pattern::parse("bstr(prefixes: h'01')").is_err());
+        assert!(Pattern::parse("bstr(prefix: \"01\")").is_err());
+        assert!(Pattern::parse("bstr({2}").is_err());
+        assert!(Pattern::parse("bstr()").is_err());
+    }
+
+    #[test]
+    fn test_parse_bytestring_set() {
+        let pattern = Pattern::parse("bstr(h'01', h'00', h'')").unwrap();
+        assert_eq!(pattern, Pattern::byte_string_in([&[][..], &[0], &[1]]));
+        assert_eq!(pattern.to_string(), "bstr(h'', h'00', h'01')");
+        assert_eq!(Pattern::parse(&pattern.to_string()).unwrap(), pattern);
+
+        assert_eq!(
+            Pattern::parse("bstr(h'00', h'00')").unwrap(),
+            Pattern::byte_string([0])
+        );
+
+        assert!(Pattern::parse("bstr(h'00',)").is_err());
+        assert!(Pattern::parse("bstr(h'00', {2})").is_err());
+        assert!(Pattern::parse("bstr(h'00' h'01')").is_err());
+    }
 }
@@ -1,15 +1,113 @@
-use crate::{Pattern, Result, parse::Token};
+use known_values::{KNOWN_VALUES, KnownValue};
 
+use crate::{
+    Error, Pattern, Result,
+    parse::{LexerErrors, Token},
+};
+
+/// Parse a known value pattern after the `known` keyword.
+///
+/// Supports the following syntax:
+/// - `known` - matches any known value
+/// - `known('isA' | 'date' | '4')` - matches any of the listed known values,
+///   given by name or number
+/// - `known(1...100)` - matches known values whose number is within the
+///   inclusive range
 pub(crate) fn parse_known_value(
-    _lexer: &mut logos::Lexer<Token>,
+    lexer: &mut logos::Lexer<Token>,
 ) -> Result<Pattern> {
-    // For the new syntax, 'known' by itself matches any known value
-    Ok(Pattern::any_known_value())
+    if !matches!(lexer.clone().next(), Some(Ok(Token::ParenOpen))) {
+        return Ok(Pattern::any_known_value());
+    }
+    lexer.next(); // consume the opening parenthesis
+
+    let pattern = match lexer.next() {
+        Some(Ok(Token::NumberLiteral(res))) => {
+            let start = parse_known_value_number(res, lexer)?;
+            match lexer.next() {
+                Some(Ok(Token::Ellipsis)) => {}
+                Some(Ok(token)) => {
+                    return Err(lexer.unexpected(token));
+                }
+                Some(Err(e)) => return Err(e),
+                None => return Err(Error::UnexpectedEndOfInput),
+            }
+            let end = match lexer.next() {
+                Some(Ok(Token::NumberLiteral(res))) => {
+                    parse_known_value_number(res, lexer)?
+                }
+                Some(Ok(token)) => {
+                    return Err(lexer.unexpected(token));
+                }
+                Some(Err(e)) => return Err(e),
+                None => return Err(Error::UnexpectedEndOfInput),
+            };
+            if start > end {
+                return Err(lexer.error(Error::InvalidRange));
+            }
+            Pattern::known_value_range(start..=end)
+        }
+        Some(Ok(Token::SingleQuoted(res))) => {
+            let mut values = vec![resolve_known_value(res?, lexer.span())?];
+            while matches!(lexer.clone().next(), Some(Ok(Token::Or))) {
+                lexer.next(); // consume the `|`
+                match lexer.next() {
+                    Some(Ok(Token::SingleQuoted(res))) => {
+                        values.push(resolve_known_value(res?, lexer.span())?);
+                    }
+                    Some(Ok(token)) => {
+                        return Err(lexer.unexpected(token));
+                    }
+                    Some(Err(e)) => return Err(e),
+                    None => return Err(Error::UnexpectedEndOfInput),
+                }
+            }
+            Pattern::known_value_set(values)
+        }
+        Some(Ok(token)) => {
+            return Err(lexer.unexpected(token));
+        }
+        Some(Err(e)) => return Err(e),
+        None => return Err(Error::UnexpectedEndOfInput),
+    };
+
+    match lexer.next() {
+        Some(Ok(Token::ParenClose)) => Ok(pattern),
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(lexer.error(Error::ExpectedCloseParen)),
+    }
+}
+
+/// Converts a number literal to a known value number, which must be a
+/// non-negative integer.
+fn parse_known_value_number(
+    res: Result<f64>,
+    lexer: &logos::Lexer<Token>,
+) -> Result<u64> {
+    let value = res?;
+    if value >= 0.0 && value.fract() == 0.0 && value <= u64::MAX as f64 {
+        Ok(value as u64)
+    } else {
+        Err(lexer.error(Error::InvalidNumberFormat))
+    }
+}
+
+/// Resolves a single-quoted member of a known value set, either a number or
+/// the name of a registered known value.
+fn resolve_known_value(value: String, span: logos::Span) -> Result<KnownValue> {
+    if let Ok(numeric_value) = value.parse::<u64>() {
+        return Ok(KnownValue::new(numeric_value));
+    }
+    let binding = KNOWN_VALUES.get();
+    binding
+        .as_ref()
+        .and_then(|store| store.known_value_named(&value).cloned())
+        .ok_or(Error::UnknownKnownValue(value, span))
 }
 
 #[cfg(test)]
 mod tests {
-    use known_values::KnownValue;
     use logos::Logos;
 
     use super::*;
@@ -24,6 +122,31 @@ mod tests {
         assert_eq!(result.to_string(), "known");
     }
 
+    #[test]
+    fn test_parse_known_value_set() {
+        let mut lexer = Token::lexer("known('isA' | 'date' | '1000000')");
+        assert_eq!(lexer.next(), Some(Ok(Token::Known)));
+        let result = parse_known_value(&mut lexer).unwrap();
+        assert_eq!(
+            result,
+            Pattern::known_value_set([
+                known_values::IS_A,
+                known_values::DATE,
+                KnownValue::new(1_000_000),
+            ])
+        );
+        assert_eq!(result.to_string(), "known('isA' | 'date' | '1000000')");
+    }
+
+    #[test]
+    fn test_parse_known_value_range() {
+        let mut lexer = Token::lexer("known(1...100)");
+        assert_eq!(lexer.next(), Some(Ok(Token::Known)));
+        let result = parse_known_value(&mut lexer).unwrap();
+        assert_eq!(result, Pattern::known_value_range(1..=100));
+        assert_eq!(result.to_string(), "known(1...100)");
+    }
+
     #[test]
     fn test_parse_known_value_numeric() {
         let mut lexer = Token::lexer("'12345'");
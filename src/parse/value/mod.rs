@@ -15,3 +15,40 @@ pub(crate) use known_value_parser::*;
 pub(crate) use null_parser::*;
 pub(crate) use number_parser::*;
 pub(crate) use text_parser::*;
+
+use crate::{
+    Error, Result,
+    parse::{LexerErrors, Token},
+};
+
+/// Parses the rest of a set literal such as `text("a", "b")` after its first
+/// item, through the closing parenthesis. `item` converts a token to an item,
+/// returning `None` for tokens that cannot be one.
+pub(crate) fn parse_set_items<T>(
+    lexer: &mut logos::Lexer<Token>,
+    first: T,
+    item: impl Fn(Token) -> Option<Result<T>>,
+) -> Result<Vec<T>> {
+    let mut items = vec![first];
+    loop {
+        match lexer.next() {
+            Some(Ok(Token::ParenClose)) => return Ok(items),
+            Some(Ok(Token::Comma)) => {}
+            Some(Ok(token)) => {
+                return Err(lexer.unexpected(token));
+            }
+            Some(Err(e)) => return Err(e),
+            None => return Err(lexer.error(Error::ExpectedCloseParen)),
+        }
+        match lexer.next() {
+            Some(Ok(token)) => match item(token.clone()) {
+                Some(value) => items.push(value?),
+                None => {
+                    return Err(lexer.unexpected(token));
+                }
+            },
+            Some(Err(e)) => return Err(e),
+            None => return Err(Error::UnexpectedEndOfInput),
+        }
+    }
+}
@@ -1,10 +1,119 @@
-use crate::{Pattern, Result, parse::Token};
+use std::ops::Bound;
 
-pub(crate) fn parse_number(
-    _lexer: &mut logos::Lexer<Token>,
+use super::parse_set_items;
+use crate::{
+    Error, Pattern, Result,
+    parse::{LexerErrors, Token},
+};
+
+/// Parse a number pattern after the `number` keyword.
+///
+/// Supports the following syntax:
+/// - `number` - matches any number
+/// - `number(1, 2, ...)` - matches any of the listed numbers, which may include
+///   `Infinity` and `-Infinity`
+pub(crate) fn parse_number(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    if !matches!(lexer.clone().next(), Some(Ok(Token::ParenOpen))) {
+        return Ok(Pattern::any_number());
+    }
+    lexer.next(); // consume the opening parenthesis
+
+    let first = match lexer.next() {
+        Some(Ok(token)) => match set_item(token.clone()) {
+            Some(res) => res?,
+            None => {
+                return Err(lexer.unexpected(token));
+            }
+        },
+        Some(Err(e)) => return Err(e),
+        None => return Err(Error::UnexpectedEndOfInput),
+    };
+    let mut values = parse_set_items(lexer, first, set_item)?;
+    // Zero and negative zero are the same number
+    values.iter_mut().for_each(|value| *value += 0.0);
+    values.sort_by(f64::total_cmp);
+    values.dedup();
+    if values.len() == 1 {
+        Ok(Pattern::number(values[0]))
+    } else {
+        Ok(Pattern::number_in(values))
+    }
+}
+
+/// Parse a number pattern after the number `start`.
+///
+/// Supports the following syntax, where a `<` marks an exclusive bound:
+/// - `start` - matches the number itself
+/// - `start...end`, `start...<end`, `start<...end`, `start<...<end` - matches
+///   numbers between the bounds
+/// - `start...`, `start<...` - matches numbers above the lower bound
+pub(crate) fn parse_number_from(
+    lexer: &mut logos::Lexer<Token>,
+    start: f64,
 ) -> Result<Pattern> {
-    // For the keyword "number", just return any number pattern
-    Ok(Pattern::any_number())
+    let mut lookahead = lexer.clone();
+    let lower = match lookahead.next() {
+        Some(Ok(Token::Ellipsis)) => Bound::Included(start),
+        Some(Ok(Token::LessThan))
+            if matches!(lookahead.next(), Some(Ok(Token::Ellipsis))) =>
+        {
+            lexer.next(); // consume the `<`
+            Bound::Excluded(start)
+        }
+        _ => return Ok(Pattern::number(start)),
+    };
+    lexer.next(); // consume the ellipsis
+    let upper = parse_upper_bound(lexer)?;
+    Ok(Pattern::number_bounded((lower, upper)))
+}
+
+/// Parse a number pattern after a leading ellipsis, `...end` or `...<end`,
+/// which matches numbers below the upper bound.
+pub(crate) fn parse_number_up_to(
+    lexer: &mut logos::Lexer<Token>,
+) -> Result<Pattern> {
+    match parse_upper_bound(lexer)? {
+        Bound::Unbounded => match lexer.next() {
+            Some(Ok(token)) => Err(lexer.unexpected(token)),
+            Some(Err(e)) => Err(e),
+            None => Err(Error::UnexpectedEndOfInput),
+        },
+        upper => Ok(Pattern::number_bounded((Bound::Unbounded, upper))),
+    }
+}
+
+/// Parse the upper bound of a range after its ellipsis: a number, `<` and a
+/// number, or nothing for an open end.
+fn parse_upper_bound(lexer: &mut logos::Lexer<Token>) -> Result<Bound<f64>> {
+    match lexer.clone().next() {
+        Some(Ok(Token::NumberLiteral(res))) => {
+            lexer.next();
+            Ok(Bound::Included(res?))
+        }
+        Some(Ok(Token::LessThan)) => {
+            lexer.next();
+            match lexer.next() {
+                Some(Ok(Token::NumberLiteral(res))) => {
+                    Ok(Bound::Excluded(res?))
+                }
+                Some(Ok(token)) => Err(lexer.unexpected(token)),
+                Some(Err(e)) => Err(e),
+                None => Err(Error::UnexpectedEndOfInput),
+            }
+        }
+        _ => Ok(Bound::Unbounded),
+    }
+}
+
+/// Returns the number a token contributes to a `number(...)` set literal.
+/// NaN is not allowed, as it is not equal to itself.
+fn set_item(token: Token) -> Option<Result<f64>> {
+    match token {
+        Token::NumberLiteral(res) => Some(res),
+        Token::Infinity => Some(Ok(f64::INFINITY)),
+        Token::NegInfinity => Some(Ok(f64::NEG_INFINITY)),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -74,4 +183,30 @@ mod tests {
             .expect("-Infinity should parse successfully");
         assert_eq!(pattern.to_string(), "-Infinity");
     }
+
+    #[test]
+    fn test_number_set() {
+        let pattern = Pattern::parse("number(3, 1, 2)").unwrap();
+        assert_eq!(pattern, Pattern::number_in([1, 2, 3]));
+        assert_eq!(pattern.to_string(), "number(1, 2, 3)");
+        assert_eq!(Pattern::parse(&pattern.to_string()).unwrap(), pattern);
+
+        let pattern =
+            Pattern::parse("number(-Infinity, 1.5, Infinity)").unwrap();
+        assert_eq!(pattern.to_string(), "number(-Infinity, 1.5, Infinity)");
+
+        // A single value, or duplicates of one, is a plain number pattern
+        assert_eq!(Pattern::parse("number(42)").unwrap(), Pattern::number(42));
+        assert_eq!(
+            Pattern::parse("number(0, -0, 0.0)").unwrap(),
+            Pattern::number(0)
+        );
+
+        assert!(Pattern::parse("number()").is_err());
+        assert!(Pattern::parse("number(NaN)").is_err());
+        assert!(Pattern::parse("number(1, NaN)").is_err());
+        assert!(Pattern::parse("number(1, \"2\")").is_err());
+        assert!(Pattern::parse("number(1...2)").is_err());
+        assert!(Pattern::parse("number(1, 2").is_err());
+    }
 }
@@ -1,7 +1,68 @@
-use crate::{Pattern, Result, parse::Token};
+use super::parse_set_items;
+use crate::{
+    Error, Pattern, Result,
+    parse::{LexerErrors, Token},
+};
 
-pub(crate) fn parse_text(_lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
-    Ok(Pattern::any_text())
+/// Parse a text pattern after the `text` keyword.
+///
+/// Supports the following syntax:
+/// - `text` - matches any text
+/// - `text(^"prefix")` - matches text starting with `prefix`
+/// - `text($"suffix")` - matches text ending with `suffix`
+/// - `text(~"value")` - matches `value`, ignoring case
+/// - `text("a", "b", ...)` - matches any of the listed values
+pub(crate) fn parse_text(lexer: &mut logos::Lexer<Token>) -> Result<Pattern> {
+    if !matches!(lexer.clone().next(), Some(Ok(Token::ParenOpen))) {
+        return Ok(Pattern::any_text());
+    }
+    lexer.next(); // consume the opening parenthesis
+
+    let constructor: fn(String) -> Pattern = match lexer.next() {
+        Some(Ok(Token::StringLiteral(res))) => {
+            let values = parse_set_items(lexer, res?, |token| match token {
+                Token::StringLiteral(res) => Some(res),
+                _ => None,
+            })?;
+            return Ok(text_set(values));
+        }
+        Some(Ok(Token::Caret)) => Pattern::text_prefix,
+        Some(Ok(Token::Dollar)) => Pattern::text_suffix,
+        Some(Ok(Token::Tilde)) => Pattern::text_case_insensitive,
+        Some(Ok(token)) => {
+            return Err(lexer.unexpected(token));
+        }
+        Some(Err(e)) => return Err(e),
+        None => return Err(Error::UnexpectedEndOfInput),
+    };
+
+    let value = match lexer.next() {
+        Some(Ok(Token::StringLiteral(res))) => res?,
+        Some(Ok(token)) => {
+            return Err(lexer.unexpected(token));
+        }
+        Some(Err(e)) => return Err(e),
+        None => return Err(Error::UnexpectedEndOfInput),
+    };
+
+    match lexer.next() {
+        Some(Ok(Token::ParenClose)) => Ok(constructor(value)),
+        Some(Ok(token)) => Err(lexer.unexpected(token)),
+        Some(Err(e)) => Err(e),
+        None => Err(lexer.error(Error::ExpectedCloseParen)),
+    }
+}
+
+/// Returns the pattern for a `text(...)` set literal, which is a plain text
+/// value when it has only one distinct member.
+fn text_set(mut values: Vec<String>) -> Pattern {
+    values.sort();
+    values.dedup();
+    if values.len() == 1 {
+        Pattern::text(values.pop().unwrap())
+    } else {
+        Pattern::text_in(values)
+    }
 }
 
 #[cfg(test)]
@@ -72,4 +133,65 @@ mod tests {
         assert_eq!(result, Pattern::any_text());
         assert_eq!(result.to_string(), "text");
     }
+
+    #[test]
+    fn test_parse_text_affixes() {
+        let cases = [
+            (r#"text(^"ERROR")"#, Pattern::text_prefix("ERROR")),
+            (r#"text($".log")"#, Pattern::text_suffix(".log")),
+            (
+                r#"text(~"Warning")"#,
+                Pattern::text_case_insensitive("Warning"),
+            ),
+            (
+                r#"text(^"say \"hi\"")"#,
+                Pattern::text_prefix(r#"say "hi""#),
+            ),
+            (r#"text($"C:\\")"#, Pattern::text_suffix(r"C:\")),
+        ];
+        for (src, expected) in cases {
+            let pattern = Pattern::parse(src).unwrap();
+            assert_eq!(pattern, expected, "parsing {}", src);
+            assert_eq!(pattern.to_string(), src);
+        }
+
+        // Whitespace is allowed around the sigil
+        assert_eq!(
+            Pattern::parse(r#"text( ^ "a" )"#).unwrap(),
+            Pattern::text_prefix("a")
+        );
+    }
+
+    #[test]
+    fn test_parse_text_affix_errors() {
+        assert!(Pattern::parse(r#"text(^/a/)"#).is_err());
+        assert!(Pattern::parse(r#"text(^"a""#).is_err());
+        assert!(Pattern::parse(r#"text(^"a)"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_text_set() {
+        let pattern =
+            Pattern::parse(r#"text("red", "green", "blue")"#).unwrap();
+        assert_eq!(pattern, Pattern::text_in(["red", "green", "blue"]));
+        assert_eq!(pattern.to_string(), r#"text("blue", "green", "red")"#);
+        assert_eq!(Pattern::parse(&pattern.to_string()).unwrap(), pattern);
+
+        // A single value, or duplicates of one, is a plain text pattern
+        assert_eq!(Pattern::parse(r#"text("a")"#).unwrap(), Pattern::text("a"));
+        assert_eq!(
+            Pattern::parse(r#"text("a", "a")"#).unwrap(),
+            Pattern::text("a")
+        );
+        assert_eq!(
+            Pattern::parse(r#"text("a", "b", "a")"#).unwrap(),
+            Pattern::text_in(["a", "b"])
+        );
+
+        assert!(Pattern::parse("text()").is_err());
+        assert!(Pattern::parse(r#"text("a",)"#).is_err());
+        assert!(Pattern::parse(r#"text("a" "b")"#).is_err());
+        assert!(Pattern::parse(r#"text("a", 1)"#).is_err());
+        assert!(Pattern::parse(r#"text("a", "b""#).is_err());
+    }
 }
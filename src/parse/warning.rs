@@ -0,0 +1,75 @@
+use logos::{Logos, Span};
+
+use super::Token;
+
+/// Something in a pattern's source that parses but probably doesn't mean
+/// what it says, as reported by
+/// [`Pattern::parse_with_warnings`](crate::Pattern::parse_with_warnings).
+///
+/// New kinds of warning may be added in any release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// `[*]`, which matches only arrays of exactly one element, spanning the
+    /// brackets. `array` or `[(*)*]` matches an array of any length.
+    SingleElementArray(Span),
+}
+
+impl Warning {
+    /// The part of the source the warning is about.
+    pub fn span(&self) -> &Span {
+        match self {
+            Warning::SingleElementArray(span) => span,
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::SingleElementArray(span) => write!(
+                f,
+                "[*] at {:?} matches only arrays of exactly one element; \
+                 use array or [(*)*] for an array of any length",
+                span
+            ),
+        }
+    }
+}
+
+/// Returns the warnings for `source`, a pattern that parses.
+///
+/// Like the check for repeated capture names, this works on the tokens so
+/// that each warning can carry a span. `[*]` is found with any number of
+/// groups around the `*`, as in `[(*)]`, which parses to the same pattern.
+pub(crate) fn warnings(source: &str) -> Vec<Warning> {
+    let tokens: Vec<(Token, Span)> = Token::lexer(source)
+        .spanned()
+        .filter_map(|(token, span)| token.ok().map(|token| (token, span)))
+        .collect();
+    let mut warnings = Vec::new();
+    for (start, (token, open)) in tokens.iter().enumerate() {
+        if *token != Token::BracketOpen {
+            continue;
+        }
+        let inner = &tokens[start + 1..];
+        let groups = inner
+            .iter()
+            .take_while(|(token, _)| *token == Token::ParenOpen)
+            .count();
+        let expected = std::iter::once(&Token::RepeatZeroOrMore)
+            .chain(std::iter::repeat_n(&Token::ParenClose, groups))
+            .chain(std::iter::once(&Token::BracketClose));
+        let len = 2 * groups + 2;
+        if inner.len() >= len
+            && inner[groups..len]
+                .iter()
+                .map(|(token, _)| token)
+                .eq(expected)
+        {
+            let end = inner[len - 1].1.end;
+            warnings.push(Warning::SingleElementArray(open.start..end));
+        }
+    }
+    warnings
+}
@@ -0,0 +1,115 @@
+use std::cell::Cell;
+
+use dcbor::prelude::*;
+
+use crate::{
+    Error, Result,
+    pattern::{Captures, Matcher, Path, Pattern},
+};
+
+/// Limits on the work a single match may do, for
+/// [`Pattern::paths_with_opts`] and [`Pattern::paths_with_captures_with_opts`].
+///
+/// Nested repeats such as `[(*)*, (*)*, (*)*, 42]` can take time polynomial
+/// or worse in the size of the haystack. A budget bounds that work when
+/// matching untrusted patterns or documents. Both limits default to `None`,
+/// meaning unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOpts {
+    /// The most steps the match may take, counting each VM instruction
+    /// executed and each assignment tried when matching array sequences.
+    pub max_steps: Option<u64>,
+    /// The most VM threads that may be waiting to run at once.
+    pub max_threads: Option<usize>,
+}
+
+/// The budget of the match in progress on this thread.
+#[derive(Clone, Copy, Default)]
+struct Budget {
+    steps_left: Option<u64>,
+    max_threads: Option<usize>,
+    exceeded: bool,
+}
+
+thread_local! {
+    static BUDGET: Cell<Budget> = Cell::new(Budget::default());
+}
+
+/// Restores the budget of the enclosing match when dropped, even on
+/// unwinding.
+struct RestoreBudget(Budget);
+
+impl Drop for RestoreBudget {
+    fn drop(&mut self) { BUDGET.set(self.0); }
+}
+
+/// Runs the match `f` under the limits in `opts`, failing with
+/// [`Error::BudgetExceeded`] if it ran out.
+fn with_budget<R>(opts: MatchOpts, f: impl FnOnce() -> R) -> Result<R> {
+    let budget = Budget {
+        steps_left: opts.max_steps,
+        max_threads: opts.max_threads,
+        exceeded: false,
+    };
+    let _restore = RestoreBudget(BUDGET.replace(budget));
+    let result = f();
+    if BUDGET.get().exceeded {
+        Err(Error::BudgetExceeded)
+    } else {
+        Ok(result)
+    }
+}
+
+/// Takes one step from the budget, returning false if the budget has run
+/// out, in which case the caller should give up.
+pub(crate) fn step() -> bool {
+    let mut budget = BUDGET.get();
+    if budget.exceeded {
+        return false;
+    }
+    if let Some(steps_left) = budget.steps_left {
+        match steps_left.checked_sub(1) {
+            Some(steps_left) => budget.steps_left = Some(steps_left),
+            None => budget.exceeded = true,
+        }
+        BUDGET.set(budget);
+    }
+    !budget.exceeded
+}
+
+/// Returns false, marking the budget as run out, if `count` threads waiting
+/// to run is more than the budget allows.
+pub(crate) fn admit_threads(count: usize) -> bool {
+    let mut budget = BUDGET.get();
+    if budget
+        .max_threads
+        .is_some_and(|max_threads| count > max_threads)
+    {
+        budget.exceeded = true;
+        BUDGET.set(budget);
+    }
+    !budget.exceeded
+}
+
+impl Pattern {
+    /// Like [`Matcher::paths`], giving up with [`Error::BudgetExceeded`] if
+    /// the match needs more work than `opts` allows.
+    pub fn paths_with_opts(
+        &self,
+        haystack: &CBOR,
+        opts: MatchOpts,
+    ) -> Result<Vec<Path>> {
+        with_budget(opts, || self.paths(haystack))
+    }
+
+    /// Like [`Matcher::paths_with_captures`], giving up with
+    /// [`Error::BudgetExceeded`] if the match needs more work than `opts`
+    /// allows.
+    pub fn paths_with_captures_with_opts(
+        &self,
+        haystack: &CBOR,
+        opts: MatchOpts,
+    ) -> Result<(Vec<Path>, Captures)> {
+        with_budget(opts, || self.paths_with_captures(haystack))
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    Error, Result,
+    pattern::{MetaPattern, Pattern},
+};
+
+/// How much each unbounded quantifier or search multiplies the score of the
+/// patterns inside it.
+const NESTED_COST_FACTOR: u64 = 4;
+
+/// Measurements of the size and shape of a pattern, returned by
+/// [`Pattern::complexity`], for judging how expensive a pattern from an
+/// untrusted source may be to run before running it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Complexity {
+    /// The number of patterns in the tree, counting the pattern itself and
+    /// every sub-pattern [`Pattern::walk`] visits.
+    pub node_count: usize,
+    /// The depth of the most deeply nested sub-pattern, where the pattern
+    /// itself is at depth 0.
+    pub max_depth: usize,
+    /// The number of quantifiers with no upper bound, such as `*`, `+`, and
+    /// `{2,}`.
+    pub unbounded_quantifiers: usize,
+    /// The number of `search(...)` patterns.
+    pub search_nodes: usize,
+    /// An overall estimate of cost, in which each pattern counts 1,
+    /// multiplied by 4 for each unbounded quantifier or search enclosing
+    /// it, so that nesting them scores far higher than using them side by
+    /// side. The sum saturates rather than overflowing.
+    pub score: u64,
+}
+
+impl Complexity {
+    /// Checks these measurements against `limits`, failing with the error
+    /// for the first limit exceeded, in the order the fields of [`Limits`]
+    /// are declared.
+    pub fn check(&self, limits: &Limits) -> Result<()> {
+        if let Some(limit) = limits.max_nodes.filter(|&l| self.node_count > l) {
+            return Err(Error::TooManyNodes(self.node_count, limit));
+        }
+        if let Some(limit) = limits.max_depth.filter(|&l| self.max_depth > l) {
+            return Err(Error::DepthLimitExceeded(self.max_depth, limit));
+        }
+        if let Some(limit) = limits
+            .max_unbounded_quantifiers
+            .filter(|&l| self.unbounded_quantifiers > l)
+        {
+            return Err(Error::TooManyUnboundedQuantifiers(
+                self.unbounded_quantifiers,
+                limit,
+            ));
+        }
+        if let Some(limit) =
+            limits.max_search_nodes.filter(|&l| self.search_nodes > l)
+        {
+            return Err(Error::TooManySearches(self.search_nodes, limit));
+        }
+        if let Some(limit) = limits.max_score.filter(|&l| self.score > l) {
+            return Err(Error::ComplexityScoreExceeded(self.score, limit));
+        }
+        Ok(())
+    }
+}
+
+/// Thresholds on the [`Complexity`] of a pattern, for
+/// [`Pattern::parse_with_limits`] and [`Complexity::check`].
+///
+/// Each limit is the greatest value allowed. All default to `None`, meaning
+/// unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The most patterns the tree may hold.
+    pub max_nodes: Option<usize>,
+    /// The deepest a sub-pattern may be nested.
+    pub max_depth: Option<usize>,
+    /// The most quantifiers with no upper bound.
+    pub max_unbounded_quantifiers: Option<usize>,
+    /// The most `search(...)` patterns.
+    pub max_search_nodes: Option<usize>,
+    /// The highest overall score.
+    pub max_score: Option<u64>,
+}
+
+impl Pattern {
+    /// Measures the size and shape of this pattern.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use dcbor_pattern::Pattern;
+    /// let pattern = Pattern::parse("search([(number)*])").unwrap();
+    /// let complexity = pattern.complexity();
+    /// assert_eq!(complexity.node_count, 4);
+    /// assert_eq!(complexity.max_depth, 3);
+    /// assert_eq!(complexity.unbounded_quantifiers, 1);
+    /// assert_eq!(complexity.search_nodes, 1);
+    /// assert_eq!(complexity.score, 1 + 4 + 4 + 16);
+    /// ```
+    pub fn complexity(&self) -> Complexity {
+        let mut complexity = Complexity::default();
+        self.measure(0, 1, &mut complexity);
+        complexity
+    }
+
+    /// Adds this pattern and its sub-patterns at `depth` to `complexity`,
+    /// where each counts `weight` towards the score before any multiplying
+    /// by this pattern.
+    fn measure(&self, depth: usize, weight: u64, complexity: &mut Complexity) {
+        complexity.node_count += 1;
+        complexity.max_depth = complexity.max_depth.max(depth);
+        complexity.score = complexity.score.saturating_add(weight);
+
+        let multiplies = match self {
+            Pattern::Meta(MetaPattern::Repeat(repeat)) => {
+                let unbounded = repeat.quantifier().is_unbounded();
+                complexity.unbounded_quantifiers += usize::from(unbounded);
+                unbounded
+            }
+            Pattern::Meta(MetaPattern::Search(_)) => {
+                complexity.search_nodes += 1;
+                true
+            }
+            _ => false,
+        };
+        let weight = if multiplies {
+            weight.saturating_mul(NESTED_COST_FACTOR)
+        } else {
+            weight
+        };
+        for child in self.children() {
+            child.measure(depth + 1, weight, complexity);
+        }
+    }
+
+    /// Parses a pattern as [`Pattern::parse`] does, then rejects it if its
+    /// [`complexity`](Pattern::complexity) exceeds any of `limits`.
+    ///
+    /// Parsing itself is already bounded by
+    /// [`ParseOpts::max_nesting_depth`](crate::ParseOpts::max_nesting_depth),
+    /// so that even a pattern that will be rejected can't exhaust the stack.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use dcbor_pattern::{Error, Limits, Pattern};
+    /// let limits = Limits { max_search_nodes: Some(1), ..Limits::default() };
+    /// assert!(Pattern::parse_with_limits("search(text)", &limits).is_ok());
+    /// assert!(matches!(
+    ///     Pattern::parse_with_limits("search(search(text))", &limits),
+    ///     Err(Error::TooManySearches(2, 1))
+    /// ));
+    /// ```
+    pub fn parse_with_limits(input: &str, limits: &Limits) -> Result<Self> {
+        let pattern = Self::parse(input)?;
+        pattern.complexity().check(limits)?;
+        Ok(pattern)
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use dcbor::prelude::*;
+
+use crate::{
+    Error, Result,
+    pattern::{Matcher, Path, Pattern, vm::Instr},
+};
+
+/// Matching logic supplied by the application, for use in a
+/// [`Pattern::Custom`].
+///
+/// A custom matcher tests a single value, like the value patterns do, so a
+/// match is the value itself and captures nothing.
+///
+/// # Example
+///
+/// ```
+/// use dcbor::prelude::*;
+/// use dcbor_pattern::{DynMatcher, Matcher, Pattern};
+///
+/// #[derive(Debug)]
+/// struct Https;
+///
+/// impl DynMatcher for Https {
+///     fn matches(&self, haystack: &CBOR) -> bool {
+///         haystack
+///             .as_text()
+///             .is_some_and(|text| text.starts_with("https://"))
+///     }
+/// }
+///
+/// Pattern::register_custom("https", || Https)?;
+/// let pattern = Pattern::parse("[custom(https)*]")?;
+/// assert!(pattern.matches(&CBOR::from(vec!["https://example.com"])));
+/// assert!(!pattern.matches(&CBOR::from(vec!["http://example.com"])));
+/// # Ok::<(), dcbor_pattern::Error>(())
+/// ```
+pub trait DynMatcher: std::fmt::Debug + Send + Sync {
+    /// Returns whether `haystack` matches.
+    fn matches(&self, haystack: &CBOR) -> bool;
+}
+
+/// A pattern that matches values accepted by a custom matcher.
+///
+/// The pattern is known by the name given to [`Pattern::custom`] or
+/// [`Pattern::register_custom`], and displays as `custom(name)`. Two custom
+/// patterns are equal if their names are, whatever their matchers.
+#[derive(Debug, Clone)]
+pub struct CustomPattern {
+    name: String,
+    matcher: Arc<dyn DynMatcher>,
+}
+
+impl CustomPattern {
+    /// Creates a new `CustomPattern` that matches the values `matcher`
+    /// accepts, known by `name`.
+    pub fn new(
+        name: impl Into<String>,
+        matcher: impl DynMatcher + 'static,
+    ) -> Self {
+        CustomPattern { name: name.into(), matcher: Arc::new(matcher) }
+    }
+
+    /// Returns the name of the matcher.
+    pub fn name(&self) -> &str { &self.name }
+
+    /// Returns the matcher.
+    pub fn matcher(&self) -> &dyn DynMatcher { self.matcher.as_ref() }
+}
+
+impl PartialEq for CustomPattern {
+    fn eq(&self, other: &Self) -> bool { self.name == other.name }
+}
+
+impl Eq for CustomPattern {}
+
+impl Matcher for CustomPattern {
+    fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        if self.matcher.matches(haystack) {
+            vec![vec![haystack.clone()]]
+        } else {
+            vec![]
+        }
+    }
+
+    fn paths_with_captures(
+        &self,
+        haystack: &CBOR,
+    ) -> (Vec<Path>, crate::Captures) {
+        (self.paths(haystack), crate::Captures::new())
+    }
+
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        _captures: &mut Vec<String>,
+    ) {
+        let idx = literals.len();
+        literals.push(Pattern::Custom(self.clone()));
+        code.push(Instr::MatchPredicate(idx));
+    }
+}
+
+impl std::fmt::Display for CustomPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "custom({})", self.name)
+    }
+}
+
+/// Creates a custom matcher registered by [`Pattern::register_custom`].
+type Factory = Arc<dyn Fn() -> Arc<dyn DynMatcher> + Send + Sync>;
+
+/// The custom matchers that `custom(name)` can refer to, shared by every
+/// thread.
+static REGISTRY: LazyLock<RwLock<BTreeMap<String, Factory>>> =
+    LazyLock::new(Default::default);
+
+impl Pattern {
+    /// Creates a pattern that matches the values `matcher` accepts, displayed
+    /// as `custom(name)`.
+    ///
+    /// The pattern only parses back from its display if a matcher is
+    /// registered under `name` with [`Pattern::register_custom`].
+    pub fn custom(
+        name: impl Into<String>,
+        matcher: impl DynMatcher + 'static,
+    ) -> Self {
+        Pattern::Custom(CustomPattern::new(name, matcher))
+    }
+
+    /// Registers `factory` as the source of the matcher that `custom(name)`
+    /// refers to in patterns parsed from now on, on any thread.
+    ///
+    /// The factory is called once for each `custom(name)` parsed. Registering
+    /// a name again replaces its factory without affecting patterns already
+    /// parsed. Fails with [`Error::InvalidCustomMatcherName`] unless `name`
+    /// starts with a letter or underscore and continues with letters,
+    /// digits, or underscores.
+    pub fn register_custom<M, F>(
+        name: impl Into<String>,
+        factory: F,
+    ) -> Result<()>
+    where
+        M: DynMatcher + 'static,
+        F: Fn() -> M + Send + Sync + 'static,
+    {
+        let name = name.into();
+        if !crate::parse::is_identifier(&name) {
+            return Err(Error::InvalidCustomMatcherName(name));
+        }
+        let factory: Factory = Arc::new(move || Arc::new(factory()));
+        REGISTRY.write().unwrap().insert(name, factory);
+        Ok(())
+    }
+}
+
+/// Returns a pattern using the matcher registered as `name`, or `None` if
+/// there isn't one.
+pub(crate) fn registered_custom(name: &str) -> Option<CustomPattern> {
+    let factory = REGISTRY.read().unwrap().get(name).cloned()?;
+    Some(CustomPattern { name: name.to_string(), matcher: factory() })
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CustomPattern {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.name.serialize(serializer)
+    }
+}
+
+/// A custom pattern is stored as its name, and deserializes to the matcher
+/// registered under that name, failing if there isn't one.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CustomPattern {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let name = String::deserialize(deserializer)?;
+        registered_custom(&name).ok_or_else(|| {
+            D::Error::custom(format!("unregistered custom matcher: {}", name))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Even;
+
+    impl DynMatcher for Even {
+        fn matches(&self, haystack: &CBOR) -> bool {
+            u64::try_from(haystack.clone()).is_ok_and(|n| n % 2 == 0)
+        }
+    }
+
+    #[test]
+    fn test_custom_pattern() {
+        let pattern = CustomPattern::new("even", Even);
+        assert_eq!(pattern.to_string(), "custom(even)");
+        assert!(pattern.matches(&CBOR::from(4)));
+        assert!(!pattern.matches(&CBOR::from(3)));
+        assert!(!pattern.matches(&CBOR::from("4")));
+
+        // Equality goes by name
+        assert_eq!(pattern, CustomPattern::new("even", Even));
+        assert_ne!(pattern, CustomPattern::new("odd", Even));
+    }
+}
@@ -0,0 +1,69 @@
+use dcbor::prelude::*;
+
+use crate::{
+    Error, Result,
+    pattern::{Captures, Matcher, Path, Pattern},
+};
+
+/// Decodes a dCBOR haystack from its encoding, which must be canonical.
+fn decode(data: &[u8]) -> Result<CBOR> {
+    CBOR::try_from_data(data)
+        .map_err(|error| Error::InvalidCborData(error.to_string()))
+}
+
+/// Decodes a dCBOR haystack from the hex of its encoding.
+fn decode_hex(hex: &str) -> Result<CBOR> {
+    let data = hex::decode(hex.trim()).map_err(|error| {
+        Error::InvalidCborData(format!("invalid hex: {}", error))
+    })?;
+    decode(&data)
+}
+
+impl Pattern {
+    /// Decodes the dCBOR encoded in `data` and returns whether this pattern
+    /// matches it.
+    ///
+    /// Fails with [`Error::InvalidCborData`] if `data` is not a single,
+    /// complete dCBOR item, including if it is valid CBOR that is not
+    /// canonically encoded.
+    pub fn matches_data(&self, data: &[u8]) -> Result<bool> {
+        Ok(self.matches(&decode(data)?))
+    }
+
+    /// Decodes the dCBOR encoded in `data` and returns the paths this
+    /// pattern matches in it, as [`Matcher::paths`] does.
+    pub fn paths_from_data(&self, data: &[u8]) -> Result<Vec<Path>> {
+        Ok(self.paths(&decode(data)?))
+    }
+
+    /// Decodes the dCBOR encoded in `data` and returns the paths and
+    /// captures this pattern matches in it, as
+    /// [`Matcher::paths_with_captures`] does.
+    pub fn paths_with_captures_from_data(
+        &self,
+        data: &[u8],
+    ) -> Result<(Vec<Path>, Captures)> {
+        Ok(self.paths_with_captures(&decode(data)?))
+    }
+
+    /// Like [`Pattern::matches_data`], but takes the encoding as a hex
+    /// string, ignoring surrounding whitespace.
+    pub fn matches_hex(&self, hex: &str) -> Result<bool> {
+        Ok(self.matches(&decode_hex(hex)?))
+    }
+
+    /// Like [`Pattern::paths_from_data`], but takes the encoding as a hex
+    /// string, ignoring surrounding whitespace.
+    pub fn paths_from_hex(&self, hex: &str) -> Result<Vec<Path>> {
+        Ok(self.paths(&decode_hex(hex)?))
+    }
+
+    /// Like [`Pattern::paths_with_captures_from_data`], but takes the
+    /// encoding as a hex string, ignoring surrounding whitespace.
+    pub fn paths_with_captures_from_hex(
+        &self,
+        hex: &str,
+    ) -> Result<(Vec<Path>, Captures)> {
+        Ok(self.paths_with_captures(&decode_hex(hex)?))
+    }
+}
@@ -0,0 +1,304 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{
+    ArrayPattern, MapPattern, Matcher, MetaPattern, Path, Pattern,
+    StructurePattern, TaggedPattern,
+};
+
+/// Where and why a pattern failed to match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchFailure {
+    path: Path,
+    pattern: Pattern,
+}
+
+impl MatchFailure {
+    /// Returns the path from the haystack root to the node the failing
+    /// sub-pattern was matched against.
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Returns the CBOR node the failing sub-pattern was matched against.
+    pub fn node(&self) -> &CBOR {
+        self.path.last().expect("failure paths are never empty")
+    }
+
+    /// Returns the sub-pattern that failed to match.
+    pub fn pattern(&self) -> &Pattern { &self.pattern }
+
+    /// Returns the depth of the failing node, where the root is at depth 0.
+    pub fn depth(&self) -> usize { self.path.len() - 1 }
+}
+
+/// The result of [`Pattern::explain`]: either a match, or the deepest point
+/// at which matching failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchReport {
+    failure: Option<MatchFailure>,
+}
+
+impl MatchReport {
+    /// Returns `true` if the pattern matched.
+    pub fn is_match(&self) -> bool { self.failure.is_none() }
+
+    /// Returns the failure, or `None` if the pattern matched.
+    pub fn failure(&self) -> Option<&MatchFailure> { self.failure.as_ref() }
+}
+
+impl std::fmt::Display for MatchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.failure {
+            None => write!(f, "matched"),
+            Some(failure) => write!(
+                f,
+                "{} did not match {} at depth {}",
+                failure.pattern(),
+                failure.node().diagnostic_flat(),
+                failure.depth()
+            ),
+        }
+    }
+}
+
+impl Pattern {
+    /// Matches this pattern against `haystack` and reports why it failed.
+    ///
+    /// When the pattern does not match, the report names the innermost
+    /// sub-pattern found to fail and the node it was matched against. The
+    /// explanation descends into array elements, map entries, tagged content,
+    /// and `&`, `|`, and capture patterns; other patterns are reported as a
+    /// whole. For sequences with repeats the elements are assigned greedily,
+    /// so the reported element is a best-effort guess at the culprit.
+    pub fn explain(&self, haystack: &CBOR) -> MatchReport {
+        MatchReport { failure: explain_at(self, vec![haystack.clone()]) }
+    }
+}
+
+/// Returns the failure of `pattern` against the last node of `path`, or
+/// `None` if it matches.
+pub(crate) fn explain_at(
+    pattern: &Pattern,
+    path: Path,
+) -> Option<MatchFailure> {
+    let node = path.last().expect("explained paths are never empty");
+    if pattern.matches(node) {
+        return None;
+    }
+    let inner = match pattern {
+        Pattern::Structure(StructurePattern::Array(array)) => {
+            explain_array(pattern, array, &path)
+        }
+        Pattern::Structure(StructurePattern::Map(map)) => {
+            explain_map(map, &path)
+        }
+        Pattern::Structure(StructurePattern::Tagged(tagged)) => {
+            explain_tagged(tagged, &path)
+        }
+        Pattern::Meta(MetaPattern::And(and)) => and
+            .patterns()
+            .iter()
+            .find_map(|pattern| explain_at(pattern, path.clone())),
+        Pattern::Meta(MetaPattern::Or(or)) => deepest(
+            or.patterns()
+                .iter()
+                .filter_map(|pattern| explain_at(pattern, path.clone())),
+        ),
+        Pattern::Meta(MetaPattern::Capture(capture)) => {
+            explain_at(capture.pattern(), path.clone())
+        }
+        _ => None,
+    };
+    Some(inner.unwrap_or(MatchFailure { path, pattern: pattern.clone() }))
+}
+
+/// Returns the failure with the longest path, preferring the first on ties.
+fn deepest(
+    failures: impl Iterator<Item = MatchFailure>,
+) -> Option<MatchFailure> {
+    failures.fold(None, |best: Option<MatchFailure>, failure| match best {
+        Some(best) if best.path.len() >= failure.path.len() => Some(best),
+        _ => Some(failure),
+    })
+}
+
+fn child_path(path: &Path, child: &CBOR) -> Path {
+    let mut child_path = path.clone();
+    child_path.push(child.clone());
+    child_path
+}
+
+fn explain_array(
+    pattern: &Pattern,
+    array: &ArrayPattern,
+    path: &Path,
+) -> Option<MatchFailure> {
+    let elements = match array {
+        ArrayPattern::Elements(elements) => elements,
+        ArrayPattern::LengthAndElements(interval, elements) => {
+            // Blame the array itself if it has the wrong length
+            let CBORCase::Array(arr) = path.last()?.as_case() else {
+                return None;
+            };
+            if !interval.contains(arr.len()) {
+                return None;
+            }
+            elements
+        }
+        _ => return None,
+    };
+    let CBORCase::Array(arr) = path.last()?.as_case() else {
+        return None;
+    };
+    let patterns = match elements.as_ref() {
+        Pattern::Meta(MetaPattern::Sequence(seq)) => seq.patterns(),
+        single => std::slice::from_ref(single),
+    };
+
+    // Walk the sequence, letting each repeat consume as many elements as it
+    // can, and explain the first element that cannot be consumed
+    let mut idx = 0;
+    for element_pattern in patterns {
+        match element_pattern {
+            Pattern::Meta(MetaPattern::Repeat(repeat)) => {
+                let quantifier = repeat.quantifier();
+                let max = quantifier.max().unwrap_or(usize::MAX);
+                let mut count = 0;
+                while count < max
+                    && idx < arr.len()
+                    && repeat.pattern().matches(&arr[idx])
+                {
+                    count += 1;
+                    idx += 1;
+                }
+                if count < quantifier.min() {
+                    return match arr.get(idx) {
+                        Some(element) => explain_at(
+                            repeat.pattern(),
+                            child_path(path, element),
+                        ),
+                        None => Some(MatchFailure {
+                            path: path.clone(),
+                            pattern: element_pattern.clone(),
+                        }),
+                    };
+                }
+            }
+            _ => {
+                let Some(element) = arr.get(idx) else {
+                    // The array ran out of elements for this pattern
+                    return Some(MatchFailure {
+                        path: path.clone(),
+                        pattern: element_pattern.clone(),
+                    });
+                };
+                if !element_pattern.matches(element) {
+                    return explain_at(
+                        element_pattern,
+                        child_path(path, element),
+                    );
+                }
+                idx += 1;
+            }
+        }
+    }
+
+    // Either elements were left over, or the greedy assignment found no
+    // culprit; report the array pattern as a whole
+    Some(MatchFailure { path: path.clone(), pattern: pattern.clone() })
+}
+
+fn explain_map(map: &MapPattern, path: &Path) -> Option<MatchFailure> {
+    // Explain the first entry whose key or value breaks the constraint on
+    // every entry
+    if let MapPattern::AllEntries(key_pattern, value_pattern) = map {
+        let CBORCase::Map(m) = path.last()?.as_case() else {
+            return None;
+        };
+        return m.iter().find_map(|(key, value)| {
+            explain_at(key_pattern, child_path(path, key))
+                .or_else(|| explain_at(value_pattern, child_path(path, value)))
+        });
+    }
+
+    let value_constraint;
+    let constraints: &[(Pattern, Pattern)] = match map {
+        MapPattern::Constraints(constraints) => constraints,
+        MapPattern::Value(key_pattern, value_pattern) => {
+            value_constraint = [(
+                key_pattern.as_ref().clone(),
+                value_pattern.as_ref().clone(),
+            )];
+            &value_constraint
+        }
+        // A map whose rest fails to match is reported as a whole
+        MapPattern::LengthAndConstraints(interval, constraints)
+        | MapPattern::ConstraintsAndRest(interval, constraints, _) => {
+            let CBORCase::Map(m) = path.last()?.as_case() else {
+                return None;
+            };
+            if !interval.contains(m.len()) {
+                return None;
+            }
+            constraints
+        }
+        _ => return None,
+    };
+    let CBORCase::Map(m) = path.last()?.as_case() else {
+        return None;
+    };
+
+    for (key_pattern, value_pattern) in constraints {
+        if m.iter().any(|(key, value)| {
+            key_pattern.matches(key) && value_pattern.matches(value)
+        }) {
+            continue;
+        }
+        // Explain the values of the entries whose keys match, or the key
+        // pattern itself if no key matches
+        let value_failures = m
+            .iter()
+            .filter(|(key, _)| key_pattern.matches(key))
+            .filter_map(|(_, value)| {
+                explain_at(value_pattern, child_path(path, value))
+            });
+        return Some(deepest(value_failures).unwrap_or(MatchFailure {
+            path: path.clone(),
+            pattern: key_pattern.clone(),
+        }));
+    }
+    None
+}
+
+fn explain_tagged(tagged: &TaggedPattern, path: &Path) -> Option<MatchFailure> {
+    let CBORCase::Tagged(tag, content) = path.last()?.as_case() else {
+        return None;
+    };
+
+    // Only blame the content if the tag itself is acceptable
+    let content_pattern = tagged.content_pattern(tag)?;
+    explain_at(content_pattern, child_path(path, content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_match() {
+        let report = Pattern::any_number().explain(&CBOR::from(1));
+        assert!(report.is_match());
+        assert_eq!(report.to_string(), "matched");
+    }
+
+    #[test]
+    fn test_explain_value_failure() {
+        let report = Pattern::any_number().explain(&CBOR::from("a"));
+        let failure = report.failure().unwrap();
+        assert_eq!(failure.pattern(), &Pattern::any_number());
+        assert_eq!(failure.node(), &CBOR::from("a"));
+        assert_eq!(failure.depth(), 0);
+        assert_eq!(
+            report.to_string(),
+            r#"number did not match "a" at depth 0"#
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use dcbor::prelude::*;
+use thiserror::Error;
+
+use crate::pattern::{Matcher, Pattern};
+
+/// Why [`Pattern::extract_one`] or [`Pattern::extract_all`] could not
+/// produce its values, where `E` is the error of the conversion from
+/// [`CBOR`].
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum ExtractError<E> {
+    /// The pattern doesn't match the haystack.
+    #[error("Pattern does not match")]
+    NoMatch,
+
+    /// Nothing was captured under the name: the pattern has no capture of
+    /// that name, or, for [`Pattern::extract_one`], the match didn't pass
+    /// through it.
+    #[error("Nothing captured as @{0}")]
+    MissingCapture(String),
+
+    /// [`Pattern::extract_one`] found more than one value captured under the
+    /// name, holding how many.
+    #[error("Expected one value captured as @{0}, found {1}")]
+    MultipleValues(String, usize),
+
+    /// A captured value could not be converted, holding the capture name,
+    /// the value, and the conversion's error.
+    #[error("Cannot convert {1} captured as @{0}: {2}")]
+    Conversion(String, CBOR, #[source] E),
+}
+
+impl Pattern {
+    /// Matches `haystack` and converts the single value captured as `name`
+    /// to a `T`.
+    ///
+    /// The value is the node that ends the capture's path. Fails if the
+    /// pattern doesn't match, if the match captured nothing or more than one
+    /// value as `name`, or if the value doesn't convert.
+    ///
+    /// ```rust
+    /// # use dcbor_parse::parse_dcbor_item;
+    /// # use dcbor_pattern::Pattern;
+    /// let pattern = Pattern::parse(r#"{"name": @name(text)}"#).unwrap();
+    /// let haystack = parse_dcbor_item(r#"{"name": "Alice"}"#).unwrap();
+    /// let name: String = pattern.extract_one(&haystack, "name").unwrap();
+    /// assert_eq!(name, "Alice");
+    /// ```
+    pub fn extract_one<T: TryFrom<CBOR>>(
+        &self,
+        haystack: &CBOR,
+        name: &str,
+    ) -> Result<T, ExtractError<T::Error>> {
+        let (paths, mut captures) = self.paths_with_captures(haystack);
+        if paths.is_empty() {
+            return Err(ExtractError::NoMatch);
+        }
+        let values = captures.shift_remove(name).unwrap_or_default();
+        match values.as_slice() {
+            [] => Err(ExtractError::MissingCapture(name.to_string())),
+            [path] => convert(name, path.last()),
+            _ => Err(ExtractError::MultipleValues(
+                name.to_string(),
+                values.len(),
+            )),
+        }
+    }
+
+    /// Matches `haystack` and converts each value captured as `name` to a
+    /// `T`, in document order.
+    ///
+    /// Like [`Pattern::extract_one`], this fails if the pattern doesn't match
+    /// or a value doesn't convert, and fails if the pattern has no capture
+    /// named `name`. A match that doesn't pass through the capture, such as
+    /// one taking the other branch of `@n(number) | text`, extracts no
+    /// values.
+    pub fn extract_all<T: TryFrom<CBOR>>(
+        &self,
+        haystack: &CBOR,
+        name: &str,
+    ) -> Result<Vec<T>, ExtractError<T::Error>> {
+        if !self.capture_names().iter().any(|n| n == name) {
+            return Err(ExtractError::MissingCapture(name.to_string()));
+        }
+        let (paths, mut captures) = self.paths_with_captures(haystack);
+        if paths.is_empty() {
+            return Err(ExtractError::NoMatch);
+        }
+        captures
+            .shift_remove(name)
+            .unwrap_or_default()
+            .iter()
+            .map(|path| convert(name, path.last()))
+            .collect()
+    }
+}
+
+/// Converts `value`, the node ending a path captured as `name`.
+fn convert<T: TryFrom<CBOR>>(
+    name: &str,
+    value: Option<&CBOR>,
+) -> Result<T, ExtractError<T::Error>> {
+    let value = value.expect("capture paths are never empty").clone();
+    T::try_from(value.clone()).map_err(|error| {
+        ExtractError::Conversion(name.to_string(), value, error)
+    })
+}
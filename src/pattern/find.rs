@@ -0,0 +1,194 @@
+use dcbor::{Date, prelude::*};
+
+use crate::{
+    Error, Result,
+    pattern::{
+        Axis, Captures, DatePattern, Matcher, Path, Pattern, SearchOpts,
+    },
+};
+
+/// A match found by [`Pattern::find_iter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    path: Path,
+    captures: Captures,
+}
+
+impl Match {
+    /// Returns the node the pattern matched.
+    pub fn node(&self) -> &CBOR {
+        self.path.last().expect("match paths are never empty")
+    }
+
+    /// Returns the path from the haystack root to the matched node.
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Returns the captures made by the match, with paths from the haystack
+    /// root.
+    pub fn captures(&self) -> &Captures { &self.captures }
+
+    /// Consumes the match, returning its path and captures.
+    pub fn into_parts(self) -> (Path, Captures) { (self.path, self.captures) }
+}
+
+/// An iterator over the matches of a pattern within a dCBOR tree, created by
+/// [`Pattern::find_iter`].
+///
+/// Nodes are visited lazily in pre-order, so dropping the iterator early
+/// leaves the rest of the tree unvisited.
+pub struct FindIter<'a> {
+    pattern: &'a Pattern,
+    opts: SearchOpts,
+    now: Date,
+    // The paths still to visit, with the next one last
+    pending: Vec<Path>,
+    visited: usize,
+}
+
+impl FindIter<'_> {
+    /// Returns the number of nodes the pattern has been matched against so
+    /// far.
+    pub fn visited(&self) -> usize { self.visited }
+}
+
+impl Iterator for FindIter<'_> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        while let Some(path) = self.pending.pop() {
+            let node = path.last().expect("pending paths are never empty");
+            for child in
+                self.opts.children(node, path.len() - 1).into_iter().rev()
+            {
+                let mut child_path = path.clone();
+                child_path.push(child);
+                self.pending.push(child_path);
+            }
+
+            self.visited += 1;
+            let (paths, captures) = DatePattern::with_now(self.now, || {
+                self.pattern.paths_with_captures(node)
+            });
+            if paths.is_empty() {
+                continue;
+            }
+
+            // Capture paths start at the matched node, which ends the path
+            let captures = captures
+                .into_iter()
+                .map(|(name, capture_paths)| {
+                    (name, extend_paths(&path, capture_paths))
+                })
+                .collect();
+            return Some(Match { path, captures });
+        }
+        None
+    }
+}
+
+/// Extends `prefix` with each of `paths`, which start at the node that ends
+/// `prefix`.
+fn extend_paths(prefix: &[CBOR], paths: Vec<Path>) -> Vec<Path> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let mut full_path = prefix.to_vec();
+            full_path.extend_from_slice(&path[1..]);
+            full_path
+        })
+        .collect()
+}
+
+/// Returns true if `child` is reachable from `parent` along some axis.
+fn is_child(parent: &CBOR, child: &CBOR) -> bool {
+    [
+        Axis::ArrayElement,
+        Axis::MapKey,
+        Axis::MapValue,
+        Axis::TaggedContent,
+        Axis::MapEntry,
+    ]
+    .iter()
+    .any(|axis| axis.children(parent).contains(child))
+}
+
+/// Checks that `path` starts at `root` and that each of its elements is a
+/// child of the one before it.
+fn validate_path(root: &CBOR, path: &[CBOR]) -> Result<()> {
+    if path.first() != Some(root) {
+        return Err(Error::InconsistentPath(0));
+    }
+    match path
+        .windows(2)
+        .position(|pair| !is_child(&pair[0], &pair[1]))
+    {
+        Some(i) => Err(Error::InconsistentPath(i + 1)),
+        None => Ok(()),
+    }
+}
+
+impl Pattern {
+    /// Returns `true` if this pattern matches the node at the end of `path`,
+    /// a path from `root` such as one returned by an earlier match.
+    ///
+    /// Returns [`Error::InconsistentPath`] if `path` doesn't start at `root`
+    /// or one of its elements isn't a child of the element before it.
+    pub fn matches_at(&self, root: &CBOR, path: &[CBOR]) -> Result<bool> {
+        validate_path(root, path)?;
+        Ok(self.matches(path.last().expect("validated paths are never empty")))
+    }
+
+    /// Matches this pattern against the node at the end of `path`, a path
+    /// from `root`, returning paths and captures that extend `path`.
+    ///
+    /// This lets one pattern find candidates cheaply and another verify them
+    /// in place, keeping the context from `root` in every result. Returns
+    /// [`Error::InconsistentPath`] if `path` doesn't start at `root` or one
+    /// of its elements isn't a child of the element before it.
+    pub fn paths_with_captures_at(
+        &self,
+        root: &CBOR,
+        path: &[CBOR],
+    ) -> Result<(Vec<Path>, Captures)> {
+        validate_path(root, path)?;
+        let node = path.last().expect("validated paths are never empty");
+        let (paths, captures) = self.paths_with_captures(node);
+        let captures = captures
+            .into_iter()
+            .map(|(name, capture_paths)| {
+                (name, extend_paths(path, capture_paths))
+            })
+            .collect();
+        Ok((extend_paths(path, paths), captures))
+    }
+}
+
+impl Pattern {
+    /// Returns an iterator over the nodes of `haystack` this pattern matches,
+    /// in document order.
+    ///
+    /// Every node of the tree, including map keys and tagged content, is
+    /// matched against the pattern, as `search(pattern)` does. Unlike a
+    /// search, the tree is walked lazily, and a value occurring at several
+    /// places is reported at each of them. Relative date patterns are
+    /// evaluated against the time the iterator was created.
+    pub fn find_iter<'a>(&'a self, haystack: &CBOR) -> FindIter<'a> {
+        self.find_iter_with(haystack, SearchOpts::default())
+    }
+
+    /// Returns an iterator over the nodes of `haystack` this pattern matches,
+    /// visiting the nodes selected by the given traversal options.
+    pub fn find_iter_with<'a>(
+        &'a self,
+        haystack: &CBOR,
+        opts: SearchOpts,
+    ) -> FindIter<'a> {
+        FindIter {
+            pattern: self,
+            opts,
+            now: Date::now(),
+            pending: vec![vec![haystack.clone()]],
+            visited: 0,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::pattern::{
+    ArrayPattern, ByteStringPattern, MapPattern, MetaPattern, Pattern,
+    StructurePattern, TaggedPattern, ValuePattern,
+};
+
+/// The kind of a [`Pattern`], as returned by [`Pattern::kind`].
+///
+/// Code that inspects patterns can match on this rather than on the
+/// [`Pattern`] enum and the enums nested in it. New kinds may be added in
+/// minor releases, so matches must include a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PatternKind {
+    /// `bool`, `true`, or `false`.
+    Bool,
+    /// `bstr` and the byte string patterns.
+    ByteString,
+    /// `cbor'...'`, matching a single dCBOR item.
+    Cbor,
+    /// `date` and the date patterns.
+    Date,
+    /// `digest` and the digest patterns.
+    Digest,
+    /// `encoded-digest'...'`.
+    EncodedDigest,
+    /// `known` and the known value patterns.
+    KnownValue,
+    /// `null`.
+    Null,
+    /// `number` and the number patterns.
+    Number,
+    /// `text` and the text patterns.
+    Text,
+    /// `array` and the array patterns, whose child is the pattern for the
+    /// elements, if any, or whose children are the patterns of an unordered
+    /// array.
+    Array,
+    /// `map` and the map patterns, whose children are the key and value
+    /// patterns of each constraint, counted or not, if any, followed by the
+    /// rest pattern, or the key and value patterns every entry must match.
+    Map,
+    /// `tagged` and the tagged patterns, whose child is the pattern for the
+    /// content, if any.
+    Tagged,
+    /// `*`.
+    Any,
+    /// `&`.
+    And,
+    /// `|`.
+    Or,
+    /// `!`.
+    Not,
+    /// `none(...)`.
+    None,
+    /// A quantified or grouped pattern.
+    Repeat,
+    /// `@name(...)`.
+    Capture,
+    /// `child(...)`.
+    Child,
+    /// `entry(...)`, whose children are its key and value patterns.
+    Entry,
+    /// `=name`.
+    Backref,
+    /// `search(...)`.
+    Search,
+    /// A comma-separated sequence of array element patterns.
+    Sequence,
+    /// `untag(...)`.
+    Untag,
+    /// `at(...)`.
+    At,
+    /// `custom(name)`.
+    Custom,
+}
+
+impl Pattern {
+    /// Returns the kind of this pattern.
+    pub fn kind(&self) -> PatternKind {
+        match self {
+            Pattern::Value(value) => match value {
+                ValuePattern::Bool(_) => PatternKind::Bool,
+                ValuePattern::ByteString(_) => PatternKind::ByteString,
+                ValuePattern::Cbor(_) => PatternKind::Cbor,
+                ValuePattern::Date(_) => PatternKind::Date,
+                ValuePattern::Digest(_) => PatternKind::Digest,
+                ValuePattern::EncodedDigest(_) => PatternKind::EncodedDigest,
+                ValuePattern::KnownValue(_) => PatternKind::KnownValue,
+                ValuePattern::Null(_) => PatternKind::Null,
+                ValuePattern::Number(_) => PatternKind::Number,
+                ValuePattern::Text(_) => PatternKind::Text,
+            },
+            Pattern::Structure(structure) => match structure {
+                StructurePattern::Array(_) => PatternKind::Array,
+                StructurePattern::Map(_) => PatternKind::Map,
+                StructurePattern::Tagged(_) => PatternKind::Tagged,
+            },
+            Pattern::Meta(meta) => match meta {
+                MetaPattern::Any(_) => PatternKind::Any,
+                MetaPattern::And(_) => PatternKind::And,
+                MetaPattern::Or(_) => PatternKind::Or,
+                MetaPattern::Not(_) => PatternKind::Not,
+                MetaPattern::None(_) => PatternKind::None,
+                MetaPattern::Repeat(_) => PatternKind::Repeat,
+                MetaPattern::Capture(_) => PatternKind::Capture,
+                MetaPattern::Child(_) => PatternKind::Child,
+                MetaPattern::Entry(_) => PatternKind::Entry,
+                MetaPattern::Backref(_) => PatternKind::Backref,
+                MetaPattern::Search(_) => PatternKind::Search,
+                MetaPattern::Sequence(_) => PatternKind::Sequence,
+                MetaPattern::Untag(_) => PatternKind::Untag,
+                MetaPattern::At(_) => PatternKind::At,
+            },
+            Pattern::Custom(_) => PatternKind::Custom,
+        }
+    }
+
+    /// Returns the sub-patterns directly nested in this pattern, in the order
+    /// they are displayed.
+    ///
+    /// The operands of `&` and `|`, the elements of a sequence, and the
+    /// patterns wrapped by the other combinators are children, as are the
+    /// element pattern of an array or the patterns of an unordered one, the
+    /// key and value patterns of each map constraint and its rest pattern,
+    /// and the content pattern of a tagged pattern. Value patterns,
+    /// back-references, and custom patterns have no children.
+    pub fn children(&self) -> Vec<&Pattern> {
+        match self {
+            Pattern::Value(ValuePattern::ByteString(
+                ByteStringPattern::Decoded(pattern),
+            )) => vec![pattern.as_ref()],
+            Pattern::Value(_) | Pattern::Custom(_) => Vec::new(),
+            Pattern::Structure(structure) => match structure {
+                StructurePattern::Array(array) => match array {
+                    ArrayPattern::Any | ArrayPattern::Length(_) => Vec::new(),
+                    ArrayPattern::Elements(elements)
+                    | ArrayPattern::LengthAndElements(_, elements) => {
+                        vec![elements.as_ref()]
+                    }
+                    ArrayPattern::Unordered(patterns) => {
+                        patterns.iter().collect()
+                    }
+                },
+                StructurePattern::Map(map) => match map {
+                    MapPattern::Any | MapPattern::Length(_) => Vec::new(),
+                    MapPattern::Constraints(constraints)
+                    | MapPattern::LengthAndConstraints(_, constraints) => {
+                        constraints
+                            .iter()
+                            .flat_map(|(key, value)| [key, value])
+                            .collect()
+                    }
+                    MapPattern::Value(key, value)
+                    | MapPattern::AllEntries(key, value) => {
+                        vec![key.as_ref(), value.as_ref()]
+                    }
+                    MapPattern::ConstraintsAndRest(_, constraints, rest) => {
+                        constraints
+                            .iter()
+                            .flat_map(|(key, value)| [key, value])
+                            .chain([rest.as_ref()])
+                            .collect()
+                    }
+                    MapPattern::CountedConstraints(_, constraints, rest) => {
+                        constraints
+                            .iter()
+                            .flat_map(|constraint| {
+                                [constraint.key(), constraint.value()]
+                            })
+                            .chain(rest.as_deref())
+                            .collect()
+                    }
+                },
+                StructurePattern::Tagged(tagged) => match tagged {
+                    TaggedPattern::Any => Vec::new(),
+                    TaggedPattern::AnyTag { pattern }
+                    | TaggedPattern::Tag { pattern, .. }
+                    | TaggedPattern::Name { pattern, .. }
+                    | TaggedPattern::Regex { pattern, .. }
+                    | TaggedPattern::Range { pattern, .. }
+                    | TaggedPattern::Set { pattern, .. } => {
+                        vec![pattern.as_ref()]
+                    }
+                },
+            },
+            Pattern::Meta(meta) => match meta {
+                MetaPattern::Any(_) | MetaPattern::Backref(_) => Vec::new(),
+                MetaPattern::And(and) => and.patterns().iter().collect(),
+                MetaPattern::Or(or) => or.patterns().iter().collect(),
+                MetaPattern::Sequence(sequence) => {
+                    sequence.patterns().iter().collect()
+                }
+                MetaPattern::Not(not) => vec![not.pattern()],
+                MetaPattern::None(none) => vec![none.pattern()],
+                MetaPattern::Repeat(repeat) => vec![repeat.pattern()],
+                MetaPattern::Capture(capture) => vec![capture.pattern()],
+                MetaPattern::Child(child) => vec![child.pattern()],
+                MetaPattern::Entry(entry) => vec![entry.key(), entry.value()],
+                MetaPattern::Search(search) => vec![search.pattern()],
+                MetaPattern::Untag(untag) => vec![untag.pattern()],
+                MetaPattern::At(at) => vec![at.pattern()],
+            },
+        }
+    }
+
+    /// Visits this pattern and every sub-pattern nested in it in pre-order,
+    /// calling `f` with each and its depth, where this pattern is at depth 0.
+    ///
+    /// Each pattern is visited before its [`children`](Pattern::children),
+    /// and each child's sub-patterns before the next child.
+    pub fn walk(&self, f: &mut dyn FnMut(&Pattern, usize)) {
+        self.walk_at(0, f);
+    }
+
+    fn walk_at(&self, depth: usize, f: &mut dyn FnMut(&Pattern, usize)) {
+        f(self, depth);
+        for child in self.children() {
+            child.walk_at(depth + 1, f);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use dcbor::prelude::*;
+
+use crate::{
+    FormatPathsOpts, format_paths_with_captures,
+    pattern::{Captures, Matcher, Path, Pattern},
+};
+
+/// The reserved capture name under which [`MatchResult::with_root_capture`]
+/// and [`FormatPathsOpts::root_capture`] store the paths of the whole match.
+///
+/// Capture names in pattern text must start with a letter or underscore, so
+/// no capture a parsed pattern makes can have this name.
+pub const ROOT_CAPTURE: &str = "$match";
+
+/// Returns `captures` with `paths` stored first, under [`ROOT_CAPTURE`], or
+/// `captures` unchanged if there are no paths.
+pub(crate) fn with_root_capture(
+    paths: &[Path],
+    captures: Captures,
+) -> Captures {
+    if paths.is_empty() {
+        return captures;
+    }
+    let mut with_root = Captures::with_capacity(captures.len() + 1);
+    with_root.insert(ROOT_CAPTURE.to_string(), paths.to_vec());
+    with_root.extend(captures);
+    with_root
+}
+
+/// The result of matching a pattern against a haystack, returned by
+/// [`Pattern::run`]: the paths the pattern matched, its captures, and the
+/// text of the pattern that produced them.
+///
+/// With the `serde` feature, a result serializes with every node of its
+/// paths stored as the hex of its deterministic encoding, so that it
+/// deserializes to an equal result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchResult {
+    pattern: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::paths"))]
+    paths: Vec<Path>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_support::captures")
+    )]
+    captures: Captures,
+}
+
+impl MatchResult {
+    /// Creates a result from the paths and captures `pattern` produced.
+    pub fn new(
+        pattern: impl Into<String>,
+        paths: Vec<Path>,
+        captures: Captures,
+    ) -> Self {
+        MatchResult { pattern: pattern.into(), paths, captures }
+    }
+
+    /// Returns the text of the pattern that produced this result.
+    pub fn pattern(&self) -> &str { &self.pattern }
+
+    /// Returns the paths the pattern matched, in document order.
+    pub fn paths(&self) -> &[Path] { &self.paths }
+
+    /// Returns the captures, by name in the order they were first made.
+    pub fn captures(&self) -> &Captures { &self.captures }
+
+    /// Returns the paths captured as `name`, or `None` if the pattern made
+    /// no capture with that name.
+    pub fn capture(&self, name: &str) -> Option<&[Path]> {
+        self.captures.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns the first path the pattern matched, if any.
+    pub fn first(&self) -> Option<&Path> { self.paths.first() }
+
+    /// Returns `true` if the pattern matched nothing.
+    pub fn is_empty(&self) -> bool { self.paths.is_empty() }
+
+    /// Formats the paths and captures as [`format_paths_with_captures`]
+    /// does with `opts`.
+    pub fn format_opt(&self, opts: impl AsRef<FormatPathsOpts>) -> String {
+        format_paths_with_captures(&self.paths, &self.captures, opts)
+    }
+
+    /// Returns this result with its paths also stored as the first capture,
+    /// named [`ROOT_CAPTURE`], for consumers that read everything from the
+    /// captures. A result with no paths is returned unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use dcbor::prelude::*;
+    /// # use dcbor_pattern::{Pattern, ROOT_CAPTURE};
+    /// let haystack = CBOR::from(vec![1, 2]);
+    /// let pattern = Pattern::parse("[@first(number), number]").unwrap();
+    /// let result = pattern.run(&haystack).with_root_capture();
+    /// assert_eq!(result.capture(ROOT_CAPTURE), Some(result.paths()));
+    /// assert_eq!(
+    ///     result.captures().keys().collect::<Vec<_>>(),
+    ///     ["$match", "first"]
+    /// );
+    /// ```
+    pub fn with_root_capture(mut self) -> Self {
+        self.captures = with_root_capture(&self.paths, self.captures);
+        self
+    }
+
+    /// Consumes the result, returning its paths and captures.
+    pub fn into_parts(self) -> (Vec<Path>, Captures) {
+        (self.paths, self.captures)
+    }
+}
+
+/// Formats the paths and captures with the default options of
+/// [`format_paths_with_captures`].
+impl std::fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.format_opt(FormatPathsOpts::default()))
+    }
+}
+
+impl Pattern {
+    /// Matches this pattern against `haystack`, returning the paths and
+    /// captures [`Matcher::paths_with_captures`] does, together with the
+    /// pattern's text.
+    pub fn run(&self, haystack: &CBOR) -> MatchResult {
+        let (paths, captures) = self.paths_with_captures(haystack);
+        MatchResult::new(self.to_string(), paths, captures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_result_accessors() {
+        let haystack = CBOR::from(vec![1, 2, 3]);
+        let pattern = Pattern::parse("[@first(number), (@rest(*))*]").unwrap();
+        let result = pattern.run(&haystack);
+
+        assert_eq!(result.pattern(), "[@first(number), (@rest(*))*]");
+        assert!(!result.is_empty());
+        assert_eq!(result.first(), Some(&vec![haystack.clone()]));
+        assert_eq!(result.paths(), [vec![haystack.clone()]]);
+        assert_eq!(
+            result.capture("first"),
+            Some(&[vec![haystack.clone(), CBOR::from(1)]][..])
+        );
+        assert_eq!(result.capture("rest").map(<[Path]>::len), Some(2));
+        assert_eq!(
+            result.captures().keys().collect::<Vec<_>>(),
+            ["first", "rest"]
+        );
+
+        // Names the pattern doesn't capture, or capture nothing for
+        assert_eq!(result.capture("missing"), None);
+        let result = pattern.run(&CBOR::from(vec![1]));
+        assert_eq!(result.capture("rest"), None);
+
+        // The old API returns the same parts
+        assert_eq!(
+            pattern.run(&haystack).into_parts(),
+            pattern.paths_with_captures(&haystack)
+        );
+    }
+
+    #[test]
+    fn test_match_result_empty() {
+        let result = Pattern::parse("@n(text)").unwrap().run(&CBOR::from(1));
+        assert!(result.is_empty());
+        assert_eq!(result.first(), None);
+        assert_eq!(result.capture("n"), None);
+        assert_eq!(result.to_string(), "");
+    }
+
+    #[test]
+    fn test_match_result_display() {
+        let haystack = CBOR::from(vec![1, 2]);
+        let pattern = Pattern::parse("[@a(number), number]").unwrap();
+        let (paths, captures) = pattern.paths_with_captures(&haystack);
+        let result = pattern.run(&haystack);
+        assert_eq!(
+            result.to_string(),
+            format_paths_with_captures(
+                &paths,
+                &captures,
+                FormatPathsOpts::default()
+            )
+        );
+        assert_eq!(result.to_string(), "@a\n    [1, 2]\n        1\n[1, 2]");
+        assert_eq!(
+            result.format_opt(FormatPathsOpts::new().last_element_only(true)),
+            "@a\n    1\n[1, 2]"
+        );
+    }
+}
@@ -1,19 +1,14 @@
-use std::collections::HashMap;
-
 use dcbor::prelude::*;
 
 use crate::{
-    Path,
+    Captures, Path,
     pattern::{Pattern, vm::Instr},
 };
 
 #[doc(hidden)]
 pub trait Matcher: std::fmt::Debug + std::fmt::Display + Clone {
     /// Return all matching paths along with any named captures.
-    fn paths_with_captures(
-        &self,
-        _haystack: &CBOR,
-    ) -> (Vec<Path>, HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, _haystack: &CBOR) -> (Vec<Path>, Captures) {
         unimplemented!(
             "Matcher::paths_with_captures not implemented for {:?}",
             self
@@ -25,10 +20,16 @@ pub trait Matcher: std::fmt::Debug + std::fmt::Display + Clone {
         self.paths_with_captures(haystack).0
     }
 
+    /// Return whether the pattern matches the root of `haystack`, rather than
+    /// some node within it, as [`Pattern::exists`] tests.
     fn matches(&self, haystack: &CBOR) -> bool {
         !self.paths(haystack).is_empty()
     }
 
+    /// Appends the VM instructions for this pattern to `code`, with the
+    /// patterns they match in `literals` and capture names in `captures`.
+    /// [`Program::compile`](crate::Program::compile) compiles a whole pattern
+    /// into a program that can be run.
     fn compile(
         &self,
         _code: &mut Vec<Instr>,
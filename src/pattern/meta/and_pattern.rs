@@ -1,9 +1,16 @@
 use dcbor::prelude::*;
+use indexmap::IndexMap;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{
+    Captures, Matcher, MetaPattern, Path, Pattern,
+    simplify::matches_in_place,
+    structure::ends_with_map_value,
+    vm::{Instr, capture_slots, merge_captures},
+};
 
 /// A pattern that matches if all contained patterns match.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AndPattern(Vec<Pattern>);
 
 impl AndPattern {
@@ -12,6 +19,33 @@ impl AndPattern {
 
     /// Returns the patterns contained in this AND pattern.
     pub fn patterns(&self) -> &[Pattern] { &self.0 }
+
+    /// Adds `pattern` as the last pattern that must match, or, if it is
+    /// itself an AND pattern, adds each of its patterns in turn.
+    pub fn push(&mut self, pattern: Pattern) {
+        match pattern {
+            Pattern::Meta(MetaPattern::And(nested)) => self.0.extend(nested.0),
+            pattern => self.0.push(pattern),
+        }
+    }
+}
+
+impl Extend<Pattern> for AndPattern {
+    fn extend<I: IntoIterator<Item = Pattern>>(&mut self, patterns: I) {
+        for pattern in patterns {
+            self.push(pattern);
+        }
+    }
+}
+
+/// Collects patterns into an AND pattern as [`AndPattern::push`] adds them,
+/// flattening any that are themselves AND patterns.
+impl FromIterator<Pattern> for AndPattern {
+    fn from_iter<I: IntoIterator<Item = Pattern>>(patterns: I) -> Self {
+        let mut pattern = AndPattern(Vec::new());
+        pattern.extend(patterns);
+        pattern
+    }
 }
 
 impl Matcher for AndPattern {
@@ -27,29 +61,30 @@ impl Matcher for AndPattern {
         }
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         // For AND patterns, all patterns must match, and we merge captures
-        let mut all_captures = std::collections::HashMap::new();
-
+        let mut groups: IndexMap<String, Vec<Vec<((), Path)>>> =
+            IndexMap::new();
         for pattern in self.patterns() {
             let (paths, captures) = pattern.paths_with_captures(haystack);
             if paths.is_empty() {
                 // If any pattern fails to match, AND fails
-                return (vec![], std::collections::HashMap::new());
+                return (vec![], Captures::new());
             }
-
-            // Merge captures
             for (name, capture_paths) in captures {
-                all_captures
-                    .entry(name)
-                    .or_insert_with(Vec::new)
-                    .extend(capture_paths);
+                groups.entry(name).or_default().push(
+                    capture_paths.into_iter().map(|path| ((), path)).collect(),
+                );
             }
         }
 
+        // A name captured by several patterns keeps each path once, unless
+        // one pattern captured it more than once
+        let all_captures = groups
+            .into_iter()
+            .map(|(name, groups)| (name, merge_captures(&groups)))
+            .collect();
+
         // If all patterns matched, return the basic path and merged captures
         (vec![vec![haystack.clone()]], all_captures)
     }
@@ -61,9 +96,19 @@ impl Matcher for AndPattern {
         lits: &mut Vec<Pattern>,
         captures: &mut Vec<String>,
     ) {
-        // Each pattern must match at this position
-        for pattern in self.patterns() {
-            pattern.compile(code, lits, captures);
+        if self.patterns().iter().all(matches_in_place) {
+            // Each pattern must match at this position
+            for pattern in self.patterns() {
+                pattern.compile(code, lits, captures);
+            }
+        } else {
+            // A pattern whose path moves away from this position would leave
+            // the next to match at the end of its path, so the patterns are
+            // matched together, as they are directly
+            let pattern = Pattern::Meta(MetaPattern::And(self.clone()));
+            capture_slots(&pattern, captures);
+            code.push(Instr::MatchStructure(lits.len()));
+            lits.push(pattern);
         }
     }
 
@@ -89,7 +134,17 @@ impl std::fmt::Display for AndPattern {
             "{}",
             self.patterns()
                 .iter()
-                .map(|p| p.to_string())
+                .enumerate()
+                .map(|(i, p)| match p {
+                    // `|` binds more loosely than `&`
+                    Pattern::Meta(MetaPattern::Or(_)) => format!("({})", p),
+                    _ if i + 1 < self.patterns().len()
+                        && ends_with_map_value(p) =>
+                    {
+                        format!("({})", p)
+                    }
+                    _ => p.to_string(),
+                })
                 .collect::<Vec<_>>()
                 .join(" & ")
         )
@@ -108,6 +163,15 @@ mod tests {
         assert_eq!(and_pattern.to_string(), ">5 & <10");
     }
 
+    #[test]
+    fn test_and_pattern_display_or_operand() {
+        let or_pattern =
+            Pattern::or(vec![Pattern::number(1), Pattern::number(2)]);
+        let and_pattern =
+            AndPattern::new(vec![or_pattern, Pattern::any_number()]);
+        assert_eq!(and_pattern.to_string(), "(1 | 2) & number");
+    }
+
     #[test]
     fn test_and_pattern_matches_when_all_patterns_match() {
         let pattern = AndPattern::new(vec![
@@ -1,9 +1,10 @@
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{Captures, Matcher, Path, Pattern, vm::Instr};
 
 /// A pattern that always matches any CBOR value.
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnyPattern;
 
 impl AnyPattern {
@@ -21,23 +22,21 @@ impl Matcher for AnyPattern {
         vec![vec![haystack.clone()]]
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         // AnyPattern has no internal captures, so just return paths and empty
         // captures
-        (self.paths(haystack), std::collections::HashMap::new())
+        (self.paths(haystack), Captures::new())
     }
 
     fn compile(
         &self,
-        code: &mut Vec<Instr>,
+        _code: &mut Vec<Instr>,
         _literals: &mut Vec<Pattern>,
         _captures: &mut Vec<String>,
     ) {
-        // Any pattern always matches - just save the current path
-        code.push(Instr::Save);
+        // Any pattern always matches and stays at the current node, so there
+        // is nothing to emit. Saving the path here would report the node as a
+        // match of its own, even when it is an element of an enclosing array.
     }
 
     fn collect_capture_names(&self, _names: &mut Vec<String>) {
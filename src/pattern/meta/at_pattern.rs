@@ -0,0 +1,210 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{
+    Captures, Matcher, Path, Pattern, value::quoted, vm::Instr,
+};
+
+/// One step of an [`AtPattern`]: the value under a text key of a map, or the
+/// element at an index of an array.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathStep {
+    /// The value stored under this text key of a map.
+    Key(String),
+    /// The element at this index of an array.
+    Index(usize),
+}
+
+impl PathStep {
+    /// Returns the child this step leads to from `cbor`, along with its
+    /// position among the children of `cbor`, or `None` if `cbor` has no
+    /// such child.
+    pub(crate) fn child(&self, cbor: &CBOR) -> Option<(usize, CBOR)> {
+        match (self, cbor.as_case()) {
+            (PathStep::Key(key), CBORCase::Map(map)) => {
+                let key = CBOR::from(key.as_str());
+                map.iter()
+                    .enumerate()
+                    .find(|(_, (k, _))| **k == key)
+                    .map(|(index, (_, v))| (index, v.clone()))
+            }
+            (PathStep::Index(index), CBORCase::Array(array)) => {
+                array.get(*index).map(|element| (*index, element.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for PathStep {
+    fn from(key: &str) -> Self { PathStep::Key(key.to_string()) }
+}
+
+impl From<String> for PathStep {
+    fn from(key: String) -> Self { PathStep::Key(key) }
+}
+
+impl From<usize> for PathStep {
+    fn from(index: usize) -> Self { PathStep::Index(index) }
+}
+
+impl std::fmt::Display for PathStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathStep::Key(key) => write!(f, "{}", quoted(key)),
+            PathStep::Index(index) => write!(f, "{}", index),
+        }
+    }
+}
+
+/// A pattern that navigates from a node to a single descendant and matches a
+/// pattern there.
+///
+/// Each step descends either to the value under a text key of a map or to
+/// the element at an index of an array. A missing key, an index out of
+/// bounds, or a step into a value of the wrong kind means no match. A match
+/// yields a path that runs from the node through every value stepped into,
+/// then on along the inner pattern's path from the destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AtPattern {
+    steps: Vec<PathStep>,
+    pattern: Box<Pattern>,
+}
+
+impl AtPattern {
+    /// Creates a new `AtPattern` that follows `steps` and matches `pattern`
+    /// at the destination.
+    pub fn new(steps: Vec<PathStep>, pattern: Pattern) -> Self {
+        AtPattern { steps, pattern: Box::new(pattern) }
+    }
+
+    /// Returns the steps from the node to the destination.
+    pub fn steps(&self) -> &[PathStep] { &self.steps }
+
+    /// Returns the pattern matched at the destination.
+    pub fn pattern(&self) -> &Pattern { &self.pattern }
+
+    /// Returns the path from `haystack` to the destination, or `None` if a
+    /// step has nowhere to go.
+    fn navigate(&self, haystack: &CBOR) -> Option<Path> {
+        let mut path = vec![haystack.clone()];
+        for step in &self.steps {
+            let (_, child) = step.child(path.last().unwrap())?;
+            path.push(child);
+        }
+        Some(path)
+    }
+}
+
+impl Default for AtPattern {
+    fn default() -> Self { Self::new(Vec::new(), Pattern::any()) }
+}
+
+impl Matcher for AtPattern {
+    fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        self.paths_with_captures(haystack).0
+    }
+
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        let Some(route) = self.navigate(haystack) else {
+            return (Vec::new(), Captures::new());
+        };
+        let (node, prefix) = route.split_last().unwrap();
+        let (paths, captures) = self.pattern().paths_with_captures(node);
+
+        // Paths are rooted at the destination, so prefix them with the nodes
+        // stepped through on the way there
+        let with_prefix = |path: Path| {
+            let mut full_path = prefix.to_vec();
+            full_path.extend(path);
+            full_path
+        };
+        let paths = paths.into_iter().map(with_prefix).collect();
+        let captures = captures
+            .into_iter()
+            .map(|(name, capture_paths)| {
+                (name, capture_paths.into_iter().map(with_prefix).collect())
+            })
+            .collect();
+        (paths, captures)
+    }
+
+    /// Compile into byte-code: one step per path step, each descending to
+    /// at most one child, then the inner pattern at the destination.
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        captures: &mut Vec<String>,
+    ) {
+        for step in &self.steps {
+            code.push(Instr::Step(step.clone()));
+        }
+        self.pattern().compile(code, literals, captures);
+    }
+
+    fn collect_capture_names(&self, names: &mut Vec<String>) {
+        self.pattern().collect_capture_names(names);
+    }
+}
+
+impl std::fmt::Display for AtPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at(")?;
+        for step in &self.steps {
+            write!(f, "{}, ", step)?;
+        }
+        write!(f, "{})", self.pattern())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_at_pattern_display() {
+        let pattern = AtPattern::new(
+            vec!["items".into(), 2.into()],
+            Pattern::any_number(),
+        );
+        assert_eq!(pattern.to_string(), r#"at("items", 2, number)"#);
+
+        let pattern =
+            AtPattern::new(vec![r#"a "b""#.into()], Pattern::any_text());
+        assert_eq!(pattern.to_string(), r#"at("a \"b\"", text)"#);
+
+        let pattern = AtPattern::new(Vec::new(), Pattern::any());
+        assert_eq!(pattern.to_string(), "at(*)");
+    }
+
+    #[test]
+    fn test_at_pattern_paths() {
+        let items = CBOR::from(vec![1, 2, 3]);
+        let mut map = Map::new();
+        map.insert("items", items.clone());
+        let haystack = CBOR::from(map);
+
+        let pattern = AtPattern::new(
+            vec!["items".into(), 2.into()],
+            Pattern::any_number(),
+        );
+        assert_eq!(
+            pattern.paths(&haystack),
+            vec![vec![haystack.clone(), items.clone(), CBOR::from(3)]]
+        );
+
+        // Missing keys, indexes out of bounds, and steps into the wrong kind
+        // of value don't match
+        for steps in [
+            vec![PathStep::from("other")],
+            vec!["items".into(), 3.into()],
+            vec![0.into()],
+            vec!["items".into(), "0".into()],
+        ] {
+            let pattern = AtPattern::new(steps, Pattern::any());
+            assert!(pattern.paths(&haystack).is_empty(), "{}", pattern);
+        }
+    }
+}
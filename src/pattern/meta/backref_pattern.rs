@@ -0,0 +1,155 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{Matcher, MetaPattern, Path, Pattern, vm::Instr};
+
+/// Values bound by captures while matching a sequence or map, in the order
+/// they were bound. Later bindings shadow earlier ones with the same name.
+pub(crate) type Bindings = Vec<(String, CBOR)>;
+
+/// A pattern that matches a value equal to one captured earlier in the same
+/// match.
+///
+/// Written `=name`, a back-reference matches when the current value equals
+/// the value most recently captured by `@name(...)`. Bindings are made by
+/// captures in earlier elements of an array sequence or in earlier entries
+/// of a map pattern, and by captures compiled into the same VM program.
+///
+/// If the referenced group has not captured anything yet, including when the
+/// back-reference is matched on its own, the back-reference does not match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackrefPattern(String);
+
+impl BackrefPattern {
+    /// Creates a new `BackrefPattern` referring to the capture with the given
+    /// name.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        BackrefPattern(name.as_ref().to_string())
+    }
+
+    /// Returns the name of the referenced capture.
+    pub fn name(&self) -> &str { &self.0 }
+
+    /// Returns the value most recently bound to the referenced capture.
+    pub(crate) fn resolve<'a>(
+        &self,
+        bindings: &'a [(String, CBOR)],
+    ) -> Option<&'a CBOR> {
+        bindings
+            .iter()
+            .rev()
+            .find(|(name, _)| name == self.name())
+            .map(|(_, value)| value)
+    }
+}
+
+impl Matcher for BackrefPattern {
+    fn paths(&self, _haystack: &CBOR) -> Vec<Path> {
+        // Without any bindings the referenced group has not captured yet
+        vec![]
+    }
+
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        _literals: &mut Vec<Pattern>,
+        captures: &mut Vec<String>,
+    ) {
+        // Refer to the capture slot of the named group, reserving one if the
+        // group has not been compiled yet; an empty slot never matches
+        let idx = match captures.iter().position(|name| name == self.name()) {
+            Some(idx) => idx,
+            None => {
+                captures.push(self.name().to_string());
+                captures.len() - 1
+            }
+        };
+        code.push(Instr::MatchBackref(idx));
+    }
+}
+
+impl std::fmt::Display for BackrefPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "={}", self.name())
+    }
+}
+
+/// Returns true if `pattern` is a back-reference.
+pub(crate) fn is_backref(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Meta(MetaPattern::Backref(_)))
+}
+
+/// Matches `pattern` against `cbor`, resolving a back-reference against
+/// `bindings`.
+pub(crate) fn matches_with_bindings(
+    pattern: &Pattern,
+    cbor: &CBOR,
+    bindings: &[(String, CBOR)],
+) -> bool {
+    match pattern {
+        Pattern::Meta(MetaPattern::Backref(backref)) => {
+            backref.resolve(bindings) == Some(cbor)
+        }
+        _ => pattern.matches(cbor),
+    }
+}
+
+/// Binds each capture made by matching `pattern` against `cbor` to the value
+/// at the end of its most recent capture path.
+pub(crate) fn bind_captures(
+    pattern: &Pattern,
+    cbor: &CBOR,
+    bindings: &mut Bindings,
+) {
+    let (_, captures) = pattern.paths_with_captures(cbor);
+    // Sort for a deterministic binding order
+    let mut captures: Vec<_> = captures.into_iter().collect();
+    captures.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, paths) in captures {
+        if let Some(value) = paths.last().and_then(|path| path.last()) {
+            bindings.push((name, value.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backref_display() {
+        assert_eq!(BackrefPattern::new("x").to_string(), "=x");
+    }
+
+    #[test]
+    fn test_backref_without_binding_does_not_match() {
+        let pattern = BackrefPattern::new("x");
+        assert!(!pattern.matches(&CBOR::from(1)));
+
+        let pattern = Pattern::backref("x");
+        let bindings = vec![("x".to_string(), CBOR::from(1))];
+        assert!(matches_with_bindings(&pattern, &CBOR::from(1), &bindings));
+        assert!(!matches_with_bindings(&pattern, &CBOR::from(2), &bindings));
+        assert!(!matches_with_bindings(&pattern, &CBOR::from(1), &[]));
+    }
+
+    #[test]
+    fn test_backref_compiled_to_vm() {
+        fn run(pattern: &Pattern, cbor: &CBOR) -> Vec<Path> {
+            crate::pattern::vm::Program::compile(pattern).run(cbor).0
+        }
+
+        let one = CBOR::from(1);
+        let bound = Pattern::and(vec![
+            Pattern::capture("x", Pattern::any_number()),
+            Pattern::backref("x"),
+        ]);
+        assert_eq!(run(&bound, &one), vec![vec![one.clone()]]);
+
+        let unbound = Pattern::and(vec![
+            Pattern::backref("x"),
+            Pattern::capture("x", Pattern::any_number()),
+        ]);
+        assert!(run(&unbound, &one).is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{Captures, Matcher, Path, Pattern, vm::Instr};
 
 /// A pattern that captures matches.
 ///
@@ -8,6 +8,7 @@ use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
 /// that match. The captured paths can be retrieved when pattern matching
 /// is performed.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CapturePattern {
     name: String,
     pattern: Box<Pattern>,
@@ -70,10 +71,7 @@ impl Matcher for CapturePattern {
         self.pattern.is_complex()
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         // Get paths from the inner pattern
         let (paths, mut captures) = self.pattern.paths_with_captures(haystack);
 
@@ -0,0 +1,152 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{
+    Captures, Matcher, Path, Pattern,
+    vm::{Axis, Instr},
+};
+
+/// The axes a [`ChildPattern`] descends along, in the order children are
+/// visited.
+const CHILD_AXES: [Axis; 4] = [
+    Axis::ArrayElement,
+    Axis::MapKey,
+    Axis::MapValue,
+    Axis::TaggedContent,
+];
+
+/// A pattern that matches the direct children of a node.
+///
+/// From the current node, this pattern steps one level down along every axis
+/// (array elements, map keys, map values, and tagged content) and applies the
+/// inner pattern to each child. Each matching child yields a path of length 2:
+/// the current node followed by the child.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChildPattern(Box<Pattern>);
+
+impl ChildPattern {
+    /// Creates a new `ChildPattern` that matches children against the given
+    /// pattern.
+    pub fn new(pattern: Pattern) -> Self { ChildPattern(Box::new(pattern)) }
+
+    /// Returns the pattern applied to each child.
+    pub fn pattern(&self) -> &Pattern { &self.0 }
+
+    fn children(haystack: &CBOR) -> impl Iterator<Item = CBOR> + '_ {
+        CHILD_AXES.iter().flat_map(|axis| axis.children(haystack))
+    }
+}
+
+impl Default for ChildPattern {
+    fn default() -> Self { Self::new(Pattern::any()) }
+}
+
+impl Matcher for ChildPattern {
+    fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        Self::children(haystack)
+            .filter(|child| self.pattern().matches(child))
+            .map(|child| vec![haystack.clone(), child])
+            .collect()
+    }
+
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        let mut paths = Vec::new();
+        let mut all_captures = Captures::new();
+
+        for child in Self::children(haystack) {
+            let (child_paths, captures) =
+                self.pattern().paths_with_captures(&child);
+            if child_paths.is_empty() {
+                continue;
+            }
+            paths.push(vec![haystack.clone(), child]);
+
+            // Capture paths are rooted at the child, so prefix them with the
+            // current node
+            for (name, capture_paths) in captures {
+                let entry: &mut Vec<Path> =
+                    all_captures.entry(name).or_default();
+                for capture_path in capture_paths {
+                    let mut full_path = vec![haystack.clone()];
+                    full_path.extend(capture_path);
+                    entry.push(full_path);
+                }
+            }
+        }
+
+        (paths, all_captures)
+    }
+
+    /// Compile into byte-code: one branch per axis, each descending to the
+    /// children along that axis and matching the inner pattern there.
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        captures: &mut Vec<String>,
+    ) {
+        let mut jumps = Vec::new();
+        for (i, axis) in CHILD_AXES.iter().enumerate() {
+            let split = (i < CHILD_AXES.len() - 1).then(|| {
+                code.push(Instr::Split { a: 0, b: 0 }); // Placeholder
+                code.len() - 1
+            });
+
+            let branch_start = code.len();
+            code.push(Instr::PushAxis(*axis));
+            self.pattern().compile(code, literals, captures);
+            // Stay at the child; the path ends there
+            code.push(Instr::DropAxis);
+            jumps.push(code.len());
+            code.push(Instr::Jump(0)); // Placeholder
+
+            if let Some(split) = split {
+                code[split] = Instr::Split { a: branch_start, b: code.len() };
+            }
+        }
+
+        let end = code.len();
+        for jump in jumps {
+            code[jump] = Instr::Jump(end);
+        }
+    }
+
+    fn collect_capture_names(&self, names: &mut Vec<String>) {
+        self.pattern().collect_capture_names(names);
+    }
+}
+
+impl std::fmt::Display for ChildPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "child({})", self.pattern())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_pattern_display() {
+        let pattern = ChildPattern::new(Pattern::any_number());
+        assert_eq!(pattern.to_string(), "child(number)");
+    }
+
+    #[test]
+    fn test_child_pattern_paths() {
+        let pattern = ChildPattern::new(Pattern::any_number());
+
+        let array = CBOR::from(vec![CBOR::from(1), CBOR::from("a")]);
+        assert_eq!(
+            pattern.paths(&array),
+            vec![vec![array.clone(), CBOR::from(1)]]
+        );
+
+        // Children are not searched recursively
+        let nested = CBOR::from(vec![CBOR::from(vec![CBOR::from(1)])]);
+        assert!(pattern.paths(&nested).is_empty());
+
+        // Leaf values have no children
+        assert!(pattern.paths(&CBOR::from(1)).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{
+    ArrayPattern, Captures, Matcher, Path, Pattern, StructurePattern,
+    vm::{Axis, Instr},
+};
+
+/// A pattern that matches the entries of a map as key-value pairs.
+///
+/// Unlike a `{key: value}` map pattern, which matches the map itself, this
+/// pattern yields one path per matching entry. Each entry is represented by a
+/// synthetic two-element array `[key, value]`, so a path has length 2: the map
+/// followed by the entry. Captures made by the key and value patterns run
+/// through the entry to the key or value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntryPattern {
+    key: Box<Pattern>,
+    value: Box<Pattern>,
+}
+
+impl EntryPattern {
+    /// Creates a new `EntryPattern` that matches entries whose key and value
+    /// match the given patterns.
+    pub fn new(key: Pattern, value: Pattern) -> Self {
+        EntryPattern { key: Box::new(key), value: Box::new(value) }
+    }
+
+    /// Returns the pattern applied to each entry's key.
+    pub fn key(&self) -> &Pattern { &self.key }
+
+    /// Returns the pattern applied to each entry's value.
+    pub fn value(&self) -> &Pattern { &self.value }
+
+    /// Splits an entry produced by [`Axis::MapEntry`] into its key and value.
+    fn split(entry: &CBOR) -> (&CBOR, &CBOR) {
+        match entry.as_case() {
+            CBORCase::Array(pair) => (&pair[0], &pair[1]),
+            _ => unreachable!("map entries are key-value arrays"),
+        }
+    }
+}
+
+impl Default for EntryPattern {
+    fn default() -> Self { Self::new(Pattern::any(), Pattern::any()) }
+}
+
+impl Matcher for EntryPattern {
+    fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        Axis::MapEntry
+            .children(haystack)
+            .into_iter()
+            .filter(|entry| {
+                let (key, value) = Self::split(entry);
+                self.key().matches(key) && self.value().matches(value)
+            })
+            .map(|entry| vec![haystack.clone(), entry])
+            .collect()
+    }
+
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        let mut paths = Vec::new();
+        let mut all_captures = Captures::new();
+
+        for entry in Axis::MapEntry.children(haystack) {
+            let (key, value) = Self::split(&entry);
+            let (key_paths, key_captures) = self.key().paths_with_captures(key);
+            if key_paths.is_empty() {
+                continue;
+            }
+            let (value_paths, value_captures) =
+                self.value().paths_with_captures(value);
+            if value_paths.is_empty() {
+                continue;
+            }
+
+            // Capture paths are rooted at the key or value, so prefix them
+            // with the map and the entry
+            let prefix = vec![haystack.clone(), entry.clone()];
+            for (name, capture_paths) in
+                key_captures.into_iter().chain(value_captures)
+            {
+                let entry_captures: &mut Vec<Path> =
+                    all_captures.entry(name).or_default();
+                for capture_path in capture_paths {
+                    let mut full_path = prefix.clone();
+                    full_path.extend(capture_path);
+                    entry_captures.push(full_path);
+                }
+            }
+            paths.push(prefix);
+        }
+
+        (paths, all_captures)
+    }
+
+    /// Compile into byte-code: descend to each entry and match it as the
+    /// array `[key, value]`, staying at the entry.
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        captures: &mut Vec<String>,
+    ) {
+        self.collect_capture_names(captures);
+
+        code.push(Instr::PushAxis(Axis::MapEntry));
+        let idx = literals.len();
+        literals.push(Pattern::Structure(StructurePattern::Array(
+            ArrayPattern::with_elements(Pattern::sequence(vec![
+                self.key().clone(),
+                self.value().clone(),
+            ])),
+        )));
+        code.push(Instr::MatchStructure(idx));
+        code.push(Instr::DropAxis);
+    }
+
+    fn collect_capture_names(&self, names: &mut Vec<String>) {
+        self.key().collect_capture_names(names);
+        self.value().collect_capture_names(names);
+    }
+}
+
+impl std::fmt::Display for EntryPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "entry({}: {})", self.key(), self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_pattern_display() {
+        let pattern =
+            EntryPattern::new(Pattern::any_text(), Pattern::number(1));
+        assert_eq!(pattern.to_string(), "entry(text: 1)");
+    }
+
+    #[test]
+    fn test_entry_pattern_paths() {
+        let pattern =
+            EntryPattern::new(Pattern::any_text(), Pattern::any_number());
+
+        let mut map = Map::new();
+        map.insert("a", 1);
+        map.insert("b", "x");
+        map.insert(2, 3);
+        let map = CBOR::from(map);
+        let entry = CBOR::from(vec![CBOR::from("a"), CBOR::from(1)]);
+        assert_eq!(pattern.paths(&map), vec![vec![map.clone(), entry]]);
+
+        // Only maps have entries
+        let array = CBOR::from(vec![CBOR::from("a"), CBOR::from(1)]);
+        assert!(pattern.paths(&array).is_empty());
+    }
+}
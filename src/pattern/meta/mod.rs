@@ -1,26 +1,42 @@
 mod and_pattern;
 mod any_pattern;
+mod at_pattern;
+mod backref_pattern;
 mod capture_pattern;
+mod child_pattern;
+mod entry_pattern;
+mod none_pattern;
 mod not_pattern;
 mod or_pattern;
 mod repeat_pattern;
 mod search_pattern;
 mod sequence_pattern;
+mod untag_pattern;
 
 pub use and_pattern::*;
 pub use any_pattern::*;
+pub use at_pattern::*;
+pub use backref_pattern::BackrefPattern;
+pub(crate) use backref_pattern::{
+    Bindings, bind_captures, is_backref, matches_with_bindings,
+};
 pub use capture_pattern::*;
+pub use child_pattern::*;
 use dcbor::prelude::*;
+pub use entry_pattern::*;
+pub use none_pattern::*;
 pub use not_pattern::*;
 pub use or_pattern::*;
 pub use repeat_pattern::*;
 pub use search_pattern::*;
 pub use sequence_pattern::*;
+pub use untag_pattern::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{Captures, Matcher, Path, Pattern, vm::Instr};
 
 /// Pattern for combining and modifying other patterns.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MetaPattern {
     /// Always matches.
     Any(AnyPattern),
@@ -30,14 +46,27 @@ pub enum MetaPattern {
     Or(OrPattern),
     /// Matches if the inner pattern does not match.
     Not(NotPattern),
+    /// Matches if the inner pattern matches nowhere in the subtree.
+    None(NonePattern),
     /// Matches with repetition.
     Repeat(RepeatPattern),
     /// Captures a pattern match.
     Capture(CapturePattern),
+    /// Matches the direct children of a node.
+    Child(ChildPattern),
+    /// Matches the entries of a map as key-value pairs.
+    Entry(EntryPattern),
+    /// Matches a value equal to an earlier capture.
+    Backref(BackrefPattern),
     /// Searches the entire dCBOR tree for matches.
     Search(SearchPattern),
     /// Matches a sequence of patterns in order.
     Sequence(SequencePattern),
+    /// Matches a pattern with or without tags around it.
+    Untag(UntagPattern),
+    /// Matches a pattern at a descendant reached by map keys and array
+    /// indexes.
+    At(AtPattern),
 }
 
 impl Matcher for MetaPattern {
@@ -47,10 +76,25 @@ impl Matcher for MetaPattern {
             MetaPattern::And(pattern) => pattern.paths(haystack),
             MetaPattern::Or(pattern) => pattern.paths(haystack),
             MetaPattern::Not(pattern) => pattern.paths(haystack),
+            MetaPattern::None(pattern) => pattern.paths(haystack),
             MetaPattern::Repeat(pattern) => pattern.paths(haystack),
             MetaPattern::Capture(pattern) => pattern.paths(haystack),
+            MetaPattern::Child(pattern) => pattern.paths(haystack),
+            MetaPattern::Entry(pattern) => pattern.paths(haystack),
+            MetaPattern::Backref(pattern) => pattern.paths(haystack),
             MetaPattern::Search(pattern) => pattern.paths(haystack),
             MetaPattern::Sequence(pattern) => pattern.paths(haystack),
+            MetaPattern::Untag(pattern) => pattern.paths(haystack),
+            MetaPattern::At(pattern) => pattern.paths(haystack),
+        }
+    }
+
+    fn matches(&self, haystack: &CBOR) -> bool {
+        match self {
+            // A search can stop at its first match rather than finding them
+            // all
+            MetaPattern::Search(pattern) => pattern.matches(haystack),
+            _ => !self.paths(haystack).is_empty(),
         }
     }
 
@@ -65,18 +109,32 @@ impl Matcher for MetaPattern {
             MetaPattern::And(pattern) => pattern.compile(code, lits, captures),
             MetaPattern::Or(pattern) => pattern.compile(code, lits, captures),
             MetaPattern::Not(pattern) => pattern.compile(code, lits, captures),
+            MetaPattern::None(pattern) => pattern.compile(code, lits, captures),
             MetaPattern::Repeat(pattern) => {
                 pattern.compile(code, lits, captures)
             }
             MetaPattern::Capture(pattern) => {
                 pattern.compile(code, lits, captures)
             }
+            MetaPattern::Child(pattern) => {
+                pattern.compile(code, lits, captures)
+            }
+            MetaPattern::Entry(pattern) => {
+                pattern.compile(code, lits, captures)
+            }
+            MetaPattern::Backref(pattern) => {
+                pattern.compile(code, lits, captures)
+            }
             MetaPattern::Search(pattern) => {
                 pattern.compile(code, lits, captures)
             }
             MetaPattern::Sequence(pattern) => {
                 pattern.compile(code, lits, captures)
             }
+            MetaPattern::Untag(pattern) => {
+                pattern.compile(code, lits, captures)
+            }
+            MetaPattern::At(pattern) => pattern.compile(code, lits, captures),
         }
     }
 
@@ -86,18 +144,26 @@ impl Matcher for MetaPattern {
             MetaPattern::And(pattern) => pattern.collect_capture_names(names),
             MetaPattern::Or(pattern) => pattern.collect_capture_names(names),
             MetaPattern::Not(pattern) => pattern.collect_capture_names(names),
+            MetaPattern::None(pattern) => pattern.collect_capture_names(names),
             MetaPattern::Repeat(pattern) => {
                 pattern.collect_capture_names(names)
             }
             MetaPattern::Capture(pattern) => {
                 pattern.collect_capture_names(names)
             }
+            MetaPattern::Child(pattern) => pattern.collect_capture_names(names),
+            MetaPattern::Entry(pattern) => pattern.collect_capture_names(names),
+            MetaPattern::Backref(pattern) => {
+                pattern.collect_capture_names(names)
+            }
             MetaPattern::Search(pattern) => {
                 pattern.collect_capture_names(names)
             }
             MetaPattern::Sequence(pattern) => {
                 pattern.collect_capture_names(names)
             }
+            MetaPattern::Untag(pattern) => pattern.collect_capture_names(names),
+            MetaPattern::At(pattern) => pattern.collect_capture_names(names),
         }
     }
 
@@ -107,34 +173,51 @@ impl Matcher for MetaPattern {
             MetaPattern::And(pattern) => pattern.is_complex(),
             MetaPattern::Or(pattern) => pattern.is_complex(),
             MetaPattern::Not(pattern) => pattern.is_complex(),
+            MetaPattern::None(pattern) => pattern.is_complex(),
             MetaPattern::Repeat(pattern) => pattern.is_complex(),
             MetaPattern::Capture(pattern) => pattern.is_complex(),
+            MetaPattern::Child(pattern) => pattern.is_complex(),
+            MetaPattern::Entry(pattern) => pattern.is_complex(),
+            MetaPattern::Backref(pattern) => pattern.is_complex(),
             MetaPattern::Search(pattern) => pattern.is_complex(),
             MetaPattern::Sequence(pattern) => pattern.is_complex(),
+            MetaPattern::Untag(pattern) => pattern.is_complex(),
+            MetaPattern::At(pattern) => pattern.is_complex(),
         }
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         match self {
             MetaPattern::Any(pattern) => pattern.paths_with_captures(haystack),
             MetaPattern::And(pattern) => pattern.paths_with_captures(haystack),
             MetaPattern::Or(pattern) => pattern.paths_with_captures(haystack),
             MetaPattern::Not(pattern) => pattern.paths_with_captures(haystack),
+            MetaPattern::None(pattern) => pattern.paths_with_captures(haystack),
             MetaPattern::Repeat(pattern) => {
                 pattern.paths_with_captures(haystack)
             }
             MetaPattern::Capture(pattern) => {
                 pattern.paths_with_captures(haystack)
             }
+            MetaPattern::Child(pattern) => {
+                pattern.paths_with_captures(haystack)
+            }
+            MetaPattern::Entry(pattern) => {
+                pattern.paths_with_captures(haystack)
+            }
+            MetaPattern::Backref(pattern) => {
+                pattern.paths_with_captures(haystack)
+            }
             MetaPattern::Search(pattern) => {
                 pattern.paths_with_captures(haystack)
             }
             MetaPattern::Sequence(pattern) => {
                 pattern.paths_with_captures(haystack)
             }
+            MetaPattern::Untag(pattern) => {
+                pattern.paths_with_captures(haystack)
+            }
+            MetaPattern::At(pattern) => pattern.paths_with_captures(haystack),
         }
     }
 }
@@ -146,10 +229,16 @@ impl std::fmt::Display for MetaPattern {
             MetaPattern::And(pattern) => pattern.fmt(f),
             MetaPattern::Or(pattern) => pattern.fmt(f),
             MetaPattern::Not(pattern) => pattern.fmt(f),
+            MetaPattern::None(pattern) => pattern.fmt(f),
             MetaPattern::Repeat(pattern) => pattern.fmt(f),
             MetaPattern::Capture(pattern) => pattern.fmt(f),
+            MetaPattern::Child(pattern) => pattern.fmt(f),
+            MetaPattern::Entry(pattern) => pattern.fmt(f),
+            MetaPattern::Backref(pattern) => pattern.fmt(f),
             MetaPattern::Search(pattern) => pattern.fmt(f),
             MetaPattern::Sequence(pattern) => pattern.fmt(f),
+            MetaPattern::Untag(pattern) => pattern.fmt(f),
+            MetaPattern::At(pattern) => pattern.fmt(f),
         }
     }
 }
@@ -0,0 +1,92 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{Matcher, Path, Pattern, meta::SearchPattern, vm::Instr};
+
+/// A pattern that matches a node only if the inner pattern matches nowhere in
+/// the subtree rooted at that node, including the node itself.
+///
+/// This is the complement of `search(pattern)` taken over a whole subtree,
+/// unlike `!pattern`, which only looks at the node itself. Under a search,
+/// `search(!number)` finds every node that is not a number, including arrays
+/// full of numbers, while `search(none(number))` finds only the nodes with no
+/// numbers anywhere inside them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NonePattern(Box<Pattern>);
+
+impl NonePattern {
+    /// Creates a new `NonePattern` that rejects subtrees containing a match
+    /// for the given pattern.
+    pub fn new(pattern: Pattern) -> Self { NonePattern(Box::new(pattern)) }
+
+    /// Returns the pattern that must not match anywhere in the subtree.
+    pub fn pattern(&self) -> &Pattern { &self.0 }
+
+    /// Returns the search for the inner pattern over the whole subtree.
+    fn search(&self) -> SearchPattern {
+        SearchPattern::new(self.pattern().clone())
+    }
+}
+
+impl Matcher for NonePattern {
+    fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        if self.search().paths(haystack).is_empty() {
+            vec![vec![haystack.clone()]]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Compile into byte-code: the node matches if a search for the inner
+    /// pattern finds nothing.
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        _captures: &mut Vec<String>,
+    ) {
+        let idx = literals.len();
+        literals.push(Pattern::Meta(crate::pattern::MetaPattern::Search(
+            self.search(),
+        )));
+        code.push(Instr::NotMatch { pat_idx: idx });
+    }
+
+    fn collect_capture_names(&self, _names: &mut Vec<String>) {
+        // Like `!`, the inner pattern never matches when this pattern does,
+        // so it has nothing to capture
+    }
+}
+
+impl std::fmt::Display for NonePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "none({})", self.pattern())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_none_pattern_display() {
+        let pattern = NonePattern::new(Pattern::any_number());
+        assert_eq!(pattern.to_string(), "none(number)");
+    }
+
+    #[test]
+    fn test_none_pattern_paths() {
+        let pattern = NonePattern::new(Pattern::any_number());
+
+        let texts = CBOR::from(vec![CBOR::from("a"), CBOR::from("b")]);
+        assert_eq!(pattern.paths(&texts), vec![vec![texts.clone()]]);
+
+        // A number nested anywhere in the subtree rejects the node
+        let nested = CBOR::from(vec![CBOR::from(vec![CBOR::from(1)])]);
+        assert!(pattern.paths(&nested).is_empty());
+
+        // The node itself is part of its subtree
+        assert!(pattern.paths(&CBOR::from(1)).is_empty());
+        assert!(pattern.matches(&CBOR::from("a")));
+    }
+}
@@ -1,10 +1,13 @@
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{
+    Captures, Matcher, Path, Pattern, meta::format_enclosed, vm::Instr,
+};
 
 /// A pattern that negates another pattern; matches when the inner pattern does
 /// not match.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NotPattern(Box<Pattern>);
 
 impl NotPattern {
@@ -26,22 +29,16 @@ impl Matcher for NotPattern {
         }
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         // For NOT patterns, we match if the inner pattern does NOT match
         let (inner_paths, _inner_captures) =
             self.pattern().paths_with_captures(haystack);
         if inner_paths.is_empty() {
             // Inner pattern doesn't match, so NOT matches
-            (
-                vec![vec![haystack.clone()]],
-                std::collections::HashMap::new(),
-            )
+            (vec![vec![haystack.clone()]], Captures::new())
         } else {
             // Inner pattern matches, so NOT doesn't match
-            (vec![], std::collections::HashMap::new())
+            (vec![], Captures::new())
         }
     }
 
@@ -75,7 +72,7 @@ impl Matcher for NotPattern {
 impl std::fmt::Display for NotPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.pattern().is_complex() {
-            write!(f, "!({})", self.pattern())
+            write!(f, "!({})", format_enclosed(self.pattern()))
         } else {
             write!(f, "!{}", self.pattern())
         }
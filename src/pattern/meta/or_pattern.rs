@@ -1,9 +1,13 @@
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{
+    Captures, Matcher, MetaPattern, Path, Pattern, RepeatPattern,
+    structure::ends_with_map_value, vm::Instr,
+};
 
 /// A pattern that matches if any contained pattern matches.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrPattern(Vec<Pattern>);
 
 impl OrPattern {
@@ -12,43 +16,64 @@ impl OrPattern {
 
     /// Returns the patterns contained in this OR pattern.
     pub fn patterns(&self) -> &[Pattern] { &self.0 }
+
+    /// Adds `pattern` as the last alternative, or, if it is itself an OR
+    /// pattern, adds each of its alternatives in turn, which matches the same
+    /// values with one less level of nesting.
+    pub fn push(&mut self, pattern: Pattern) {
+        match pattern {
+            Pattern::Meta(MetaPattern::Or(nested)) => self.0.extend(nested.0),
+            pattern => self.0.push(pattern),
+        }
+    }
+}
+
+impl Extend<Pattern> for OrPattern {
+    fn extend<I: IntoIterator<Item = Pattern>>(&mut self, patterns: I) {
+        for pattern in patterns {
+            self.push(pattern);
+        }
+    }
+}
+
+/// Collects patterns into an OR pattern as [`OrPattern::push`] adds them,
+/// flattening any that are themselves OR patterns.
+impl FromIterator<Pattern> for OrPattern {
+    fn from_iter<I: IntoIterator<Item = Pattern>>(patterns: I) -> Self {
+        let mut pattern = OrPattern(Vec::new());
+        pattern.extend(patterns);
+        pattern
+    }
 }
 
 impl Matcher for OrPattern {
+    /// Returns the paths of the first pattern that matches.
     fn paths(&self, haystack: &CBOR) -> Vec<Path> {
-        if self
-            .patterns()
+        self.patterns()
             .iter()
-            .any(|pattern| pattern.matches(haystack))
-        {
-            vec![vec![haystack.clone()]]
-        } else {
-            vec![]
-        }
+            .map(|pattern| pattern.paths(haystack))
+            .find(|paths| !paths.is_empty())
+            .unwrap_or_default()
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
-        let mut all_paths = Vec::new();
-        let mut all_captures = std::collections::HashMap::new();
+    fn matches(&self, haystack: &CBOR) -> bool {
+        self.patterns()
+            .iter()
+            .any(|pattern| pattern.matches(haystack))
+    }
 
-        // Try each pattern in the OR group
+    /// Returns the paths and captures of the first pattern that matches.
+    ///
+    /// Later patterns are not evaluated once one matches, so the captures
+    /// show which alternative matched, as in `@v1(...) | @v2(...)`.
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         for pattern in self.patterns() {
             let (paths, captures) = pattern.paths_with_captures(haystack);
-            all_paths.extend(paths);
-
-            // Merge captures
-            for (name, capture_paths) in captures {
-                all_captures
-                    .entry(name)
-                    .or_insert_with(Vec::new)
-                    .extend(capture_paths);
+            if !paths.is_empty() {
+                return (paths, captures);
             }
         }
-
-        (all_paths, all_captures)
+        (vec![], Captures::new())
     }
 
     /// Compile into byte-code (OR = any can match).
@@ -62,41 +87,26 @@ impl Matcher for OrPattern {
             return;
         }
 
-        // For N patterns: Split(p1, Split(p2, ... Split(pN-1, pN)))
-        let mut splits = Vec::new();
-
-        // Generate splits for all but the last pattern
-        for _ in 0..self.patterns().len() - 1 {
-            splits.push(code.len());
-            code.push(Instr::Split { a: 0, b: 0 }); // Placeholder
-        }
-
-        // Now fill in the actual split targets
-        for (i, pattern) in self.patterns().iter().enumerate() {
-            let pattern_start = code.len();
-
-            // Compile this pattern
-            pattern.compile(code, lits, captures);
-
-            // This pattern will jump to the end if it matches
-            let jump_past_all = code.len();
-            code.push(Instr::Jump(0)); // Placeholder
+        let marks = (code.len(), lits.len(), captures.len());
+        self.compile_split(code, lits, captures);
 
-            // If there's a next pattern, update the split to point here
-            if i < self.patterns().len() - 1 {
-                let next_pattern = code.len();
-                code[splits[i]] =
-                    Instr::Split { a: pattern_start, b: next_pattern };
-            }
-
-            // Will patch this jump once we know where "past all" is
-            splits.push(jump_past_all);
-        }
-
-        // Now patch all the jumps to point past all the patterns
-        let past_all = code.len();
-        for &jump in &splits[self.patterns().len() - 1..] {
-            code[jump] = Instr::Jump(past_all);
+        // Alternatives that capture are matched as a whole, like a group, so
+        // that only the first matching alternative's captures are kept. The
+        // split code is compiled first to find back-references, which only
+        // see earlier captures when run in the VM.
+        let mut names = Vec::new();
+        self.collect_capture_names(&mut names);
+        let has_backref = code[marks.0..]
+            .iter()
+            .any(|instr| matches!(instr, Instr::MatchBackref(_)));
+        if !names.is_empty() && !has_backref {
+            code.truncate(marks.0);
+            lits.truncate(marks.1);
+            captures.truncate(marks.2);
+            let group = RepeatPattern::new(Pattern::Meta(MetaPattern::Or(
+                self.clone(),
+            )));
+            group.compile(code, lits, captures);
         }
     }
 
@@ -115,6 +125,43 @@ impl Matcher for OrPattern {
     }
 }
 
+impl OrPattern {
+    /// Compiles the patterns as alternatives the VM tries in turn.
+    fn compile_split(
+        &self,
+        code: &mut Vec<Instr>,
+        lits: &mut Vec<Pattern>,
+        captures: &mut Vec<String>,
+    ) {
+        // For N patterns: Split(p1, Split(p2, ... Split(pN-1, pN))), with
+        // each split placed just before the pattern it tries first
+        let mut jumps = Vec::new();
+        for (i, pattern) in self.patterns().iter().enumerate() {
+            let split = (i + 1 < self.patterns().len()).then(|| {
+                code.push(Instr::Split { a: 0, b: 0 }); // Placeholder
+                code.len() - 1
+            });
+
+            pattern.compile(code, lits, captures);
+
+            // This pattern will jump past all the others if it matches
+            jumps.push(code.len());
+            code.push(Instr::Jump(0)); // Placeholder
+
+            // If it doesn't, the split tries the next pattern
+            if let Some(split) = split {
+                code[split] = Instr::Split { a: split + 1, b: code.len() };
+            }
+        }
+
+        // Now patch all the jumps to point past all the patterns
+        let past_all = code.len();
+        for jump in jumps {
+            code[jump] = Instr::Jump(past_all);
+        }
+    }
+}
+
 impl std::fmt::Display for OrPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -122,7 +169,14 @@ impl std::fmt::Display for OrPattern {
             "{}",
             self.patterns()
                 .iter()
-                .map(|p| p.to_string())
+                .enumerate()
+                .map(|(i, p)| {
+                    if i + 1 < self.patterns().len() && ends_with_map_value(p) {
+                        format!("({})", p)
+                    } else {
+                        p.to_string()
+                    }
+                })
                 .collect::<Vec<_>>()
                 .join(" | ")
         )
@@ -2,11 +2,16 @@ use dcbor::prelude::*;
 
 use crate::{
     Quantifier,
-    pattern::{Matcher, Path, Pattern, vm::Instr},
+    pattern::{
+        Captures, Matcher, Path, Pattern,
+        meta::format_enclosed,
+        vm::{Instr, capture_slots},
+    },
 };
 
 /// A pattern that matches with repetition using a quantifier.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RepeatPattern {
     pattern: Box<Pattern>,
     quantifier: Quantifier,
@@ -63,88 +68,22 @@ impl Matcher for RepeatPattern {
         }
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
-        // Check if the inner pattern has any captures
-        let mut capture_names = Vec::new();
-        self.pattern.collect_capture_names(&mut capture_names);
-
-        if capture_names.is_empty() {
-            // No captures in the inner pattern, use basic implementation
-            return (self.paths(haystack), std::collections::HashMap::new());
-        }
-
-        // For patterns with captures, we need to handle different quantifier
-        // cases
-        match haystack.as_case() {
-            CBORCase::Array(arr) => {
-                // For array inputs, the repeat pattern should match against
-                // elements
-                let mut all_captures = std::collections::HashMap::new();
-                let mut valid_match = false;
-
-                // Check if this pattern can match the array length
-                if self.quantifier.contains(arr.len()) {
-                    valid_match = true;
-
-                    // If minimum is 0 and array is empty, we have an empty
-                    // capture
-                    if arr.is_empty() && self.quantifier.contains(0) {
-                        // For empty arrays, captures should be empty but
-                        // present
-                        for name in &capture_names {
-                            all_captures.insert(name.clone(), vec![]);
-                        }
-                    } else {
-                        // For non-empty arrays, collect captures from each
-                        // element
-                        for element in arr {
-                            let (_element_paths, element_captures) =
-                                self.pattern.paths_with_captures(element);
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        // Mirrors `paths`: the captures are those of the single iteration
+        // over the haystack, if the quantifier allows one
+        let (inner_paths, captures) =
+            self.pattern.paths_with_captures(haystack);
 
-                            for (capture_name, captured_paths) in
-                                element_captures
-                            {
-                                all_captures
-                                    .entry(capture_name)
-                                    .or_insert_with(Vec::new)
-                                    .extend(captured_paths);
-                            }
-                        }
-                    }
-                }
-
-                if valid_match {
-                    (vec![vec![haystack.clone()]], all_captures)
-                } else {
-                    (vec![], std::collections::HashMap::new())
-                }
-            }
-            _ => {
-                // For non-array inputs, use the basic repeat logic
-                let inner_paths = self.pattern.paths(haystack);
-                let matches = !inner_paths.is_empty();
-
-                if matches && self.quantifier.contains(1) {
-                    // Inner pattern matches and quantifier allows 1 match
-                    let (_paths, captures) =
-                        self.pattern.paths_with_captures(haystack);
-                    (vec![vec![haystack.clone()]], captures)
-                } else if !matches && self.quantifier.contains(0) {
-                    // Inner pattern doesn't match but quantifier allows 0
-                    // matches
-                    let mut empty_captures = std::collections::HashMap::new();
-                    for name in &capture_names {
-                        empty_captures.insert(name.clone(), vec![]);
-                    }
-                    (vec![vec![haystack.clone()]], empty_captures)
-                } else {
-                    // No valid match
-                    (vec![], std::collections::HashMap::new())
-                }
+        if !inner_paths.is_empty() {
+            if self.quantifier.contains(1) {
+                (inner_paths, captures)
+            } else {
+                (vec![], Captures::new())
             }
+        } else if self.quantifier.contains(0) {
+            (vec![vec![haystack.clone()]], Captures::new())
+        } else {
+            (vec![], Captures::new())
         }
     }
 
@@ -152,12 +91,17 @@ impl Matcher for RepeatPattern {
         &self,
         code: &mut Vec<Instr>,
         literals: &mut Vec<Pattern>,
-        _captures: &mut Vec<String>,
+        captures: &mut Vec<String>,
     ) {
         // Emit a high-level `Repeat` instruction for the VM
         let idx = literals.len();
         literals.push((*self.pattern).clone());
-        code.push(Instr::Repeat { pat_idx: idx, quantifier: self.quantifier });
+        let capture_map = capture_slots(&self.pattern, captures);
+        code.push(Instr::Repeat {
+            pat_idx: idx,
+            quantifier: self.quantifier,
+            capture_map,
+        });
     }
 
     fn collect_capture_names(&self, names: &mut Vec<String>) {
@@ -169,6 +113,6 @@ impl Matcher for RepeatPattern {
 impl std::fmt::Display for RepeatPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let formatted_range = self.quantifier.to_string();
-        write!(f, "({}){}", self.pattern, formatted_range)
+        write!(f, "({}){}", format_enclosed(&self.pattern), formatted_range)
     }
 }
@@ -1,176 +1,648 @@
-use dcbor::prelude::*;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use dcbor::{Simple, prelude::*};
+
+use crate::pattern::{
+    Captures, Matcher, MetaPattern, Path, Pattern, StructurePattern,
+    ValuePattern, pin_evaluation_now,
+    structure::MapPattern,
+    vm::{Instr, capture_slots},
+};
+
+/// The deepest level of a dCBOR tree any search visits, where the root is at
+/// depth 0.
+///
+/// Nodes nested more deeply are never visited, so they never match, rather
+/// than deeply nested input exhausting the stack.
+pub const MAX_SEARCH_DEPTH: usize = 512;
+
+/// Options controlling how a [`SearchPattern`] traverses the dCBOR tree.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchOpts {
+    /// The maximum depth to visit, where the root is at depth 0. `None`
+    /// searches the whole tree. Either way no search goes deeper than
+    /// [`MAX_SEARCH_DEPTH`].
+    pub max_depth: Option<usize>,
+    /// Whether to visit map keys. Map values are always visited.
+    pub visit_map_keys: bool,
+    /// Whether to visit the content of tagged values.
+    pub visit_tagged_content: bool,
+}
+
+impl Default for SearchOpts {
+    /// Returns options that search the whole tree, including map keys and
+    /// tagged content.
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            visit_map_keys: true,
+            visit_tagged_content: true,
+        }
+    }
+}
+
+impl SearchOpts {
+    /// Returns the children of `cbor` to visit at the given depth.
+    pub(crate) fn children(&self, cbor: &CBOR, depth: usize) -> Vec<CBOR> {
+        let max_depth = self.max_depth.map_or(MAX_SEARCH_DEPTH, |max_depth| {
+            max_depth.min(MAX_SEARCH_DEPTH)
+        });
+        if depth >= max_depth {
+            return Vec::new();
+        }
+        match cbor.as_case() {
+            CBORCase::Array(arr) => arr.to_vec(),
+            CBORCase::Map(map) => {
+                let mut children = Vec::new();
+                for (key, value) in map.iter() {
+                    if self.visit_map_keys {
+                        children.push(key.clone());
+                    }
+                    children.push(value.clone());
+                }
+                children
+            }
+            CBORCase::Tagged(_, content) if self.visit_tagged_content => {
+                vec![content.clone()]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A set of the kinds of dCBOR node, used by [`SearchPattern`] to skip
+/// nodes its pattern can't match without running the pattern on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ValueKindSet(u8);
+
+impl ValueKindSet {
+    const NUMBER: Self = Self(1 << 0);
+    const BYTE_STRING: Self = Self(1 << 1);
+    const TEXT: Self = Self(1 << 2);
+    const ARRAY: Self = Self(1 << 3);
+    const MAP: Self = Self(1 << 4);
+    const TAGGED: Self = Self(1 << 5);
+    const BOOL: Self = Self(1 << 6);
+    const NULL: Self = Self(1 << 7);
+    const ALL: Self = Self(u8::MAX);
+
+    /// Returns the kind of `cbor`.
+    fn of(cbor: &CBOR) -> Self {
+        match cbor.as_case() {
+            CBORCase::Unsigned(_) | CBORCase::Negative(_) => Self::NUMBER,
+            CBORCase::ByteString(_) => Self::BYTE_STRING,
+            CBORCase::Text(_) => Self::TEXT,
+            CBORCase::Array(_) => Self::ARRAY,
+            CBORCase::Map(_) => Self::MAP,
+            CBORCase::Tagged(..) => Self::TAGGED,
+            CBORCase::Simple(Simple::True | Simple::False) => Self::BOOL,
+            CBORCase::Simple(Simple::Null) => Self::NULL,
+            CBORCase::Simple(Simple::Float(_)) => Self::NUMBER,
+        }
+    }
+
+    /// Returns the kinds of node `pattern` can match, or all kinds if that
+    /// can't be told from the pattern alone.
+    fn matched_by(pattern: &Pattern) -> Self {
+        match pattern {
+            Pattern::Value(value) => match value {
+                ValuePattern::Bool(_) => Self::BOOL,
+                ValuePattern::ByteString(_) => Self::BYTE_STRING,
+                ValuePattern::Cbor(cbor) => Self::of(cbor.value()),
+                // Dates, digests, and known values are all tagged
+                ValuePattern::Date(_)
+                | ValuePattern::Digest(_)
+                | ValuePattern::KnownValue(_) => Self::TAGGED,
+                ValuePattern::EncodedDigest(_) => Self::ALL,
+                ValuePattern::Null(_) => Self::NULL,
+                ValuePattern::Number(_) => Self::NUMBER,
+                ValuePattern::Text(_) => Self::TEXT,
+            },
+            Pattern::Structure(structure) => match structure {
+                StructurePattern::Array(_) => Self::ARRAY,
+                StructurePattern::Map(_) => Self::MAP,
+                StructurePattern::Tagged(_) => Self::TAGGED,
+            },
+            Pattern::Meta(MetaPattern::Or(or)) => {
+                or.patterns().iter().fold(Self(0), |kinds, pattern| {
+                    Self(kinds.0 | Self::matched_by(pattern).0)
+                })
+            }
+            Pattern::Meta(MetaPattern::And(and)) => {
+                and.patterns().iter().fold(Self::ALL, |kinds, pattern| {
+                    Self(kinds.0 & Self::matched_by(pattern).0)
+                })
+            }
+            Pattern::Meta(MetaPattern::Capture(capture)) => {
+                Self::matched_by(capture.pattern())
+            }
+            _ => Self::ALL,
+        }
+    }
+
+    /// Returns `true` if the kind of `cbor` is in the set.
+    fn contains(self, cbor: &CBOR) -> bool { self.0 & Self::of(cbor).0 != 0 }
+}
+
+/// Returns `true` if `cbor` has no children to search.
+fn is_leaf(cbor: &CBOR) -> bool {
+    !matches!(
+        cbor.as_case(),
+        CBORCase::Array(_) | CBORCase::Map(_) | CBORCase::Tagged(..)
+    )
+}
+
+/// Returns a summary of `cbor` that takes no time to compute and is the same
+/// for equal values: its kind, and its value, length, or tag.
+fn summary(cbor: &CBOR) -> (u8, u64) {
+    match cbor.as_case() {
+        CBORCase::Unsigned(n) => (0, *n),
+        CBORCase::Negative(n) => (1, *n),
+        CBORCase::ByteString(bytes) => (2, bytes.len() as u64),
+        CBORCase::Text(text) => (3, text.len() as u64),
+        CBORCase::Array(array) => (4, array.len() as u64),
+        CBORCase::Map(map) => (5, map.len() as u64),
+        CBORCase::Tagged(tag, _) => (6, tag.value()),
+        CBORCase::Simple(Simple::Float(f)) => (7, f.to_bits()),
+        CBORCase::Simple(Simple::False) => (8, 0),
+        CBORCase::Simple(Simple::True) => (8, 1),
+        CBORCase::Simple(Simple::Null) => (8, 2),
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of times a search has run its pattern on a node, so tests
+    /// can check how many nodes were skipped.
+    static MATCHER_CALLS: std::cell::Cell<usize> = const {
+        std::cell::Cell::new(0)
+    };
+}
+
+/// Records that a search is about to run its pattern on a node.
+fn count_matcher_call() {
+    #[cfg(test)]
+    MATCHER_CALLS.set(MATCHER_CALLS.get() + 1);
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of children a search has encoded to compare with their
+    /// siblings, so tests can check that it encodes only those it must.
+    static ENCODED_CHILDREN: std::cell::Cell<usize> = const {
+        std::cell::Cell::new(0)
+    };
+}
+
+/// Returns the encoding of `child`, which a search compares with its
+/// siblings' encodings.
+fn encode_child(child: &CBOR) -> Vec<u8> {
+    #[cfg(test)]
+    ENCODED_CHILDREN.set(ENCODED_CHILDREN.get() + 1);
+    child.to_cbor_data()
+}
 
 /// A pattern that searches the entire dCBOR tree for matches.
 ///
 /// This pattern recursively traverses the dCBOR tree and applies the inner
-/// pattern at each node, returning all matching paths.
+/// pattern at each node, returning all matching paths. The traversal can be
+/// limited with [`SearchOpts`].
+///
+/// Nodes are visited in pre-order, and matches are returned in the order
+/// their nodes are visited: a container comes before everything inside it,
+/// and each child's whole subtree comes before its next sibling. The
+/// children of an array are its elements in index order; those of a map are
+/// each entry's key and then its value, in map order, which sorts keys by
+/// their encoding; and that of a tagged value is its content. Paths do not
+/// record whether a node is a key or a value, so a node equal to an earlier
+/// sibling, such as a value equal to its key, has the same path and is
+/// returned once.
+///
+/// Each node the pattern matches is returned once, however many paths the
+/// pattern finds at it: `search({(*): (*)})` returns a map once, not once per
+/// entry. Captures, by contrast, are made for every path the pattern finds.
+///
+/// Nodes of a kind the pattern can't match, such as numbers when searching
+/// for text, are skipped without running the pattern on them.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SearchPattern(Box<Pattern>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchPattern {
+    pattern: Box<Pattern>,
+    opts: SearchOpts,
+}
 
 impl SearchPattern {
     /// Creates a new `SearchPattern` that searches for the given pattern.
-    pub fn new(pattern: Pattern) -> Self { SearchPattern(Box::new(pattern)) }
+    pub fn new(pattern: Pattern) -> Self {
+        Self::with_opts(pattern, SearchOpts::default())
+    }
+
+    /// Creates a new `SearchPattern` that searches for the given pattern
+    /// using the given traversal options.
+    pub fn with_opts(pattern: Pattern, opts: SearchOpts) -> Self {
+        SearchPattern { pattern: Box::new(pattern), opts }
+    }
 
     /// Returns a reference to the inner pattern.
-    pub fn pattern(&self) -> &Pattern { &self.0 }
+    pub fn pattern(&self) -> &Pattern { &self.pattern }
+
+    /// Returns the traversal options.
+    pub fn opts(&self) -> &SearchOpts { &self.opts }
+
+    /// Returns the children of `cbor` to visit at the given depth, each with
+    /// the index of its first sibling equal to it.
+    ///
+    /// Equal siblings lead to equal paths, so a path is identified by the
+    /// sequence of these indices along it, which is much cheaper to compare
+    /// than the values themselves.
+    fn keyed_children(&self, cbor: &CBOR, depth: usize) -> Vec<(usize, CBOR)> {
+        let children = self.opts.children(cbor, depth);
+        if children.len() < 2 {
+            return children.into_iter().map(|child| (0, child)).collect();
+        }
+        // Encoding a child takes time in the size of its subtree, so only
+        // children whose summaries show they might equal a sibling are
+        // encoded to find out
+        let summaries: Vec<_> = children.iter().map(summary).collect();
+        let mut summary_counts = HashMap::new();
+        for summary in &summaries {
+            *summary_counts.entry(*summary).or_insert(0) += 1;
+        }
+        let mut first_index = HashMap::new();
+        children
+            .into_iter()
+            .zip(summaries)
+            .enumerate()
+            .map(|(i, (child, summary))| {
+                if summary_counts[&summary] < 2 {
+                    (i, child)
+                } else {
+                    let index =
+                        *first_index.entry(encode_child(&child)).or_insert(i);
+                    (index, child)
+                }
+            })
+            .collect()
+    }
+
+    /// Runs `f` with the memo this search keeps within the search enclosing
+    /// it, or with `None` if it isn't nested in another search, and keeps
+    /// what `f` adds to the memo for the next time this search runs.
+    fn with_memo<R>(&self, f: impl FnOnce(&mut Option<ExistsMemo>) -> R) -> R {
+        let taken = NESTED_MEMOS.with_borrow_mut(|memos| {
+            let memos = memos.as_mut()?;
+            Some(match memos.iter().position(|(search, _)| search == self) {
+                Some(index) => memos.swap_remove(index),
+                None => (self.clone(), ExistsMemo::new()),
+            })
+        });
+        let Some((search, memo)) = taken else {
+            return f(&mut None);
+        };
+        let mut memo = Some(memo);
+        let result = f(&mut memo);
+        NESTED_MEMOS.with_borrow_mut(|memos| {
+            if let (Some(memos), Some(memo)) = (memos, memo) {
+                memos.push((search, memo));
+            }
+        });
+        result
+    }
 
     // Helper method to recursively search through CBOR tree
     fn search_recursive(
         &self,
         cbor: &CBOR,
-        path: Vec<CBOR>,
+        path: &mut Vec<CBOR>,
+        key: &mut Vec<usize>,
+        kinds: ValueKindSet,
         results: &mut Vec<Path>,
+        seen: &mut HashSet<Vec<usize>>,
     ) {
-        // Test the pattern against this node
-        let pattern_paths = self.0.paths(cbor);
+        // Test the pattern against this node, if it could match, and add the
+        // current path to results unless an equal path was already found
+        if kinds.contains(cbor) {
+            count_matcher_call();
+            if !continues_into_value(&self.pattern) {
+                if self.pattern.matches(cbor) && seen.insert(key.clone()) {
+                    results.push(path.clone());
+                }
+            } else {
+                let pattern_paths = self.pattern.paths(cbor);
+                if !pattern_paths.is_empty() && seen.insert(key.clone()) {
+                    results.extend(extend_path(path, pattern_paths));
+                }
+            }
+        }
 
-        // If the pattern matches, add the current path to results
-        if !pattern_paths.is_empty() {
-            results.push(path.clone());
+        // Recursively search the children selected by the options, except
+        // leaves the pattern can't match
+        for (index, child) in self.keyed_children(cbor, path.len() - 1) {
+            if is_leaf(&child) && !kinds.contains(&child) {
+                continue;
+            }
+            path.push(child.clone());
+            key.push(index);
+            self.search_recursive(&child, path, key, kinds, results, seen);
+            path.pop();
+            key.pop();
         }
+    }
 
-        // Recursively search children based on CBOR type
-        match cbor.as_case() {
-            CBORCase::Array(arr) => {
-                for child in arr.iter() {
-                    let mut new_path = path.clone();
-                    new_path.push(child.clone());
-                    self.search_recursive(child, new_path, results);
+    // Helper method to recursively search through CBOR tree, collecting
+    // only the matched nodes, so that no path is built for nodes that don't
+    // match
+    fn search_terminal_recursive(
+        &self,
+        cbor: &CBOR,
+        key: &mut Vec<usize>,
+        kinds: ValueKindSet,
+        results: &mut Vec<CBOR>,
+        seen: &mut HashSet<Vec<usize>>,
+    ) {
+        if kinds.contains(cbor) {
+            count_matcher_call();
+            if !continues_into_value(&self.pattern) {
+                if self.pattern.matches(cbor) && seen.insert(key.clone()) {
+                    results.push(cbor.clone());
                 }
-            }
-            CBORCase::Map(map) => {
-                for (key, value) in map.iter() {
-                    // Search both keys and values
-                    let mut key_path = path.clone();
-                    key_path.push(key.clone());
-                    self.search_recursive(key, key_path, results);
-
-                    let mut value_path = path.clone();
-                    value_path.push(value.clone());
-                    self.search_recursive(value, value_path, results);
+            } else {
+                let pattern_paths = self.pattern.paths(cbor);
+                if !pattern_paths.is_empty() && seen.insert(key.clone()) {
+                    results.extend(
+                        pattern_paths.into_iter().filter_map(|mut p| p.pop()),
+                    );
                 }
             }
-            CBORCase::Tagged(_, content) => {
-                let mut new_path = path.clone();
-                new_path.push(content.clone());
-                self.search_recursive(content, new_path, results);
-            }
-            _ => {
-                // Leaf nodes (primitives) - no children to search
+        }
+
+        for (index, child) in self.keyed_children(cbor, key.len()) {
+            if is_leaf(&child) && !kinds.contains(&child) {
+                continue;
             }
+            key.push(index);
+            self.search_terminal_recursive(&child, key, kinds, results, seen);
+            key.pop();
         }
     }
 
+    /// Returns the node at the end of each path [`Matcher::paths`] returns,
+    /// in the same order, without building the paths.
+    pub fn terminal_matches(&self, haystack: &CBOR) -> Vec<CBOR> {
+        let mut results = Vec::new();
+        in_search(|| {
+            self.search_terminal_recursive(
+                haystack,
+                &mut Vec::new(),
+                ValueKindSet::matched_by(&self.pattern),
+                &mut results,
+                &mut HashSet::new(),
+            )
+        });
+        results
+    }
+
     // Helper method to recursively search through CBOR tree with capture
     // support
     fn search_recursive_with_captures(
         &self,
         cbor: &CBOR,
-        path: Vec<CBOR>,
-        results: &mut Vec<Path>,
-        all_captures: &mut std::collections::HashMap<String, Vec<Path>>,
+        path: &mut Vec<CBOR>,
+        key: &mut Vec<usize>,
+        kinds: ValueKindSet,
+        found: &mut (Vec<Path>, HashSet<Vec<usize>>),
+        all_captures: &mut Captures,
     ) {
-        // Test the pattern against this node with captures
-        let (pattern_paths, captures) = self.0.paths_with_captures(cbor);
+        let (results, seen) = found;
 
-        // If the pattern matches, add the current path to results and handle
-        // captures
+        // Test the pattern against this node with captures, if it could
+        // match
+        let (pattern_paths, captures) = if kinds.contains(cbor) {
+            count_matcher_call();
+            self.pattern.paths_with_captures(cbor)
+        } else {
+            (Vec::new(), Captures::new())
+        };
+
+        // If the pattern matches, add the current path, continued along the
+        // pattern's paths where they lead into the node, to results and
+        // handle captures
         if !pattern_paths.is_empty() {
-            results.push(path.clone());
-
-            // Handle captures based on the pattern type:
-            // - For array element captures like [@a(*), @b(*), @c(*)], preserve
-            //   the original paths so that @a points to element 1, @b to
-            //   element 2, etc.
-            // - For complex/nested patterns, captures point to the search
-            //   location where the pattern was found, which provides the
-            //   context for the match.
-            for (name, capture_paths) in captures {
-                if capture_paths.len() > 1
-                    || (capture_paths.len() == 1
-                        && capture_paths[0].len() > path.len())
-                {
-                    // This appears to be an array pattern with element-level
-                    // captures or a nested pattern where we
-                    // want to preserve the original paths
-                    for capture_path in capture_paths {
-                        // Transform the capture path to include the search
-                        // context
-                        let mut transformed_path = path.clone();
-                        // Add the relative path from the matched location to
-                        // the captured value
-                        if capture_path.len() > 1 {
-                            transformed_path
-                                .extend_from_slice(&capture_path[1..]);
-                        }
-                        all_captures
-                            .entry(name.clone())
-                            .or_default()
-                            .push(transformed_path);
-                    }
+            if seen.insert(key.clone()) {
+                if continues_into_value(&self.pattern) {
+                    results.extend(extend_path(path, pattern_paths));
                 } else {
-                    // For simple captures or when unclear, use the search
-                    // location
-                    all_captures.entry(name).or_default().push(path.clone());
+                    results.push(path.clone());
                 }
             }
-        }
 
-        // Recursively search children based on CBOR type
-        match cbor.as_case() {
-            CBORCase::Array(arr) => {
-                for child in arr.iter() {
-                    let mut new_path = path.clone();
-                    new_path.push(child.clone());
-                    self.search_recursive_with_captures(
-                        child,
-                        new_path,
-                        results,
-                        all_captures,
-                    );
+            // Capture paths start at the node the pattern matched, which is
+            // the end of the search path
+            for (name, capture_paths) in captures {
+                let entry = all_captures.entry(name).or_default();
+                for capture_path in capture_paths {
+                    let mut full_path = path.clone();
+                    full_path.extend_from_slice(&capture_path[1..]);
+                    entry.push(full_path);
                 }
             }
-            CBORCase::Map(map) => {
-                for (key, value) in map.iter() {
-                    // Search both keys and values
-                    let mut key_path = path.clone();
-                    key_path.push(key.clone());
-                    self.search_recursive_with_captures(
-                        key,
-                        key_path,
-                        results,
-                        all_captures,
-                    );
+        }
 
-                    let mut value_path = path.clone();
-                    value_path.push(value.clone());
-                    self.search_recursive_with_captures(
-                        value,
-                        value_path,
-                        results,
-                        all_captures,
-                    );
-                }
-            }
-            CBORCase::Tagged(_, content) => {
-                let mut tagged_path = path.clone();
-                tagged_path.push(content.clone());
-                self.search_recursive_with_captures(
-                    content,
-                    tagged_path,
-                    results,
-                    all_captures,
-                );
-            }
-            _ => {
-                // For primitive types, no further recursion
+        // Recursively search the children selected by the options, except
+        // leaves the pattern can't match
+        for (index, child) in self.keyed_children(cbor, path.len() - 1) {
+            if is_leaf(&child) && !kinds.contains(&child) {
+                continue;
             }
+            path.push(child.clone());
+            key.push(index);
+            self.search_recursive_with_captures(
+                &child,
+                path,
+                key,
+                kinds,
+                found,
+                all_captures,
+            );
+            path.pop();
+            key.pop();
         }
     }
 }
 
+/// Returns `true` if the pattern's paths can continue from the node it
+/// matches into a value inside it, as those of `{key}: value` do, so that the
+/// paths of a search continue along them.
+///
+/// The paths of a nested search lead to what it found, not through the node,
+/// so they don't continue the paths of the search around it.
+fn continues_into_value(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Structure(StructurePattern::Map(MapPattern::Value(..))) => {
+            true
+        }
+        Pattern::Meta(MetaPattern::Capture(capture)) => {
+            continues_into_value(capture.pattern())
+        }
+        Pattern::Meta(MetaPattern::And(and)) => {
+            and.patterns().iter().any(continues_into_value)
+        }
+        Pattern::Meta(MetaPattern::Or(or)) => {
+            or.patterns().iter().any(continues_into_value)
+        }
+        _ => false,
+    }
+}
+
+/// Continues `path`, which ends at the node a pattern matched, along each of
+/// the pattern's `paths` from that node.
+fn extend_path(path: &Path, paths: Vec<Path>) -> impl Iterator<Item = Path> {
+    paths.into_iter().map(move |pattern_path| {
+        let mut full_path = path.clone();
+        full_path.extend(pattern_path.into_iter().skip(1));
+        full_path
+    })
+}
+
+/// What a nested search has found out about the subtrees it visited, keyed
+/// by the address of each subtree's root: whether a match was found, and the
+/// depth the subtree was visited at. The root is held so that its address
+/// can't be reused while the memo lives.
+///
+/// A match found at some depth is also found from any shallower depth, and a
+/// match not found at some depth isn't found from any deeper one, since the
+/// depth only limits how far beneath the subtree's root is visited.
+type ExistsMemo = HashMap<usize, (CBOR, usize, bool)>;
+
+thread_local! {
+    /// The memos of the searches nested within the search in progress on
+    /// this thread, or `None` if no search is in progress.
+    static NESTED_MEMOS: RefCell<Option<Vec<(SearchPattern, ExistsMemo)>>> =
+        const { RefCell::new(None) };
+}
+
+/// Discards the memos of nested searches when the outermost search ends,
+/// even on unwinding.
+struct ClearMemos;
+
+impl Drop for ClearMemos {
+    fn drop(&mut self) { NESTED_MEMOS.set(None); }
+}
+
+/// Runs `f` as a search, so that searches nested within it remember the
+/// subtrees they visit. A search nested in the pattern of another is run
+/// at every node the outer search visits, and would otherwise visit the
+/// same subtrees again each time.
+fn in_search<R>(f: impl FnOnce() -> R) -> R {
+    if NESTED_MEMOS.with_borrow(Option::is_some) {
+        return f();
+    }
+    NESTED_MEMOS.set(Some(Vec::new()));
+    let _clear = ClearMemos;
+    f()
+}
+
+/// Returns the address identifying `cbor` in an [`ExistsMemo`].
+fn memo_key(cbor: &CBOR) -> usize { cbor.as_case() as *const CBORCase as usize }
+
+/// Returns `true` if `pattern` matches `cbor` or any node beneath it that
+/// `opts` visits, stopping at the first match rather than finding them all.
+fn exists_recursive(
+    pattern: &Pattern,
+    opts: &SearchOpts,
+    cbor: &CBOR,
+    depth: usize,
+    kinds: ValueKindSet,
+    memo: &mut Option<ExistsMemo>,
+) -> bool {
+    if let Some(&(_, memo_depth, found)) =
+        memo.as_ref().and_then(|memo| memo.get(&memo_key(cbor)))
+        && (if found {
+            depth <= memo_depth
+        } else {
+            depth >= memo_depth
+        })
+    {
+        return found;
+    }
+    let found = (kinds.contains(cbor) && {
+        count_matcher_call();
+        pattern.matches(cbor)
+    }) || opts.children(cbor, depth).iter().any(|child| {
+        (!is_leaf(child) || kinds.contains(child))
+            && exists_recursive(pattern, opts, child, depth + 1, kinds, memo)
+    });
+    if let Some(memo) = memo {
+        memo.insert(memo_key(cbor), (cbor.clone(), depth, found));
+    }
+    found
+}
+
+impl Pattern {
+    /// Returns `true` if this pattern matches the root of `haystack`.
+    ///
+    /// This is what [`Matcher::matches`] does; the name sets it apart from
+    /// [`Pattern::exists`], which also looks beneath the root.
+    pub fn matches_root(&self, haystack: &CBOR) -> bool {
+        self.matches(haystack)
+    }
+
+    /// Returns `true` if this pattern matches `haystack` or any node within
+    /// it, as `search(...)` around the pattern would.
+    ///
+    /// Nodes are visited in the same order as a search visits them, and
+    /// visiting stops at the first match, so this is cheaper than finding
+    /// the paths of a search when only their existence matters.
+    pub fn exists(&self, haystack: &CBOR) -> bool {
+        in_search(|| {
+            exists_recursive(
+                self,
+                &SearchOpts::default(),
+                haystack,
+                0,
+                ValueKindSet::matched_by(self),
+                &mut None,
+            )
+        })
+    }
+
+    /// Returns the node each path [`Matcher::paths`] returns ends at, in the
+    /// same order, for when only the matched nodes are needed.
+    ///
+    /// A `search(...)` pattern visits the tree without building a path for
+    /// each node it visits. Other patterns are matched as by
+    /// [`Matcher::paths`], since their matching navigates by the path.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use dcbor::prelude::*;
+    /// # use dcbor_pattern::Pattern;
+    /// let haystack = CBOR::from(vec![CBOR::from(1), CBOR::from("a")]);
+    /// let pattern = Pattern::parse("search(number | text)").unwrap();
+    /// assert_eq!(
+    ///     pattern.terminal_matches(&haystack),
+    ///     [CBOR::from(1), CBOR::from("a")]
+    /// );
+    /// ```
+    pub fn terminal_matches(&self, haystack: &CBOR) -> Vec<CBOR> {
+        pin_evaluation_now(|| match self {
+            Pattern::Meta(MetaPattern::Search(search)) => {
+                search.terminal_matches(haystack)
+            }
+            _ => self
+                .paths(haystack)
+                .into_iter()
+                .filter_map(|mut path| path.pop())
+                .collect(),
+        })
+    }
+}
+
 impl Default for SearchPattern {
     fn default() -> Self {
         // Create a default search pattern that matches any value
@@ -181,60 +653,55 @@ impl Default for SearchPattern {
 impl Matcher for SearchPattern {
     fn paths(&self, haystack: &CBOR) -> Vec<Path> {
         let mut result_paths = Vec::new();
-        self.search_recursive(
-            haystack,
-            vec![haystack.clone()],
-            &mut result_paths,
-        );
-
-        // Remove duplicates based on CBOR values in the path
-        let mut seen = std::collections::HashSet::new();
-        let mut unique = Vec::new();
-        for path in result_paths {
-            // Create a unique key based on the path's CBOR values
-            let path_key: Vec<_> = path
-                .iter()
-                .map(|cbor| cbor.to_cbor_data()) // Use serialized form as key
-                .collect();
-            if seen.insert(path_key) {
-                unique.push(path);
-            }
-        }
-
-        unique
+        in_search(|| {
+            self.search_recursive(
+                haystack,
+                &mut vec![haystack.clone()],
+                &mut Vec::new(),
+                ValueKindSet::matched_by(&self.pattern),
+                &mut result_paths,
+                &mut HashSet::new(),
+            )
+        });
+        result_paths
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
-        let mut result_paths = Vec::new();
-        let mut all_captures = std::collections::HashMap::new();
+    fn matches(&self, haystack: &CBOR) -> bool {
+        self.with_memo(|memo| {
+            in_search(|| {
+                exists_recursive(
+                    &self.pattern,
+                    &self.opts,
+                    haystack,
+                    0,
+                    ValueKindSet::matched_by(&self.pattern),
+                    memo,
+                )
+            })
+        })
+    }
 
-        self.search_recursive_with_captures(
-            haystack,
-            vec![haystack.clone()],
-            &mut result_paths,
-            &mut all_captures,
-        );
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        let mut found = (Vec::new(), HashSet::new());
+        let mut all_captures = Captures::new();
 
-        // Remove duplicates from result paths
-        let mut seen = std::collections::HashSet::new();
-        let mut unique_paths = Vec::new();
-        for path in result_paths {
-            let path_key: Vec<_> =
-                path.iter().map(|cbor| cbor.to_cbor_data()).collect();
-            if seen.insert(path_key) {
-                unique_paths.push(path);
-            }
-        }
+        in_search(|| {
+            self.search_recursive_with_captures(
+                haystack,
+                &mut vec![haystack.clone()],
+                &mut Vec::new(),
+                ValueKindSet::matched_by(&self.pattern),
+                &mut found,
+                &mut all_captures,
+            )
+        });
 
-        (unique_paths, all_captures)
+        (found.0, all_captures)
     }
 
     fn collect_capture_names(&self, names: &mut Vec<String>) {
         // Delegate to the inner pattern to collect its capture names
-        self.0.collect_capture_names(names);
+        self.pattern.collect_capture_names(names);
     }
 
     fn compile(
@@ -244,31 +711,228 @@ impl Matcher for SearchPattern {
         captures: &mut Vec<String>,
     ) {
         let idx = literals.len();
-        literals.push((*self.0).clone());
-
-        // Collect capture names from the inner pattern
-        let mut inner_names = Vec::new();
-        self.0.collect_capture_names(&mut inner_names);
-        let mut capture_map = Vec::new();
-
-        for name in inner_names {
-            let pos = if let Some(i) = captures.iter().position(|n| n == &name)
-            {
-                i
-            } else {
-                let i = captures.len();
-                captures.push(name.clone());
-                i
-            };
-            capture_map.push((name, pos));
-        }
+        literals.push(Pattern::Meta(crate::pattern::MetaPattern::Search(
+            self.clone(),
+        )));
 
+        let capture_map = capture_slots(&self.pattern, captures);
         code.push(Instr::Search { pat_idx: idx, capture_map });
     }
 }
 
 impl std::fmt::Display for SearchPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "search({})", self.pattern())
+        write!(f, "search({}", self.pattern())?;
+        let defaults = SearchOpts::default();
+        if let Some(max_depth) = self.opts.max_depth {
+            write!(f, ", depth: {}", max_depth)?;
+        }
+        if self.opts.visit_map_keys != defaults.visit_map_keys {
+            write!(f, ", keys: {}", self.opts.visit_map_keys)?;
+        }
+        if self.opts.visit_tagged_content != defaults.visit_tagged_content {
+            write!(f, ", tagged: {}", self.opts.visit_tagged_content)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dcbor_parse::parse_dcbor_item;
+
+    use super::*;
+
+    /// Returns the result of `f` and the number of times it ran a search's
+    /// pattern on a node.
+    fn counting_calls<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        MATCHER_CALLS.set(0);
+        let result = f();
+        (result, MATCHER_CALLS.get())
+    }
+
+    /// Returns a document of 200 records, each a map with numbers, a byte
+    /// string, and an array of numbers, with a text field in every tenth.
+    fn large_document() -> CBOR {
+        let records: Vec<CBOR> = (0..200u64)
+            .map(|i| {
+                let mut map = Map::new();
+                map.insert(1, i);
+                map.insert(2, CBOR::to_byte_string(vec![0u8; 64]));
+                map.insert(3, (0..20u64).collect::<Vec<_>>());
+                if i % 10 == 0 {
+                    map.insert(4, format!("x{}", i));
+                }
+                map.into()
+            })
+            .collect();
+        records.into()
+    }
+
+    #[test]
+    fn test_keyed_children_encodes_only_possible_duplicates() {
+        let search = SearchPattern::new(Pattern::any());
+        let keyed = |source: &str| {
+            ENCODED_CHILDREN.set(0);
+            let keys: Vec<usize> = search
+                .keyed_children(&parse_dcbor_item(source).unwrap(), 0)
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect();
+            (keys, ENCODED_CHILDREN.get())
+        };
+
+        // Siblings of different kinds or lengths are never encoded
+        assert_eq!(
+            keyed(r#"[[1, 2], [3], "a", 1, {1: 2}]"#),
+            (vec![0, 1, 2, 3, 4], 0)
+        );
+
+        // Siblings with the same summary are, to tell them apart
+        assert_eq!(keyed("[[1], [2], [1], 3]"), (vec![0, 1, 0, 3], 3));
+
+        // A deep document has one child per level, so searching it encodes
+        // nothing, where encoding every child would take quadratic time
+        let mut deep = CBOR::from(0);
+        for _ in 0..500 {
+            deep = vec![deep, CBOR::from("x")].into();
+        }
+        ENCODED_CHILDREN.set(0);
+        assert_eq!(search.paths(&deep).len(), 1001);
+        assert_eq!(ENCODED_CHILDREN.get(), 0);
+    }
+
+    #[test]
+    fn test_value_kinds() {
+        let kinds =
+            |source| ValueKindSet::matched_by(&Pattern::parse(source).unwrap());
+        assert_eq!(kinds("text"), ValueKindSet::TEXT);
+        assert_eq!(kinds("1...5"), ValueKindSet::NUMBER);
+        assert_eq!(kinds("date"), ValueKindSet::TAGGED);
+        assert_eq!(kinds("@t(\"x\")"), ValueKindSet::TEXT);
+        assert_eq!(
+            kinds("text | [*]"),
+            ValueKindSet(ValueKindSet::TEXT.0 | ValueKindSet::ARRAY.0)
+        );
+        assert_eq!(kinds("number & text"), ValueKindSet(0));
+        assert_eq!(kinds("!text"), ValueKindSet::ALL);
+        assert!(kinds("number").contains(&CBOR::from(1.5)));
+        assert!(!kinds("number").contains(&CBOR::from("1")));
+    }
+
+    #[test]
+    fn test_search_skips_nodes_of_other_kinds() {
+        let document = large_document();
+
+        // `!!text` matches the same nodes as `text`, but its kinds can't be
+        // told, so it runs on every node
+        let pruned = SearchPattern::new(Pattern::parse(r#"/^x/"#).unwrap());
+        let unpruned = SearchPattern::new(Pattern::parse(r#"!!/^x/"#).unwrap());
+
+        let (pruned_paths, pruned_calls) =
+            counting_calls(|| pruned.paths(&document));
+        let (unpruned_paths, unpruned_calls) =
+            counting_calls(|| unpruned.paths(&document));
+        assert_eq!(pruned_paths, unpruned_paths);
+        assert_eq!(pruned_paths.len(), 20);
+
+        // Only the text values are tested, where without pruning every node
+        // is: the root, and in each record the map, its three keys and
+        // values, and the twenty numbers in its array
+        assert_eq!(pruned_calls, 20);
+        assert_eq!(unpruned_calls, 1 + 200 * 27 + 20 * 2);
+
+        // Captures are found the same way
+        let pruned = SearchPattern::new(Pattern::parse("@t(/^x/)").unwrap());
+        let unpruned =
+            SearchPattern::new(Pattern::parse("@t(!!/^x/)").unwrap());
+        let (pruned_result, pruned_calls) =
+            counting_calls(|| pruned.paths_with_captures(&document));
+        let (unpruned_result, unpruned_calls) =
+            counting_calls(|| unpruned.paths_with_captures(&document));
+        assert_eq!(pruned_result, unpruned_result);
+        assert_eq!(pruned_calls, 20);
+        assert_eq!(unpruned_calls, 1 + 200 * 27 + 20 * 2);
+    }
+
+    #[test]
+    fn test_search_prunes_through_containers() {
+        let document = large_document();
+
+        // Containers are still descended into when the pattern can only
+        // match leaves beneath them
+        let pattern = SearchPattern::new(Pattern::parse("[{20}]").unwrap());
+        let (paths, calls) = counting_calls(|| pattern.paths(&document));
+        assert_eq!(paths.len(), 200);
+        assert_eq!(calls, 1 + 200);
+
+        // A pattern that can match nothing runs on nothing
+        let pattern =
+            SearchPattern::new(Pattern::parse("number & text").unwrap());
+        let (paths, calls) = counting_calls(|| pattern.paths(&document));
+        assert!(paths.is_empty());
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn test_exists_stops_at_first_match() {
+        let document = large_document();
+
+        // The first text value is the only node tested, where finding the
+        // paths tests all twenty
+        let pattern = Pattern::parse("/^x/").unwrap();
+        let (exists, calls) = counting_calls(|| pattern.exists(&document));
+        assert!(exists);
+        assert_eq!(calls, 1);
+
+        // Without pruning, the nodes up to the first record's text value are
+        // tested: the root, the record, its first three keys and values, the
+        // twenty numbers in its array, and its fourth key and value
+        let pattern = Pattern::parse("!!/^x/").unwrap();
+        let (exists, calls) = counting_calls(|| pattern.exists(&document));
+        assert!(exists);
+        assert_eq!(calls, 1 + 1 + 6 + 20 + 2);
+
+        // A search pattern stops at the first match the same way
+        let search = SearchPattern::new(pattern);
+        let (matches, calls) = counting_calls(|| search.matches(&document));
+        assert!(matches);
+        assert_eq!(calls, 1 + 1 + 6 + 20 + 2);
+
+        // Without a match, every node that could match is tested
+        let pattern = Pattern::parse("/^y/").unwrap();
+        let (exists, calls) = counting_calls(|| pattern.exists(&document));
+        assert!(!exists);
+        assert_eq!(calls, 20);
+    }
+
+    #[test]
+    fn test_nested_search_tests_each_node_once() {
+        // Maps nested 100 deep, each also holding a number, with text at
+        // the bottom
+        let mut document = CBOR::from("x");
+        for i in 0..100u64 {
+            let mut map = Map::new();
+            map.insert(1, document);
+            map.insert(2, i);
+            document = map.into();
+        }
+
+        // The outer search tests every node, and the inner search, which
+        // runs at every map, tests each text node once over all of its runs
+        // rather than once per map above it
+        let pattern =
+            SearchPattern::new(Pattern::parse("search(text)").unwrap());
+        let (paths, calls) = counting_calls(|| pattern.paths(&document));
+        assert_eq!(paths.len(), 101);
+        assert_eq!(calls, (1 + 100 * 4) + 1);
+
+        // Outside a search, nothing is remembered between runs
+        let inner = SearchPattern::new(Pattern::any_text());
+        let (_, calls) = counting_calls(|| {
+            inner.matches(&document);
+            inner.matches(&document)
+        });
+        assert_eq!(calls, 2);
     }
 }
@@ -1,12 +1,25 @@
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{
+    Captures, Matcher, MetaPattern, Path, Pattern, vm::Instr,
+};
 
 /// A pattern that matches a sequence of patterns in order.
 ///
-/// This pattern is used to match multiple patterns consecutively,
-/// which is particularly useful for array elements or other sequential
-/// data structures.
+/// This pattern is used to match multiple patterns consecutively against
+/// the elements of an array pattern such as `[number, text]`, or against
+/// the items of a dCBOR sequence with [`Pattern::match_sequence`].
+///
+/// A sequence describes several items, so on its own it matches no single
+/// value: [`Matcher::paths`] and [`Matcher::paths_with_captures`] return
+/// nothing for a sequence that is not inside an array pattern.
+///
+/// Between array brackets a sequence is written with commas alone, as in
+/// `[number, text]`. Anywhere else it is written in parentheses, as in
+/// `(number, text)` or `[(number, text) | null]`, and that is how it
+/// displays, so the text of any pattern parses back to the same pattern.
+/// A bare sequence outside an array, such as `number, text`, is rejected
+/// with [`Error::SequenceOutsideArray`](crate::Error::SequenceOutsideArray).
 ///
 /// # Examples
 ///
@@ -21,11 +34,27 @@ use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
 /// ]);
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SequencePattern(Vec<Pattern>);
 
 impl SequencePattern {
     /// Creates a new sequence pattern with the given patterns.
-    pub fn new(patterns: Vec<Pattern>) -> Self { Self(patterns) }
+    ///
+    /// A sequence among the patterns is spliced into this one, as a run of
+    /// elements within a run of elements is no different from the elements
+    /// themselves.
+    pub fn new(patterns: Vec<Pattern>) -> Self {
+        let mut flattened = Vec::with_capacity(patterns.len());
+        for pattern in patterns {
+            match pattern {
+                Pattern::Meta(MetaPattern::Sequence(sequence)) => {
+                    flattened.extend(sequence.0)
+                }
+                pattern => flattened.push(pattern),
+            }
+        }
+        Self(flattened)
+    }
 
     /// Returns a reference to the patterns in this sequence.
     pub fn patterns(&self) -> &[Pattern] { &self.0 }
@@ -35,20 +64,21 @@ impl SequencePattern {
 
     /// Returns the number of patterns in this sequence.
     pub fn len(&self) -> usize { self.patterns().len() }
+
+    /// Returns the patterns of this sequence separated by commas, without the
+    /// parentheses that set the sequence apart from what surrounds it.
+    fn elements_string(&self) -> String {
+        let patterns: Vec<String> =
+            self.patterns().iter().map(|p| p.to_string()).collect();
+        patterns.join(", ")
+    }
 }
 
 impl Matcher for SequencePattern {
     fn paths(&self, _haystack: &CBOR) -> Vec<Path> {
-        // For a sequence pattern, we need to find paths where all patterns
-        // match consecutively. This is primarily used within array patterns
-        // or other structural contexts.
-        //
-        // Since sequence patterns are typically used as part of other patterns
-        // (like arrays), and not as standalone matchers against arbitrary CBOR,
-        // we return empty paths when used directly.
-        //
-        // The actual sequence matching logic is handled by the VM through
-        // SequenceStart and SequenceNext instructions.
+        // A sequence only matches the elements of an array, which the array
+        // pattern and the VM's sequence instructions handle, so it matches
+        // no single value on its own
         vec![]
     }
 
@@ -103,26 +133,31 @@ impl Matcher for SequencePattern {
             || self.patterns().iter().any(|p| p.is_complex())
     }
 
-    fn paths_with_captures(
-        &self,
-        cbor: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
-        // For sequence patterns, the capture logic is handled by the
-        // VM when compiled by the main Pattern. When called directly,
-        // we use the basic implementation.
-        (self.paths(cbor), std::collections::HashMap::new())
+    fn paths_with_captures(&self, cbor: &CBOR) -> (Vec<Path>, Captures) {
+        (self.paths(cbor), Captures::new())
     }
 }
 
 impl std::fmt::Display for SequencePattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.patterns().is_empty() {
-            write!(f, "()")
-        } else {
-            let patterns_str: Vec<String> =
-                self.patterns().iter().map(|p| p.to_string()).collect();
-            write!(f, "{}", patterns_str.join(", "))
+        match self.patterns() {
+            [pattern] => write!(f, "{}", pattern),
+            _ => write!(f, "({})", self.elements_string()),
+        }
+    }
+}
+
+/// Formats `pattern` to stand alone between brackets or parentheses, where a
+/// sequence needs no parentheses of its own, as in `[number, text]` or
+/// `(number, text)*`.
+pub(crate) fn format_enclosed(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Meta(MetaPattern::Sequence(sequence))
+            if sequence.len() > 1 =>
+        {
+            sequence.elements_string()
         }
+        _ => pattern.to_string(),
     }
 }
 
@@ -163,11 +198,22 @@ mod tests {
             Pattern::text("third"),
         ];
         let sequence = SequencePattern::new(patterns);
-        let display = sequence.to_string();
-        assert!(display.contains("first"));
-        assert!(display.contains("second"));
-        assert!(display.contains("third"));
-        assert!(display.contains(", "));
+        assert_eq!(sequence.to_string(), r#"("first", "second", "third")"#);
+
+        // A sequence of one is just its pattern
+        let sequence = SequencePattern::new(vec![Pattern::text("only")]);
+        assert_eq!(sequence.to_string(), r#""only""#);
+    }
+
+    #[test]
+    fn test_sequence_pattern_new_flattens() {
+        let inner =
+            Pattern::sequence(vec![Pattern::number(2), Pattern::number(3)]);
+        let sequence = SequencePattern::new(vec![Pattern::number(1), inner]);
+        assert_eq!(
+            sequence.patterns(),
+            &[Pattern::number(1), Pattern::number(2), Pattern::number(3)]
+        );
     }
 
     #[test]
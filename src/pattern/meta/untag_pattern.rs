@@ -0,0 +1,157 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{
+    Captures, Matcher, Path, Pattern,
+    vm::{Axis, Instr},
+};
+
+/// A pattern that matches a node either directly or through any number of
+/// tag layers around it.
+///
+/// The inner pattern is tried against the node itself and, if the node is
+/// tagged, against its content, and so on through nested tags. A match
+/// through tags yields a path that runs from the outermost tagged value
+/// through each tag layer to the content that matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UntagPattern(Box<Pattern>);
+
+impl UntagPattern {
+    /// Creates a new `UntagPattern` that matches the given pattern with or
+    /// without tags around it.
+    pub fn new(pattern: Pattern) -> Self { UntagPattern(Box::new(pattern)) }
+
+    /// Returns the pattern matched beneath the tags.
+    pub fn pattern(&self) -> &Pattern { &self.0 }
+
+    /// Returns the node and each tagged content beneath it, outermost first,
+    /// as paths from the node.
+    fn layers(haystack: &CBOR) -> Vec<Path> {
+        let mut layers = vec![vec![haystack.clone()]];
+        while let CBORCase::Tagged(_, content) =
+            layers.last().unwrap().last().unwrap().as_case()
+        {
+            let mut path = layers.last().unwrap().clone();
+            path.push(content.clone());
+            layers.push(path);
+        }
+        layers
+    }
+}
+
+impl Default for UntagPattern {
+    fn default() -> Self { Self::new(Pattern::any()) }
+}
+
+impl Matcher for UntagPattern {
+    fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        let mut paths = Vec::new();
+        for layer in Self::layers(haystack) {
+            let (node, prefix) = layer.split_last().unwrap();
+            for path in self.pattern().paths(node) {
+                let mut full_path = prefix.to_vec();
+                full_path.extend(path);
+                paths.push(full_path);
+            }
+        }
+        paths
+    }
+
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        let mut paths = Vec::new();
+        let mut all_captures = Captures::new();
+
+        for layer in Self::layers(haystack) {
+            let (node, prefix) = layer.split_last().unwrap();
+            let (layer_paths, captures) =
+                self.pattern().paths_with_captures(node);
+            if layer_paths.is_empty() {
+                continue;
+            }
+
+            // Paths are rooted at the untagged content, so prefix them with
+            // the tag layers above it
+            let with_prefix = |path: Path| {
+                let mut full_path = prefix.to_vec();
+                full_path.extend(path);
+                full_path
+            };
+            paths.extend(layer_paths.into_iter().map(with_prefix));
+            for (name, capture_paths) in captures {
+                all_captures
+                    .entry(name)
+                    .or_default()
+                    .extend(capture_paths.into_iter().map(with_prefix));
+            }
+        }
+
+        (paths, all_captures)
+    }
+
+    /// Compile into byte-code: a loop that either matches the inner pattern
+    /// at the current node or steps into tagged content and tries again.
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        captures: &mut Vec<String>,
+    ) {
+        let split = code.len();
+        code.push(Instr::Split { a: 0, b: 0 }); // Placeholder
+
+        let direct = code.len();
+        self.pattern().compile(code, literals, captures);
+        let jump = code.len();
+        code.push(Instr::Jump(0)); // Placeholder
+
+        // Descend through one tag layer, keeping it in the path but not as a
+        // frame that an enclosing `Pop` would return to
+        let untag = code.len();
+        code.push(Instr::PushAxis(Axis::TaggedContent));
+        code.push(Instr::DropAxis);
+        code.push(Instr::Jump(split));
+
+        code[split] = Instr::Split { a: direct, b: untag };
+        code[jump] = Instr::Jump(code.len());
+    }
+
+    fn collect_capture_names(&self, names: &mut Vec<String>) {
+        self.pattern().collect_capture_names(names);
+    }
+}
+
+impl std::fmt::Display for UntagPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "untag({})", self.pattern())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_untag_pattern_display() {
+        let pattern = UntagPattern::new(Pattern::number(42));
+        assert_eq!(pattern.to_string(), "untag(42)");
+    }
+
+    #[test]
+    fn test_untag_pattern_paths() {
+        let pattern = UntagPattern::new(Pattern::number(42));
+
+        let plain = CBOR::from(42);
+        assert_eq!(pattern.paths(&plain), vec![vec![plain.clone()]]);
+
+        let inner = CBOR::to_tagged_value(200, 42);
+        let outer = CBOR::to_tagged_value(100, inner.clone());
+        assert_eq!(
+            pattern.paths(&outer),
+            vec![vec![outer.clone(), inner, plain]]
+        );
+
+        // Untagged content that doesn't match, and values that aren't tagged
+        assert!(pattern.paths(&CBOR::to_tagged_value(100, 7)).is_empty());
+        assert!(pattern.paths(&CBOR::from("42")).is_empty());
+    }
+}
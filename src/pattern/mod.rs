@@ -1,13 +1,39 @@
+mod budget;
+mod complexity;
+mod custom_pattern;
+mod data;
+mod explain;
+mod extract;
+mod find;
+mod introspect;
+mod match_result;
 mod matcher;
 mod meta;
+mod simplify;
+#[cfg(feature = "streaming")]
+mod streaming;
 mod structure;
+mod validate;
 mod value;
 mod vm;
 
+pub use budget::MatchOpts;
+pub(crate) use budget::{admit_threads, step};
+pub use complexity::{Complexity, Limits};
+pub(crate) use custom_pattern::registered_custom;
+pub use custom_pattern::{CustomPattern, DynMatcher};
 use dcbor::prelude::*;
+pub use explain::*;
+pub use extract::*;
+pub use find::*;
+pub use introspect::*;
+pub use match_result::*;
 pub use matcher::*;
 pub use meta::*;
+#[cfg(feature = "streaming")]
+pub use streaming::*;
 pub use structure::*;
+pub use validate::*;
 pub use value::*;
 pub use vm::*;
 
@@ -15,13 +41,147 @@ use crate::{Error, Result};
 
 pub type Path = Vec<CBOR>;
 
+/// Named captures, mapping each capture name to the paths it captured.
+///
+/// The captures returned by [`Pattern`] list their names in the order the
+/// capture patterns appear in the pattern text, and each name's paths in
+/// document order.
+pub type Captures = indexmap::IndexMap<String, Vec<Path>>;
+
+/// Returns the position of each step of `path` among the children of the node
+/// before it. Children are numbered in pre-order: array elements, then the key
+/// and value of each map entry, then tagged content. Steps that are not
+/// children of the previous node, such as synthesized sub-arrays, sort after
+/// all real children.
+fn document_position(
+    path: &Path,
+    positions: &mut ChildPositions,
+) -> Vec<usize> {
+    path.windows(2)
+        .map(|pair| {
+            positions.position(&pair[0], &pair[1]).unwrap_or(usize::MAX)
+        })
+        .collect()
+}
+
+/// The number of children from which a parent's children are indexed rather
+/// than searched for each path.
+const WIDE_PARENT: usize = 16;
+
+/// Finds the positions of children among the children of their parents,
+/// indexing the children of each wide parent the first time it is seen, so
+/// that sorting many paths through a wide array or map doesn't search it
+/// once for each path.
+#[derive(Default)]
+struct ChildPositions {
+    indexed: Vec<(CBOR, std::collections::HashMap<CBOR, usize>)>,
+}
+
+impl ChildPositions {
+    fn position(&mut self, parent: &CBOR, child: &CBOR) -> Option<usize> {
+        let width = match parent.as_case() {
+            CBORCase::Array(arr) => arr.len(),
+            CBORCase::Map(map) => 2 * map.len(),
+            _ => 0,
+        };
+        if width < WIDE_PARENT {
+            return child_position(parent, child);
+        }
+        let slot = match self.indexed.iter().position(|(p, _)| p == parent) {
+            Some(slot) => slot,
+            None => {
+                self.indexed.push((parent.clone(), index_children(parent)));
+                self.indexed.len() - 1
+            }
+        };
+        self.indexed[slot].1.get(child).copied()
+    }
+}
+
+/// Returns the position of `child` among the children of `parent`, or
+/// `None` if it isn't one.
+fn child_position(parent: &CBOR, child: &CBOR) -> Option<usize> {
+    match parent.as_case() {
+        CBORCase::Array(arr) => arr.iter().position(|e| same_node(e, child)),
+        CBORCase::Map(map) => {
+            map.iter().enumerate().find_map(|(i, (key, value))| {
+                if same_node(key, child) {
+                    Some(2 * i)
+                } else if same_node(value, child) {
+                    Some(2 * i + 1)
+                } else {
+                    None
+                }
+            })
+        }
+        CBORCase::Tagged(_, content) => same_node(content, child).then_some(0),
+        _ => None,
+    }
+}
+
+/// Returns `true` if `a` equals `b`, without comparing their contents when
+/// they share them, as the steps of a path and the children they were cloned
+/// from do. Comparing the contents of deeply nested values takes time and
+/// stack in their depth.
+fn same_node(a: &CBOR, b: &CBOR) -> bool {
+    std::ptr::eq(a.as_case(), b.as_case()) || a == b
+}
+
+/// Maps each child of an array or map to its first position, as
+/// [`child_position`] finds it.
+fn index_children(parent: &CBOR) -> std::collections::HashMap<CBOR, usize> {
+    let mut index = std::collections::HashMap::new();
+    match parent.as_case() {
+        CBORCase::Array(arr) => {
+            for (i, element) in arr.iter().enumerate() {
+                index.entry(element.clone()).or_insert(i);
+            }
+        }
+        CBORCase::Map(map) => {
+            for (i, (key, value)) in map.iter().enumerate() {
+                index.entry(key.clone()).or_insert(2 * i);
+                index.entry(value.clone()).or_insert(2 * i + 1);
+            }
+        }
+        _ => {}
+    }
+    index
+}
+
+/// Sorts paths sharing a root into document order, so that a path to a node
+/// comes before the paths to its descendants and to its later siblings.
+pub(crate) fn sort_paths(paths: &mut [Path]) {
+    if paths.len() > 1 {
+        let mut positions = ChildPositions::default();
+        paths
+            .sort_by_cached_key(|path| document_position(path, &mut positions));
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Pattern {
     Value(ValuePattern),
     Structure(StructurePattern),
     Meta(MetaPattern),
+    /// Matches values accepted by matching logic supplied by the
+    /// application.
+    Custom(CustomPattern),
 }
 
+// Patterns and compiled programs are shared between threads, so everything
+// they hold must be `Send` and `Sync`; this fails to compile if anything
+// isn't. Matching only touches thread-local state, apart from looking up
+// names in the known values registry, which is locked for each lookup.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Pattern>();
+    assert_send_sync::<Program>();
+    assert_send_sync::<MatchResult>();
+    assert_send_sync::<crate::PatternLibrary>();
+    assert_send_sync::<crate::Error>();
+};
+
 impl Pattern {
     /// Creates a pattern that matches any boolean value.
     pub fn any_bool() -> Self {
@@ -64,6 +224,25 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches numbers within a range that includes
+    /// its start but not its end, e.g. `10...<20`.
+    pub fn number_range_exclusive<A>(range: std::ops::Range<A>) -> Self
+    where
+        A: Into<f64> + Copy,
+    {
+        Pattern::Value(ValuePattern::Number(
+            crate::pattern::value::NumberPattern::range_exclusive(range),
+        ))
+    }
+
+    /// Creates a pattern that matches numbers within the given bounds, each
+    /// of which may be inclusive, exclusive, or unbounded, e.g. `10<...<20`.
+    pub fn number_bounded(bounds: impl std::ops::RangeBounds<f64>) -> Self {
+        Pattern::Value(ValuePattern::Number(
+            crate::pattern::value::NumberPattern::bounded(bounds),
+        ))
+    }
+
     /// Creates a pattern that matches numbers greater than the specified value.
     pub fn number_greater_than<T>(value: T) -> Self
     where
@@ -127,6 +306,16 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches any of the given numbers.
+    pub fn number_in<T>(values: impl IntoIterator<Item = T>) -> Self
+    where
+        T: Into<f64>,
+    {
+        Pattern::Value(ValuePattern::Number(
+            crate::pattern::value::NumberPattern::set(values),
+        ))
+    }
+
     /// Creates a pattern that matches any text value.
     pub fn any_text() -> Self {
         Pattern::Value(ValuePattern::Text(
@@ -148,6 +337,37 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches text starting with the given prefix.
+    pub fn text_prefix<T: Into<String>>(prefix: T) -> Self {
+        Pattern::Value(ValuePattern::Text(
+            crate::pattern::value::TextPattern::prefix(prefix),
+        ))
+    }
+
+    /// Creates a pattern that matches text ending with the given suffix.
+    pub fn text_suffix<T: Into<String>>(suffix: T) -> Self {
+        Pattern::Value(ValuePattern::Text(
+            crate::pattern::value::TextPattern::suffix(suffix),
+        ))
+    }
+
+    /// Creates a pattern that matches a specific text value, ignoring case.
+    pub fn text_case_insensitive<T: Into<String>>(value: T) -> Self {
+        Pattern::Value(ValuePattern::Text(
+            crate::pattern::value::TextPattern::case_insensitive(value),
+        ))
+    }
+
+    /// Creates a pattern that matches any of the given text values, checking
+    /// membership in a single lookup rather than one alternative at a time.
+    pub fn text_in<T: Into<String>>(
+        values: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Pattern::Value(ValuePattern::Text(
+            crate::pattern::value::TextPattern::set(values),
+        ))
+    }
+
     /// Creates a pattern that matches any byte string value.
     pub fn any_byte_string() -> Self {
         Pattern::Value(ValuePattern::ByteString(
@@ -169,6 +389,66 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches byte strings whose length in bytes is
+    /// within the given interval.
+    pub fn byte_string_with_length(
+        interval: impl Into<crate::Interval>,
+    ) -> Self {
+        Pattern::Value(ValuePattern::ByteString(
+            crate::pattern::value::ByteStringPattern::length(interval),
+        ))
+    }
+
+    /// Creates a pattern that matches byte strings starting with the given
+    /// bytes.
+    pub fn byte_string_prefix(prefix: impl AsRef<[u8]>) -> Self {
+        Pattern::Value(ValuePattern::ByteString(
+            crate::pattern::value::ByteStringPattern::prefix(prefix),
+        ))
+    }
+
+    /// Creates a pattern that matches any of the given byte strings.
+    pub fn byte_string_in(
+        values: impl IntoIterator<Item = impl AsRef<[u8]>>,
+    ) -> Self {
+        Pattern::Value(ValuePattern::ByteString(
+            crate::pattern::value::ByteStringPattern::set(values),
+        ))
+    }
+
+    /// Creates a pattern that matches byte strings holding the encoding of a
+    /// dCBOR item that `pattern` matches, written `bstr(cbor: pattern)`.
+    ///
+    /// Byte strings that aren't a deterministic encoding don't match. Each
+    /// path runs from the byte string into the decoded item, as if the item
+    /// were the byte string's only child.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use dcbor::prelude::*;
+    /// # use dcbor_pattern::{Matcher, Pattern};
+    /// let item = CBOR::from(vec![1, 2]);
+    /// let haystack = CBOR::to_byte_string(item.to_cbor_data());
+    /// let pattern =
+    ///     Pattern::byte_string_decoded(Pattern::parse("[*, 2]").unwrap());
+    /// assert_eq!(pattern.paths(&haystack), [vec![haystack.clone(), item]]);
+    /// assert!(!pattern.matches(&CBOR::to_byte_string([0xff])));
+    /// ```
+    pub fn byte_string_decoded(pattern: Pattern) -> Self {
+        Pattern::Value(ValuePattern::ByteString(
+            crate::pattern::value::ByteStringPattern::decoded(pattern),
+        ))
+    }
+
+    /// Creates a pattern that matches only values equal to the given dCBOR
+    /// item.
+    pub fn cbor(value: impl Into<CBOR>) -> Self {
+        Pattern::Value(ValuePattern::Cbor(
+            crate::pattern::value::CBORPattern::new(value),
+        ))
+    }
+
     /// Creates a pattern that matches any date value.
     pub fn any_date() -> Self {
         Pattern::Value(ValuePattern::Date(
@@ -222,6 +502,45 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches dates no later than the current time.
+    pub fn date_past() -> Self {
+        Pattern::Value(ValuePattern::Date(
+            crate::pattern::value::DatePattern::past(),
+        ))
+    }
+
+    /// Creates a pattern that matches dates no earlier than the current time.
+    pub fn date_future() -> Self {
+        Pattern::Value(ValuePattern::Date(
+            crate::pattern::value::DatePattern::future(),
+        ))
+    }
+
+    /// Creates a pattern that matches dates within the given duration before
+    /// the current time.
+    pub fn date_within_past(duration: std::time::Duration) -> Self {
+        Pattern::Value(ValuePattern::Date(
+            crate::pattern::value::DatePattern::within_past(duration),
+        ))
+    }
+
+    /// Creates a pattern that matches dates within the given duration after
+    /// the current time.
+    pub fn date_within_future(duration: std::time::Duration) -> Self {
+        Pattern::Value(ValuePattern::Date(
+            crate::pattern::value::DatePattern::within_future(duration),
+        ))
+    }
+
+    /// Creates a pattern that matches the dates `pattern` matches, whether
+    /// encoded as tag 1 with a numeric timestamp or as tag 0 with an ISO-8601
+    /// string.
+    pub fn date_any_encoding(
+        pattern: crate::pattern::value::DatePattern,
+    ) -> Self {
+        Pattern::Value(ValuePattern::Date(pattern.any_encoding()))
+    }
+
     /// Creates a pattern that matches null values.
     pub fn null() -> Self {
         Pattern::Value(ValuePattern::Null(crate::pattern::value::NullPattern))
@@ -256,6 +575,23 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches any of the given known values.
+    pub fn known_value_set(
+        values: impl IntoIterator<Item = known_values::KnownValue>,
+    ) -> Self {
+        Pattern::Value(ValuePattern::KnownValue(
+            crate::pattern::value::KnownValuePattern::set(values),
+        ))
+    }
+
+    /// Creates a pattern that matches known values whose numeric value is
+    /// within the inclusive range.
+    pub fn known_value_range(range: std::ops::RangeInclusive<u64>) -> Self {
+        Pattern::Value(ValuePattern::KnownValue(
+            crate::pattern::value::KnownValuePattern::range(range),
+        ))
+    }
+
     // Digest pattern convenience methods
 
     /// Creates a pattern that matches any digest value.
@@ -286,6 +622,22 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches values whose dCBOR encoding has the
+    /// specified digest.
+    pub fn encoded_digest(digest: bc_components::Digest) -> Self {
+        Pattern::Value(ValuePattern::EncodedDigest(
+            crate::pattern::value::EncodedDigestPattern::digest(digest),
+        ))
+    }
+
+    /// Creates a pattern that matches values whose dCBOR encoding has a
+    /// digest with the specified prefix.
+    pub fn encoded_digest_prefix(prefix: impl AsRef<[u8]>) -> Self {
+        Pattern::Value(ValuePattern::EncodedDigest(
+            crate::pattern::value::EncodedDigestPattern::prefix(prefix),
+        ))
+    }
+
     /// Creates a pattern that always matches any CBOR value.
     pub fn any() -> Self {
         Pattern::Meta(MetaPattern::Any(crate::pattern::meta::AnyPattern::new()))
@@ -305,13 +657,45 @@ impl Pattern {
         )))
     }
 
+    /// Creates a pattern that matches if all of `patterns` match, taking the
+    /// patterns of any that are themselves AND patterns in their place, so
+    /// that `all_of(&[a & b, c])` is `a & b & c`.
+    ///
+    /// With no patterns, it matches every value.
+    pub fn all_of(patterns: &[Pattern]) -> Self {
+        Pattern::Meta(MetaPattern::And(patterns.iter().cloned().collect()))
+    }
+
+    /// Creates a pattern that matches if any of `patterns` match, taking the
+    /// alternatives of any that are themselves OR patterns in their place,
+    /// so that `any_of(&[a | b, c])` is `a | b | c`.
+    ///
+    /// With no patterns, it matches no value.
+    pub fn any_of(patterns: &[Pattern]) -> Self {
+        Pattern::Meta(MetaPattern::Or(patterns.iter().cloned().collect()))
+    }
+
     /// Creates a pattern that matches if the inner pattern does not match.
+    ///
+    /// Captures inside `pattern` never capture anything, since the pattern
+    /// only matches where `pattern` doesn't. The parser rejects them, and
+    /// [`Pattern::try_not_matching`] does too.
     pub fn not_matching(pattern: Pattern) -> Self {
         Pattern::Meta(MetaPattern::Not(crate::pattern::meta::NotPattern::new(
             pattern,
         )))
     }
 
+    /// Creates a pattern that matches if the inner pattern does not match,
+    /// failing with [`Error::CaptureInsideNegation`] if `pattern` contains a
+    /// capture. The error has no span, as there is no source to point into.
+    pub fn try_not_matching(pattern: Pattern) -> Result<Self> {
+        match pattern.capture_names().into_iter().next() {
+            Some(name) => Err(Error::CaptureInsideNegation(name, None)),
+            None => Ok(Self::not_matching(pattern)),
+        }
+    }
+
     /// Creates a pattern that captures matches with the given name.
     pub fn capture(name: impl AsRef<str>, pattern: Pattern) -> Self {
         Pattern::Meta(MetaPattern::Capture(
@@ -319,6 +703,74 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches the direct children of a node: array
+    /// elements, map keys, map values, and tagged content.
+    pub fn child(pattern: Pattern) -> Self {
+        Pattern::Meta(MetaPattern::Child(
+            crate::pattern::meta::ChildPattern::new(pattern),
+        ))
+    }
+
+    /// Creates a pattern that matches a node that matches `pattern` either
+    /// directly or beneath any number of tags.
+    pub fn untag(pattern: Pattern) -> Self {
+        Pattern::Meta(MetaPattern::Untag(
+            crate::pattern::meta::UntagPattern::new(pattern),
+        ))
+    }
+
+    /// Creates a pattern that follows `steps` from a node, through the values
+    /// under text keys of maps and the elements at indexes of arrays, and
+    /// matches `pattern` at the destination.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use dcbor::prelude::*;
+    /// # use dcbor_pattern::{Matcher, Pattern};
+    /// let pattern =
+    ///     Pattern::at(["items".into(), 2.into()], Pattern::any_number());
+    /// assert_eq!(pattern.to_string(), r#"at("items", 2, number)"#);
+    ///
+    /// let mut map = Map::new();
+    /// map.insert("items", vec![1, 2, 3]);
+    /// assert!(pattern.matches(&CBOR::from(map)));
+    /// ```
+    pub fn at(
+        steps: impl IntoIterator<Item = crate::pattern::meta::PathStep>,
+        pattern: Pattern,
+    ) -> Self {
+        Pattern::Meta(MetaPattern::At(crate::pattern::meta::AtPattern::new(
+            steps.into_iter().collect(),
+            pattern,
+        )))
+    }
+
+    /// Creates a pattern that matches the entries of a map whose key and value
+    /// match the given patterns. Each matching entry yields a path ending at
+    /// the entry, represented as the array `[key, value]`.
+    pub fn entry(key: Pattern, value: Pattern) -> Self {
+        Pattern::Meta(MetaPattern::Entry(
+            crate::pattern::meta::EntryPattern::new(key, value),
+        ))
+    }
+
+    /// Creates a pattern that matches a node only if the given pattern matches
+    /// nowhere in its subtree, including the node itself.
+    pub fn none(pattern: Pattern) -> Self {
+        Pattern::Meta(MetaPattern::None(
+            crate::pattern::meta::NonePattern::new(pattern),
+        ))
+    }
+
+    /// Creates a back-reference pattern that matches a value equal to the one
+    /// most recently captured with the given name.
+    pub fn backref(name: impl AsRef<str>) -> Self {
+        Pattern::Meta(MetaPattern::Backref(
+            crate::pattern::meta::BackrefPattern::new(name),
+        ))
+    }
+
     /// Creates a search pattern that recursively searches the entire dCBOR
     /// tree.
     pub fn search(pattern: Pattern) -> Self {
@@ -327,6 +779,17 @@ impl Pattern {
         ))
     }
 
+    /// Creates a search pattern that recursively searches for the given
+    /// pattern, limited by the given traversal options.
+    pub fn search_with(
+        pattern: Pattern,
+        opts: crate::pattern::meta::SearchOpts,
+    ) -> Self {
+        Pattern::Meta(MetaPattern::Search(
+            crate::pattern::meta::SearchPattern::with_opts(pattern, opts),
+        ))
+    }
+
     /// Creates a pattern that matches with repetition using a quantifier.
     pub fn repeat(pattern: Pattern, quantifier: crate::Quantifier) -> Self {
         Pattern::Meta(MetaPattern::Repeat(
@@ -334,14 +797,20 @@ impl Pattern {
         ))
     }
 
-    /// Creates a pattern that wraps another pattern (matches exactly once).
-    pub fn group(pattern: Pattern) -> Self {
-        Pattern::Meta(MetaPattern::Repeat(
-            crate::pattern::meta::RepeatPattern::new(pattern),
-        ))
-    }
+    /// Returns `pattern` unchanged, as parentheses now only group.
+    ///
+    /// Parentheses used to parse to a repeat matching exactly once, which
+    /// this created. [`Pattern::repeat`] with [`Quantifier::default`](
+    /// crate::Quantifier::default) still creates that repeat, written
+    /// `(pattern){1}`.
+    #[deprecated(note = "parentheses only group, so use the pattern itself")]
+    pub fn group(pattern: Pattern) -> Self { pattern }
 
     /// Creates a sequence pattern that matches patterns in order.
+    ///
+    /// A sequence matches the elements of an array when it is the content of
+    /// an array pattern such as `[number, text]`, or the items given to
+    /// [`Pattern::match_sequence`]; on its own it matches no value.
     pub fn sequence(patterns: Vec<Pattern>) -> Self {
         Pattern::Meta(MetaPattern::Sequence(
             crate::pattern::meta::SequencePattern::new(patterns),
@@ -361,6 +830,16 @@ impl Pattern {
             crate::pattern::structure::MapPattern::any(),
         ))
     }
+
+    /// Creates a pattern that matches a map with an entry whose key and value
+    /// match the given patterns, with paths continuing into the value.
+    pub fn map_value(key: Pattern, value: Pattern) -> Self {
+        Pattern::Structure(crate::pattern::structure::StructurePattern::Map(
+            crate::pattern::structure::MapPattern::with_value_at_key(
+                key, value,
+            ),
+        ))
+    }
 }
 
 impl Pattern {
@@ -378,6 +857,14 @@ impl Pattern {
         ))
     }
 
+    /// Creates a pattern that matches a tagged item with any tag and content
+    /// that matches `pattern`.
+    pub fn any_tagged_with_content(pattern: Pattern) -> Self {
+        Pattern::Structure(crate::pattern::structure::StructurePattern::Tagged(
+            crate::pattern::structure::TaggedPattern::any_with_content(pattern),
+        ))
+    }
+
     /// Creates a pattern that matches a tagged item with content pattern and
     /// a specific tag name.
     pub fn tagged_name(name: impl Into<String>, pattern: Pattern) -> Self {
@@ -395,6 +882,141 @@ impl Pattern {
             ),
         ))
     }
+
+    /// Creates a pattern that matches a tagged item with content pattern and
+    /// a tag number within the inclusive range.
+    pub fn tagged_range(
+        range: std::ops::RangeInclusive<u64>,
+        pattern: Pattern,
+    ) -> Self {
+        Pattern::Structure(crate::pattern::structure::StructurePattern::Tagged(
+            crate::pattern::structure::TaggedPattern::with_tag_range(
+                range, pattern,
+            ),
+        ))
+    }
+
+    /// Creates a pattern that matches a tagged item with content pattern and
+    /// a tag number that is one of `tags`.
+    pub fn tagged_set(
+        tags: impl IntoIterator<Item = u64>,
+        pattern: Pattern,
+    ) -> Self {
+        Pattern::Structure(crate::pattern::structure::StructurePattern::Tagged(
+            crate::pattern::structure::TaggedPattern::with_tag_set(
+                tags, pattern,
+            ),
+        ))
+    }
+}
+
+impl Pattern {
+    /// Evaluates this pattern against every item in `items`, returning one
+    /// `bool` per item indicating whether it matched.
+    ///
+    /// The pattern is compiled once into a [`Program`], which is run on every
+    /// item. The result for each item is identical to calling
+    /// [`Matcher::matches`] on it.
+    pub fn filter<'a>(
+        &self,
+        items: impl IntoIterator<Item = &'a CBOR>,
+    ) -> Vec<bool> {
+        let program = Program::compile(self);
+        items
+            .into_iter()
+            .map(|item| program.matches(item))
+            .collect()
+    }
+
+    /// Returns `true` if this pattern matches at least one of `items`.
+    ///
+    /// The pattern is compiled once, as for [`Pattern::filter`], and
+    /// evaluation stops at the first matching item.
+    pub fn matches_any_of(&self, items: &[CBOR]) -> bool {
+        let program = Program::compile(self);
+        items.iter().any(|item| program.matches(item))
+    }
+
+    /// Parallel version of [`Pattern::filter`], evaluating items on the rayon
+    /// thread pool with a single compiled [`Program`] shared by every thread.
+    ///
+    /// Results are returned in the same order as `items`.
+    #[cfg(feature = "parallel")]
+    pub fn par_filter(&self, items: &[CBOR]) -> Vec<bool> {
+        use rayon::prelude::*;
+
+        let program = std::sync::Arc::new(Program::compile(self));
+        items
+            .par_iter()
+            .map_with(program, |program, item| program.matches(item))
+            .collect()
+    }
+
+    /// Matches this pattern against a dCBOR sequence (RFC 8742), treating
+    /// `items` as the elements of an implicit array.
+    ///
+    /// A sequence pattern such as `number, text, (*)*` must account for every
+    /// item, with quantifiers and back-references behaving exactly as they
+    /// do between array brackets; any other pattern is a sequence of one.
+    /// A match returns a single path listing the items, standing in for the
+    /// path to the array, even when there are no items; no match returns no
+    /// paths.
+    pub fn match_sequence(&self, items: &[CBOR]) -> Vec<Path> {
+        self.paths_with_captures_sequence(items).0
+    }
+
+    /// Like [`Pattern::match_sequence`], also returning the captures.
+    ///
+    /// Capture paths start at the item they were found in, and a capture
+    /// around a repeat such as `@rest((*)*)` captures the items it spans as
+    /// a single array.
+    pub fn paths_with_captures_sequence(
+        &self,
+        items: &[CBOR],
+    ) -> (Vec<Path>, Captures) {
+        let sequence = match self {
+            Pattern::Meta(MetaPattern::Sequence(_)) => self.clone(),
+            _ => Pattern::sequence(vec![self.clone()]),
+        };
+        let wrapper = Pattern::Structure(StructurePattern::Array(
+            ArrayPattern::with_elements(sequence),
+        ));
+        let array = items.to_vec().to_cbor();
+        let (paths, captures) = wrapper.paths_with_captures(&array);
+        if paths.is_empty() {
+            return (vec![], Captures::new());
+        }
+
+        // Drop the implicit array from the front of every capture path
+        let captures = captures
+            .into_iter()
+            .map(|(name, paths)| {
+                let paths =
+                    paths.into_iter().map(|path| path[1..].to_vec()).collect();
+                (name, paths)
+            })
+            .collect();
+        (vec![items.to_vec()], captures)
+    }
+
+    /// Returns the names of the captures in this pattern, in the order they
+    /// first appear, without repeats.
+    ///
+    /// Captures inside `!` patterns are not included, as they never produce
+    /// results.
+    pub fn capture_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.collect_capture_names(&mut names);
+        let mut seen = std::collections::HashSet::new();
+        names.retain(|name| seen.insert(name.clone()));
+        names
+    }
+}
+
+impl std::str::FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> { Self::parse(s) }
 }
 
 impl TryFrom<&str> for Pattern {
@@ -403,62 +1025,90 @@ impl TryFrom<&str> for Pattern {
     fn try_from(value: &str) -> Result<Self> { Self::parse(value) }
 }
 
-impl Matcher for Pattern {
-    fn paths_with_captures(
+impl TryFrom<String> for Pattern {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> { Self::parse(&value) }
+}
+
+impl Pattern {
+    /// Matches with captures once the evaluation's current time is pinned.
+    fn paths_with_captures_now(
         &self,
         haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    ) -> (Vec<Path>, Captures) {
         // Collect all capture names from this pattern
         let mut capture_names = Vec::new();
         self.collect_capture_names(&mut capture_names);
 
         // If no captures, use the faster direct path matching
         if capture_names.is_empty() {
-            return (self.paths(haystack), std::collections::HashMap::new());
+            return (self.paths(haystack), Captures::new());
         }
 
-        // For certain pattern types, delegate directly to their
-        // paths_with_captures
-        match self {
-            Pattern::Meta(pattern) => {
-                // Meta patterns like SearchPattern handle their own capture
-                // logic
-                return pattern.paths_with_captures(haystack);
-            }
+        let (mut paths, captures) = match self {
+            // Meta patterns like SearchPattern handle their own capture logic
+            Pattern::Meta(pattern) => pattern.paths_with_captures(haystack),
+            // Structure patterns like ArrayPattern handle their own capture
+            // logic, including special handling for SequencePattern
             Pattern::Structure(pattern) => {
-                // Structure patterns like ArrayPattern handle their own capture
-                // logic, including special handling for SequencePattern
-                return pattern.paths_with_captures(haystack);
+                pattern.paths_with_captures(haystack)
             }
-            _ => {
-                // Use VM for other pattern types that need it
+            // Value patterns capture only the named groups of regexes, and
+            // custom patterns nothing
+            Pattern::Value(pattern) => pattern.paths_with_captures(haystack),
+            Pattern::Custom(pattern) => {
+                (pattern.paths(haystack), Captures::new())
             }
-        }
-
-        // Compile pattern to VM program for capture-aware matching
-        let mut code = Vec::new();
-        let mut literals = Vec::new();
-        let mut captures = Vec::new();
+        };
 
-        self.compile(&mut code, &mut literals, &mut captures);
-        code.push(crate::pattern::vm::Instr::Accept);
+        sort_paths(&mut paths);
+        (paths, order_captures(captures, &capture_names))
+    }
+}
 
-        let program = crate::pattern::vm::Program {
-            code,
-            literals,
-            capture_names: captures,
-        };
+/// Lists the captures in the order of `capture_names`, the order the names
+/// appear in the pattern, each in document order.
+pub(crate) fn order_captures(
+    mut captures: Captures,
+    capture_names: &[String],
+) -> Captures {
+    let mut ordered = Captures::with_capacity(captures.len());
+    for name in capture_names {
+        if let Some(mut capture_paths) = captures.shift_remove(name) {
+            sort_paths(&mut capture_paths);
+            ordered.insert(name.clone(), capture_paths);
+        }
+    }
+    ordered.extend(captures);
+    ordered
+}
 
-        // Run VM to get paths and captures
-        crate::pattern::vm::run(&program, haystack)
+impl Matcher for Pattern {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        pin_evaluation_now(|| self.paths_with_captures_now(haystack))
     }
 
     fn paths(&self, haystack: &CBOR) -> Vec<Path> {
-        match self {
-            Pattern::Value(pattern) => pattern.paths(haystack),
-            Pattern::Structure(pattern) => pattern.paths(haystack),
-            Pattern::Meta(pattern) => pattern.paths(haystack),
-        }
+        pin_evaluation_now(|| {
+            let mut paths = match self {
+                Pattern::Value(pattern) => pattern.paths(haystack),
+                Pattern::Structure(pattern) => pattern.paths(haystack),
+                Pattern::Meta(pattern) => pattern.paths(haystack),
+                Pattern::Custom(pattern) => pattern.paths(haystack),
+            };
+            sort_paths(&mut paths);
+            paths
+        })
+    }
+
+    fn matches(&self, haystack: &CBOR) -> bool {
+        pin_evaluation_now(|| match self {
+            Pattern::Value(pattern) => pattern.matches(haystack),
+            Pattern::Structure(pattern) => pattern.matches(haystack),
+            Pattern::Meta(pattern) => pattern.matches(haystack),
+            Pattern::Custom(pattern) => pattern.matches(haystack),
+        })
     }
 
     fn compile(
@@ -477,14 +1127,21 @@ impl Matcher for Pattern {
             Pattern::Meta(pattern) => {
                 pattern.compile(code, literals, captures);
             }
+            Pattern::Custom(pattern) => {
+                pattern.compile(code, literals, captures);
+            }
         }
     }
 
     /// Recursively collect all capture names from this pattern.
     fn collect_capture_names(&self, names: &mut Vec<String>) {
         match self {
-            Pattern::Value(_) => {
-                // Value patterns don't contain captures
+            Pattern::Value(pattern) => {
+                // Only the named groups of regexes
+                pattern.collect_capture_names(names);
+            }
+            Pattern::Custom(_) => {
+                // Custom patterns don't contain captures
             }
             Pattern::Structure(pattern) => {
                 pattern.collect_capture_names(names);
@@ -501,6 +1158,7 @@ impl Matcher for Pattern {
             Pattern::Structure(_pattern) => false, /* TODO: implement when */
             // ready
             Pattern::Meta(pattern) => pattern.is_complex(),
+            Pattern::Custom(pattern) => pattern.is_complex(),
         }
     }
 }
@@ -511,6 +1169,7 @@ impl std::fmt::Display for Pattern {
             Pattern::Value(pattern) => write!(f, "{}", pattern),
             Pattern::Structure(pattern) => write!(f, "{}", pattern),
             Pattern::Meta(pattern) => write!(f, "{}", pattern),
+            Pattern::Custom(pattern) => write!(f, "{}", pattern),
         }
     }
 }
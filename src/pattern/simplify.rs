@@ -0,0 +1,382 @@
+use crate::{
+    Quantifier,
+    pattern::{
+        ArrayPattern, ByteStringPattern, MapPattern, Matcher, MetaPattern,
+        Pattern, StructurePattern, TaggedPattern, ValuePattern,
+    },
+};
+
+/// How the result of a sub-pattern is used by the pattern containing it,
+/// which decides the rewrites that are safe to apply to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// The sub-pattern's paths are part of the result.
+    Paths,
+    /// Only whether the sub-pattern matches is used, as for the operands of
+    /// `&` and `!` or the pattern a search looks for.
+    Existence,
+    /// The sub-pattern is an element of an array or sequence, where it may
+    /// consume any number of items.
+    Element,
+}
+
+impl Pattern {
+    /// Returns an equivalent pattern with redundant structure removed.
+    ///
+    /// The rewrites are:
+    /// - nested `&` and `|` patterns are flattened into their parent;
+    /// - duplicate `|` alternatives are removed, keeping the first;
+    /// - capture-free groups, such as the one `((42))` parses to, are removed
+    ///   unless they are needed to keep precedence or to make a repeat or
+    ///   sequence consume a single array element;
+    /// - an empty `&` becomes `*`, and an empty `|` becomes `!*`;
+    /// - `search(search(p))` becomes `search(p)` where only whether the pattern
+    ///   matches is used, such as under `&` or `!`. Elsewhere the outer search
+    ///   also reports every ancestor of a match, so the two are not
+    ///   interchangeable.
+    ///
+    /// The simplified pattern returns the same paths as the original for any
+    /// haystack.
+    pub fn simplify(&self) -> Pattern { simplify(self, Context::Paths) }
+}
+
+fn simplify(pattern: &Pattern, context: Context) -> Pattern {
+    match pattern {
+        Pattern::Value(ValuePattern::ByteString(
+            ByteStringPattern::Decoded(pattern),
+        )) => Pattern::byte_string_decoded(simplify(pattern, Context::Paths)),
+        Pattern::Value(_) | Pattern::Custom(_) => pattern.clone(),
+        Pattern::Structure(structure) => simplify_structure(structure),
+        Pattern::Meta(meta) => simplify_meta(meta, context),
+    }
+}
+
+fn simplify_structure(structure: &StructurePattern) -> Pattern {
+    let structure = match structure {
+        StructurePattern::Array(ArrayPattern::Elements(elements)) => {
+            StructurePattern::Array(ArrayPattern::with_elements(simplify(
+                elements,
+                Context::Element,
+            )))
+        }
+        StructurePattern::Array(ArrayPattern::LengthAndElements(
+            interval,
+            elements,
+        )) => StructurePattern::Array(ArrayPattern::with_length_and_elements(
+            *interval,
+            simplify(elements, Context::Element),
+        )),
+        StructurePattern::Array(ArrayPattern::Unordered(patterns)) => {
+            StructurePattern::Array(ArrayPattern::Unordered(
+                patterns
+                    .iter()
+                    .map(|pattern| simplify(pattern, Context::Paths))
+                    .collect(),
+            ))
+        }
+        StructurePattern::Array(array) => {
+            StructurePattern::Array(array.clone())
+        }
+        StructurePattern::Map(map) => StructurePattern::Map(match map {
+            MapPattern::Constraints(constraints) => {
+                MapPattern::Constraints(simplify_constraints(constraints))
+            }
+            MapPattern::LengthAndConstraints(interval, constraints) => {
+                MapPattern::LengthAndConstraints(
+                    *interval,
+                    simplify_constraints(constraints),
+                )
+            }
+            MapPattern::Value(key, value) => MapPattern::Value(
+                Box::new(simplify(key, Context::Paths)),
+                Box::new(simplify(value, Context::Paths)),
+            ),
+            MapPattern::AllEntries(key, value) => MapPattern::AllEntries(
+                Box::new(simplify(key, Context::Paths)),
+                Box::new(simplify(value, Context::Paths)),
+            ),
+            MapPattern::ConstraintsAndRest(interval, constraints, rest) => {
+                MapPattern::ConstraintsAndRest(
+                    *interval,
+                    simplify_constraints(constraints),
+                    Box::new(simplify(rest, Context::Paths)),
+                )
+            }
+            MapPattern::CountedConstraints(interval, constraints, rest) => {
+                MapPattern::CountedConstraints(
+                    *interval,
+                    constraints
+                        .iter()
+                        .map(|constraint| {
+                            MapPattern::entry(
+                                simplify(constraint.key(), Context::Paths),
+                                simplify(constraint.value(), Context::Paths),
+                            )
+                            .with_count(constraint.count())
+                        })
+                        .collect(),
+                    rest.as_ref()
+                        .map(|rest| Box::new(simplify(rest, Context::Paths))),
+                )
+            }
+            MapPattern::Any | MapPattern::Length(_) => map.clone(),
+        }),
+        StructurePattern::Tagged(tagged) => {
+            StructurePattern::Tagged(match tagged {
+                TaggedPattern::Any => TaggedPattern::Any,
+                TaggedPattern::AnyTag { pattern } => TaggedPattern::AnyTag {
+                    pattern: Box::new(simplify(pattern, Context::Paths)),
+                },
+                TaggedPattern::Tag { tag, pattern } => TaggedPattern::Tag {
+                    tag: tag.clone(),
+                    pattern: Box::new(simplify(pattern, Context::Paths)),
+                },
+                TaggedPattern::Name { name, pattern } => TaggedPattern::Name {
+                    name: name.clone(),
+                    pattern: Box::new(simplify(pattern, Context::Paths)),
+                },
+                TaggedPattern::Regex { regex, pattern } => {
+                    TaggedPattern::Regex {
+                        regex: regex.clone(),
+                        pattern: Box::new(simplify(pattern, Context::Paths)),
+                    }
+                }
+                TaggedPattern::Range { range, pattern } => {
+                    TaggedPattern::Range {
+                        range: range.clone(),
+                        pattern: Box::new(simplify(pattern, Context::Paths)),
+                    }
+                }
+                TaggedPattern::Set { tags, pattern } => TaggedPattern::Set {
+                    tags: tags.clone(),
+                    pattern: Box::new(simplify(pattern, Context::Paths)),
+                },
+            })
+        }
+    };
+    Pattern::Structure(structure)
+}
+
+fn simplify_constraints(
+    constraints: &[(Pattern, Pattern)],
+) -> Vec<(Pattern, Pattern)> {
+    constraints
+        .iter()
+        .map(|(key, value)| {
+            (
+                simplify(key, Context::Paths),
+                simplify(value, Context::Paths),
+            )
+        })
+        .collect()
+}
+
+fn simplify_meta(meta: &MetaPattern, context: Context) -> Pattern {
+    // Inside an array the operands of `&` and `|` are matched against the
+    // same elements, so they stay element patterns
+    let operand_context = |outside| {
+        if context == Context::Element {
+            Context::Element
+        } else {
+            outside
+        }
+    };
+
+    match meta {
+        MetaPattern::And(and) => {
+            let operand_context = operand_context(Context::Existence);
+            let mut operands = Vec::new();
+            for operand in and.patterns() {
+                match simplify(operand, operand_context) {
+                    Pattern::Meta(MetaPattern::And(nested)) => {
+                        operands.extend(nested.patterns().iter().cloned())
+                    }
+                    operand => operands.push(operand),
+                }
+            }
+            match operands.len() {
+                // An empty `&` consumes nothing inside an array
+                0 if context != Context::Element => Pattern::any(),
+                1 if matches_in_place(&operands[0]) => operands.remove(0),
+                _ => Pattern::and(operands),
+            }
+        }
+        MetaPattern::Or(or) => {
+            let operand_context = operand_context(Context::Paths);
+            let mut alternatives: Vec<Pattern> = Vec::new();
+            for alternative in or.patterns() {
+                let flattened = match simplify(alternative, operand_context) {
+                    Pattern::Meta(MetaPattern::Or(nested)) => {
+                        nested.patterns().to_vec()
+                    }
+                    alternative => vec![alternative],
+                };
+                for alternative in flattened {
+                    if !alternatives.contains(&alternative) {
+                        alternatives.push(alternative);
+                    }
+                }
+            }
+            match alternatives.len() {
+                // An empty `|` consumes nothing inside an array
+                0 if context != Context::Element => {
+                    Pattern::not_matching(Pattern::any())
+                }
+                1 if matches_in_place(&alternatives[0]) => {
+                    alternatives.remove(0)
+                }
+                _ => Pattern::or(alternatives),
+            }
+        }
+        MetaPattern::Not(not) => {
+            Pattern::not_matching(simplify(not.pattern(), Context::Existence))
+        }
+        MetaPattern::Repeat(repeat) => {
+            // A repeat matching exactly once passes its pattern's result
+            // through unchanged
+            if *repeat.quantifier() == Quantifier::default()
+                && !has_captures(repeat.pattern())
+            {
+                let inner = simplify(repeat.pattern(), context);
+                if (context != Context::Element || is_single_element(&inner))
+                    && !matches!(inner, Pattern::Meta(MetaPattern::Sequence(_)))
+                {
+                    return inner;
+                }
+            }
+            Pattern::repeat(
+                simplify(repeat.pattern(), Context::Element),
+                *repeat.quantifier(),
+            )
+        }
+        MetaPattern::Capture(capture) => {
+            let inner = match capture.pattern() {
+                // Inside an array a captured repeat, even one matching exactly
+                // once, captures the elements it consumed as an array
+                Pattern::Meta(MetaPattern::Repeat(repeat))
+                    if context == Context::Element =>
+                {
+                    Pattern::repeat(
+                        simplify(repeat.pattern(), Context::Element),
+                        *repeat.quantifier(),
+                    )
+                }
+                inner => simplify(inner, operand_context(Context::Paths)),
+            };
+            Pattern::capture(capture.name(), inner)
+        }
+        MetaPattern::Child(child) => {
+            Pattern::child(simplify(child.pattern(), Context::Paths))
+        }
+        MetaPattern::Untag(untag) => {
+            Pattern::untag(simplify(untag.pattern(), Context::Paths))
+        }
+        MetaPattern::At(at) => Pattern::at(
+            at.steps().iter().cloned(),
+            simplify(at.pattern(), Context::Paths),
+        ),
+        MetaPattern::Entry(entry) => Pattern::entry(
+            simplify(entry.key(), Context::Paths),
+            simplify(entry.value(), Context::Paths),
+        ),
+        MetaPattern::None(none) => {
+            Pattern::none(simplify(none.pattern(), Context::Existence))
+        }
+        MetaPattern::Search(search) => {
+            let inner = simplify(search.pattern(), Context::Existence);
+            let opts = *search.opts();
+            // Both searches reach every node the other can, so the outer one
+            // finds a match exactly when the inner one does
+            if context == Context::Existence
+                && opts.max_depth.is_none()
+                && !has_captures(&inner)
+                && let Pattern::Meta(MetaPattern::Search(nested)) = &inner
+                && *nested.opts() == opts
+            {
+                inner
+            } else {
+                Pattern::search_with(inner, opts)
+            }
+        }
+        MetaPattern::Sequence(sequence) => Pattern::sequence(
+            sequence
+                .patterns()
+                .iter()
+                .map(|element| {
+                    match simplify(element, Context::Element) {
+                        // A sequence element that is itself a sequence stays
+                        // wrapped in a repeat, both to match the same items
+                        // and to display unambiguously
+                        element @ Pattern::Meta(MetaPattern::Sequence(_)) => {
+                            Pattern::repeat(element, Quantifier::default())
+                        }
+                        element => element,
+                    }
+                })
+                .collect(),
+        ),
+        MetaPattern::Any(_) | MetaPattern::Backref(_) => {
+            Pattern::Meta(meta.clone())
+        }
+    }
+}
+
+/// Returns `true` if the pattern contains a capture.
+fn has_captures(pattern: &Pattern) -> bool {
+    let mut names = Vec::new();
+    pattern.collect_capture_names(&mut names);
+    !names.is_empty()
+}
+
+/// Returns `true` if the pattern only ever matches with the haystack itself
+/// as its path, so that `&` or `|` of this pattern alone is the pattern.
+pub(crate) fn matches_in_place(pattern: &Pattern) -> bool {
+    match pattern {
+        // Paths continue into the decoded item, the value, or the content
+        Pattern::Value(ValuePattern::ByteString(
+            ByteStringPattern::Decoded(_),
+        ))
+        | Pattern::Structure(StructurePattern::Map(MapPattern::Value(..))) => {
+            false
+        }
+        Pattern::Structure(StructurePattern::Tagged(tagged)) => {
+            matches!(tagged, TaggedPattern::Any)
+        }
+        Pattern::Value(_) | Pattern::Structure(_) | Pattern::Custom(_) => true,
+        Pattern::Meta(MetaPattern::And(and)) => {
+            and.patterns().iter().all(matches_in_place)
+        }
+        Pattern::Meta(MetaPattern::Or(or)) => {
+            or.patterns().iter().all(matches_in_place)
+        }
+        Pattern::Meta(MetaPattern::Capture(capture)) => {
+            matches_in_place(capture.pattern())
+        }
+        Pattern::Meta(meta) => matches!(
+            meta,
+            MetaPattern::Any(_) | MetaPattern::Not(_) | MetaPattern::Backref(_)
+        ),
+    }
+}
+
+/// Returns `true` if the pattern always consumes exactly one element of an
+/// array, so that grouping it changes nothing.
+fn is_single_element(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Meta(MetaPattern::Repeat(_) | MetaPattern::Sequence(_)) => {
+            false
+        }
+        Pattern::Meta(MetaPattern::And(and)) => {
+            !and.patterns().is_empty()
+                && and.patterns().iter().all(is_single_element)
+        }
+        Pattern::Meta(MetaPattern::Or(or)) => {
+            !or.patterns().is_empty()
+                && or.patterns().iter().all(is_single_element)
+        }
+        Pattern::Meta(MetaPattern::Capture(capture)) => {
+            is_single_element(capture.pattern())
+        }
+        _ => true,
+    }
+}
@@ -0,0 +1,556 @@
+//! Matching a pattern against dCBOR read incrementally from an
+//! [`io::Read`](std::io::Read) source, for inputs too large to decode whole.
+
+use std::io::{self, BufReader, Read};
+
+use dcbor::prelude::*;
+
+use crate::{
+    Error, Result,
+    pattern::{
+        ArrayPattern, MapPattern, Matcher, MetaPattern, Pattern, SearchOpts,
+        StructurePattern, TaggedPattern, ValuePattern, meta::MAX_SEARCH_DEPTH,
+    },
+};
+
+/// The default for [`StreamOpts::max_nesting_depth`].
+pub const DEFAULT_MAX_STREAM_NESTING_DEPTH: usize = 1024;
+
+/// The default for [`StreamOpts::max_value_len`].
+pub const DEFAULT_MAX_STREAM_VALUE_LEN: usize = 16 * 1024 * 1024;
+
+/// Limits on the memory [`Pattern::match_from_reader`] uses, which depends
+/// on the shape of the data rather than its size.
+///
+/// Data exceeding a limit is rejected with [`Error::InvalidCborData`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamOpts {
+    /// The deepest nesting of arrays and maps allowed, which is also the
+    /// most tags allowed around a single item. Defaults to
+    /// [`DEFAULT_MAX_STREAM_NESTING_DEPTH`].
+    pub max_nesting_depth: usize,
+    /// The longest encoding of a single byte string or text string, or of a
+    /// map key, held in memory. Defaults to [`DEFAULT_MAX_STREAM_VALUE_LEN`].
+    pub max_value_len: usize,
+}
+
+impl Default for StreamOpts {
+    fn default() -> Self {
+        Self {
+            max_nesting_depth: DEFAULT_MAX_STREAM_NESTING_DEPTH,
+            max_value_len: DEFAULT_MAX_STREAM_VALUE_LEN,
+        }
+    }
+}
+
+/// An item of a stream that a pattern matched, as found by
+/// [`Pattern::match_from_reader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamMatch {
+    /// The position of the first byte of the item's encoding in the stream.
+    pub offset: u64,
+    /// How deep the item is, where the root is at depth 0 and the content
+    /// of a tagged value is one deeper than the tagged value.
+    pub depth: usize,
+    /// The item itself, if it has no array or map inside it. Arrays, maps,
+    /// and tagged values holding them are never held in memory, so for
+    /// them this is `None`.
+    pub value: Option<CBOR>,
+}
+
+/// The result of [`Pattern::match_from_reader`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchSummary {
+    /// Every item the pattern matched, in the order of their encodings in
+    /// the stream, which is the order a search visits them.
+    pub matches: Vec<StreamMatch>,
+    /// How many items the stream held, counting the root and every element,
+    /// map key and value, and tagged content inside it.
+    pub items: u64,
+}
+
+impl MatchSummary {
+    /// Returns `true` if the pattern matched at least one item.
+    pub fn is_match(&self) -> bool { !self.matches.is_empty() }
+}
+
+impl Pattern {
+    /// Reads one dCBOR item from `reader` and returns the items this pattern
+    /// matches in it, without holding the whole item in memory.
+    ///
+    /// Only a subset of patterns can be matched this way: value patterns,
+    /// `*`, `array` and `map` with at most a length, as in `[{3,}]`, and
+    /// `&`, `|`, and `!` combinations of these, either alone, to match the
+    /// root, or inside a `search(...)`. Other patterns, and any pattern
+    /// with captures, fail with [`Error::UnsupportedForStreaming`] before
+    /// anything is read.
+    ///
+    /// Each match is reported at its position in the stream, so matches of
+    /// equal values at different positions are all reported, where
+    /// [`Matcher::paths`] would report their equal paths once. The reader
+    /// is buffered internally.
+    ///
+    /// Fails with [`Error::InvalidCborData`] if the stream is not a single,
+    /// complete, canonically encoded dCBOR item, or if it exceeds the limits
+    /// in `opts`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use dcbor::prelude::*;
+    /// # use dcbor_pattern::{Pattern, StreamOpts};
+    /// let data = CBOR::from(vec![1, 2, 3]).to_cbor_data();
+    /// let pattern = Pattern::parse("search(>1)").unwrap();
+    /// let summary = pattern
+    ///     .match_from_reader(data.as_slice(), StreamOpts::default())
+    ///     .unwrap();
+    /// let values: Vec<_> = summary
+    ///     .matches
+    ///     .into_iter()
+    ///     .map(|m| m.value.unwrap())
+    ///     .collect();
+    /// assert_eq!(values, [CBOR::from(2), CBOR::from(3)]);
+    /// assert_eq!(summary.items, 4);
+    /// ```
+    pub fn match_from_reader(
+        &self,
+        reader: impl Read,
+        opts: StreamOpts,
+    ) -> Result<MatchSummary> {
+        if let Some(name) = self.capture_names().into_iter().next() {
+            return Err(Error::UnsupportedForStreaming(format!("@{}", name)));
+        }
+        let (pattern, search) = match self {
+            Pattern::Meta(MetaPattern::Search(search)) => {
+                (search.pattern(), Some(*search.opts()))
+            }
+            pattern => (pattern, None),
+        };
+        check_streamable(pattern)?;
+        let mut stream = Stream {
+            reader: Counted { reader: BufReader::new(reader), offset: 0 },
+            pattern,
+            search,
+            opts,
+            key_bytes: Vec::new(),
+            recording: 0,
+            summary: MatchSummary::default(),
+        };
+        stream.run()?;
+        let mut summary = stream.summary;
+        // A tagged value is matched once its content has been read, after
+        // the matches inside it
+        summary.matches.sort_by_key(|m| m.offset);
+        Ok(summary)
+    }
+}
+
+/// Fails unless `pattern` can be decided on a single node, given its value
+/// if it has no array or map inside it, or else just its kind and length.
+fn check_streamable(pattern: &Pattern) -> Result<()> {
+    let supported = match pattern {
+        Pattern::Value(ValuePattern::Cbor(cbor)) => {
+            holds_no_container(cbor.value())
+        }
+        Pattern::Value(ValuePattern::EncodedDigest(_)) => false,
+        Pattern::Value(_) => true,
+        Pattern::Structure(StructurePattern::Array(array)) => {
+            matches!(array, ArrayPattern::Any | ArrayPattern::Length(_))
+        }
+        Pattern::Structure(StructurePattern::Map(map)) => {
+            matches!(map, MapPattern::Any | MapPattern::Length(_))
+        }
+        Pattern::Structure(StructurePattern::Tagged(_))
+        | Pattern::Meta(
+            MetaPattern::Any(_)
+            | MetaPattern::And(_)
+            | MetaPattern::Or(_)
+            | MetaPattern::Not(_),
+        ) => {
+            return pattern
+                .children()
+                .into_iter()
+                .try_for_each(check_streamable);
+        }
+        _ => false,
+    };
+    if supported {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedForStreaming(pattern.to_string()))
+    }
+}
+
+fn holds_no_container(cbor: &CBOR) -> bool {
+    match cbor.as_case() {
+        CBORCase::Array(_) | CBORCase::Map(_) => false,
+        CBORCase::Tagged(_, content) => holds_no_container(content),
+        _ => true,
+    }
+}
+
+/// What a streamed node is known by when it is matched.
+enum Node {
+    /// A node with no array or map inside it, held whole.
+    Value(CBOR),
+    Array(usize),
+    Map(usize),
+    /// A tagged value around an array or map, or around another such
+    /// tagged value.
+    Tagged(Tag, Box<Node>),
+}
+
+/// Returns whether `pattern`, which [`check_streamable`] accepted, matches
+/// `node`.
+fn node_matches(pattern: &Pattern, node: &Node) -> bool {
+    if let Node::Value(cbor) = node {
+        return pattern.matches(cbor);
+    }
+    match pattern {
+        Pattern::Structure(StructurePattern::Array(array)) => match node {
+            Node::Array(len) => match array {
+                ArrayPattern::Length(interval) => interval.contains(*len),
+                _ => true,
+            },
+            _ => false,
+        },
+        Pattern::Structure(StructurePattern::Map(map)) => match node {
+            Node::Map(len) => match map {
+                MapPattern::Length(interval) => interval.contains(*len),
+                _ => true,
+            },
+            _ => false,
+        },
+        Pattern::Structure(StructurePattern::Tagged(tagged)) => match node {
+            Node::Tagged(tag, content) => match tagged {
+                TaggedPattern::Any => true,
+                _ => tagged
+                    .content_pattern(tag)
+                    .is_some_and(|pattern| node_matches(pattern, content)),
+            },
+            _ => false,
+        },
+        Pattern::Meta(MetaPattern::Any(_)) => true,
+        Pattern::Meta(MetaPattern::And(and)) => {
+            and.patterns().iter().all(|p| node_matches(p, node))
+        }
+        Pattern::Meta(MetaPattern::Or(or)) => {
+            or.patterns().iter().any(|p| node_matches(p, node))
+        }
+        Pattern::Meta(MetaPattern::Not(not)) => {
+            !node_matches(not.pattern(), node)
+        }
+        // Value patterns that could match an array or map, or a tagged value
+        // around one, were rejected by `check_streamable`
+        _ => false,
+    }
+}
+
+/// A reader that counts the bytes read from it.
+struct Counted<R> {
+    reader: R,
+    offset: u64,
+}
+
+impl<R: Read> Counted<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.reader
+            .read_exact(buf)
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof => invalid(format!(
+                    "unexpected end of data at {}",
+                    self.offset
+                )),
+                _ => invalid(error.to_string()),
+            })?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Returns `true` if the reader has no more bytes.
+    fn at_end(&mut self) -> Result<bool> {
+        let mut byte = [0];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(n) => return Ok(n == 0),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(invalid(error.to_string())),
+            }
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidCborData(message.into())
+}
+
+/// The head of an encoded item: its major type and argument, with the bytes
+/// encoding them.
+struct Head {
+    major: u8,
+    argument: u64,
+    bytes: Vec<u8>,
+}
+
+/// An array or map whose items are being read.
+struct Frame {
+    /// The items left to read; a map has two for each entry.
+    remaining: u64,
+    /// The depth of the items.
+    depth: usize,
+    /// Whether the items are visited by the search.
+    visible: bool,
+    /// For a map, the key being read, as its start in the recorded bytes,
+    /// and the encoding of the key before it.
+    keys: Option<(usize, Option<Vec<u8>>)>,
+}
+
+struct Stream<'a, R> {
+    reader: Counted<R>,
+    pattern: &'a Pattern,
+    search: Option<SearchOpts>,
+    opts: StreamOpts,
+    /// The encodings of the map keys being read, nested keys inside their
+    /// enclosing keys, for checking that keys are in order.
+    key_bytes: Vec<u8>,
+    /// How many map keys are being read.
+    recording: usize,
+    summary: MatchSummary,
+}
+
+impl<R: Read> Stream<'_, R> {
+    fn run(&mut self) -> Result<()> {
+        let mut frames: Vec<Frame> = Vec::new();
+        if let Some(frame) = self.read_item(0, true)? {
+            frames.push(frame);
+        }
+        while let Some(frame) = frames.last_mut() {
+            if let Some((start, previous)) = &mut frame.keys {
+                if frame.remaining % 2 == 1 {
+                    // The key before the value to be read is complete
+                    let key = self.key_bytes[*start..].to_vec();
+                    if previous.as_ref().is_some_and(|p| *p >= key) {
+                        return Err(invalid(format!(
+                            "map keys out of order or duplicated at {}",
+                            self.reader.offset
+                        )));
+                    }
+                    *previous = Some(key);
+                    self.recording -= 1;
+                    if self.recording == 0 {
+                        self.key_bytes.clear();
+                    }
+                } else if frame.remaining > 0 {
+                    *start = self.key_bytes.len();
+                    self.recording += 1;
+                }
+            }
+            if frame.remaining == 0 {
+                frames.pop();
+                continue;
+            }
+            let is_key = frame.keys.is_some() && frame.remaining % 2 == 0;
+            frame.remaining -= 1;
+            let visible = frame.visible
+                && (!is_key || self.search.is_some_and(|s| s.visit_map_keys));
+            let depth = frame.depth;
+            if let Some(frame) = self.read_item(depth, visible)? {
+                if frames.len() >= self.opts.max_nesting_depth {
+                    return Err(invalid(format!(
+                        "nested more than {} deep at {}",
+                        self.opts.max_nesting_depth, self.reader.offset
+                    )));
+                }
+                frames.push(frame);
+            }
+        }
+        if !self.reader.at_end()? {
+            return Err(invalid(format!(
+                "extra data after the item at {}",
+                self.reader.offset
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns whether a node at `depth` is visited by the search, or for a
+    /// pattern without one, whether it is the root.
+    fn visits(&self, depth: usize) -> bool {
+        match self.search {
+            None => depth == 0,
+            Some(search) => {
+                depth
+                    <= search.max_depth.map_or(MAX_SEARCH_DEPTH, |max_depth| {
+                        max_depth.min(MAX_SEARCH_DEPTH)
+                    })
+            }
+        }
+    }
+
+    /// Reads an item at `depth`, matching it if `visible`, and returns the
+    /// frame for its items if it is an array or map, or a tagged value
+    /// around one.
+    fn read_item(
+        &mut self,
+        depth: usize,
+        visible: bool,
+    ) -> Result<Option<Frame>> {
+        let mut tags = Vec::new();
+        let (offset, head) = loop {
+            let offset = self.reader.offset;
+            let head = self.read_head()?;
+            if head.major != 6 {
+                break (offset, head);
+            }
+            tags.push((offset, head.argument));
+            if tags.len() > self.opts.max_nesting_depth {
+                return Err(invalid(format!(
+                    "nested more than {} deep at {}",
+                    self.opts.max_nesting_depth, self.reader.offset
+                )));
+            }
+        };
+        self.summary.items += 1 + tags.len() as u64;
+        let content_visible = visible
+            && (tags.is_empty()
+                || self.search.is_some_and(|s| s.visit_tagged_content));
+        match head.major {
+            4 | 5 => {
+                let len = usize::try_from(head.argument).unwrap_or(usize::MAX);
+                let (mut node, remaining, keys) = if head.major == 4 {
+                    (Node::Array(len), head.argument, None)
+                } else {
+                    let remaining =
+                        head.argument.checked_mul(2).ok_or_else(|| {
+                            invalid(format!("map too long at {}", offset))
+                        })?;
+                    (Node::Map(len), remaining, Some((0, None)))
+                };
+                let content_depth = depth + tags.len();
+                self.visit(&node, offset, content_depth, content_visible);
+                for (i, (tag_offset, tag)) in tags.into_iter().enumerate().rev()
+                {
+                    node = Node::Tagged(Tag::with_value(tag), Box::new(node));
+                    let visible = visible && (i == 0 || content_visible);
+                    self.visit(&node, tag_offset, depth + i, visible);
+                }
+                Ok(Some(Frame {
+                    remaining,
+                    depth: content_depth + 1,
+                    visible: content_visible,
+                    keys,
+                }))
+            }
+            _ => {
+                let mut value = self.read_scalar(head)?;
+                let depth = depth + tags.len();
+                self.visit(
+                    &Node::Value(value.clone()),
+                    offset,
+                    depth,
+                    content_visible,
+                );
+                for (i, (tag_offset, tag)) in tags.into_iter().enumerate().rev()
+                {
+                    value = CBOR::to_tagged_value(tag, value);
+                    let visible = visible && (i == 0 || content_visible);
+                    self.visit(
+                        &Node::Value(value.clone()),
+                        tag_offset,
+                        depth + i,
+                        visible,
+                    );
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn visit(&mut self, node: &Node, offset: u64, depth: usize, visible: bool) {
+        if visible && self.visits(depth) && node_matches(self.pattern, node) {
+            let value = match node {
+                Node::Value(cbor) => Some(cbor.clone()),
+                _ => None,
+            };
+            self.summary
+                .matches
+                .push(StreamMatch { offset, depth, value });
+        }
+    }
+
+    /// Reads the bytes of an item, recording them if a map key is being
+    /// read.
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        if len > self.opts.max_value_len {
+            return Err(invalid(format!(
+                "value of {} bytes, longer than the limit of {}, at {}",
+                len, self.opts.max_value_len, self.reader.offset
+            )));
+        }
+        let mut bytes = vec![0; len];
+        self.reader.read_exact(&mut bytes)?;
+        if self.recording > 0 {
+            if self.key_bytes.len() + len > self.opts.max_value_len {
+                return Err(invalid(format!(
+                    "map key longer than the limit of {} bytes at {}",
+                    self.opts.max_value_len, self.reader.offset
+                )));
+            }
+            self.key_bytes.extend_from_slice(&bytes);
+        }
+        Ok(bytes)
+    }
+
+    /// Reads the head of an item, which must be in its shortest form.
+    fn read_head(&mut self) -> Result<Head> {
+        let offset = self.reader.offset;
+        let mut bytes = self.read_bytes(1)?;
+        let major = bytes[0] >> 5;
+        let info = bytes[0] & 0x1f;
+        let len = match info {
+            0..=23 => 0,
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => {
+                return Err(invalid(format!(
+                    "unsupported or indefinite-length item at {}",
+                    offset
+                )));
+            }
+        };
+        bytes.extend(self.read_bytes(len)?);
+        let argument = if len == 0 {
+            info as u64
+        } else {
+            bytes[1..].iter().fold(0, |n, byte| n << 8 | *byte as u64)
+        };
+        // Floats are checked by dCBOR when the item is decoded
+        let shortest = match len {
+            1 => argument >= 24,
+            2 => argument > 0xff,
+            4 => argument > 0xffff,
+            8 => argument > 0xffff_ffff,
+            _ => true,
+        };
+        if !shortest && major != 7 {
+            return Err(invalid(format!(
+                "non-canonical encoding at {}",
+                offset
+            )));
+        }
+        Ok(Head { major, argument, bytes })
+    }
+
+    /// Reads the rest of an item with no items inside it and decodes it, so
+    /// that dCBOR checks its encoding is canonical.
+    fn read_scalar(&mut self, head: Head) -> Result<CBOR> {
+        let mut data = head.bytes;
+        if matches!(head.major, 2 | 3) {
+            let len = usize::try_from(head.argument).unwrap_or(usize::MAX);
+            data.extend(self.read_bytes(len)?);
+        }
+        CBOR::try_from_data(&data).map_err(|error| invalid(error.to_string()))
+    }
+}
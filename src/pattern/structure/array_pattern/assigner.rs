@@ -6,7 +6,7 @@ use super::{
     },
     helpers::has_repeat_patterns_in_slice,
 };
-use crate::pattern::{Matcher, Pattern};
+use crate::pattern::{Matcher, Pattern, meta::is_backref};
 
 /// Helper struct for handling element-to-pattern assignment logic.
 /// Encapsulates the complex logic for mapping array elements to sequence
@@ -23,6 +23,13 @@ impl<'a> SequenceAssigner<'a> {
         Self { patterns, arr }
     }
 
+    /// Returns true if the patterns contain repeats or back-references, which
+    /// rule out one-to-one matching.
+    fn needs_backtracking(&self) -> bool {
+        has_repeat_patterns_in_slice(self.patterns)
+            || self.patterns.iter().any(is_backref)
+    }
+
     /// Check if the sequence can match against the array elements (boolean
     /// result).
     pub fn can_match(&self) -> bool {
@@ -31,12 +38,12 @@ impl<'a> SequenceAssigner<'a> {
             return self.arr.is_empty();
         }
 
-        // Check if we have any repeat patterns that require backtracking
-        let has_repeat_patterns = has_repeat_patterns_in_slice(self.patterns);
+        // Repeats and back-references require backtracking
+        let needs_backtracking = self.needs_backtracking();
 
-        // Simple case: if pattern count equals element count AND no repeat
-        // patterns
-        if self.patterns.len() == self.arr.len() && !has_repeat_patterns {
+        // Simple case: if pattern count equals element count AND no
+        // backtracking is needed
+        if self.patterns.len() == self.arr.len() && !needs_backtracking {
             // Try one-to-one matching
             return self
                 .patterns
@@ -46,7 +53,7 @@ impl<'a> SequenceAssigner<'a> {
         }
 
         // Complex case: use generic backtracking framework
-        let backtracker = GenericBacktracker::new(self.patterns, self.arr);
+        let mut backtracker = GenericBacktracker::new(self.patterns, self.arr);
         let mut state = BooleanBacktrackState;
         backtracker.backtrack(&mut state, 0, 0)
     }
@@ -62,12 +69,12 @@ impl<'a> SequenceAssigner<'a> {
             };
         }
 
-        // Check if we have any repeat patterns that require backtracking
-        let has_repeat_patterns = has_repeat_patterns_in_slice(self.patterns);
+        // Repeats and back-references require backtracking
+        let needs_backtracking = self.needs_backtracking();
 
-        // Simple case: if pattern count equals element count AND no repeat
-        // patterns
-        if self.patterns.len() == self.arr.len() && !has_repeat_patterns {
+        // Simple case: if pattern count equals element count AND no
+        // backtracking is needed
+        if self.patterns.len() == self.arr.len() && !needs_backtracking {
             let mut assignments = Vec::new();
             for (pattern_idx, pattern) in self.patterns.iter().enumerate() {
                 let element = &self.arr[pattern_idx];
@@ -81,7 +88,7 @@ impl<'a> SequenceAssigner<'a> {
         }
 
         // Complex case: use generic backtracking framework
-        let backtracker = GenericBacktracker::new(self.patterns, self.arr);
+        let mut backtracker = GenericBacktracker::new(self.patterns, self.arr);
         let mut state = AssignmentBacktrackState::new();
         if backtracker.backtrack(&mut state, 0, 0) {
             Some(state.assignments)
@@ -1,9 +1,20 @@
 use dcbor::prelude::*;
 
 use super::helpers::{
-    calculate_repeat_bounds, can_repeat_match, extract_capture_with_repeat,
+    calculate_repeat_bounds, can_repeat_match, captures_run,
+    extract_capture_with_repeat, extract_negated_repeat,
+};
+use crate::{
+    Reluctance,
+    pattern::{
+        Matcher, MetaPattern, Pattern,
+        meta::{
+            Bindings, RepeatPattern, bind_captures, is_backref,
+            matches_with_bindings,
+        },
+        step,
+    },
 };
-use crate::pattern::{Matcher, MetaPattern, Pattern, meta::RepeatPattern};
 
 /// Generic backtracking framework for unifying different types of backtracking
 /// state management. This trait abstracts the differences between boolean
@@ -101,20 +112,80 @@ impl BacktrackState<Vec<(usize, usize)>> for AssignmentBacktrackState {
 pub struct GenericBacktracker<'a> {
     patterns: &'a [Pattern],
     arr: &'a [CBOR],
+    /// Values captured by the elements assigned so far, tracked only when the
+    /// sequence contains back-references
+    bindings: Option<Bindings>,
 }
 
 impl<'a> GenericBacktracker<'a> {
     pub fn new(patterns: &'a [Pattern], arr: &'a [CBOR]) -> Self {
-        Self { patterns, arr }
+        let bindings = patterns.iter().any(is_backref).then(Vec::new);
+        Self { patterns, arr, bindings }
+    }
+
+    /// Returns true if `pattern` matches `element` given the current
+    /// bindings.
+    fn element_matches(&self, pattern: &Pattern, element: &CBOR) -> bool {
+        match &self.bindings {
+            Some(bindings) => matches_with_bindings(pattern, element, bindings),
+            None => pattern.matches(element),
+        }
+    }
+
+    /// Binds the captures made by the pattern at `pattern_idx` over the
+    /// `count` elements starting at `element_idx`, returning a mark to pass to
+    /// `unbind` when backtracking.
+    fn bind(
+        &mut self,
+        pattern_idx: usize,
+        element_idx: usize,
+        count: usize,
+    ) -> usize {
+        let Some(bindings) = self.bindings.as_mut() else {
+            return 0;
+        };
+        let mark = bindings.len();
+        let pattern = &self.patterns[pattern_idx];
+        let elements = &self.arr[element_idx..element_idx + count];
+        match pattern {
+            // A capture around a repeat captures the run as an array
+            Pattern::Meta(MetaPattern::Capture(capture))
+                if captures_run(pattern) =>
+            {
+                bindings.push((capture.name().to_string(), elements.to_cbor()));
+            }
+            Pattern::Meta(MetaPattern::Repeat(repeat_pattern)) => {
+                for element in elements {
+                    bind_captures(repeat_pattern.pattern(), element, bindings);
+                }
+            }
+            _ => {
+                for element in elements {
+                    bind_captures(pattern, element, bindings);
+                }
+            }
+        }
+        mark
+    }
+
+    /// Discards the bindings made since `mark`.
+    fn unbind(&mut self, mark: usize) {
+        if let Some(bindings) = self.bindings.as_mut() {
+            bindings.truncate(mark);
+        }
     }
 
     /// Generic backtracking algorithm that works with any state type
     pub fn backtrack<T, S: BacktrackState<T>>(
-        &self,
+        &mut self,
         state: &mut S,
         pattern_idx: usize,
         element_idx: usize,
     ) -> bool {
+        if !step() {
+            return false;
+        }
+
         // Base case: if we've matched all patterns
         if state.is_success(
             pattern_idx,
@@ -129,12 +200,26 @@ impl<'a> GenericBacktracker<'a> {
             return false; // No more patterns but still have elements
         }
 
-        let current_pattern = &self.patterns[pattern_idx];
+        let patterns = self.patterns;
+        let current_pattern = &patterns[pattern_idx];
+
+        // A negated repeat like `!(text)*` consumes a run of elements, none of
+        // which match the repeated pattern
+        if let Some(repeat_pattern) = extract_negated_repeat(current_pattern) {
+            return self.try_repeat_backtrack(
+                repeat_pattern,
+                true,
+                state,
+                pattern_idx,
+                element_idx,
+            );
+        }
 
         match current_pattern {
             Pattern::Meta(MetaPattern::Repeat(repeat_pattern)) => self
                 .try_repeat_backtrack(
                     repeat_pattern,
+                    false,
                     state,
                     pattern_idx,
                     element_idx,
@@ -147,6 +232,7 @@ impl<'a> GenericBacktracker<'a> {
                     // Handle this like a repeat pattern
                     self.try_repeat_backtrack(
                         repeat_pattern,
+                        false,
                         state,
                         pattern_idx,
                         element_idx,
@@ -155,11 +241,13 @@ impl<'a> GenericBacktracker<'a> {
                     // Handle as a normal single-element capture
                     if element_idx < self.arr.len() {
                         let element = &self.arr[element_idx];
-                        let matches = current_pattern.matches(element);
+                        let matches =
+                            self.element_matches(current_pattern, element);
 
                         if matches
                             && state.try_advance(pattern_idx, element_idx)
                         {
+                            let mark = self.bind(pattern_idx, element_idx, 1);
                             if self.backtrack(
                                 state,
                                 pattern_idx + 1,
@@ -169,6 +257,7 @@ impl<'a> GenericBacktracker<'a> {
                             }
                             // Backtracking is handled by the recursive call
                             // failing
+                            self.unbind(mark);
                             state.backtrack();
                         }
                     }
@@ -179,9 +268,11 @@ impl<'a> GenericBacktracker<'a> {
                 // Non-repeat pattern: must match exactly one element
                 if element_idx < self.arr.len() {
                     let element = &self.arr[element_idx];
-                    let matches = current_pattern.matches(element);
+                    let matches =
+                        self.element_matches(current_pattern, element);
 
                     if matches && state.try_advance(pattern_idx, element_idx) {
+                        let mark = self.bind(pattern_idx, element_idx, 1);
                         if self.backtrack(
                             state,
                             pattern_idx + 1,
@@ -190,6 +281,7 @@ impl<'a> GenericBacktracker<'a> {
                             return true;
                         }
                         // Backtracking is handled by the recursive call failing
+                        self.unbind(mark);
                         state.backtrack();
                     }
                 }
@@ -198,10 +290,13 @@ impl<'a> GenericBacktracker<'a> {
         }
     }
 
-    /// Helper for repeat pattern backtracking with generic state
+    /// Helper for repeat pattern backtracking with generic state. When
+    /// `negated` is true the consumed elements must not match the repeated
+    /// pattern.
     fn try_repeat_backtrack<T, S: BacktrackState<T>>(
-        &self,
+        &mut self,
         repeat_pattern: &RepeatPattern,
+        negated: bool,
         state: &mut S,
         pattern_idx: usize,
         element_idx: usize,
@@ -210,14 +305,31 @@ impl<'a> GenericBacktracker<'a> {
         let (min_count, max_count) =
             calculate_repeat_bounds(quantifier, element_idx, self.arr.len());
 
-        // Try different numbers of repetitions (greedy: start with max)
-        for rep_count in (min_count..=max_count).rev() {
+        let counts: Vec<usize> = match quantifier.reluctance() {
+            Reluctance::Greedy => (min_count..=max_count).rev().collect(),
+            Reluctance::Lazy => (min_count..=max_count).collect(),
+            // Possessive takes the longest run it can and never gives any of
+            // it back
+            Reluctance::Possessive => {
+                let run = self.arr[element_idx..]
+                    .iter()
+                    .take(max_count)
+                    .take_while(|element| {
+                        repeat_pattern.pattern().matches(element) != negated
+                    })
+                    .count();
+                if run >= min_count { vec![run] } else { vec![] }
+            }
+        };
+
+        for rep_count in counts {
             if element_idx + rep_count <= self.arr.len()
                 && can_repeat_match(
                     repeat_pattern,
                     self.arr,
                     element_idx,
                     rep_count,
+                    negated,
                 )
             {
                 // Record state for all consumed elements
@@ -233,6 +345,7 @@ impl<'a> GenericBacktracker<'a> {
                 }
 
                 // Try to match the rest of the sequence recursively
+                let mark = self.bind(pattern_idx, element_idx, rep_count);
                 if self.backtrack(
                     state,
                     pattern_idx + 1,
@@ -243,6 +356,7 @@ impl<'a> GenericBacktracker<'a> {
 
                 // Backtrack: undo all the advances we made for this
                 // rep_count
+                self.unbind(mark);
                 for _ in 0..rep_count {
                     state.backtrack();
                 }
@@ -1,14 +1,11 @@
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, MetaPattern, Pattern, meta::RepeatPattern};
+use crate::pattern::{
+    Captures, Matcher, MetaPattern, Pattern, meta::RepeatPattern,
+};
 
 // Helper functions for pattern type detection
 
-/// Check if a pattern is a repeat pattern.
-pub fn is_repeat_pattern(pattern: &Pattern) -> bool {
-    matches!(pattern, Pattern::Meta(MetaPattern::Repeat(_)))
-}
-
 /// Check if a pattern is a capture pattern containing a repeat pattern.
 /// Returns the inner repeat pattern if found.
 pub fn extract_capture_with_repeat(
@@ -23,6 +20,16 @@ pub fn extract_capture_with_repeat(
     None
 }
 
+/// Check if a pattern is a capture around a repeat whose run of elements is
+/// captured as an array. A repeat of exactly one, as in `@a((*){1})`, always
+/// consumes a single element, which is captured as `@a(*)` would capture it.
+pub fn captures_run(pattern: &Pattern) -> bool {
+    extract_capture_with_repeat(pattern).is_some_and(|repeat_pattern| {
+        let quantifier = repeat_pattern.quantifier();
+        quantifier.min() != 1 || quantifier.max() != Some(1)
+    })
+}
+
 /// Extract any repeat pattern from a pattern, whether direct or within a
 /// capture.
 pub fn extract_repeat_pattern(pattern: &Pattern) -> Option<&RepeatPattern> {
@@ -43,26 +50,52 @@ pub fn extract_repeat_pattern(pattern: &Pattern) -> Option<&RepeatPattern> {
     }
 }
 
-/// Check if a slice of patterns contains any repeat patterns (direct or in
-/// captures).
+/// Check if a pattern is a negated repeat pattern, like `!(text)*`.
+/// Returns the inner repeat pattern if found.
+///
+/// Within an array sequence a negated repeat consumes a run of elements, none
+/// of which match the repeated pattern.
+pub fn extract_negated_repeat(pattern: &Pattern) -> Option<&RepeatPattern> {
+    if let Pattern::Meta(MetaPattern::Not(not_pattern)) = pattern
+        && let Pattern::Meta(MetaPattern::Repeat(repeat_pattern)) =
+            not_pattern.pattern()
+    {
+        return Some(repeat_pattern);
+    }
+    None
+}
+
+/// Check if a slice of patterns contains any repeat patterns (direct, in
+/// captures, or negated).
 pub fn has_repeat_patterns_in_slice(patterns: &[Pattern]) -> bool {
-    patterns.iter().any(|p| extract_repeat_pattern(p).is_some())
+    patterns.iter().any(|p| {
+        extract_repeat_pattern(p).is_some()
+            || extract_negated_repeat(p).is_some()
+    })
 }
 
-/// Format a pattern for display within array context.
-/// This handles sequence patterns specially to use commas instead of >.
-pub fn format_array_element_pattern(pattern: &Pattern) -> String {
+/// Check if a pattern always consumes exactly one array element, rather than
+/// a run of elements as repeats, negated repeats and sequences do.
+pub fn is_single_element(pattern: &Pattern) -> bool {
     match pattern {
-        Pattern::Meta(crate::pattern::MetaPattern::Sequence(seq_pattern)) => {
-            // For sequence patterns within arrays, use commas instead of >
-            let patterns_str: Vec<String> = seq_pattern
-                .patterns()
-                .iter()
-                .map(format_array_element_pattern)
-                .collect();
-            patterns_str.join(", ")
+        Pattern::Meta(MetaPattern::Repeat(_) | MetaPattern::Sequence(_)) => {
+            false
+        }
+        Pattern::Meta(MetaPattern::Not(_)) => {
+            extract_negated_repeat(pattern).is_none()
+        }
+        Pattern::Meta(MetaPattern::Or(or_pattern)) => {
+            !or_pattern.patterns().is_empty()
+                && or_pattern.patterns().iter().all(is_single_element)
         }
-        _ => pattern.to_string(),
+        Pattern::Meta(MetaPattern::And(and_pattern)) => {
+            !and_pattern.patterns().is_empty()
+                && and_pattern.patterns().iter().all(is_single_element)
+        }
+        Pattern::Meta(MetaPattern::Capture(capture_pattern)) => {
+            is_single_element(capture_pattern.pattern())
+        }
+        _ => true,
     }
 }
 
@@ -85,19 +118,21 @@ pub fn calculate_repeat_bounds(
 }
 
 /// Check if a repeat pattern can match a specific number of elements starting
-/// at element_idx.
+/// at element_idx. When `negated` is true, each element must instead fail to
+/// match the repeated pattern.
 pub fn can_repeat_match(
     repeat_pattern: &RepeatPattern,
     arr: &[CBOR],
     element_idx: usize,
     rep_count: usize,
+    negated: bool,
 ) -> bool {
     if rep_count == 0 {
         true // Zero repetitions always match
     } else {
         (0..rep_count).all(|i| {
             let element = &arr[element_idx + i];
-            repeat_pattern.pattern().matches(element)
+            repeat_pattern.pattern().matches(element) != negated
         })
     }
 }
@@ -112,17 +147,24 @@ pub fn build_simple_array_context_path(
     vec![array_cbor.clone(), element.clone()]
 }
 
-/// Build an extended array context path: [array_cbor, element] + captured_path
-/// (skip first element)
+/// Build an extended array context path: `[array_cbor]` followed by
+/// `captured_path`, which starts at `element`.
+///
+/// The element pattern's own captures are composed the same way, so a path
+/// that goes several levels into the element, through nested arrays, maps,
+/// or tags, is kept whole rather than cut short at the element.
 pub fn build_extended_array_context_path(
     array_cbor: &CBOR,
     element: &CBOR,
     captured_path: &[CBOR],
 ) -> Vec<CBOR> {
+    debug_assert_eq!(
+        captured_path.first(),
+        Some(element),
+        "element capture paths start at the element"
+    );
     let mut array_path = vec![array_cbor.clone(), element.clone()];
-    if captured_path.len() > 1 {
-        array_path.extend(captured_path.iter().skip(1).cloned());
-    }
+    array_path.extend(captured_path.iter().skip(1).cloned());
     array_path
 }
 
@@ -130,8 +172,8 @@ pub fn build_extended_array_context_path(
 pub fn transform_captures_with_array_context(
     array_cbor: &CBOR,
     element: &CBOR,
-    nested_captures: std::collections::HashMap<String, Vec<Vec<CBOR>>>,
-    all_captures: &mut std::collections::HashMap<String, Vec<Vec<CBOR>>>,
+    nested_captures: Captures,
+    all_captures: &mut Captures,
 ) {
     for (capture_name, captured_paths) in nested_captures {
         let mut array_context_paths = Vec::new();
@@ -1,25 +1,49 @@
-use std::ops::RangeBounds;
+use std::{cell::RefCell, collections::HashMap, ops::RangeBounds, sync::Arc};
 
 use dcbor::prelude::*;
 
 use crate::{
-    Interval,
+    Error, Interval, Result,
     pattern::{
-        Matcher, MetaPattern, Path, Pattern,
-        meta::{RepeatPattern, SequencePattern},
-        vm::Instr,
+        Captures, Matcher, MetaPattern, Path, Pattern,
+        meta::{RepeatPattern, SequencePattern, format_enclosed, is_backref},
+        vm::{Instr, Program},
     },
 };
 
 mod assigner;
 mod backtrack;
 mod helpers;
+mod unordered;
 
 use assigner::SequenceAssigner;
+pub(crate) use helpers::is_single_element;
 use helpers::*;
+use unordered::assign_unordered;
+
+thread_local! {
+    /// The programs compiled for array patterns on this thread, by the
+    /// address of the pattern, each kept with a copy of the pattern it was
+    /// compiled from, since another may since have taken that address.
+    static PROGRAMS: RefCell<HashMap<usize, (ArrayPattern, Arc<Program>)>> =
+        RefCell::new(HashMap::new());
+}
+
+/// The most programs [`PROGRAMS`] keeps before starting over.
+const MAX_CACHED_PROGRAMS: usize = 256;
+
+#[cfg(test)]
+thread_local! {
+    /// The number of array programs compiled, so tests can check that
+    /// matching a pattern again reuses its program.
+    static COMPILED_PROGRAMS: std::cell::Cell<usize> = const {
+        std::cell::Cell::new(0)
+    };
+}
 
 /// Pattern for matching CBOR array structures.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ArrayPattern {
     /// Matches any array.
     Any,
@@ -27,6 +51,17 @@ pub enum ArrayPattern {
     Elements(Box<Pattern>),
     /// Matches arrays with length in the given interval.
     Length(Interval),
+    /// Matches arrays with length in the given interval whose elements match
+    /// the given pattern, as [`ArrayPattern::Elements`] does.
+    ///
+    /// The length is checked before the elements.
+    LengthAndElements(Interval, Box<Pattern>),
+    /// Matches arrays with exactly one element for each of the given
+    /// patterns, in any order: the elements must pair off one to one with
+    /// patterns they match.
+    ///
+    /// Each pattern matches a single element, so none may be a repeat.
+    Unordered(Vec<Pattern>),
 }
 
 impl ArrayPattern {
@@ -39,6 +74,9 @@ impl ArrayPattern {
         ArrayPattern::Elements(Box::new(pattern))
     }
 
+    /// Creates a new `ArrayPattern` that matches arrays with a number of
+    /// elements in the given range, displayed as `[{n,m}]`, `[{n,}]`, or
+    /// `[{,m}]`. An empty range is clamped as [`Interval::new`] clamps it.
     pub fn with_length_range<R: RangeBounds<usize>>(range: R) -> Self {
         ArrayPattern::Length(Interval::new(range))
     }
@@ -49,6 +87,107 @@ impl ArrayPattern {
         ArrayPattern::Length(interval)
     }
 
+    /// Creates a new `ArrayPattern` that matches arrays with length in the
+    /// given interval whose elements match the given pattern.
+    pub fn with_length_and_elements(
+        interval: Interval,
+        pattern: Pattern,
+    ) -> Self {
+        ArrayPattern::LengthAndElements(interval, Box::new(pattern))
+    }
+
+    /// Creates a new `ArrayPattern` that matches arrays with exactly one
+    /// element for each of `patterns`, in any order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of `patterns` can match a run of elements rather than a
+    /// single one, as a repeat does. Use
+    /// [`ArrayPattern::try_with_elements_unordered`] for patterns that may.
+    pub fn with_elements_unordered(patterns: Vec<Pattern>) -> Self {
+        Self::try_with_elements_unordered(patterns)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Creates a new `ArrayPattern` that matches arrays with exactly one
+    /// element for each of `patterns`, in any order, failing with
+    /// [`Error::RepeatInUnorderedArray`] if any of them can match a run of
+    /// elements rather than a single one.
+    pub fn try_with_elements_unordered(patterns: Vec<Pattern>) -> Result<Self> {
+        if let Some(repeat) =
+            patterns.iter().find(|pattern| !is_single_element(pattern))
+        {
+            return Err(Error::RepeatInUnorderedArray(
+                repeat.to_string(),
+                None,
+            ));
+        }
+        Ok(ArrayPattern::Unordered(patterns))
+    }
+
+    /// Returns the VM program for this entire array pattern, compiling it
+    /// the first time this pattern is matched with captures on this thread.
+    fn cached_program(&self) -> Arc<Program> {
+        let address = self as *const Self as usize;
+        let cached = PROGRAMS.with_borrow(|programs| {
+            programs
+                .get(&address)
+                .filter(|(pattern, _)| pattern == self)
+                .map(|(_, program)| program.clone())
+        });
+        if let Some(program) = cached {
+            return program;
+        }
+        let program = Arc::new(self.program());
+        PROGRAMS.with_borrow_mut(|programs| {
+            if programs.len() >= MAX_CACHED_PROGRAMS {
+                programs.clear();
+            }
+            programs.insert(address, (self.clone(), program.clone()));
+        });
+        program
+    }
+
+    /// Compiles the VM program for this entire array pattern, which matches
+    /// the elements with their captures.
+    fn program(&self) -> Program {
+        #[cfg(test)]
+        COMPILED_PROGRAMS.set(COMPILED_PROGRAMS.get() + 1);
+        Program::build(|code, literals, captures| {
+            if let ArrayPattern::Elements(pattern)
+            | ArrayPattern::LengthAndElements(_, pattern) = self
+            {
+                self.compile_elements(pattern, code, literals, captures);
+            }
+        })
+    }
+
+    /// Emits the instructions that check this is an array, of the right
+    /// length if there is a length constraint, and then match `pattern`
+    /// against each of its elements, returning to the array afterwards.
+    fn compile_elements(
+        &self,
+        pattern: &Pattern,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        captures: &mut Vec<String>,
+    ) {
+        let array_check = match self {
+            ArrayPattern::LengthAndElements(interval, ..) => {
+                ArrayPattern::Length(*interval)
+            }
+            _ => ArrayPattern::Any,
+        };
+        let array_check_idx = literals.len();
+        literals.push(Pattern::Structure(
+            crate::pattern::StructurePattern::Array(array_check),
+        ));
+        code.push(Instr::MatchStructure(array_check_idx));
+        code.push(Instr::PushAxis(crate::pattern::vm::Axis::ArrayElement));
+        pattern.compile(code, literals, captures);
+        code.push(Instr::Pop);
+    }
+
     /// Match a complex sequence against array elements using VM-based matching.
     /// This handles patterns with repeats and other complex constructs that
     /// require backtracking and proper quantifier evaluation.
@@ -151,107 +290,56 @@ impl ArrayPattern {
         seq_pattern: &SequencePattern,
         array_cbor: &CBOR,
         arr: &[CBOR],
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    ) -> (Vec<Path>, Captures) {
         // Use the existing sequence matching logic to find element assignments
         if let Some(assignments) =
             self.find_sequence_element_assignments(seq_pattern, arr)
         {
-            let mut all_captures = std::collections::HashMap::new();
+            let mut all_captures = Captures::new();
 
             // Process each pattern and its assigned elements
             for (pattern_idx, pattern) in
                 seq_pattern.patterns().iter().enumerate()
             {
                 // Check if this is a capture pattern containing a repeat
-                // pattern
+                // pattern that can consume a run of elements
                 if let Pattern::Meta(crate::pattern::MetaPattern::Capture(
                     capture_pattern,
                 )) = pattern
+                    && captures_run(pattern)
                 {
-                    // Check if the capture contains a repeat pattern
-                    if extract_capture_with_repeat(pattern).is_some() {
-                        // This is a capture pattern with a repeat (like
-                        // @rest((*)*)) We need to
-                        // capture the sub-array of matched elements
-                        let captured_elements: Vec<CBOR> = assignments
-                            .iter()
-                            .filter_map(|&(p_idx, e_idx)| {
-                                if p_idx == pattern_idx {
-                                    Some(arr[e_idx].clone())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-
-                        // Create a sub-array from the captured elements
-                        let sub_array = captured_elements.to_cbor();
-
-                        // For capture patterns, we directly capture the
-                        // sub-array with the capture name
-                        let capture_name = capture_pattern.name().to_string();
-                        let array_context_path =
-                            build_simple_array_context_path(
-                                array_cbor, &sub_array,
-                            );
-
-                        all_captures
-                            .entry(capture_name.clone())
-                            .or_insert_with(Vec::new)
-                            .push(array_context_path);
-
-                        continue;
-                    }
-                }
-                // Check if this is a direct repeat pattern that might capture
-                // multiple elements
-                else if is_repeat_pattern(pattern)
-                    && let Pattern::Meta(crate::pattern::MetaPattern::Repeat(
-                        repeat_pattern,
-                    )) = pattern
-                {
-                    // For repeat patterns, check if they have captures
-                    let mut repeat_capture_names = Vec::new();
-                    repeat_pattern
-                        .collect_capture_names(&mut repeat_capture_names);
-
-                    if !repeat_capture_names.is_empty() {
-                        // This is a repeat pattern with captures (like
-                        // @rest((*)*))
-                        // We need to capture the sub-array of matched
-                        // elements
-                        let captured_elements: Vec<CBOR> = assignments
-                            .iter()
-                            .filter_map(|&(p_idx, e_idx)| {
-                                if p_idx == pattern_idx {
-                                    Some(arr[e_idx].clone())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-
-                        // Create a sub-array from the captured elements
-                        let sub_array = captured_elements.to_cbor();
-
-                        // Get captures from the repeat pattern against the
-                        // sub-array
-                        let (_sub_paths, sub_captures) =
-                            repeat_pattern.paths_with_captures(&sub_array);
-
-                        // Transform captures to include array context
-                        transform_captures_with_array_context(
-                            array_cbor,
-                            &sub_array,
-                            sub_captures,
-                            &mut all_captures,
-                        );
-                        continue;
-                    }
-                }
+                    // This is a capture pattern with a repeat (like
+                    // @rest((*)*)) We need to
+                    // capture the sub-array of matched elements
+                    let captured_elements: Vec<CBOR> = assignments
+                        .iter()
+                        .filter_map(|&(p_idx, e_idx)| {
+                            if p_idx == pattern_idx {
+                                Some(arr[e_idx].clone())
+                            } else {
+                                None
+                            }
+                        })
+                        .collect();
 
-                // For non-repeat patterns or repeat patterns without captures,
-                // process each assigned element individually
+                    // Create a sub-array from the captured elements
+                    let sub_array = captured_elements.to_cbor();
+
+                    // For capture patterns, we directly capture the
+                    // sub-array with the capture name
+                    let capture_name = capture_pattern.name().to_string();
+                    let array_context_path =
+                        build_simple_array_context_path(array_cbor, &sub_array);
+
+                    all_captures
+                        .entry(capture_name.clone())
+                        .or_default()
+                        .push(array_context_path);
+
+                    continue;
+                }
+                // Otherwise process each assigned element individually, so
+                // captures inside repeats get one path per matched element
                 for element_idx in
                     assignments.iter().filter_map(|&(p_idx, e_idx)| {
                         if p_idx == pattern_idx {
@@ -281,7 +369,118 @@ impl ArrayPattern {
             (vec![vec![array_cbor.clone()]], all_captures)
         } else {
             // Sequence doesn't match the array
-            (vec![], std::collections::HashMap::new())
+            (vec![], Captures::new())
+        }
+    }
+
+    /// Pairs `patterns` with the elements of `array_cbor` in any order, and
+    /// collects the captures each pattern makes on its element with proper
+    /// array context.
+    fn handle_unordered_captures(
+        patterns: &[Pattern],
+        array_cbor: &CBOR,
+    ) -> (Vec<Path>, Captures) {
+        let CBORCase::Array(arr) = array_cbor.as_case() else {
+            return (vec![], Captures::new());
+        };
+        let Some(assignment) = assign_unordered(patterns, arr) else {
+            return (vec![], Captures::new());
+        };
+        let mut all_captures = Captures::new();
+        for (pattern, element_idx) in patterns.iter().zip(assignment) {
+            let element = &arr[element_idx];
+            let (_element_paths, element_captures) =
+                pattern.paths_with_captures(element);
+            transform_captures_with_array_context(
+                array_cbor,
+                element,
+                element_captures,
+                &mut all_captures,
+            );
+        }
+        (vec![vec![array_cbor.clone()]], all_captures)
+    }
+
+    /// Returns `true` if the elements of `array`, which is `arr`, match
+    /// `pattern` as a whole.
+    ///
+    /// For unified syntax, the pattern matches the array elements as a
+    /// sequence, not any individual element. For example, `[42]` matches
+    /// `[42]` but not `[1, 42, 3]`, and `["a", "b"]` matches `["a", "b"]` but
+    /// not `["a", "x", "b"]`.
+    fn elements_match(
+        &self,
+        array: &CBOR,
+        arr: &[CBOR],
+        pattern: &Pattern,
+    ) -> bool {
+        match pattern {
+            // Complex case: sequences, repeats, etc.
+            Pattern::Meta(MetaPattern::Sequence(seq_pattern)) => {
+                let patterns = seq_pattern.patterns();
+
+                // Check if this sequence contains any repeat patterns or
+                // back-references that require backtracking
+                let needs_backtracking = has_repeat_patterns_in_slice(patterns)
+                    || patterns.iter().any(is_backref);
+
+                if needs_backtracking {
+                    // Use VM-based matching for complex sequences
+                    !self.match_complex_sequence(array, pattern).is_empty()
+                } else {
+                    // Simple sequence: match each pattern against
+                    // consecutive elements
+                    patterns.len() == arr.len()
+                        && patterns
+                            .iter()
+                            .zip(arr)
+                            .all(|(pattern, element)| pattern.matches(element))
+                }
+            }
+
+            // For individual repeat patterns
+            Pattern::Meta(MetaPattern::Repeat(_)) => {
+                // Use VM-based matching for repeat patterns
+                !self.match_complex_sequence(array, pattern).is_empty()
+            }
+
+            // A negated repeat consumes a run of elements, like a repeat
+            Pattern::Meta(MetaPattern::Not(_))
+                if extract_negated_repeat(pattern).is_some() =>
+            {
+                SequenceAssigner::new(std::slice::from_ref(pattern), arr)
+                    .can_match()
+            }
+
+            // Alternatives or conjunctions that consume more or less than one
+            // element combine how each operand matches the elements
+            Pattern::Meta(MetaPattern::Or(or_pattern))
+                if !is_single_element(pattern) =>
+            {
+                or_pattern
+                    .patterns()
+                    .iter()
+                    .any(|pattern| self.elements_match(array, arr, pattern))
+            }
+            Pattern::Meta(MetaPattern::And(and_pattern))
+                if !is_single_element(pattern) =>
+            {
+                and_pattern
+                    .patterns()
+                    .iter()
+                    .all(|pattern| self.elements_match(array, arr, pattern))
+            }
+
+            // A capture matches the elements as the pattern it captures does,
+            // so `[@n(number)]` matches only an array of one number
+            Pattern::Meta(MetaPattern::Capture(capture_pattern)) => {
+                self.elements_match(array, arr, capture_pattern.pattern())
+            }
+
+            // Simple case: any other pattern, including `|`, `&` and `!` of
+            // single-element patterns, matches an array with exactly one
+            // element
+            _ => arr.len() == 1 && pattern.matches(&arr[0]),
         }
     }
 
@@ -310,122 +509,10 @@ impl Matcher for ArrayPattern {
                         vec![vec![haystack.clone()]]
                     }
                     ArrayPattern::Elements(pattern) => {
-                        // For unified syntax, the pattern should match against
-                        // the array elements
-                        // as a sequence, not against any individual element.
-                        //
-                        // Examples:
-                        // - [42] should match [42] but not [1, 42, 3]
-                        // - ["a" , "b"] should match ["a", "b"] but not ["a",
-                        //   "x", "b"]
-
-                        // Check if this is a simple single-element case
-                        use crate::pattern::{MetaPattern, Pattern};
-
-                        match pattern.as_ref() {
-                            // Simple case: single pattern should match array
-                            // with exactly one element
-                            Pattern::Value(_)
-                            | Pattern::Structure(_)
-                            | Pattern::Meta(MetaPattern::Any(_)) => {
-                                if arr.len() == 1 {
-                                    if pattern.matches(&arr[0]) {
-                                        vec![vec![haystack.clone()]]
-                                    } else {
-                                        vec![]
-                                    }
-                                } else {
-                                    vec![]
-                                }
-                            }
-
-                            // Complex case: sequences, repeats, etc.
-                            Pattern::Meta(MetaPattern::Sequence(
-                                seq_pattern,
-                            )) => {
-                                let patterns = seq_pattern.patterns();
-
-                                // Check if this sequence contains any repeat
-                                // patterns that require VM-based matching
-                                let has_repeat_patterns =
-                                    has_repeat_patterns_in_slice(patterns);
-
-                                if has_repeat_patterns {
-                                    // Use VM-based matching for complex
-                                    // sequences
-
-                                    self.match_complex_sequence(
-                                        haystack, pattern,
-                                    )
-                                } else {
-                                    // Simple sequence: match each pattern
-                                    // against consecutive elements
-                                    if patterns.len() == arr.len() {
-                                        // Check if each pattern matches the
-                                        // corresponding array element
-                                        for (i, element_pattern) in
-                                            patterns.iter().enumerate()
-                                        {
-                                            if !element_pattern.matches(&arr[i])
-                                            {
-                                                return vec![];
-                                            }
-                                        }
-                                        vec![vec![haystack.clone()]]
-                                    } else {
-                                        vec![]
-                                    }
-                                }
-                            }
-
-                            // For individual repeat patterns
-                            Pattern::Meta(MetaPattern::Repeat(_)) => {
-                                // Use VM-based matching for repeat patterns
-                                self.match_complex_sequence(haystack, pattern)
-                            }
-
-                            // For other meta patterns, handle them properly
-                            Pattern::Meta(MetaPattern::Capture(
-                                capture_pattern,
-                            )) => {
-                                // Capture patterns should search within array
-                                // elements
-                                // (This is different from non-capture patterns)
-                                let has_matching_element =
-                                    arr.iter().any(|element| {
-                                        capture_pattern
-                                            .pattern()
-                                            .matches(element)
-                                    });
-
-                                if has_matching_element {
-                                    vec![vec![haystack.clone()]]
-                                } else {
-                                    vec![]
-                                }
-                            }
-
-                            // For other meta patterns (or, and, etc.), use the
-                            // old heuristic
-                            // This handles cases like `[(number | text)]`
-                            _ => {
-                                // Check if the pattern matches the array as a
-                                // whole sequence
-                                // For now, use a heuristic: if it's a simple
-                                // meta pattern,
-                                // apply it to each element and require at least
-                                // one match
-                                // This is not perfect but maintains some
-                                // compatibility
-                                let mut result = Vec::new();
-                                for element in arr {
-                                    if pattern.matches(element) {
-                                        result.push(vec![haystack.clone()]);
-                                        break;
-                                    }
-                                }
-                                result
-                            }
+                        if self.elements_match(haystack, arr, pattern) {
+                            vec![vec![haystack.clone()]]
+                        } else {
+                            vec![]
                         }
                     }
                     ArrayPattern::Length(interval) => {
@@ -435,6 +522,22 @@ impl Matcher for ArrayPattern {
                             vec![]
                         }
                     }
+                    ArrayPattern::LengthAndElements(interval, pattern) => {
+                        if interval.contains(arr.len())
+                            && self.elements_match(haystack, arr, pattern)
+                        {
+                            vec![vec![haystack.clone()]]
+                        } else {
+                            vec![]
+                        }
+                    }
+                    ArrayPattern::Unordered(patterns) => {
+                        if assign_unordered(patterns, arr).is_some() {
+                            vec![vec![haystack.clone()]]
+                        } else {
+                            vec![]
+                        }
+                    }
                 }
             }
             _ => {
@@ -453,53 +556,14 @@ impl Matcher for ArrayPattern {
         // Collect capture names from inner patterns
         self.collect_capture_names(captures);
 
-        // Check if this pattern has captures
-        let mut capture_names = Vec::new();
-        self.collect_capture_names(&mut capture_names);
-
-        if capture_names.is_empty() {
-            // No captures, use the simple MatchStructure approach
-            let idx = literals.len();
-            literals.push(Pattern::Structure(
-                crate::pattern::StructurePattern::Array(self.clone()),
-            ));
-            code.push(Instr::MatchStructure(idx));
-        } else {
-            // Has captures, compile to VM navigation instructions
-            match self {
-                ArrayPattern::Elements(pattern) => {
-                    // First check that we have an array
-                    let array_check_idx = literals.len();
-                    literals.push(Pattern::Structure(
-                        crate::pattern::StructurePattern::Array(
-                            ArrayPattern::Any,
-                        ),
-                    ));
-                    code.push(Instr::MatchStructure(array_check_idx));
-
-                    // Navigate to array elements
-                    code.push(Instr::PushAxis(
-                        crate::pattern::vm::Axis::ArrayElement,
-                    ));
-
-                    // Compile the inner pattern with captures
-                    pattern.compile(code, literals, captures);
-
-                    // Pop back to array level
-                    code.push(Instr::Pop);
-                }
-                _ => {
-                    // Other array patterns (length-based) don't support
-                    // captures in this context Fall back to
-                    // MatchStructure
-                    let idx = literals.len();
-                    literals.push(Pattern::Structure(
-                        crate::pattern::StructurePattern::Array(self.clone()),
-                    ));
-                    code.push(Instr::MatchStructure(idx));
-                }
-            }
-        }
+        // Match the array as a whole, captures included, rather than
+        // navigating into its elements: the threads of an enclosing program
+        // would otherwise match a sequence one element at a time
+        let idx = literals.len();
+        literals.push(Pattern::Structure(
+            crate::pattern::StructurePattern::Array(self.clone()),
+        ));
+        code.push(Instr::MatchStructure(idx));
     }
 
     fn collect_capture_names(&self, names: &mut Vec<String>) {
@@ -507,36 +571,40 @@ impl Matcher for ArrayPattern {
             ArrayPattern::Any => {
                 // No captures in a simple any pattern
             }
-            ArrayPattern::Elements(pattern) => {
+            ArrayPattern::Elements(pattern)
+            | ArrayPattern::LengthAndElements(_, pattern) => {
                 // Collect captures from the element pattern
                 pattern.collect_capture_names(names);
             }
             ArrayPattern::Length(_) => {
                 // No captures in length range patterns
             }
+            ArrayPattern::Unordered(patterns) => {
+                for pattern in patterns {
+                    pattern.collect_capture_names(names);
+                }
+            }
         }
     }
 
-    fn paths_with_captures(
-        &self,
-        cbor: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, cbor: &CBOR) -> (Vec<Path>, Captures) {
         // For simple cases that never have captures, use the fast path
         match self {
             ArrayPattern::Any | ArrayPattern::Length(_) => {
-                return (self.paths(cbor), std::collections::HashMap::new());
+                return (self.paths(cbor), Captures::new());
             }
-            ArrayPattern::Elements(pattern) => {
+            ArrayPattern::Unordered(patterns) => {
+                return Self::handle_unordered_captures(patterns, cbor);
+            }
+            ArrayPattern::Elements(pattern)
+            | ArrayPattern::LengthAndElements(_, pattern) => {
                 // Check if this specific pattern has any captures
                 let mut capture_names = Vec::new();
                 pattern.collect_capture_names(&mut capture_names);
 
                 if capture_names.is_empty() {
                     // No captures in the element pattern, use the fast path
-                    return (
-                        self.paths(cbor),
-                        std::collections::HashMap::new(),
-                    );
+                    return (self.paths(cbor), Captures::new());
                 }
 
                 // Has captures, continue with complex logic below
@@ -545,10 +613,13 @@ impl Matcher for ArrayPattern {
 
         match cbor.as_case() {
             CBORCase::Array(_arr) => {
-                if let ArrayPattern::Elements(pattern) = self {
-                    // First check if this array pattern matches at all
+                if let ArrayPattern::Elements(pattern)
+                | ArrayPattern::LengthAndElements(_, pattern) = self
+                {
+                    // First check if this array pattern matches at all, which
+                    // checks any length constraint before the elements
                     if self.paths(cbor).is_empty() {
-                        return (vec![], std::collections::HashMap::new());
+                        return (vec![], Captures::new());
                     }
 
                     // For patterns with captures, we need special handling
@@ -565,102 +636,118 @@ impl Matcher for ArrayPattern {
                                 _arr,
                             )
                         }
-                        Pattern::Meta(
-                            crate::pattern::MetaPattern::Capture(
-                                _capture_pattern,
-                            ),
-                        ) => {
-                            // For capture patterns like [@item(number)] or
-                            // [@item(42)],
-                            // use the VM approach for consistency with existing
-                            // behavior
-
-                            // Use the VM approach for consistent behavior
-                            let mut code = Vec::new();
-                            let mut literals = Vec::new();
-                            let mut captures_list = Vec::new();
-
-                            // Compile the entire ArrayPattern (not just the
-                            // inner pattern)
-                            let array_pattern = Pattern::Structure(
-                                crate::pattern::StructurePattern::Array(
-                                    self.clone(),
-                                ),
-                            );
-                            array_pattern.compile(
-                                &mut code,
-                                &mut literals,
-                                &mut captures_list,
-                            );
-                            code.push(crate::pattern::vm::Instr::Accept);
-
-                            let program = crate::pattern::vm::Program {
-                                code,
-                                literals,
-                                capture_names: captures_list,
-                            };
-
-                            // Run the VM program against the CBOR
-                            crate::pattern::vm::run(&program, cbor)
-                        }
                         _ => {
-                            // For non-sequence patterns, use the original VM
-                            // approach
-                            // but start with the main Pattern's VM compilation
-                            // for better compatibility
-                            let mut code = Vec::new();
-                            let mut literals = Vec::new();
-                            let mut captures = Vec::new();
-
-                            // Compile the entire ArrayPattern (not just the
-                            // inner pattern)
-                            let array_pattern = Pattern::Structure(
-                                crate::pattern::StructurePattern::Array(
-                                    self.clone(),
-                                ),
-                            );
-                            array_pattern.compile(
-                                &mut code,
-                                &mut literals,
-                                &mut captures,
-                            );
-                            code.push(crate::pattern::vm::Instr::Accept);
-
-                            let program = crate::pattern::vm::Program {
-                                code,
-                                literals,
-                                capture_names: captures,
-                            };
-
-                            // Run the VM program against the CBOR
-                            crate::pattern::vm::run(&program, cbor)
+                            // For capture patterns like [@item(number)] and
+                            // other non-sequence patterns, run the VM program
+                            // for the entire ArrayPattern (not just the inner
+                            // pattern)
+                            self.cached_program().run(cbor)
                         }
                     }
                 } else {
                     // Other array patterns (length-based) don't have inner
                     // patterns with captures
-                    (self.paths(cbor), std::collections::HashMap::new())
+                    (self.paths(cbor), Captures::new())
                 }
             }
             _ => {
                 // Not an array, no match
-                (vec![], std::collections::HashMap::new())
+                (vec![], Captures::new())
             }
         }
     }
 }
 
+/// Formats the content of an array pattern.
+///
+/// A `!` at the start of the content negates all of it, as in
+/// `[!(number, text)]` or `[!(number | text)]`, so content that starts with
+/// a narrower negation is parenthesized to keep the negation to itself:
+/// just the first element of a sequence, or otherwise the whole content.
+fn format_elements(pattern: &Pattern) -> String {
+    let formatted = format_enclosed(pattern);
+    if !formatted.starts_with('!') {
+        return formatted;
+    }
+    match pattern {
+        Pattern::Meta(MetaPattern::Not(_)) => formatted,
+        Pattern::Meta(MetaPattern::Sequence(sequence))
+            if sequence.len() > 1 =>
+        {
+            let (first, rest) = sequence.patterns().split_first().unwrap();
+            let rest: Vec<String> =
+                rest.iter().map(Pattern::to_string).collect();
+            format!("({}), {}", first, rest.join(", "))
+        }
+        _ => format!("({})", formatted),
+    }
+}
+
 impl std::fmt::Display for ArrayPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ArrayPattern::Any => write!(f, "array"),
             ArrayPattern::Elements(pattern) => {
-                let formatted_pattern = format_array_element_pattern(pattern);
-                write!(f, "[{}]", formatted_pattern)
+                write!(f, "[{}]", format_elements(pattern))
             }
             ArrayPattern::Length(interval) => {
                 write!(f, "[{}]", interval)
             }
+            ArrayPattern::LengthAndElements(interval, pattern) => {
+                write!(f, "[{} & {}]", interval, format_elements(pattern))
+            }
+            ArrayPattern::Unordered(patterns) if patterns.is_empty() => {
+                write!(f, "[~]")
+            }
+            ArrayPattern::Unordered(patterns) => {
+                let formatted: Vec<String> =
+                    patterns.iter().map(Pattern::to_string).collect();
+                write!(f, "[~ {}]", formatted.join(", "))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the number of array programs compiled while running `f`.
+    fn compiled_programs(f: impl FnOnce()) -> usize {
+        let before = COMPILED_PROGRAMS.get();
+        f();
+        COMPILED_PROGRAMS.get() - before
+    }
+
+    #[test]
+    fn test_repeated_matches_reuse_compiled_program() {
+        let pattern = Pattern::parse("[(@outer([@inner(number)]))*]").unwrap();
+        let cbor = CBOR::from(vec![vec![1], vec![2], vec![3]]);
+        // Compiled once for each array pattern, the first time it's matched
+        let mut expected = None;
+        let compiled = compiled_programs(|| {
+            expected = Some(pattern.paths_with_captures(&cbor));
+        });
+        assert_eq!(compiled, 2);
+        let expected = expected.unwrap();
+        assert_eq!(expected.1["inner"].len(), 3);
+
+        let compiled = compiled_programs(|| {
+            for _ in 0..10 {
+                assert_eq!(pattern.paths_with_captures(&cbor), expected);
+            }
+        });
+        assert_eq!(compiled, 0);
+
+        // A clone has a program of its own, and so does another pattern
+        let clone = pattern.clone();
+        let other = Pattern::parse("[@n(number)*]").unwrap();
+        let compiled = compiled_programs(|| {
+            for _ in 0..10 {
+                assert_eq!(clone.paths_with_captures(&cbor), expected);
+                other.paths_with_captures(&CBOR::from(vec![1, 2]));
+            }
+        });
+        assert_eq!(compiled, 3);
+    }
+}
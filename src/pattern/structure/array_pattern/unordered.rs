@@ -0,0 +1,114 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{Matcher, Pattern};
+
+/// Pairs each of `patterns` with a distinct element of `arr` that it
+/// matches, returning the index of the element paired with each pattern, or
+/// `None` if there is no such pairing.
+///
+/// This is a bipartite matching, found by augmenting paths: each pattern in
+/// turn takes the first free element it matches, or else one that can be
+/// freed by moving the pattern holding it to another of its elements. Earlier
+/// patterns therefore keep earlier elements unless a later pattern needs
+/// them, and the pairing found for a given pattern and array is always the
+/// same.
+pub(super) fn assign_unordered(
+    patterns: &[Pattern],
+    arr: &[CBOR],
+) -> Option<Vec<usize>> {
+    if patterns.len() != arr.len() {
+        return None;
+    }
+
+    // The elements each pattern matches, tested once each
+    let candidates: Vec<Vec<usize>> = patterns
+        .iter()
+        .map(|pattern| {
+            (0..arr.len())
+                .filter(|&idx| pattern.matches(&arr[idx]))
+                .collect()
+        })
+        .collect();
+    if candidates.iter().any(Vec::is_empty) {
+        return None;
+    }
+
+    let mut holder = vec![None; arr.len()];
+    for pattern_idx in 0..patterns.len() {
+        let mut visited = vec![false; arr.len()];
+        if !augment(pattern_idx, &candidates, &mut holder, &mut visited) {
+            return None;
+        }
+    }
+
+    let mut assignment = vec![0; patterns.len()];
+    for (element_idx, pattern_idx) in holder.into_iter().enumerate() {
+        assignment[pattern_idx?] = element_idx;
+    }
+    Some(assignment)
+}
+
+/// Finds an element for `pattern_idx`, preferring a free one, and otherwise
+/// moving the pattern holding one of its candidates to another element.
+/// `holder` records the pattern holding each element.
+fn augment(
+    pattern_idx: usize,
+    candidates: &[Vec<usize>],
+    holder: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    let free = candidates[pattern_idx].iter().find(|&&element_idx| {
+        !visited[element_idx] && holder[element_idx].is_none()
+    });
+    if let Some(&element_idx) = free {
+        visited[element_idx] = true;
+        holder[element_idx] = Some(pattern_idx);
+        return true;
+    }
+    for &element_idx in &candidates[pattern_idx] {
+        if visited[element_idx] {
+            continue;
+        }
+        visited[element_idx] = true;
+        let other = holder[element_idx].expect("no candidate is free");
+        if augment(other, candidates, holder, visited) {
+            holder[element_idx] = Some(pattern_idx);
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assign(patterns: &[&str], arr: &[i64]) -> Option<Vec<usize>> {
+        let patterns: Vec<_> = patterns
+            .iter()
+            .map(|p| Pattern::parse(p).unwrap())
+            .collect();
+        let arr: Vec<_> = arr.iter().map(|&n| CBOR::from(n)).collect();
+        assign_unordered(&patterns, &arr)
+    }
+
+    #[test]
+    fn test_assign_unordered() {
+        assert_eq!(assign(&["1", "2"], &[2, 1]), Some(vec![1, 0]));
+        assert_eq!(assign(&[], &[]), Some(vec![]));
+        assert_eq!(assign(&["1", "2"], &[1, 1]), None);
+        assert_eq!(assign(&["1"], &[1, 2]), None);
+
+        // The first pattern gives up the element the second needs
+        assert_eq!(assign(&["number", "1"], &[1, 2]), Some(vec![1, 0]));
+
+        // Both patterns match both elements
+        assert_eq!(assign(&["number", "number"], &[1, 2]), Some(vec![0, 1]));
+
+        // A chain of moves frees the last element
+        assert_eq!(
+            assign(&["1 | 2", "2 | 3", "1"], &[1, 2, 3]),
+            Some(vec![1, 2, 0])
+        );
+    }
+}
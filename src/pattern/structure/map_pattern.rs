@@ -4,11 +4,108 @@ use dcbor::prelude::*;
 
 use crate::{
     Interval,
-    pattern::{Matcher, Path, Pattern, vm::Instr},
+    pattern::{
+        Captures, Matcher, MetaPattern, Path, Pattern, StructurePattern,
+        meta::{Bindings, bind_captures, is_backref, matches_with_bindings},
+        vm::Instr,
+    },
 };
 
+/// A constraint on the entries of a map: the number of entries whose key and
+/// value match its patterns must be in its count, which is at least one
+/// unless set otherwise.
+///
+/// Created with [`MapPattern::entry`] and used with
+/// [`MapPattern::with_counted_constraints`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MapConstraint {
+    key: Pattern,
+    value: Pattern,
+    count: Interval,
+}
+
+impl MapConstraint {
+    /// Requires at least `min` entries to match, keeping any maximum.
+    pub fn at_least(mut self, min: usize) -> Self {
+        self.count = match self.count.max() {
+            Some(max) => Interval::new(min..=max),
+            None => Interval::new(min..),
+        };
+        self
+    }
+
+    /// Requires at most `max` entries to match, keeping any minimum. A `max`
+    /// below the minimum is raised to it, as [`Interval::new`] does.
+    pub fn at_most(mut self, max: usize) -> Self {
+        self.count = Interval::new(self.count.min()..=max);
+        self
+    }
+
+    /// Requires exactly `count` entries to match.
+    pub fn exactly(self, count: usize) -> Self {
+        self.with_count(Interval::new(count..=count))
+    }
+
+    /// Requires the number of entries that match to be in `count`.
+    pub fn with_count(mut self, count: Interval) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Returns the pattern an entry's key must match.
+    pub fn key(&self) -> &Pattern { &self.key }
+
+    /// Returns the pattern an entry's value must match.
+    pub fn value(&self) -> &Pattern { &self.value }
+
+    /// Returns the number of entries allowed to match.
+    pub fn count(&self) -> Interval { self.count }
+
+    /// Returns `true` if the constraint only asks for some entry to match,
+    /// as the constraints of the other map patterns do.
+    pub(crate) fn is_uncounted(&self) -> bool {
+        self.count == Interval::new(1..)
+    }
+
+    /// Returns the key and value patterns of the constraint, dropping its
+    /// count.
+    pub(crate) fn into_key_value(self) -> (Pattern, Pattern) {
+        (self.key, self.value)
+    }
+
+    /// Returns the entries of `map` that satisfy the constraint, or `None` if
+    /// there are too few or too many of them.
+    fn entries<'a>(&self, map: &'a Map) -> Option<Vec<(&'a CBOR, &'a CBOR)>> {
+        let entries: Vec<_> = map
+            .iter()
+            .filter(|(key, value)| {
+                self.key.matches(key) && self.value.matches(value)
+            })
+            .collect();
+        self.count.contains(entries.len()).then_some(entries)
+    }
+}
+
+impl std::fmt::Display for MapConstraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_uncounted() {
+            write!(f, "{}: {}", self.key, self.value)
+        } else {
+            write!(f, "({}: {}){}", self.key, self.value, self.count)
+        }
+    }
+}
+
 /// Pattern for matching CBOR map structures.
+///
+/// A dCBOR map holds one entry for each key, sorted by the key's encoding.
+/// Entries are always visited in that order, so when several entries satisfy
+/// a constraint, the paths and captures they produce come in key order, and
+/// the same order results whether the pattern is matched directly or by the
+/// VM within a larger pattern.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MapPattern {
     /// Matches any map.
     Any,
@@ -17,6 +114,38 @@ pub enum MapPattern {
     Constraints(Vec<(Pattern, Pattern)>),
     /// Matches maps with number of key-value pairs in the given interval.
     Length(Interval),
+    /// Matches maps with number of key-value pairs in the given interval
+    /// that also satisfy all of the key-value constraints.
+    LengthAndConstraints(Interval, Vec<(Pattern, Pattern)>),
+    /// Matches maps with an entry whose key matches the first pattern and
+    /// whose value matches the second, continuing the path into the value.
+    ///
+    /// Unlike the other variants, whose paths stop at the map, each matching
+    /// entry yields the map followed by the path the value pattern matched,
+    /// so `{"a"}: {"b"}: number` reaches the number two levels down.
+    Value(Box<Pattern>, Box<Pattern>),
+    /// Matches maps with number of key-value pairs in the given interval
+    /// that satisfy all of the key-value constraints, and whose entries not
+    /// consumed by the constraints make up a map matching the rest pattern.
+    ///
+    /// The rest pattern is matched against a new map holding just those
+    /// entries, so a capture like `@rest(*)` captures the map itself, with a
+    /// path from the haystack map straight to it.
+    ConstraintsAndRest(Interval, Vec<(Pattern, Pattern)>, Box<Pattern>),
+    /// Matches maps every entry of which has a key matching the first
+    /// pattern and a value matching the second, including the empty map.
+    AllEntries(Box<Pattern>, Box<Pattern>),
+    /// Matches maps with number of key-value pairs in the given interval,
+    /// where the number of entries satisfying each constraint is within its
+    /// count, and whose entries satisfying no constraint make up a map
+    /// matching the rest pattern, if any, as in
+    /// [`MapPattern::ConstraintsAndRest`].
+    ///
+    /// Each constraint counts the entries that satisfy it independently of
+    /// the others, and its captures are made once for each of them.
+    /// Back-references are not bound between constraints, so one in a
+    /// counted map never matches.
+    CountedConstraints(Interval, Vec<MapConstraint>, Option<Box<Pattern>>),
 }
 
 impl MapPattern {
@@ -48,6 +177,414 @@ impl MapPattern {
     pub fn with_length_interval(interval: Interval) -> Self {
         MapPattern::Length(interval)
     }
+
+    /// Creates a new `MapPattern` that matches maps with number of key-value
+    /// pairs in the given interval that also satisfy all of the key-value
+    /// constraints.
+    pub fn with_constraints(
+        interval: Interval,
+        constraints: Vec<(Pattern, Pattern)>,
+    ) -> Self {
+        MapPattern::LengthAndConstraints(interval, constraints)
+    }
+
+    /// Creates a new `MapPattern` that matches maps with an entry whose key
+    /// and value match the given patterns, with paths continuing into the
+    /// matched value.
+    pub fn with_value_at_key(key: Pattern, value: Pattern) -> Self {
+        MapPattern::Value(Box::new(key), Box::new(value))
+    }
+
+    /// Creates a new `MapPattern` that matches maps with number of key-value
+    /// pairs in the given interval that satisfy all of the key-value
+    /// constraints, and whose remaining entries match `rest`.
+    pub fn with_rest(
+        interval: Interval,
+        constraints: Vec<(Pattern, Pattern)>,
+        rest: Pattern,
+    ) -> Self {
+        MapPattern::ConstraintsAndRest(interval, constraints, Box::new(rest))
+    }
+
+    /// Creates a new `MapPattern` that matches maps whose keys all match
+    /// `pattern`.
+    pub fn with_all_keys(pattern: Pattern) -> Self {
+        Self::with_all_entries(pattern, Pattern::any())
+    }
+
+    /// Creates a new `MapPattern` that matches maps whose values all match
+    /// `pattern`.
+    pub fn with_all_values(pattern: Pattern) -> Self {
+        Self::with_all_entries(Pattern::any(), pattern)
+    }
+
+    /// Creates a new `MapPattern` that matches maps every entry of which has
+    /// a key matching `key` and a value matching `value`.
+    pub fn with_all_entries(key: Pattern, value: Pattern) -> Self {
+        MapPattern::AllEntries(Box::new(key), Box::new(value))
+    }
+
+    /// Creates a constraint requiring at least one entry whose key and value
+    /// match the given patterns, which [`MapConstraint::at_least`] and the
+    /// other methods change the count of.
+    pub fn entry(key: Pattern, value: Pattern) -> MapConstraint {
+        MapConstraint { key, value, count: Interval::new(1..) }
+    }
+
+    /// Creates a new `MapPattern` that matches maps with number of key-value
+    /// pairs in the given interval where the number of entries satisfying
+    /// each constraint is within its count, and whose remaining entries
+    /// match `rest`, if given.
+    ///
+    /// If no constraint has a count other than the default of at least one,
+    /// this is the same pattern that [`MapPattern::with_constraints`] or
+    /// [`MapPattern::with_rest`] creates.
+    pub fn with_counted_constraints(
+        interval: Interval,
+        constraints: Vec<MapConstraint>,
+        rest: Option<Pattern>,
+    ) -> Self {
+        if !constraints.iter().all(MapConstraint::is_uncounted) {
+            return MapPattern::CountedConstraints(
+                interval,
+                constraints,
+                rest.map(Box::new),
+            );
+        }
+        let constraints = constraints
+            .into_iter()
+            .map(MapConstraint::into_key_value)
+            .collect();
+        match rest {
+            Some(rest) => Self::with_rest(interval, constraints, rest),
+            None => Self::with_constraints(interval, constraints),
+        }
+    }
+
+    /// Returns the paths through the values of the entries whose keys match
+    /// `key_pattern`, with the captures made along them rooted at the map.
+    fn value_paths_with_captures(
+        key_pattern: &Pattern,
+        value_pattern: &Pattern,
+        haystack: &CBOR,
+        map: &Map,
+    ) -> (Vec<Path>, Captures) {
+        let mut paths = Vec::new();
+        let mut all_captures = Captures::new();
+
+        for (key, value) in map.iter() {
+            let (key_paths, key_captures) =
+                key_pattern.paths_with_captures(key);
+            if key_paths.is_empty() {
+                continue;
+            }
+            let (value_paths, value_captures) =
+                value_pattern.paths_with_captures(value);
+            if value_paths.is_empty() {
+                continue;
+            }
+
+            for value_path in value_paths {
+                let mut path = vec![haystack.clone()];
+                path.extend(value_path);
+                paths.push(path);
+            }
+
+            // Capture paths are rooted at the key or value, so prefix them
+            // with the map
+            for (name, capture_paths) in
+                key_captures.into_iter().chain(value_captures)
+            {
+                let entry: &mut Vec<Path> =
+                    all_captures.entry(name).or_default();
+                for capture_path in capture_paths {
+                    let mut full_path = vec![haystack.clone()];
+                    full_path.extend(capture_path);
+                    entry.push(full_path);
+                }
+            }
+        }
+
+        (paths, all_captures)
+    }
+
+    /// Returns true if every constraint is satisfied by some entry of the map.
+    fn constraints_satisfied(
+        constraints: &[(Pattern, Pattern)],
+        map: &Map,
+    ) -> bool {
+        Self::constraint_entries(constraints, map).is_some()
+    }
+
+    /// Returns true if any constraint's key or value is a back-reference.
+    fn has_backrefs(constraints: &[(Pattern, Pattern)]) -> bool {
+        constraints
+            .iter()
+            .any(|(key, value)| is_backref(key) || is_backref(value))
+    }
+
+    /// Chooses an entry of the map satisfying each constraint, returning the
+    /// chosen entries in constraint order.
+    ///
+    /// Each constraint takes the first entry that satisfies it. When the
+    /// constraints contain back-references, the captures made by earlier
+    /// constraints are bound and later choices are backtracked until every
+    /// back-reference agrees with its binding.
+    fn constraint_entries<'a>(
+        constraints: &[(Pattern, Pattern)],
+        map: &'a Map,
+    ) -> Option<Vec<(&'a CBOR, &'a CBOR)>> {
+        if !Self::has_backrefs(constraints) {
+            return constraints
+                .iter()
+                .map(|(key_pattern, value_pattern)| {
+                    map.iter().find(|(key, value)| {
+                        key_pattern.matches(key) && value_pattern.matches(value)
+                    })
+                })
+                .collect();
+        }
+
+        fn assign<'a>(
+            constraints: &[(Pattern, Pattern)],
+            map: &'a Map,
+            bindings: &mut Bindings,
+            chosen: &mut Vec<(&'a CBOR, &'a CBOR)>,
+        ) -> bool {
+            let Some((key_pattern, value_pattern)) =
+                constraints.get(chosen.len())
+            else {
+                return true;
+            };
+            for (key, value) in map.iter() {
+                if !matches_with_bindings(key_pattern, key, bindings)
+                    || !matches_with_bindings(value_pattern, value, bindings)
+                {
+                    continue;
+                }
+                let mark = bindings.len();
+                bind_captures(key_pattern, key, bindings);
+                bind_captures(value_pattern, value, bindings);
+                chosen.push((key, value));
+                if assign(constraints, map, bindings, chosen) {
+                    return true;
+                }
+                chosen.pop();
+                bindings.truncate(mark);
+            }
+            false
+        }
+
+        let mut chosen = Vec::new();
+        assign(constraints, map, &mut Bindings::new(), &mut chosen)
+            .then_some(chosen)
+    }
+
+    /// Matches the key-value constraints against the map, collecting the
+    /// captures of every entry that satisfies each constraint.
+    ///
+    /// When the constraints contain back-references, only the entries chosen
+    /// for them by [`Self::constraint_entries`] are captured, so that the
+    /// captures agree with the bindings the match was made under.
+    fn constraints_paths_with_captures(
+        constraints: &[(Pattern, Pattern)],
+        haystack: &CBOR,
+        map: &Map,
+    ) -> (Vec<Path>, Captures) {
+        let Some(chosen) = Self::constraint_entries(constraints, map) else {
+            return (vec![], Captures::new());
+        };
+        let has_backrefs = Self::has_backrefs(constraints);
+
+        let mut all_captures = Captures::new();
+        for ((key_pattern, value_pattern), chosen_entry) in
+            constraints.iter().zip(chosen)
+        {
+            let entries: Vec<(&CBOR, &CBOR)> = if has_backrefs {
+                vec![chosen_entry]
+            } else {
+                map.iter()
+                    .filter(|(key, value)| {
+                        key_pattern.matches(key) && value_pattern.matches(value)
+                    })
+                    .collect()
+            };
+            for (key, value) in entries {
+                Self::collect_entry_captures(
+                    key_pattern,
+                    value_pattern,
+                    key,
+                    value,
+                    haystack,
+                    &mut all_captures,
+                );
+            }
+        }
+
+        (vec![vec![haystack.clone()]], all_captures)
+    }
+
+    /// Returns the map of the entries the constraints did not consume, given
+    /// the entries [`Self::constraint_entries`] chose for them.
+    ///
+    /// An entry is consumed if it satisfies any constraint, or when the
+    /// constraints contain back-references, if it was chosen for one, which
+    /// is what the captures are made from.
+    fn rest_map(
+        constraints: &[(Pattern, Pattern)],
+        chosen: &[(&CBOR, &CBOR)],
+        map: &Map,
+    ) -> CBOR {
+        let has_backrefs = Self::has_backrefs(constraints);
+        let mut rest = Map::new();
+        for (key, value) in map.iter() {
+            let consumed = if has_backrefs {
+                chosen.iter().any(|(chosen_key, _)| *chosen_key == key)
+            } else {
+                constraints.iter().any(|(key_pattern, value_pattern)| {
+                    key_pattern.matches(key) && value_pattern.matches(value)
+                })
+            };
+            if !consumed {
+                rest.insert(key.clone(), value.clone());
+            }
+        }
+        rest.into()
+    }
+
+    /// Matches the key-value constraints and the rest pattern against the
+    /// map, adding the captures made by the rest pattern, which are rooted at
+    /// the map of the remaining entries, to those of the constraints.
+    fn rest_paths_with_captures(
+        constraints: &[(Pattern, Pattern)],
+        rest_pattern: &Pattern,
+        haystack: &CBOR,
+        map: &Map,
+    ) -> (Vec<Path>, Captures) {
+        let Some(chosen) = Self::constraint_entries(constraints, map) else {
+            return (vec![], Captures::new());
+        };
+        let rest = Self::rest_map(constraints, &chosen, map);
+        let (rest_paths, rest_captures) =
+            rest_pattern.paths_with_captures(&rest);
+        if rest_paths.is_empty() {
+            return (vec![], Captures::new());
+        }
+
+        let (paths, mut all_captures) =
+            Self::constraints_paths_with_captures(constraints, haystack, map);
+        for (name, capture_paths) in rest_captures {
+            let entry = all_captures.entry(name).or_default();
+            for capture_path in capture_paths {
+                let mut full_path = vec![haystack.clone()];
+                full_path.extend(capture_path);
+                entry.push(full_path);
+            }
+        }
+        (paths, all_captures)
+    }
+
+    /// Returns the entries satisfying each counted constraint, in constraint
+    /// order, or `None` if any constraint is satisfied by too few or too
+    /// many entries.
+    fn counted_entries<'a>(
+        constraints: &[MapConstraint],
+        map: &'a Map,
+    ) -> Option<Vec<Vec<(&'a CBOR, &'a CBOR)>>> {
+        constraints
+            .iter()
+            .map(|constraint| constraint.entries(map))
+            .collect()
+    }
+
+    /// Returns the map of the entries that satisfy no counted constraint.
+    fn counted_rest_map(constraints: &[MapConstraint], map: &Map) -> CBOR {
+        let mut rest = Map::new();
+        for (key, value) in map.iter() {
+            if !constraints.iter().any(|constraint| {
+                constraint.key.matches(key) && constraint.value.matches(value)
+            }) {
+                rest.insert(key.clone(), value.clone());
+            }
+        }
+        rest.into()
+    }
+
+    /// Matches counted constraints and the optional rest pattern against the
+    /// map, collecting the captures of every entry that satisfies each
+    /// constraint and those of the rest pattern.
+    fn counted_paths_with_captures(
+        constraints: &[MapConstraint],
+        rest_pattern: Option<&Pattern>,
+        haystack: &CBOR,
+        map: &Map,
+    ) -> (Vec<Path>, Captures) {
+        let Some(entries) = Self::counted_entries(constraints, map) else {
+            return (vec![], Captures::new());
+        };
+        let rest_captures = match rest_pattern {
+            Some(rest_pattern) => {
+                let rest = Self::counted_rest_map(constraints, map);
+                let (rest_paths, rest_captures) =
+                    rest_pattern.paths_with_captures(&rest);
+                if rest_paths.is_empty() {
+                    return (vec![], Captures::new());
+                }
+                rest_captures
+            }
+            None => Captures::new(),
+        };
+
+        let mut all_captures = Captures::new();
+        for (constraint, entries) in constraints.iter().zip(entries) {
+            for (key, value) in entries {
+                Self::collect_entry_captures(
+                    &constraint.key,
+                    &constraint.value,
+                    key,
+                    value,
+                    haystack,
+                    &mut all_captures,
+                );
+            }
+        }
+        for (name, capture_paths) in rest_captures {
+            let entry = all_captures.entry(name).or_default();
+            for capture_path in capture_paths {
+                let mut full_path = vec![haystack.clone()];
+                full_path.extend(capture_path);
+                entry.push(full_path);
+            }
+        }
+        (vec![vec![haystack.clone()]], all_captures)
+    }
+
+    /// Adds the captures made by a constraint on one entry, prefixing them
+    /// with the map.
+    fn collect_entry_captures(
+        key_pattern: &Pattern,
+        value_pattern: &Pattern,
+        key: &CBOR,
+        value: &CBOR,
+        haystack: &CBOR,
+        all_captures: &mut Captures,
+    ) {
+        let (_, key_captures) = key_pattern.paths_with_captures(key);
+        let (_, value_captures) = value_pattern.paths_with_captures(value);
+
+        // Capture paths are rooted at the key or value, so prefix them
+        // with the map
+        for (name, capture_paths) in
+            key_captures.into_iter().chain(value_captures)
+        {
+            let entry = all_captures.entry(name).or_default();
+            for capture_path in capture_paths {
+                let mut full_path = vec![haystack.clone()];
+                full_path.extend(capture_path);
+                entry.push(full_path);
+            }
+        }
+    }
 }
 
 impl Matcher for MapPattern {
@@ -62,21 +599,11 @@ impl Matcher for MapPattern {
                     }
                     MapPattern::Constraints(constraints) => {
                         // All constraints must be satisfied
-                        for (key_pattern, value_pattern) in constraints {
-                            let mut found_match = false;
-                            for (key, value) in map.iter() {
-                                if key_pattern.matches(key)
-                                    && value_pattern.matches(value)
-                                {
-                                    found_match = true;
-                                    break;
-                                }
-                            }
-                            if !found_match {
-                                return vec![];
-                            }
+                        if Self::constraints_satisfied(constraints, map) {
+                            vec![vec![haystack.clone()]]
+                        } else {
+                            vec![]
                         }
-                        vec![vec![haystack.clone()]]
                     }
                     MapPattern::Length(interval) => {
                         if interval.contains(map.len()) {
@@ -85,6 +612,76 @@ impl Matcher for MapPattern {
                             vec![]
                         }
                     }
+                    MapPattern::LengthAndConstraints(interval, constraints) => {
+                        // Check the cheap length test before the constraints
+                        if interval.contains(map.len())
+                            && Self::constraints_satisfied(constraints, map)
+                        {
+                            vec![vec![haystack.clone()]]
+                        } else {
+                            vec![]
+                        }
+                    }
+                    MapPattern::Value(key_pattern, value_pattern) => map
+                        .iter()
+                        .filter(|(key, _)| key_pattern.matches(key))
+                        .flat_map(|(_, value)| value_pattern.paths(value))
+                        .map(|value_path| {
+                            let mut path = vec![haystack.clone()];
+                            path.extend(value_path);
+                            path
+                        })
+                        .collect(),
+                    MapPattern::ConstraintsAndRest(
+                        interval,
+                        constraints,
+                        rest_pattern,
+                    ) => {
+                        let matched = interval.contains(map.len())
+                            && Self::constraint_entries(constraints, map)
+                                .is_some_and(|chosen| {
+                                    rest_pattern.matches(&Self::rest_map(
+                                        constraints,
+                                        &chosen,
+                                        map,
+                                    ))
+                                });
+                        if matched {
+                            vec![vec![haystack.clone()]]
+                        } else {
+                            vec![]
+                        }
+                    }
+                    MapPattern::AllEntries(key_pattern, value_pattern) => {
+                        if map.iter().all(|(key, value)| {
+                            key_pattern.matches(key)
+                                && value_pattern.matches(value)
+                        }) {
+                            vec![vec![haystack.clone()]]
+                        } else {
+                            vec![]
+                        }
+                    }
+                    MapPattern::CountedConstraints(
+                        interval,
+                        constraints,
+                        rest_pattern,
+                    ) => {
+                        let matched = interval.contains(map.len())
+                            && Self::counted_entries(constraints, map)
+                                .is_some()
+                            && rest_pattern.as_ref().is_none_or(|rest| {
+                                rest.matches(&Self::counted_rest_map(
+                                    constraints,
+                                    map,
+                                ))
+                            });
+                        if matched {
+                            vec![vec![haystack.clone()]]
+                        } else {
+                            vec![]
+                        }
+                    }
                 }
             }
             _ => {
@@ -115,99 +712,130 @@ impl Matcher for MapPattern {
             MapPattern::Any => {
                 // No captures in a simple any pattern
             }
-            MapPattern::Constraints(constraints) => {
+            MapPattern::Constraints(constraints)
+            | MapPattern::LengthAndConstraints(_, constraints) => {
                 // Collect captures from all key and value patterns
                 for (key_pattern, value_pattern) in constraints {
                     key_pattern.collect_capture_names(names);
                     value_pattern.collect_capture_names(names);
                 }
             }
+            MapPattern::Value(key_pattern, value_pattern)
+            | MapPattern::AllEntries(key_pattern, value_pattern) => {
+                key_pattern.collect_capture_names(names);
+                value_pattern.collect_capture_names(names);
+            }
+            MapPattern::ConstraintsAndRest(_, constraints, rest_pattern) => {
+                for (key_pattern, value_pattern) in constraints {
+                    key_pattern.collect_capture_names(names);
+                    value_pattern.collect_capture_names(names);
+                }
+                rest_pattern.collect_capture_names(names);
+            }
+            MapPattern::CountedConstraints(_, constraints, rest_pattern) => {
+                for constraint in constraints {
+                    constraint.key.collect_capture_names(names);
+                    constraint.value.collect_capture_names(names);
+                }
+                if let Some(rest_pattern) = rest_pattern {
+                    rest_pattern.collect_capture_names(names);
+                }
+            }
             MapPattern::Length(_) => {
                 // No captures in length interval patterns
             }
         }
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         // Check if this CBOR value is a map
         let CBORCase::Map(map) = haystack.as_case() else {
-            return (vec![], std::collections::HashMap::new());
+            return (vec![], Captures::new());
         };
 
         match self {
             MapPattern::Any => {
                 // Matches any map, no captures
-                (
-                    vec![vec![haystack.clone()]],
-                    std::collections::HashMap::new(),
-                )
+                (vec![vec![haystack.clone()]], Captures::new())
             }
             MapPattern::Constraints(constraints) => {
                 // Match if all key-value constraints are satisfied
-                let mut all_captures = std::collections::HashMap::new();
-                let mut all_constraints_satisfied = true;
-
-                for (key_pattern, value_pattern) in constraints {
-                    let mut constraint_satisfied = false;
-
-                    for (key, value) in map.iter() {
-                        let (key_paths, key_captures) =
-                            key_pattern.paths_with_captures(key);
-                        let (value_paths, value_captures) =
-                            value_pattern.paths_with_captures(value);
-
-                        if !key_paths.is_empty() && !value_paths.is_empty() {
-                            constraint_satisfied = true;
-
-                            // Merge key captures
-                            for (name, capture_paths) in key_captures {
-                                let updated_paths: Vec<Path> = capture_paths
-                                    .iter()
-                                    .map(|_capture_path| {
-                                        vec![haystack.clone(), key.clone()]
-                                    })
-                                    .collect();
-                                all_captures
-                                    .entry(name)
-                                    .or_insert_with(Vec::new)
-                                    .extend(updated_paths);
-                            }
-
-                            // Merge value captures
-                            for (name, capture_paths) in value_captures {
-                                let updated_paths: Vec<Path> = capture_paths
-                                    .iter()
-                                    .map(|_capture_path| {
-                                        vec![haystack.clone(), value.clone()]
-                                    })
-                                    .collect();
-                                all_captures
-                                    .entry(name)
-                                    .or_insert_with(Vec::new)
-                                    .extend(updated_paths);
-                            }
-                            break; // Found a matching key-value pair for this constraint
-                        }
-                    }
-
-                    if !constraint_satisfied {
-                        all_constraints_satisfied = false;
-                        break;
-                    }
+                Self::constraints_paths_with_captures(
+                    constraints,
+                    haystack,
+                    map,
+                )
+            }
+            MapPattern::LengthAndConstraints(interval, constraints) => {
+                if !interval.contains(map.len()) {
+                    return (vec![], Captures::new());
                 }
-
-                if all_constraints_satisfied {
-                    (vec![vec![haystack.clone()]], all_captures)
-                } else {
-                    (vec![], all_captures)
+                Self::constraints_paths_with_captures(
+                    constraints,
+                    haystack,
+                    map,
+                )
+            }
+            MapPattern::Value(key_pattern, value_pattern) => {
+                Self::value_paths_with_captures(
+                    key_pattern,
+                    value_pattern,
+                    haystack,
+                    map,
+                )
+            }
+            MapPattern::ConstraintsAndRest(
+                interval,
+                constraints,
+                rest_pattern,
+            ) => {
+                if !interval.contains(map.len()) {
+                    return (vec![], Captures::new());
                 }
+                Self::rest_paths_with_captures(
+                    constraints,
+                    rest_pattern,
+                    haystack,
+                    map,
+                )
+            }
+            MapPattern::AllEntries(key_pattern, value_pattern) => {
+                if self.paths(haystack).is_empty() {
+                    return (vec![], Captures::new());
+                }
+                // Every entry satisfies the constraint, so every entry makes
+                // its captures
+                let mut all_captures = Captures::new();
+                for (key, value) in map.iter() {
+                    Self::collect_entry_captures(
+                        key_pattern,
+                        value_pattern,
+                        key,
+                        value,
+                        haystack,
+                        &mut all_captures,
+                    );
+                }
+                (vec![vec![haystack.clone()]], all_captures)
+            }
+            MapPattern::CountedConstraints(
+                interval,
+                constraints,
+                rest_pattern,
+            ) => {
+                if !interval.contains(map.len()) {
+                    return (vec![], Captures::new());
+                }
+                Self::counted_paths_with_captures(
+                    constraints,
+                    rest_pattern.as_deref(),
+                    haystack,
+                    map,
+                )
             }
             _ => {
                 // For other variants, fall back to basic paths without captures
-                (self.paths(haystack), std::collections::HashMap::new())
+                (self.paths(haystack), Captures::new())
             }
         }
     }
@@ -219,19 +847,116 @@ impl std::fmt::Display for MapPattern {
             MapPattern::Any => write!(f, "map"),
             MapPattern::Constraints(constraints) => {
                 write!(f, "{{")?;
-                for (i, (key_pattern, value_pattern)) in
-                    constraints.iter().enumerate()
-                {
+                write_constraints(f, constraints)?;
+                write!(f, "}}")
+            }
+            MapPattern::Length(interval) => {
+                write!(f, "{{{}}}", interval)
+            }
+            MapPattern::LengthAndConstraints(interval, constraints) => {
+                write!(f, "{{{}, ", interval)?;
+                write_constraints(f, constraints)?;
+                write!(f, "}}")
+            }
+            MapPattern::Value(key_pattern, value_pattern) => {
+                // `{0}` alone would be a map length
+                let key = key_pattern.to_string();
+                if key.bytes().all(|byte| byte.is_ascii_digit()) {
+                    write!(f, "{{({})}}: {}", key, value_pattern)
+                } else {
+                    write!(f, "{{{}}}: {}", key, value_pattern)
+                }
+            }
+            MapPattern::ConstraintsAndRest(
+                interval,
+                constraints,
+                rest_pattern,
+            ) => {
+                write!(f, "{{")?;
+                // Any length is the default, so it goes unwritten
+                if *interval != Interval::new(0..) {
+                    write!(f, "{}, ", interval)?;
+                }
+                write_constraints(f, constraints)?;
+                if !constraints.is_empty() {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}}}", rest_pattern)
+            }
+            MapPattern::AllEntries(key_pattern, value_pattern) => {
+                // A side that accepts anything goes unwritten
+                let any = Pattern::any();
+                if **key_pattern == any {
+                    write!(f, "{{values: {}}}", value_pattern)
+                } else if **value_pattern == any {
+                    write!(f, "{{keys: {}}}", key_pattern)
+                } else {
+                    write!(
+                        f,
+                        "{{keys: {}, values: {}}}",
+                        key_pattern, value_pattern
+                    )
+                }
+            }
+            MapPattern::CountedConstraints(
+                interval,
+                constraints,
+                rest_pattern,
+            ) => {
+                write!(f, "{{")?;
+                if *interval != Interval::new(0..) {
+                    write!(f, "{}, ", interval)?;
+                }
+                for (i, constraint) in constraints.iter().enumerate() {
                     if i > 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "{}: {}", key_pattern, value_pattern)?;
+                    write!(f, "{}", constraint)?;
+                }
+                if let Some(rest_pattern) = rest_pattern {
+                    write!(f, ", {}", rest_pattern)?;
                 }
                 write!(f, "}}")
             }
-            MapPattern::Length(interval) => {
-                write!(f, "{{{}}}", interval)
-            }
         }
     }
 }
+
+/// Returns true if `pattern` is written ending in the value pattern of
+/// `{key}: value`, which takes in any `&` or `|` that follows it, so that an
+/// operand written before another must be parenthesized.
+pub(crate) fn ends_with_map_value(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Structure(StructurePattern::Map(MapPattern::Value(..))) => {
+            true
+        }
+        Pattern::Meta(MetaPattern::Not(not)) => {
+            !not.pattern().is_complex() && ends_with_map_value(not.pattern())
+        }
+        // An `|` inside `&` is parenthesized
+        Pattern::Meta(MetaPattern::And(and)) => {
+            and.patterns().last().is_some_and(|last| {
+                !matches!(last, Pattern::Meta(MetaPattern::Or(_)))
+                    && ends_with_map_value(last)
+            })
+        }
+        Pattern::Meta(MetaPattern::Or(or)) => {
+            or.patterns().last().is_some_and(ends_with_map_value)
+        }
+        _ => false,
+    }
+}
+
+/// Writes key-value constraints separated by commas.
+fn write_constraints(
+    f: &mut std::fmt::Formatter<'_>,
+    constraints: &[(Pattern, Pattern)],
+) -> std::fmt::Result {
+    for (i, (key_pattern, value_pattern)) in constraints.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}: {}", key_pattern, value_pattern)?;
+    }
+    Ok(())
+}
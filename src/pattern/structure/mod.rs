@@ -3,13 +3,16 @@ mod map_pattern;
 mod tagged_pattern;
 
 pub use array_pattern::ArrayPattern;
+pub(crate) use array_pattern::is_single_element;
 use dcbor::prelude::*;
+pub(crate) use map_pattern::ends_with_map_value;
 pub use map_pattern::*;
 pub use tagged_pattern::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{Captures, Matcher, Path, Pattern, vm::Instr};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StructurePattern {
     Array(ArrayPattern),
     Map(MapPattern),
@@ -58,10 +61,7 @@ impl Matcher for StructurePattern {
         }
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         match self {
             StructurePattern::Array(pattern) => {
                 pattern.paths_with_captures(haystack)
@@ -1,15 +1,27 @@
+use std::{collections::BTreeSet, ops::RangeInclusive};
+
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{Captures, Matcher, Path, Pattern, vm::Instr};
 
 /// Pattern for matching CBOR tagged value structures.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TaggedPattern {
     /// Matches any tagged value.
     Any,
+    /// Matches tagged values with any tag AND content that matches the
+    /// pattern.
+    AnyTag {
+        pattern: Box<Pattern>,
+    },
     /// Matches tagged values with specific tag AND content that matches the
     /// pattern.
     Tag {
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::tag")
+        )]
         tag: Tag,
         pattern: Box<Pattern>,
     },
@@ -20,15 +32,35 @@ pub enum TaggedPattern {
     /// Matches tagged values with a tag name that matches the given regex AND
     /// content that matches the pattern.
     Regex {
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::regex")
+        )]
         regex: regex::Regex,
         pattern: Box<Pattern>,
     },
+    /// Matches tagged values whose tag number is within the inclusive range
+    /// AND content that matches the pattern.
+    Range {
+        range: RangeInclusive<u64>,
+        pattern: Box<Pattern>,
+    },
+    /// Matches tagged values whose tag number is one of the given numbers AND
+    /// content that matches the pattern.
+    Set {
+        tags: BTreeSet<u64>,
+        pattern: Box<Pattern>,
+    },
 }
 
 impl PartialEq for TaggedPattern {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (TaggedPattern::Any, TaggedPattern::Any) => true,
+            (
+                TaggedPattern::AnyTag { pattern: content_a },
+                TaggedPattern::AnyTag { pattern: content_b },
+            ) => content_a == content_b,
             (
                 TaggedPattern::Tag { tag: tag_a, pattern: content_a },
                 TaggedPattern::Tag { tag: tag_b, pattern: content_b },
@@ -41,6 +73,14 @@ impl PartialEq for TaggedPattern {
                 TaggedPattern::Regex { regex: regex_a, pattern: content_a },
                 TaggedPattern::Regex { regex: regex_b, pattern: content_b },
             ) => regex_a.as_str() == regex_b.as_str() && content_a == content_b,
+            (
+                TaggedPattern::Range { range: range_a, pattern: content_a },
+                TaggedPattern::Range { range: range_b, pattern: content_b },
+            ) => range_a == range_b && content_a == content_b,
+            (
+                TaggedPattern::Set { tags: tags_a, pattern: content_a },
+                TaggedPattern::Set { tags: tags_b, pattern: content_b },
+            ) => tags_a == tags_b && content_a == content_b,
             _ => false,
         }
     }
@@ -52,6 +92,12 @@ impl TaggedPattern {
     /// Creates a new `TaggedPattern` that matches any tagged value.
     pub fn any() -> Self { TaggedPattern::Any }
 
+    /// Creates a new `TaggedPattern` that matches tagged values with any tag
+    /// AND content that matches the pattern.
+    pub fn any_with_content(pattern: Pattern) -> Self {
+        TaggedPattern::AnyTag { pattern: Box::new(pattern) }
+    }
+
     /// Creates a new `TaggedPattern` that matches tagged values with specific
     /// tag AND content that matches the pattern.
     pub fn with_tag(tag: impl Into<Tag>, pattern: Pattern) -> Self {
@@ -69,6 +115,54 @@ impl TaggedPattern {
     pub fn with_regex(tag_regex: regex::Regex, pattern: Pattern) -> Self {
         TaggedPattern::Regex { regex: tag_regex, pattern: Box::new(pattern) }
     }
+
+    /// Creates a new `TaggedPattern` that matches tagged values whose tag
+    /// number is within the inclusive range AND content that matches the
+    /// pattern.
+    pub fn with_tag_range(
+        range: RangeInclusive<u64>,
+        pattern: Pattern,
+    ) -> Self {
+        TaggedPattern::Range { range, pattern: Box::new(pattern) }
+    }
+
+    /// Creates a new `TaggedPattern` that matches tagged values whose tag
+    /// number is one of `tags` AND content that matches the pattern.
+    pub fn with_tag_set(
+        tags: impl IntoIterator<Item = u64>,
+        pattern: Pattern,
+    ) -> Self {
+        TaggedPattern::Set {
+            tags: tags.into_iter().collect(),
+            pattern: Box::new(pattern),
+        }
+    }
+
+    /// Returns the pattern the content must match if `tag` satisfies this
+    /// pattern's tag constraint, or `None` if it doesn't or this is `Any`.
+    pub(crate) fn content_pattern(&self, tag: &Tag) -> Option<&Pattern> {
+        match self {
+            TaggedPattern::Any => None,
+            TaggedPattern::AnyTag { pattern } => Some(pattern),
+            TaggedPattern::Tag { tag: target_tag, pattern } => {
+                (tag == target_tag).then_some(pattern)
+            }
+            TaggedPattern::Name { name: tag_name, pattern } => tag
+                .name()
+                .is_some_and(|name| name.as_str() == tag_name)
+                .then_some(pattern),
+            TaggedPattern::Regex { regex: tag_regex, pattern } => tag
+                .name()
+                .is_some_and(|name| tag_regex.is_match(name.as_str()))
+                .then_some(pattern),
+            TaggedPattern::Range { range, pattern } => {
+                range.contains(&tag.value()).then_some(pattern)
+            }
+            TaggedPattern::Set { tags, pattern } => {
+                tags.contains(&tag.value()).then_some(pattern)
+            }
+        }
+    }
 }
 
 impl Matcher for TaggedPattern {
@@ -76,45 +170,19 @@ impl Matcher for TaggedPattern {
         // First check if this is a tagged value
         match haystack.as_case() {
             CBORCase::Tagged(tag, content) => {
-                match self {
-                    TaggedPattern::Any => {
-                        // Match any tagged value - return the tagged value
-                        // itself
-                        vec![vec![haystack.clone()]]
-                    }
-                    TaggedPattern::Tag { tag: target_tag, pattern } => {
-                        if tag == target_tag && pattern.matches(content) {
-                            vec![vec![haystack.clone()]]
-                        } else {
-                            vec![]
-                        }
-                    }
-                    TaggedPattern::Name { name: tag_name, pattern } => {
-                        if let Some(name) = tag.name() {
-                            if name.as_str() == tag_name
-                                && pattern.matches(content)
-                            {
-                                vec![vec![haystack.clone()]]
-                            } else {
-                                vec![]
-                            }
-                        } else {
-                            vec![]
-                        }
-                    }
-                    TaggedPattern::Regex { regex: tag_regex, pattern } => {
-                        if let Some(name) = tag.name() {
-                            if tag_regex.is_match(name.as_str())
-                                && pattern.matches(content)
-                            {
-                                vec![vec![haystack.clone()]]
-                            } else {
-                                vec![]
-                            }
-                        } else {
-                            vec![]
-                        }
-                    }
+                if let TaggedPattern::Any = self {
+                    // Match any tagged value - return the tagged value itself
+                    return vec![vec![haystack.clone()]];
+                }
+                // Each path continues from the tagged value through the
+                // content's path
+                match self.content_pattern(tag) {
+                    Some(pattern) => pattern
+                        .paths(content)
+                        .into_iter()
+                        .map(|path| through(haystack, path))
+                        .collect(),
+                    None => vec![],
                 }
             }
             _ => {
@@ -153,82 +221,70 @@ impl Matcher for TaggedPattern {
                 // Collect captures from the content pattern
                 pattern.collect_capture_names(names);
             }
-            TaggedPattern::Regex { pattern, .. } => {
+            TaggedPattern::AnyTag { pattern }
+            | TaggedPattern::Regex { pattern, .. }
+            | TaggedPattern::Range { pattern, .. }
+            | TaggedPattern::Set { pattern, .. } => {
                 // Collect captures from the content pattern
                 pattern.collect_capture_names(names);
             }
         }
     }
 
-    fn paths_with_captures(
-        &self,
-        haystack: &CBOR,
-    ) -> (Vec<Path>, std::collections::HashMap<String, Vec<Path>>) {
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
         // Check if this CBOR value is a tagged value
-        let CBORCase::Tagged(tag_value, content) = haystack.as_case() else {
-            return (vec![], std::collections::HashMap::new());
+        let CBORCase::Tagged(tag, content) = haystack.as_case() else {
+            return (vec![], Captures::new());
         };
 
-        match self {
-            TaggedPattern::Any => {
-                // Matches any tagged value, no captures
-                (
-                    vec![vec![haystack.clone()]],
-                    std::collections::HashMap::new(),
-                )
-            }
-            TaggedPattern::Tag { tag: expected_tag, pattern } => {
-                if *tag_value == *expected_tag {
-                    // Match specific tag and check content with potential
-                    // captures
-                    let (content_paths, captures) =
-                        pattern.paths_with_captures(content);
-                    if !content_paths.is_empty() {
-                        // Build paths that include the tagged value as root
-                        let tagged_paths: Vec<Path> = content_paths
-                            .iter()
-                            .map(|content_path| {
-                                let mut path = vec![haystack.clone()];
-                                path.extend_from_slice(&content_path[1..]); // Skip the content's root
-                                path
-                            })
-                            .collect();
-
-                        // Update captures to include tagged value as root
-                        let mut updated_captures =
-                            std::collections::HashMap::new();
-                        for (name, capture_paths) in captures {
-                            let updated_paths: Vec<Path> = capture_paths
-                                .iter()
-                                .map(|_capture_path| {
-                                    // For tagged patterns, the capture path
-                                    // should be [tagged_value, content]
-                                    vec![haystack.clone(), content.clone()]
-                                })
-                                .collect();
-                            updated_captures.insert(name, updated_paths);
-                        }
-
-                        (tagged_paths, updated_captures)
-                    } else {
-                        (vec![], captures)
-                    }
-                } else {
-                    (vec![], std::collections::HashMap::new())
-                }
-            }
-            _ => {
-                // For other variants, fall back to basic paths without captures
-                (self.paths(haystack), std::collections::HashMap::new())
-            }
+        if let TaggedPattern::Any = self {
+            // Matches any tagged value, no captures
+            return (vec![vec![haystack.clone()]], Captures::new());
+        }
+        let Some(pattern) = self.content_pattern(tag) else {
+            return (vec![], Captures::new());
+        };
+
+        let (content_paths, content_captures) =
+            pattern.paths_with_captures(content);
+        if content_paths.is_empty() {
+            return (vec![], Captures::new());
         }
+
+        // Like `paths`, the paths and captures continue from the tagged
+        // value through the content
+        let captures = content_captures
+            .into_iter()
+            .map(|(name, capture_paths)| {
+                let capture_paths = capture_paths
+                    .into_iter()
+                    .map(|capture_path| through(haystack, capture_path))
+                    .collect();
+                (name, capture_paths)
+            })
+            .collect();
+        let paths = content_paths
+            .into_iter()
+            .map(|path| through(haystack, path))
+            .collect();
+        (paths, captures)
     }
 }
 
+/// Prefixes `path`, which starts at the content of `tagged`, with `tagged`.
+fn through(tagged: &CBOR, path: Path) -> Path {
+    let mut full_path = vec![tagged.clone()];
+    full_path.extend(path);
+    full_path
+}
+
 impl std::fmt::Display for TaggedPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TaggedPattern::Any => write!(f, "tagged"),
+            TaggedPattern::AnyTag { pattern } => {
+                write!(f, "tagged(*, {})", pattern)
+            }
             TaggedPattern::Tag { tag, pattern } => {
                 write!(f, "tagged({}, {})", tag.value(), pattern)
             }
@@ -238,6 +294,20 @@ impl std::fmt::Display for TaggedPattern {
             TaggedPattern::Regex { regex, pattern } => {
                 write!(f, "tagged(/{}/,  {})", regex.as_str(), pattern)
             }
+            TaggedPattern::Range { range, pattern } => {
+                write!(
+                    f,
+                    "tagged({}...{}, {})",
+                    range.start(),
+                    range.end(),
+                    pattern
+                )
+            }
+            TaggedPattern::Set { tags, pattern } => {
+                let tags: Vec<String> =
+                    tags.iter().map(|tag| tag.to_string()).collect();
+                write!(f, "tagged({}, {})", tags.join(" | "), pattern)
+            }
         }
     }
 }
@@ -0,0 +1,422 @@
+use dcbor::prelude::*;
+
+use crate::{
+    Interval,
+    pattern::{
+        ArrayPattern, BoolPattern, ByteStringPattern, DigestPattern,
+        KnownValuePattern, MapConstraint, MapPattern, Matcher, MetaPattern,
+        NumberPattern, Path, Pattern, StructurePattern, TaggedPattern,
+        TextPattern, ValuePattern, explain::explain_at,
+        structure::is_single_element,
+    },
+};
+
+/// The kind of constraint a [`Violation`] reports as broken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// A map has no entry whose key matches the pattern.
+    MissingEntry,
+    /// A value is not of the type the pattern requires, such as text where
+    /// a number is required, or a tagged value with the wrong tag.
+    WrongType,
+    /// A value is of the right type, but not one the pattern accepts.
+    WrongValue,
+    /// An array or map has a length outside the range the pattern allows.
+    LengthOutOfRange,
+    /// An array has more or fewer elements than its element patterns, or a
+    /// map more or fewer entries satisfying a counted constraint than it
+    /// allows.
+    WrongArity,
+    /// None of the alternatives of an `|` pattern matched.
+    NoAlternativeMatched,
+    /// The pattern failed to match in a way not described by the other
+    /// kinds.
+    Other,
+}
+
+impl std::fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            ViolationKind::MissingEntry => "missing entry",
+            ViolationKind::WrongType => "wrong type",
+            ViolationKind::WrongValue => "wrong value",
+            ViolationKind::LengthOutOfRange => "length out of range",
+            ViolationKind::WrongArity => "wrong number of elements",
+            ViolationKind::NoAlternativeMatched => "no alternative matched",
+            ViolationKind::Other => "no match",
+        };
+        f.write_str(description)
+    }
+}
+
+/// A constraint that a value failed to satisfy, found by
+/// [`Pattern::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    path: Path,
+    pattern: Pattern,
+    kind: ViolationKind,
+}
+
+impl Violation {
+    /// Returns the path from the haystack root to the node that broke the
+    /// constraint.
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Returns the CBOR node that broke the constraint.
+    pub fn node(&self) -> &CBOR {
+        self.path.last().expect("violation paths are never empty")
+    }
+
+    /// Returns the sub-pattern that the node failed to match. For a
+    /// [`ViolationKind::MissingEntry`], this is the key pattern no entry
+    /// matched.
+    pub fn pattern(&self) -> &Pattern { &self.pattern }
+
+    /// Returns the kind of constraint broken.
+    pub fn kind(&self) -> ViolationKind { self.kind }
+
+    /// Returns the depth of the node, where the root is at depth 0.
+    pub fn depth(&self) -> usize { self.path.len() - 1 }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} did not match {} at depth {}",
+            self.kind,
+            self.pattern,
+            self.node().diagnostic_flat(),
+            self.depth()
+        )
+    }
+}
+
+impl Pattern {
+    /// Matches this pattern against `haystack` as a schema, returning every
+    /// constraint it breaks, or an empty list if it matches.
+    ///
+    /// Unlike [`Pattern::explain`], which stops at the first failure,
+    /// validation carries on past it: every constraint of a `{key: value}`
+    /// map pattern, every element of an array with a fixed number of
+    /// elements, and every operand of `&` is checked. Arrays with repeats
+    /// and patterns that validation does not look inside, such as `!` and
+    /// `search`, report a single violation where [`Pattern::explain`]
+    /// locates the failure.
+    pub fn validate(&self, haystack: &CBOR) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        validate_at(self, vec![haystack.clone()], &mut violations);
+        violations
+    }
+}
+
+/// Adds the violations of `pattern` against the last node of `path` to
+/// `violations`.
+fn validate_at(pattern: &Pattern, path: Path, violations: &mut Vec<Violation>) {
+    let node = path.last().expect("validated paths are never empty");
+    if pattern.matches(node) {
+        return;
+    }
+    let found = violations.len();
+    match pattern {
+        Pattern::Value(value) => {
+            let kind = if value_type_matches(value, node) {
+                ViolationKind::WrongValue
+            } else {
+                ViolationKind::WrongType
+            };
+            violations.push(violation(pattern, &path, kind));
+        }
+        Pattern::Structure(StructurePattern::Array(array)) => {
+            validate_array(pattern, array, &path, violations);
+        }
+        Pattern::Structure(StructurePattern::Map(map)) => {
+            validate_map(pattern, map, &path, violations);
+        }
+        Pattern::Structure(StructurePattern::Tagged(tagged)) => {
+            validate_tagged(pattern, tagged, &path, violations);
+        }
+        Pattern::Meta(MetaPattern::And(and)) => {
+            for pattern in and.patterns() {
+                validate_at(pattern, path.clone(), violations);
+            }
+        }
+        Pattern::Meta(MetaPattern::Or(_)) => {
+            violations.push(violation(
+                pattern,
+                &path,
+                ViolationKind::NoAlternativeMatched,
+            ));
+        }
+        Pattern::Meta(MetaPattern::Capture(capture)) => {
+            validate_at(capture.pattern(), path.clone(), violations);
+        }
+        _ => {}
+    }
+
+    // Fall back to a single violation where the explanation puts the
+    // failure, for patterns not looked inside or that failed as a whole
+    if violations.len() == found {
+        let failure = explain_at(pattern, path.clone());
+        let (pattern, path) = match &failure {
+            Some(failure) => (failure.pattern(), failure.path()),
+            None => (pattern, &path),
+        };
+        violations.push(violation(pattern, path, ViolationKind::Other));
+    }
+}
+
+fn violation(pattern: &Pattern, path: &Path, kind: ViolationKind) -> Violation {
+    Violation { path: path.clone(), pattern: pattern.clone(), kind }
+}
+
+fn child_path(path: &Path, child: &CBOR) -> Path {
+    let mut child_path = path.clone();
+    child_path.push(child.clone());
+    child_path
+}
+
+/// Returns `true` if `node` is of the type `value` matches, whether or not
+/// `value` accepts it.
+fn value_type_matches(value: &ValuePattern, node: &CBOR) -> bool {
+    match value {
+        ValuePattern::Bool(_) => BoolPattern::any().matches(node),
+        ValuePattern::ByteString(_) => ByteStringPattern::any().matches(node),
+        ValuePattern::Date(date) => date.date_of(node).is_some(),
+        ValuePattern::Digest(_) => DigestPattern::any().matches(node),
+        ValuePattern::KnownValue(_) => KnownValuePattern::any().matches(node),
+        ValuePattern::Number(_) => NumberPattern::any().matches(node),
+        ValuePattern::Text(_) => TextPattern::any().matches(node),
+        // Null is a type with a single value
+        ValuePattern::Null(_) => false,
+        // These accept values of any type
+        ValuePattern::Cbor(_) | ValuePattern::EncodedDigest(_) => true,
+    }
+}
+
+fn validate_array(
+    pattern: &Pattern,
+    array: &ArrayPattern,
+    path: &Path,
+    violations: &mut Vec<Violation>,
+) {
+    let CBORCase::Array(arr) = path.last().unwrap().as_case() else {
+        violations.push(violation(pattern, path, ViolationKind::WrongType));
+        return;
+    };
+    let elements = match array {
+        ArrayPattern::Any => return,
+        ArrayPattern::Length(_) => {
+            violations.push(violation(
+                pattern,
+                path,
+                ViolationKind::LengthOutOfRange,
+            ));
+            return;
+        }
+        ArrayPattern::LengthAndElements(interval, elements) => {
+            if !interval.contains(arr.len()) {
+                let length = Pattern::Structure(StructurePattern::Array(
+                    ArrayPattern::Length(*interval),
+                ));
+                violations.push(violation(
+                    &length,
+                    path,
+                    ViolationKind::LengthOutOfRange,
+                ));
+            }
+            elements
+        }
+        ArrayPattern::Elements(elements) => elements,
+        // Pairing elements with patterns in any order is left to the fallback
+        ArrayPattern::Unordered(patterns) => {
+            if patterns.len() != arr.len() {
+                violations.push(violation(
+                    pattern,
+                    path,
+                    ViolationKind::WrongArity,
+                ));
+            }
+            return;
+        }
+    };
+    let patterns = match elements.as_ref() {
+        Pattern::Meta(MetaPattern::Sequence(seq)) => seq.patterns(),
+        single => std::slice::from_ref(single),
+    };
+
+    // Only a sequence of single elements pairs patterns with elements one
+    // to one; repeats are left to the fallback
+    if !patterns.iter().all(is_single_element) {
+        return;
+    }
+    if patterns.len() != arr.len() {
+        violations.push(violation(pattern, path, ViolationKind::WrongArity));
+        return;
+    }
+    for (element_pattern, element) in patterns.iter().zip(arr) {
+        validate_at(element_pattern, child_path(path, element), violations);
+    }
+}
+
+fn validate_map(
+    pattern: &Pattern,
+    map: &MapPattern,
+    path: &Path,
+    violations: &mut Vec<Violation>,
+) {
+    let CBORCase::Map(m) = path.last().unwrap().as_case() else {
+        violations.push(violation(pattern, path, ViolationKind::WrongType));
+        return;
+    };
+    let constraints = match map {
+        MapPattern::Any => return,
+        MapPattern::Length(_) => {
+            violations.push(violation(
+                pattern,
+                path,
+                ViolationKind::LengthOutOfRange,
+            ));
+            return;
+        }
+        MapPattern::LengthAndConstraints(interval, constraints)
+        | MapPattern::ConstraintsAndRest(interval, constraints, _) => {
+            if !interval.contains(m.len()) {
+                let length = Pattern::Structure(StructurePattern::Map(
+                    MapPattern::Length(*interval),
+                ));
+                violations.push(violation(
+                    &length,
+                    path,
+                    ViolationKind::LengthOutOfRange,
+                ));
+            }
+            constraints
+        }
+        MapPattern::Constraints(constraints) => constraints,
+        // A single entry continuing the path into its value
+        MapPattern::Value(..) => return,
+        MapPattern::CountedConstraints(interval, constraints, _) => {
+            if !interval.contains(m.len()) {
+                let length = Pattern::Structure(StructurePattern::Map(
+                    MapPattern::Length(*interval),
+                ));
+                violations.push(violation(
+                    &length,
+                    path,
+                    ViolationKind::LengthOutOfRange,
+                ));
+            }
+            validate_counted(constraints, m, path, violations);
+            return;
+        }
+        MapPattern::AllEntries(key_pattern, value_pattern) => {
+            for (key, value) in m.iter() {
+                validate_at(key_pattern, child_path(path, key), violations);
+                validate_at(value_pattern, child_path(path, value), violations);
+            }
+            return;
+        }
+    };
+
+    for (key_pattern, value_pattern) in constraints {
+        if m.iter().any(|(key, value)| {
+            key_pattern.matches(key) && value_pattern.matches(value)
+        }) {
+            continue;
+        }
+        validate_missing_entry(key_pattern, value_pattern, m, path, violations);
+    }
+}
+
+/// Adds the violations of a map with no entry satisfying a constraint: the
+/// key pattern if no key matches it, or else the failures of the values of
+/// the entries whose keys do.
+fn validate_missing_entry(
+    key_pattern: &Pattern,
+    value_pattern: &Pattern,
+    m: &Map,
+    path: &Path,
+    violations: &mut Vec<Violation>,
+) {
+    let mut keyed = m.iter().filter(|(key, _)| key_pattern.matches(key));
+    match keyed.next() {
+        None => violations.push(violation(
+            key_pattern,
+            path,
+            ViolationKind::MissingEntry,
+        )),
+        // Validate the value of each entry with a matching key
+        Some(first) => {
+            for (_, value) in std::iter::once(first).chain(keyed) {
+                validate_at(value_pattern, child_path(path, value), violations);
+            }
+        }
+    }
+}
+
+/// Adds the violations of counted map constraints: a constraint no entry
+/// satisfies is reported as for any other map, and one satisfied by too few
+/// or too many entries as a whole.
+fn validate_counted(
+    constraints: &[MapConstraint],
+    m: &Map,
+    path: &Path,
+    violations: &mut Vec<Violation>,
+) {
+    for constraint in constraints {
+        let count = m
+            .iter()
+            .filter(|(key, value)| {
+                constraint.key().matches(key)
+                    && constraint.value().matches(value)
+            })
+            .count();
+        if constraint.count().contains(count) {
+            continue;
+        }
+        if count == 0 {
+            validate_missing_entry(
+                constraint.key(),
+                constraint.value(),
+                m,
+                path,
+                violations,
+            );
+        } else {
+            let counted = Pattern::Structure(StructurePattern::Map(
+                MapPattern::CountedConstraints(
+                    Interval::new(0..),
+                    vec![constraint.clone()],
+                    None,
+                ),
+            ));
+            violations.push(violation(
+                &counted,
+                path,
+                ViolationKind::WrongArity,
+            ));
+        }
+    }
+}
+
+fn validate_tagged(
+    pattern: &Pattern,
+    tagged: &TaggedPattern,
+    path: &Path,
+    violations: &mut Vec<Violation>,
+) {
+    let CBORCase::Tagged(tag, content) = path.last().unwrap().as_case() else {
+        violations.push(violation(pattern, path, ViolationKind::WrongType));
+        return;
+    };
+    match tagged.content_pattern(tag) {
+        Some(content_pattern) => {
+            validate_at(content_pattern, child_path(path, content), violations)
+        }
+        None => {
+            violations.push(violation(pattern, path, ViolationKind::WrongType))
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
 
 /// Pattern for matching boolean values in dCBOR.
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BoolPattern {
     /// Matches any boolean value.
     Any,
@@ -1,16 +1,61 @@
+use std::collections::BTreeSet;
+
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use super::{ValuePattern, compile_with_groups, group_captures};
+use crate::{
+    Interval,
+    pattern::{
+        Captures, Matcher, Path, Pattern,
+        vm::{Instr, capture_slots},
+    },
+};
 
 /// Pattern for matching byte string values in dCBOR.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ByteStringPattern {
     /// Matches any byte string.
     Any,
     /// Matches the specific byte string.
-    Value(Vec<u8>),
+    Value(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::hex_bytes")
+        )]
+        Vec<u8>,
+    ),
     /// Matches the binary regex for a byte string.
-    BinaryRegex(regex::bytes::Regex),
+    BinaryRegex(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::bytes_regex")
+        )]
+        regex::bytes::Regex,
+    ),
+    /// Matches byte strings whose length is within the interval.
+    Length(Interval),
+    /// Matches byte strings that start with the given bytes.
+    Prefix(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::hex_bytes")
+        )]
+        Vec<u8>,
+    ),
+    /// Matches byte strings equal to any of the given values.
+    Set(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::hex_bytes_set")
+        )]
+        BTreeSet<Vec<u8>>,
+    ),
+    /// Matches byte strings holding the encoding of a dCBOR item the pattern
+    /// matches. The paths of the pattern in the decoded item continue on
+    /// from the byte string, so the decoded item follows the byte string in
+    /// each path although it isn't a child of it.
+    Decoded(Box<Pattern>),
 }
 
 impl PartialEq for ByteStringPattern {
@@ -24,6 +69,16 @@ impl PartialEq for ByteStringPattern {
                 ByteStringPattern::BinaryRegex(a),
                 ByteStringPattern::BinaryRegex(b),
             ) => a.as_str() == b.as_str(),
+            (ByteStringPattern::Length(a), ByteStringPattern::Length(b)) => {
+                a == b
+            }
+            (ByteStringPattern::Prefix(a), ByteStringPattern::Prefix(b)) => {
+                a == b
+            }
+            (ByteStringPattern::Set(a), ByteStringPattern::Set(b)) => a == b,
+            (ByteStringPattern::Decoded(a), ByteStringPattern::Decoded(b)) => {
+                a == b
+            }
             _ => false,
         }
     }
@@ -46,6 +101,24 @@ impl std::hash::Hash for ByteStringPattern {
                 // Regex does not implement Hash, so we hash its pattern string.
                 regex.as_str().hash(state);
             }
+            ByteStringPattern::Length(interval) => {
+                3u8.hash(state);
+                interval.hash(state);
+            }
+            ByteStringPattern::Prefix(prefix) => {
+                4u8.hash(state);
+                prefix.hash(state);
+            }
+            ByteStringPattern::Set(values) => {
+                5u8.hash(state);
+                values.hash(state);
+            }
+            ByteStringPattern::Decoded(pattern) => {
+                6u8.hash(state);
+                // Pattern does not implement Hash, so we hash its text, which
+                // is the same for equal patterns.
+                pattern.to_string().hash(state);
+            }
         }
     }
 }
@@ -64,15 +137,59 @@ impl ByteStringPattern {
     pub fn regex(regex: regex::bytes::Regex) -> Self {
         ByteStringPattern::BinaryRegex(regex)
     }
+
+    /// Creates a new `ByteStringPattern` that matches byte strings whose
+    /// length in bytes is within the given interval.
+    pub fn length(interval: impl Into<Interval>) -> Self {
+        ByteStringPattern::Length(interval.into())
+    }
+
+    /// Creates a new `ByteStringPattern` that matches byte strings starting
+    /// with the given bytes.
+    pub fn prefix(prefix: impl AsRef<[u8]>) -> Self {
+        ByteStringPattern::Prefix(prefix.as_ref().to_vec())
+    }
+
+    /// Creates a new `ByteStringPattern` that matches byte strings equal to
+    /// any of the given values.
+    pub fn set(values: impl IntoIterator<Item = impl AsRef<[u8]>>) -> Self {
+        ByteStringPattern::Set(
+            values
+                .into_iter()
+                .map(|value| value.as_ref().to_vec())
+                .collect(),
+        )
+    }
+
+    /// Creates a new `ByteStringPattern` that matches byte strings holding
+    /// the encoding of a dCBOR item that `pattern` matches.
+    pub fn decoded(pattern: Pattern) -> Self {
+        ByteStringPattern::Decoded(Box::new(pattern))
+    }
+
+    /// Returns the dCBOR item encoded in `bytes`, or `None` if they aren't a
+    /// well-formed deterministic encoding.
+    fn decode(bytes: &[u8]) -> Option<CBOR> { CBOR::try_from_data(bytes).ok() }
 }
 
 impl Matcher for ByteStringPattern {
     fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        if let ByteStringPattern::Decoded(_) = self {
+            return self.paths_with_captures(haystack).0;
+        }
         let is_hit =
             haystack.as_byte_string().is_some_and(|bytes| match self {
                 ByteStringPattern::Any => true,
                 ByteStringPattern::Value(want) => bytes == want,
                 ByteStringPattern::BinaryRegex(regex) => regex.is_match(bytes),
+                ByteStringPattern::Length(interval) => {
+                    interval.contains(bytes.len())
+                }
+                ByteStringPattern::Prefix(prefix) => bytes.starts_with(prefix),
+                ByteStringPattern::Set(values) => values.contains(bytes),
+                ByteStringPattern::Decoded(_) => {
+                    unreachable!("decoded patterns are matched above")
+                }
             });
 
         if is_hit {
@@ -82,17 +199,85 @@ impl Matcher for ByteStringPattern {
         }
     }
 
+    /// A regex's named groups are captures, each captured as the bytes it
+    /// matched, following the path to the byte string value.
+    ///
+    /// The paths and captures of a decoded pattern run from the byte string
+    /// through the decoded item.
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        if let ByteStringPattern::Decoded(pattern) = self {
+            let Some(decoded) =
+                haystack.as_byte_string().and_then(Self::decode)
+            else {
+                return (Vec::new(), Captures::new());
+            };
+            let (paths, captures) = pattern.paths_with_captures(&decoded);
+            let with_prefix = |path: Path| {
+                let mut full_path = vec![haystack.clone()];
+                full_path.extend(path);
+                full_path
+            };
+            let paths = paths.into_iter().map(with_prefix).collect();
+            let captures = captures
+                .into_iter()
+                .map(|(name, capture_paths)| {
+                    (name, capture_paths.into_iter().map(with_prefix).collect())
+                })
+                .collect();
+            return (paths, captures);
+        }
+        let paths = self.paths(haystack);
+        let captures = match (self, haystack.as_byte_string()) {
+            (ByteStringPattern::BinaryRegex(regex), Some(bytes))
+                if !paths.is_empty() =>
+            {
+                let groups = regex.captures(bytes);
+                group_captures(
+                    haystack,
+                    regex.capture_names().flatten().map(|name| {
+                        let group = groups.as_ref().and_then(|g| g.name(name));
+                        (
+                            name,
+                            group.map(|group| {
+                                CBOR::to_byte_string(group.as_bytes())
+                            }),
+                        )
+                    }),
+                )
+            }
+            _ => Captures::new(),
+        };
+        (paths, captures)
+    }
+
     fn compile(
         &self,
         code: &mut Vec<Instr>,
         literals: &mut Vec<Pattern>,
-        _captures: &mut Vec<String>,
+        captures: &mut Vec<String>,
     ) {
-        let idx = literals.len();
-        literals.push(Pattern::Value(
-            crate::pattern::ValuePattern::ByteString(self.clone()),
-        ));
-        code.push(Instr::MatchPredicate(idx));
+        let pattern = ValuePattern::ByteString(self.clone());
+        if let ByteStringPattern::Decoded(_) = self {
+            // Paths continue into the decoded item, which only a structure
+            // match follows
+            let pattern = Pattern::Value(pattern);
+            capture_slots(&pattern, captures);
+            code.push(Instr::MatchStructure(literals.len()));
+            literals.push(pattern);
+        } else {
+            compile_with_groups(pattern, code, literals, captures);
+        }
+    }
+
+    fn collect_capture_names(&self, names: &mut Vec<String>) {
+        match self {
+            ByteStringPattern::BinaryRegex(regex) => names
+                .extend(regex.capture_names().flatten().map(str::to_string)),
+            ByteStringPattern::Decoded(pattern) => {
+                pattern.collect_capture_names(names)
+            }
+            _ => {}
+        }
     }
 }
 
@@ -106,6 +291,22 @@ impl std::fmt::Display for ByteStringPattern {
             ByteStringPattern::BinaryRegex(regex) => {
                 write!(f, "h'/{}/'", regex.as_str())
             }
+            ByteStringPattern::Length(interval) => {
+                write!(f, "bstr({})", interval)
+            }
+            ByteStringPattern::Prefix(prefix) => {
+                write!(f, "bstr(prefix: h'{}')", hex::encode(prefix))
+            }
+            ByteStringPattern::Set(values) => {
+                let values: Vec<_> = values
+                    .iter()
+                    .map(|value| format!("h'{}'", hex::encode(value)))
+                    .collect();
+                write!(f, "bstr({})", values.join(", "))
+            }
+            ByteStringPattern::Decoded(pattern) => {
+                write!(f, "bstr(cbor: {})", pattern)
+            }
         }
     }
 }
@@ -174,6 +375,34 @@ mod tests {
         assert!(!alpha_pattern.matches(&text_cbor));
     }
 
+    #[test]
+    fn test_byte_string_pattern_length_and_prefix() {
+        let empty = CBOR::to_byte_string(Vec::<u8>::new());
+        let hash = CBOR::to_byte_string([0xab; 32]);
+        let long = CBOR::to_byte_string([0xab; 33]);
+
+        let exact = ByteStringPattern::length(Interval::new(32..=32));
+        assert!(exact.matches(&hash));
+        assert!(!exact.matches(&long));
+        assert!(!exact.matches(&empty));
+        assert!(!exact.matches(&"x".repeat(32).to_cbor()));
+
+        let bounded = ByteStringPattern::length(Interval::new(0..=32));
+        assert!(bounded.matches(&empty));
+        assert!(bounded.matches(&hash));
+        assert!(!bounded.matches(&long));
+
+        let prefix = ByteStringPattern::prefix([0xab, 0xab]);
+        assert!(prefix.matches(&hash));
+        assert!(!prefix.matches(&empty));
+        assert!(ByteStringPattern::prefix([]).matches(&empty));
+
+        assert_eq!(exact.to_string(), "bstr({32})");
+        assert_eq!(bounded.to_string(), "bstr({,32})");
+        assert_eq!(prefix.to_string(), "bstr(prefix: h'abab')");
+        assert_ne!(prefix, ByteStringPattern::value([0xab, 0xab]));
+    }
+
     #[test]
     fn test_byte_string_pattern_paths() {
         let hello_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f]; // "Hello"
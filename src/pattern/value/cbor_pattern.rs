@@ -0,0 +1,163 @@
+use dcbor::prelude::*;
+
+use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+
+/// Pattern for matching a single, exact dCBOR item.
+///
+/// Written `cbor'...'` with the item in diagnostic notation, this pattern
+/// matches a value only if it is equal to the item. Because dCBOR encodings
+/// are deterministic, equality is structural: maps match regardless of how
+/// their keys were ordered in the literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CBORPattern(
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cbor"))]
+    CBOR,
+);
+
+impl CBORPattern {
+    /// Creates a new `CBORPattern` that matches the given item.
+    pub fn new(value: impl Into<CBOR>) -> Self { CBORPattern(value.into()) }
+
+    /// Returns the item this pattern matches.
+    pub fn value(&self) -> &CBOR { &self.0 }
+}
+
+impl Matcher for CBORPattern {
+    fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        if haystack == self.value() {
+            vec![vec![haystack.clone()]]
+        } else {
+            vec![]
+        }
+    }
+
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        _captures: &mut Vec<String>,
+    ) {
+        let idx = literals.len();
+        literals.push(Pattern::Value(crate::pattern::ValuePattern::Cbor(
+            self.clone(),
+        )));
+        code.push(Instr::MatchPredicate(idx));
+    }
+}
+
+impl std::fmt::Display for CBORPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Escape quotes, and any backslash the lexer would otherwise read as
+        // the start of an escape
+        let diagnostic = diagnostic(self.value());
+        let mut escaped = String::with_capacity(diagnostic.len());
+        let mut chars = diagnostic.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => escaped.push_str("\\'"),
+                '\\' if matches!(chars.peek(), None | Some('\\' | '\'')) => {
+                    escaped.push_str("\\\\")
+                }
+                c => escaped.push(c),
+            }
+        }
+        write!(f, "cbor'{}'", escaped)
+    }
+}
+
+/// Returns `cbor` in flat diagnostic notation, as [`CBOR::diagnostic_flat`]
+/// does, except that texts are written as they are wherever `dcbor_parse`
+/// would read them back unchanged.
+///
+/// `dcbor_parse` keeps the escapes of a text as written, so the text of
+/// `"a\"b"` is `a\"b`, while the diagnostic notation escapes every quote,
+/// writing that text as `"a\\"b"`, which doesn't parse.
+fn diagnostic(cbor: &CBOR) -> String {
+    match cbor.as_case() {
+        CBORCase::Text(text) if is_literal_text(text) => {
+            format!("\"{}\"", text)
+        }
+        CBORCase::Array(array) => {
+            let items: Vec<String> = array.iter().map(diagnostic).collect();
+            format!("[{}]", items.join(", "))
+        }
+        CBORCase::Map(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(key, value)| {
+                    format!("{}: {}", diagnostic(key), diagnostic(value))
+                })
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        CBORCase::Tagged(tag, item) => {
+            format!("{}({})", tag.value(), diagnostic(item))
+        }
+        _ => cbor.diagnostic_flat(),
+    }
+}
+
+/// Returns `true` if `text` can be written between quotes for `dcbor_parse`
+/// to read: it has no control characters, and has quotes and backslashes
+/// only in escapes.
+fn is_literal_text(text: &str) -> bool {
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return false,
+            '\\' => match chars.next() {
+                Some('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't') => {}
+                Some('u') => {
+                    let hex = chars.by_ref().take(4);
+                    if hex.filter(char::is_ascii_hexdigit).count() != 4 {
+                        return false;
+                    }
+                }
+                _ => return false,
+            },
+            c if c < ' ' => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbor_pattern_matching() {
+        let value = CBOR::from(vec![CBOR::from(1), CBOR::from("a")]);
+        let pattern = CBORPattern::new(value.clone());
+        assert_eq!(pattern.paths(&value), vec![vec![value.clone()]]);
+
+        assert!(!pattern.matches(&CBOR::from(vec![CBOR::from(1)])));
+        assert!(!pattern.matches(&CBOR::from(1)));
+    }
+
+    #[test]
+    fn test_cbor_pattern_display() {
+        assert_eq!(CBORPattern::new(42).to_string(), "cbor'42'");
+        assert_eq!(
+            CBORPattern::new(vec![CBOR::from(1), CBOR::from("a")]).to_string(),
+            r#"cbor'[1, "a"]'"#
+        );
+        assert_eq!(
+            CBORPattern::new(CBOR::to_byte_string([0u8])).to_string(),
+            r"cbor'h\'00\''"
+        );
+
+        // Texts read from diagnostic notation keep their escapes
+        let value = dcbor_parse::parse_dcbor_item(r#"{"a\"b": ["c\\"]}"#);
+        assert_eq!(
+            CBORPattern::new(value.unwrap()).to_string(),
+            r#"cbor'{"a\"b": ["c\\\"]}'"#
+        );
+        assert!(!is_literal_text("a\"b"));
+        assert!(!is_literal_text("a\\"));
+        assert!(!is_literal_text("\\u12"));
+        assert!(is_literal_text("\\u12ab\\n"));
+    }
+}
@@ -1,27 +1,120 @@
-use std::ops::RangeInclusive;
+use std::{cell::Cell, ops::RangeInclusive, time::Duration};
 
 use dcbor::{Date, prelude::*};
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use super::{compile_with_groups, group_captures};
+use crate::pattern::{Captures, Matcher, Path, Pattern, vm::Instr};
 
 /// Pattern for matching date values in dCBOR.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DatePattern {
     /// Matches any date.
     Any,
     /// Matches a specific date.
-    Value(Date),
+    Value(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::date")
+        )]
+        Date,
+    ),
     /// Matches dates within a range (inclusive).
-    Range(RangeInclusive<Date>),
+    Range(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::date_range")
+        )]
+        RangeInclusive<Date>,
+    ),
     /// Matches dates that are on or after the specified date.
-    Earliest(Date),
+    Earliest(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::date")
+        )]
+        Date,
+    ),
     /// Matches dates that are on or before the specified date.
-    Latest(Date),
+    Latest(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::date")
+        )]
+        Date,
+    ),
     /// Matches a date by its ISO-8601 string representation.
     String(String),
     /// Matches dates whose ISO-8601 string representation matches the given
     /// regex pattern.
-    Regex(regex::Regex),
+    Regex(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::regex")
+        )]
+        regex::Regex,
+    ),
+    /// Matches dates no later than the current time, and if a duration is
+    /// given, no earlier than that long before it.
+    Past(Option<Duration>),
+    /// Matches dates no earlier than the current time, and if a duration is
+    /// given, no later than that long after it.
+    Future(Option<Duration>),
+    /// Matches dates the inner pattern matches, whether encoded as dCBOR
+    /// prefers, as tag 1 with a numeric timestamp, or as tag 0 with an
+    /// ISO-8601 string.
+    AnyEncoding(Box<DatePattern>),
+}
+
+/// The current time as seen by relative date patterns on this thread.
+#[derive(Clone, Copy)]
+enum EvaluationNow {
+    /// No evaluation is in progress; every read takes the clock.
+    Unpinned,
+    /// An evaluation is in progress but nothing has read the time yet.
+    Pending,
+    /// The time every relative date pattern sees until the evaluation ends.
+    Fixed(Date),
+}
+
+thread_local! {
+    static EVALUATION_NOW: Cell<EvaluationNow> =
+        const { Cell::new(EvaluationNow::Unpinned) };
+}
+
+/// Restores the previous evaluation time when dropped, even on unwinding.
+struct RestoreNow(EvaluationNow);
+
+impl Drop for RestoreNow {
+    fn drop(&mut self) { EVALUATION_NOW.set(self.0); }
+}
+
+/// Runs `f` as a single evaluation, so that every relative date pattern it
+/// matches sees the same current time. The clock is only read if a relative
+/// date pattern is actually matched, and nested evaluations share the time
+/// of the outermost one.
+pub(crate) fn pin_evaluation_now<R>(f: impl FnOnce() -> R) -> R {
+    match EVALUATION_NOW.get() {
+        EvaluationNow::Unpinned => {
+            let _restore =
+                RestoreNow(EVALUATION_NOW.replace(EvaluationNow::Pending));
+            f()
+        }
+        _ => f(),
+    }
+}
+
+/// Returns the current time for the evaluation in progress.
+fn evaluation_now() -> Date {
+    match EVALUATION_NOW.get() {
+        EvaluationNow::Fixed(now) => now,
+        EvaluationNow::Pending => {
+            let now = Date::now();
+            EVALUATION_NOW.set(EvaluationNow::Fixed(now));
+            now
+        }
+        EvaluationNow::Unpinned => Date::now(),
+    }
 }
 
 impl PartialEq for DatePattern {
@@ -36,6 +129,11 @@ impl PartialEq for DatePattern {
             (DatePattern::Regex(a), DatePattern::Regex(b)) => {
                 a.as_str() == b.as_str()
             }
+            (DatePattern::Past(a), DatePattern::Past(b)) => a == b,
+            (DatePattern::Future(a), DatePattern::Future(b)) => a == b,
+            (DatePattern::AnyEncoding(a), DatePattern::AnyEncoding(b)) => {
+                a == b
+            }
             _ => false,
         }
     }
@@ -75,6 +173,18 @@ impl std::hash::Hash for DatePattern {
                 // Regex does not implement Hash, so we hash its pattern string.
                 regex.as_str().hash(state);
             }
+            DatePattern::Past(duration) => {
+                7u8.hash(state);
+                duration.hash(state);
+            }
+            DatePattern::Future(duration) => {
+                8u8.hash(state);
+                duration.hash(state);
+            }
+            DatePattern::AnyEncoding(pattern) => {
+                9u8.hash(state);
+                pattern.hash(state);
+            }
         }
     }
 }
@@ -109,62 +219,196 @@ impl DatePattern {
     /// Creates a new `DatePattern` that matches dates whose ISO-8601 string
     /// representation matches the given regex pattern.
     pub fn regex(regex: regex::Regex) -> Self { DatePattern::Regex(regex) }
+
+    /// Creates a new `DatePattern` that matches dates no later than the
+    /// current time.
+    pub fn past() -> Self { DatePattern::Past(None) }
+
+    /// Creates a new `DatePattern` that matches dates no earlier than the
+    /// current time.
+    pub fn future() -> Self { DatePattern::Future(None) }
+
+    /// Creates a new `DatePattern` that matches dates within the given
+    /// duration before the current time.
+    pub fn within_past(duration: Duration) -> Self {
+        DatePattern::Past(Some(duration))
+    }
+
+    /// Creates a new `DatePattern` that matches dates within the given
+    /// duration after the current time.
+    pub fn within_future(duration: Duration) -> Self {
+        DatePattern::Future(Some(duration))
+    }
+
+    /// Returns a pattern that matches the dates this one does, whether
+    /// encoded as tag 1 with a numeric timestamp or as tag 0 with an ISO-8601
+    /// string.
+    pub fn any_encoding(self) -> Self {
+        match self {
+            DatePattern::AnyEncoding(_) => self,
+            pattern => DatePattern::AnyEncoding(Box::new(pattern)),
+        }
+    }
+
+    /// Runs `f` with the current time seen by relative date patterns such as
+    /// `date'past 30d'` fixed at `now`.
+    ///
+    /// Outside of this, each evaluation of a pattern reads the clock at most
+    /// once, the first time it matches a relative date pattern.
+    pub fn with_now<R>(now: Date, f: impl FnOnce() -> R) -> R {
+        let _restore =
+            RestoreNow(EVALUATION_NOW.replace(EvaluationNow::Fixed(now)));
+        f()
+    }
+
+    /// Returns the date `haystack` encodes, if it is encoded in a way this
+    /// pattern reads. A tag 0 string that isn't a valid ISO-8601 date is not a
+    /// date.
+    pub(crate) fn date_of(&self, haystack: &CBOR) -> Option<Date> {
+        match haystack.as_case() {
+            CBORCase::Tagged(tag, _) if tag.value() == 1 => {
+                Date::try_from(haystack.clone()).ok()
+            }
+            CBORCase::Tagged(tag, content)
+                if tag.value() == 0
+                    && matches!(self, DatePattern::AnyEncoding(_)) =>
+            {
+                Date::from_string(content.as_text()?).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if `date` matches this pattern.
+    fn matches_date(&self, date: Date) -> bool {
+        match self {
+            DatePattern::AnyEncoding(pattern) => pattern.matches_date(date),
+            DatePattern::Any => true,
+            DatePattern::Value(expected_date) => date == *expected_date,
+            DatePattern::Range(range) => range.contains(&date),
+            DatePattern::Earliest(earliest) => date >= *earliest,
+            DatePattern::Latest(latest) => date <= *latest,
+            DatePattern::String(expected_string) => {
+                date.to_string() == *expected_string
+            }
+            DatePattern::Regex(regex) => regex.is_match(&date.to_string()),
+            DatePattern::Past(duration) => {
+                let now = evaluation_now().timestamp();
+                let offset = now - date.timestamp();
+                offset >= 0.0
+                    && duration.is_none_or(|d| offset <= d.as_secs_f64())
+            }
+            DatePattern::Future(duration) => {
+                let now = evaluation_now().timestamp();
+                let offset = date.timestamp() - now;
+                offset >= 0.0
+                    && duration.is_none_or(|d| offset <= d.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// The units durations are written in, largest first, with their length in
+/// nanoseconds.
+const DURATION_UNITS: &[(&str, u128)] = &[
+    ("w", 7 * 24 * 60 * 60 * 1_000_000_000),
+    ("d", 24 * 60 * 60 * 1_000_000_000),
+    ("h", 60 * 60 * 1_000_000_000),
+    ("m", 60 * 1_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+/// Parses a duration written as a whole number followed by a unit, e.g.
+/// `30d`, `12h`, or `500ms`.
+pub(crate) fn parse_duration(src: &str) -> Option<Duration> {
+    let split = src.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = src.split_at(split);
+    let amount: u128 = amount.parse().ok()?;
+    let (_, unit_nanos) = DURATION_UNITS.iter().find(|(u, _)| *u == unit)?;
+    let nanos = amount.checked_mul(*unit_nanos)?;
+    let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+    Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+/// Formats a duration in the largest unit that represents it exactly, the
+/// inverse of [`parse_duration`].
+fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    if nanos == 0 {
+        return "0s".to_string();
+    }
+    let (unit, unit_nanos) = DURATION_UNITS
+        .iter()
+        .find(|(_, unit_nanos)| nanos.is_multiple_of(*unit_nanos))
+        .unwrap_or(&("ns", 1));
+    format!("{}{}", nanos / unit_nanos, unit)
 }
 
 impl Matcher for DatePattern {
     fn paths(&self, haystack: &CBOR) -> Vec<Path> {
-        // Check if the CBOR is a tagged value with date tag (tag 1)
-        if let CBORCase::Tagged(tag, _) = haystack.as_case() {
-            // Check if this is a date tag (tag 1)
-            if tag.value() == 1 {
-                // Try to extract the date
-                if let Ok(date) = Date::try_from(haystack.clone()) {
-                    let is_hit = match self {
-                        DatePattern::Any => true,
-                        DatePattern::Value(expected_date) => {
-                            date == *expected_date
-                        }
-                        DatePattern::Range(range) => range.contains(&date),
-                        DatePattern::Earliest(earliest) => date >= *earliest,
-                        DatePattern::Latest(latest) => date <= *latest,
-                        DatePattern::String(expected_string) => {
-                            date.to_string() == *expected_string
-                        }
-                        DatePattern::Regex(regex) => {
-                            regex.is_match(&date.to_string())
-                        }
-                    };
-
-                    if is_hit {
-                        vec![vec![haystack.clone()]]
-                    } else {
-                        vec![]
-                    }
-                } else {
-                    // Tagged with date tag but couldn't be parsed as date
-                    vec![]
-                }
-            } else {
-                // Not a date tag
-                vec![]
+        match self.date_of(haystack) {
+            Some(date) if self.matches_date(date) => {
+                vec![vec![haystack.clone()]]
             }
-        } else {
-            // Not tagged
-            vec![]
+            _ => vec![],
         }
     }
 
+    /// A regex's named groups are captures, each captured as the text it
+    /// matched in the date's ISO-8601 string, following the path to the
+    /// date.
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        let paths = self.paths(haystack);
+        let pattern = match self {
+            DatePattern::AnyEncoding(pattern) => pattern.as_ref(),
+            pattern => pattern,
+        };
+        let captures = match pattern {
+            DatePattern::Regex(regex) if !paths.is_empty() => {
+                let date = self
+                    .date_of(haystack)
+                    .expect("matched dates are valid")
+                    .to_string();
+                let groups = regex.captures(&date);
+                group_captures(
+                    haystack,
+                    regex.capture_names().flatten().map(|name| {
+                        let group = groups.as_ref().and_then(|g| g.name(name));
+                        (name, group.map(|group| group.as_str().to_cbor()))
+                    }),
+                )
+            }
+            _ => Captures::new(),
+        };
+        (paths, captures)
+    }
+
     fn compile(
         &self,
         code: &mut Vec<Instr>,
         literals: &mut Vec<Pattern>,
-        _captures: &mut Vec<String>,
+        captures: &mut Vec<String>,
     ) {
-        let idx = literals.len();
-        literals.push(Pattern::Value(crate::pattern::ValuePattern::Date(
-            self.clone(),
-        )));
-        code.push(Instr::MatchPredicate(idx));
+        compile_with_groups(
+            crate::pattern::ValuePattern::Date(self.clone()),
+            code,
+            literals,
+            captures,
+        );
+    }
+
+    fn collect_capture_names(&self, names: &mut Vec<String>) {
+        match self {
+            DatePattern::Regex(regex) => names
+                .extend(regex.capture_names().flatten().map(str::to_string)),
+            DatePattern::AnyEncoding(pattern) => {
+                pattern.collect_capture_names(names)
+            }
+            _ => {}
+        }
     }
 }
 
@@ -184,6 +428,19 @@ impl std::fmt::Display for DatePattern {
             DatePattern::Regex(regex) => {
                 write!(f, "date'/{}/'", regex.as_str())
             }
+            DatePattern::Past(None) => write!(f, "date'past'"),
+            DatePattern::Past(Some(duration)) => {
+                write!(f, "date'past {}'", format_duration(*duration))
+            }
+            DatePattern::Future(None) => write!(f, "date'future'"),
+            DatePattern::Future(Some(duration)) => {
+                write!(f, "date'future {}'", format_duration(*duration))
+            }
+            DatePattern::AnyEncoding(pattern) => {
+                let inner = pattern.to_string();
+                let quoted = inner.strip_prefix("date").unwrap_or(&inner);
+                write!(f, "date-any{}", quoted)
+            }
         }
     }
 }
@@ -353,5 +610,115 @@ mod tests {
 
         let regex = regex::Regex::new(r"2023-.*").unwrap();
         assert_eq!(DatePattern::regex(regex).to_string(), "date'/2023-.*/'");
+
+        assert_eq!(DatePattern::past().to_string(), "date'past'");
+        assert_eq!(DatePattern::future().to_string(), "date'future'");
+        assert_eq!(
+            DatePattern::within_past(Duration::from_secs(30 * 86400))
+                .to_string(),
+            "date'past 30d'"
+        );
+        assert_eq!(
+            DatePattern::within_future(Duration::from_millis(1500)).to_string(),
+            "date'future 1500ms'"
+        );
+    }
+
+    #[test]
+    fn test_date_pattern_any_encoding() {
+        let date = Date::from_ymd(2023, 12, 25);
+        let text = CBOR::to_tagged_value(0, date.to_string());
+
+        // Only tag 1 is read unless the pattern reads any encoding
+        assert!(!DatePattern::value(date).matches(&text));
+        let pattern = DatePattern::value(date).any_encoding();
+        assert!(pattern.matches(&text));
+        assert!(pattern.matches(&CBOR::from(date)));
+        assert_eq!(pattern.to_string(), format!("date-any'{}'", date));
+        assert_eq!(DatePattern::any().any_encoding().to_string(), "date-any");
+
+        // Applying it twice changes nothing
+        assert_eq!(pattern.clone().any_encoding(), pattern);
+
+        // Text that isn't a date, and tag 0 content that isn't text
+        let any = DatePattern::any().any_encoding();
+        assert!(!any.matches(&CBOR::to_tagged_value(0, "not a date")));
+        assert!(!any.matches(&CBOR::to_tagged_value(0, 1)));
+        assert!(!any.matches(&CBOR::from(date.to_string())));
+    }
+
+    #[test]
+    fn test_duration_round_trip() {
+        let cases = [
+            ("2w", Duration::from_secs(14 * 86400)),
+            ("30d", Duration::from_secs(30 * 86400)),
+            ("36h", Duration::from_secs(36 * 3600)),
+            ("90m", Duration::from_secs(90 * 60)),
+            ("45s", Duration::from_secs(45)),
+            ("250ms", Duration::from_millis(250)),
+            ("7us", Duration::from_micros(7)),
+            ("1000000001ns", Duration::new(1, 1)),
+            ("0s", Duration::ZERO),
+        ];
+        for (text, duration) in cases {
+            assert_eq!(parse_duration(text), Some(duration), "{}", text);
+            assert_eq!(format_duration(duration), text);
+        }
+
+        // Larger units are preferred when they are exact
+        assert_eq!(parse_duration("48h"), parse_duration("2d"));
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+
+        for text in ["", "30", "d", "30x", "-1d", "1.5h", "30 d"] {
+            assert_eq!(parse_duration(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_date_pattern_relative() {
+        let now = Date::from_ymd(2024, 6, 15);
+        let day = 86400.0;
+        let at = |days: f64| {
+            CBOR::from(Date::from_timestamp(now.timestamp() + days * day))
+        };
+
+        let last_month =
+            DatePattern::within_past(Duration::from_secs(30 * 86400));
+        let next_week =
+            DatePattern::within_future(Duration::from_secs(7 * 86400));
+        DatePattern::with_now(now, || {
+            assert!(last_month.matches(&at(0.0)));
+            assert!(last_month.matches(&at(-1.0)));
+            assert!(last_month.matches(&at(-30.0)));
+            assert!(!last_month.matches(&at(-31.0)));
+            assert!(!last_month.matches(&at(1.0)));
+
+            assert!(next_week.matches(&at(0.0)));
+            assert!(next_week.matches(&at(7.0)));
+            assert!(!next_week.matches(&at(8.0)));
+            assert!(!next_week.matches(&at(-1.0)));
+
+            assert!(DatePattern::past().matches(&at(-10000.0)));
+            assert!(!DatePattern::past().matches(&at(1.0)));
+            assert!(DatePattern::future().matches(&at(10000.0)));
+            assert!(!DatePattern::future().matches(&at(-1.0)));
+        });
+
+        // The same pattern gives different results at a different time
+        DatePattern::with_now(Date::from_ymd(2025, 1, 1), || {
+            assert!(!last_month.matches(&at(0.0)));
+        });
+    }
+
+    #[test]
+    fn test_evaluation_reads_clock_once() {
+        pin_evaluation_now(|| {
+            let first = evaluation_now();
+            std::thread::sleep(Duration::from_millis(5));
+            assert_eq!(evaluation_now(), first);
+
+            // Nested evaluations share the outer time
+            pin_evaluation_now(|| assert_eq!(evaluation_now(), first));
+        });
     }
 }
@@ -4,17 +4,43 @@ use dcbor::prelude::*;
 
 use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
 
+/// Registers the tags of `bc-components`, which digests need to convert to
+/// and from URs, the first time it is called.
+pub(crate) fn register_digest_tags() {
+    static REGISTERED: std::sync::Once = std::sync::Once::new();
+    REGISTERED.call_once(bc_components::register_tags);
+}
+
 /// Pattern for matching dCBOR digest values (CBOR tag 40001).
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DigestPattern {
     /// Matches any digest value.
     Any,
     /// Matches the exact digest.
-    Digest(Digest),
+    Digest(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::digest")
+        )]
+        Digest,
+    ),
     /// Matches the prefix of a digest (case insensitive).
-    Prefix(Vec<u8>),
+    Prefix(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::hex_bytes")
+        )]
+        Vec<u8>,
+    ),
     /// Matches the binary regular expression for a digest.
-    BinaryRegex(regex::bytes::Regex),
+    BinaryRegex(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::bytes_regex")
+        )]
+        regex::bytes::Regex,
+    ),
 }
 
 impl PartialEq for DigestPattern {
@@ -75,6 +101,18 @@ impl DigestPattern {
     pub fn binary_regex(regex: regex::bytes::Regex) -> Self {
         DigestPattern::BinaryRegex(regex)
     }
+
+    /// Returns true if the bytes of a digest satisfy this pattern.
+    pub(crate) fn matches_digest_bytes(&self, digest_bytes: &[u8]) -> bool {
+        match self {
+            DigestPattern::Any => true,
+            DigestPattern::Digest(pattern_digest) => {
+                digest_bytes == pattern_digest.data()
+            }
+            DigestPattern::Prefix(prefix) => digest_bytes.starts_with(prefix),
+            DigestPattern::BinaryRegex(regex) => regex.is_match(digest_bytes),
+        }
+    }
 }
 
 impl Matcher for DigestPattern {
@@ -86,23 +124,10 @@ impl Matcher for DigestPattern {
             // Try to extract the digest from the tagged content
             match CBOR::try_into_byte_string(content.clone()) {
                 Ok(digest_bytes) => {
-                    if digest_bytes.len() == Digest::DIGEST_SIZE {
-                        let is_hit = match self {
-                            DigestPattern::Any => true,
-                            DigestPattern::Digest(pattern_digest) => {
-                                digest_bytes == pattern_digest.data()
-                            }
-                            DigestPattern::Prefix(prefix) => {
-                                digest_bytes.starts_with(prefix)
-                            }
-                            DigestPattern::BinaryRegex(regex) => {
-                                regex.is_match(&digest_bytes)
-                            }
-                        };
-
-                        if is_hit {
-                            return vec![vec![haystack.clone()]];
-                        }
+                    if digest_bytes.len() == Digest::DIGEST_SIZE
+                        && self.matches_digest_bytes(&digest_bytes)
+                    {
+                        return vec![vec![haystack.clone()]];
                     }
                 }
                 Err(_) => {
@@ -133,6 +158,7 @@ impl std::fmt::Display for DigestPattern {
         match self {
             DigestPattern::Any => write!(f, "digest"),
             DigestPattern::Digest(digest) => {
+                register_digest_tags();
                 write!(f, "digest'{}'", digest.ur_string())
             }
             DigestPattern::Prefix(prefix) => {
@@ -161,8 +187,6 @@ mod tests {
 
     #[test]
     fn test_digest_pattern_display() {
-        bc_components::register_tags();
-
         let digest = test_digest();
         let pattern = DigestPattern::digest(digest);
         assert_eq!(
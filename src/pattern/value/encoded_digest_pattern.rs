@@ -0,0 +1,103 @@
+use bc_components::Digest;
+use dcbor::prelude::*;
+
+use crate::pattern::{DigestPattern, Matcher, Path, Pattern, vm::Instr};
+
+/// Pattern for matching values by the digest of their dCBOR encoding.
+///
+/// Where [`DigestPattern`] matches digests stored in a document, this
+/// pattern computes the SHA-256 digest of each value's canonical encoding and
+/// matches the value itself, which allows content-addressed lookup of
+/// subtrees with `search(...)`. The digest is computed afresh for every value
+/// matched, so searching a large tree hashes each subtree once per level
+/// above it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncodedDigestPattern(DigestPattern);
+
+impl EncodedDigestPattern {
+    /// Creates a new `EncodedDigestPattern` that matches values whose
+    /// encoding has exactly the given digest.
+    pub fn digest(digest: Digest) -> Self {
+        EncodedDigestPattern(DigestPattern::digest(digest))
+    }
+
+    /// Creates a new `EncodedDigestPattern` that matches values whose
+    /// encoding has a digest starting with the given bytes.
+    pub fn prefix(prefix: impl AsRef<[u8]>) -> Self {
+        EncodedDigestPattern(DigestPattern::prefix(prefix))
+    }
+
+    /// Creates a new `EncodedDigestPattern` that matches values whose
+    /// encoding has a digest matching the binary regex.
+    pub fn binary_regex(regex: regex::bytes::Regex) -> Self {
+        EncodedDigestPattern(DigestPattern::binary_regex(regex))
+    }
+
+    /// Creates a new `EncodedDigestPattern` from the pattern in a quoted
+    /// `digest'...'` literal, which is never `DigestPattern::Any`.
+    pub(crate) fn from_digest_pattern(pattern: DigestPattern) -> Self {
+        EncodedDigestPattern(pattern)
+    }
+
+    /// Returns the pattern the computed digest is matched against.
+    pub fn digest_pattern(&self) -> &DigestPattern { &self.0 }
+}
+
+impl Matcher for EncodedDigestPattern {
+    fn paths(&self, haystack: &CBOR) -> Vec<Path> {
+        let digest = Digest::from_image(haystack.to_cbor_data());
+        if self.0.matches_digest_bytes(digest.data()) {
+            vec![vec![haystack.clone()]]
+        } else {
+            vec![]
+        }
+    }
+
+    fn compile(
+        &self,
+        code: &mut Vec<Instr>,
+        literals: &mut Vec<Pattern>,
+        _captures: &mut Vec<String>,
+    ) {
+        let idx = literals.len();
+        literals.push(Pattern::Value(
+            crate::pattern::ValuePattern::EncodedDigest(self.clone()),
+        ));
+        code.push(Instr::MatchPredicate(idx));
+    }
+}
+
+impl std::fmt::Display for EncodedDigestPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The inner pattern displays as a quoted `digest'...'` literal
+        write!(f, "encoded-{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoded_digest_pattern_matches_encoding() {
+        let value = CBOR::from(vec![1, 2, 3]);
+        let digest = Digest::from_image(value.to_cbor_data());
+
+        let pattern = EncodedDigestPattern::digest(digest);
+        assert_eq!(pattern.paths(&value), vec![vec![value.clone()]]);
+        assert!(!pattern.matches(&CBOR::from(vec![1, 2])));
+
+        // A stored digest of the value is a different value
+        assert!(!pattern.matches(&digest.to_cbor()));
+
+        let pattern = EncodedDigestPattern::prefix(&digest.data()[..4]);
+        assert!(pattern.matches(&value));
+    }
+
+    #[test]
+    fn test_encoded_digest_pattern_display() {
+        let pattern = EncodedDigestPattern::prefix([0xab, 0xcd]);
+        assert_eq!(pattern.to_string(), "encoded-digest'abcd'");
+    }
+}
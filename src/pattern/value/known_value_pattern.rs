@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 use dcbor::prelude::*;
 use known_values::{KNOWN_VALUES, KnownValue};
 
@@ -8,15 +10,39 @@ const KNOWN_VALUE_TAG: Tag = Tag::with_value(40000);
 
 /// Pattern for matching known values.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KnownValuePattern {
     /// Matches any known value.
     Any,
     /// Matches the specific known value.
-    Value(KnownValue),
+    Value(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::known_value")
+        )]
+        KnownValue,
+    ),
     /// Matches the name of a known value.
     Name(String),
     /// Matches the regex for a known value name.
-    Regex(regex::Regex),
+    Regex(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::regex")
+        )]
+        regex::Regex,
+    ),
+    /// Matches any of the given known values.
+    Set(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::known_value_set")
+        )]
+        Vec<KnownValue>,
+    ),
+    /// Matches known values whose numeric value is within the inclusive
+    /// range.
+    Range(RangeInclusive<u64>),
 }
 
 impl PartialEq for KnownValuePattern {
@@ -30,6 +56,10 @@ impl PartialEq for KnownValuePattern {
             (KnownValuePattern::Regex(a), KnownValuePattern::Regex(b)) => {
                 a.as_str() == b.as_str()
             }
+            (KnownValuePattern::Set(a), KnownValuePattern::Set(b)) => a == b,
+            (KnownValuePattern::Range(a), KnownValuePattern::Range(b)) => {
+                a == b
+            }
             _ => false,
         }
     }
@@ -56,6 +86,14 @@ impl std::hash::Hash for KnownValuePattern {
                 // Regex does not implement Hash, so we hash its pattern string.
                 regex.as_str().hash(state);
             }
+            KnownValuePattern::Set(values) => {
+                4u8.hash(state);
+                values.hash(state);
+            }
+            KnownValuePattern::Range(range) => {
+                5u8.hash(state);
+                range.hash(state);
+            }
         }
     }
 }
@@ -77,6 +115,29 @@ impl KnownValuePattern {
     pub fn regex(regex: regex::Regex) -> Self {
         KnownValuePattern::Regex(regex)
     }
+
+    /// Creates a new `KnownValuePattern` that matches any of the given known
+    /// values.
+    pub fn set(values: impl IntoIterator<Item = KnownValue>) -> Self {
+        KnownValuePattern::Set(values.into_iter().collect())
+    }
+
+    /// Creates a new `KnownValuePattern` that matches known values whose
+    /// numeric value is within the inclusive range.
+    pub fn range(range: RangeInclusive<u64>) -> Self {
+        KnownValuePattern::Range(range)
+    }
+}
+
+/// Returns the registered name of the known value, or its number if it has
+/// none.
+fn registered_name(value: &KnownValue) -> String {
+    let binding = KNOWN_VALUES.get();
+    if let Some(known_values_store) = binding.as_ref() {
+        known_values_store.name(value.clone())
+    } else {
+        value.name()
+    }
 }
 
 impl Matcher for KnownValuePattern {
@@ -120,16 +181,7 @@ impl Matcher for KnownValuePattern {
                         KnownValuePattern::Regex(regex) => {
                             // Check if the known value's name matches the regex
                             // Use the global registry to get the proper name
-                            let name = {
-                                let binding = KNOWN_VALUES.get();
-                                if let Some(known_values_store) =
-                                    binding.as_ref()
-                                {
-                                    known_values_store.name(known_value.clone())
-                                } else {
-                                    known_value.name()
-                                }
-                            };
+                            let name = registered_name(&known_value);
 
                             if regex.is_match(&name) {
                                 vec![vec![haystack.clone()]]
@@ -137,6 +189,20 @@ impl Matcher for KnownValuePattern {
                                 vec![]
                             }
                         }
+                        KnownValuePattern::Set(values) => {
+                            if values.contains(&known_value) {
+                                vec![vec![haystack.clone()]]
+                            } else {
+                                vec![]
+                            }
+                        }
+                        KnownValuePattern::Range(range) => {
+                            if range.contains(value) {
+                                vec![vec![haystack.clone()]]
+                            } else {
+                                vec![]
+                            }
+                        }
                     }
                 } else {
                     vec![]
@@ -174,6 +240,16 @@ impl std::fmt::Display for KnownValuePattern {
             KnownValuePattern::Regex(regex) => {
                 write!(f, "'/{}/'", regex.as_str())
             }
+            KnownValuePattern::Set(values) => {
+                let names: Vec<String> = values
+                    .iter()
+                    .map(|value| format!("'{}'", registered_name(value)))
+                    .collect();
+                write!(f, "known({})", names.join(" | "))
+            }
+            KnownValuePattern::Range(range) => {
+                write!(f, "known({}...{})", range.start(), range.end())
+            }
         }
     }
 }
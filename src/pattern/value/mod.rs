@@ -1,7 +1,9 @@
 mod bool_pattern;
 mod bytestring_pattern;
+mod cbor_pattern;
 mod date_pattern;
 mod digest_pattern;
+mod encoded_digest_pattern;
 mod known_value_pattern;
 mod null_pattern;
 mod number_pattern;
@@ -9,22 +11,30 @@ mod text_pattern;
 
 pub use bool_pattern::*;
 pub use bytestring_pattern::*;
+pub use cbor_pattern::*;
 pub use date_pattern::*;
 use dcbor::prelude::*;
 pub use digest_pattern::*;
+pub use encoded_digest_pattern::*;
 pub use known_value_pattern::*;
 pub use null_pattern::*;
 pub use number_pattern::*;
 pub use text_pattern::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use crate::pattern::{
+    Captures, Matcher, Path, Pattern,
+    vm::{Instr, capture_slots},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValuePattern {
     Bool(BoolPattern),
     ByteString(ByteStringPattern),
+    Cbor(CBORPattern),
     Date(DatePattern),
     Digest(DigestPattern),
+    EncodedDigest(EncodedDigestPattern),
     KnownValue(KnownValuePattern),
     Null(NullPattern),
     Number(NumberPattern),
@@ -36,8 +46,10 @@ impl Matcher for ValuePattern {
         match self {
             ValuePattern::Bool(pattern) => pattern.paths(haystack),
             ValuePattern::ByteString(pattern) => pattern.paths(haystack),
+            ValuePattern::Cbor(pattern) => pattern.paths(haystack),
             ValuePattern::Date(pattern) => pattern.paths(haystack),
             ValuePattern::Digest(pattern) => pattern.paths(haystack),
+            ValuePattern::EncodedDigest(pattern) => pattern.paths(haystack),
             ValuePattern::KnownValue(pattern) => pattern.paths(haystack),
             ValuePattern::Null(pattern) => pattern.paths(haystack),
             ValuePattern::Number(pattern) => pattern.paths(haystack),
@@ -45,6 +57,21 @@ impl Matcher for ValuePattern {
         }
     }
 
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        match self {
+            ValuePattern::ByteString(pattern) => {
+                pattern.paths_with_captures(haystack)
+            }
+            ValuePattern::Date(pattern) => {
+                pattern.paths_with_captures(haystack)
+            }
+            ValuePattern::Text(pattern) => {
+                pattern.paths_with_captures(haystack)
+            }
+            _ => (self.paths(haystack), Captures::new()),
+        }
+    }
+
     fn compile(
         &self,
         code: &mut Vec<Instr>,
@@ -58,12 +85,18 @@ impl Matcher for ValuePattern {
             ValuePattern::ByteString(pattern) => {
                 pattern.compile(code, literals, captures)
             }
+            ValuePattern::Cbor(pattern) => {
+                pattern.compile(code, literals, captures)
+            }
             ValuePattern::Date(pattern) => {
                 pattern.compile(code, literals, captures)
             }
             ValuePattern::Digest(pattern) => {
                 pattern.compile(code, literals, captures)
             }
+            ValuePattern::EncodedDigest(pattern) => {
+                pattern.compile(code, literals, captures)
+            }
             ValuePattern::KnownValue(pattern) => {
                 pattern.compile(code, literals, captures)
             }
@@ -78,6 +111,52 @@ impl Matcher for ValuePattern {
             }
         }
     }
+
+    fn collect_capture_names(&self, names: &mut Vec<String>) {
+        match self {
+            ValuePattern::ByteString(pattern) => {
+                pattern.collect_capture_names(names)
+            }
+            ValuePattern::Date(pattern) => pattern.collect_capture_names(names),
+            ValuePattern::Text(pattern) => pattern.collect_capture_names(names),
+            _ => {}
+        }
+    }
+}
+
+/// Compiles a value pattern whose regex may have named groups. Without
+/// groups it is a simple predicate; with them it reports the groups as
+/// captures, like a structure pattern.
+fn compile_with_groups(
+    pattern: ValuePattern,
+    code: &mut Vec<Instr>,
+    literals: &mut Vec<Pattern>,
+    captures: &mut Vec<String>,
+) {
+    let pattern = Pattern::Value(pattern);
+    let has_groups = !capture_slots(&pattern, captures).is_empty();
+    let idx = literals.len();
+    literals.push(pattern);
+    if has_groups {
+        code.push(Instr::MatchStructure(idx));
+    } else {
+        code.push(Instr::MatchPredicate(idx));
+    }
+}
+
+/// Returns the captures made by the named groups of a regex that matched
+/// `haystack`, each a path from `haystack` to the value the group matched.
+/// Groups that took no part in the match capture nothing.
+fn group_captures<'a>(
+    haystack: &CBOR,
+    groups: impl Iterator<Item = (&'a str, Option<CBOR>)>,
+) -> Captures {
+    groups
+        .filter_map(|(name, value)| {
+            let value = value?;
+            Some((name.to_string(), vec![vec![haystack.clone(), value]]))
+        })
+        .collect()
 }
 
 impl std::fmt::Display for ValuePattern {
@@ -85,8 +164,10 @@ impl std::fmt::Display for ValuePattern {
         match self {
             ValuePattern::Bool(pattern) => write!(f, "{}", pattern),
             ValuePattern::ByteString(pattern) => write!(f, "{}", pattern),
+            ValuePattern::Cbor(pattern) => write!(f, "{}", pattern),
             ValuePattern::Date(pattern) => write!(f, "{}", pattern),
             ValuePattern::Digest(pattern) => write!(f, "{}", pattern),
+            ValuePattern::EncodedDigest(pattern) => write!(f, "{}", pattern),
             ValuePattern::KnownValue(pattern) => write!(f, "{}", pattern),
             ValuePattern::Null(pattern) => write!(f, "{}", pattern),
             ValuePattern::Number(pattern) => write!(f, "{}", pattern),
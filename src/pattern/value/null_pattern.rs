@@ -4,6 +4,7 @@ use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
 
 /// Pattern for matching null values in dCBOR.
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NullPattern;
 
 impl Matcher for NullPattern {
@@ -1,4 +1,4 @@
-use std::ops::RangeInclusive;
+use std::ops::{Bound, RangeBounds, RangeInclusive};
 
 use dcbor::prelude::*;
 
@@ -6,6 +6,7 @@ use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
 
 /// Pattern for matching number values in dCBOR.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NumberPattern {
     /// Matches any number.
     Any,
@@ -13,6 +14,10 @@ pub enum NumberPattern {
     Value(f64),
     /// Matches numbers within a range, inclusive (..=).
     Range(RangeInclusive<f64>),
+    /// Matches numbers between a lower and an upper bound, at least one of
+    /// which is exclusive. [`NumberPattern::bounded`] builds the other
+    /// variants for ranges that are inclusive or open at either end.
+    Bounded(Bound<f64>, Bound<f64>),
     /// Matches numbers that are greater than the specified value.
     GreaterThan(f64),
     /// Matches numbers that are greater than or equal to the specified value.
@@ -27,6 +32,15 @@ pub enum NumberPattern {
     Infinity,
     /// Matches negative infinity.
     NegInfinity,
+    /// Matches any of the given numbers, which are kept sorted and without
+    /// repeats.
+    Set(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::number_set")
+        )]
+        Vec<f64>,
+    ),
 }
 
 impl std::hash::Hash for NumberPattern {
@@ -61,6 +75,28 @@ impl std::hash::Hash for NumberPattern {
             NumberPattern::NaN => 7u8.hash(state),
             NumberPattern::Infinity => 8u8.hash(state),
             NumberPattern::NegInfinity => 9u8.hash(state),
+            NumberPattern::Set(values) => {
+                10u8.hash(state);
+                for value in values {
+                    value.to_bits().hash(state);
+                }
+            }
+            NumberPattern::Bounded(lower, upper) => {
+                11u8.hash(state);
+                for bound in [lower, upper] {
+                    match bound {
+                        Bound::Included(value) => {
+                            0u8.hash(state);
+                            value.to_bits().hash(state);
+                        }
+                        Bound::Excluded(value) => {
+                            1u8.hash(state);
+                            value.to_bits().hash(state);
+                        }
+                        Bound::Unbounded => 2u8.hash(state),
+                    }
+                }
+            }
         }
     }
 }
@@ -71,6 +107,10 @@ impl PartialEq for NumberPattern {
             (NumberPattern::Any, NumberPattern::Any) => true,
             (NumberPattern::Value(a), NumberPattern::Value(b)) => a == b,
             (NumberPattern::Range(a), NumberPattern::Range(b)) => a == b,
+            (
+                NumberPattern::Bounded(lower_a, upper_a),
+                NumberPattern::Bounded(lower_b, upper_b),
+            ) => lower_a == lower_b && upper_a == upper_b,
             (NumberPattern::GreaterThan(a), NumberPattern::GreaterThan(b)) => {
                 a == b
             }
@@ -86,6 +126,7 @@ impl PartialEq for NumberPattern {
             (NumberPattern::NaN, NumberPattern::NaN) => true,
             (NumberPattern::Infinity, NumberPattern::Infinity) => true,
             (NumberPattern::NegInfinity, NumberPattern::NegInfinity) => true,
+            (NumberPattern::Set(a), NumberPattern::Set(b)) => a == b,
             _ => false,
         }
     }
@@ -116,6 +157,46 @@ impl NumberPattern {
         NumberPattern::Range(RangeInclusive::new(start, end))
     }
 
+    /// Creates a new `NumberPattern` that matches numbers within the range
+    /// `start` (inclusive) to `end` (exclusive).
+    pub fn range_exclusive<A>(range: std::ops::Range<A>) -> Self
+    where
+        A: Into<f64> + Copy,
+    {
+        Self::bounded(range.start.into()..range.end.into())
+    }
+
+    /// Creates a new `NumberPattern` that matches numbers within the given
+    /// bounds, each of which may be inclusive, exclusive, or unbounded, as in
+    /// `10.0..20.0` or `(Bound::Excluded(10.0), Bound::Excluded(20.0))`.
+    ///
+    /// Inclusive ranges are [`NumberPattern::Range`], and ranges open at one
+    /// end are comparisons like [`NumberPattern::GreaterThan`], so each set of
+    /// bounds has a single representation.
+    pub fn bounded(bounds: impl RangeBounds<f64>) -> Self {
+        match (bounds.start_bound().cloned(), bounds.end_bound().cloned()) {
+            (Bound::Included(start), Bound::Included(end)) => {
+                NumberPattern::Range(start..=end)
+            }
+            (Bound::Unbounded, Bound::Unbounded) => {
+                NumberPattern::Range(f64::NEG_INFINITY..=f64::INFINITY)
+            }
+            (Bound::Included(start), Bound::Unbounded) => {
+                NumberPattern::GreaterThanOrEqual(start)
+            }
+            (Bound::Excluded(start), Bound::Unbounded) => {
+                NumberPattern::GreaterThan(start)
+            }
+            (Bound::Unbounded, Bound::Included(end)) => {
+                NumberPattern::LessThanOrEqual(end)
+            }
+            (Bound::Unbounded, Bound::Excluded(end)) => {
+                NumberPattern::LessThan(end)
+            }
+            (lower, upper) => NumberPattern::Bounded(lower, upper),
+        }
+    }
+
     /// Creates a new `NumberPattern` that matches numbers greater than the
     /// specified value.
     pub fn greater_than<T>(value: T) -> Self
@@ -160,6 +241,23 @@ impl NumberPattern {
 
     /// Creates a new `NumberPattern` that matches negative infinity.
     pub fn neg_infinity() -> Self { NumberPattern::NegInfinity }
+
+    /// Creates a new `NumberPattern` that matches any of the given numbers.
+    /// NaN is never a member of the set.
+    pub fn set<T>(values: impl IntoIterator<Item = T>) -> Self
+    where
+        T: Into<f64>,
+    {
+        // Adding zero turns -0 into 0, which compares equal to it
+        let mut values: Vec<f64> = values
+            .into_iter()
+            .map(|value| value.into() + 0.0)
+            .filter(|value| !value.is_nan())
+            .collect();
+        values.sort_by(f64::total_cmp);
+        values.dedup();
+        NumberPattern::Set(values)
+    }
 }
 
 impl Matcher for NumberPattern {
@@ -180,6 +278,13 @@ impl Matcher for NumberPattern {
                     false
                 }
             }
+            NumberPattern::Bounded(lower, upper) => {
+                if let Ok(value) = f64::try_from_cbor(haystack) {
+                    (*lower, *upper).contains(&value)
+                } else {
+                    false
+                }
+            }
             NumberPattern::GreaterThan(want) => {
                 if let Ok(value) = f64::try_from_cbor(haystack) {
                     value > *want
@@ -229,6 +334,16 @@ impl Matcher for NumberPattern {
                     false
                 }
             }
+            NumberPattern::Set(values) => {
+                if let Ok(value) = f64::try_from_cbor(haystack) {
+                    let value = value + 0.0;
+                    values
+                        .binary_search_by(|probe| probe.total_cmp(&value))
+                        .is_ok()
+                } else {
+                    false
+                }
+            }
         };
 
         if is_hit {
@@ -260,6 +375,18 @@ impl std::fmt::Display for NumberPattern {
             NumberPattern::Range(range) => {
                 write!(f, "{}...{}", range.start(), range.end())
             }
+            NumberPattern::Bounded(lower, upper) => {
+                match lower {
+                    Bound::Included(value) => write!(f, "{}...", value)?,
+                    Bound::Excluded(value) => write!(f, "{}<...", value)?,
+                    Bound::Unbounded => write!(f, "...")?,
+                }
+                match upper {
+                    Bound::Included(value) => write!(f, "{}", value),
+                    Bound::Excluded(value) => write!(f, "<{}", value),
+                    Bound::Unbounded => Ok(()),
+                }
+            }
             NumberPattern::GreaterThan(value) => {
                 write!(f, ">{}", value)
             }
@@ -273,6 +400,17 @@ impl std::fmt::Display for NumberPattern {
             NumberPattern::NaN => write!(f, "NaN"),
             NumberPattern::Infinity => write!(f, "Infinity"),
             NumberPattern::NegInfinity => write!(f, "-Infinity"),
+            NumberPattern::Set(values) => {
+                let values: Vec<_> = values
+                    .iter()
+                    .map(|value| match *value {
+                        f64::INFINITY => "Infinity".to_string(),
+                        f64::NEG_INFINITY => "-Infinity".to_string(),
+                        value => value.to_string(),
+                    })
+                    .collect();
+                write!(f, "number({})", values.join(", "))
+            }
         }
     }
 }
@@ -296,6 +434,42 @@ mod tests {
         assert_eq!(NumberPattern::nan().to_string(), "NaN");
         assert_eq!(NumberPattern::infinity().to_string(), "Infinity");
         assert_eq!(NumberPattern::neg_infinity().to_string(), "-Infinity");
+        assert_eq!(
+            NumberPattern::range_exclusive(1.0..10.0).to_string(),
+            "1...<10"
+        );
+        assert_eq!(
+            NumberPattern::bounded((
+                Bound::Excluded(1.0),
+                Bound::Included(10.0)
+            ))
+            .to_string(),
+            "1<...10"
+        );
+    }
+
+    #[test]
+    fn test_number_pattern_bounded_canonical() {
+        use Bound::*;
+
+        // Only bounds with an exclusive end need the `Bounded` variant
+        assert_eq!(
+            NumberPattern::bounded((Included(1.0), Included(2.0))),
+            NumberPattern::range(1.0..=2.0)
+        );
+        assert_eq!(
+            NumberPattern::bounded((Excluded(1.0), Unbounded)),
+            NumberPattern::greater_than(1.0)
+        );
+        assert_eq!(
+            NumberPattern::bounded(..=2.0),
+            NumberPattern::less_than_or_equal(2.0)
+        );
+        assert_eq!(
+            NumberPattern::bounded(1.0..2.0),
+            NumberPattern::Bounded(Included(1.0), Excluded(2.0))
+        );
+        assert!(!NumberPattern::bounded(..).matches(&f64::NAN.to_cbor()));
     }
 
     #[test]
@@ -1,16 +1,34 @@
+use std::collections::BTreeSet;
+
 use dcbor::prelude::*;
 
-use crate::pattern::{Matcher, Path, Pattern, vm::Instr};
+use super::{compile_with_groups, group_captures};
+use crate::pattern::{Captures, Matcher, Path, Pattern, vm::Instr};
 
 /// Pattern for matching text values in dCBOR.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TextPattern {
     /// Matches any text.
     Any,
     /// Matches the specific text.
     Value(String),
     /// Matches the regex for a text.
-    Regex(regex::Regex),
+    Regex(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::serde_support::regex")
+        )]
+        regex::Regex,
+    ),
+    /// Matches text that starts with the given prefix.
+    Prefix(String),
+    /// Matches text that ends with the given suffix.
+    Suffix(String),
+    /// Matches text equal to the given value, ignoring case.
+    CaseInsensitive(String),
+    /// Matches text equal to any of the given values.
+    Set(BTreeSet<String>),
 }
 
 impl PartialEq for TextPattern {
@@ -21,6 +39,13 @@ impl PartialEq for TextPattern {
             (TextPattern::Regex(a), TextPattern::Regex(b)) => {
                 a.as_str() == b.as_str()
             }
+            (TextPattern::Prefix(a), TextPattern::Prefix(b)) => a == b,
+            (TextPattern::Suffix(a), TextPattern::Suffix(b)) => a == b,
+            (
+                TextPattern::CaseInsensitive(a),
+                TextPattern::CaseInsensitive(b),
+            ) => a == b,
+            (TextPattern::Set(a), TextPattern::Set(b)) => a == b,
             _ => false,
         }
     }
@@ -43,6 +68,22 @@ impl std::hash::Hash for TextPattern {
                 // Regex does not implement Hash, so we hash its pattern string.
                 regex.as_str().hash(state);
             }
+            TextPattern::Prefix(s) => {
+                3u8.hash(state);
+                s.hash(state);
+            }
+            TextPattern::Suffix(s) => {
+                4u8.hash(state);
+                s.hash(state);
+            }
+            TextPattern::CaseInsensitive(s) => {
+                5u8.hash(state);
+                s.hash(state);
+            }
+            TextPattern::Set(values) => {
+                6u8.hash(state);
+                values.hash(state);
+            }
         }
     }
 }
@@ -58,6 +99,30 @@ impl TextPattern {
 
     /// Creates a new `TextPattern` that matches the regex for a text.
     pub fn regex(regex: regex::Regex) -> Self { TextPattern::Regex(regex) }
+
+    /// Creates a new `TextPattern` that matches text starting with the given
+    /// prefix.
+    pub fn prefix<T: Into<String>>(prefix: T) -> Self {
+        TextPattern::Prefix(prefix.into())
+    }
+
+    /// Creates a new `TextPattern` that matches text ending with the given
+    /// suffix.
+    pub fn suffix<T: Into<String>>(suffix: T) -> Self {
+        TextPattern::Suffix(suffix.into())
+    }
+
+    /// Creates a new `TextPattern` that matches the specific text, ignoring
+    /// case.
+    pub fn case_insensitive<T: Into<String>>(value: T) -> Self {
+        TextPattern::CaseInsensitive(value.into())
+    }
+
+    /// Creates a new `TextPattern` that matches text equal to any of the
+    /// given values.
+    pub fn set<T: Into<String>>(values: impl IntoIterator<Item = T>) -> Self {
+        TextPattern::Set(values.into_iter().map(Into::into).collect())
+    }
 }
 
 impl Matcher for TextPattern {
@@ -66,6 +131,12 @@ impl Matcher for TextPattern {
             TextPattern::Any => true,
             TextPattern::Value(want) => value == *want,
             TextPattern::Regex(regex) => regex.is_match(value),
+            TextPattern::Prefix(prefix) => value.starts_with(prefix.as_str()),
+            TextPattern::Suffix(suffix) => value.ends_with(suffix.as_str()),
+            TextPattern::CaseInsensitive(want) => {
+                value.to_lowercase() == want.to_lowercase()
+            }
+            TextPattern::Set(values) => values.contains(value),
         });
 
         if is_hit {
@@ -75,29 +146,73 @@ impl Matcher for TextPattern {
         }
     }
 
+    /// A regex's named groups are captures, each captured as the text it
+    /// matched, following the path to the text value.
+    fn paths_with_captures(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        let paths = self.paths(haystack);
+        let captures = match (self, haystack.as_text()) {
+            (TextPattern::Regex(regex), Some(text)) if !paths.is_empty() => {
+                let groups = regex.captures(text);
+                group_captures(
+                    haystack,
+                    regex.capture_names().flatten().map(|name| {
+                        let group = groups.as_ref().and_then(|g| g.name(name));
+                        (name, group.map(|group| group.as_str().to_cbor()))
+                    }),
+                )
+            }
+            _ => Captures::new(),
+        };
+        (paths, captures)
+    }
+
     fn compile(
         &self,
         code: &mut Vec<Instr>,
         literals: &mut Vec<Pattern>,
-        _captures: &mut Vec<String>,
+        captures: &mut Vec<String>,
     ) {
-        let idx = literals.len();
-        literals.push(Pattern::Value(crate::pattern::ValuePattern::Text(
-            self.clone(),
-        )));
-        code.push(Instr::MatchPredicate(idx));
+        compile_with_groups(
+            crate::pattern::ValuePattern::Text(self.clone()),
+            code,
+            literals,
+            captures,
+        );
+    }
+
+    fn collect_capture_names(&self, names: &mut Vec<String>) {
+        if let TextPattern::Regex(regex) = self {
+            names.extend(regex.capture_names().flatten().map(str::to_string));
+        }
     }
 }
 
+/// Quotes `value` as a string literal that the parser reads back unchanged.
+pub(crate) fn quoted(value: &str) -> String {
+    let escaped = value.replace("\\", "\\\\").replace("\"", "\\\"");
+    format!("\"{}\"", escaped)
+}
+
 impl std::fmt::Display for TextPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TextPattern::Any => write!(f, "text"),
-            TextPattern::Value(value) => {
-                let escaped = value.replace("\\", "\\\\").replace("\"", "\\\"");
-                write!(f, "\"{}\"", escaped)
-            }
+            TextPattern::Value(value) => write!(f, "{}", quoted(value)),
             TextPattern::Regex(regex) => write!(f, "/{}/", regex),
+            TextPattern::Prefix(prefix) => {
+                write!(f, "text(^{})", quoted(prefix))
+            }
+            TextPattern::Suffix(suffix) => {
+                write!(f, "text(${})", quoted(suffix))
+            }
+            TextPattern::CaseInsensitive(value) => {
+                write!(f, "text(~{})", quoted(value))
+            }
+            TextPattern::Set(values) => {
+                let values: Vec<_> =
+                    values.iter().map(|value| quoted(value)).collect();
+                write!(f, "text({})", values.join(", "))
+            }
         }
     }
 }
@@ -157,6 +272,28 @@ mod tests {
         assert!(!word_pattern.matches(&number_cbor));
     }
 
+    #[test]
+    fn test_text_pattern_affix_and_case_matching() {
+        let prefix = TextPattern::prefix("ERROR");
+        assert!(prefix.matches(&"ERROR: disk full".to_cbor()));
+        assert!(!prefix.matches(&"error: disk full".to_cbor()));
+        assert!(!prefix.matches(&42.to_cbor()));
+
+        let suffix = TextPattern::suffix(".log");
+        assert!(suffix.matches(&"system.log".to_cbor()));
+        assert!(!suffix.matches(&"system.log.1".to_cbor()));
+
+        let case_insensitive = TextPattern::case_insensitive("Error");
+        assert!(case_insensitive.matches(&"ERROR".to_cbor()));
+        assert!(case_insensitive.matches(&"error".to_cbor()));
+        assert!(!case_insensitive.matches(&"errors".to_cbor()));
+
+        assert_eq!(prefix.to_string(), r#"text(^"ERROR")"#);
+        assert_eq!(suffix.to_string(), r#"text($".log")"#);
+        assert_eq!(case_insensitive.to_string(), r#"text(~"Error")"#);
+        assert_ne!(prefix, TextPattern::suffix("ERROR"));
+    }
+
     #[test]
     fn test_text_pattern_paths() {
         let hello_cbor = "Hello".to_cbor();
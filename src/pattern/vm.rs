@@ -1,12 +1,26 @@
 //! Tiny Thompson-style VM for walking dCBOR trees.
 //!
 //! The VM runs byte-code produced by `Pattern::compile` methods.
+//!
+//! [`Program::compile`] and [`Program::run`], with the free function [`run`],
+//! are the supported way to embed the VM, and follow the crate's semver. The
+//! public fields of [`Program`], and [`Instr`], [`Axis`], and [`Vm`], are
+//! exposed for inspecting and hand-assembling programs, but
+//! the instruction set follows the needs of the patterns and may change in
+//! any release that changes the minor version.
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::Arc,
+};
 
 use dcbor::prelude::*;
 
-use super::{Matcher, Path, Pattern};
+use super::{
+    Captures, Matcher, Path, PathStep, Pattern, admit_threads, order_captures,
+    sort_paths, step, value::pin_evaluation_now,
+};
 use crate::{Quantifier, Reluctance};
 
 /// Navigation axis for traversing dCBOR tree structures.
@@ -20,6 +34,8 @@ pub enum Axis {
     MapValue,
     /// Navigate to tagged value content
     TaggedContent,
+    /// Navigate to map entries, each as the array `[key, value]`
+    MapEntry,
 }
 
 impl Axis {
@@ -36,6 +52,10 @@ impl Axis {
             (Axis::TaggedContent, CBORCase::Tagged(_, content)) => {
                 vec![content.clone()]
             }
+            (Axis::MapEntry, CBORCase::Map(map)) => map
+                .iter()
+                .map(|(k, v)| vec![k.clone(), v.clone()].to_cbor())
+                .collect(),
             _ => Vec::new(),
         }
     }
@@ -46,7 +66,10 @@ impl Axis {
 pub enum Instr {
     /// Match predicate: `literals[idx].matches(cbor)`
     MatchPredicate(usize),
-    /// Match structure: use `literals[idx].paths(cbor)` for structure patterns
+    /// Match structure: use `literals[idx].paths_with_captures(cbor)` for
+    /// structure patterns, for value patterns whose regexes capture named
+    /// groups, and for `&` patterns that can't be matched one pattern at a
+    /// time
     MatchStructure(usize),
     /// ε-split: fork execution to `a` and `b`
     Split { a: usize, b: usize },
@@ -54,8 +77,14 @@ pub enum Instr {
     Jump(usize),
     /// Descend to children via axis, one thread per child
     PushAxis(Axis),
+    /// Descend to the one child a path step leads to, ending the thread if
+    /// there is none, without a frame for `Pop` to return to
+    Step(PathStep),
     /// Pop one CBOR value from the path
     Pop,
+    /// Discard the innermost `PushAxis` frame while staying at the current
+    /// node, for patterns whose result path ends at the child
+    DropAxis,
     /// Emit current path
     Save,
     /// Final accept, emit current path and halt thread
@@ -71,17 +100,23 @@ pub enum Instr {
     CombineSequence,
     /// Match only if pattern at `pat_idx` does not match
     NotMatch { pat_idx: usize },
-    /// Repeat a sub pattern according to range and greediness
+    /// Repeat a sub pattern according to range and greediness, propagating
+    /// the captures made in each iteration
     Repeat {
         pat_idx: usize,
         quantifier: Quantifier,
+        capture_map: Vec<(String, usize)>,
     },
+    /// Match only if the current value equals the end of the most recent
+    /// path captured in the capture group
+    MatchBackref(usize),
     /// Mark the start of a capture group
     CaptureStart(usize),
     /// Mark the end of a capture group
     CaptureEnd(usize),
 }
 
+/// A compiled pattern, ready to run against any number of haystacks.
 #[derive(Debug, Clone)]
 pub struct Program {
     pub code: Vec<Instr>,
@@ -89,16 +124,94 @@ pub struct Program {
     pub capture_names: Vec<String>,
 }
 
+impl Program {
+    /// Compiles `pattern` into a program that, when [run](Self::run), finds
+    /// what [`Matcher::paths_with_captures`] finds for the pattern.
+    ///
+    /// ```
+    /// use dcbor::prelude::*;
+    /// use dcbor_pattern::{Pattern, Program};
+    ///
+    /// let program = Program::compile(&Pattern::parse("[@n(number)*]")?);
+    /// let (paths, captures) = program.run(&CBOR::from(vec![1, 2]));
+    /// assert_eq!(paths.len(), 1);
+    /// assert_eq!(captures["n"].len(), 2);
+    /// # Ok::<(), dcbor_pattern::Error>(())
+    /// ```
+    pub fn compile(pattern: &Pattern) -> Self {
+        Self::build(|code, literals, captures| {
+            pattern.compile(code, literals, captures)
+        })
+    }
+
+    /// Builds a program from the instructions `emit` adds, followed by
+    /// [`Instr::Accept`].
+    pub(crate) fn build(
+        emit: impl FnOnce(&mut Vec<Instr>, &mut Vec<Pattern>, &mut Vec<String>),
+    ) -> Self {
+        let mut code = Vec::new();
+        let mut literals = Vec::new();
+        let mut capture_names = Vec::new();
+        emit(&mut code, &mut literals, &mut capture_names);
+        code.push(Instr::Accept);
+        Program { code, literals, capture_names }
+    }
+
+    /// Runs the program against `haystack`, returning the paths and captures
+    /// in document order as patterns do, with the captures in the order their
+    /// names first appear. [`run`] returns them in the order the VM finds
+    /// them.
+    pub fn run(&self, haystack: &CBOR) -> (Vec<Path>, Captures) {
+        pin_evaluation_now(|| {
+            let (mut paths, captures) = run(self, haystack);
+            sort_paths(&mut paths);
+            (paths, order_captures(captures, &self.capture_names))
+        })
+    }
+
+    /// Returns `true` if the program finds at least one path in `haystack`.
+    pub(crate) fn matches(&self, haystack: &CBOR) -> bool {
+        pin_evaluation_now(|| !run(self, haystack).0.is_empty())
+    }
+}
+
+/// A captured path, along with the positions of the node it was captured at.
+///
+/// Paths hold only values, so equal values at different positions give equal
+/// paths. The positions tell such captures apart from the same capture made
+/// by different threads.
+type Captured = (Vec<usize>, Path);
+
 /// Internal back-tracking state.
 #[derive(Clone)]
 struct Thread {
     pc: usize,
     cbor: CBOR,
     path: Path,
+    /// The index of each child descended into by `PushAxis` on the way to
+    /// the current node
+    positions: Vec<usize>,
     /// Stack of saved paths for nested sequence patterns
     saved_paths: Vec<Path>,
-    captures: Vec<Vec<Path>>,
+    /// Captures made so far for each capture group, shared with the threads
+    /// forked from this one until one of them captures something
+    captures: Arc<Vec<Vec<Captured>>>,
     capture_stack: Vec<Vec<usize>>,
+    /// Path and position lengths at each `PushAxis`, so `Pop` can return to
+    /// the parent even after instructions like `Search` have navigated deeper
+    axis_stack: Vec<(usize, usize)>,
+}
+
+impl Thread {
+    /// Returns the captures of group `idx` for adding to, copying the
+    /// captures first if they are shared with another thread.
+    fn captures_mut(&mut self, idx: usize) -> &mut Vec<Captured> {
+        let captures = Arc::make_mut(&mut self.captures);
+        if captures.len() <= idx {
+            captures.resize_with(idx + 1, Vec::new);
+        }
+        &mut captures[idx]
+    }
 }
 
 /// Match atomic patterns without recursion into the VM.
@@ -116,6 +229,7 @@ pub(crate) fn atomic_paths(
     match p {
         Value(v) => v.paths(cbor),
         Structure(s) => s.paths(cbor),
+        Custom(c) => c.paths(cbor),
         Meta(meta) => match meta {
             crate::pattern::meta::MetaPattern::Any(_) => {
                 vec![vec![cbor.clone()]]
@@ -133,33 +247,95 @@ pub(crate) fn atomic_paths(
     }
 }
 
+/// Returns the capture slot for each capture name in `pattern`, reserving
+/// slots in `captures` for names not seen before.
+pub(crate) fn capture_slots(
+    pattern: &Pattern,
+    captures: &mut Vec<String>,
+) -> Vec<(String, usize)> {
+    let mut names = Vec::new();
+    pattern.collect_capture_names(&mut names);
+    names
+        .into_iter()
+        .map(|name| {
+            let slot = match captures.iter().position(|n| n == &name) {
+                Some(slot) => slot,
+                None => {
+                    captures.push(name.clone());
+                    captures.len() - 1
+                }
+            };
+            (name, slot)
+        })
+        .collect()
+}
+
+/// One way of repeating a pattern some number of times: the node reached,
+/// the path to it, and the captures made along the way.
+#[derive(Clone)]
+struct RepeatState {
+    cbor: CBOR,
+    path: Path,
+    captures: Captures,
+    /// Set when the last iteration matched without moving to another node;
+    /// repeating it again would match the same way forever.
+    stalled: bool,
+}
+
 fn repeat_paths(
     pat: &Pattern,
     cbor: &CBOR,
     path: &Path,
     quantifier: Quantifier,
-) -> Vec<(CBOR, Path)> {
+) -> Vec<(CBOR, Path, Captures)> {
     // Build states for all possible repetition counts
-    let mut states: Vec<Vec<(CBOR, Path)>> =
-        vec![vec![(cbor.clone(), path.clone())]];
+    let start = RepeatState {
+        cbor: cbor.clone(),
+        path: path.clone(),
+        captures: Captures::new(),
+        stalled: false,
+    };
+    let mut states: Vec<Vec<RepeatState>> = vec![vec![start.clone()]];
     let bound = quantifier.max().unwrap_or(usize::MAX);
 
     // Try matching the pattern repeatedly
     for _ in 0..bound {
         let mut next = Vec::new();
-        for (c, pth) in states.last().unwrap().iter() {
-            for sub_path in pat.paths(c) {
+        for state in states.last().unwrap().iter() {
+            if state.stalled {
+                continue;
+            }
+            let c = &state.cbor;
+            let (sub_paths, sub_captures) = pat.paths_with_captures(c);
+            for sub_path in sub_paths {
                 if let Some(last) = sub_path.last() {
-                    if last.to_cbor_data() == c.to_cbor_data() {
-                        continue; // Avoid infinite loops
-                    }
-                    let mut combined = pth.clone();
+                    let mut combined = state.path.clone();
                     if sub_path.first() == Some(c) {
                         combined.extend(sub_path.iter().skip(1).cloned());
                     } else {
                         combined.extend(sub_path.iter().cloned());
                     }
-                    next.push((last.clone(), combined));
+
+                    // Capture paths are rooted at the node this iteration
+                    // started from
+                    let mut captures = state.captures.clone();
+                    for (name, capture_paths) in &sub_captures {
+                        let entry = captures.entry(name.clone()).or_default();
+                        for capture_path in capture_paths {
+                            let mut full_path = state.path.clone();
+                            full_path
+                                .extend(capture_path.iter().skip(1).cloned());
+                            entry.push(full_path);
+                        }
+                    }
+
+                    next.push(RepeatState {
+                        cbor: last.clone(),
+                        path: combined,
+                        captures,
+                        // Avoid infinite loops
+                        stalled: last.to_cbor_data() == c.to_cbor_data(),
+                    });
                 }
             }
         }
@@ -172,7 +348,7 @@ fn repeat_paths(
     // Zero repetition case
     let has_zero_rep = quantifier.min() == 0;
     let zero_rep_result = if has_zero_rep {
-        vec![(cbor.clone(), path.clone())]
+        vec![start.clone()]
     } else {
         vec![]
     };
@@ -194,7 +370,10 @@ fn repeat_paths(
         quantifier.min()
     };
     let max_count = if max_allowed < min_count {
-        return zero_rep_result;
+        return zero_rep_result
+            .into_iter()
+            .map(|state| (state.cbor, state.path, state.captures))
+            .collect();
     } else {
         max_allowed
     };
@@ -229,12 +408,12 @@ fn repeat_paths(
         // For greedy matching, add zero repetition case at the end if
         // applicable
         if has_zero_rep && out.is_empty() {
-            out.push((cbor.clone(), path.clone()));
+            out.push(start);
         }
     } else {
         // For lazy/possessive, include zero repetition first if applicable
         if has_zero_rep {
-            out.push((cbor.clone(), path.clone()));
+            out.push(start);
         }
 
         // Then include results from counts determined by reluctance
@@ -245,7 +424,9 @@ fn repeat_paths(
         }
     }
 
-    out
+    out.into_iter()
+        .map(|state| (state.cbor, state.path, state.captures))
+        .collect()
 }
 
 /// Execute `prog` starting at `root`. Every time `SAVE` or `ACCEPT` executes,
@@ -255,14 +436,20 @@ fn repeat_paths(
 fn run_thread(
     prog: &Program,
     start: Thread,
-    out: &mut Vec<(Path, Vec<Vec<Path>>)>,
+    out: &mut Vec<(Path, Arc<Vec<Vec<Captured>>>)>,
 ) -> bool {
     use Instr::*;
     let mut produced = false;
     let mut stack = vec![start];
 
     while let Some(mut th) = stack.pop() {
+        if !admit_threads(stack.len() + 1) {
+            return produced;
+        }
         loop {
+            if !step() {
+                return produced;
+            }
             match prog.code[th.pc] {
                 MatchPredicate(idx) => {
                     if atomic_paths(&prog.literals[idx], &th.cbor).is_empty() {
@@ -273,55 +460,71 @@ fn run_thread(
                 MatchStructure(idx) => {
                     // Use the structure pattern's matcher, with captures if
                     // present
-                    if let crate::pattern::Pattern::Structure(sp) =
-                        &prog.literals[idx]
+                    let (structure_paths, structure_captures) = match &prog
+                        .literals[idx]
                     {
-                        let (structure_paths, structure_captures) =
-                            sp.paths_with_captures(&th.cbor);
-
-                        if structure_paths.is_empty() {
-                            break;
+                        Pattern::Structure(sp) => {
+                            sp.paths_with_captures(&th.cbor)
                         }
+                        // Value patterns whose regexes have named groups
+                        Pattern::Value(vp) => vp.paths_with_captures(&th.cbor),
+                        // `&` of patterns whose paths move from the node
+                        Pattern::Meta(
+                            crate::pattern::meta::MetaPattern::And(and),
+                        ) => and.paths_with_captures(&th.cbor),
+                        _ => panic!(
+                            "MatchStructure used with non-structure pattern"
+                        ),
+                    };
 
-                        // Merge structure captures into thread captures
-                        for (i, name) in prog.capture_names.iter().enumerate() {
-                            if let Some(captured_paths) =
-                                structure_captures.get(name)
-                            {
-                                // Ensure capture storage is initialized
-                                while th.captures.len() <= i {
-                                    th.captures.push(Vec::new());
-                                }
-                                th.captures[i].extend(captured_paths.clone());
-                            }
+                    if structure_paths.is_empty() {
+                        break;
+                    }
+
+                    // Merge structure captures into thread captures. They
+                    // start at the current node, so extend them back to
+                    // the root through the thread's path
+                    let parents = th.path.len().saturating_sub(1);
+                    for (i, name) in prog.capture_names.iter().enumerate() {
+                        if let Some(captured_paths) =
+                            structure_captures.get(name)
+                        {
+                            let captured: Vec<Captured> = captured_paths
+                                .iter()
+                                .map(|captured_path| {
+                                    let mut path = th.path[..parents].to_vec();
+                                    path.extend_from_slice(captured_path);
+                                    (th.positions.clone(), path)
+                                })
+                                .collect();
+                            th.captures_mut(i).extend(captured);
                         }
+                    }
 
-                        // Handle structure paths
-                        if structure_paths.len() == 1
-                            && structure_paths[0].len() == 1
+                    // Handle structure paths
+                    if structure_paths.len() == 1
+                        && structure_paths[0].len() == 1
+                    {
+                        // Simple case: single path with single element
+                        th.pc += 1;
+                    } else {
+                        // Complex case: multiple paths or multi-element
+                        // paths. Threads are pushed last to first, so they
+                        // run, and emit their results, in the order of the
+                        // paths
+                        for structure_path in structure_paths.into_iter().rev()
                         {
-                            // Simple case: single path with single element
-                            th.pc += 1;
-                        } else {
-                            // Complex case: multiple paths or multi-element
-                            // paths
-                            for structure_path in structure_paths {
-                                if let Some(target) = structure_path.last() {
-                                    let mut new_thread = th.clone();
-                                    new_thread.cbor = target.clone();
-                                    new_thread.path.extend(
-                                        structure_path.iter().skip(1).cloned(),
-                                    );
-                                    new_thread.pc += 1;
-                                    stack.push(new_thread);
-                                }
+                            if let Some(target) = structure_path.last() {
+                                let mut new_thread = th.clone();
+                                new_thread.cbor = target.clone();
+                                new_thread.path.extend(
+                                    structure_path.iter().skip(1).cloned(),
+                                );
+                                new_thread.pc += 1;
+                                stack.push(new_thread);
                             }
-                            break;
                         }
-                    } else {
-                        panic!(
-                            "MatchStructure used with non-structure pattern"
-                        );
+                        break;
                     }
                 }
                 Split { a, b } => {
@@ -334,26 +537,57 @@ fn run_thread(
                     th.pc = addr;
                 }
                 PushAxis(axis) => {
+                    // Pushed last to first, so the children run in order
                     let children = axis.children(&th.cbor);
-                    for child in children {
+                    for (index, child) in children.into_iter().enumerate().rev()
+                    {
                         let mut new_thread = th.clone();
-                        new_thread.cbor = child.clone();
-                        new_thread.path.push(child);
+                        new_thread.axis_stack.push((
+                            new_thread.path.len(),
+                            new_thread.positions.len(),
+                        ));
+                        new_thread.path.push(child.clone());
+                        new_thread.cbor = child;
+                        new_thread.positions.push(index);
                         new_thread.pc += 1;
                         stack.push(new_thread);
                     }
                     break;
                 }
+                Step(ref step) => {
+                    let Some((index, child)) = step.child(&th.cbor) else {
+                        break;
+                    };
+                    th.path.push(child.clone());
+                    th.cbor = child;
+                    th.positions.push(index);
+                    th.pc += 1;
+                }
                 Pop => {
                     if th.path.is_empty() {
                         break;
                     }
-                    th.path.pop();
+                    match th.axis_stack.pop() {
+                        Some((len, positions))
+                            if len > 0 && len < th.path.len() =>
+                        {
+                            th.path.truncate(len);
+                            th.positions.truncate(positions);
+                        }
+                        _ => {
+                            th.path.pop();
+                            th.positions.pop();
+                        }
+                    }
                     if let Some(parent) = th.path.last() {
                         th.cbor = parent.clone();
                     }
                     th.pc += 1;
                 }
+                DropAxis => {
+                    th.axis_stack.pop();
+                    th.pc += 1;
+                }
                 Save => {
                     out.push((th.path.clone(), th.captures.clone()));
                     produced = true;
@@ -365,23 +599,38 @@ fn run_thread(
                     break;
                 }
                 Search { pat_idx, ref capture_map } => {
-                    // Implement recursive search pattern with capture support
+                    // The literal is the search pattern itself, so the
+                    // traversal honors its search options. Result and capture
+                    // paths are rooted at the current node, so they are
+                    // extended from the thread's path.
                     let (search_results, captures) =
                         prog.literals[pat_idx].paths_with_captures(&th.cbor);
 
                     for search_path in search_results {
+                        let Some(target) = search_path.last() else {
+                            continue;
+                        };
                         let mut new_thread = th.clone();
-                        new_thread.path = search_path.clone();
+                        new_thread.cbor = target.clone();
+                        new_thread
+                            .path
+                            .extend(search_path.iter().skip(1).cloned());
 
                         // Apply capture mappings - map captured paths to thread
                         // state
                         for (name, capture_idx) in capture_map {
-                            if *capture_idx < new_thread.captures.len()
-                                && let Some(capture_paths) = captures.get(name)
-                            {
+                            if let Some(capture_paths) = captures.get(name) {
+                                let captured =
+                                    new_thread.captures_mut(*capture_idx);
                                 for capture_path in capture_paths {
-                                    new_thread.captures[*capture_idx]
-                                        .push(capture_path.clone());
+                                    let mut full_path = th.path.clone();
+                                    full_path.extend(
+                                        capture_path.iter().skip(1).cloned(),
+                                    );
+                                    captured.push((
+                                        th.positions.clone(),
+                                        full_path,
+                                    ));
                                 }
                             }
                         }
@@ -415,27 +664,49 @@ fn run_thread(
                     }
                     th.pc += 1;
                 }
-                Repeat { pat_idx, quantifier } => {
+                Repeat { pat_idx, quantifier, ref capture_map } => {
                     let repeat_results = repeat_paths(
                         &prog.literals[pat_idx],
                         &th.cbor,
                         &th.path,
                         quantifier,
                     );
-                    for (result_cbor, result_path) in repeat_results {
+                    for (result_cbor, result_path, captures) in repeat_results {
                         let mut new_thread = th.clone();
                         new_thread.cbor = result_cbor;
                         new_thread.path = result_path;
+
+                        // Each result carries the captures of its own
+                        // iterations, already rooted at the thread's root
+                        for (name, capture_idx) in capture_map {
+                            if let Some(capture_paths) = captures.get(name) {
+                                new_thread.captures_mut(*capture_idx).extend(
+                                    capture_paths.iter().map(|path| {
+                                        (th.positions.clone(), path.clone())
+                                    }),
+                                );
+                            }
+                        }
+
                         new_thread.pc += 1;
                         stack.push(new_thread);
                     }
                     break;
                 }
+                MatchBackref(idx) => {
+                    let bound = th
+                        .captures
+                        .get(idx)
+                        .and_then(|paths| paths.last())
+                        .and_then(|(_, path)| path.last());
+                    if bound != Some(&th.cbor) {
+                        break; // Unbound or different value
+                    }
+                    th.pc += 1;
+                }
                 CaptureStart(idx) => {
                     // Initialize capture group
-                    while th.captures.len() <= idx {
-                        th.captures.push(Vec::new());
-                    }
+                    th.captures_mut(idx);
                     while th.capture_stack.len() <= idx {
                         th.capture_stack.push(Vec::new());
                     }
@@ -453,9 +724,11 @@ fn run_thread(
                         // For captures, we want to capture the full path to the
                         // current CBOR value
                         // not just the delta since CaptureStart
-                        let captured_path = th.path.clone();
-                        if let Some(captures) = th.captures.get_mut(idx) {
-                            captures.push(captured_path);
+                        let captured = (th.positions.clone(), th.path.clone());
+                        if idx < th.captures.len()
+                            && !captured_at_node(&th.captures[idx], &captured)
+                        {
+                            th.captures_mut(idx).push(captured);
                         }
                     }
                     th.pc += 1;
@@ -467,19 +740,82 @@ fn run_thread(
     produced
 }
 
+/// Returns `true` if `captured` is already among `captures`, having been made
+/// at the same node by another conjunct of an `&` with the same capture name.
+///
+/// Captures made at or below a node are the latest ones made, so the search
+/// stops at the first capture made elsewhere.
+fn captured_at_node(captures: &[Captured], captured: &Captured) -> bool {
+    let (positions, _) = captured;
+    captures
+        .iter()
+        .rev()
+        .take_while(|(earlier, _)| earlier.starts_with(positions))
+        .any(|earlier| earlier == captured)
+}
+
+/// A path as a hash key, hashed by the shape of its nodes rather than all of
+/// their contents.
+///
+/// The paths a program produces share their nodes, and often all start at
+/// a large root. Hashing each in full would take time in the size of the
+/// whole tree for every path, while comparing shared nodes is immediate.
+#[derive(PartialEq, Eq)]
+struct PathKey<'a>(&'a Path);
+
+impl Hash for PathKey<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_usize(self.0.len());
+        for node in self.0 {
+            match node.as_case() {
+                CBORCase::Array(elements) => (0u8, elements.len()).hash(state),
+                CBORCase::Map(map) => (1u8, map.len()).hash(state),
+                CBORCase::Tagged(tag, _) => (2u8, tag.value()).hash(state),
+                _ => node.hash(state),
+            }
+        }
+    }
+}
+
+/// Merges the captures made by several ways of matching into one list,
+/// preserving order.
+///
+/// Each capture is keyed by the position it was made at. A capture made the
+/// same number of times at the same position by each way of matching is a
+/// single capture counted several times, so it appears that number of times
+/// in the result, however many ways made it.
+pub(crate) fn merge_captures<'a, K: std::hash::Hash + Eq + 'a>(
+    groups: impl IntoIterator<Item = &'a Vec<(K, Path)>>,
+) -> Vec<Path> {
+    let mut merged = Vec::new();
+    let mut kept: HashMap<(&K, PathKey), usize> = HashMap::new();
+    for group in groups {
+        let mut counts: HashMap<(&K, PathKey), usize> = HashMap::new();
+        for (key, path) in group {
+            let count = counts.entry((key, PathKey(path))).or_default();
+            *count += 1;
+            let kept_count = kept.entry((key, PathKey(path))).or_default();
+            if *count > *kept_count {
+                *kept_count = *count;
+                merged.push(path.clone());
+            }
+        }
+    }
+    merged
+}
+
 /// Execute a program against a dCBOR value, returning all matching paths and
 /// captures.
-pub fn run(
-    prog: &Program,
-    root: &CBOR,
-) -> (Vec<Path>, HashMap<String, Vec<Path>>) {
+pub fn run(prog: &Program, root: &CBOR) -> (Vec<Path>, Captures) {
     let start = Thread {
         pc: 0,
         cbor: root.clone(),
         path: vec![root.clone()],
+        positions: Vec::new(),
         saved_paths: Vec::new(),
-        captures: Vec::new(),
+        captures: Arc::new(Vec::new()),
         capture_stack: Vec::new(),
+        axis_stack: Vec::new(),
     };
 
     let mut results = Vec::new();
@@ -490,58 +826,200 @@ pub fn run(
     let paths: Vec<Path> = results
         .iter()
         .filter_map(|(path, _)| {
-            if seen_paths.contains(path) {
-                None // Already seen, skip
-            } else {
-                seen_paths.insert(path.clone());
+            if seen_paths.insert(PathKey(path)) {
                 Some(path.clone()) // First occurrence, keep
+            } else {
+                None // Already seen, skip
             }
         })
         .collect();
 
-    // Build capture map from capture names and results
-    // Collect all captured paths from all threads, then deduplicate per capture
-    // while preserving order
-    let mut captures = HashMap::new();
+    // Build capture map from capture names and results. Threads that make
+    // the same capture at the same position count it once, while captures
+    // of equal values at different positions are all kept.
+    let mut captures = Captures::new();
     for (i, name) in prog.capture_names.iter().enumerate() {
-        let mut captured_paths = Vec::new();
-        for (_, thread_captures) in &results {
-            if let Some(capture_group) = thread_captures.get(i) {
-                captured_paths.extend(capture_group.clone());
-            }
-        }
-
-        // Deduplicate captured paths for this capture name while preserving
-        // order
+        let captured_paths = merge_captures(
+            results
+                .iter()
+                .filter_map(|(_, thread_captures)| thread_captures.get(i)),
+        );
         if !captured_paths.is_empty() {
-            let mut seen_capture_paths = HashSet::new();
-            let deduplicated_captured_paths: Vec<Path> = captured_paths
-                .into_iter()
-                .filter(|path| {
-                    if seen_capture_paths.contains(path) {
-                        false // Already seen, skip
-                    } else {
-                        seen_capture_paths.insert(path.clone());
-                        true // First occurrence, keep
-                    }
-                })
-                .collect();
-            captures.insert(name.clone(), deduplicated_captured_paths);
+            captures.insert(name.clone(), captured_paths);
         }
     }
 
+    debug_assert!(
+        paths
+            .iter()
+            .chain(captures.values().flatten())
+            .all(is_path_chain),
+        "VM emitted a path that is not a chain of parent-child steps"
+    );
+
     (paths, captures)
 }
 
+/// Returns `true` if each node of `path` can be reached from the one before
+/// it, as every path the VM emits should be.
+///
+/// A node is reached from its parent as an array element, a map key or
+/// value, or tagged content. Some patterns also step to values they build:
+/// a run of consecutive array elements, a map entry as a `[key, value]`
+/// array or a map of some of the entries, a named regex group within
+/// text, bytes, or a date, and the item encoded in a byte string.
+fn is_path_chain(path: &Path) -> bool {
+    path.windows(2).all(|step| is_path_step(&step[0], &step[1]))
+}
+
+fn is_path_step(parent: &CBOR, child: &CBOR) -> bool {
+    match (parent.as_case(), child.as_case()) {
+        (CBORCase::Array(elements), CBORCase::Array(run)) => {
+            run.is_empty()
+                || elements.contains(child)
+                || elements.windows(run.len()).any(|window| window == run)
+        }
+        (CBORCase::Array(elements), _) => elements.contains(child),
+        (CBORCase::Map(map), CBORCase::Array(entry)) => {
+            map.iter()
+                .any(|(key, value)| key == child || value == child)
+                || matches!(entry.as_slice(), [key, value]
+                    if map.iter().any(|entry| entry == (key, value)))
+        }
+        (CBORCase::Map(map), CBORCase::Map(entries)) => {
+            map.iter()
+                .any(|(key, value)| key == child || value == child)
+                || entries
+                    .iter()
+                    .all(|entry| map.iter().any(|other| other == entry))
+        }
+        (CBORCase::Map(map), _) => map
+            .iter()
+            .any(|(key, value)| key == child || value == child),
+        (CBORCase::Tagged(_, content), CBORCase::Text(_)) => {
+            // A date's regex groups match its text form
+            content == child
+                || super::DatePattern::any()
+                    .any_encoding()
+                    .date_of(parent)
+                    .is_some()
+        }
+        (CBORCase::Tagged(_, content), _) => content == child,
+        (CBORCase::Text(text), CBORCase::Text(group)) => {
+            text.contains(group.as_str())
+        }
+        (CBORCase::ByteString(bytes), CBORCase::ByteString(group))
+            if group.is_empty()
+                || bytes
+                    .windows(group.len())
+                    .any(|window| window == &group[..]) =>
+        {
+            true
+        }
+        (CBORCase::ByteString(bytes), _) => {
+            child.to_cbor_data()[..] == bytes[..]
+        }
+        _ => false,
+    }
+}
+
 /// VM for executing pattern programs against dCBOR values.
 pub struct Vm;
 
 impl Vm {
     /// Execute a program against a dCBOR value.
-    pub fn run(
-        prog: &Program,
-        root: &CBOR,
-    ) -> (Vec<Path>, HashMap<String, Vec<Path>>) {
+    pub fn run(prog: &Program, root: &CBOR) -> (Vec<Path>, Captures) {
         run(prog, root)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `repeat_paths` for `@n(number)` and returns, for each result in
+    /// order, the length of its path and how many `n` captures it made.
+    fn repeat_results(
+        cbor: &CBOR,
+        quantifier: Quantifier,
+    ) -> Vec<(usize, usize)> {
+        let pattern = Pattern::capture("n", Pattern::any_number());
+        repeat_paths(&pattern, cbor, &vec![cbor.clone()], quantifier)
+            .into_iter()
+            .map(|(_, path, captures)| {
+                (path.len(), captures.get("n").map_or(0, Vec::len))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_repeat_captures_follow_reluctance() {
+        let number = CBOR::from(1);
+
+        // Greedy tries the single iteration only, so the number is captured
+        let greedy = Quantifier::new(0..=1, Reluctance::Greedy);
+        assert_eq!(repeat_results(&number, greedy), vec![(1, 1)]);
+
+        // Lazy tries zero iterations first, which capture nothing
+        let lazy = Quantifier::new(0..=1, Reluctance::Lazy);
+        assert_eq!(repeat_results(&number, lazy), vec![(1, 0), (1, 1)]);
+
+        // Without a match, only the zero-iteration result remains
+        let text = CBOR::from("a");
+        assert_eq!(repeat_results(&text, greedy), vec![(1, 0)]);
+        assert_eq!(repeat_results(&text, lazy), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn test_repeat_non_advancing_match_counts_once() {
+        // A match that stays on the same node satisfies one iteration but
+        // is not repeated, so `{2,}` cannot be reached by matching twice
+        let number = CBOR::from(1);
+        let one_or_more = Quantifier::new(1.., Reluctance::Greedy);
+        assert_eq!(repeat_results(&number, one_or_more), vec![(1, 1)]);
+
+        let two_or_more = Quantifier::new(2.., Reluctance::Greedy);
+        assert!(repeat_results(&number, two_or_more).is_empty());
+    }
+
+    #[test]
+    fn test_forked_threads_share_captures_until_written() {
+        let root = CBOR::from(1);
+        let mut th = Thread {
+            pc: 0,
+            cbor: root.clone(),
+            path: vec![root.clone()],
+            positions: Vec::new(),
+            saved_paths: Vec::new(),
+            captures: Arc::new(Vec::new()),
+            capture_stack: Vec::new(),
+            axis_stack: Vec::new(),
+        };
+        th.captures_mut(0).push((vec![], vec![root.clone()]));
+        let mut fork = th.clone();
+        assert!(Arc::ptr_eq(&th.captures, &fork.captures));
+
+        // Writing copies the captures for the writer alone
+        fork.captures_mut(1).push((vec![], vec![root.clone()]));
+        assert!(!Arc::ptr_eq(&th.captures, &fork.captures));
+        assert_eq!(th.captures.len(), 1);
+        assert_eq!(fork.captures.len(), 2);
+        assert_eq!(fork.captures[0], th.captures[0]);
+    }
+
+    #[test]
+    fn test_merge_captures_keys_paths_by_content() {
+        let root: CBOR = vec![vec![1], vec![1], vec![2]].into();
+        let elements: Vec<CBOR> = vec![vec![1].into(), vec![2].into()];
+        let path = |element: &CBOR| vec![root.clone(), element.clone()];
+
+        // Paths of equal shape but different content are different captures,
+        // and equal paths from separate nodes are the same
+        let first = vec![(0, path(&elements[0])), (0, path(&elements[1]))];
+        let second = vec![(0, path(&vec![1].into())), (1, path(&elements[0]))];
+        assert_eq!(
+            merge_captures([&first, &second]),
+            vec![path(&elements[0]), path(&elements[1]), path(&elements[0])]
+        );
+    }
+}
@@ -1,22 +1,63 @@
-use std::ops::RangeBounds;
+use std::ops::{Bound, RangeBounds};
 
-use crate::{Interval, Reluctance};
+use crate::{Error, Interval, Reluctance, Result};
 
 /// Defines how many times a pattern may or must match, with an interval and a
 /// reluctance.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quantifier {
     interval: Interval,
     reluctance: Reluctance,
 }
 
 impl Quantifier {
+    /// Creates a quantifier allowing the counts in `interval`.
+    ///
+    /// An interval with no counts, such as `3..=1`, is raised to the single
+    /// count of its minimum, as [`Interval::new`] does. Use
+    /// [`Quantifier::try_new`] to reject such intervals instead.
     pub fn new(
         interval: impl RangeBounds<usize>,
         reluctance: Reluctance,
     ) -> Self {
-        let repeat = Interval::new(interval);
-        Self { interval: repeat, reluctance }
+        Self { interval: Interval::new(interval), reluctance }
+    }
+
+    /// Creates a quantifier allowing the counts in `interval`, failing with
+    /// [`Error::InvalidQuantifier`] if it contains no counts.
+    pub fn try_new(
+        interval: impl RangeBounds<usize>,
+        reluctance: Reluctance,
+    ) -> Result<Self> {
+        let min = match interval.start_bound() {
+            Bound::Included(&start) => Some(start),
+            Bound::Excluded(&start) => start.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let max = match interval.end_bound() {
+            Bound::Included(&end) => Some(Some(end)),
+            Bound::Excluded(&end) => end.checked_sub(1).map(Some),
+            Bound::Unbounded => Some(None),
+        };
+        let (Some(min), Some(max)) = (min, max) else {
+            return Err(Error::InvalidQuantifier(
+                "the interval is empty".to_string(),
+            ));
+        };
+        if let Some(max) = max
+            && min > max
+        {
+            return Err(Error::InvalidQuantifier(format!(
+                "the minimum {} is greater than the maximum {}",
+                min, max
+            )));
+        }
+        let interval = match max {
+            Some(max) => Interval::new(min..=max),
+            None => Interval::new(min..),
+        };
+        Ok(Self { interval, reluctance })
     }
 
     pub fn min(&self) -> usize { self.interval.min() }
@@ -38,6 +79,9 @@ impl Default for Quantifier {
     fn default() -> Self { Quantifier::new(1..=1, Reluctance::Greedy) }
 }
 
+/// Displays the quantifier in its shortest form: `?`, `*`, or `+` where one
+/// applies, and otherwise `{n}`, `{,m}`, `{n,}`, or `{n,m}`, followed by `?`
+/// if it is lazy or `+` if it is possessive.
 impl std::fmt::Display for Quantifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -72,5 +116,54 @@ mod tests {
         assert_eq!(format!("{}", Quantifier::new(0..=1, Reluctance::Greedy)), "?");
         assert_eq!(format!("{}", Quantifier::new(0..=1, Reluctance::Lazy)), "??");
         assert_eq!(format!("{}", Quantifier::new(0..=1, Reluctance::Possessive)), "?+");
+        assert_eq!(format!("{}", Quantifier::new(0..=3, Reluctance::Lazy)), "{,3}?");
+        assert_eq!(format!("{}", Quantifier::new(..3, Reluctance::Possessive)), "{,2}+");
+        assert_eq!(format!("{}", Quantifier::new(0..=0, Reluctance::Greedy)), "{0}");
+    }
+
+    #[test]
+    fn test_quantifier_try_new() {
+        let lazy = Reluctance::Lazy;
+        assert_eq!(
+            Quantifier::try_new(2..=2, lazy),
+            Ok(Quantifier::new(2..=2, lazy))
+        );
+        assert_eq!(
+            Quantifier::try_new(2..5, lazy),
+            Ok(Quantifier::new(2..=4, lazy))
+        );
+        assert_eq!(
+            Quantifier::try_new(.., lazy),
+            Ok(Quantifier::new(0.., lazy))
+        );
+
+        // Intervals with no counts in them
+        let (min, max) = (3, 1);
+        assert_eq!(
+            Quantifier::try_new(min..=max, lazy),
+            Err(Error::InvalidQuantifier(
+                "the minimum 3 is greater than the maximum 1".to_string()
+            ))
+        );
+        for error in [
+            Quantifier::try_new(..0, lazy),
+            Quantifier::try_new(2..2, lazy),
+            Quantifier::try_new(
+                (Bound::Excluded(usize::MAX), Bound::Unbounded),
+                lazy,
+            ),
+        ] {
+            assert!(matches!(error, Err(Error::InvalidQuantifier(_))));
+        }
+    }
+
+    #[test]
+    fn test_quantifier_new_clamps_empty_interval() {
+        let (min, max) = (3, 1);
+        assert_eq!(
+            Quantifier::new(min..=max, Reluctance::Greedy),
+            Quantifier::new(3..=3, Reluctance::Greedy)
+        );
+        assert_eq!(Quantifier::new(..0, Reluctance::Lazy).to_string(), "{0}?");
     }
 }
@@ -1,5 +1,6 @@
 /// Reluctance for quantifiers.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Reluctance {
     /// Grabs as many repetitions as possible, then backtracks if the rest of
     /// the pattern cannot match.
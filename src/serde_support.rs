@@ -0,0 +1,399 @@
+//! Serde adapters for the foreign types embedded in patterns.
+//!
+//! These modules are used with `#[serde(with = "...")]` on pattern variants
+//! whose payloads don't implement `Serialize`/`Deserialize` themselves.
+//! Regular expressions are stored as their source strings and recompiled on
+//! deserialization, which fails if the stored source is not a valid regex.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+/// A text regex, stored as its source string.
+pub(crate) mod regex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        regex: &::regex::Regex,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        regex.as_str().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<::regex::Regex, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        ::regex::Regex::new(&source).map_err(D::Error::custom)
+    }
+}
+
+/// A binary regex, stored as its source string.
+pub(crate) mod bytes_regex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        regex: &::regex::bytes::Regex,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        regex.as_str().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<::regex::bytes::Regex, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        ::regex::bytes::Regex::new(&source).map_err(D::Error::custom)
+    }
+}
+
+/// A byte string, stored as lowercase hex.
+pub(crate) mod hex_bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        bytes: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        hex::encode(bytes).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        hex::decode(encoded).map_err(D::Error::custom)
+    }
+}
+
+/// A set of byte strings, stored as lowercase hex.
+pub(crate) mod hex_bytes_set {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        values: &BTreeSet<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        values
+            .iter()
+            .map(hex::encode)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeSet<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|encoded| hex::decode(encoded).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// A set of numbers, stored with infinities as the strings `"Infinity"` and
+/// `"-Infinity"`, which formats such as JSON cannot otherwise represent.
+pub(crate) mod number_set {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Finite(f64),
+        Named(String),
+    }
+
+    pub fn serialize<S: Serializer>(
+        values: &[f64],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        values
+            .iter()
+            .map(|&value| match value {
+                f64::INFINITY => Number::Named("Infinity".to_string()),
+                f64::NEG_INFINITY => Number::Named("-Infinity".to_string()),
+                value => Number::Finite(value),
+            })
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<f64>, D::Error> {
+        let mut values = Vec::<Number>::deserialize(deserializer)?
+            .into_iter()
+            .map(|number| match number {
+                Number::Finite(value) if !value.is_nan() => Ok(value + 0.0),
+                Number::Named(name) if name == "Infinity" => Ok(f64::INFINITY),
+                Number::Named(name) if name == "-Infinity" => {
+                    Ok(f64::NEG_INFINITY)
+                }
+                _ => Err(D::Error::custom("invalid number in set")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        values.sort_by(f64::total_cmp);
+        values.dedup();
+        Ok(values)
+    }
+}
+
+/// A CBOR tag, stored as its numeric value.
+pub(crate) mod tag {
+    use dcbor::prelude::*;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        tag: &Tag,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        tag.value().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Tag, D::Error> {
+        Ok(Tag::with_value(u64::deserialize(deserializer)?))
+    }
+}
+
+/// A date, stored as seconds since the Unix epoch.
+pub(crate) mod date {
+    use dcbor::Date;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        date: &Date,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        date.timestamp().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Date, D::Error> {
+        Ok(Date::from_timestamp(f64::deserialize(deserializer)?))
+    }
+}
+
+/// An inclusive date range, stored as a pair of timestamps.
+pub(crate) mod date_range {
+    use std::ops::RangeInclusive;
+
+    use dcbor::Date;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        range: &RangeInclusive<Date>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        (range.start().timestamp()..=range.end().timestamp())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<RangeInclusive<Date>, D::Error> {
+        let range = RangeInclusive::<f64>::deserialize(deserializer)?;
+        Ok(Date::from_timestamp(*range.start())
+            ..=Date::from_timestamp(*range.end()))
+    }
+}
+
+/// A digest, stored as lowercase hex.
+pub(crate) mod digest {
+    use bc_components::Digest;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        digest: &Digest,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        digest.hex().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Digest, D::Error> {
+        let bytes = hex_bytes::deserialize(deserializer)?;
+        Digest::from_data_ref(bytes).map_err(D::Error::custom)
+    }
+}
+
+/// A dCBOR item, stored as the hex of its deterministic encoding.
+pub(crate) mod cbor {
+    use dcbor::CBOR;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        cbor: &CBOR,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        cbor.hex().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<CBOR, D::Error> {
+        let bytes = hex_bytes::deserialize(deserializer)?;
+        CBOR::try_from_data(bytes).map_err(D::Error::custom)
+    }
+}
+
+/// A known value, stored as its numeric value.
+pub(crate) mod known_value {
+    use known_values::KnownValue;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        value: &KnownValue,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.value().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<KnownValue, D::Error> {
+        Ok(KnownValue::new(u64::deserialize(deserializer)?))
+    }
+}
+
+/// A list of known values, stored as their numeric values.
+pub(crate) mod known_value_set {
+    use known_values::KnownValue;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        values: &[KnownValue],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        values
+            .iter()
+            .map(KnownValue::value)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<KnownValue>, D::Error> {
+        let values = Vec::<u64>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(KnownValue::new).collect())
+    }
+}
+
+/// A list of paths, each stored as a list of the hex of its nodes'
+/// deterministic encodings.
+pub(crate) mod paths {
+    use dcbor::CBOR;
+
+    use super::*;
+    use crate::pattern::Path;
+
+    struct Nodes<'a>(&'a [CBOR]);
+
+    impl Serialize for Nodes<'_> {
+        fn serialize<S: Serializer>(
+            &self,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter().map(CBOR::hex))
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        paths: &[Path],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| Nodes(path)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Path>, D::Error> {
+        Vec::<Vec<String>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|path| {
+                path.into_iter()
+                    .map(|encoded| {
+                        let bytes =
+                            hex::decode(encoded).map_err(D::Error::custom)?;
+                        CBOR::try_from_data(bytes).map_err(D::Error::custom)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Captures, stored as a map from each name to its paths, with the names in
+/// the order they were first captured.
+pub(crate) mod captures {
+    use std::fmt;
+
+    use serde::de::{MapAccess, Visitor};
+
+    use super::*;
+    use crate::pattern::{Captures, Path};
+
+    struct BorrowedPaths<'a>(&'a [Path]);
+
+    impl Serialize for BorrowedPaths<'_> {
+        fn serialize<S: Serializer>(
+            &self,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            super::paths::serialize(self.0, serializer)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    struct OwnedPaths(#[serde(with = "super::paths")] Vec<Path>);
+
+    pub fn serialize<S: Serializer>(
+        captures: &Captures,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            captures
+                .iter()
+                .map(|(name, paths)| (name, BorrowedPaths(paths))),
+        )
+    }
+
+    struct CapturesVisitor;
+
+    impl<'de> Visitor<'de> for CapturesVisitor {
+        type Value = Captures;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a map from capture names to paths")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> Result<Captures, A::Error> {
+            let mut captures = Captures::new();
+            while let Some((name, OwnedPaths(paths))) = map.next_entry()? {
+                captures.insert(name, paths);
+            }
+            Ok(captures)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Captures, D::Error> {
+        deserializer.deserialize_map(CapturesVisitor)
+    }
+}
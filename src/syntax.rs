@@ -0,0 +1,408 @@
+//! A registry of the pattern syntax, checked against the parser.
+//!
+//! [`grammar_summary`] lists each production of the syntax as a
+//! [`SyntaxRule`]: a name, an example in pattern text, and a builder for the
+//! [`Pattern`] that text should parse to. The crate's tests parse every
+//! example and compare it with what its builder produces, and parse each
+//! built pattern's display again, so the lexer, the parsers, and the
+//! `Display` implementations can't drift apart without a rule failing.
+//!
+//! ```
+//! use dcbor_pattern::{Pattern, syntax::grammar_summary};
+//!
+//! for rule in grammar_summary() {
+//!     let parsed = Pattern::parse(rule.example()).unwrap();
+//!     assert_eq!(parsed, rule.build(), "{}", rule.name());
+//! }
+//! ```
+//!
+//! Two productions are left out because they depend on state outside the
+//! pattern: `custom(name)`, which needs a matcher registered with
+//! [`Pattern::register_custom`], and `$name`, which needs a
+//! [`PatternLibrary`](crate::PatternLibrary).
+
+use std::time::Duration;
+
+use bc_components::Digest;
+use dcbor::{Date, prelude::*};
+use known_values::KnownValue;
+
+use crate::{
+    ArrayPattern, DatePattern, Interval, MapPattern, Pattern, SearchOpts,
+    StructurePattern,
+    dsl::{self, PatternExt},
+};
+
+/// One production of the pattern syntax, with an example of it and the
+/// pattern the example parses to.
+#[derive(Debug, Clone, Copy)]
+pub struct SyntaxRule {
+    name: &'static str,
+    example: &'static str,
+    build: fn() -> Pattern,
+}
+
+impl SyntaxRule {
+    const fn new(
+        name: &'static str,
+        example: &'static str,
+        build: fn() -> Pattern,
+    ) -> Self {
+        Self { name, example, build }
+    }
+
+    /// A short description of the production, such as `"number range"`.
+    pub fn name(&self) -> &'static str { self.name }
+
+    /// An example of the production in pattern text.
+    pub fn example(&self) -> &'static str { self.example }
+
+    /// Builds the pattern [`example`](Self::example) parses to.
+    pub fn build(&self) -> Pattern { (self.build)() }
+}
+
+fn date(iso: &str) -> Date { Date::from_string(iso).unwrap() }
+
+fn digest() -> Digest { Digest::from_image(b"abc") }
+
+fn array(pattern: ArrayPattern) -> Pattern {
+    Pattern::Structure(StructurePattern::Array(pattern))
+}
+
+fn map(pattern: MapPattern) -> Pattern {
+    Pattern::Structure(StructurePattern::Map(pattern))
+}
+
+/// Returns a rule for each production of the pattern syntax, grouped as in
+/// the syntax reference: value patterns, then structure patterns, then meta
+/// patterns.
+pub fn grammar_summary() -> Vec<SyntaxRule> {
+    vec![
+        // Boolean
+        SyntaxRule::new("any boolean", "bool", dsl::bool),
+        SyntaxRule::new("true", "true", || Pattern::bool(true)),
+        SyntaxRule::new("false", "false", || Pattern::bool(false)),
+        // Byte string
+        SyntaxRule::new("any byte string", "bstr", dsl::bstr),
+        SyntaxRule::new("byte string", "h'cafe'", || {
+            Pattern::byte_string([0xca, 0xfe])
+        }),
+        SyntaxRule::new("byte string regex", "h'/^\\x00/'", || {
+            Pattern::byte_string_regex(
+                regex::bytes::Regex::new("^\\x00").unwrap(),
+            )
+        }),
+        SyntaxRule::new("byte string set", "bstr(h'00', h'01')", || {
+            Pattern::byte_string_in([[0x00], [0x01]])
+        }),
+        SyntaxRule::new("byte string prefix", "bstr(prefix: h'ca')", || {
+            Pattern::byte_string_prefix([0xca])
+        }),
+        SyntaxRule::new("byte string length", "bstr({2,4})", || {
+            Pattern::byte_string_with_length(Interval::new(2..=4))
+        }),
+        SyntaxRule::new("encoded CBOR", "bstr(cbor: [number])", || {
+            dsl::bstr_cbor(dsl::array([dsl::number()]))
+        }),
+        // CBOR
+        SyntaxRule::new("CBOR value", "cbor'[1, \"a\"]'", || {
+            Pattern::cbor(vec![CBOR::from(1), CBOR::from("a")])
+        }),
+        // Date
+        SyntaxRule::new("any date", "date", dsl::date),
+        SyntaxRule::new("date", "date'2023-12-25'", || {
+            Pattern::date(date("2023-12-25"))
+        }),
+        SyntaxRule::new("date range", "date'2023-12-24...2023-12-26'", || {
+            Pattern::date_range(date("2023-12-24")..=date("2023-12-26"))
+        }),
+        SyntaxRule::new("earliest date", "date'2023-12-24...'", || {
+            Pattern::date_earliest(date("2023-12-24"))
+        }),
+        SyntaxRule::new("latest date", "date'...2023-12-26'", || {
+            Pattern::date_latest(date("2023-12-26"))
+        }),
+        SyntaxRule::new("date regex", "date'/^2023-/'", || {
+            Pattern::date_regex(regex::Regex::new("^2023-").unwrap())
+        }),
+        SyntaxRule::new("past date", "date'past'", Pattern::date_past),
+        SyntaxRule::new("future date", "date'future'", Pattern::date_future),
+        SyntaxRule::new("date within the past", "date'past 30d'", || {
+            Pattern::date_within_past(Duration::from_secs(30 * 24 * 60 * 60))
+        }),
+        SyntaxRule::new("date within the future", "date'future 2h'", || {
+            Pattern::date_within_future(Duration::from_secs(2 * 60 * 60))
+        }),
+        SyntaxRule::new("date in any encoding", "date-any", || {
+            Pattern::date_any_encoding(DatePattern::any())
+        }),
+        SyntaxRule::new(
+            "date value in any encoding",
+            "date-any'2023-12-25'",
+            || {
+                Pattern::date_any_encoding(DatePattern::value(date(
+                    "2023-12-25",
+                )))
+            },
+        ),
+        // Known value
+        SyntaxRule::new("any known value", "known", dsl::known),
+        SyntaxRule::new("known value", "'1'", || {
+            Pattern::known_value(KnownValue::new(1))
+        }),
+        SyntaxRule::new("named known value", "'isA'", || {
+            Pattern::known_value_named("isA")
+        }),
+        SyntaxRule::new("known value regex", "'/^is/'", || {
+            Pattern::known_value_regex(regex::Regex::new("^is").unwrap())
+        }),
+        SyntaxRule::new("known value set", "known('1' | '2')", || {
+            Pattern::known_value_set([KnownValue::new(1), KnownValue::new(2)])
+        }),
+        SyntaxRule::new("known value range", "known(1...5)", || {
+            Pattern::known_value_range(1..=5)
+        }),
+        // Null
+        SyntaxRule::new("null", "null", dsl::null),
+        // Number
+        SyntaxRule::new("any number", "number", dsl::number),
+        SyntaxRule::new("number", "42", || Pattern::number(42)),
+        SyntaxRule::new("number range", "1...10", || {
+            Pattern::number_range(1..=10)
+        }),
+        SyntaxRule::new("number at least", ">=5", || {
+            Pattern::number_greater_than_or_equal(5)
+        }),
+        SyntaxRule::new("number at most", "<=5", || {
+            Pattern::number_less_than_or_equal(5)
+        }),
+        SyntaxRule::new("number greater than", ">5", || {
+            Pattern::number_greater_than(5)
+        }),
+        SyntaxRule::new("number less than", "<5", || {
+            Pattern::number_less_than(5)
+        }),
+        SyntaxRule::new("NaN", "NaN", Pattern::number_nan),
+        SyntaxRule::new("infinity", "Infinity", Pattern::number_infinity),
+        SyntaxRule::new(
+            "negative infinity",
+            "-Infinity",
+            Pattern::number_neg_infinity,
+        ),
+        SyntaxRule::new("number set", "number(1, 2, Infinity)", || {
+            Pattern::number_in([1.0, 2.0, f64::INFINITY])
+        }),
+        // Text
+        SyntaxRule::new("any text", "text", dsl::text),
+        SyntaxRule::new("text", "\"hello\"", || Pattern::text("hello")),
+        SyntaxRule::new("text regex", "/^h.*o$/", || {
+            Pattern::text_regex(regex::Regex::new("^h.*o$").unwrap())
+        }),
+        SyntaxRule::new("text set", "text(\"a\", \"b\")", || {
+            Pattern::text_in(["a", "b"])
+        }),
+        SyntaxRule::new("text prefix", "text(^\"ab\")", || {
+            Pattern::text_prefix("ab")
+        }),
+        SyntaxRule::new("text suffix", "text($\"yz\")", || {
+            Pattern::text_suffix("yz")
+        }),
+        SyntaxRule::new("case-insensitive text", "text(~\"Hi\")", || {
+            Pattern::text_case_insensitive("Hi")
+        }),
+        // Digest
+        SyntaxRule::new("any digest", "digest", dsl::digest),
+        SyntaxRule::new("digest prefix", "digest'ba7816'", || {
+            Pattern::digest_prefix([0xba, 0x78, 0x16])
+        }),
+        SyntaxRule::new(
+            "digest",
+            "digest'ur:digest/hdcxrdkscmrsmyadtkwdfpfpfzuehlplcpcnpfaxhsotmtch\
+             knnsqzbezmhswzaebzpmldvduymn'",
+            || Pattern::digest(digest()),
+        ),
+        SyntaxRule::new("digest regex", "digest'/^\\xba/'", || {
+            Pattern::digest_binary_regex(
+                regex::bytes::Regex::new("^\\xba").unwrap(),
+            )
+        }),
+        SyntaxRule::new(
+            "encoded digest prefix",
+            "encoded-digest'ba7816'",
+            || Pattern::encoded_digest_prefix([0xba, 0x78, 0x16]),
+        ),
+        // Array
+        SyntaxRule::new("any array", "array", dsl::any_array),
+        SyntaxRule::new("array length", "[{2,4}]", || dsl::array_len(2..=4)),
+        SyntaxRule::new("array minimum length", "[{2,}]", || {
+            dsl::array_len(2..)
+        }),
+        SyntaxRule::new("array maximum length", "[{,4}]", || {
+            dsl::array_len(..=4)
+        }),
+        SyntaxRule::new("array element", "[number]", || {
+            dsl::array([dsl::number()])
+        }),
+        SyntaxRule::new("array sequence", "[number, (*)*]", || {
+            dsl::array([dsl::number(), dsl::any().repeat(0..)])
+        }),
+        SyntaxRule::new("grouped sequence", "[(number, text) | null]", || {
+            dsl::array([Pattern::sequence(vec![dsl::number(), dsl::text()])
+                .or(dsl::null())])
+        }),
+        SyntaxRule::new(
+            "array length and elements",
+            "[{2,} & (number)*]",
+            || {
+                array(ArrayPattern::with_length_and_elements(
+                    Interval::new(2..),
+                    dsl::number().repeat(0..),
+                ))
+            },
+        ),
+        SyntaxRule::new("unordered array", "[~ number, text]", || {
+            array(ArrayPattern::with_elements_unordered(vec![
+                dsl::number(),
+                dsl::text(),
+            ]))
+        }),
+        // Map
+        SyntaxRule::new("any map", "map", dsl::any_map),
+        SyntaxRule::new("map length", "{{2,4}}", || dsl::map_len(2..=4)),
+        SyntaxRule::new("bare map length", "{0}", || dsl::map_len(0..=0)),
+        SyntaxRule::new("map entries", "{text: number, 1: *}", || {
+            dsl::map([
+                (dsl::text(), dsl::number()),
+                (Pattern::number(1), dsl::any()),
+            ])
+        }),
+        SyntaxRule::new(
+            "map length and entries",
+            "{{1,}, text: number}",
+            || {
+                map(MapPattern::with_constraints(
+                    Interval::new(1..),
+                    vec![(dsl::text(), dsl::number())],
+                ))
+            },
+        ),
+        SyntaxRule::new("counted map entries", "{(text: number){2,}}", || {
+            map(MapPattern::with_counted_constraints(
+                Interval::new(0..),
+                vec![MapPattern::entry(dsl::text(), dsl::number()).at_least(2)],
+                None,
+            ))
+        }),
+        SyntaxRule::new("map rest", "{\"type\": text, @rest(*)}", || {
+            map(MapPattern::with_rest(
+                Interval::new(0..),
+                vec![(Pattern::text("type"), dsl::text())],
+                dsl::capture("rest", dsl::any()),
+            ))
+        }),
+        SyntaxRule::new("map keys", "{keys: text}", || {
+            map(MapPattern::with_all_keys(dsl::text()))
+        }),
+        SyntaxRule::new("map values", "{values: number}", || {
+            map(MapPattern::with_all_values(dsl::number()))
+        }),
+        SyntaxRule::new(
+            "map keys and values",
+            "{keys: text, values: number}",
+            || map(MapPattern::with_all_entries(dsl::text(), dsl::number())),
+        ),
+        SyntaxRule::new("map value at key", "{\"a\"}: number", || {
+            dsl::map_value(Pattern::text("a"), dsl::number())
+        }),
+        // Tagged
+        SyntaxRule::new("any tagged value", "tagged", dsl::any_tagged),
+        SyntaxRule::new("tagged value", "tagged(100, number)", || {
+            dsl::tagged(100, dsl::number())
+        }),
+        SyntaxRule::new("tag range", "tagged(100...200, number)", || {
+            Pattern::tagged_range(100..=200, dsl::number())
+        }),
+        SyntaxRule::new("tag set", "tagged(1 | 100, number)", || {
+            Pattern::tagged_set([1, 100], dsl::number())
+        }),
+        SyntaxRule::new("tag name", "tagged(date, number)", || {
+            Pattern::tagged_name("date", dsl::number())
+        }),
+        SyntaxRule::new("tag name regex", "tagged(/^da/, number)", || {
+            Pattern::tagged_regex(
+                regex::Regex::new("^da").unwrap(),
+                dsl::number(),
+            )
+        }),
+        // Meta
+        SyntaxRule::new("any", "*", dsl::any),
+        SyntaxRule::new("and", "number & !0", || {
+            dsl::number().and(dsl::not(Pattern::number(0)))
+        }),
+        SyntaxRule::new("or", "number | text", || {
+            dsl::number().or(dsl::text())
+        }),
+        SyntaxRule::new("not", "!text", || dsl::not(dsl::text())),
+        SyntaxRule::new("group", "(number | text) & !0", || {
+            dsl::number()
+                .or(dsl::text())
+                .and(dsl::not(Pattern::number(0)))
+        }),
+        SyntaxRule::new("capture", "@n(number)", || {
+            dsl::capture("n", dsl::number())
+        }),
+        SyntaxRule::new("back-reference", "[@n(*), =n]", || {
+            dsl::array([dsl::capture("n", dsl::any()), dsl::backref("n")])
+        }),
+        SyntaxRule::new("entry", "entry(text: number)", || {
+            Pattern::entry(dsl::text(), dsl::number())
+        }),
+        SyntaxRule::new("none", "none(text)", || dsl::none(dsl::text())),
+        SyntaxRule::new("child", "child(number)", || dsl::child(dsl::number())),
+        SyntaxRule::new("untag", "untag(number)", || dsl::untag(dsl::number())),
+        SyntaxRule::new("at", r#"at("items", 2, number)"#, || {
+            dsl::at(["items".into(), 2.into()], dsl::number())
+        }),
+        SyntaxRule::new("search", "search(text)", || dsl::search(dsl::text())),
+        SyntaxRule::new(
+            "search with options",
+            "search(number, depth: 2, keys: false, tagged: false)",
+            || {
+                Pattern::search_with(
+                    dsl::number(),
+                    SearchOpts {
+                        max_depth: Some(2),
+                        visit_map_keys: false,
+                        visit_tagged_content: false,
+                    },
+                )
+            },
+        ),
+        // Repeat
+        SyntaxRule::new("repeat", "(number)*", || dsl::number().repeat(0..)),
+        SyntaxRule::new("one or more", "(number)+", || {
+            dsl::number().repeat(1..)
+        }),
+        SyntaxRule::new("optional", "(number)?", || {
+            dsl::number().repeat(0..=1)
+        }),
+        SyntaxRule::new("repeat range", "(number){2,5}", || {
+            dsl::number().repeat(2..=5)
+        }),
+        SyntaxRule::new("repeat at most", "(number){,3}", || {
+            dsl::number().repeat(..=3)
+        }),
+        SyntaxRule::new("repeat exactly once", "(number){1}", || {
+            dsl::number().repeat(1..=1)
+        }),
+        SyntaxRule::new("lazy repeat", "(number)*?", || {
+            dsl::number().repeat_lazy(0..)
+        }),
+        SyntaxRule::new("possessive repeat", "(number)++", || {
+            dsl::number().repeat_possessive(1..)
+        }),
+        SyntaxRule::new("unparenthesized repeat", "[number{3}]", || {
+            dsl::array([dsl::number().repeat(3..=3)])
+        }),
+    ]
+}
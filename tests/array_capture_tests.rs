@@ -3,7 +3,8 @@ mod common;
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
 use dcbor_pattern::{
-    Matcher, Pattern, format_paths, format_paths_with_captures,
+    ArrayPattern, Interval, Matcher, Pattern, Program, Quantifier, Reluctance,
+    StructurePattern, format_paths, format_paths_with_captures,
 };
 use indoc::indoc;
 
@@ -20,6 +21,8 @@ fn test_array_capture_basic() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // Validate formatted output with captures
     // expected-text-output-rubric:
     #[rustfmt::skip]
@@ -55,6 +58,8 @@ fn test_array_capture_multiple_items() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // Validate formatted output with captures
     // expected-text-output-rubric:
     #[rustfmt::skip]
@@ -84,6 +89,8 @@ fn test_array_capture_with_any_pattern() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // Validate formatted output with captures
     // expected-text-output-rubric:
     #[rustfmt::skip]
@@ -92,8 +99,6 @@ fn test_array_capture_with_any_pattern() {
             ["hello"]
                 "hello"
         ["hello"]
-            "hello"
-        ["hello"]
     "#}.trim();
     assert_actual_expected!(
         format_paths_with_captures(
@@ -159,6 +164,8 @@ fn test_array_nested_capture() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // Validate formatted output with nested captures
     // expected-text-output-rubric:
     #[rustfmt::skip]
@@ -194,3 +201,464 @@ fn test_array_capture_non_matching() {
         "No paths should be returned for non-matching pattern"
     );
 }
+
+/// Returns the single element captured as `@x` in each match.
+fn captured_x(pattern: &Pattern, array: &str) -> Vec<CBOR> {
+    let (_, captures) = pattern.paths_with_captures(&cbor(array));
+    captures
+        .get("x")
+        .map(|paths| {
+            paths
+                .iter()
+                .map(|path| path.last().unwrap().clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_array_capture_after_greedy_and_lazy_prefix() {
+    // A greedy prefix leaves the capture as late as possible
+    let greedy = parse("[(*)*, @x(number), (*)*]");
+    assert_eq!(captured_x(&greedy, "[1, 2, 3]"), vec![cbor("3")]);
+    assert_eq!(captured_x(&greedy, r#"[1, 2, "a"]"#), vec![cbor("2")]);
+    assert_eq!(captured_x(&greedy, "[5]"), vec![cbor("5")]);
+
+    // A lazy prefix leaves it as early as possible
+    let lazy = parse("[(*)*?, @x(number), (*)*]");
+    assert_eq!(lazy.to_string(), "[(*)*?, @x(number), (*)*]");
+    assert_eq!(parse(&lazy.to_string()), lazy);
+    assert_eq!(captured_x(&lazy, "[1, 2, 3]"), vec![cbor("1")]);
+    assert_eq!(captured_x(&lazy, r#"["a", 2, 3]"#), vec![cbor("2")]);
+    assert_eq!(captured_x(&lazy, "[5]"), vec![cbor("5")]);
+
+    // The reluctance of the trailing repeat doesn't move the capture
+    let lazy_both = parse("[(*)*?, @x(number), (*)*?]");
+    assert_eq!(captured_x(&lazy_both, "[1, 2, 3]"), vec![cbor("1")]);
+    let greedy_lazy = parse("[(*)*, @x(number), (*)*?]");
+    assert_eq!(captured_x(&greedy_lazy, "[1, 2, 3]"), vec![cbor("3")]);
+
+    // Matching doesn't depend on reluctance
+    for pattern in [&greedy, &lazy, &lazy_both, &greedy_lazy] {
+        assert!(pattern.matches(&cbor("[1, 2, 3]")));
+        assert!(!pattern.matches(&cbor(r#"["a", "b"]"#)));
+        assert!(captured_x(pattern, r#"["a", "b"]"#).is_empty());
+    }
+}
+
+#[test]
+fn test_array_length_and_elements() {
+    let pattern = parse("[{3,} & (number)*]");
+    assert!(pattern.matches(&cbor("[1, 2, 3]")));
+    assert!(pattern.matches(&cbor("[1, 2, 3, 4]")));
+    assert!(!pattern.matches(&cbor("[1, 2]")));
+    assert!(!pattern.matches(&cbor(r#"[1, 2, "3"]"#)));
+    assert!(!pattern.matches(&cbor("{1: 2, 3: 4, 5: 6}")));
+
+    // The length applies to the whole sequence
+    let pattern = parse("[{2,3} & text, (number)*]");
+    assert!(pattern.matches(&cbor(r#"["a", 1]"#)));
+    assert!(pattern.matches(&cbor(r#"["a", 1, 2]"#)));
+    assert!(!pattern.matches(&cbor(r#"["a"]"#)));
+    assert!(!pattern.matches(&cbor(r#"["a", 1, 2, 3]"#)));
+
+    // Built directly, or by `&` of a length and an element pattern
+    let elements = Pattern::sequence(vec![
+        Pattern::any_text(),
+        Pattern::repeat(
+            Pattern::any_number(),
+            Quantifier::new(0.., Reluctance::Greedy),
+        ),
+    ]);
+    assert_eq!(
+        pattern,
+        Pattern::Structure(StructurePattern::Array(
+            ArrayPattern::with_length_and_elements(
+                Interval::new(2..=3),
+                elements
+            )
+        ))
+    );
+    assert_eq!(parse("[text, (number)*] & [{2,3}]"), pattern);
+}
+
+#[test]
+fn test_array_length_and_elements_captures() {
+    let pattern = parse("[{3,} & @first(number), @rest((number)*)]");
+    let data = cbor("[1, 2, 3]");
+    let (paths, captures) = pattern.paths_with_captures(&data);
+    assert_path_chains!(&data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @first
+            [1, 2, 3]
+                1
+        @rest
+            [1, 2, 3]
+                [2, 3]
+        [1, 2, 3]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // Too short: the elements would match, but the length doesn't, so
+    // nothing is captured
+    let (paths, captures) = pattern.paths_with_captures(&cbor("[1, 2]"));
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+
+    // A capture of a single element, matched by the VM
+    let pattern = parse("[{2} & @n(number)]");
+    assert!(pattern.paths_with_captures(&cbor("[1]")).0.is_empty());
+    let pattern = parse("[{1} & @n(number)]");
+    let data = cbor("[7]");
+    let (paths, captures) = pattern.paths_with_captures(&data);
+    assert_path_chains!(&data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @n
+            [7]
+                7
+        [7]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // Inside a search, only arrays of the right length contribute captures
+    let pattern = parse("search([{2,} & @first(text), (*)*])");
+    let data = cbor(r#"[["a"], ["b", 1], ["c", 2, 3]]"#);
+    let (paths, captures) = pattern.paths_with_captures(&data);
+    assert_path_chains!(&data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @first
+            [["a"], ["b", 1], ["c", 2, 3]]
+                ["b", 1]
+                    "b"
+            [["a"], ["b", 1], ["c", 2, 3]]
+                ["c", 2, 3]
+                    "c"
+        [["a"], ["b", 1], ["c", 2, 3]]
+            ["b", 1]
+        [["a"], ["b", 1], ["c", 2, 3]]
+            ["c", 2, 3]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_array_capture_known_values_and_digests() {
+    // Known values by name in a sequence, with captures on either side
+    let pattern = parse("[@first('isA'), (@rest(!known))*]");
+    let cbor_data = cbor(r#"[40000(1), "Person", 7]"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @first
+            [40000(1), "Person", 7]
+                40000(1)
+        @rest
+            [40000(1), "Person", 7]
+                "Person"
+            [40000(1), "Person", 7]
+                7
+        [40000(1), "Person", 7]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // A digest followed by the value it identifies
+    let digest = "40001(h'0102030405060708091011121314151617181920212223242526272829303132')";
+    let pattern = parse("[digest'0102', @value(text)]");
+    let cbor_data = cbor(&format!(r#"[{}, "Alice"]"#, digest));
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    assert_eq!(paths, vec![vec![cbor_data.clone()]]);
+    assert_eq!(
+        captures["value"],
+        vec![vec![cbor_data.clone(), cbor(r#""Alice""#)]]
+    );
+    assert!(!parse("[digest'ff', @value(text)]").matches(&cbor_data));
+}
+
+#[test]
+fn test_array_sequence_nested_array_captures() {
+    // Two levels: the capture path runs from the outer array through the
+    // inner one
+    let pattern = parse("[[@a(number)], @b(text)]");
+    let cbor_data = cbor(r#"[[1], "x"]"#);
+    let result = pattern.run(&cbor_data);
+    assert_path_chains!(&cbor_data, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @a
+            [[1], "x"]
+                [1]
+                    1
+        @b
+            [[1], "x"]
+                "x"
+        [[1], "x"]
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected);
+
+    // The same, found by search below the root
+    let pattern = parse("search([[@a(number)], @b(text)])");
+    let cbor_data = cbor(r#"{"k": [[1], "x"]}"#);
+    let result = pattern.run(&cbor_data);
+    assert_path_chains!(&cbor_data, result.paths(), result.captures());
+    assert_eq!(
+        result.capture("a"),
+        Some(
+            &[vec![
+                cbor_data.clone(),
+                cbor(r#"[[1], "x"]"#),
+                cbor("[1]"),
+                cbor("1"),
+            ]][..]
+        )
+    );
+
+    // Three levels, with captures in the middle level and inside repeats
+    let pattern = parse("[[[(@a(number))*], @c(*)], @b(text)]");
+    let cbor_data = cbor(r#"[[[1, 2], 3], "x"]"#);
+    let result = pattern.run(&cbor_data);
+    assert_path_chains!(&cbor_data, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @a
+            [[[1, 2], 3], "x"]
+                [[1, 2], 3]
+                    [1, 2]
+                        1
+            [[[1, 2], 3], "x"]
+                [[1, 2], 3]
+                    [1, 2]
+                        2
+        @b
+            [[[1, 2], 3], "x"]
+                "x"
+        @c
+            [[[1, 2], 3], "x"]
+                [[1, 2], 3]
+                    3
+        [[[1, 2], 3], "x"]
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected);
+}
+
+#[test]
+fn test_array_sequence_captures_through_map() {
+    // A map between the arrays, with a sub-array capture at the bottom
+    let pattern = parse(r#"[{"k": [[@a(number)], @rest((*)*)]}, @b(text)]"#);
+    let cbor_data = cbor(r#"[{"k": [[1], 2, 3]}, "x"]"#);
+    let result = pattern.run(&cbor_data);
+    assert_path_chains!(&cbor_data, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @a
+            [{"k": [[1], 2, 3]}, "x"]
+                {"k": [[1], 2, 3]}
+                    [[1], 2, 3]
+                        [1]
+                            1
+        @b
+            [{"k": [[1], 2, 3]}, "x"]
+                "x"
+        @rest
+            [{"k": [[1], 2, 3]}, "x"]
+                {"k": [[1], 2, 3]}
+                    [[1], 2, 3]
+                        [2, 3]
+        [{"k": [[1], 2, 3]}, "x"]
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected);
+
+    // And below the root
+    let pattern =
+        parse(r#"search([{"k": [[@a(number)], @rest((*)*)]}, @b(text)])"#);
+    let cbor_data = cbor(r#"[[{"k": [[1], 2, 3]}, "x"]]"#);
+    let result = pattern.run(&cbor_data);
+    assert_path_chains!(&cbor_data, result.paths(), result.captures());
+    assert_eq!(result.capture("a").map(|paths| paths[0].len()), Some(6));
+    assert_eq!(
+        result.capture("rest").map(|paths| paths[0].last().cloned()),
+        Some(Some(cbor("[2, 3]")))
+    );
+}
+
+#[test]
+fn test_array_unordered_captures() {
+    // Each capture binds to the element its pattern was paired with
+    let pattern = parse("[~ @n(number), @t(text)]");
+    let cbor_data = cbor(r#"["x", 1]"#);
+    let result = pattern.run(&cbor_data);
+    assert_path_chains!(&cbor_data, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @n
+            ["x", 1]
+                1
+        @t
+            ["x", 1]
+                "x"
+        ["x", 1]
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected);
+
+    // Both patterns match the first element, but only the first pattern can
+    // take the second, so the pairing is forced
+    let pattern = parse("[~ @a(number), @b(1)]");
+    let cbor_data = cbor("[1, 2]");
+    let result = pattern.run(&cbor_data);
+    assert_eq!(
+        result.capture("a"),
+        Some(&[vec![cbor_data.clone(), cbor("2")]][..])
+    );
+    assert_eq!(
+        result.capture("b"),
+        Some(&[vec![cbor_data.clone(), cbor("1")]][..])
+    );
+
+    // Where either pairing works, the same one is chosen every time
+    let pattern = parse("[~ @a(number), @b(number)]");
+    let result = pattern.run(&cbor_data);
+    assert_eq!(
+        result.capture("a"),
+        Some(&[vec![cbor_data.clone(), cbor("1")]][..])
+    );
+    assert_eq!(
+        result.capture("b"),
+        Some(&[vec![cbor_data.clone(), cbor("2")]][..])
+    );
+
+    // No pairing, no captures
+    let result = pattern.run(&cbor(r#"[1, "x"]"#));
+    assert!(result.is_empty());
+    assert!(result.captures().is_empty());
+
+    // Nested below other patterns, captures keep the full path
+    let pattern = parse(r#"search([~ @n(number), [~ @t(text), null]])"#);
+    let cbor_data = cbor(r#"{"k": [[null, "x"], 1]}"#);
+    let result = pattern.run(&cbor_data);
+    assert_path_chains!(&cbor_data, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @n
+            {"k": [[null, "x"], 1]}
+                [[null, "x"], 1]
+                    1
+        @t
+            {"k": [[null, "x"], 1]}
+                [[null, "x"], 1]
+                    [null, "x"]
+                        "x"
+        {"k": [[null, "x"], 1]}
+            [[null, "x"], 1]
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected);
+}
+
+#[test]
+fn test_array_any_element_matrix() {
+    // `*` in an array is one element of any kind, grouped or not; `(*)*` is
+    // any number of them
+    let haystacks =
+        [cbor("[]"), cbor("[1]"), cbor("[1, 2]"), cbor("[1, 2, 3]")];
+    #[rustfmt::skip]
+    let matrix: &[(&str, [bool; 4])] = &[
+        ("[*]",                 [false, true,  false, false]),
+        ("[(*)]",               [false, true,  false, false]),
+        ("[(*){1}]",            [false, true,  false, false]),
+        ("[(*)*]",              [true,  true,  true,  true ]),
+        ("[*, *]",              [false, false, true,  false]),
+        ("[(*), (*)]",          [false, false, true,  false]),
+        ("[(*){1}, (*){1}]",    [false, false, true,  false]),
+        ("[*, (*)*]",           [false, true,  true,  true ]),
+        ("array",               [true,  true,  true,  true ]),
+    ];
+    for (source, expected) in matrix {
+        let pattern = parse(source);
+        for (haystack, expected) in haystacks.iter().zip(expected) {
+            assert_eq!(
+                pattern.matches(haystack),
+                *expected,
+                "{} on {}",
+                source,
+                haystack
+            );
+            let (vm_paths, _) = Program::compile(&pattern).run(haystack);
+            assert_eq!(!vm_paths.is_empty(), *expected, "{}", source);
+        }
+    }
+}
+
+#[test]
+fn test_array_capture_of_one_repeat_matches_plain_capture() {
+    // A capture around a repeat of exactly one captures the element, as the
+    // plain capture does, rather than a one-element array
+    let haystack = cbor(r#"[1, "a", 1]"#);
+    for (plain, repeat) in [
+        ("[@a(*), text, number]", "[@a((*){1}), text, number]"),
+        ("[@a(number), @b(*), *]", "[@a((number){1}), @b((*){1}), *]"),
+        ("[(*)*, @a(*)]", "[(*)*, @a((*){1})]"),
+        ("[@a(*), (*)*, =a]", "[@a((*){1}), (*)*, =a]"),
+    ] {
+        let plain = parse(plain);
+        let repeat = parse(repeat);
+        let expected = plain.paths_with_captures(&haystack);
+        assert!(!expected.0.is_empty(), "{}", plain);
+        assert_eq!(
+            repeat.paths_with_captures(&haystack),
+            expected,
+            "{}",
+            repeat
+        );
+        assert_eq!(
+            Program::compile(&repeat).run(&haystack),
+            expected,
+            "{}",
+            repeat
+        );
+    }
+
+    // Other repeats still capture the run of elements as an array
+    let (_, captures) =
+        parse("[@a((*){2}), number]").paths_with_captures(&haystack);
+    assert_eq!(captures["a"], [vec![haystack.clone(), cbor(r#"[1, "a"]"#)]]);
+}
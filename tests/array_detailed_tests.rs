@@ -3,7 +3,8 @@ mod common;
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
 use dcbor_pattern::{
-    FormatPathsOpts, Matcher, Pattern, format_paths, format_paths_with_captures,
+    ArrayPattern, FormatPathsOpts, Matcher, Pattern, Quantifier, Reluctance,
+    StructurePattern, format_paths, format_paths_with_captures,
 };
 use indoc::indoc;
 
@@ -105,7 +106,7 @@ fn test_array_element_traversal() {
 #[test]
 fn test_array_pattern_with_multiple_elements() {
     let cbor_data = cbor("[42, 100, 200]");
-    let pattern = Pattern::parse("[@item(number)]").unwrap();
+    let pattern = Pattern::parse("[(@item(number))*]").unwrap();
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
@@ -114,11 +115,11 @@ fn test_array_pattern_with_multiple_elements() {
     let expected = indoc! {r#"
         @item
             [42, 100, 200]
-                200
+                42
             [42, 100, 200]
                 100
             [42, 100, 200]
-                42
+                200
         [42, 100, 200]
     "#}.trim();
     assert_actual_expected!(
@@ -135,7 +136,7 @@ fn test_array_pattern_with_multiple_elements() {
 fn test_array_pattern_nested_structure() {
     let cbor_data = cbor(r#"[[42], [100]]"#);
     let pattern =
-        Pattern::parse("[@outer_item([@inner_item(number)])]").unwrap();
+        Pattern::parse("[(@outer_item([@inner_item(number)]))*]").unwrap();
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
@@ -143,17 +144,17 @@ fn test_array_pattern_nested_structure() {
     #[rustfmt::skip]
     let expected = indoc! {r#"
         @inner_item
-            [[42], [100]]
-                [100]
-                    100
             [[42], [100]]
                 [42]
                     42
-        @outer_item
             [[42], [100]]
                 [100]
+                    100
+        @outer_item
             [[42], [100]]
                 [42]
+            [[42], [100]]
+                [100]
         [[42], [100]]
     "#}.trim();
     assert_actual_expected!(
@@ -169,7 +170,7 @@ fn test_array_pattern_nested_structure() {
 #[test]
 fn test_array_pattern_specific_value_matching() {
     let cbor_data = cbor("[42, 100, 42]");
-    let pattern = Pattern::parse("[@specific(42)]").unwrap();
+    let pattern = Pattern::parse("[(@specific(42) | *)*]").unwrap();
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
@@ -179,6 +180,8 @@ fn test_array_pattern_specific_value_matching() {
         @specific
             [42, 100, 42]
                 42
+            [42, 100, 42]
+                42
         [42, 100, 42]
     "#}.trim();
     assert_actual_expected!(
@@ -216,7 +219,7 @@ fn test_array_pattern_no_match() {
 #[test]
 fn test_array_pattern_mixed_types() {
     let cbor_data = cbor(r#"[42, "hello", true, [1, 2]]"#);
-    let pattern = Pattern::parse("[@any_item(*)]").unwrap();
+    let pattern = Pattern::parse("[(@any_item(*))*]").unwrap();
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
@@ -225,22 +228,330 @@ fn test_array_pattern_mixed_types() {
     let expected = indoc! {r#"
         @any_item
             [42, "hello", true, [1, 2]]
-                [1, 2]
-            [42, "hello", true, [1, 2]]
-                true
+                42
             [42, "hello", true, [1, 2]]
                 "hello"
             [42, "hello", true, [1, 2]]
-                42
-        [42, "hello", true, [1, 2]]
-            [1, 2]
-        [42, "hello", true, [1, 2]]
-        [42, "hello", true, [1, 2]]
-            true
-        [42, "hello", true, [1, 2]]
-            "hello"
+                true
+            [42, "hello", true, [1, 2]]
+                [1, 2]
         [42, "hello", true, [1, 2]]
-            42
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_array_negated_elements_in_sequence() {
+    // Repeat of a negated element: any run of non-text values, then 42
+    let pattern = Pattern::parse("[(!text)*, 42]").unwrap();
+    assert!(pattern.matches(&cbor("[1, 2, 42]")));
+    assert!(pattern.matches(&cbor("[42]")));
+    assert!(!pattern.matches(&cbor(r#"["a", 42]"#)));
+    assert!(!pattern.matches(&cbor(r#"[1, "a", 42]"#)));
+
+    // A single negated element consumes exactly one element
+    let pattern = Pattern::parse("[number, !text, *]").unwrap();
+    assert!(pattern.matches(&cbor("[1, 2, 3]")));
+    assert!(pattern.matches(&cbor(r#"[1, [2], "c"]"#)));
+    assert!(!pattern.matches(&cbor(r#"[1, "b", 3]"#)));
+    assert!(!pattern.matches(&cbor("[1, 2]")));
+}
+
+#[test]
+fn test_array_negated_repeat_in_sequence() {
+    // A negated repeat consumes a run of elements that don't match the
+    // repeated pattern, honoring its quantifier
+    let pattern = Pattern::parse("[1, !(text)*, 42]").unwrap();
+    assert!(pattern.matches(&cbor("[1, 42]")));
+    assert!(pattern.matches(&cbor("[1, 2, 3, 42]")));
+    assert!(!pattern.matches(&cbor(r#"[1, 2, "a", 42]"#)));
+
+    let pattern = Pattern::parse("[1, !(text){1,2}, 42]").unwrap();
+    assert!(!pattern.matches(&cbor("[1, 42]")));
+    assert!(pattern.matches(&cbor("[1, true, 42]")));
+    assert!(pattern.matches(&cbor("[1, true, null, 42]")));
+    assert!(!pattern.matches(&cbor("[1, true, null, false, 42]")));
+
+    // The same pattern built through the API
+    let pattern = Pattern::sequence(vec![
+        Pattern::not_matching(Pattern::repeat(
+            Pattern::any_text(),
+            Quantifier::new(0.., Reluctance::Greedy),
+        )),
+        Pattern::number(42),
+    ]);
+    let pattern = Pattern::Structure(StructurePattern::Array(
+        ArrayPattern::with_elements(pattern),
+    ));
+    assert!(pattern.matches(&cbor("[1, 2, 42]")));
+    assert!(pattern.matches(&cbor("[42]")));
+    assert!(!pattern.matches(&cbor(r#"["a", 42]"#)));
+
+    // Captures inside the sequence still resolve against the assignment
+    let pattern = Pattern::parse("[1, !(text)*, @answer(42)]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&cbor("[1, 2, 3, 42]"));
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @answer
+            [1, 2, 3, 42]
+                42
+        [1, 2, 3, 42]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_array_or_matches_single_element() {
+    // Like `[number]`, an alternative matches an array of exactly one element
+    let pattern = Pattern::parse("[(number | text)]").unwrap();
+    assert!(pattern.matches(&cbor("[1]")));
+    assert!(pattern.matches(&cbor(r#"["a"]"#)));
+    assert!(!pattern.matches(&cbor("[true]")));
+    assert!(!pattern.matches(&cbor("[]")));
+    assert!(!pattern.matches(&cbor("[1, 2, 3]")));
+    assert!(!pattern.matches(&cbor(r#"[true, "a"]"#)));
+    assert!(
+        !Pattern::parse("[number]")
+            .unwrap()
+            .matches(&cbor("[1, 2, 3]"))
+    );
+
+    // A leading alternative followed by any elements
+    let pattern = Pattern::parse("[(number | text), (*)*]").unwrap();
+    assert!(pattern.matches(&cbor("[1]")));
+    assert!(pattern.matches(&cbor("[1, 2, 3]")));
+    assert!(pattern.matches(&cbor(r#"["a", true]"#)));
+    assert!(!pattern.matches(&cbor(r#"[true, "a"]"#)));
+    assert!(!pattern.matches(&cbor("[]")));
+
+    // An alternative anywhere in the array
+    let pattern = Pattern::parse("[(*)*, (number | text), (*)*]").unwrap();
+    assert!(pattern.matches(&cbor(r#"[true, "a"]"#)));
+    assert!(!pattern.matches(&cbor("[true, null]")));
+
+    // Captures inside the alternative only bind when the array matches
+    let pattern = Pattern::parse("[(@n(number) | text)]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&cbor("[1, 2]"));
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+    let (paths, captures) = pattern.paths_with_captures(&cbor("[1]"));
+    assert_eq!(paths, vec![vec![cbor("[1]")]]);
+    assert_eq!(captures["n"], vec![vec![cbor("[1]"), cbor("1")]]);
+}
+
+#[test]
+fn test_array_capture_matches_single_element() {
+    // A capture matches the elements as its pattern does, so `[@n(number)]`
+    // is `[number]` with a capture
+    let pattern = Pattern::parse("[@n(number)]").unwrap();
+    assert!(pattern.matches(&cbor("[1]")));
+    assert!(!pattern.matches(&cbor("[1, 2, 3]")));
+    assert!(!pattern.matches(&cbor(r#"["a", 1]"#)));
+    let (paths, captures) = pattern.paths_with_captures(&cbor("[1, 2, 3]"));
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+    let (paths, captures) = pattern.paths_with_captures(&cbor("[1]"));
+    assert_eq!(paths, vec![vec![cbor("[1]")]]);
+    assert_eq!(captures["n"], vec![vec![cbor("[1]"), cbor("1")]]);
+
+    // A repeat captures every element, and a capture between any elements
+    // captures the first one that matches
+    let haystack = cbor(r#"["a", 1, 2]"#);
+    let pattern = Pattern::parse("[(@n(number))*]").unwrap();
+    assert!(!pattern.matches(&haystack));
+    let pattern = Pattern::parse("[(*)*, @n(number), (*)*]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_eq!(paths, vec![vec![haystack.clone()]]);
+    assert_eq!(captures["n"].len(), 1);
+
+    // A captured repeat still matches the run of elements it captures
+    let pattern = Pattern::parse("[@rest((number)*)]").unwrap();
+    assert!(pattern.matches(&cbor("[1, 2, 3]")));
+    assert!(!pattern.matches(&haystack));
+}
+
+#[test]
+fn test_array_grouped_elements_are_plain() {
+    // Parentheses only group, so a grouped element is the element itself
+    // and needs no backtracking to match
+    for (grouped, plain) in [
+        ("[(number | text)]", "[number | text]"),
+        ("[(1 | 2), \"hello\"]", "[1 | 2, \"hello\"]"),
+        ("[(number), (text)]", "[number, text]"),
+        ("[(number & >=10), (*)*]", "[number & >=10, (*)*]"),
+    ] {
+        let grouped = Pattern::parse(grouped).unwrap();
+        assert_eq!(grouped, Pattern::parse(plain).unwrap());
+        assert_eq!(grouped.to_string(), plain);
+    }
+}
+
+#[test]
+fn test_array_and_not_match_single_element() {
+    let pattern = Pattern::parse("[(number & >=10)]").unwrap();
+    assert!(pattern.matches(&cbor("[10]")));
+    assert!(!pattern.matches(&cbor("[5]")));
+    assert!(!pattern.matches(&cbor("[5, 10]")));
+
+    let pattern = Pattern::parse("[!text]").unwrap();
+    assert!(pattern.matches(&cbor("[1]")));
+    assert!(!pattern.matches(&cbor(r#"["a"]"#)));
+    assert!(!pattern.matches(&cbor(r#"[1, "a"]"#)));
+    assert!(!pattern.matches(&cbor("[]")));
+
+    // A negated repeat still consumes a run of elements
+    let pattern = Pattern::parse("[!(text)*]").unwrap();
+    assert!(pattern.matches(&cbor("[]")));
+    assert!(pattern.matches(&cbor("[1, 2]")));
+    assert!(!pattern.matches(&cbor(r#"[1, "a"]"#)));
+}
+
+#[test]
+fn test_array_or_of_sequences() {
+    // Alternatives that consume several elements each match the whole array
+    let pattern = Pattern::or(vec![
+        Pattern::sequence(vec![Pattern::number(1), Pattern::number(2)]),
+        Pattern::repeat(
+            Pattern::any_text(),
+            Quantifier::new(0.., Reluctance::Greedy),
+        ),
+    ]);
+    let pattern = Pattern::Structure(StructurePattern::Array(
+        ArrayPattern::with_elements(pattern),
+    ));
+    assert!(pattern.matches(&cbor("[1, 2]")));
+    assert!(pattern.matches(&cbor(r#"["a", "b", "c"]"#)));
+    assert!(pattern.matches(&cbor("[]")));
+    assert!(!pattern.matches(&cbor("[1]")));
+    assert!(!pattern.matches(&cbor(r#"[1, 2, "a"]"#)));
+}
+
+#[test]
+fn test_array_possessive_repeat_in_sequence() {
+    // Greedy repeats give elements back so the rest of the sequence can match
+    let array = cbor("[1, 2, 3]");
+    for source in ["[(number)*, number]", "[(number)+, number]"] {
+        let pattern = Pattern::parse(source).unwrap();
+        assert!(pattern.matches(&array), "{}", source);
+        assert_eq!(pattern.paths(&array), vec![vec![array.clone()]]);
+    }
+    let pattern = Pattern::parse("[(number)?, number]").unwrap();
+    assert!(pattern.matches(&cbor("[1]")));
+
+    // Possessive repeats keep every element they can take
+    for (source, array) in [
+        ("[(number)*+, number]", "[1, 2, 3]"),
+        ("[(number)++, number]", "[1, 2, 3]"),
+        ("[(number)?+, number]", "[1]"),
+    ] {
+        let pattern = Pattern::parse(source).unwrap();
+        let array = cbor(array);
+        assert!(!pattern.matches(&array), "{}", source);
+        assert!(pattern.paths(&array).is_empty(), "{}", source);
+    }
+
+    // They still match when the rest of the sequence doesn't need their
+    // elements
+    let pattern = Pattern::parse("[(number)*+, text]").unwrap();
+    assert!(pattern.matches(&cbor(r#"[1, 2, "a"]"#)));
+    assert!(pattern.matches(&cbor(r#"["a"]"#)));
+    let pattern = Pattern::parse("[(number)++, text]").unwrap();
+    assert!(!pattern.matches(&cbor(r#"["a"]"#)));
+    let pattern = Pattern::parse("[(number){2,3}+, number]").unwrap();
+    assert!(pattern.matches(&cbor("[1, 2, 3, 4]")));
+    assert!(!pattern.matches(&cbor("[1, 2, 3]")));
+
+    // The same holds when captures route matching through the VM
+    let array = cbor("[1, 2, 3]");
+    let pattern = Pattern::parse("[@run((number)*), @last(number)]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&array);
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @last
+            [1, 2, 3]
+                3
+        @run
+            [1, 2, 3]
+                [1, 2]
+        [1, 2, 3]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+    for source in [
+        "[@run((number)*+), @last(number)]",
+        "[@run((number)++), @last(number)]",
+        "[@run((number)?+), @last(number)]",
+    ] {
+        let pattern = Pattern::parse(source).unwrap();
+        let array = if source.contains('?') {
+            cbor("[1]")
+        } else {
+            array.clone()
+        };
+        let (paths, captures) = pattern.paths_with_captures(&array);
+        assert!(paths.is_empty(), "{}", source);
+        assert!(captures.is_empty(), "{}", source);
+    }
+}
+
+#[test]
+fn test_array_lazy_repeat_in_sequence() {
+    // A lazy repeat takes as few elements as the rest of the sequence allows
+    let array = cbor("[1, 2, 3]");
+    let pattern =
+        Pattern::parse("[@head((number)*?), @tail((number)*)]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&array);
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @head
+            [1, 2, 3]
+                []
+        @tail
+            [1, 2, 3]
+                [1, 2, 3]
+        [1, 2, 3]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    let pattern =
+        Pattern::parse("[@head((number)+?), @tail((number)*)]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&array);
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @head
+            [1, 2, 3]
+                [1]
+        @tail
+            [1, 2, 3]
+                [2, 3]
+        [1, 2, 3]
     "#}.trim();
     assert_actual_expected!(
         format_paths_with_captures(
@@ -0,0 +1,216 @@
+mod common;
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    Error, FormatPathsOpts, Matcher, PathStep, Pattern, Program, format_paths,
+    format_paths_with_captures,
+};
+use indoc::indoc;
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+/// The nested document in `tests/fixtures/people.diag`.
+fn people() -> CBOR {
+    let diag = std::fs::read_to_string(format!(
+        "{}/tests/fixtures/people.diag",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    cbor(&diag)
+}
+
+#[test]
+fn test_at_paths() {
+    let pattern = parse(r#"at("people", 1, "name", text)"#);
+
+    // The path runs from the root through every value stepped into
+    let paths = pattern.paths(&people());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+            [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                {"age": 25, "name": "Bob"}
+                    "Bob"
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_at_continues_along_inner_path() {
+    // The inner pattern's own path carries on from the destination
+    let pattern = parse(r#"at("people", search("Alice"))"#);
+    let paths = pattern.paths(&people());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+            [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                {"age": 30, "name": "Alice"}
+                    "Alice"
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_at_no_match() {
+    let people = people();
+
+    assert!(parse(r#"at("people", 0, "age", 30)"#).matches(&people));
+    assert!(parse(r#"at("count", number)"#).matches(&people));
+
+    for pattern in [
+        // The destination doesn't match
+        r#"at("people", 0, "age", 25)"#,
+        // Missing keys
+        r#"at("persons", *)"#,
+        r#"at("people", 0, "email", *)"#,
+        // Indexes out of bounds
+        r#"at("people", 2, *)"#,
+        r#"at("people", 1000000, *)"#,
+        // Steps into values of the wrong kind
+        r#"at(0, *)"#,
+        r#"at("people", "0", *)"#,
+        r#"at("count", 0, *)"#,
+    ] {
+        assert!(!parse(pattern).matches(&people), "{}", pattern);
+    }
+
+    // Keys must be text, so a number key is never stepped to
+    assert!(!parse("at(1, *)").matches(&cbor(r#"{1: "one"}"#)));
+}
+
+#[test]
+fn test_at_captures() {
+    let pattern = parse(r#"at("people", 0, {"name": @name(text)})"#);
+    let (paths, captures) = pattern.paths_with_captures(&people());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @name
+            {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+                [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                    {"age": 30, "name": "Alice"}
+                        "Alice"
+        {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+            [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                {"age": 30, "name": "Alice"}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_at_inside_sequence() {
+    // Array sequences run on the VM, which steps to each destination
+    let pattern = parse(r#"[(*)*, @bob(at("name", "Bob"))]"#);
+    let data = people();
+    let people = data.as_map().unwrap().get::<_, CBOR>("people").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&people);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @bob
+            [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                {"age": 25, "name": "Bob"}
+                    "Bob"
+        [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    assert!(!parse(r#"[(*)*, at("name", "Carol")]"#).matches(&people));
+}
+
+#[test]
+fn test_at_compiled_matches_direct() {
+    let data = people();
+    for pattern in [
+        r#"at("people", 1, "name", text)"#,
+        r#"at("people", 0, {"name": @name(text)})"#,
+        r#"at("people", @person({"age": 25}))"#,
+        r#"at("people", 2, *)"#,
+        r#"at("count", "people", *)"#,
+        "at(*)",
+    ] {
+        let pattern = parse(pattern);
+        let program = Program::compile(&pattern);
+
+        assert_eq!(
+            program.run(&data),
+            pattern.paths_with_captures(&data),
+            "{}",
+            pattern
+        );
+    }
+}
+
+#[test]
+fn test_at_display_round_trip() {
+    for source in [
+        r#"at("items", 2, number)"#,
+        r#"at("a \"quoted\" key", [number, text])"#,
+        r#"at("items", 2)"#,
+        r#"at(0, 1, @x(*))"#,
+        "at(number | text)",
+    ] {
+        let pattern = parse(source);
+        assert_eq!(pattern.to_string(), source);
+        assert_eq!(parse(&pattern.to_string()), pattern);
+    }
+
+    assert_eq!(parse("AT(0, *)"), parse("at(0, *)"));
+}
+
+#[test]
+fn test_at_builder() {
+    let pattern = Pattern::at(
+        [PathStep::from("people"), PathStep::from(1)],
+        Pattern::any_map(),
+    );
+    assert_eq!(pattern, parse(r#"at("people", 1, map)"#));
+
+    // With no steps, the pattern applies at the node itself
+    assert_eq!(
+        Pattern::at([], Pattern::number(2)).paths(&cbor("2")),
+        vec![vec![cbor("2")]]
+    );
+}
+
+#[test]
+fn test_at_parse_errors() {
+    for (source, span) in [
+        ("at(-1, number)", 3..5),
+        ("at(1.5, number)", 3..6),
+        (r#"at("a", 1e100, number)"#, 8..13),
+    ] {
+        match Pattern::parse(source) {
+            Err(Error::InvalidNumberFormat(_, actual)) => {
+                assert_eq!(actual, span, "{}", source)
+            }
+            other => panic!("{}: {:?}", source, other),
+        }
+    }
+
+    assert!(Pattern::parse(r#"at("a", )"#).is_err());
+    assert!(Pattern::parse(r#"at("a")x"#).is_err());
+    assert!(Pattern::parse(r#"at("a", number"#).is_err());
+    assert!(Pattern::parse("at").is_err());
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    FormatPathsOpts, Matcher, Pattern, format_paths_with_captures,
+};
+use indoc::indoc;
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+#[test]
+fn test_parse_backref() {
+    let pattern = parse("[@x(*), (*)*, =x]");
+    assert_eq!(pattern.to_string(), "[@x(*), (*)*, =x]");
+    assert_eq!(
+        parse(r#"{@k(text): *, "copy": =k}"#).to_string(),
+        r#"{@k(text): *, "copy": =k}"#
+    );
+    assert_eq!(parse("=x"), Pattern::backref("x"));
+}
+
+#[test]
+fn test_backref_array_first_equals_last() {
+    let pattern = parse("[@x(*), (*)*, =x]");
+
+    assert!(pattern.matches(&cbor("[1, 2, 3, 1]")));
+    assert!(pattern.matches(&cbor(r#"["a", "a"]"#)));
+    assert!(pattern.matches(&cbor("[[1, 2], 0, [1, 2]]")));
+    assert!(!pattern.matches(&cbor("[1, 2, 3]")));
+    assert!(!pattern.matches(&cbor("[1]")));
+    assert!(!pattern.matches(&cbor("[]")));
+}
+
+#[test]
+fn test_backref_array_without_repeats() {
+    let pattern = parse("[@x(number), text, =x]");
+
+    assert!(pattern.matches(&cbor(r#"[7, "a", 7]"#)));
+    assert!(!pattern.matches(&cbor(r#"[7, "a", 8]"#)));
+    assert!(!pattern.matches(&cbor(r#"[7, "a", "7"]"#)));
+}
+
+#[test]
+fn test_backref_array_backtracks_binding() {
+    // The first repeat must give up elements until the captured value
+    // appears again later in the array
+    let pattern = parse("[(*)*, @x(number), (*)*, =x, (*)*]");
+
+    assert!(pattern.matches(&cbor("[1, 2, 3, 2]")));
+    assert!(!pattern.matches(&cbor("[1, 2, 3, 4]")));
+
+    // A capture around a repeat binds the run as an array
+    let pattern = parse("[@run((number)*), text, =run]");
+    assert!(pattern.matches(&cbor(r#"[1, 2, "sep", [1, 2]]"#)));
+    assert!(!pattern.matches(&cbor(r#"[1, 2, "sep", [2, 1]]"#)));
+}
+
+#[test]
+fn test_backref_array_captures() {
+    let data = cbor("[5, 6, 5]");
+    let pattern = parse("[@x(*), (*)*, =x]");
+    let (paths, captures) = pattern.paths_with_captures(&data);
+    assert_path_chains!(&data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @x
+            [5, 6, 5]
+                5
+        [5, 6, 5]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_backref_nested_capture_binding() {
+    // The capture is made inside the first element's array pattern
+    let pattern = parse("[[@x(number), *], =x]");
+
+    assert!(pattern.matches(&cbor(r#"[[3, "a"], 3]"#)));
+    assert!(!pattern.matches(&cbor(r#"[[3, "a"], 4]"#)));
+}
+
+#[test]
+fn test_backref_map() {
+    let pattern = parse(r#"{@k(text): *, "copy": =k}"#);
+
+    assert!(pattern.matches(&cbor(r#"{"a": 1, "copy": "a"}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"a": 1, "copy": "b"}"#)));
+
+    // "a" is tried first, but only "b" makes the back-reference agree
+    let data = cbor(r#"{"a": 1, "b": 2, "copy": "b"}"#);
+    let (paths, captures) = pattern.paths_with_captures(&data);
+    assert_path_chains!(&data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @k
+            {"a": 1, "b": 2, "copy": "b"}
+                "b"
+        {"a": 1, "b": 2, "copy": "b"}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // Back-references can also constrain keys
+    let pattern = parse(r#"{"ref": @v(*), =v: *}"#);
+    assert!(pattern.matches(&cbor(r#"{"ref": 2, 2: "two"}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"ref": 3, 2: "two"}"#)));
+}
+
+#[test]
+fn test_backref_unbound_does_not_match() {
+    // On its own, a back-reference has nothing to refer to
+    assert!(!parse("=x").matches(&cbor("1")));
+
+    // A back-reference before its capture is unbound when it is matched
+    assert!(!parse("[=x, @x(*)]").matches(&cbor("[1, 1]")));
+
+    // References to a name that is never captured never match
+    assert!(!parse("[@x(*), =y]").matches(&cbor("[1, 1]")));
+}
@@ -3,7 +3,7 @@ mod common;
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
 use dcbor_pattern::{
-    FormatPathsOpts, Matcher, Pattern, Result, format_paths,
+    AndPattern, FormatPathsOpts, Matcher, Pattern, Result, format_paths,
     format_paths_with_captures,
 };
 use indoc::indoc;
@@ -46,6 +46,7 @@ fn test_simple_pattern_with_capture() -> Result<()> {
 
     // Test paths with captures using the proper rubric
     let (vm_paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &vm_paths, &captures);
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected = indoc! {r#"
@@ -114,6 +115,8 @@ fn test_capture_with_array_pattern() -> Result<()> {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected = indoc! {r#"
@@ -140,6 +143,8 @@ fn test_capture_with_nested_pattern() -> Result<()> {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected = indoc! {r#"
@@ -161,3 +166,116 @@ fn test_capture_with_nested_pattern() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_capture_in_every_and_conjunct() -> Result<()> {
+    let seven = cbor("7");
+
+    // Two and three conjuncts, each with its own capture
+    let (paths, captures) =
+        parse("@a(number) & @b(>5)").paths_with_captures(&seven);
+    assert_path_chains!(&seven, &paths, &captures);
+    assert_eq!(captures.keys().collect::<Vec<_>>(), ["a", "b"]);
+    let (_, captures) =
+        parse("@a(number) & @b(>5) & @c(<10)").paths_with_captures(&seven);
+    assert_eq!(captures.keys().collect::<Vec<_>>(), ["a", "b", "c"]);
+    assert!(
+        captures
+            .values()
+            .all(|paths| paths == &vec![vec![seven.clone()]])
+    );
+
+    // A conjunct that matches without capturing, and one that fails
+    let (paths, captures) =
+        parse("@a(number) & >5").paths_with_captures(&seven);
+    assert_eq!(paths, vec![vec![seven.clone()]]);
+    assert_eq!(captures["a"], vec![vec![seven.clone()]]);
+    let (paths, captures) =
+        parse("@a(number) & @b(>9)").paths_with_captures(&seven);
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+
+    // Captures inside conjuncts run through the paths they match
+    let array = cbor("[1, 2]");
+    let pattern =
+        parse("@all([(*)*]) & [@first(number), (*)*] & [(*)*, @last(*)]");
+    let (paths, captures) = pattern.paths_with_captures(&array);
+    assert_path_chains!(&array, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @all
+            [1, 2]
+        @first
+            [1, 2]
+                1
+        @last
+            [1, 2]
+                2
+        [1, 2]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_and_conjuncts_sharing_a_capture_name() -> Result<()> {
+    // The parser rejects duplicate names, but patterns built in code can
+    // share them between conjuncts. Each path is then captured once.
+    let seven = cbor("7");
+    let (_, captures) = pattern_with_shared_name().paths_with_captures(&seven);
+    assert_eq!(captures["n"], vec![vec![seven.clone()]]);
+    let and = AndPattern::new(vec![
+        Pattern::capture("n", Pattern::any_number()),
+        Pattern::capture("n", Pattern::number_greater_than(5)),
+    ]);
+    assert_eq!(
+        and.paths_with_captures(&seven),
+        (vec![vec![seven.clone()]], captures)
+    );
+
+    // Different paths for the same name are concatenated
+    let array = cbor("[7]");
+    let pattern = Pattern::and(vec![
+        Pattern::capture("n", Pattern::any_array()),
+        Pattern::parse("[@n(number)]")?,
+    ]);
+    let (paths, captures) = pattern.paths_with_captures(&array);
+    assert_path_chains!(&array, &paths, &captures);
+    assert_eq!(
+        captures["n"],
+        vec![vec![array.clone()], vec![array.clone(), seven.clone()]]
+    );
+
+    // Searching keeps one capture for each node matched
+    let numbers = cbor("[7, 3, 9]");
+    let (paths, captures) = Pattern::search(pattern_with_shared_name())
+        .paths_with_captures(&numbers);
+    assert_eq!(paths.len(), 2);
+    assert_eq!(
+        captures["n"],
+        vec![
+            vec![numbers.clone(), seven],
+            vec![numbers.clone(), cbor("9")],
+        ]
+    );
+
+    Ok(())
+}
+
+/// Returns `@n(number) & @n(>5)`, which can't be parsed.
+fn pattern_with_shared_name() -> Pattern {
+    Pattern::and(vec![
+        Pattern::capture("n", Pattern::any_number()),
+        Pattern::capture("n", Pattern::number_greater_than(5)),
+    ])
+}
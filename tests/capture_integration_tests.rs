@@ -3,7 +3,7 @@
 mod common;
 
 use dcbor_parse::parse_dcbor_item;
-use dcbor_pattern::{Matcher, Pattern, Result, format_paths_with_captures};
+use dcbor_pattern::{FormatPathsOpts, Matcher, Pattern, ROOT_CAPTURE, Result};
 use indoc::indoc;
 
 /// Test basic capture functionality with simple patterns
@@ -12,7 +12,9 @@ fn test_capture_basic_number() -> Result<()> {
     let pattern = Pattern::parse("@num(42)")?;
     let cbor = parse_dcbor_item("42").unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Validate formatted output with captures
     // expected-text-output-rubric:
@@ -22,14 +24,7 @@ fn test_capture_basic_number() -> Result<()> {
             42
         42
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -40,7 +35,9 @@ fn test_capture_basic_text() -> Result<()> {
     let pattern = Pattern::parse(r#"@greeting("hello")"#)?;
     let cbor = parse_dcbor_item(r#""hello""#).unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Validate formatted output with captures
     // expected-text-output-rubric:
@@ -50,14 +47,7 @@ fn test_capture_basic_text() -> Result<()> {
             "hello"
         "hello"
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -68,18 +58,13 @@ fn test_capture_no_match() -> Result<()> {
     let pattern = Pattern::parse("@num(42)")?;
     let cbor = parse_dcbor_item("24").unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Should not match - should be empty output
     let expected_output = "";
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -91,7 +76,8 @@ fn test_multiple_captures_or() -> Result<()> {
 
     // Test matching the first alternative
     let cbor1 = parse_dcbor_item("42").unwrap();
-    let (paths1, captures1) = pattern.paths_with_captures(&cbor1);
+    let result1 = pattern.run(&cbor1);
+    assert_path_chains!(&cbor1, result1.paths(), result1.captures());
 
     // expected-text-output-rubric:
     #[rustfmt::skip]
@@ -100,18 +86,12 @@ fn test_multiple_captures_or() -> Result<()> {
             42
         42
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths1,
-            &captures1,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output1
-    );
+    assert_actual_expected!(result1.to_string(), expected_output1);
 
     // Test matching the second alternative
     let cbor2 = parse_dcbor_item(r#""hello""#).unwrap();
-    let (paths2, captures2) = pattern.paths_with_captures(&cbor2);
+    let result2 = pattern.run(&cbor2);
+    assert_path_chains!(&cbor2, result2.paths(), result2.captures());
 
     // expected-text-output-rubric:
     #[rustfmt::skip]
@@ -120,15 +100,152 @@ fn test_multiple_captures_or() -> Result<()> {
             "hello"
         "hello"
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths2,
-            &captures2,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output2
+    assert_actual_expected!(result2.to_string(), expected_output2);
+
+    Ok(())
+}
+
+/// Test that only the first matching alternative of an OR captures
+#[test]
+fn test_or_captures_from_matching_branch_only() -> Result<()> {
+    let pattern = Pattern::parse(
+        r#"@v1({"version": 1, "body": @body(text)}) | @v2({"version": number, "payload": @payload(*)})"#,
+    )?;
+
+    // Both alternatives match, and the first one wins
+    let cbor =
+        parse_dcbor_item(r#"{"version": 1, "body": "hi", "payload": 2}"#)
+            .unwrap();
+    let result = pattern.run(&cbor);
+    assert_path_chains!(&cbor, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_output = indoc! {r#"
+        @body
+            {"body": "hi", "payload": 2, "version": 1}
+                "hi"
+        @v1
+            {"body": "hi", "payload": 2, "version": 1}
+        {"body": "hi", "payload": 2, "version": 1}
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected_output);
+
+    // Only the second alternative matches
+    let cbor = parse_dcbor_item(r#"{"version": 2, "payload": [1]}"#).unwrap();
+    let result = pattern.run(&cbor);
+    assert_path_chains!(&cbor, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_output = indoc! {r#"
+        @payload
+            {"payload": [1], "version": 2}
+                [1]
+        @v2
+            {"payload": [1], "version": 2}
+        {"payload": [1], "version": 2}
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected_output);
+
+    Ok(())
+}
+
+/// Test OR alternatives that capture under the same name
+#[test]
+fn test_or_captures_with_shared_names() -> Result<()> {
+    let opts = dcbor_pattern::ParseOpts {
+        allow_duplicate_captures: true,
+        ..Default::default()
+    };
+    let pattern = Pattern::parse_with_opts(
+        "[@id(number), text] | [@id(number), number]",
+        opts,
+    )?;
+
+    // Each alternative captures the first element, which is reported once,
+    // and only by the alternative that matched
+    let cbor = parse_dcbor_item("[1, 2]").unwrap();
+    let result = pattern.run(&cbor);
+    assert_path_chains!(&cbor, result.paths(), result.captures());
+    assert_eq!(result.paths().len(), 1);
+    assert_eq!(result.captures().len(), 1);
+    assert_eq!(result.captures()["id"], vec![vec![cbor.clone(), 1.into()]]);
+
+    Ok(())
+}
+
+/// Test OR alternatives with captures inside array elements
+#[test]
+fn test_or_captures_in_array_elements() -> Result<()> {
+    let pattern = Pattern::parse("[@n(number) | @any(*)]")?;
+    let cbor = parse_dcbor_item("[1]").unwrap();
+    let result = pattern.run(&cbor);
+    assert_path_chains!(&cbor, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_output = indoc! {r#"
+        @n
+            [1]
+                1
+        [1]
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected_output);
+
+    // Every alternative is tried, not just the first two
+    let pattern = Pattern::parse("[@a(1) | @b(2) | @c(3)]")?;
+    let result = pattern.run(&parse_dcbor_item("[3]").unwrap());
+    assert_path_chains!(
+        &parse_dcbor_item("[3]").unwrap(),
+        result.paths(),
+        result.captures()
     );
+    assert_eq!(result.paths().len(), 1);
+    assert_eq!(result.captures().keys().collect::<Vec<_>>(), vec!["c"]);
+
+    let pattern = Pattern::parse("[@x(1 | 2 | 3)]")?;
+    assert!(pattern.matches(&parse_dcbor_item("[3]").unwrap()));
+    let result = pattern.run(&parse_dcbor_item("[3]").unwrap());
+    assert_eq!(result.paths().len(), 1);
+
+    Ok(())
+}
 
+/// Test that an OR reports the paths of its first matching alternative
+/// whether or not it captures, including alternatives whose paths lead
+/// below the haystack
+#[test]
+fn test_or_paths_agree_with_captures() -> Result<()> {
+    let map = parse_dcbor_item(r#"{"a": [1, "x"]}"#).unwrap();
+    let tagged = parse_dcbor_item("100([42])").unwrap();
+    for (alternatives, haystack) in [
+        (&["search(number)", "map"][..], &map),
+        (&["@n(search(number))", "map"], &map),
+        (&["search(text)", "@m(map)"], &map),
+        (&[r#"at("a", 1, text)"#, "map"], &map),
+        (&[r#"at("a", 0, text)"#, r#"@s(at("a", 1, text))"#], &map),
+        (&["tagged(100, search(42))", "tagged"], &tagged),
+        (&["tagged(100, [@n(number)])", "*"], &tagged),
+        (
+            &["tagged(1, *)", "@t(tagged(100, search(number)))"],
+            &tagged,
+        ),
+    ] {
+        let pattern = Pattern::parse(&alternatives.join(" | "))?;
+        let first = alternatives
+            .iter()
+            .map(|alternative| Pattern::parse(alternative).unwrap())
+            .map(|alternative| alternative.paths(haystack))
+            .find(|paths| !paths.is_empty())
+            .unwrap();
+
+        let paths = pattern.paths(haystack);
+        assert_eq!(paths, first, "{}", pattern);
+        assert_eq!(
+            paths,
+            pattern.paths_with_captures(haystack).0,
+            "{}",
+            pattern
+        );
+    }
     Ok(())
 }
 
@@ -138,7 +255,9 @@ fn test_nested_captures() -> Result<()> {
     let pattern = Pattern::parse("@outer(@inner(42))")?;
     let cbor = parse_dcbor_item("42").unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Should have both captures pointing to the same value, sorted
     // alphabetically
@@ -151,14 +270,7 @@ fn test_nested_captures() -> Result<()> {
             42
         42
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -169,7 +281,9 @@ fn test_capture_in_array() -> Result<()> {
     let pattern = Pattern::parse("[@item(42)]")?;
     let cbor = parse_dcbor_item("[42]").unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Validate the structured output
     // expected-text-output-rubric:
@@ -180,14 +294,7 @@ fn test_capture_in_array() -> Result<()> {
                 42
         [42]
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -198,7 +305,9 @@ fn test_capture_in_array_sequence() -> Result<()> {
     let pattern = Pattern::parse(r#"[@first("a"), @second(42)]"#)?;
     let cbor = parse_dcbor_item(r#"["a", 42]"#).unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Should capture both elements, sorted alphabetically
     // expected-text-output-rubric:
@@ -212,14 +321,7 @@ fn test_capture_in_array_sequence() -> Result<()> {
                 42
         ["a", 42]
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -230,7 +332,9 @@ fn test_capture_in_map() -> Result<()> {
     let pattern = Pattern::parse(r#"{@key("name"): @value("Alice")}"#)?;
     let cbor = parse_dcbor_item(r#"{"name": "Alice"}"#).unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Validate formatted output with captures
     // expected-text-output-rubric:
@@ -244,14 +348,7 @@ fn test_capture_in_map() -> Result<()> {
                 "Alice"
         {"name": "Alice"}
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -262,7 +359,9 @@ fn test_capture_with_search() -> Result<()> {
     let pattern = Pattern::parse("search(@found(42))")?;
     let cbor = parse_dcbor_item(r#"[1, [2, 42], 3]"#).unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Validate formatted output with captures
     // expected-text-output-rubric:
@@ -276,14 +375,7 @@ fn test_capture_with_search() -> Result<()> {
             [2, 42]
                 42
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -294,7 +386,9 @@ fn test_capture_with_tagged() -> Result<()> {
     let pattern = Pattern::parse("tagged(1, @content(42))")?;
     let cbor = parse_dcbor_item("1(42)").unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Validate formatted output with captures
     // expected-text-output-rubric:
@@ -304,15 +398,99 @@ fn test_capture_with_tagged() -> Result<()> {
             1(42)
                 42
         1(42)
+            42
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
+
+    Ok(())
+}
+
+/// Test that captures through nested tags include every tagged layer
+#[test]
+fn test_capture_with_nested_tagged() -> Result<()> {
+    let pattern =
+        Pattern::parse("tagged(100, @outer(tagged(200, @inner(number))))")?;
+    let cbor = parse_dcbor_item("100(200(42))").unwrap();
+
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_output = indoc! {r#"
+        @inner
+            100(200(42))
+                200(42)
+                    42
+        @outer
+            100(200(42))
+                200(42)
+                    42
+        100(200(42))
+            200(42)
+                42
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected_output);
+
+    // The match continues through both tagged layers, as without captures
+    assert_eq!(result.paths(), pattern.paths(&cbor));
+
+    Ok(())
+}
+
+/// Test that captures inside tagged content keep the full path
+#[test]
+fn test_capture_inside_tagged_content() -> Result<()> {
+    let pattern = Pattern::parse("tagged(100, [@x(number)])")?;
+    let cbor = parse_dcbor_item("100([1])").unwrap();
+
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_output = indoc! {r#"
+        @x
+            100([1])
+                [1]
+                    1
+        100([1])
+            [1]
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected_output);
+
+    Ok(())
+}
+
+/// Test that the VM routes into tagged content build the same capture paths
+/// as matching the tagged pattern directly
+#[test]
+fn test_capture_tagged_routes_agree() -> Result<()> {
+    let cbor = parse_dcbor_item("[100(200(42))]").unwrap();
+    let tagged = parse_dcbor_item("100(200(42))").unwrap();
+    let expected = vec![vec![
+        cbor.clone(),
+        tagged.clone(),
+        parse_dcbor_item("200(42)").unwrap(),
+        parse_dcbor_item("42").unwrap(),
+    ]];
+
+    // Into the array element, then through both tags
+    let pattern = Pattern::parse("[tagged(100, tagged(200, @inner(number)))]")?;
+    assert_eq!(pattern.run(&cbor).capture("inner"), Some(&expected[..]));
+
+    // Through the tagged content axis
+    let pattern = Pattern::parse("[child(tagged(200, @inner(number)))]")?;
+    assert_eq!(pattern.run(&cbor).capture("inner"), Some(&expected[..]));
+
+    // Directly, with the array prefix added by hand
+    let pattern = Pattern::parse("tagged(100, tagged(200, @inner(number)))")?;
+    let result = pattern.run(&tagged);
+    let mut direct = vec![cbor.clone()];
+    direct.extend(result.capture("inner").unwrap()[0].clone());
+    assert_eq!(vec![direct], expected);
 
     Ok(())
 }
@@ -330,7 +508,8 @@ fn test_capture_performance() -> Result<()> {
     let pattern = Pattern::parse("search(@nums(number))")?;
 
     let start = std::time::Instant::now();
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+    assert_path_chains!(&cbor, result.paths(), result.captures());
     let duration = start.elapsed();
 
     // Validate formatted output with all captured numbers
@@ -411,14 +590,7 @@ fn test_capture_performance() -> Result<()> {
                 [7, 8, 9]
                     9
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     // Should complete reasonably quickly (less than 10ms for this small
     // example)
@@ -437,7 +609,9 @@ fn test_no_captures_optimization() -> Result<()> {
     let pattern = Pattern::parse("42")?;
     let cbor = parse_dcbor_item("42").unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Validate formatted output with no captures
     // expected-text-output-rubric:
@@ -445,14 +619,7 @@ fn test_no_captures_optimization() -> Result<()> {
     let expected_output = indoc! {r#"
         42
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -468,6 +635,59 @@ fn test_capture_parsing_errors() {
 
     // Invalid capture name (empty)
     assert!(Pattern::parse("@(42)").is_err());
+
+    // Names can't start with `$`, so no capture collides with the reserved
+    // root capture name
+    assert!(Pattern::parse("@$x(42)").is_err());
+    assert!(Pattern::parse(&format!("@{}(42)", ROOT_CAPTURE)).is_err());
+}
+
+/// Test the whole match stored as a capture under the reserved root name
+#[test]
+fn test_root_capture() -> Result<()> {
+    let pattern = Pattern::parse(r#"{"a": @a(number), "b": [@b(*)]}"#)?;
+    let cbor = parse_dcbor_item(r#"{"a": 1, "b": ["x"]}"#).unwrap();
+
+    let result = pattern.run(&cbor).with_root_capture();
+    assert_path_chains!(&cbor, result.paths(), result.captures());
+    assert_eq!(result.capture(ROOT_CAPTURE), Some(result.paths()));
+    assert_eq!(
+        result.captures().keys().collect::<Vec<_>>(),
+        [ROOT_CAPTURE, "a", "b"]
+    );
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_output = indoc! {r#"
+        @$match
+            {"a": 1, "b": ["x"]}
+        @a
+            {"a": 1, "b": ["x"]}
+                1
+        @b
+            {"a": 1, "b": ["x"]}
+                ["x"]
+                    "x"
+        {"a": 1, "b": ["x"]}
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected_output);
+
+    // The formatter adds the same capture to a plain result
+    let plain = pattern.run(&cbor);
+    assert_eq!(plain.capture(ROOT_CAPTURE), None);
+    let opts = FormatPathsOpts::new().root_capture(true);
+    assert_actual_expected!(plain.format_opt(&opts), expected_output);
+    assert_eq!(
+        plain.format_opt(opts.clone().json(true)),
+        result.format_opt(FormatPathsOpts::new().json(true))
+    );
+
+    // Without a match there is nothing to capture
+    let missed = pattern.run(&parse_dcbor_item("[]").unwrap());
+    assert_eq!(missed.clone().with_root_capture(), missed);
+    assert_actual_expected!(missed.format_opt(&opts), "");
+
+    Ok(())
 }
 
 /// Test complex nested captures with multiple levels
@@ -488,7 +708,9 @@ fn test_complex_nested_captures() -> Result<()> {
     let cbor =
         parse_dcbor_item(r#"[{"type": "person"}, {"name": "Alice"}]"#).unwrap();
 
-    let (paths, captures) = pattern.paths_with_captures(&cbor);
+    let result = pattern.run(&cbor);
+
+    assert_path_chains!(&cbor, result.paths(), result.captures());
 
     // Validate formatted output with all captures
     // expected-text-output-rubric:
@@ -518,14 +740,7 @@ fn test_complex_nested_captures() -> Result<()> {
                     "Alice"
         [{"type": "person"}, {"name": "Alice"}]
     "#}.trim();
-    assert_actual_expected!(
-        format_paths_with_captures(
-            &paths,
-            &captures,
-            dcbor_pattern::FormatPathsOpts::default()
-        ),
-        expected_output
-    );
+    assert_actual_expected!(result.to_string(), expected_output);
 
     Ok(())
 }
@@ -44,3 +44,109 @@ macro_rules! assert_actual_expected {
         }
     };
 }
+
+/// Asserts that every path, and every captured path, starts at the haystack
+/// and is a chain of parent-child steps: each element is an element of the
+/// array before it, a key or value of the map before it, or the content of
+/// the tagged value before it.
+///
+/// The exceptions are a step from a map to a map made of some of its
+/// entries, as captured by the rest of a map pattern, and the last step of a
+/// path, which may instead be a run of consecutive elements of the array
+/// before it, as captured by `@name((*)*)` inside an array, or part of the
+/// text, byte string, or date before it, as captured by a named regex group.
+///
+/// ```rust
+/// let (paths, captures) = pattern.paths_with_captures(&haystack);
+/// assert_path_chains!(&haystack, &paths, &captures);
+/// ```
+#[macro_export]
+macro_rules! assert_path_chains {
+    ($haystack:expr, $paths:expr, $captures:expr $(,)?) => {{
+        use dcbor::prelude::*;
+
+        fn is_child(parent: &CBOR, child: &CBOR) -> bool {
+            match parent.as_case() {
+                CBORCase::Array(arr) => arr.contains(child),
+                CBORCase::Map(map) => map
+                    .iter()
+                    .any(|(key, value)| key == child || value == child),
+                CBORCase::Tagged(_, content) => content == child,
+                _ => false,
+            }
+        }
+
+        fn is_run(parent: &CBOR, child: &CBOR) -> bool {
+            match (parent.as_case(), child.as_case()) {
+                (CBORCase::Array(arr), CBORCase::Array(run)) => {
+                    run.is_empty()
+                        || arr.windows(run.len()).any(|window| window == run)
+                }
+                _ => false,
+            }
+        }
+
+        fn is_submap(parent: &CBOR, child: &CBOR) -> bool {
+            match (parent.as_case(), child.as_case()) {
+                (CBORCase::Map(map), CBORCase::Map(submap)) => {
+                    submap.iter().all(|(key, value)| {
+                        map.iter().any(|entry| entry == (key, value))
+                    })
+                }
+                _ => false,
+            }
+        }
+
+        fn is_group(parent: &CBOR, child: &CBOR) -> bool {
+            match (parent.as_case(), child.as_case()) {
+                (CBORCase::Text(text), CBORCase::Text(group)) => {
+                    text.contains(group.as_str())
+                }
+                (CBORCase::ByteString(bytes), CBORCase::ByteString(group)) => {
+                    group.is_empty()
+                        || bytes.windows(group.len()).any(|w| w == &group[..])
+                }
+                (CBORCase::Tagged(_, _), CBORCase::Text(group)) => {
+                    dcbor::Date::try_from(parent.clone()).is_ok_and(|date| {
+                        date.to_string().contains(group.as_str())
+                    })
+                }
+                _ => false,
+            }
+        }
+
+        fn check(haystack: &CBOR, path: &[CBOR], label: &str) {
+            let rendered = path
+                .iter()
+                .map(|element| element.diagnostic_flat())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            assert_eq!(
+                path.first(),
+                Some(haystack),
+                "{label} path does not start at the haystack: {rendered}"
+            );
+            for (i, step) in path.windows(2).enumerate() {
+                let last = i + 2 == path.len();
+                assert!(
+                    is_child(&step[0], &step[1])
+                        || is_submap(&step[0], &step[1])
+                        || (last && is_run(&step[0], &step[1]))
+                        || (last && is_group(&step[0], &step[1])),
+                    "{label} path breaks at element {}: {rendered}",
+                    i + 1
+                );
+            }
+        }
+
+        let haystack: &CBOR = $haystack;
+        for path in $paths.iter() {
+            check(haystack, path, "match");
+        }
+        for (name, paths) in $captures.iter() {
+            for path in paths.iter() {
+                check(haystack, path, &format!("@{name}"));
+            }
+        }
+    }};
+}
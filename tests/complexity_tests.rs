@@ -0,0 +1,155 @@
+use dcbor_pattern::{
+    ArrayPattern, Complexity, Error, Limits, Pattern, Quantifier, Reluctance,
+    StructurePattern,
+};
+
+fn star(pattern: Pattern) -> Pattern {
+    Pattern::repeat(pattern, Quantifier::new(0.., Reluctance::Greedy))
+}
+
+fn array(pattern: Pattern) -> Pattern {
+    Pattern::Structure(StructurePattern::Array(ArrayPattern::with_elements(
+        pattern,
+    )))
+}
+
+#[test]
+fn test_complexity_of_value() {
+    assert_eq!(
+        Pattern::number(42).complexity(),
+        Complexity {
+            node_count: 1,
+            max_depth: 0,
+            unbounded_quantifiers: 0,
+            search_nodes: 0,
+            score: 1,
+        }
+    );
+}
+
+#[test]
+fn test_complexity_of_sequence() {
+    // [(*)*, (*)*, 42]
+    let pattern = array(Pattern::sequence(vec![
+        star(Pattern::any()),
+        star(Pattern::any()),
+        Pattern::number(42),
+    ]));
+    assert_eq!(
+        pattern.complexity(),
+        Complexity {
+            node_count: 7,
+            max_depth: 3,
+            unbounded_quantifiers: 2,
+            search_nodes: 0,
+            // The array, sequence, repeats and 42 count 1 each, and the
+            // patterns the repeats enclose 4 each
+            score: 5 + 2 * 4,
+        }
+    );
+    assert_eq!(
+        Pattern::parse("[(*)*, (*)*, 42]").unwrap().complexity(),
+        pattern.complexity()
+    );
+
+    // A bounded repeat is not counted, and doesn't multiply
+    let bounded = array(Pattern::repeat(
+        Pattern::any(),
+        Quantifier::new(0..=3, Reluctance::Greedy),
+    ));
+    assert_eq!(bounded.complexity().unbounded_quantifiers, 0);
+    assert_eq!(bounded.complexity().score, 3);
+}
+
+#[test]
+fn test_complexity_of_nesting() {
+    // Searches and unbounded repeats multiply the score of what they enclose
+    let nested = Pattern::search(array(star(Pattern::search(
+        Pattern::capture("n", Pattern::any_number()),
+    ))));
+    assert_eq!(
+        nested.complexity(),
+        Complexity {
+            node_count: 6,
+            max_depth: 5,
+            unbounded_quantifiers: 1,
+            search_nodes: 2,
+            score: 1 + 4 + 4 + 16 + 64 + 64,
+        }
+    );
+
+    // The same patterns side by side score far lower
+    let flat = Pattern::or(vec![
+        Pattern::search(Pattern::any_number()),
+        Pattern::search(Pattern::any_number()),
+        array(star(Pattern::any_number())),
+    ]);
+    let complexity = flat.complexity();
+    assert_eq!(complexity.node_count, 8);
+    assert_eq!(complexity.max_depth, 3);
+    assert_eq!(complexity.score, 1 + 2 * (1 + 4) + 1 + 1 + 4);
+    assert!(complexity.score < nested.complexity().score);
+}
+
+#[test]
+fn test_parse_with_limits() {
+    let source = "search([(search(@n(number)))*])";
+    let complexity = Pattern::parse(source).unwrap().complexity();
+
+    // Limits at the pattern's own measurements accept it
+    let limits = Limits {
+        max_nodes: Some(complexity.node_count),
+        max_depth: Some(complexity.max_depth),
+        max_unbounded_quantifiers: Some(complexity.unbounded_quantifiers),
+        max_search_nodes: Some(complexity.search_nodes),
+        max_score: Some(complexity.score),
+    };
+    assert_eq!(
+        Pattern::parse_with_limits(source, &limits),
+        Pattern::parse(source)
+    );
+    assert!(Pattern::parse_with_limits(source, &Limits::default()).is_ok());
+
+    // One below any of them rejects it
+    let reject = |limits: Limits| {
+        Pattern::parse_with_limits(source, &limits).unwrap_err()
+    };
+    assert_eq!(
+        reject(Limits { max_nodes: Some(5), ..limits }),
+        Error::TooManyNodes(6, 5)
+    );
+    assert_eq!(
+        reject(Limits { max_depth: Some(4), ..limits }),
+        Error::DepthLimitExceeded(5, 4)
+    );
+    assert_eq!(
+        reject(Limits { max_unbounded_quantifiers: Some(0), ..limits }),
+        Error::TooManyUnboundedQuantifiers(1, 0)
+    );
+    assert_eq!(
+        reject(Limits { max_search_nodes: Some(1), ..limits }),
+        Error::TooManySearches(2, 1)
+    );
+    assert_eq!(
+        reject(Limits { max_score: Some(100), ..limits }),
+        Error::ComplexityScoreExceeded(153, 100)
+    );
+    assert_eq!(
+        Error::ComplexityScoreExceeded(153, 100).to_string(),
+        "Pattern complexity score 153 exceeds the limit of 100"
+    );
+
+    // The first limit exceeded is reported, and parse errors come first
+    assert_eq!(
+        reject(Limits {
+            max_nodes: Some(0),
+            max_search_nodes: Some(0),
+            ..limits
+        }),
+        Error::TooManyNodes(6, 0)
+    );
+    assert!(matches!(
+        Pattern::parse_with_limits("search(", &Limits::default()),
+        Err(Error::UnexpectedEndOfInput)
+    ));
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    DynMatcher, Error, FormatPathsOpts, Matcher, Pattern, format_paths,
+    format_paths_with_captures,
+};
+use indoc::indoc;
+
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Accepts text that looks like an absolute `http` or `https` URL.
+#[derive(Debug)]
+struct Url;
+
+impl DynMatcher for Url {
+    fn matches(&self, haystack: &CBOR) -> bool {
+        haystack.as_text().is_some_and(|text| {
+            ["http://", "https://"].iter().any(|scheme| {
+                text.strip_prefix(scheme)
+                    .is_some_and(|rest| !rest.is_empty())
+            })
+        })
+    }
+}
+
+/// Accepts a 33-byte compressed secp256k1 public key.
+#[derive(Debug)]
+struct PublicKey;
+
+impl DynMatcher for PublicKey {
+    fn matches(&self, haystack: &CBOR) -> bool {
+        haystack.as_byte_string().is_some_and(|bytes| {
+            bytes.len() == 33 && matches!(bytes[0], 0x02 | 0x03)
+        })
+    }
+}
+
+fn register() {
+    Pattern::register_custom("url", || Url).unwrap();
+    Pattern::register_custom("pubkey", || PublicKey).unwrap();
+}
+
+#[test]
+fn test_custom_matcher_parse_and_display() {
+    register();
+
+    let pattern = Pattern::parse("custom(url)").unwrap();
+    assert_eq!(pattern, Pattern::custom("url", Url));
+    assert_eq!(pattern.to_string(), "custom(url)");
+    assert!(pattern.matches(&cbor(r#""https://example.com""#)));
+    assert!(!pattern.matches(&cbor(r#""example.com""#)));
+    assert!(!pattern.matches(&cbor("42")));
+
+    // Custom patterns combine with the rest of the syntax and display as
+    // they parse
+    for source in [
+        "custom(url) | null",
+        "[(custom(url))*, custom(pubkey)]",
+        r#"{"key": custom(pubkey)}"#,
+        "search(@link(custom(url)))",
+        "tagged(32, custom(url))",
+    ] {
+        let pattern = Pattern::parse(source).unwrap();
+        assert_eq!(pattern.to_string(), source);
+        assert_eq!(Pattern::parse(&pattern.to_string()).unwrap(), pattern);
+    }
+
+    let key = format!("h'02{}'", "ab".repeat(32));
+    let pattern = Pattern::parse("bstr & custom(pubkey)").unwrap();
+    assert!(pattern.matches(&cbor(&key)));
+    assert!(!pattern.matches(&cbor(&format!("h'04{}'", "ab".repeat(32)))));
+}
+
+#[test]
+fn test_custom_matcher_in_search_and_arrays() {
+    register();
+    let haystack = cbor(
+        r#"{"home": "https://example.com", "links": ["ftp://x", "http://a.b"]}"#,
+    );
+
+    let paths = Pattern::parse("search(custom(url))")
+        .unwrap()
+        .paths(&haystack);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {"home": "https://example.com", "links": ["ftp://x", "http://a.b"]}
+            "https://example.com"
+        {"home": "https://example.com", "links": ["ftp://x", "http://a.b"]}
+            ["ftp://x", "http://a.b"]
+                "http://a.b"
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    let links = cbor(r#"["ftp://x", "http://a.b"]"#);
+    assert!(
+        Pattern::parse("[text, custom(url)]")
+            .unwrap()
+            .matches(&links)
+    );
+    assert!(!Pattern::parse("[custom(url)*]").unwrap().matches(&links));
+    assert!(
+        Pattern::parse("[(!custom(url))*, custom(url)]")
+            .unwrap()
+            .matches(&links)
+    );
+}
+
+#[test]
+fn test_custom_matcher_captures() {
+    register();
+    let haystack = cbor(r#"["ftp://x", "http://a.b", "https://c.d"]"#);
+
+    let pattern = Pattern::parse("[text, @links(custom(url)*)]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_path_chains!(&haystack, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @links
+            ["ftp://x", "http://a.b", "https://c.d"]
+                ["http://a.b", "https://c.d"]
+        ["ftp://x", "http://a.b", "https://c.d"]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    let pattern = Pattern::parse("search(@link(custom(url)))").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_path_chains!(&haystack, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @link
+            ["ftp://x", "http://a.b", "https://c.d"]
+                "http://a.b"
+            ["ftp://x", "http://a.b", "https://c.d"]
+                "https://c.d"
+        ["ftp://x", "http://a.b", "https://c.d"]
+            "http://a.b"
+        ["ftp://x", "http://a.b", "https://c.d"]
+            "https://c.d"
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_custom_matcher_errors() {
+    register();
+
+    assert_eq!(
+        Pattern::parse("[custom(uri)]").unwrap_err(),
+        Error::UnknownCustomMatcher("uri".to_string(), 8..11)
+    );
+    assert_eq!(
+        Pattern::parse("custom(uri)").unwrap_err().to_string(),
+        "Unknown custom matcher: uri at 7..10"
+    );
+    assert!(Pattern::parse("custom").is_err());
+    assert!(Pattern::parse("custom()").is_err());
+    assert_eq!(
+        Pattern::parse("custom(url").unwrap_err(),
+        Error::ExpectedCloseParen(String::new(), 10..10)
+    );
+
+    assert_eq!(
+        Pattern::register_custom("web-url", || Url).unwrap_err(),
+        Error::InvalidCustomMatcherName("web-url".to_string())
+    );
+}
@@ -0,0 +1,62 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Error, Matcher, Pattern};
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+#[test]
+fn test_match_encoded_haystack() {
+    let haystack = cbor(r#"{"name": "Alice", "roles": ["admin", "dev"]}"#);
+    let data = haystack.to_cbor_data();
+    let pattern = parse(r#"{"roles": [@role(text)*]}"#);
+
+    assert_eq!(pattern.matches_data(&data), Ok(true));
+    assert_eq!(parse("number").matches_data(&data), Ok(false));
+    assert_eq!(pattern.paths_from_data(&data), Ok(pattern.paths(&haystack)));
+    assert_eq!(
+        pattern.paths_with_captures_from_data(&data),
+        Ok(pattern.paths_with_captures(&haystack))
+    );
+
+    // The same through hex, with whitespace around it ignored
+    let hex = format!(" {}\n", hex::encode(&data));
+    assert_eq!(pattern.matches_hex(&hex), Ok(true));
+    assert_eq!(pattern.paths_from_hex(&hex), Ok(pattern.paths(&haystack)));
+    assert_eq!(
+        pattern.paths_with_captures_from_hex(&hex),
+        Ok(pattern.paths_with_captures(&haystack))
+    );
+    assert_eq!(parse("42").matches_hex("182a"), Ok(true));
+}
+
+#[test]
+fn test_invalid_encoded_haystack() {
+    let pattern = parse("number");
+    let is_invalid_data =
+        |result| matches!(result, Err(Error::InvalidCborData(_)));
+
+    // Valid CBOR, but 1 encoded in two bytes rather than one isn't dCBOR
+    assert!(is_invalid_data(pattern.matches_data(&[0x18, 0x01])));
+    assert!(is_invalid_data(pattern.matches_hex("1801")));
+
+    // Truncated input, and an item with data after it
+    let data = cbor("[1, 2, 3]").to_cbor_data();
+    assert!(is_invalid_data(pattern.matches_data(&data[..2])));
+    assert!(is_invalid_data(pattern.matches_data(&[])));
+    assert!(is_invalid_data(pattern.matches_data(&[0x01, 0x02])));
+    assert!(pattern.paths_from_data(&data[..2]).is_err());
+    assert!(pattern.paths_with_captures_from_data(&data[..2]).is_err());
+
+    // Input that isn't hex at all
+    let error = pattern.matches_hex("18zz").unwrap_err();
+    assert!(matches!(error, Error::InvalidCborData(_)));
+    assert!(
+        error
+            .to_string()
+            .starts_with("Invalid dCBOR data: invalid hex")
+    );
+}
@@ -14,7 +14,7 @@ mod deduplication_tests {
     fn test_no_duplicate_paths_simple_array() {
         // Test case that previously showed duplicate paths
         let cbor_data = parse_dcbor_item("[42, 100, 200]").unwrap();
-        let pattern = Pattern::parse("[@item(number)]").unwrap();
+        let pattern = Pattern::parse("[(@item(number))*]").unwrap();
 
         let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
@@ -28,11 +28,11 @@ mod deduplication_tests {
         let expected = indoc! {r#"
             @item
                 [42, 100, 200]
-                    200
+                    42
                 [42, 100, 200]
                     100
                 [42, 100, 200]
-                    42
+                    200
             [42, 100, 200]
         "#}.trim();
         assert_actual_expected!(actual, expected);
@@ -42,7 +42,7 @@ mod deduplication_tests {
     fn test_no_duplicate_paths_nested_array() {
         let nested_cbor = parse_dcbor_item(r#"[[42], [100]]"#).unwrap();
         let nested_pattern =
-            Pattern::parse("[@outer_item([@inner_item(number)])]").unwrap();
+            Pattern::parse("[(@outer_item([@inner_item(number)]))*]").unwrap();
 
         let (nested_paths, nested_captures) =
             nested_pattern.paths_with_captures(&nested_cbor);
@@ -56,27 +56,28 @@ mod deduplication_tests {
         #[rustfmt::skip]
         let expected = indoc! {r#"
             @inner_item
-                [[42], [100]]
-                    [100]
-                        100
                 [[42], [100]]
                     [42]
                         42
-            @outer_item
                 [[42], [100]]
                     [100]
+                        100
+            @outer_item
                 [[42], [100]]
                     [42]
+                [[42], [100]]
+                    [100]
             [[42], [100]]
         "#}.trim();
         assert_actual_expected!(actual, expected);
     }
 
     #[test]
-    fn test_no_duplicate_paths_with_repeated_values() {
-        // Test with actual duplicate values that should create identical paths
+    fn test_repeated_values_captured_at_each_position() {
+        // Equal values at different positions create identical paths, but
+        // are distinct captures
         let cbor_data = parse_dcbor_item("[42, 100, 42]").unwrap();
-        let pattern = Pattern::parse("[@specific(42)]").unwrap();
+        let pattern = Pattern::parse("[(@specific(42) | *)*]").unwrap();
 
         let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
@@ -91,8 +92,28 @@ mod deduplication_tests {
             @specific
                 [42, 100, 42]
                     42
+                [42, 100, 42]
+                    42
             [42, 100, 42]
         "#}.trim();
         assert_actual_expected!(actual, expected);
     }
+
+    #[test]
+    fn test_capture_counts_each_position_once() {
+        let cbor_data = parse_dcbor_item("[42, 100, 42]").unwrap();
+
+        // Both alternatives match each 42, but at the same position, so it
+        // is captured once per position
+        let pattern = Pattern::parse("[(@item(42 | number))*]").unwrap();
+        let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+        assert_path_chains!(&cbor_data, &paths, &captures);
+        assert_eq!(captures["item"].len(), 3);
+
+        // Search finds the 42 at each position
+        let pattern = Pattern::parse("search(@item(42))").unwrap();
+        let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+        assert_path_chains!(&cbor_data, &paths, &captures);
+        assert_eq!(captures["item"].len(), 2);
+    }
 }
@@ -0,0 +1,147 @@
+//! Drives the `dgrep` example against the files in `tests/fixtures`.
+
+use std::{
+    path::PathBuf,
+    process::{Command, Output},
+    sync::OnceLock,
+};
+
+use indoc::indoc;
+use serde_json::{Value, json};
+
+/// Returns the path of the `dgrep` example, building it the first time it's
+/// needed so the tests never run a binary left over from an older checkout.
+fn dgrep_path() -> &'static PathBuf {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    PATH.get_or_init(|| {
+        // Test binaries live in `target/<profile>/deps`, examples next to it
+        let exe = std::env::current_exe().unwrap();
+        let profile_dir = exe.parent().unwrap().parent().unwrap();
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+        let manifest = format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR"));
+        let mut build = Command::new(cargo);
+        build.args(["build", "--example", "dgrep", "--manifest-path"]);
+        build.arg(manifest);
+        if profile_dir.ends_with("release") {
+            build.arg("--release");
+        }
+        assert!(
+            build.status().unwrap().success(),
+            "building the dgrep example failed"
+        );
+        profile_dir
+            .join("examples")
+            .join(format!("dgrep{}", std::env::consts::EXE_SUFFIX))
+    })
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn dgrep(args: &[&str]) -> Output {
+    Command::new(dgrep_path()).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone())
+        .unwrap()
+        .trim_end()
+        .to_string()
+}
+
+#[test]
+fn test_dgrep_diagnostic_input() {
+    let output = dgrep(&[r#"{"name": @name(text)}"#, &fixture("people.diag")]);
+    assert_eq!(output.status.code(), Some(0));
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @name
+            {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+                [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                    {"age": 30, "name": "Alice"}
+                        "Alice"
+            {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+                [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                    {"age": 25, "name": "Bob"}
+                        "Bob"
+        {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+            [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                {"age": 30, "name": "Alice"}
+        {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+            [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]
+                {"age": 25, "name": "Bob"}
+    "#}.trim();
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn test_dgrep_binary_input() {
+    // The binary fixture encodes the same document as the diagnostic one
+    let pattern = "search(>=25)";
+    let text = dgrep(&[pattern, &fixture("people.diag")]);
+    let binary = dgrep(&[pattern, &fixture("people.cbor")]);
+    assert_eq!(binary.status.code(), Some(0));
+    assert_eq!(stdout(&binary), stdout(&text));
+    assert!(!stdout(&binary).is_empty());
+}
+
+#[test]
+fn test_dgrep_count_and_first() {
+    let output = dgrep(&["--count", "number", &fixture("people.cbor")]);
+    assert_eq!(stdout(&output), "3");
+
+    let output =
+        dgrep(&["--first", "--count", "number", &fixture("people.cbor")]);
+    assert_eq!(stdout(&output), "1");
+
+    let output = dgrep(&["--first", "text", &fixture("people.diag")]);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}
+            "count"
+    "#}.trim();
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn test_dgrep_json() {
+    let output = dgrep(&["--json", r#"@n("Bob")"#, &fixture("people.diag")]);
+    assert_eq!(output.status.code(), Some(0));
+    let value: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let document = r#"{"count": 2, "people": [{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]}"#;
+    let path = json!([
+        document,
+        r#"[{"age": 30, "name": "Alice"}, {"age": 25, "name": "Bob"}]"#,
+        r#"{"age": 25, "name": "Bob"}"#,
+        r#""Bob""#,
+    ]);
+    assert_eq!(
+        value,
+        json!({ "paths": [path], "captures": { "n": [path] } })
+    );
+}
+
+#[test]
+fn test_dgrep_exit_status() {
+    // No match
+    let output = dgrep(&["bstr", &fixture("people.diag")]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+
+    // Bad pattern, reported against the pattern source
+    let output = dgrep(&["[number", &fixture("people.diag")]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("error:"));
+
+    // Missing file
+    let output = dgrep(&["number", &fixture("missing.cbor")]);
+    assert_eq!(output.status.code(), Some(2));
+
+    // Missing pattern
+    let output = dgrep(&[]);
+    assert_eq!(output.status.code(), Some(2));
+}
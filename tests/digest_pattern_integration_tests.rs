@@ -118,3 +118,82 @@ fn test_digest_pattern_errors() {
     // Test empty content
     assert!(Pattern::parse("digest''").is_err());
 }
+
+/// A document holding the map `{"name": "Alice", "age": 30}` twice, once
+/// directly and once inside an array.
+fn document_with_map() -> (CBOR, CBOR) {
+    let mut map = Map::new();
+    map.insert("name", "Alice");
+    map.insert("age", 30);
+    let map = CBOR::from(map);
+
+    let mut document = Map::new();
+    document.insert("person", map.clone());
+    document.insert("people", vec![CBOR::from("Bob"), map.clone()]);
+    (CBOR::from(document), map)
+}
+
+#[test]
+fn test_encoded_digest_search() {
+    bc_components::register_tags();
+
+    let (document, map) = document_with_map();
+    let digest = Digest::from_image(map.to_cbor_data());
+
+    let src = format!("search(encoded-digest'{}')", hex::encode(digest.data()));
+    let pattern = Pattern::parse(&src).unwrap();
+    let paths = pattern.paths(&document);
+    assert_eq!(paths.len(), 2);
+    for path in &paths {
+        assert_eq!(path.last(), Some(&map));
+    }
+
+    // The UR form of the digest finds the same subtrees
+    let src = format!("search(encoded-digest'{}')", digest.ur_string());
+    let pattern = Pattern::parse(&src).unwrap();
+    assert_eq!(pattern.paths(&document), paths);
+
+    // A prefix is enough
+    let src = format!(
+        "search(encoded-digest'{}')",
+        hex::encode(&digest.data()[..4])
+    );
+    let pattern = Pattern::parse(&src).unwrap();
+    assert_eq!(pattern.paths(&document), paths);
+}
+
+#[test]
+fn test_encoded_digest_does_not_match_stored_digests() {
+    bc_components::register_tags();
+
+    let (_, map) = document_with_map();
+    let digest = Digest::from_image(map.to_cbor_data());
+
+    // `digest'...'` matches the stored digest, `encoded-digest'...'` the map
+    let haystack = CBOR::from(vec![digest.to_cbor(), map.clone()]);
+    let stored = Pattern::search(Pattern::digest(digest));
+    let encoded = Pattern::search(Pattern::encoded_digest(digest));
+    assert_eq!(stored.paths(&haystack)[0].last(), Some(&digest.to_cbor()));
+    assert_eq!(encoded.paths(&haystack)[0].last(), Some(&map));
+    assert_eq!(encoded.paths(&haystack).len(), 1);
+}
+
+#[test]
+fn test_encoded_digest_round_trip() {
+    bc_components::register_tags();
+
+    let (_, map) = document_with_map();
+    let digest = Digest::from_image(map.to_cbor_data());
+    let patterns = vec![
+        Pattern::encoded_digest(digest),
+        Pattern::encoded_digest_prefix(&digest.data()[..8]),
+    ];
+    for pattern in patterns {
+        let string_repr = pattern.to_string();
+        assert!(string_repr.starts_with("encoded-digest'"));
+        assert_eq!(Pattern::parse(&string_repr).unwrap(), pattern);
+    }
+
+    assert!(Pattern::parse("encoded-digest''").is_err());
+    assert!(Pattern::parse("encoded-digest'abc'").is_err());
+}
@@ -0,0 +1,131 @@
+use dcbor::prelude::*;
+use dcbor_pattern::dsl::*;
+
+/// Asserts that a built pattern is identical to the parsed source.
+fn assert_builds(built: Pattern, source: &str) {
+    let parsed = Pattern::parse(source).unwrap();
+    assert_eq!(built, parsed, "built {} for {}", built, source);
+}
+
+#[test]
+fn test_dsl_values() {
+    assert_builds(any(), "*");
+    assert_builds(bool(), "bool");
+    assert_builds(bool_value(true), "true");
+    assert_builds(number(), "number");
+    assert_builds(number_value(42), "42");
+    assert_builds(number_range(1..=10), "1...10");
+    assert_builds(text(), "text");
+    assert_builds(text_value("hello"), r#""hello""#);
+    assert_builds(text_regex(regex::Regex::new("h.*o").unwrap()), "/h.*o/");
+    assert_builds(bstr(), "bstr");
+    assert_builds(bstr_value([0xca, 0xfe]), "h'cafe'");
+    assert_builds(date(), "date");
+    assert_builds(null(), "null");
+    assert_builds(known(), "known");
+    assert_builds(digest(), "digest");
+    assert_builds(cbor(vec![1, 2]), "cbor'[1, 2]'");
+}
+
+#[test]
+fn test_dsl_arrays() {
+    assert_builds(any_array(), "array");
+    assert_builds(array([]), "[{0}]");
+    assert_builds(array([number()]), "[number]");
+    assert_builds(array([number(), text()]), "[number, text]");
+    assert_builds(array_len(2..=5), "[{2,5}]");
+    assert_builds(array_len(3..), "[{3,}]");
+    assert_builds(
+        array([
+            number().repeat(0..),
+            capture("item", number_value(42)),
+            any().repeat(0..),
+        ]),
+        "[(number)*, @item(42), (*)*]",
+    );
+}
+
+#[test]
+fn test_dsl_quantifiers() {
+    assert_builds(number(), "(number)");
+    assert_builds(number().repeat(0..), "(number)*");
+    assert_builds(number().repeat(1..), "(number)+");
+    assert_builds(number().repeat(0..=1), "(number)?");
+    assert_builds(number().repeat(2..=5), "(number){2,5}");
+    assert_builds(number().repeat_lazy(0..), "(number)*?");
+    assert_builds(number().repeat_lazy(2..=3), "(number){2,3}?");
+    assert_builds(number().repeat_possessive(1..), "(number)++");
+    assert_builds(
+        text().quantified(Quantifier::new(..=3, Reluctance::Greedy)),
+        "(text){,3}",
+    );
+}
+
+#[test]
+fn test_dsl_operators() {
+    assert_builds(number().or(text()), "number | text");
+    assert_builds(number().or(text()).or(null()), "number | text | null");
+    assert_builds(number().or(text().or(null())), "number | text | null");
+    assert_builds(number().and(not(number_value(0))), "number & !0");
+    assert_builds(
+        number().and(number_range(1..=9)).and(not(number_value(5))),
+        "number & 1...9 & !5",
+    );
+    assert_builds(not(text()), "!text");
+    assert_builds(number().or(text()), "(number | text)");
+    assert_builds(
+        number().or(text()).and(not(number_value(0))),
+        "(number | text) & !0",
+    );
+
+    // Length constraints on the same kind of value merge into one
+    assert_builds(array_len(2..).and(array_len(..=4)), "[{2,}] & [{,4}]");
+}
+
+#[test]
+fn test_dsl_maps_and_tags() {
+    assert_builds(any_map(), "map");
+    assert_builds(map_len(1..), "{{1,}}");
+    assert_builds(
+        map([(text_value("id"), number()), (text_value("name"), text())]),
+        r#"{"id": number, "name": text}"#,
+    );
+    assert_builds(
+        map_value(text_value("a"), number().capture("n")),
+        r#"{"a"}: @n(number)"#,
+    );
+    assert_builds(any_tagged(), "tagged");
+    assert_builds(tagged(1, number()), "tagged(1, number)");
+}
+
+#[test]
+fn test_dsl_meta() {
+    assert_builds(number().capture("n"), "@n(number)");
+    assert_builds(capture("n", number()), "@n(number)");
+    assert_builds(backref("n"), "=n");
+    assert_builds(number().search(), "search(number)");
+    assert_builds(search(text().capture("t")), "search(@t(text))");
+    assert_builds(child(text()), "child(text)");
+    assert_builds(untag(number()), "untag(number)");
+    assert_builds(none(null()), "none(null)");
+    assert_builds(
+        array([capture("a", number()), backref("a")]),
+        "[@a(number), =a]",
+    );
+}
+
+#[test]
+fn test_dsl_matching() {
+    let pattern = array([
+        number().repeat(0..),
+        capture("item", number_value(42)),
+        any().repeat(0..),
+    ]);
+    let haystack = CBOR::from(vec![1, 42, 3]);
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_eq!(paths, vec![vec![haystack.clone()]]);
+    assert_eq!(
+        captures["item"],
+        vec![vec![haystack.clone(), CBOR::from(42)]]
+    );
+}
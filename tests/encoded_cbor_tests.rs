@@ -0,0 +1,162 @@
+mod common;
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    Error, FormatPathsOpts, Matcher, Pattern, Program, format_paths,
+    format_paths_with_captures,
+};
+use indoc::indoc;
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+/// A byte string holding the encoding of the item in diagnostic notation.
+fn encoded(s: &str) -> CBOR { CBOR::to_byte_string(cbor(s).to_cbor_data()) }
+
+#[test]
+fn test_decoded_paths() {
+    let pattern = parse(r#"bstr(cbor: {"type": text})"#);
+    let haystack = encoded(r#"{"type": "person"}"#);
+
+    // The decoded item follows the byte string in the path
+    let paths = pattern.paths(&haystack);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        h'a1647479706566706572736f6e'
+            {"type": "person"}
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    assert!(!pattern.matches(&encoded(r#"{"type": 1}"#)));
+    assert!(!pattern.matches(&encoded(r#"["type", "person"]"#)));
+}
+
+#[test]
+fn test_decoded_no_match() {
+    let pattern = parse("bstr(cbor: *)");
+
+    assert!(pattern.matches(&encoded("1")));
+    for haystack in [
+        // Not well-formed
+        CBOR::to_byte_string([0xff]),
+        CBOR::to_byte_string([]),
+        // Well-formed but not deterministic: 1 in two bytes
+        CBOR::to_byte_string([0x18, 0x01]),
+        // An item followed by extra bytes
+        CBOR::to_byte_string([0x01, 0x02]),
+        // Not a byte string
+        cbor(r#""a""#),
+        cbor("1"),
+    ] {
+        assert!(!pattern.matches(&haystack), "{}", haystack);
+    }
+}
+
+#[test]
+fn test_decoded_captures() {
+    // An embedded document in tag 24, captured through the decoding
+    let pattern = parse(r#"tagged(24, bstr(cbor: {"type": @type(text)}))"#);
+    let haystack = CBOR::to_tagged_value(24, encoded(r#"{"type": "person"}"#));
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @type
+            24(h'a1647479706566706572736f6e')
+                h'a1647479706566706572736f6e'
+                    {"type": "person"}
+                        "person"
+        24(h'a1647479706566706572736f6e')
+            h'a1647479706566706572736f6e'
+                {"type": "person"}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_decoded_nested_search() {
+    // A search finds the byte string, and the search inside it continues
+    // into the decoded item
+    let inner = encoded("[1, 2]");
+    let mut map = Map::new();
+    map.insert("payload", encoded(r#"{"a": 1}"#));
+    let haystack = CBOR::from(map);
+    let pattern = parse("search(bstr(cbor: search(@n(number))))");
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_eq!(paths.len(), 1);
+    assert_eq!(captures["n"].len(), 1);
+    assert_eq!(captures["n"][0].last(), Some(&CBOR::from(1)));
+
+    let pattern = parse("bstr(cbor: [@n(number)*])");
+    let (_, captures) = pattern.paths_with_captures(&inner);
+    assert_eq!(
+        captures["n"],
+        [
+            vec![inner.clone(), cbor("[1, 2]"), cbor("1")],
+            vec![inner.clone(), cbor("[1, 2]"), cbor("2")],
+        ]
+    );
+}
+
+#[test]
+fn test_decoded_compiled_matches_direct() {
+    let haystack = CBOR::from(vec![encoded(r#"{"a": [1, 2]}"#), CBOR::from(1)]);
+    for pattern in [
+        r#"[bstr(cbor: {"a": [@n(number)*]}), number]"#,
+        r#"[(bstr(cbor: *))*, @x(number)]"#,
+        "[bstr(cbor: text), *]",
+        "search(bstr(cbor: @m(map)))",
+    ] {
+        let pattern = parse(pattern);
+        let program = Program::compile(&pattern);
+
+        assert_eq!(
+            program.run(&haystack),
+            pattern.paths_with_captures(&haystack),
+            "{}",
+            pattern
+        );
+    }
+}
+
+#[test]
+fn test_decoded_display_round_trip() {
+    for source in [
+        "bstr(cbor: number)",
+        r#"bstr(cbor: {"type": @type(text)})"#,
+        "bstr(cbor: bstr(cbor: *))",
+        "bstr(cbor: number | text)",
+    ] {
+        let pattern = parse(source);
+        assert_eq!(pattern.to_string(), source);
+        assert_eq!(parse(&pattern.to_string()), pattern);
+    }
+
+    assert_eq!(
+        Pattern::byte_string_decoded(Pattern::any_number()),
+        parse("bstr( cbor :number)")
+    );
+}
+
+#[test]
+fn test_decoded_parse_errors() {
+    assert!(matches!(
+        Pattern::parse("bstr(cbor number)"),
+        Err(Error::ExpectedColon(_, _))
+    ));
+    assert!(Pattern::parse("bstr(cbor: )").is_err());
+    assert!(Pattern::parse("bstr(cbor: number").is_err());
+    assert!(Pattern::parse("bstr(cbor: number, text)").is_err());
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    FormatPathsOpts, Matcher, Pattern, format_paths, format_paths_with_captures,
+};
+use indoc::indoc;
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+#[test]
+fn test_entry_paths() {
+    let pattern = parse("entry(text: number)");
+    let cbor_data = cbor(r#"{"a": 1, "b": "x", 2: 3, "c": 4}"#);
+
+    // Each matching entry yields its own path
+    let paths = pattern.paths(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {2: 3, "a": 1, "b": "x", "c": 4}
+            ["a", 1]
+        {2: 3, "a": 1, "b": "x", "c": 4}
+            ["c", 4]
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    assert!(!pattern.matches(&cbor(r#"{"b": "x"}"#)));
+    assert!(!pattern.matches(&cbor(r#"["a", 1]"#)));
+}
+
+#[test]
+fn test_search_entry_captures() {
+    let pattern = parse("search(entry(@k(text): number))");
+    let cbor_data = cbor(r#"{"a": [{"b": 1}], "c": {"d": 2, "e": "x"}}"#);
+
+    // One capture per matching entry, each running through its map
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @k
+            {"a": [{"b": 1}], "c": {"d": 2, "e": "x"}}
+                [{"b": 1}]
+                    {"b": 1}
+                        ["b", 1]
+                            "b"
+            {"a": [{"b": 1}], "c": {"d": 2, "e": "x"}}
+                {"d": 2, "e": "x"}
+                    ["d", 2]
+                        "d"
+        {"a": [{"b": 1}], "c": {"d": 2, "e": "x"}}
+            [{"b": 1}]
+                {"b": 1}
+        {"a": [{"b": 1}], "c": {"d": 2, "e": "x"}}
+            {"d": 2, "e": "x"}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_entry_captures_key_and_value() {
+    let pattern = parse("entry(@k(text): @v([(*)*]))");
+    let cbor_data = cbor(r#"{"a": [1], "b": 2}"#);
+
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @k
+            {"a": [1], "b": 2}
+                ["a", [1]]
+                    "a"
+        @v
+            {"a": [1], "b": 2}
+                ["a", [1]]
+                    [1]
+        {"a": [1], "b": 2}
+            ["a", [1]]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_entry_captures_through_vm() {
+    // Inside arrays and tagged values the entry pattern runs on the VM, which
+    // extends its captures the same way, and a tagged value's paths continue
+    // through the content
+    let map = r#"{"a": 1, "b": "x"}"#;
+    let expected_entry = vec![cbor(map), cbor(r#"["a", 1]"#), cbor(r#""a""#)];
+
+    let array = cbor(&format!("[{}]", map));
+    let (paths, captures) =
+        parse("[entry(@k(text): number)]").paths_with_captures(&array);
+    assert_eq!(paths, vec![vec![array.clone()]]);
+    let mut expected = vec![array];
+    expected.extend(expected_entry.iter().cloned());
+    assert_eq!(captures["k"], vec![expected]);
+
+    let tagged = cbor(&format!("1({})", map));
+    let (paths, captures) = parse("tagged(1, entry(@k(text): number))")
+        .paths_with_captures(&tagged);
+    assert_eq!(
+        paths,
+        vec![vec![tagged.clone(), cbor(map), cbor(r#"["a", 1]"#)]]
+    );
+    let mut expected = vec![tagged];
+    expected.extend(expected_entry);
+    assert_eq!(captures["k"], vec![expected]);
+}
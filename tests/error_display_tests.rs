@@ -0,0 +1,245 @@
+use dcbor_pattern::{Error, Pattern, Token};
+use indoc::indoc;
+
+/// Parses `source`, expecting an error, and renders the error against it.
+fn render(source: &str) -> String {
+    Pattern::parse(source).unwrap_err().to_display(source)
+}
+
+#[test]
+fn test_display_single_line() {
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        error: Unexpected '|' at 9..10
+         --> 1:10
+          |
+        1 | [number, | text]
+          |          ^
+    "#}.trim_end();
+    assert_eq!(render("[number, | text]"), expected);
+}
+
+#[test]
+fn test_display_multi_line() {
+    let source = "{\n    \"name\": text,\n    \"id\": h'01zz'\n}";
+    let error = Pattern::parse(source).unwrap_err();
+
+    // The span points at the first invalid character inside the literal
+    let span = error.span().unwrap();
+    assert_eq!(&source[span], "z");
+
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        error: Invalid hex string at 34..35
+         --> 3:15
+          |
+        3 |     "id": h'01zz'
+          |               ^
+    "#}.trim_end();
+    assert_eq!(error.to_display(source), expected);
+}
+
+#[test]
+fn test_display_unterminated_string() {
+    // An unterminated literal is underlined to the end of its line
+    let source = "[\n  text,\n  \"abc, number\n]";
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        error: Unterminated string literal at 12..26
+         --> 3:3
+          |
+        3 |   "abc, number
+          |   ^^^^^^^^^^^^
+    "#}.trim_end();
+    assert_eq!(render(source), expected);
+}
+
+#[test]
+fn test_display_invalid_quoted_contents() {
+    // Errors inside quoted literals underline the whole literal
+    let source = "[text, date'2023-13-45']";
+    let error = Pattern::parse(source).unwrap_err();
+    assert!(matches!(error, Error::InvalidDateFormat(_, _)));
+    assert_eq!(&source[error.span().unwrap()], "date'2023-13-45'");
+
+    let source = "[number, /(abc/]";
+    let error = Pattern::parse(source).unwrap_err();
+    assert!(matches!(error, Error::InvalidRegex(_, _)));
+    assert_eq!(&source[error.span().unwrap()], "/(abc/");
+
+    let source = "tagged(/(abc/, text)";
+    let error = Pattern::parse(source).unwrap_err();
+    assert!(matches!(error, Error::InvalidRegex(_, _)));
+    assert_eq!(&source[error.span().unwrap()], "/(abc/");
+}
+
+#[test]
+fn test_display_wide_characters() {
+    // Columns count characters, not bytes
+    let source = "\"héllo\" | %";
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        error: Unrecognized token at position 11..12
+         --> 1:11
+          |
+        1 | "héllo" | %
+          |           ^
+    "#}.trim_end();
+    assert_eq!(render(source), expected);
+}
+
+#[test]
+fn test_display_end_of_input() {
+    let source = "[number,\n  text &";
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        error: Unexpected end of input
+         --> 2:9
+          |
+        2 |   text &
+          |         ^
+    "#}.trim_end();
+    assert_eq!(render(source), expected);
+
+    assert_eq!(Error::Unknown.to_display("x"), "error: Unknown error");
+}
+
+#[test]
+fn test_from_str_round_trip() {
+    for source in [
+        "number",
+        r#"[text, (number)*, @rest((*)*)]"#,
+        r#"{"name": @name(text)} | search(>=10)"#,
+        "tagged(1, !bool)",
+    ] {
+        let pattern: Pattern = source.parse().unwrap();
+        assert_eq!(pattern.to_string().parse::<Pattern>(), Ok(pattern.clone()));
+        assert_eq!(Pattern::try_from(source), Ok(pattern.clone()));
+        assert_eq!(Pattern::try_from(source.to_string()), Ok(pattern));
+    }
+
+    // Errors convert into boxed standard errors, so `?` works on them
+    fn parse_boxed(
+        source: &str,
+    ) -> Result<Pattern, Box<dyn std::error::Error>> {
+        Ok(source.parse()?)
+    }
+    assert!(parse_boxed("number").is_ok());
+    let error = parse_boxed("[number").unwrap_err();
+    assert_eq!(error.to_string(), "Expected closing bracket at 7..7");
+    assert!(error.source().is_none());
+}
+
+#[test]
+fn test_error_messages() {
+    let message =
+        |source: &str| source.parse::<Pattern>().unwrap_err().to_string();
+
+    assert_eq!(message("[number"), "Expected closing bracket at 7..7");
+    assert_eq!(message("{1: 2"), "Expected closing brace at 5..5");
+    assert_eq!(
+        message("search(number"),
+        "Expected closing parenthesis at 13..13"
+    );
+    assert_eq!(message("42 text"), "Extra data at 3..7");
+    assert_eq!(message("tagged("), "Unexpected end of input");
+
+    // Unexpected tokens are shown as written, or by kind for literals
+    assert_eq!(message(")"), "Unexpected ')' at 0..1");
+    assert_eq!(message("[number, | text]"), "Unexpected '|' at 9..10");
+    assert_eq!(message("{1 2}"), "Unexpected number literal at 3..4");
+
+    assert_eq!(message("%"), "Unrecognized token at position 0..1");
+    assert_eq!(message("/(abc/"), "Invalid regex pattern at 0..6");
+    assert_eq!(message("\"abc"), "Unterminated string literal at 0..4");
+    assert_eq!(message("{3,1}"), "Invalid range at 0..5");
+    assert_eq!(
+        message("@a(1) | @a(2)"),
+        "Duplicate capture name: a at 8..10"
+    );
+    assert_eq!(
+        message("number{2}{3}"),
+        "Quantifier has nothing to repeat at 9..12"
+    );
+}
+
+#[test]
+fn test_error_lexemes() {
+    // Syntax errors carry the source they point at, from each parser module
+    for (source, lexeme, span) in [
+        // A bad hex digit, and hex digits that don't come in pairs
+        ("bstr | h'0g'", "g", 10..11),
+        ("[h'abc']", "h'abc'", 1..7),
+        // Bad regexes, as a token and in the text a tagged pattern rescans
+        ("text | /(/", "/(/", 7..10),
+        ("tagged(/(/, *)", "/(/", 7..10),
+        ("'/(/'", "'/(/'", 0..5),
+        // A backwards range, and a quantifier on a quantifier
+        ("(number){3,1}", "{3,1}", 8..13),
+        ("number**", "*", 7..8),
+        // Tokens that can't follow &
+        ("number & )", ")", 9..10),
+        ("number & &", "&", 9..10),
+        // A search option that isn't a number
+        ("search(number, depth: x)", "x", 22..23),
+        // A map closed with a bracket, and one never closed
+        (r#"{"a": number ]"#, "]", 13..14),
+        (r#"{"a": number"#, "", 12..12),
+        ("42.x", ".x", 2..4),
+    ] {
+        let error = Pattern::parse(source).unwrap_err();
+        assert_eq!(error.lexeme(), Some(lexeme), "{}", source);
+        assert_eq!(error.span(), Some(span), "{}", source);
+    }
+
+    assert_eq!(
+        Pattern::parse(r#"{"a": number ]"#),
+        Err(Error::UnexpectedToken(
+            Box::new(Token::BracketClose),
+            "]".to_string(),
+            13..14
+        ))
+    );
+
+    // Errors that name what they're about have no lexeme
+    let error = Pattern::parse("@a(1) | @a(2)").unwrap_err();
+    assert_eq!(error.lexeme(), None);
+    assert_eq!(error.span(), Some(8..10));
+    assert_eq!(Pattern::parse("").unwrap_err().lexeme(), None);
+}
+
+#[test]
+fn test_sequence_outside_array() {
+    // A top-level comma points at the sequence, not the rest of the input
+    for (source, span) in [
+        ("number, text", 6..7),
+        ("1 | 2, 3", 5..6),
+        ("@x(number) , text", 11..12),
+    ] {
+        let error = Pattern::parse(source).unwrap_err();
+        assert_eq!(
+            error,
+            Error::SequenceOutsideArray(",".to_string(), span),
+            "{}",
+            source
+        );
+    }
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        error: Sequence outside an array pattern at 6..7; wrap it in [...] or use Pattern::match_sequence
+         --> 1:7
+          |
+        1 | number, text
+          |       ^
+    "#}.trim_end();
+    assert_eq!(render("number, text"), expected);
+
+    // The same sequence is fine between brackets
+    assert!(Pattern::parse("[number, text]").is_ok());
+    assert_eq!(
+        Pattern::parse("42 text").unwrap_err(),
+        Error::ExtraData("text".to_string(), 3..7)
+    );
+}
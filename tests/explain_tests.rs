@@ -0,0 +1,114 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::Pattern;
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+/// Explains `pattern` against `data` and returns the failing sub-pattern,
+/// the failing node, and its depth.
+fn failure(pattern: &str, data: &str) -> (String, CBOR, usize) {
+    let report = parse(pattern).explain(&cbor(data));
+    let failure = report.failure().expect("pattern unexpectedly matched");
+    (
+        failure.pattern().to_string(),
+        failure.node().clone(),
+        failure.depth(),
+    )
+}
+
+#[test]
+fn test_explain_matching_pattern() {
+    let report = parse(r#"{"header": [number, (text)+]}"#)
+        .explain(&cbor(r#"{"header": [1, "a", "b"]}"#));
+    assert!(report.is_match());
+    assert!(report.failure().is_none());
+}
+
+#[test]
+fn test_explain_map_value_array_element() {
+    let pattern = r#"{"header": [number, (text)+], "body": *}"#;
+
+    // The second element of the header array is not text
+    let (failed, node, depth) =
+        failure(pattern, r#"{"header": [1, 2], "body": null}"#);
+    assert_eq!(failed, "text");
+    assert_eq!(node, cbor("2"));
+    assert_eq!(depth, 2);
+
+    // The first element of the header array is not a number
+    let (failed, node, depth) =
+        failure(pattern, r#"{"header": ["x", "y"], "body": null}"#);
+    assert_eq!(failed, "number");
+    assert_eq!(node, cbor(r#""x""#));
+    assert_eq!(depth, 2);
+
+    // The header value is not an array
+    let (failed, node, depth) =
+        failure(pattern, r#"{"header": "none", "body": null}"#);
+    assert_eq!(failed, "[number, (text)+]");
+    assert_eq!(node, cbor(r#""none""#));
+    assert_eq!(depth, 1);
+}
+
+#[test]
+fn test_explain_missing_map_key() {
+    let pattern = r#"{"header": [number, (text)+], "body": *}"#;
+    let data = r#"{"header": [1, "a"]}"#;
+    let (failed, node, depth) = failure(pattern, data);
+    assert_eq!(failed, r#""body""#);
+    assert_eq!(node, cbor(data));
+    assert_eq!(depth, 0);
+}
+
+#[test]
+fn test_explain_array_too_short() {
+    let (failed, node, depth) = failure("[number, text, bool]", r#"[1, "a"]"#);
+    assert_eq!(failed, "bool");
+    assert_eq!(node, cbor(r#"[1, "a"]"#));
+    assert_eq!(depth, 0);
+}
+
+#[test]
+fn test_explain_tagged_content() {
+    let (failed, node, depth) = failure(
+        r#"tagged(100, [text, {"n": number}])"#,
+        r#"100(["a", {"n": "x"}])"#,
+    );
+    assert_eq!(failed, "number");
+    assert_eq!(node, cbor(r#""x""#));
+    assert_eq!(depth, 3);
+
+    // A different tag is blamed on the tagged pattern itself
+    let (failed, _, depth) = failure(r#"tagged(100, [text])"#, r#"101(["a"])"#);
+    assert_eq!(failed, "tagged(100, [text])");
+    assert_eq!(depth, 0);
+}
+
+#[test]
+fn test_explain_and_or() {
+    // The first failing conjunct is reported
+    let (failed, _, _) = failure("number & >10", "5");
+    assert_eq!(failed, ">10");
+
+    // The alternative that got furthest is reported
+    let (failed, node, depth) =
+        failure("[text] | [number, [number]]", r#"[1, ["a"]]"#);
+    assert_eq!(failed, "number");
+    assert_eq!(node, cbor(r#""a""#));
+    assert_eq!(depth, 2);
+
+    // Captures are transparent
+    let (failed, node, _) = failure("[@x(number)]", r#"["a"]"#);
+    assert_eq!(failed, "number");
+    assert_eq!(node, cbor(r#""a""#));
+}
+
+#[test]
+fn test_explain_report_display() {
+    let report = parse("[number, text]").explain(&cbor("[1, 2]"));
+    assert_eq!(report.to_string(), "text did not match 2 at depth 1");
+}
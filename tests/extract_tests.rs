@@ -0,0 +1,145 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{ExtractError, Pattern};
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+fn person() -> CBOR {
+    cbor(
+        r#"{
+            "name": "Alice",
+            "age": 42.5,
+            "key": h'0102ff',
+            "born": 1(1700000000),
+            "tags": ["admin", "ops"]
+        }"#,
+    )
+}
+
+#[test]
+fn test_extract_one() {
+    let haystack = person();
+
+    let name: String = parse(r#"{"name": @name(text)}"#)
+        .extract_one(&haystack, "name")
+        .unwrap();
+    assert_eq!(name, "Alice");
+
+    let age: f64 = parse(r#"{"age": @age(number)}"#)
+        .extract_one(&haystack, "age")
+        .unwrap();
+    assert_eq!(age, 42.5);
+
+    // Byte strings convert to ByteString, and from there to bytes
+    let key: Vec<u8> = parse(r#"{"key": @key(bstr)}"#)
+        .extract_one::<ByteString>(&haystack, "key")
+        .unwrap()
+        .into();
+    assert_eq!(key, [0x01, 0x02, 0xff]);
+
+    let born: Date = parse(r#"{"born": @born(date)}"#)
+        .extract_one(&haystack, "born")
+        .unwrap();
+    assert_eq!(born, Date::try_from(cbor("1(1700000000)")).unwrap());
+
+    // The value is the node that ends the captured path, deep in a search
+    let tag: String = parse(r#"search(@tag("ops"))"#)
+        .extract_one(&haystack, "tag")
+        .unwrap();
+    assert_eq!(tag, "ops");
+}
+
+#[test]
+fn test_extract_all() {
+    let haystack = person();
+
+    let tags: Vec<String> = parse(r#"{"tags": [(@tag(text))*]}"#)
+        .extract_all(&haystack, "tag")
+        .unwrap();
+    assert_eq!(tags, ["admin", "ops"]);
+
+    let texts: Vec<String> = parse("search(@t(text))")
+        .extract_all(&haystack, "t")
+        .unwrap();
+    assert_eq!(texts.len(), 8);
+    assert!(texts.contains(&"Alice".to_string()));
+
+    // A match that skips the capture extracts nothing
+    let numbers: Vec<f64> = parse("@n(number) | map")
+        .extract_all(&haystack, "n")
+        .unwrap();
+    assert!(numbers.is_empty());
+}
+
+#[test]
+fn test_extract_errors() {
+    let haystack = person();
+
+    assert!(matches!(
+        parse("@n(number)").extract_one::<f64>(&haystack, "n"),
+        Err(ExtractError::NoMatch)
+    ));
+    assert!(matches!(
+        parse("@n(number)").extract_all::<f64>(&haystack, "n"),
+        Err(ExtractError::NoMatch)
+    ));
+
+    // A misspelled name, and a capture the match didn't pass through
+    let pattern = parse(r#"{"name": @name(text)}"#);
+    assert!(matches!(
+        pattern.extract_one::<String>(&haystack, "nmae"),
+        Err(ExtractError::MissingCapture(name)) if name == "nmae"
+    ));
+    assert!(matches!(
+        pattern.extract_all::<String>(&haystack, "nmae"),
+        Err(ExtractError::MissingCapture(name)) if name == "nmae"
+    ));
+    assert!(matches!(
+        parse("@n(number) | map").extract_one::<f64>(&haystack, "n"),
+        Err(ExtractError::MissingCapture(name)) if name == "n"
+    ));
+
+    let error = parse(r#"{"tags": [(@tag(text))*]}"#)
+        .extract_one::<String>(&haystack, "tag")
+        .unwrap_err();
+    assert!(matches!(
+        &error,
+        ExtractError::MultipleValues(name, 2) if name == "tag"
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Expected one value captured as @tag, found 2"
+    );
+}
+
+#[test]
+fn test_extract_conversion_error() {
+    let haystack = person();
+    let error = parse(r#"{"name": @name(text)}"#)
+        .extract_one::<f64>(&haystack, "name")
+        .unwrap_err();
+    let ExtractError::Conversion(name, value, source) = &error else {
+        panic!("expected a conversion error, got {:?}", error);
+    };
+    assert_eq!(name, "name");
+    assert_eq!(value, &CBOR::from("Alice"));
+
+    // The message names the capture and the value, then says why
+    assert_eq!(
+        error.to_string(),
+        format!("Cannot convert \"Alice\" captured as @name: {}", source)
+    );
+    assert!(std::error::Error::source(&error).is_some());
+
+    let error = parse(r#"{"tags": [(@tag(*))*]}"#)
+        .extract_all::<f64>(&haystack, "tag")
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        ExtractError::Conversion(_, value, _) if value == CBOR::from("admin")
+    ));
+}
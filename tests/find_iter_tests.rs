@@ -0,0 +1,221 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Error, Matcher, Pattern, SearchOpts};
+
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// An array of 1,000 maps, each mapping a key to an array holding a
+/// number, for 4,001 nodes in all.
+fn large_haystack() -> CBOR {
+    let elements: Vec<CBOR> = (0..1_000)
+        .map(|i| {
+            let mut map = Map::new();
+            map.insert("n", vec![i]);
+            CBOR::from(map)
+        })
+        .collect();
+    CBOR::from(elements)
+}
+
+#[test]
+fn test_find_iter_stops_early() {
+    let haystack = large_haystack();
+    let pattern = Pattern::parse("number").unwrap();
+
+    let mut iter = pattern.find_iter(&haystack);
+    let numbers: Vec<CBOR> =
+        iter.by_ref().take(3).map(|m| m.node().clone()).collect();
+    assert_eq!(numbers, vec![CBOR::from(0), CBOR::from(1), CBOR::from(2)]);
+
+    // Only the outer array and the first three maps were visited
+    assert_eq!(iter.visited(), 1 + 3 * 4);
+
+    // Resuming continues where the iterator left off
+    assert_eq!(iter.next().unwrap().node(), &CBOR::from(3));
+}
+
+#[test]
+fn test_find_iter_document_order() {
+    let haystack = cbor(r#"[1, {"a": [2, 3]}, 4(5), 6]"#);
+    let pattern = Pattern::parse("number").unwrap();
+
+    let paths: Vec<_> = pattern
+        .find_iter(&haystack)
+        .map(|m| m.path().clone())
+        .collect();
+    let nodes: Vec<_> = paths.iter().map(|path| path.last().unwrap()).collect();
+    assert_eq!(
+        nodes,
+        vec![
+            &CBOR::from(1),
+            &CBOR::from(2),
+            &CBOR::from(3),
+            &CBOR::from(5),
+            &CBOR::from(6)
+        ]
+    );
+
+    // The paths are the ones a search reports, in the same order
+    let search = Pattern::search(pattern);
+    assert_eq!(paths, search.paths(&haystack));
+}
+
+#[test]
+fn test_find_iter_repeated_values() {
+    // Each occurrence is reported, where a search reports the path once
+    let haystack = cbor("[1, 1]");
+    let pattern = Pattern::parse("1").unwrap();
+    assert_eq!(pattern.find_iter(&haystack).count(), 2);
+    assert_eq!(Pattern::search(pattern).paths(&haystack).len(), 1);
+}
+
+#[test]
+fn test_find_iter_captures() {
+    let haystack = cbor(r#"{"a": [{"id": 1}], "b": {"id": 2}}"#);
+    let pattern = Pattern::parse(r#"{"id": @id(number)}"#).unwrap();
+
+    let matches: Vec<_> = pattern.find_iter(&haystack).collect();
+    assert_eq!(matches.len(), 2);
+    for m in &matches {
+        // Capture paths run from the root through the matched map
+        let id_paths = &m.captures()["id"];
+        assert_eq!(id_paths.len(), 1);
+        let id_path = &id_paths[0];
+        assert_eq!(id_path[0], haystack);
+        assert_eq!(&id_path[..id_path.len() - 1], m.path().as_slice());
+    }
+    assert_eq!(matches[0].captures()["id"][0].last(), Some(&CBOR::from(1)));
+    assert_eq!(matches[1].captures()["id"][0].last(), Some(&CBOR::from(2)));
+
+    // The captures agree with the ones a search makes
+    let (_, search_captures) =
+        Pattern::search(pattern).paths_with_captures(&haystack);
+    let find_captures: Vec<_> = matches
+        .into_iter()
+        .flat_map(|m| m.into_parts().1.swap_remove("id").unwrap())
+        .collect();
+    assert_eq!(find_captures, search_captures["id"]);
+}
+
+#[test]
+fn test_find_iter_with_opts() {
+    let haystack = cbor("[1, [2, [3]]]");
+    let pattern = Pattern::parse("number").unwrap();
+
+    let nodes: Vec<_> = pattern
+        .find_iter_with(
+            &haystack,
+            SearchOpts { max_depth: Some(2), ..SearchOpts::default() },
+        )
+        .map(|m| m.node().clone())
+        .collect();
+    assert_eq!(nodes, vec![CBOR::from(1), CBOR::from(2)]);
+}
+
+#[test]
+fn test_matches_at_path() {
+    let root =
+        cbor(r#"{"people": [{"name": "Alice", "age": 30}, {"name": "Bob"}]}"#);
+
+    // Find candidates with one pattern, then check another at each of them
+    let candidates: Vec<_> = Pattern::parse(r#"{"name": text}"#)
+        .unwrap()
+        .find_iter(&root)
+        .map(|m| m.path().clone())
+        .collect();
+    assert_eq!(candidates.len(), 2);
+    let adult = Pattern::parse(r#"{"age": >=18}"#).unwrap();
+    let verified: Vec<bool> = candidates
+        .iter()
+        .map(|path| adult.matches_at(&root, path).unwrap())
+        .collect();
+    assert_eq!(verified, vec![true, false]);
+
+    // Paths may pass through map keys and tagged content
+    let tagged = cbor(r#"{1: 100("x")}"#);
+    let path = vec![tagged.clone(), cbor(r#"100("x")"#), cbor(r#""x""#)];
+    assert!(
+        Pattern::parse("text")
+            .unwrap()
+            .matches_at(&tagged, &path)
+            .unwrap()
+    );
+    let path = vec![tagged.clone(), cbor("1")];
+    assert!(
+        Pattern::parse("1")
+            .unwrap()
+            .matches_at(&tagged, &path)
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_matches_at_inconsistent_path() {
+    let root = cbor("[1, [2, 3]]");
+    let pattern = Pattern::parse("number").unwrap();
+
+    // The path must start at the root
+    assert_eq!(
+        pattern.matches_at(&root, &[]),
+        Err(Error::InconsistentPath(0))
+    );
+    assert_eq!(
+        pattern.matches_at(&root, &[cbor("[2, 3]"), cbor("2")]),
+        Err(Error::InconsistentPath(0))
+    );
+
+    // Each element must be a child of the one before it
+    assert_eq!(
+        pattern.matches_at(&root, &[root.clone(), cbor("3")]),
+        Err(Error::InconsistentPath(1))
+    );
+    assert_eq!(
+        pattern.paths_with_captures_at(
+            &root,
+            &[root.clone(), cbor("[2, 3]"), cbor("1")]
+        ),
+        Err(Error::InconsistentPath(2))
+    );
+
+    // A consistent path that doesn't match isn't an error
+    assert_eq!(
+        Pattern::parse("text")
+            .unwrap()
+            .matches_at(&root, &[root.clone(), cbor("1")]),
+        Ok(false)
+    );
+}
+
+#[test]
+fn test_paths_with_captures_at_extends_path() {
+    let root = cbor(r#"{"items": [[1, "a"], [2, "b"]]}"#);
+    let prefix = vec![root.clone(), cbor(r#"[[1, "a"], [2, "b"]]"#)];
+    let pattern = Pattern::parse(r#"[(*)*, [@n(number), "b"], (*)*]"#).unwrap();
+
+    let (paths, captures) =
+        pattern.paths_with_captures_at(&root, &prefix).unwrap();
+    assert_eq!(paths, vec![prefix.clone()]);
+    let mut n_path = prefix.clone();
+    n_path.extend([cbor(r#"[2, "b"]"#), cbor("2")]);
+    assert_eq!(captures["n"], vec![n_path]);
+
+    // Paths produced by the pattern itself extend the prefix as well
+    let pattern = Pattern::parse("search(text)").unwrap();
+    let (paths, _) = pattern.paths_with_captures_at(&root, &prefix).unwrap();
+    let mut expected = Vec::new();
+    for (element, text) in
+        [(r#"[1, "a"]"#, r#""a""#), (r#"[2, "b"]"#, r#""b""#)]
+    {
+        let mut path = prefix.clone();
+        path.extend([cbor(element), cbor(text)]);
+        expected.push(path);
+    }
+    assert_eq!(paths, expected);
+
+    // With no match there are no paths or captures
+    let pattern = Pattern::parse("@n(map)").unwrap();
+    let (paths, captures) =
+        pattern.paths_with_captures_at(&root, &prefix).unwrap();
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+}
@@ -0,0 +1,148 @@
+use dcbor_pattern::{MetaPattern, Pattern, PatternKind};
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+/// Returns the kind and depth of each pattern `pattern` walks, in order.
+fn walked(pattern: &Pattern) -> Vec<(PatternKind, usize)> {
+    let mut visited = Vec::new();
+    pattern.walk(&mut |pattern, depth| visited.push((pattern.kind(), depth)));
+    visited
+}
+
+/// Returns the names of the captures in `pattern`, found through
+/// `children()` but not inside `!` or `none`, whose captures never produce
+/// results.
+fn capture_names(pattern: &Pattern) -> Vec<String> {
+    let mut names = Vec::new();
+    if matches!(pattern.kind(), PatternKind::Not | PatternKind::None) {
+        return names;
+    }
+    if let Pattern::Meta(MetaPattern::Capture(capture)) = pattern {
+        names.push(capture.name().to_string());
+    }
+    for child in pattern.children() {
+        for name in capture_names(child) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+const COMPLEX: &str = r#"
+    @user({"name": @name(text), "tags": [(text)*, @last(*)]})
+    | search(tagged(1, number & !null))
+"#;
+
+#[test]
+fn test_walk_complex_pattern() {
+    use PatternKind::*;
+
+    let pattern = parse(COMPLEX);
+    assert_eq!(
+        walked(&pattern),
+        vec![
+            (Or, 0),
+            (Capture, 1),
+            (Map, 2),
+            (Text, 3),
+            (Capture, 3),
+            (Text, 4),
+            (Text, 3),
+            (Array, 3),
+            (Sequence, 4),
+            (Repeat, 5),
+            (Text, 6),
+            (Capture, 5),
+            (Any, 6),
+            (Search, 1),
+            (Tagged, 2),
+            (And, 3),
+            (Number, 4),
+            (Not, 4),
+            (Null, 5),
+        ]
+    );
+
+    // Children are in display order
+    let displayed = |pattern: &Pattern| {
+        pattern
+            .children()
+            .iter()
+            .map(|child| child.to_string())
+            .collect::<Vec<_>>()
+    };
+    let user = pattern.children()[0];
+    let map = user.children()[0];
+    assert_eq!(
+        displayed(map),
+        [
+            r#""name""#,
+            "@name(text)",
+            r#""tags""#,
+            "[(text)*, @last(*)]"
+        ]
+    );
+}
+
+#[test]
+fn test_children_of_each_kind() {
+    let cases = [
+        ("42", PatternKind::Number, 0),
+        ("/ab+/", PatternKind::Text, 0),
+        ("[*]", PatternKind::Array, 1),
+        ("[{2}]", PatternKind::Array, 0),
+        ("array", PatternKind::Array, 0),
+        ("map", PatternKind::Map, 0),
+        (r#"{"a": 1, "b": 2}"#, PatternKind::Map, 4),
+        (r#"{"a"}: number"#, PatternKind::Map, 2),
+        ("tagged", PatternKind::Tagged, 0),
+        ("tagged(1...5, text)", PatternKind::Tagged, 1),
+        ("1 | 2 | 3", PatternKind::Or, 3),
+        ("number & >1", PatternKind::And, 2),
+        ("!number", PatternKind::Not, 1),
+        ("none(text)", PatternKind::None, 1),
+        ("(number){2}", PatternKind::Repeat, 1),
+        ("child(text)", PatternKind::Child, 1),
+        ("entry(text: number)", PatternKind::Entry, 2),
+        ("untag(number)", PatternKind::Untag, 1),
+        ("search(text)", PatternKind::Search, 1),
+        ("*", PatternKind::Any, 0),
+    ];
+    for (source, kind, children) in cases {
+        let pattern = parse(source);
+        assert_eq!(pattern.kind(), kind, "{}", source);
+        assert_eq!(pattern.children().len(), children, "{}", source);
+    }
+
+    let sequence = parse("[1, text, (*)*]").children()[0].clone();
+    assert_eq!(sequence.kind(), PatternKind::Sequence);
+    assert_eq!(sequence.children().len(), 3);
+    let backref = parse("[@a(*), =a]").children()[0].children()[1].clone();
+    assert_eq!(backref.kind(), PatternKind::Backref);
+    assert!(backref.children().is_empty());
+}
+
+#[test]
+fn test_children_agree_with_capture_names() {
+    let sources = [
+        COMPLEX,
+        "@a(number)",
+        "[@first(*), (@rest(*))*]",
+        r#"{@k(text): @v([@inner(*)])}"#,
+        "search(entry(@key(text): @value(number)))",
+        "untag(@n(number)) | child(@c(*)) | none(number)",
+        "number",
+    ];
+    for source in sources {
+        let pattern = parse(source);
+        assert_eq!(
+            capture_names(&pattern),
+            pattern.capture_names(),
+            "{}",
+            source
+        );
+    }
+}
@@ -3,7 +3,7 @@ mod common;
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
 use dcbor_pattern::{
-    Matcher, Pattern, format_paths, format_paths_with_captures,
+    Matcher, Pattern, Program, format_paths, format_paths_with_captures,
 };
 use indoc::indoc;
 
@@ -29,6 +29,7 @@ fn test_map_capture_key_value() {
 
     // Test with captures using the proper rubric
     let (capture_paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &capture_paths, &captures);
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -59,6 +60,8 @@ fn test_map_capture_multiple_entries() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -93,6 +96,8 @@ fn test_map_capture_value_only() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -118,6 +123,8 @@ fn test_map_capture_with_any_pattern() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -146,6 +153,8 @@ fn test_map_capture_nested() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -194,3 +203,766 @@ fn test_map_capture_non_matching() {
         "No paths should be returned for non-matching pattern"
     );
 }
+
+#[test]
+fn test_map_capture_with_length_constraint() {
+    let pattern = parse(r#"{{2,}, "type": @kind(text)}"#);
+
+    let cbor_data = cbor(r#"{"type": "user", "name": "Alice"}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @kind
+            {"name": "Alice", "type": "user"}
+                "user"
+        {"name": "Alice", "type": "user"}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // The entry matches, but the map is too small, so nothing is captured
+    let too_small = cbor(r#"{"type": "user"}"#);
+    let (paths, captures) = pattern.paths_with_captures(&too_small);
+    assert_path_chains!(&too_small, &paths, &captures);
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+}
+
+#[test]
+fn test_map_capture_value_at_key() {
+    let pattern = parse(r#"{"a"}: @v(number)"#);
+    let cbor_data = cbor(r#"{"a": 1, "b": 2}"#);
+
+    // The path continues from the map into the value of the matched entry,
+    // and the capture agrees with it
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    assert_eq!(paths, pattern.paths(&cbor_data));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_with_captures = indoc! {r#"
+        @v
+            {"a": 1, "b": 2}
+                1
+        {"a": 1, "b": 2}
+            1
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected_with_captures
+    );
+
+    // Entry constraints in braces still stop at the map
+    let pattern = parse(r#"{"a": @v(number)}"#);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_paths = indoc! {r#"
+        {"a": 1, "b": 2}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths(&pattern.paths(&cbor_data)),
+        expected_paths
+    );
+}
+
+#[test]
+fn test_map_capture_value_at_key_chained() {
+    let pattern = parse(r#"{"config"}: {text}: @port(number)"#);
+    let cbor_data =
+        cbor(r#"{"config": {"http": 80, "https": 443, "host": "x"}, "n": 1}"#);
+
+    // Every entry whose key matches yields its own path
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_with_captures = indoc! {r#"
+        @port
+            {"n": 1, "config": {"host": "x", "http": 80, "https": 443}}
+                {"host": "x", "http": 80, "https": 443}
+                    80
+            {"n": 1, "config": {"host": "x", "http": 80, "https": 443}}
+                {"host": "x", "http": 80, "https": 443}
+                    443
+        {"n": 1, "config": {"host": "x", "http": 80, "https": 443}}
+            {"host": "x", "http": 80, "https": 443}
+                80
+        {"n": 1, "config": {"host": "x", "http": 80, "https": 443}}
+            {"host": "x", "http": 80, "https": 443}
+                443
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected_with_captures
+    );
+
+    assert!(!pattern.matches(&cbor(r#"{"config": {"host": "x"}}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"other": {"http": 80}}"#)));
+}
+
+#[test]
+fn test_map_capture_nested_map_full_path() {
+    let pattern = parse(r#"{"config": {"port": @p(number)}}"#);
+    let cbor_data = cbor(r#"{"config": {"port": 80}}"#);
+
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_with_captures = indoc! {r#"
+        @p
+            {"config": {"port": 80}}
+                {"port": 80}
+                    80
+        {"config": {"port": 80}}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected_with_captures
+    );
+}
+
+#[test]
+fn test_map_capture_three_levels_mixed() {
+    // Map, then tagged content, then array
+    let pattern = parse(r#"{"items": tagged(100, [@n(number), *])}"#);
+    let cbor_data = cbor(r#"{"items": 100([7, "x"])}"#);
+
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_with_captures = indoc! {r#"
+        @n
+            {"items": 100([7, "x"])}
+                100([7, "x"])
+                    [7, "x"]
+                        7
+        {"items": 100([7, "x"])}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected_with_captures
+    );
+
+    // Array, then map, then tagged content
+    let pattern = parse(r#"[text, {"v": tagged(1, @v(number))}]"#);
+    let cbor_data = cbor(r#"["a", {"v": 1(5)}]"#);
+
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_with_captures = indoc! {r#"
+        @v
+            ["a", {"v": 1(5)}]
+                {"v": 1(5)}
+                    1(5)
+                        5
+        ["a", {"v": 1(5)}]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected_with_captures
+    );
+}
+
+#[test]
+fn test_map_capture_nested_under_search() {
+    let pattern = parse(r#"search({"config": {"port": @p(number)}})"#);
+    let cbor_data = cbor(
+        r#"[{"name": "a", "config": {"port": 80}}, 1({"config": {"port": 443}})]"#,
+    );
+
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected_with_captures = indoc! {r#"
+        @p
+            [{"name": "a", "config": {"port": 80}}, 1({"config": {"port": 443}})]
+                {"name": "a", "config": {"port": 80}}
+                    {"port": 80}
+                        80
+            [{"name": "a", "config": {"port": 80}}, 1({"config": {"port": 443}})]
+                1({"config": {"port": 443}})
+                    {"config": {"port": 443}}
+                        {"port": 443}
+                            443
+        [{"name": "a", "config": {"port": 80}}, 1({"config": {"port": 443}})]
+            {"name": "a", "config": {"port": 80}}
+        [{"name": "a", "config": {"port": 80}}, 1({"config": {"port": 443}})]
+            1({"config": {"port": 443}})
+                {"config": {"port": 443}}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected_with_captures
+    );
+}
+
+#[test]
+fn test_map_capture_every_matching_entry() {
+    // Wildcard keys capture every key of the map
+    let pattern = parse("{@k(*): *}");
+    let cbor_data = cbor(r#"{"b": 2, "c": true, "e": 5}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @k
+            {"b": 2, "c": true, "e": 5}
+                "b"
+            {"b": 2, "c": true, "e": 5}
+                "c"
+            {"b": 2, "c": true, "e": 5}
+                "e"
+        {"b": 2, "c": true, "e": 5}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // Only the entries satisfying the whole constraint are captured
+    let pattern = parse("{*: @v(number)}");
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @v
+            {"b": 2, "c": true, "e": 5}
+                2
+            {"b": 2, "c": true, "e": 5}
+                5
+        {"b": 2, "c": true, "e": 5}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_map_capture_key_and_value_under_search() {
+    let pattern = parse("search({@k(text): @v(number)})");
+    let cbor_data = cbor(r#"[{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @k
+            [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+                {"a": 1}
+                    "a"
+            [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+                {"c": 3}
+                    "c"
+            [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+                1({"d": 4})
+                    {"d": 4}
+                        "d"
+        @v
+            [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+                {"a": 1}
+                    1
+            [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+                {"c": 3}
+                    3
+            [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+                1({"d": 4})
+                    {"d": 4}
+                        4
+        [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+            {"a": 1}
+        [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+            {"c": 3}
+        [{"a": 1}, {"b": true}, {"c": 3}, 1({"d": 4})]
+            1({"d": 4})
+                {"d": 4}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_map_capture_backref_keeps_chosen_entry() {
+    // With a back-reference, only the entries that agree with it are captured
+    let pattern = parse("{@k(text): @v(number), =v: *}");
+    let cbor_data = cbor(r#"{"a": 1, "b": 2, 2: true}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    assert_eq!(paths, vec![vec![cbor_data.clone()]]);
+    assert_eq!(captures["k"], vec![vec![cbor_data.clone(), cbor(r#""b""#)]]);
+    assert_eq!(captures["v"], vec![vec![cbor_data.clone(), cbor("2")]]);
+}
+
+#[test]
+fn test_map_capture_rest() {
+    let pattern = parse(r#"{"type": text, @rest(*)}"#);
+    let cbor_data =
+        cbor(r#"{"id": 7, "type": "user", "name": "Alice", "admin": false}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @rest
+            {"id": 7, "name": "Alice", "type": "user", "admin": false}
+                {"id": 7, "name": "Alice", "admin": false}
+        {"id": 7, "name": "Alice", "type": "user", "admin": false}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // The rest is a map of the three entries left over
+    let rest = &captures["rest"][0];
+    assert_eq!(rest.len(), 2);
+    assert_eq!(rest[1].as_map().unwrap().len(), 3);
+
+    // The same through the VM, inside an array
+    let wrapped =
+        cbor(r#"[{"id": 7, "type": "user", "name": "Alice", "admin": false}]"#);
+    let (_, captures) =
+        parse(r#"[{"type": text, @rest(*)}]"#).paths_with_captures(&wrapped);
+    assert_path_chains!(&wrapped, &[] as &[Vec<CBOR>], &captures);
+    assert_eq!(captures["rest"][0][2], rest[1]);
+}
+
+#[test]
+fn test_map_capture_empty_rest() {
+    let pattern = parse(r#"{"type": text, @rest(*)}"#);
+    let cbor_data = cbor(r#"{"type": "user"}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    assert_eq!(paths, vec![vec![cbor_data.clone()]]);
+    assert_eq!(captures["rest"], vec![vec![cbor_data.clone(), cbor("{}")]]);
+
+    // A rest that must be empty closes the map to other entries
+    let closed = parse(r#"{"type": text, @rest({{0}})}"#);
+    assert!(closed.matches(&cbor_data));
+    assert!(!closed.matches(&cbor(r#"{"type": "user", "id": 7}"#)));
+}
+
+#[test]
+fn test_map_rest_syntax() {
+    // Captures inside the rest are rooted at the rest map
+    let pattern = parse(r#"{{2,}, "type": text, @rest({@k(text): number})}"#);
+    let cbor_data = cbor(r#"{"type": "user", "id": 7}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    assert_eq!(
+        captures["k"],
+        vec![vec![
+            cbor_data.clone(),
+            cbor(r#"{"id": 7}"#),
+            cbor(r#""id""#)
+        ]]
+    );
+
+    // A rest on its own binds the whole map
+    let whole = parse("{@all(map)}");
+    let (_, captures) = whole.paths_with_captures(&cbor_data);
+    assert_eq!(
+        captures["all"],
+        vec![vec![cbor_data.clone(), cbor_data.clone()]]
+    );
+
+    // The rest displays last, and round-trips
+    for source in [
+        r#"{"type": text, @rest(*)}"#,
+        r#"{{2,}, "type": text, @rest({@k(text): number})}"#,
+        "{@all(map)}",
+    ] {
+        assert_eq!(parse(source).to_string(), source);
+    }
+
+    // The rest must come last
+    assert!(Pattern::parse(r#"{@rest(*), "type": text}"#).is_err());
+}
+
+#[test]
+fn test_map_capture_all_entries() {
+    // A capture inside a constraint on every entry captures each entry
+    let pattern = parse("{keys: @k(text), values: @v(number)}");
+    let cbor_data = cbor(r#"{"a": 1, "b": 2}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @k
+            {"a": 1, "b": 2}
+                "a"
+            {"a": 1, "b": 2}
+                "b"
+        @v
+            {"a": 1, "b": 2}
+                1
+            {"a": 1, "b": 2}
+                2
+        {"a": 1, "b": 2}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // Nothing is captured when an entry fails
+    let (paths, captures) =
+        pattern.paths_with_captures(&cbor(r#"{"a": 1, "b": "2"}"#));
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+}
+
+#[test]
+fn test_map_capture_known_value_keys() {
+    // An envelope-like map keyed by known values, `isA` being 40000(1) and
+    // `id` 40000(2)
+    let pattern = parse("{'isA': @type(text), 'id': @id(*)}");
+    let cbor_data = cbor(r#"{40000(1): "Person", 40000(2): 42}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @id
+            {40000(1): "Person", 40000(2): 42}
+                42
+        @type
+            {40000(1): "Person", 40000(2): 42}
+                "Person"
+        {40000(1): "Person", 40000(2): 42}
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // Known values given by number, captured keys, and under search
+    let pattern = parse("search({@key('1'): @type(text)})");
+    let cbor_data =
+        cbor(r#"[{40000(1): "Person"}, {40000(2): "x"}, {40000(1): "Org"}]"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    assert_eq!(paths.len(), 2);
+    assert_eq!(captures["key"].len(), 2);
+    assert_eq!(
+        captures["type"]
+            .iter()
+            .map(|path| path.last().unwrap().clone())
+            .collect::<Vec<_>>(),
+        [cbor(r#""Person""#), cbor(r#""Org""#)]
+    );
+}
+
+#[test]
+fn test_map_capture_counted_constraints() {
+    // Captures are made once for every entry the constraint counts
+    let pattern = parse(r#"{(@k(text): @v(number)){2,}, "id": @id(*)}"#);
+    let cbor_data = cbor(r#"{"a": 1, "b": 2, "id": "x"}"#);
+    let result = pattern.run(&cbor_data);
+    assert_path_chains!(&cbor_data, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @id
+            {"a": 1, "b": 2, "id": "x"}
+                "x"
+        @k
+            {"a": 1, "b": 2, "id": "x"}
+                "a"
+            {"a": 1, "b": 2, "id": "x"}
+                "b"
+        @v
+            {"a": 1, "b": 2, "id": "x"}
+                1
+            {"a": 1, "b": 2, "id": "x"}
+                2
+        {"a": 1, "b": 2, "id": "x"}
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected);
+
+    // Too few entries, no captures
+    let result = pattern.run(&cbor(r#"{"a": 1, "id": "x"}"#));
+    assert!(result.is_empty());
+    assert!(result.captures().is_empty());
+}
+
+/// Five entries written out of order, three of them with number values.
+/// In key order, `10` sorts first and `"bb"` last, as shorter encodings sort
+/// before longer ones.
+const SCRAMBLED: &str = r#"{"bb": 2, "c": "x", 10: 3, "a": 1, -1: true}"#;
+
+/// The values captured as `name`, in order.
+fn captured_values(
+    captures: &dcbor_pattern::Captures,
+    name: &str,
+) -> Vec<CBOR> {
+    captures[name]
+        .iter()
+        .map(|path| path.last().unwrap().clone())
+        .collect()
+}
+
+#[test]
+fn test_map_capture_key_order() {
+    let haystack = cbor(SCRAMBLED);
+    assert_eq!(
+        haystack.to_string(),
+        r#"{10: 3, -1: true, "a": 1, "c": "x", "bb": 2}"#
+    );
+
+    let pattern = parse("{(*): @v(number)}");
+    let result = pattern.run(&haystack);
+    assert_path_chains!(&haystack, result.paths(), result.captures());
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @v
+            {10: 3, -1: true, "a": 1, "c": "x", "bb": 2}
+                3
+            {10: 3, -1: true, "a": 1, "c": "x", "bb": 2}
+                1
+            {10: 3, -1: true, "a": 1, "c": "x", "bb": 2}
+                2
+        {10: 3, -1: true, "a": 1, "c": "x", "bb": 2}
+    "#}.trim();
+    assert_actual_expected!(result.to_string(), expected);
+}
+
+#[test]
+fn test_map_capture_key_order_every_form() {
+    let haystack = cbor(SCRAMBLED);
+    let numbers = vec![cbor("3"), cbor("1"), cbor("2")];
+    for (source, name, expected) in [
+        ("{(*): @v(number)}", "v", numbers.clone()),
+        (
+            "{@k(*): number}",
+            "k",
+            vec![cbor("10"), cbor(r#""a""#), cbor(r#""bb""#)],
+        ),
+        ("{{5}, (*): @v(number)}", "v", numbers.clone()),
+        ("{(*): @v(number), @rest(*)}", "v", numbers.clone()),
+        (
+            "{(@k(*): number){3}, @rest({(*): @o(*)})}",
+            "o",
+            vec![cbor("true"), cbor(r#""x""#)],
+        ),
+        (
+            "{values: @v(*)}",
+            "v",
+            vec![
+                cbor("3"),
+                cbor("true"),
+                cbor("1"),
+                cbor(r#""x""#),
+                cbor("2"),
+            ],
+        ),
+        ("{(*)}: @v(number)", "v", numbers.clone()),
+        (
+            "entry(@k(*): number)",
+            "k",
+            vec![cbor("10"), cbor(r#""a""#), cbor(r#""bb""#)],
+        ),
+    ] {
+        let pattern = parse(source);
+        let (_, captures) = pattern.paths_with_captures(&haystack);
+        assert_eq!(captured_values(&captures, name), expected, "{}", source);
+
+        // The VM matches the pattern inside a sequence, and captures in the
+        // same order
+        let array = CBOR::from(vec![CBOR::from(0), haystack.clone()]);
+        let sequence = parse(&format!("[(*)*, {}]", source));
+        let (_, captures) = sequence.paths_with_captures(&array);
+        assert_eq!(captured_values(&captures, name), expected, "{}", sequence);
+
+        let program = Program::compile(&pattern);
+        assert_eq!(
+            program.run(&haystack),
+            pattern.paths_with_captures(&haystack),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn test_map_capture_duplicate_keys() {
+    // A map holds one entry for each key, so inserting a key again replaces
+    // its value, and the entry is captured once
+    let mut map = Map::new();
+    map.insert("a", 1);
+    map.insert("b", 2);
+    map.insert("a", 3);
+    let haystack = CBOR::from(map);
+    let (_, captures) =
+        parse("{@k(text): @v(number)}").paths_with_captures(&haystack);
+    assert_eq!(
+        captured_values(&captures, "k"),
+        [cbor(r#""a""#), cbor(r#""b""#)]
+    );
+    assert_eq!(captured_values(&captures, "v"), [cbor("3"), cbor("2")]);
+}
+
+#[test]
+fn test_map_capture_value_at_key_in_search() {
+    let pattern = parse(r#"search({"a"}: @v(number))"#);
+    let cbor_data = cbor(r#"[{"a": 1, "b": 2}, {"a": "x"}, {"b": {"a": 3}}]"#);
+
+    // Each path continues from the map the search found into the value, as
+    // the capture does
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_eq!(paths, pattern.paths(&cbor_data));
+    assert_eq!(captures["v"], paths);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [{"a": 1, "b": 2}, {"a": "x"}, {"b": {"a": 3}}]
+            {"a": 1, "b": 2}
+                1
+        [{"a": 1, "b": 2}, {"a": "x"}, {"b": {"a": 3}}]
+            {"b": {"a": 3}}
+                {"a": 3}
+                    3
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // The compiled program finds the same, and so does a capture around
+    // the map value
+    assert_eq!(
+        Program::compile(&pattern).run(&cbor_data),
+        (paths, captures)
+    );
+    let pattern = parse(r#"search(@m({"a"}: number))"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_eq!(captures["m"], paths);
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_map_capture_value_at_key_nested() {
+    // A map value pattern inside another continues its path, and a capture
+    // of the inner one starts where the outer one led
+    let pattern = parse(r#"{"x"}: @inner({"a"}: @v(number))"#);
+    let cbor_data = cbor(r#"{"x": {"a": 1}}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_eq!(paths, pattern.paths(&cbor_data));
+    assert_eq!(captures["inner"], paths);
+    assert_eq!(captures["v"], paths);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {"x": {"a": 1}}
+            {"a": 1}
+                1
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+    assert_eq!(
+        Program::compile(&pattern).run(&cbor_data),
+        (paths, captures)
+    );
+}
+
+#[test]
+fn test_map_value_display_round_trip() {
+    use dcbor_pattern::dsl::*;
+
+    let entry = || map_value(text_value("k"), number());
+    for (pattern, displayed) in [
+        // The value pattern takes in any `|` or `&` that follows it, so a
+        // map value before another operand is parenthesized
+        (
+            text().or(entry()).or(null()),
+            r#"text | ({"k"}: number) | null"#,
+        ),
+        (entry().or(text()), r#"({"k"}: number) | text"#),
+        (text().or(entry()), r#"text | {"k"}: number"#),
+        (entry().and(any_map()), r#"({"k"}: number) & map"#),
+        (
+            map_value(text_value("k"), number().or(text())),
+            r#"{"k"}: number | text"#,
+        ),
+        (not(entry()).and(any_map()), r#"(!{"k"}: number) & map"#),
+        // `{0}` alone would be a map length
+        (map_value(number_value(0.0), text()), "{(0)}: text"),
+    ] {
+        assert_eq!(pattern.to_string(), displayed);
+        assert_eq!(parse(displayed), pattern, "{}", displayed);
+    }
+}
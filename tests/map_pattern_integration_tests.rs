@@ -2,7 +2,7 @@ mod common;
 
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
-use dcbor_pattern::{MapPattern, Matcher, Pattern, format_paths};
+use dcbor_pattern::{Interval, MapPattern, Matcher, Pattern, format_paths};
 
 /// Helper function to parse CBOR diagnostic notation into CBOR objects
 fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
@@ -308,3 +308,263 @@ fn test_map_key_value_constraints_complex_patterns() {
     let no_match = cbor(r#"{"somekey": "other", 42: true}"#); // text value is wrong
     assert!(!pattern.matches(&no_match));
 }
+
+#[test]
+fn test_map_length_and_key_value_constraints() {
+    let pattern = Pattern::parse(r#"{{3,}, "type": text}"#).unwrap();
+
+    let matching_map = cbor(r#"{"type": "user", "name": "Al", "age": 3}"#);
+    assert!(pattern.matches(&matching_map));
+
+    let too_small = cbor(r#"{"type": "user", "name": "Al"}"#);
+    assert!(!pattern.matches(&too_small));
+
+    let wrong_type = cbor(r#"{"type": 1, "name": "Al", "age": 3}"#);
+    assert!(!pattern.matches(&wrong_type));
+
+    // The same pattern built through the API
+    let api_pattern = MapPattern::with_constraints(
+        Interval::new(3..),
+        vec![(Pattern::text("type"), Pattern::any_text())],
+    );
+    assert!(api_pattern.matches(&matching_map));
+    assert!(!api_pattern.matches(&too_small));
+    assert_eq!(api_pattern.to_string(), r#"{{3,}, "type": text}"#);
+}
+
+#[test]
+fn test_map_length_and_key_value_constraints_round_trip() {
+    let patterns = [
+        r#"{{2}, "type": text}"#,
+        r#"{{1,4}, "a": number, "b": *}"#,
+        r#"{{0,}, *: @v(number)}"#,
+    ];
+    for pattern_str in patterns {
+        let pattern = Pattern::parse(pattern_str).unwrap();
+        assert_eq!(pattern.to_string(), pattern_str);
+        assert_eq!(Pattern::parse(&pattern.to_string()).unwrap(), pattern);
+    }
+}
+
+#[test]
+fn test_map_keyword_keys() {
+    let keyed = cbor(r#"{null: "n", true: 1, false: 2, "null": "text"}"#);
+
+    // Type keywords match any key of their type, and value keywords match
+    // exactly that key
+    let cases = [
+        ("{null: text}", cbor(r#"{null: "n"}"#), true),
+        ("{bool: number}", cbor("{true: 1}"), true),
+        ("{bool: number}", cbor("{false: 1}"), true),
+        ("{true: *}", cbor("{true: 1}"), true),
+        ("{true: *}", cbor("{false: 1}"), false),
+        ("{false: 2}", cbor("{false: 2}"), true),
+        ("{false: 2}", cbor("{true: 2}"), false),
+        ("{bool: *}", cbor("{1: true}"), false),
+        ("{null: *}", cbor("{0: null}"), false),
+    ];
+    for (pattern_str, haystack, expected) in cases {
+        let pattern = Pattern::parse(pattern_str).unwrap();
+        assert_eq!(
+            pattern.matches(&haystack),
+            expected,
+            "{} on {}",
+            pattern_str,
+            haystack.diagnostic_flat()
+        );
+        assert_eq!(pattern.to_string(), pattern_str);
+    }
+
+    // Quoted keywords are text and known values, not the keyword's value
+    assert!(
+        !Pattern::parse(r#"{"true": *}"#)
+            .unwrap()
+            .matches(&cbor("{true: 1}"))
+    );
+    assert!(
+        !Pattern::parse("{'null': *}")
+            .unwrap()
+            .matches(&cbor("{null: 1}"))
+    );
+    assert!(
+        Pattern::parse(r#"{"null": "text"}"#)
+            .unwrap()
+            .matches(&keyed)
+    );
+
+    // Keyword keys combine like any other key patterns
+    let pattern =
+        Pattern::parse(r#"{null: text, true: 1, false: number}"#).unwrap();
+    assert!(pattern.matches(&keyed));
+    assert!(Pattern::parse("{true | null: *}").unwrap().matches(&keyed));
+    assert!(Pattern::parse("{{4}, bool: 2}").unwrap().matches(&keyed));
+
+    // A lone keyword key continues into its value, in map order, where
+    // `false` sorts before `true`
+    let pattern = Pattern::parse("{bool}: number").unwrap();
+    let paths = pattern.paths(&keyed);
+    assert_eq!(
+        paths,
+        vec![
+            vec![keyed.clone(), cbor("2")],
+            vec![keyed.clone(), cbor("1")],
+        ]
+    );
+}
+
+#[test]
+fn test_map_all_entries() {
+    let all_numbers = Pattern::parse("{values: number}").unwrap();
+    assert!(all_numbers.matches(&cbor(r#"{"a": 1, "b": 2.5}"#)));
+    assert!(!all_numbers.matches(&cbor(r#"{"a": 1, "b": "2"}"#)));
+    // Unlike `{*: number}`, which only needs one entry with a number value
+    let some_number = Pattern::parse("{*: number}").unwrap();
+    assert!(some_number.matches(&cbor(r#"{"a": 1, "b": "2"}"#)));
+
+    // Every entry of the empty map satisfies any constraint
+    assert!(all_numbers.matches(&cbor("{}")));
+    assert!(!some_number.matches(&cbor("{}")));
+
+    let text_keys = Pattern::parse("{keys: text}").unwrap();
+    assert!(text_keys.matches(&cbor(r#"{"a": [1], "b": null}"#)));
+    assert!(!text_keys.matches(&cbor(r#"{"a": 1, 2: 3}"#)));
+    assert!(!text_keys.matches(&cbor("[1]")));
+
+    let both = Pattern::parse("{keys: text, values: 0...9}").unwrap();
+    assert!(both.matches(&cbor(r#"{"a": 1, "b": 9}"#)));
+    assert!(!both.matches(&cbor(r#"{"a": 1, "b": 10}"#)));
+
+    // The constructors build the same patterns
+    assert_eq!(
+        all_numbers,
+        Pattern::Structure(dcbor_pattern::StructurePattern::Map(
+            MapPattern::with_all_values(Pattern::any_number())
+        ))
+    );
+    assert_eq!(
+        text_keys,
+        Pattern::Structure(dcbor_pattern::StructurePattern::Map(
+            MapPattern::with_all_keys(Pattern::any_text())
+        ))
+    );
+}
+
+#[test]
+fn test_map_all_entries_syntax() {
+    for source in [
+        "{values: number}",
+        "{keys: text}",
+        "{keys: text, values: number | null}",
+    ] {
+        assert_eq!(Pattern::parse(source).unwrap().to_string(), source);
+    }
+
+    // Text keys named like the words are written quoted
+    let pattern = Pattern::parse(r#"{"values": number}"#).unwrap();
+    assert!(pattern.matches(&cbor(r#"{"values": 1, "a": "x"}"#)));
+
+    // `keys` comes before `values`, once each
+    assert!(Pattern::parse("{values: number, keys: text}").is_err());
+    assert!(Pattern::parse("{keys: text, keys: text}").is_err());
+    assert!(Pattern::parse("{keys: text, \"a\": 1}").is_err());
+    assert!(Pattern::parse("{keys: text").is_err());
+}
+
+#[test]
+fn test_map_counted_constraints() {
+    let one = cbor(r#"{"a": 1, "b": "x"}"#);
+    let two = cbor(r#"{"a": 1, "b": 2, "c": "x"}"#);
+    let three = cbor(r#"{"a": 1, "b": 2, "c": 3}"#);
+
+    // Only entries matching both the key and the value are counted
+    let at_least_two = Pattern::parse("{(text: number){2,}}").unwrap();
+    assert!(!at_least_two.matches(&one));
+    assert!(at_least_two.matches(&two));
+    assert!(at_least_two.matches(&three));
+
+    let exactly_two = Pattern::parse("{(text: number){2}}").unwrap();
+    assert!(!exactly_two.matches(&one));
+    assert!(exactly_two.matches(&two));
+    assert!(!exactly_two.matches(&three));
+
+    // No entry may match a count of zero
+    let none = Pattern::parse(r#"{(text: "x"){0}}"#).unwrap();
+    assert!(none.matches(&three));
+    assert!(!none.matches(&two));
+
+    // The same patterns built through the API
+    let entry = MapPattern::entry(Pattern::any_text(), Pattern::any_number());
+    let api_pattern = MapPattern::with_counted_constraints(
+        Interval::new(0..),
+        vec![entry.clone().at_least(2)],
+        None,
+    );
+    assert_eq!(api_pattern.to_string(), "{(text: number){2,}}");
+    assert!(api_pattern.matches(&two));
+    assert!(!api_pattern.matches(&one));
+    let api_pattern = MapPattern::with_counted_constraints(
+        Interval::new(0..),
+        vec![entry.clone().exactly(2)],
+        None,
+    );
+    assert!(api_pattern.matches(&two));
+    assert!(!api_pattern.matches(&three));
+    assert_eq!(
+        entry.clone().at_least(2).at_most(3).count(),
+        Interval::new(2..=3)
+    );
+
+    // At least one entry, the default, is an ordinary constraint
+    assert_eq!(
+        MapPattern::with_counted_constraints(
+            Interval::new(0..),
+            vec![entry],
+            None
+        ),
+        MapPattern::with_constraints(
+            Interval::new(0..),
+            vec![(Pattern::any_text(), Pattern::any_number())]
+        )
+    );
+    assert_eq!(
+        Pattern::parse("{(text: number){1,}}").unwrap(),
+        Pattern::parse("{text: number}").unwrap()
+    );
+}
+
+#[test]
+fn test_map_counted_constraints_with_other_constraints() {
+    // Each constraint counts independently, so an entry may satisfy several
+    let pattern =
+        Pattern::parse(r#"{"id": number, (text: number){2}}"#).unwrap();
+    assert!(pattern.matches(&cbor(r#"{"id": 7, "a": 1}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"id": 7, "a": 1, "b": 2}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"a": 1, "b": 2}"#)));
+
+    // Alongside a length, another count, and the rest of the entries
+    let pattern = Pattern::parse(
+        r#"{{3,}, (text: number){2,}, (number: *){,1}, @rest(*)}"#,
+    )
+    .unwrap();
+    assert!(pattern.matches(&cbor(r#"{"a": 1, "b": 2, 3: 4}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"a": 1, "b": 2}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"a": 1, "b": 2, 3: 4, 5: 6}"#)));
+
+    // Parenthesized keys are still keys
+    let pattern = Pattern::parse(r#"{(text | number): null}"#).unwrap();
+    assert!(pattern.matches(&cbor("{1: null}")));
+
+    for source in [
+        r#"{(text: number){2,}}"#,
+        r#"{"id": number, (text: number){2}}"#,
+        r#"{{3,}, (text: number){2,}, (number: *){,1}, @rest(*)}"#,
+    ] {
+        let pattern = Pattern::parse(source).unwrap();
+        assert_eq!(pattern.to_string(), source);
+        assert_eq!(Pattern::parse(&pattern.to_string()).unwrap(), pattern);
+    }
+
+    // A count is required, and reluctance has no meaning for one
+    assert!(Pattern::parse("{(text: number)}").is_err());
+    assert!(Pattern::parse("{(text: number){2,}?}").is_err());
+}
@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use dcbor::prelude::*;
+use dcbor_pattern::{Error, MatchOpts, Matcher, Pattern};
+
+/// An array of the numbers from 0 up to `len`, which never contains 42 at
+/// its end.
+fn numbers(len: u64) -> CBOR { CBOR::from((0..len).collect::<Vec<_>>()) }
+
+/// Sequences of unbounded repeats that can't match take time polynomial in
+/// the array length, with one factor per repeat.
+const EXPLOSIVE: &str = "[(*)*, (*)*, (*)*, (*)*, 42]";
+
+#[test]
+fn test_budget_stops_explosive_match() {
+    let pattern = Pattern::parse(EXPLOSIVE).unwrap();
+    let haystack = numbers(200);
+    let opts = MatchOpts { max_steps: Some(10_000), ..Default::default() };
+
+    let start = Instant::now();
+    assert_eq!(
+        pattern.paths_with_opts(&haystack, opts),
+        Err(Error::BudgetExceeded)
+    );
+    assert_eq!(
+        pattern.paths_with_captures_with_opts(&haystack, opts),
+        Err(Error::BudgetExceeded)
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_budget_allows_cheap_match() {
+    let pattern = Pattern::parse(EXPLOSIVE).unwrap();
+    let opts = MatchOpts { max_steps: Some(10_000), max_threads: Some(100) };
+
+    // Small inputs fit the budget and give the same results as without it
+    let mut elements = (0..5).map(CBOR::from).collect::<Vec<_>>();
+    elements.push(CBOR::from(42));
+    let haystack = CBOR::from(elements);
+    assert_eq!(
+        pattern.paths_with_opts(&haystack, opts),
+        Ok(pattern.paths(&haystack))
+    );
+    assert_eq!(pattern.paths_with_opts(&numbers(5), opts), Ok(vec![]));
+
+    let pattern = Pattern::parse("search(@n(number))").unwrap();
+    assert_eq!(
+        pattern.paths_with_captures_with_opts(&haystack, opts),
+        Ok(pattern.paths_with_captures(&haystack))
+    );
+
+    // No limits means no failures
+    assert_eq!(
+        pattern.paths_with_opts(&haystack, MatchOpts::default()),
+        Ok(pattern.paths(&haystack))
+    );
+}
+
+#[test]
+fn test_budget_limits_threads() {
+    // Each element of the array is matched in a thread of its own
+    let haystack = numbers(100);
+    let pattern = Pattern::parse("[(@n(number))*]").unwrap();
+
+    let opts = MatchOpts { max_threads: Some(10), ..Default::default() };
+    assert_eq!(
+        pattern.paths_with_captures_with_opts(&haystack, opts),
+        Err(Error::BudgetExceeded)
+    );
+    let opts = MatchOpts { max_threads: Some(1_000), ..Default::default() };
+    let (paths, captures) = pattern
+        .paths_with_captures_with_opts(&haystack, opts)
+        .unwrap();
+    assert_eq!(paths, vec![vec![haystack.clone()]]);
+    assert_eq!(captures["n"].len(), 100);
+}
+
+#[test]
+fn test_budget_does_not_outlive_match() {
+    let pattern = Pattern::parse(EXPLOSIVE).unwrap();
+    let opts = MatchOpts { max_steps: Some(10), ..Default::default() };
+    assert!(pattern.paths_with_opts(&numbers(50), opts).is_err());
+
+    // Infallible matching afterwards is unaffected
+    let mut elements = (0..5).map(CBOR::from).collect::<Vec<_>>();
+    elements.push(CBOR::from(42));
+    let haystack = CBOR::from(elements);
+    assert_eq!(pattern.paths(&haystack), vec![vec![haystack.clone()]]);
+}
@@ -0,0 +1,81 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Matcher, Path, Pattern};
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+/// Returns maps nested `depth` deep under the key `"a"`, each also holding
+/// an array under `"b"`, with `42` at the bottom: about four nodes a level.
+fn chain(depth: usize) -> CBOR {
+    let mut node = CBOR::from(42);
+    for i in 0..depth {
+        let mut map = Map::new();
+        map.insert("a", node);
+        map.insert("b", vec![i as u64, 1, 2]);
+        node = map.into();
+    }
+    node
+}
+
+/// The paths of `search(*)` ending at a node `inner` matches, each node's
+/// match found on its own rather than within a search.
+fn naive_search(inner: &Pattern, haystack: &CBOR) -> Vec<Path> {
+    parse("search(*)")
+        .paths(haystack)
+        .into_iter()
+        .filter(|path| inner.matches(path.last().unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_nested_search_on_large_document() {
+    // The work saved by reusing subtree results is counted by the unit tests
+    // of `SearchPattern`, and timed by the `nested search` benchmark
+    let document = chain(250);
+
+    let paths = parse(r#"search({"a": search(42)})"#).paths(&document);
+    assert_eq!(paths.len(), 250);
+    let paths = parse("search(search(42))").paths(&document);
+    // Every map on the way down, the `"a"` key above the bottom, and `42`
+    assert_eq!(paths.len(), 253);
+}
+
+#[test]
+fn test_nested_search_matches_naive_nesting() {
+    let documents = [
+        chain(4),
+        cbor(r#"[[1, [42]], [1, [42]], {"a": [42], [42]: "a"}]"#),
+        cbor(r#"[[[[42]]], [[[43]]], 1("x"), {42: [[42]]}]"#),
+        cbor("[[[[[[1]]]]], [[[[42]]]]]"),
+    ];
+    for inner in [
+        r#"{"a": search(42)}"#,
+        "search(42)",
+        "[search(42)]",
+        "[search(42, depth: 2)]",
+        "search(search(42, depth: 1), depth: 3)",
+        "search(42, keys: false)",
+        "search(text, tagged: false)",
+        "!search(42)",
+    ] {
+        let inner = parse(inner);
+        let nested = Pattern::search(inner.clone());
+        for document in &documents {
+            assert_eq!(
+                nested.paths(document),
+                naive_search(&inner, document),
+                "search({}) in {}",
+                inner,
+                document
+            );
+            assert_eq!(
+                nested.matches(document),
+                !naive_search(&inner, document).is_empty()
+            );
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use dcbor::prelude::*;
+use dcbor_pattern::{
+    DEFAULT_MAX_NESTING_DEPTH, Error, MAX_SEARCH_DEPTH, Matcher, ParseOpts,
+    Pattern,
+};
+
+/// Returns `open` repeated `depth` times, then `1`, then `close` repeated
+/// `depth` times.
+fn nested_source(open: &str, close: &str, depth: usize) -> String {
+    format!("{}1{}", open.repeat(depth), close.repeat(depth))
+}
+
+/// Arrays nested `depth` deep around the number 1.
+///
+/// Deeper values would overflow the stack when dropped, whatever the matcher
+/// does.
+fn nested_arrays(depth: usize) -> CBOR {
+    let mut cbor = CBOR::from(1);
+    for _ in 0..depth {
+        cbor = CBOR::from(vec![cbor]);
+    }
+    cbor
+}
+
+#[test]
+fn test_parse_rejects_deep_nesting() {
+    for (open, close) in [
+        ("(", ")"),
+        ("[", "]"),
+        ("{1: ", "}"),
+        ("!", ""),
+        ("@a(", ")"),
+        ("search(", ")"),
+        ("child(", ")"),
+        ("untag(", ")"),
+        ("tagged(1, ", ")"),
+    ] {
+        let source = nested_source(open, close, 100_000);
+        let opts = ParseOpts {
+            allow_duplicate_captures: true,
+            ..ParseOpts::default()
+        };
+        let error = Pattern::parse_with_opts(&source, opts).unwrap_err();
+        assert!(
+            matches!(error, Error::NestingTooDeep(_, _)),
+            "{}: {:?}",
+            open,
+            error
+        );
+
+        // The error points at the first token past the limit
+        let span = error.span().unwrap();
+        assert_eq!(span.start, DEFAULT_MAX_NESTING_DEPTH * open.len());
+        assert!(open.starts_with(&source[span]), "{}", open);
+    }
+}
+
+#[test]
+fn test_parse_nesting_at_limit() {
+    let source = nested_source("(", ")", DEFAULT_MAX_NESTING_DEPTH);
+    assert!(Pattern::parse(&source).is_ok());
+    let source = nested_source("(", ")", DEFAULT_MAX_NESTING_DEPTH + 1);
+    assert!(matches!(
+        Pattern::parse(&source),
+        Err(Error::NestingTooDeep(_, _))
+    ));
+
+    // Nesting counts across the different kinds of pattern
+    let source = format!(
+        "{}{}",
+        "[".repeat(DEFAULT_MAX_NESTING_DEPTH / 2),
+        nested_source("(", ")", DEFAULT_MAX_NESTING_DEPTH / 2 + 1)
+    ) + &"]".repeat(DEFAULT_MAX_NESTING_DEPTH / 2);
+    assert!(matches!(
+        Pattern::parse(&source),
+        Err(Error::NestingTooDeep(_, _))
+    ));
+
+    // Sibling patterns do not add up
+    let wide = vec![nested_source("[", "]", 40); 10].join(" | ");
+    assert!(Pattern::parse(&wide).is_ok());
+}
+
+#[test]
+fn test_parse_nesting_limit_is_configurable() {
+    let source = nested_source("(", ")", 10);
+    let strict = ParseOpts { max_nesting_depth: 8, ..ParseOpts::default() };
+    assert_eq!(
+        Pattern::parse_with_opts(&source, strict),
+        Err(Error::NestingTooDeep("(".to_string(), 8..9))
+    );
+
+    let source = nested_source("(", ")", 100);
+    assert!(Pattern::parse(&source).is_err());
+    let relaxed = ParseOpts { max_nesting_depth: 100, ..ParseOpts::default() };
+    let pattern = Pattern::parse_with_opts(&source, relaxed).unwrap();
+    assert!(pattern.matches(&CBOR::from(1)));
+}
+
+#[test]
+fn test_search_deep_haystack() {
+    let haystack = nested_arrays(5_000);
+
+    // Nodes below the search depth limit are not visited
+    let pattern = Pattern::parse("search(1)").unwrap();
+    assert!(pattern.paths(&haystack).is_empty());
+    let pattern = Pattern::parse("search(@one(1))").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+
+    let pattern = Pattern::parse("search(array)").unwrap();
+    let paths = pattern.paths(&haystack);
+    assert_eq!(paths.len(), MAX_SEARCH_DEPTH + 1);
+    assert_eq!(paths.last().unwrap().len(), MAX_SEARCH_DEPTH + 1);
+
+    // Nor does iterating over the matches go deeper
+    let pattern = Pattern::parse("array").unwrap();
+    assert_eq!(pattern.find_iter(&haystack).count(), MAX_SEARCH_DEPTH + 1);
+
+    // Values within the limit are still found
+    let shallow = nested_arrays(MAX_SEARCH_DEPTH);
+    assert_eq!(
+        Pattern::parse("search(1)").unwrap().paths(&shallow).len(),
+        1
+    );
+}
+
+#[test]
+fn test_search_deep_map_and_tagged_haystack() {
+    let mut haystack = CBOR::from(1);
+    for i in 0..2_500 {
+        haystack = if i % 2 == 0 {
+            let mut map = Map::new();
+            map.insert("k", haystack);
+            CBOR::from(map)
+        } else {
+            CBOR::to_tagged_value(100, haystack)
+        };
+    }
+    let pattern = Pattern::parse("search(1) | none(2)").unwrap();
+    assert!(pattern.matches(&haystack));
+    assert!(
+        Pattern::parse("search(1)")
+            .unwrap()
+            .paths(&haystack)
+            .is_empty()
+    );
+}
@@ -0,0 +1,110 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Error, Matcher, Pattern, Result};
+use indoc::indoc;
+
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+#[test]
+fn test_parse_multiline_with_comments() -> Result<()> {
+    let source = indoc! {r#"
+        # header check
+        {
+          "type": text,  # required
+          "items": [ (number)* ]
+        }
+    "#};
+    let pattern = Pattern::parse(source)?;
+    assert_eq!(
+        pattern,
+        Pattern::parse(r#"{"type": text, "items": [(number)*]}"#)?
+    );
+    assert_eq!(
+        pattern.to_string(),
+        r#"{"type": text, "items": [(number)*]}"#
+    );
+    assert!(pattern.matches(&cbor(r#"{"type": "a", "items": [1, 2]}"#)));
+    Ok(())
+}
+
+#[test]
+fn test_parse_comments_inside_arrays() -> Result<()> {
+    let source = indoc! {r#"
+        [
+            number,  # the count
+            # the label follows
+            text     # the label
+        ]
+    "#};
+    let pattern = Pattern::parse(source)?;
+    assert_eq!(pattern, Pattern::parse("[number, text]")?);
+    Ok(())
+}
+
+#[test]
+fn test_parse_comments_between_or_branches() -> Result<()> {
+    let source = "number # numbers\n| text # or text\n| null";
+    let pattern = Pattern::parse(source)?;
+    assert_eq!(pattern, Pattern::parse("number | text | null")?);
+
+    // A comment runs to the end of the input if there is no newline
+    assert_eq!(Pattern::parse("number # | text")?, Pattern::any_number());
+    Ok(())
+}
+
+#[test]
+fn test_parse_hash_inside_literals() -> Result<()> {
+    // `#` inside quoted strings and regexes does not start a comment
+    let pattern = Pattern::parse(r#""a # b""#)?;
+    assert!(pattern.matches(&cbor(r#""a # b""#)));
+
+    let pattern = Pattern::parse(r#"/^#\d+$/ # issue numbers"#)?;
+    assert!(pattern.matches(&cbor(r##""#42""##)));
+
+    let pattern = Pattern::parse(r##"cbor'"#"'"##)?;
+    assert!(pattern.matches(&cbor(r##""#""##)));
+    Ok(())
+}
+
+#[test]
+fn test_parse_comments_in_directly_scanned_syntax() -> Result<()> {
+    // Tagged content may contain comments, even with parentheses in them
+    let source = indoc! {r##"
+        tagged(
+            1,  # the tag
+            [number, "#)"]  # content :)
+        )
+    "##};
+    let pattern = Pattern::parse(source)?;
+    assert_eq!(pattern, Pattern::parse(r##"tagged(1, [number, "#)"])"##)?);
+
+    // Search options may be commented
+    let source = "search(number, # options\n depth: 1)";
+    let pattern = Pattern::parse(source)?;
+    assert_eq!(pattern, Pattern::parse("search(number, depth: 1)")?);
+    Ok(())
+}
+
+#[test]
+fn test_parse_block_comments_are_regexes() {
+    // `/` always starts a regex, so `/* ... */` is not a comment
+    let source = "/* note */ 42";
+    let error = Pattern::parse(source).unwrap_err();
+    assert!(matches!(error, Error::InvalidRegex(_, _)), "{:?}", error);
+    assert_eq!(&source[error.span().unwrap()], "/* note */");
+
+    assert!(Pattern::parse("/a*/").is_ok());
+}
+
+#[test]
+fn test_parse_comment_error_spans() {
+    let source = "# leading comment\n[number, %]";
+    let error = Pattern::parse(source).unwrap_err();
+    assert_eq!(&source[error.span().unwrap()], "%");
+
+    let source = "tagged(1, # note\n number ~)";
+    let error = Pattern::parse(source).unwrap_err();
+    assert_eq!(&source[error.span().unwrap()], "~");
+
+    assert!(Pattern::parse("# nothing but a comment").is_err());
+}
@@ -6,7 +6,7 @@ mod parse_partial_tests {
     fn test_parse_partial_basic() {
         let (pattern, consumed) = Pattern::parse_partial("true rest").unwrap();
         assert_eq!(pattern, Pattern::bool(true));
-        assert_eq!(consumed, 5); // "true ".len() - includes whitespace that is skipped
+        assert_eq!(consumed, 4); // "true".len() - excludes the whitespace after it
     }
 
     #[test]
@@ -14,7 +14,7 @@ mod parse_partial_tests {
         let (pattern, consumed) =
             Pattern::parse_partial("42    more stuff").unwrap();
         assert_eq!(pattern, Pattern::number(42));
-        assert_eq!(consumed, 6); // "42    ".len() - includes whitespace that is skipped
+        assert_eq!(consumed, 2); // "42".len() - excludes the whitespace after it
     }
 
     #[test]
@@ -41,7 +41,7 @@ mod parse_partial_tests {
         // Should still return error for extra data (backward compatibility)
         let result = Pattern::parse("true extra");
         match result {
-            Err(Error::ExtraData(_)) => (), // Expected
+            Err(Error::ExtraData(_, _)) => (), // Expected
             other => panic!("Expected ExtraData error, got: {:?}", other),
         }
     }
@@ -50,13 +50,68 @@ mod parse_partial_tests {
     fn test_parse_partial_with_valid_following_token() {
         let (pattern, consumed) = Pattern::parse_partial("true false").unwrap();
         assert_eq!(pattern, Pattern::bool(true));
-        assert_eq!(consumed, 5); // "true ".len() - includes whitespace
+        assert_eq!(consumed, 4); // "true".len()
 
-        // Should be able to parse the rest
+        // Should be able to parse the rest, whitespace and all
         let remaining = &"true false"[consumed..];
+        assert_eq!(remaining, " false");
         let (pattern2, consumed2) = Pattern::parse_partial(remaining).unwrap();
         assert_eq!(pattern2, Pattern::bool(false));
-        assert_eq!(consumed2, 5); // "false".len() (no trailing whitespace)
+        assert_eq!(consumed2, 6); // " false".len(), counting leading whitespace
+    }
+
+    #[test]
+    fn test_parse_partial_ends_at_last_token() {
+        // Patterns ending in each kind of token, followed by garbage
+        for pattern in [
+            "number",
+            "NaN",
+            r#"text("x")"#,
+            r#""hello""#,
+            "'isA'",
+            "h'cafe'",
+            "h'/^ca/'",
+            "/h.*o/",
+            "date'2023-12-25'",
+            "42",
+            ">=5",
+            "(number)*",
+            "(number)+?",
+            "(number){2,3}",
+            "number{2}",
+            "[{2,}]",
+            "{{1}}",
+            "{text: number}",
+            "[number, text]",
+            "tagged(100, number)",
+            "search(number, depth: 2)",
+            "@n(number)",
+            "[@n(*), =n]",
+        ] {
+            for rest in [" ~~", "   # comment\n ~~", "\t~~"] {
+                let input = format!("{}{}", pattern, rest);
+                let (parsed, consumed) =
+                    Pattern::parse_partial(&input).unwrap();
+                assert_eq!(consumed, pattern.len(), "{:?}", input);
+                assert_eq!(parsed, Pattern::parse(pattern).unwrap());
+                assert_eq!(&input[consumed..], rest);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_partial_trailing_whitespace() {
+        // Trailing whitespace and comments are not consumed, but `parse`
+        // still accepts them
+        let (_, consumed) = Pattern::parse_partial("number  # any\n").unwrap();
+        assert_eq!(consumed, 6);
+        assert!(Pattern::parse("number  # any\n").is_ok());
+
+        // Extra data is reported from its first token
+        assert!(matches!(
+            Pattern::parse("true  extra"),
+            Err(Error::ExtraData(_, span)) if span == (6..11)
+        ));
     }
 
     #[test]
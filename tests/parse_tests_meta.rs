@@ -1,5 +1,8 @@
 use dcbor::prelude::*;
-use dcbor_pattern::{Pattern, Result};
+use dcbor_pattern::{
+    ArrayPattern, Error, Interval, Matcher, MetaPattern, ParseOpts, Pattern,
+    Quantifier, Reluctance, Result, StructurePattern,
+};
 
 /// Test the OR parser with various patterns
 #[test]
@@ -116,9 +119,19 @@ fn test_parentheses_grouping_parsing() -> Result<()> {
 #[test]
 fn test_nested_parentheses() -> Result<()> {
     let pattern = Pattern::parse("((bool))")?;
-    // Should create nested RepeatPatterns with "exactly one" quantifiers
-    assert!(matches!(pattern, Pattern::Meta(_)));
-    assert_eq!(pattern.to_string(), "((bool){1}){1}");
+    // Parentheses only group, so they leave no trace in the pattern
+    assert_eq!(pattern, Pattern::any_bool());
+    assert_eq!(pattern.to_string(), "bool");
+
+    // An explicit {1} still creates a repeat
+    let pattern = Pattern::parse("(bool){1}")?;
+    assert!(matches!(pattern, Pattern::Meta(MetaPattern::Repeat(_))));
+    assert_eq!(pattern.to_string(), "(bool){1}");
+
+    // Grouping that changes precedence survives display
+    let pattern = Pattern::parse("((bool | text)) & !true")?;
+    assert_eq!(pattern.to_string(), "(bool | text) & !true");
+    assert_eq!(Pattern::parse(&pattern.to_string())?, pattern);
     Ok(())
 }
 
@@ -178,6 +191,139 @@ fn test_parse_capture_unclosed_parens() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_parse_capture_duplicate_names() {
+    let source = "[@item(42), @item(text)]";
+    assert_eq!(
+        Pattern::parse(source),
+        Err(Error::DuplicateCaptureName("item".to_string(), 12..17))
+    );
+
+    // Nested duplicates are found at any depth, including inside tagged
+    // content
+    assert_eq!(
+        Pattern::parse("@x([@x(number)])"),
+        Err(Error::DuplicateCaptureName("x".to_string(), 4..6))
+    );
+    assert_eq!(
+        Pattern::parse("tagged(1, @x(number) | @x(text))"),
+        Err(Error::DuplicateCaptureName("x".to_string(), 23..25))
+    );
+
+    // Names in text, regexes, and backreferences are not captures
+    assert!(Pattern::parse(r#"@x("@x") | /@x/ | =x"#).is_ok());
+}
+
+/// Alternatives that can never both match still may not share a capture
+/// name by default. With duplicates allowed, each name reports the paths of
+/// whichever captures with that name matched.
+#[test]
+fn test_parse_capture_duplicate_names_in_alternatives() -> Result<()> {
+    let source = "@n(number) | @n(text)";
+    assert_eq!(
+        Pattern::parse(source),
+        Err(Error::DuplicateCaptureName("n".to_string(), 13..15))
+    );
+
+    let opts = ParseOpts {
+        allow_duplicate_captures: true,
+        ..ParseOpts::default()
+    };
+    let pattern = Pattern::parse_with_opts(source, opts)?;
+    assert_eq!(pattern.capture_names(), vec!["n".to_string()]);
+    for value in [CBOR::from(42), CBOR::from("a")] {
+        let (paths, captures) = pattern.paths_with_captures(&value);
+        assert_eq!(paths, vec![vec![value.clone()]]);
+        assert_eq!(captures["n"], vec![vec![value]]);
+    }
+
+    // Captures in the same sequence are merged under the one name
+    let pattern = Pattern::parse_with_opts("[@n(number), @n(text)]", opts)?;
+    let array = CBOR::from(vec![CBOR::from(1), CBOR::from("a")]);
+    let (_, captures) = pattern.paths_with_captures(&array);
+    assert_eq!(
+        captures["n"],
+        vec![
+            vec![array.clone(), CBOR::from(1)],
+            vec![array.clone(), CBOR::from("a")]
+        ]
+    );
+
+    let (_, consumed) =
+        Pattern::parse_partial_with_opts("@n(1) | @n(2) rest", opts)?;
+    assert_eq!(consumed, 13);
+    assert!(Pattern::parse_partial("@n(1) | @n(2) rest").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_parse_capture_reserved_names() {
+    // The lexer only accepts names that start with a letter or underscore
+    assert!(Pattern::parse("@(42)").is_err());
+    assert!(Pattern::parse("@1x(42)").is_err());
+    assert!(Pattern::parse("@_1(42)").is_ok());
+}
+
+#[test]
+fn test_capture_names() -> Result<()> {
+    let pattern = Pattern::parse("[@b(number), @a(@c(text) | !bool)]")?;
+    assert_eq!(pattern.capture_names(), vec!["b", "a", "c"]);
+    assert!(Pattern::parse("[number]")?.capture_names().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_parse_capture_inside_negation() {
+    let error = |source: &str| Pattern::parse(source).unwrap_err();
+
+    // The error points at the capture
+    assert_eq!(
+        error("!@x(number)"),
+        Error::CaptureInsideNegation("x".into(), Some(1..3))
+    );
+    assert_eq!(
+        error("[number, !(text | @y(*))]"),
+        Error::CaptureInsideNegation("y".into(), Some(18..20))
+    );
+    assert_eq!(
+        error("search(none(@z(*)))"),
+        Error::CaptureInsideNegation("z".into(), Some(12..14))
+    );
+
+    // Or at the whole negated pattern if the capture is a regex group
+    assert_eq!(
+        error("!/(?<g>a)/"),
+        Error::CaptureInsideNegation("g".into(), Some(1..10))
+    );
+
+    // Captures beside a negation are fine
+    assert!(Pattern::parse("@x(!number)").is_ok());
+    assert!(Pattern::parse("!number & @x(*)").is_ok());
+}
+
+#[test]
+fn test_try_not_matching() {
+    let pattern = Pattern::try_not_matching(Pattern::number(1)).unwrap();
+    assert_eq!(pattern, Pattern::not_matching(Pattern::number(1)));
+
+    let captured = Pattern::or(vec![
+        Pattern::text("a"),
+        Pattern::capture("x", Pattern::any()),
+    ]);
+    assert_eq!(
+        Pattern::try_not_matching(captured),
+        Err(Error::CaptureInsideNegation("x".into(), None))
+    );
+
+    // Built in code, the error has no source to point into
+    let error =
+        Pattern::try_not_matching(Pattern::capture("x", Pattern::any()))
+            .unwrap_err();
+    assert_eq!(error.span(), None);
+    assert_eq!(error.lexeme(), None);
+    assert_eq!(error.to_string(), "Capture inside negation: x");
+}
+
 #[test]
 fn test_parse_parentheses_unclosed() {
     let result = Pattern::parse("(bool");
@@ -268,6 +414,102 @@ fn test_grouping_functionality() -> Result<()> {
     Ok(())
 }
 
+/// Test quantifiers written directly after a pattern, without parentheses
+#[test]
+fn test_parse_unparenthesized_quantifiers() -> Result<()> {
+    let pattern = Pattern::parse("[number{3}]")?;
+    assert_eq!(pattern, Pattern::parse("[(number){3}]")?);
+    assert!(pattern.matches(&CBOR::from(vec![1, 2, 3])));
+    assert!(!pattern.matches(&CBOR::from(vec![1, 2])));
+
+    let pattern = Pattern::parse("[text{1,2}?, number]")?;
+    assert_eq!(pattern.to_string(), "[(text){1,2}?, number]");
+    assert!(pattern.matches(&CBOR::from(vec![
+        CBOR::from("a"),
+        CBOR::from("b"),
+        CBOR::from(1)
+    ])));
+    assert!(!pattern.matches(&CBOR::from(vec![1])));
+
+    assert_eq!(
+        Pattern::parse("[number*, text+]")?,
+        Pattern::parse("[(number)*, (text)+]")?
+    );
+    assert_eq!(
+        Pattern::parse("[@n(number){2,}]")?,
+        Pattern::parse("[(@n(number)){2,}]")?
+    );
+    Ok(())
+}
+
+/// Test that every shape of quantifier, with every reluctance, displays in
+/// its shortest form and parses back to the same pattern
+#[test]
+fn test_quantifier_round_trip() -> Result<()> {
+    let reluctances =
+        [Reluctance::Greedy, Reluctance::Lazy, Reluctance::Possessive];
+    for reluctance in reluctances {
+        for min in 0..4 {
+            let intervals = [
+                Interval::new(min..),
+                Interval::new(min..=min),
+                Interval::new(min..=min + 1),
+                Interval::new(min..=min + 3),
+            ];
+            for interval in intervals {
+                let quantifier = Quantifier::try_new(interval, reluctance)?;
+                let repeat = Pattern::repeat(Pattern::any_number(), quantifier);
+                let array = Pattern::Structure(StructurePattern::Array(
+                    ArrayPattern::with_elements(repeat.clone()),
+                ));
+                for pattern in [repeat, array] {
+                    let display = pattern.to_string();
+                    let parsed = Pattern::parse(&display)?;
+                    assert_eq!(parsed, pattern, "{}", display);
+                    assert_eq!(parsed.to_string(), display);
+                }
+            }
+        }
+    }
+
+    // Longer forms parse to the shortest
+    for (source, display) in [
+        ("[number{0,}]", "[(number)*]"),
+        ("[number{1,}?]", "[(number)+?]"),
+        ("[number{0,1}+]", "[(number)?+]"),
+        ("[number{0,3}]", "[(number){,3}]"),
+        ("[number{,0}]", "[(number){0}]"),
+        ("[number{2,2}?]", "[(number){2}?]"),
+    ] {
+        assert_eq!(Pattern::parse(source)?.to_string(), display, "{}", source);
+    }
+    Ok(())
+}
+
+/// Test quantifiers with no pattern before them to repeat
+#[test]
+fn test_parse_quantifier_nothing_to_repeat() -> Result<()> {
+    // A range alone in brackets is still an array length, but one with no
+    // pattern before it elsewhere is a map length
+    assert_eq!(Pattern::parse("[{3}]")?.to_string(), "[{3}]");
+    assert_eq!(
+        Pattern::parse("[{3}, number]")?.to_string(),
+        "[{{3}}, number]"
+    );
+
+    for (source, quantifier) in [
+        ("[number{3}{2}]", "{2}"),
+        ("(number)*{2,}?", "{2,}?"),
+        ("[(number)*+?]", "?"),
+        ("[number, +]", "+"),
+    ] {
+        let error = Pattern::parse(source).unwrap_err();
+        assert!(matches!(error, Error::NothingToRepeat(_, _)), "{:?}", error);
+        assert_eq!(&source[error.span().unwrap()], quantifier, "{}", source);
+    }
+    Ok(())
+}
+
 /// Test `search` pattern parsing
 #[test]
 fn test_parse_search_simple() -> Result<()> {
@@ -299,6 +541,74 @@ fn test_parse_search_with_any() -> Result<()> {
     Ok(())
 }
 
+/// Test `child` pattern parsing
+#[test]
+fn test_parse_child() -> Result<()> {
+    let pattern = Pattern::parse("child(number)")?;
+    assert_eq!(pattern, Pattern::child(Pattern::any_number()));
+    assert_eq!(pattern.to_string(), "child(number)");
+
+    let pattern = Pattern::parse("child(child(text | 42))")?;
+    assert_eq!(pattern.to_string(), "child(child(text | 42))");
+
+    assert!(Pattern::parse("child").is_err());
+    assert!(Pattern::parse("child(number").is_err());
+    Ok(())
+}
+
+/// Test `untag` pattern parsing
+#[test]
+fn test_parse_untag() -> Result<()> {
+    let pattern = Pattern::parse("untag(42)")?;
+    assert_eq!(pattern, Pattern::untag(Pattern::number(42)));
+    assert_eq!(pattern.to_string(), "untag(42)");
+
+    let pattern = Pattern::parse("[untag(@n(number)), untag(text | null)]")?;
+    assert_eq!(
+        pattern.to_string(),
+        "[untag(@n(number)), untag(text | null)]"
+    );
+
+    assert!(Pattern::parse("untag").is_err());
+    assert!(Pattern::parse("untag(number").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_parse_entry() -> Result<()> {
+    let pattern = Pattern::parse("entry(text: number)")?;
+    assert_eq!(
+        pattern,
+        Pattern::entry(Pattern::any_text(), Pattern::any_number())
+    );
+    assert_eq!(pattern.to_string(), "entry(text: number)");
+
+    let pattern = Pattern::parse(r#"search(entry(@k("a" | "b"): [(*)*]))"#)?;
+    assert_eq!(
+        pattern.to_string(),
+        r#"search(entry(@k("a" | "b"): [(*)*]))"#
+    );
+
+    assert!(Pattern::parse("entry").is_err());
+    assert!(Pattern::parse("entry(text)").is_err());
+    assert!(Pattern::parse("entry(text: number").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_parse_none() -> Result<()> {
+    let pattern = Pattern::parse("none(number)")?;
+    assert_eq!(pattern, Pattern::none(Pattern::any_number()));
+    assert_eq!(pattern.to_string(), "none(number)");
+
+    let pattern = Pattern::parse("search(none(text | [*]))")?;
+    assert_eq!(pattern.to_string(), "search(none(text | [*]))");
+
+    assert!(Pattern::parse("none").is_err());
+    assert!(Pattern::parse("none(number").is_err());
+    Ok(())
+}
+
 #[test]
 fn test_parse_search_with_complex_pattern() -> Result<()> {
     let pattern = Pattern::parse("search(bool | text)")?;
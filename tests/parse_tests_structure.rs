@@ -1 +1,173 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    ArrayPattern, Interval, Matcher, Pattern, StructurePattern,
+};
 
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Builds an array of `n` integers in diagnostic notation.
+fn array_of(n: usize) -> CBOR {
+    let items: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    cbor(&format!("[{}]", items.join(", ")))
+}
+
+#[test]
+fn parse_interval_forms_round_trip() {
+    let cases = vec![
+        ("[{3}]", "[{3}]"),
+        ("[{0}]", "[{0}]"),
+        ("[{2,5}]", "[{2,5}]"),
+        ("[{2,}]", "[{2,}]"),
+        ("[{0,}]", "[{0,}]"),
+        ("[{,4}]", "[{,4}]"),
+        ("[{ , 4 }]", "[{,4}]"),
+        ("[{0,4}]", "[{,4}]"),
+        ("[{1,1}]", "[{1}]"),
+        ("{{,2}}", "{{,2}}"),
+        ("bstr({,8})", "bstr({,8})"),
+        ("[(number){,3}]", "[(number){,3}]"),
+        ("[(number){0,3}?]", "[(number){,3}?]"),
+    ];
+
+    for (source, expected) in cases {
+        let pattern = Pattern::parse(source).unwrap();
+        assert_eq!(pattern.to_string(), expected, "Display of {}", source);
+        let round_trip = Pattern::parse(&pattern.to_string()).unwrap();
+        assert_eq!(pattern, round_trip, "Round trip failed for: {}", source);
+    }
+}
+
+#[test]
+fn parse_at_most_interval() {
+    let pattern = Pattern::parse("[{,2}]").unwrap();
+    for n in 0..=2 {
+        assert!(pattern.matches(&array_of(n)), "{} elements", n);
+    }
+    assert!(!pattern.matches(&array_of(3)));
+
+    let pattern = Pattern::parse("[(number){,2}, text]").unwrap();
+    assert!(pattern.matches(&cbor(r#"["a"]"#)));
+    assert!(pattern.matches(&cbor(r#"[1, 2, "a"]"#)));
+    assert!(!pattern.matches(&cbor(r#"[1, 2, 3, "a"]"#)));
+
+    let pattern = Pattern::parse("{{,1}}").unwrap();
+    assert!(pattern.matches(&cbor("{}")));
+    assert!(pattern.matches(&cbor("{1: 2}")));
+    assert!(!pattern.matches(&cbor("{1: 2, 3: 4}")));
+}
+
+#[test]
+fn parse_interval_boundaries() {
+    let intervals = [
+        Interval::new(3..=3),
+        Interval::new(2..=5),
+        Interval::new(2..),
+        Interval::at_most(4),
+        Interval::new(0..),
+    ];
+    for interval in intervals {
+        let pattern = Pattern::parse(&format!("[{}]", interval)).unwrap();
+        for n in 0..=7 {
+            assert_eq!(
+                pattern.matches(&array_of(n)),
+                interval.contains(n),
+                "[{}] against {} elements",
+                interval,
+                n
+            );
+        }
+    }
+}
+
+#[test]
+fn parse_array_length_forms() {
+    // Each form, the lengths it matches, and the lengths just outside them
+    let cases: [(&str, &[usize], &[usize]); 5] = [
+        ("[{0}]", &[0], &[1]),
+        ("[{3}]", &[3], &[2, 4]),
+        ("[{3,}]", &[3, 4, 100], &[0, 2]),
+        ("[{,3}]", &[0, 1, 3], &[4]),
+        ("[{2,5}]", &[2, 5], &[1, 6]),
+    ];
+    for (source, matching, not_matching) in cases {
+        let pattern = Pattern::parse(source).unwrap();
+        assert_eq!(pattern.to_string(), source);
+        assert_eq!(Pattern::parse(&pattern.to_string()).unwrap(), pattern);
+        for &n in matching {
+            assert!(pattern.matches(&array_of(n)), "{} against {}", source, n);
+        }
+        for &n in not_matching {
+            assert!(!pattern.matches(&array_of(n)), "{} against {}", source, n);
+        }
+    }
+
+    // Lengths built from Rust ranges display in the same forms
+    let built = [
+        (ArrayPattern::with_length_range(..=3), "[{,3}]"),
+        (ArrayPattern::with_length_range(..4), "[{,3}]"),
+        (ArrayPattern::with_length_range(3..), "[{3,}]"),
+        (ArrayPattern::with_length_range(2..6), "[{2,5}]"),
+        (ArrayPattern::with_length_range(0..1), "[{0}]"),
+    ];
+    for (pattern, expected) in built {
+        let pattern = Pattern::Structure(StructurePattern::Array(pattern));
+        assert_eq!(pattern.to_string(), expected);
+        assert_eq!(Pattern::parse(expected).unwrap(), pattern);
+    }
+}
+
+#[test]
+fn parse_invalid_intervals() {
+    for source in ["[{,}]", "[{,x}]", "[{5,2}]"] {
+        assert!(
+            Pattern::parse(source).is_err(),
+            "{} should not parse",
+            source
+        );
+    }
+}
+
+#[test]
+fn parse_brace_position() {
+    // On its own, a range is a map length
+    let empty = Pattern::parse("{0}").unwrap();
+    assert_eq!(empty, Pattern::parse("{{0}}").unwrap());
+    assert!(empty.matches(&cbor("{}")));
+    assert!(!empty.matches(&cbor("{1: 2}")));
+    assert!(!empty.matches(&cbor("[]")));
+
+    let five = Pattern::parse("{5}").unwrap();
+    assert_eq!(five.to_string(), "{{5}}");
+    assert!(five.matches(&cbor("{1: 1, 2: 2, 3: 3, 4: 4, 5: 5}")));
+    assert!(!five.matches(&cbor("{1: 1}")));
+
+    // After a pattern, it is a quantifier
+    let repeat = Pattern::parse("[(*){5}]").unwrap();
+    assert_eq!(repeat.to_string(), "[(*){5}]");
+    assert!(repeat.matches(&array_of(5)));
+    assert!(!repeat.matches(&array_of(4)));
+    assert_eq!(Pattern::parse("{5}{2}").unwrap().to_string(), "({{5}}){2}");
+    assert_eq!(Pattern::parse("{5}?").unwrap().to_string(), "({{5}})?");
+
+    // Followed by a colon, a number is a key
+    let keyed = Pattern::parse("{5: *}").unwrap();
+    assert_eq!(keyed.to_string(), "{5: *}");
+    assert!(keyed.matches(&cbor(r#"{5: "x"}"#)));
+    assert!(!keyed.matches(&cbor(r#"{4: "x"}"#)));
+
+    // As is a map length
+    let map_key = Pattern::parse("{{0}: text}").unwrap();
+    assert_eq!(map_key.to_string(), "{{{0}}: text}");
+    assert!(map_key.matches(&cbor(r#"{{}: "x"}"#)));
+    assert!(!map_key.matches(&cbor(r#"{{1: 2}: "x"}"#)));
+
+    // In brackets, only a range closed by the bracket or followed by `&` is
+    // the array's length
+    let sequence = Pattern::parse("[{0}, {5: *}]").unwrap();
+    assert_eq!(sequence.to_string(), "[{{0}}, {5: *}]");
+    assert!(sequence.matches(&cbor(r#"[{}, {5: "x"}]"#)));
+    assert_eq!(Pattern::parse("[{0}]").unwrap().to_string(), "[{0}]");
+    assert!(Pattern::parse("[{0}]").unwrap().matches(&cbor("[]")));
+}
@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
-use dcbor_pattern::{Matcher, Pattern};
+use dcbor_pattern::{DatePattern, Error, Matcher, Pattern};
 
 /// Helper function to parse CBOR diagnostic notation into CBOR objects
 fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
@@ -156,6 +158,94 @@ fn parse_date_regex() {
     assert_eq!(p.to_string(), "date'/2023-.*/'");
 }
 
+#[test]
+fn parse_date_relative() {
+    let cases = [
+        ("date'past'", Pattern::date_past()),
+        ("date'future'", Pattern::date_future()),
+        (
+            "date'past 30d'",
+            Pattern::date_within_past(Duration::from_secs(30 * 86400)),
+        ),
+        (
+            "date'future 12h'",
+            Pattern::date_within_future(Duration::from_secs(12 * 3600)),
+        ),
+    ];
+    for (src, expected) in cases {
+        let p = Pattern::parse(src).unwrap();
+        assert_eq!(p, expected, "{}", src);
+        assert_eq!(p.to_string(), src);
+    }
+
+    // Durations are shown in the largest exact unit
+    let p = Pattern::parse("date'past 720h'").unwrap();
+    assert_eq!(p.to_string(), "date'past 30d'");
+
+    for src in [
+        "date'past 30'",
+        "date'past d'",
+        "date'past  30d'",
+        "date'pastime'",
+        "date'future 1.5h'",
+    ] {
+        assert!(
+            matches!(Pattern::parse(src), Err(Error::InvalidDateFormat(_, _))),
+            "{} should not parse",
+            src
+        );
+    }
+}
+
+#[test]
+fn match_date_relative() {
+    let now = Date::try_from(cbor("2024-06-15T12:00:00Z")).unwrap();
+    let p = Pattern::parse("[(date'past 30d')*]").unwrap();
+
+    let recent = cbor("[2024-06-01, 2024-05-20, 2024-06-15T12:00:00Z]");
+    let stale = cbor("[2024-06-01, 2024-04-01]");
+    let future = cbor("[2024-06-16]");
+    DatePattern::with_now(now, || {
+        assert!(p.matches(&recent));
+        assert!(!p.matches(&stale));
+        assert!(!p.matches(&future));
+    });
+}
+
+#[test]
+fn parse_date_any_encoding() {
+    let date = Date::try_from(cbor("2023-12-25")).unwrap();
+    let cases = [
+        ("date-any", Pattern::date_any_encoding(DatePattern::any())),
+        (
+            "date-any'2023-12-25'",
+            Pattern::date_any_encoding(DatePattern::value(date)),
+        ),
+        (
+            "date-any'past 30d'",
+            Pattern::date_any_encoding(DatePattern::within_past(
+                Duration::from_secs(30 * 86400),
+            )),
+        ),
+        (
+            "date-any'/^2023-/'",
+            Pattern::date_any_encoding(DatePattern::regex(
+                regex::Regex::new("^2023-").unwrap(),
+            )),
+        ),
+    ];
+    for (src, expected) in cases {
+        let p = Pattern::parse(src).unwrap();
+        assert_eq!(p, expected, "{}", src);
+        assert_eq!(p.to_string(), src);
+    }
+
+    assert!(matches!(
+        Pattern::parse("date-any'2023-13-45'"),
+        Err(Error::InvalidDateFormat(_, _))
+    ));
+}
+
 #[test]
 fn parse_date_patterns_round_trip() {
     // Create some date patterns and test round-trip parsing
@@ -168,6 +258,10 @@ fn parse_date_patterns_round_trip() {
         Pattern::date_range(date1..=date2),
         Pattern::date_earliest(date2),
         Pattern::date_latest(date2),
+        Pattern::date_past(),
+        Pattern::date_future(),
+        Pattern::date_within_past(Duration::from_secs(30 * 86400)),
+        Pattern::date_within_future(Duration::from_millis(90)),
     ];
 
     for pattern in patterns {
@@ -215,6 +309,26 @@ fn parse_text_literal_with_escapes() {
     assert_eq!(p.to_string(), src);
 }
 
+#[test]
+fn parse_text_prefix_suffix_case_insensitive() {
+    let src = r#"text(^"ERROR")"#;
+    let p = Pattern::parse(src).unwrap();
+    assert_eq!(p, Pattern::text_prefix("ERROR"));
+    assert_eq!(p.to_string(), src);
+
+    let src = r#"text($" \"done\"")"#;
+    let p = Pattern::parse(src).unwrap();
+    assert_eq!(p, Pattern::text_suffix(r#" "done""#));
+    assert_eq!(p.to_string(), src);
+
+    let src = r#"text(~"error")"#;
+    let p = Pattern::parse(src).unwrap();
+    assert_eq!(p, Pattern::text_case_insensitive("error"));
+    assert_eq!(p.to_string(), src);
+    assert!(p.matches(&CBOR::from("ERROR")));
+    assert!(!p.matches(&CBOR::from("ERRORS")));
+}
+
 #[test]
 fn parse_text_regex() {
     let src = r"/h.*o/";
@@ -315,6 +429,25 @@ fn parse_bytestring_regex() {
     assert!(!pattern.matches(&cbor(r#"h'646566'"#))); // "def" in hex
 }
 
+#[test]
+fn parse_bytestring_length_and_prefix() {
+    let pattern = Pattern::parse("bstr({16,64})").unwrap();
+    assert!(!pattern.matches(&cbor(&format!("h'{}'", "00".repeat(15)))));
+    assert!(pattern.matches(&cbor(&format!("h'{}'", "00".repeat(16)))));
+    assert!(pattern.matches(&cbor(&format!("h'{}'", "00".repeat(64)))));
+    assert!(!pattern.matches(&cbor(&format!("h'{}'", "00".repeat(65)))));
+
+    let pattern = Pattern::parse("bstr({0})").unwrap();
+    assert!(pattern.matches(&cbor("h''")));
+    assert!(!pattern.matches(&cbor("h'00'")));
+
+    let pattern = Pattern::parse("bstr(prefix: h'0102')").unwrap();
+    assert!(pattern.matches(&cbor("h'0102'")));
+    assert!(pattern.matches(&cbor("h'010203'")));
+    assert!(!pattern.matches(&cbor("h'01'")));
+    assert!(!pattern.matches(&cbor("h''")));
+}
+
 #[test]
 fn parse_bytestring_patterns_round_trip() {
     let cases = vec![
@@ -323,6 +456,9 @@ fn parse_bytestring_patterns_round_trip() {
         r#"h''"#,
         r"h'/^[a-f0-9]+$/'",
         r"h'/test/'",
+        "bstr({32})",
+        "bstr({16,64})",
+        "bstr(prefix: h'0102')",
     ];
 
     for case in cases {
@@ -331,3 +467,48 @@ fn parse_bytestring_patterns_round_trip() {
         assert_eq!(p, round_trip, "Round trip failed for: {}", case);
     }
 }
+
+#[test]
+fn parse_cbor_literal() {
+    let pattern =
+        Pattern::parse(r#"cbor'{"a": [1, 2, {"b": h\'00\'}]}'"#).unwrap();
+    assert_eq!(
+        pattern,
+        Pattern::cbor(cbor(r#"{"a": [1, 2, {"b": h'00'}]}"#))
+    );
+    assert_eq!(
+        pattern.to_string(),
+        r#"cbor'{"a": [1, 2, {"b": h\'00\'}]}'"#
+    );
+    assert!(pattern.matches(&cbor(r#"{"a": [1, 2, {"b": h'00'}]}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"a": [1, 2, {"b": h'01'}]}"#)));
+
+    // Map keys may be written in any order
+    let pattern = Pattern::parse(r#"cbor'{"b": 2, "a": 1}'"#).unwrap();
+    assert!(pattern.matches(&cbor(r#"{"a": 1, "b": 2}"#)));
+
+    // Tagged values and escaped quotes inside text
+    let pattern =
+        Pattern::parse(r#"cbor'1(["it\'s", "a\"b", "back\\\\"])'"#).unwrap();
+    assert!(pattern.matches(&cbor(r#"1(["it's", "a\"b", "back\\"])"#)));
+    let round_trip = Pattern::parse(&pattern.to_string()).unwrap();
+    assert_eq!(pattern, round_trip);
+
+    // Exact subtrees combine with structural patterns
+    let pattern = Pattern::parse(r#"[cbor'{"v": 1}', (*)*]"#).unwrap();
+    assert!(pattern.matches(&cbor(r#"[{"v": 1}, 2, 3]"#)));
+    assert!(pattern.matches(&cbor(r#"[{"v": 1}]"#)));
+    assert!(!pattern.matches(&cbor(r#"[{"v": 1, "w": 2}]"#)));
+}
+
+#[test]
+fn parse_cbor_literal_errors() {
+    assert!(matches!(
+        Pattern::parse("cbor'[1, 2'"),
+        Err(Error::InvalidCbor(_, span)) if span == (0..11)
+    ));
+    assert!(matches!(
+        Pattern::parse("cbor'[1, 2]"),
+        Err(Error::UnterminatedCborQuoted(_, span)) if span == (0..11)
+    ));
+}
@@ -0,0 +1,69 @@
+use dcbor_pattern::{Error, Pattern, Warning};
+
+#[test]
+fn test_single_element_array_warning() {
+    for (source, span) in [
+        ("[*]", 0..3),
+        ("[(*)]", 0..5),
+        ("[ ((*)) ]", 0..9),
+        (r#"{"items": [*]}"#, 10..13),
+        ("search([*])", 7..10),
+    ] {
+        let (pattern, warnings) = Pattern::parse_with_warnings(source).unwrap();
+        assert_eq!(pattern, Pattern::parse(source).unwrap());
+        assert_eq!(warnings, [Warning::SingleElementArray(span)], "{}", source);
+    }
+
+    let (_, warnings) = Pattern::parse_with_warnings("[*] | [[*]]").unwrap();
+    assert_eq!(
+        warnings,
+        [
+            Warning::SingleElementArray(0..3),
+            Warning::SingleElementArray(7..10),
+        ]
+    );
+}
+
+#[test]
+fn test_no_warnings() {
+    for source in [
+        "array",
+        "[(*)*]",
+        "[*, *]",
+        "[(*){1}]",
+        "[*, (*)*]",
+        "[@item(*)]",
+        "[number]",
+        "[(*)+]",
+        "tagged(*, [(*)*])",
+        "/[*]/",
+        r#""[*]""#,
+    ] {
+        let (pattern, warnings) = Pattern::parse_with_warnings(source).unwrap();
+        assert_eq!(pattern, Pattern::parse(source).unwrap());
+        assert!(warnings.is_empty(), "{}: {:?}", source, warnings);
+    }
+}
+
+#[test]
+fn test_warning_display() {
+    let (_, warnings) = Pattern::parse_with_warnings("[*]").unwrap();
+    assert_eq!(warnings[0].span(), &(0..3));
+    assert_eq!(
+        warnings[0].to_string(),
+        "[*] at 0..3 matches only arrays of exactly one element; use array or \
+         [(*)*] for an array of any length"
+    );
+}
+
+#[test]
+fn test_parse_errors_take_precedence() {
+    assert_eq!(
+        Pattern::parse_with_warnings("[*] text"),
+        Err(Error::ExtraData("text".to_string(), 4..8))
+    );
+    assert!(matches!(
+        Pattern::parse_with_warnings("[*, @a(*), @a(*)]"),
+        Err(Error::DuplicateCaptureName(..))
+    ));
+}
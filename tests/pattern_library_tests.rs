@@ -0,0 +1,174 @@
+mod common;
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    Error, FormatPathsOpts, Matcher, Pattern, PatternLibrary,
+    format_paths_with_captures,
+};
+use indoc::indoc;
+
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+fn library() -> PatternLibrary {
+    let mut library = PatternLibrary::new();
+    library
+        .define("id", "number | text")
+        .unwrap()
+        .define("user", r#"{"name": @name(text), "id": $id}"#)
+        .unwrap();
+    library
+}
+
+#[test]
+fn test_library_substitutes_in_place() {
+    let library = library();
+
+    // References expand to the definitions, with nothing left to resolve
+    let pattern = library.parse("[$user, $user]").unwrap();
+    assert_eq!(
+        pattern,
+        Pattern::parse(
+            r#"[{"name": @user_1_name(text), "id": number | text},
+                {"name": @user_2_name(text), "id": number | text}]"#
+        )
+        .unwrap()
+    );
+
+    // In arrays, maps, and tagged values
+    let users =
+        cbor(r#"[{"name": "Alice", "id": 1}, {"name": "Bob", "id": "b"}]"#);
+    assert!(pattern.matches(&users));
+    assert!(!pattern.matches(&cbor(r#"[{"name": "Alice", "id": 1}]"#)));
+
+    let pattern = library.parse(r#"{"owner": $user, $id: *}"#).unwrap();
+    assert!(
+        pattern.matches(&cbor(
+            r#"{"owner": {"name": "Alice", "id": 1}, 7: null}"#
+        ))
+    );
+    assert!(
+        !pattern.matches(&cbor(r#"{"owner": {"name": "Alice"}, 7: null}"#))
+    );
+
+    let pattern = library.parse("tagged(40000, $user)").unwrap();
+    assert!(pattern.matches(&cbor(r#"40000({"name": "Alice", "id": 1})"#)));
+    assert!(!pattern.matches(&cbor(r#"40001({"name": "Alice", "id": 1})"#)));
+
+    // A reference quantifies like any other primary pattern
+    let pattern = library.parse("[$id{2}]").unwrap();
+    assert!(pattern.matches(&cbor(r#"[1, "a"]"#)));
+    assert!(!pattern.matches(&cbor("[1]")));
+}
+
+#[test]
+fn test_library_renames_captures_per_reference() {
+    let library = library();
+    let pattern = library.parse("[$user, $user]").unwrap();
+    let users =
+        cbor(r#"[{"name": "Alice", "id": 1}, {"name": "Bob", "id": 2}]"#);
+
+    let (paths, captures) = pattern.paths_with_captures(&users);
+    assert_path_chains!(&users, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @user_1_name
+            [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]
+                {"id": 1, "name": "Alice"}
+                    "Alice"
+        @user_2_name
+            [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]
+                {"id": 2, "name": "Bob"}
+                    "Bob"
+        [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // Prefixes nest through definitions that refer to other definitions,
+    // and back-references are renamed with their captures
+    let mut library = library;
+    library
+        .define("pair", "[@first(number), =first]")
+        .unwrap()
+        .define("team", r#"{"lead": $user, "pair": $pair}"#)
+        .unwrap();
+    let pattern = library.parse("[$team, $team]").unwrap();
+    assert_eq!(
+        pattern.capture_names(),
+        [
+            "team_1_user_1_name",
+            "team_1_pair_1_first",
+            "team_2_user_1_name",
+            "team_2_pair_1_first",
+        ]
+    );
+    let team = r#"{"lead": {"name": "Alice", "id": 1}, "pair": [3, 3]}"#;
+    assert!(pattern.matches(&cbor(&format!("[{team}, {team}]"))));
+    let unequal = r#"{"lead": {"name": "Bob", "id": 2}, "pair": [3, 4]}"#;
+    assert!(!pattern.matches(&cbor(&format!("[{team}, {unequal}]"))));
+}
+
+#[test]
+fn test_library_errors() {
+    let mut library = library();
+
+    // Each name is defined once
+    assert_eq!(
+        library.define("user", "text").unwrap_err(),
+        Error::DuplicateDefinition("user".to_string())
+    );
+    assert_eq!(
+        library.define("2fa", "text").unwrap_err(),
+        Error::InvalidDefinitionName("2fa".to_string())
+    );
+
+    // A definition can't refer to itself, or to a name not yet defined
+    assert_eq!(
+        library.define("tree", "[$tree, number]").unwrap_err(),
+        Error::RecursiveDefinition("tree".to_string(), 1..6)
+    );
+    assert_eq!(
+        library.define("forest", "[$tree]").unwrap_err(),
+        Error::UndefinedReference("tree".to_string(), 1..6)
+    );
+    assert!(library.definition("tree").is_none());
+
+    // Definitions must parse, with spans relative to their own source
+    assert_eq!(
+        library.define("broken", "[number").unwrap_err(),
+        Error::ExpectedCloseBracket(String::new(), 7..7)
+    );
+    assert_eq!(library.names().collect::<Vec<_>>(), ["id", "user"]);
+
+    // References to undefined names are reported where they appear
+    assert_eq!(
+        library.parse("[$user, $group]").unwrap_err(),
+        Error::UndefinedReference("group".to_string(), 8..14)
+    );
+    assert_eq!(
+        Pattern::parse("$user").unwrap_err(),
+        Error::UndefinedReference("user".to_string(), 0..5)
+    );
+    assert_eq!(
+        Pattern::parse("$user").unwrap_err().to_string(),
+        "Undefined reference: $user at 0..5"
+    );
+
+    // A reference to a definition with captures can't be negated
+    assert_eq!(
+        library.parse("[!$user]").unwrap_err(),
+        Error::CaptureInsideNegation("user_1_name".to_string(), Some(2..7))
+    );
+    assert!(library.parse("[!$id]").is_ok());
+
+    // `$` still introduces text suffixes
+    assert!(library.parse(r#"[text($"ice"), $user]"#).is_ok());
+}
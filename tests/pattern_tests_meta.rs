@@ -2,7 +2,10 @@ mod common;
 
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
-use dcbor_pattern::{Matcher, Pattern, format_paths};
+use dcbor_pattern::{
+    AndPattern, FormatPathsOpts, Matcher, MetaPattern, OrPattern, Pattern,
+    Quantifier, format_paths, format_paths_with_captures,
+};
 use indoc::indoc;
 
 /// Helper function to parse CBOR diagnostic notation into CBOR objects
@@ -122,6 +125,70 @@ fn test_or_pattern() {
     assert_eq!(pattern.to_string(), r#"5 | "hello" | true"#);
 }
 
+#[test]
+fn test_any_of_all_of_flatten() {
+    let one_two = Pattern::or(vec![Pattern::number(1), Pattern::number(2)]);
+    let pattern = Pattern::any_of(&[one_two, Pattern::number(3)]);
+    assert_eq!(pattern, Pattern::parse("1 | 2 | 3").unwrap());
+    assert_eq!(pattern.to_string(), "1 | 2 | 3");
+
+    let bounds = Pattern::and(vec![
+        Pattern::number_greater_than(0),
+        Pattern::number_less_than(10),
+    ]);
+    let pattern =
+        Pattern::all_of(&[bounds, Pattern::not_matching(Pattern::number(5))]);
+    assert_eq!(pattern.to_string(), ">0 & <10 & !5");
+    assert!(pattern.matches(&cbor("4")));
+    assert!(!pattern.matches(&cbor("5")));
+
+    // Only a nested operator of the same kind is flattened
+    let pattern = Pattern::all_of(&[
+        Pattern::parse("1 | 2").unwrap(),
+        Pattern::number(1),
+    ]);
+    assert_eq!(pattern.to_string(), "(1 | 2) & 1");
+
+    // An empty AND matches everything, and an empty OR nothing
+    assert!(Pattern::all_of(&[]).matches(&cbor("42")));
+    assert!(!Pattern::any_of(&[]).matches(&cbor("42")));
+}
+
+#[test]
+fn test_or_pattern_push_flattens() {
+    let mut or = OrPattern::new(vec![Pattern::number(1)]);
+    or.push(Pattern::or(vec![Pattern::number(2), Pattern::number(3)]));
+    or.extend([Pattern::number(4)]);
+    assert_eq!(or.patterns().len(), 4);
+    assert_eq!(or.to_string(), "1 | 2 | 3 | 4");
+
+    let and: AndPattern =
+        [Pattern::any_number(), Pattern::parse(">0 & <9").unwrap()]
+            .into_iter()
+            .collect();
+    assert_eq!(and.patterns().len(), 3);
+}
+
+#[test]
+fn test_parser_flattens_grouped_operators() {
+    for (source, displayed) in [
+        ("1|2|3|4", "1 | 2 | 3 | 4"),
+        ("(1 | 2) | (3 | 4)", "1 | 2 | 3 | 4"),
+        ("1 | ((2 | 3) | 4)", "1 | 2 | 3 | 4"),
+        ("(>0 & <9) & !5", ">0 & <9 & !5"),
+    ] {
+        let pattern = Pattern::parse(source).unwrap();
+        assert_eq!(pattern.to_string(), displayed);
+        assert_eq!(pattern, Pattern::parse(displayed).unwrap());
+    }
+    let Pattern::Meta(MetaPattern::Or(or)) =
+        Pattern::parse("(1 | 2) | (3 | 4)").unwrap()
+    else {
+        panic!("expected an OR pattern");
+    };
+    assert_eq!(or.patterns().len(), 4);
+}
+
 #[test]
 fn test_not_pattern() {
     let pattern = Pattern::not_matching(Pattern::number(5));
@@ -420,7 +487,7 @@ fn test_capture_pattern_is_complex() {
 #[test]
 fn test_repeat_pattern_basic() {
     // Test exact match (default quantifier)
-    let pattern = Pattern::group(Pattern::number(42));
+    let pattern = Pattern::repeat(Pattern::number(42), Quantifier::default());
 
     let forty_two_cbor = cbor("42");
     let paths = pattern.paths(&forty_two_cbor);
@@ -847,3 +914,402 @@ fn test_search_array_order() {
     "#}.trim();
     assert_actual_expected!(format_paths(&paths), expected);
 }
+
+#[test]
+fn test_search_quantified_map_order() {
+    let data = cbor(r#"{"a": {"x": 1}, "b": [1, {"y": 2, "z": 3}], "c": 3}"#);
+    let pattern = Pattern::parse("search({(*): (*)})").unwrap();
+
+    // The three-entry map once, not once per entry, then the nested maps
+    let paths = pattern.paths(&data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {"a": {"x": 1}, "b": [1, {"y": 2, "z": 3}], "c": 3}
+        {"a": {"x": 1}, "b": [1, {"y": 2, "z": 3}], "c": 3}
+            {"x": 1}
+        {"a": {"x": 1}, "b": [1, {"y": 2, "z": 3}], "c": 3}
+            [1, {"y": 2, "z": 3}]
+                {"y": 2, "z": 3}
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+    assert_eq!(pattern.paths_with_captures(&data).0, paths);
+
+    // The same paths however the map pattern finds its entries, while
+    // captures are made for each entry
+    for source in [
+        "search({*: *})",
+        "search(map)",
+        "search({(*)+: (*)?})",
+        "search({(@k(*)): (*)})",
+    ] {
+        let pattern = Pattern::parse(source).unwrap();
+        assert_eq!(pattern.paths(&data), paths, "{}", source);
+        assert_eq!(pattern.paths_with_captures(&data).0, paths, "{}", source);
+    }
+    let (_, captures) = Pattern::parse("search({(@k(*)): (*)})")
+        .unwrap()
+        .paths_with_captures(&data);
+    assert_eq!(captures["k"].len(), 6);
+}
+
+#[test]
+fn test_search_map_order() {
+    // Map entries in map order, which sorts keys by their encoding, with
+    // each key and its subtree before the value and its subtree
+    let data = cbor(r#"{"b": ["x", 1], ["k"]: "v", "a": {"c": "d"}}"#);
+    let pattern = Pattern::parse("search(text)").unwrap();
+
+    let paths = pattern.paths(&data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {"a": {"c": "d"}, "b": ["x", 1], ["k"]: "v"}
+            "a"
+        {"a": {"c": "d"}, "b": ["x", 1], ["k"]: "v"}
+            {"c": "d"}
+                "c"
+        {"a": {"c": "d"}, "b": ["x", 1], ["k"]: "v"}
+            {"c": "d"}
+                "d"
+        {"a": {"c": "d"}, "b": ["x", 1], ["k"]: "v"}
+            "b"
+        {"a": {"c": "d"}, "b": ["x", 1], ["k"]: "v"}
+            ["x", 1]
+                "x"
+        {"a": {"c": "d"}, "b": ["x", 1], ["k"]: "v"}
+            ["k"]
+                "k"
+        {"a": {"c": "d"}, "b": ["x", 1], ["k"]: "v"}
+            "v"
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // The same order with captures, through the VM, and lazily
+    let pattern = Pattern::parse("search(@t(text))").unwrap();
+    let (capture_paths, captures) = pattern.paths_with_captures(&data);
+    assert_eq!(capture_paths, paths);
+    assert_eq!(captures["t"], paths);
+    let pattern = Pattern::parse("@s(search(text))").unwrap();
+    assert_eq!(pattern.paths(&data), paths);
+    let pattern = Pattern::parse("text").unwrap();
+    let found: Vec<_> =
+        pattern.find_iter(&data).map(|m| m.path().clone()).collect();
+    assert_eq!(found, paths);
+
+    // A value equal to its own key has the same path, so it is reported once
+    let data = cbor(r#"{"a": "a", "b": 1}"#);
+    let paths = Pattern::parse("search(text)").unwrap().paths(&data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {"a": "a", "b": 1}
+            "a"
+        {"a": "a", "b": 1}
+            "b"
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_search_tagged_order() {
+    // A tagged value comes before its content, and the content's subtree
+    // before the tagged value's next sibling
+    let data = cbor(r#"[1(2([3])), 4, 5({6: 7})]"#);
+    let pattern = Pattern::parse("search(tagged | number)").unwrap();
+
+    let paths = pattern.paths(&data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [1(2([3])), 4, 5({6: 7})]
+            1(2([3]))
+        [1(2([3])), 4, 5({6: 7})]
+            1(2([3]))
+                2([3])
+        [1(2([3])), 4, 5({6: 7})]
+            1(2([3]))
+                2([3])
+                    [3]
+                        3
+        [1(2([3])), 4, 5({6: 7})]
+            4
+        [1(2([3])), 4, 5({6: 7})]
+            5({6: 7})
+        [1(2([3])), 4, 5({6: 7})]
+            5({6: 7})
+                {6: 7}
+                    6
+        [1(2([3])), 4, 5({6: 7})]
+            5({6: 7})
+                {6: 7}
+                    7
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_child_pattern() {
+    let pattern = Pattern::parse("child(number)").unwrap();
+
+    // Array elements, without descending further
+    let paths = pattern.paths(&cbor(r#"[1, "a", [2], 3]"#));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [1, "a", [2], 3]
+            1
+        [1, "a", [2], 3]
+            3
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Map keys and values
+    let paths = pattern.paths(&cbor(r#"{1: "one", "two": 2}"#));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {1: "one", "two": 2}
+            1
+        {1: "one", "two": 2}
+            2
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Tagged content
+    let paths = pattern.paths(&cbor("100(42)"));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        100(42)
+            42
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Leaf values have no children
+    assert!(!pattern.matches(&cbor("42")));
+}
+
+#[test]
+fn test_untag_pattern() {
+    let pattern = Pattern::parse("untag(42)").unwrap();
+
+    // The path runs through every tag layer to the content that matched
+    let paths = pattern.paths(&cbor("42"));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        42
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    let paths = pattern.paths(&cbor("100(42)"));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        100(42)
+            42
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    let paths = pattern.paths(&cbor("100(200(42))"));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        100(200(42))
+            200(42)
+                42
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    assert!(!pattern.matches(&cbor("100(43)")));
+    assert!(!pattern.matches(&cbor("[42]")));
+    assert!(!pattern.matches(&cbor(r#"100("42")"#)));
+
+    // Each layer the inner pattern matches at is reported, outermost first
+    let paths = Pattern::parse("untag(tagged)")
+        .unwrap()
+        .paths(&cbor("1(2(3))"));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        1(2(3))
+        1(2(3))
+            2(3)
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_untag_pattern_captures() {
+    // Captures run through the tag layers, whether the untag pattern is
+    // matched on its own or inside an array
+    for source in ["untag(@n(number))", "[untag(@n(number)), text]"] {
+        let pattern = Pattern::parse(source).unwrap();
+        let haystack = if source.starts_with('[') {
+            cbor(r#"[100(200(42)), "a"]"#)
+        } else {
+            cbor("100(200(42))")
+        };
+        let (paths, captures) = pattern.paths_with_captures(&haystack);
+        assert_path_chains!(&haystack, &paths, &captures);
+        assert_eq!(paths, pattern.paths(&haystack));
+        assert_eq!(captures["n"].len(), 1);
+        assert_eq!(captures["n"][0].last(), Some(&cbor("42")));
+        assert_eq!(
+            captures["n"][0].len(),
+            if source.starts_with('[') { 4 } else { 3 }
+        );
+    }
+
+    let pattern = Pattern::parse("untag(@n(number))").unwrap();
+    let haystack = cbor("100(200(42))");
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @n
+            100(200(42))
+                200(42)
+                    42
+        100(200(42))
+            200(42)
+                42
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_child_pattern_with_captures() {
+    let data = cbor(r#"{"a": 1, "b": [2]}"#);
+
+    let pattern = Pattern::parse("child(@n(number))").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @n
+            {"a": 1, "b": [2]}
+                1
+        {"a": 1, "b": [2]}
+            1
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // Compiled inside an array pattern, the child step returns to the
+    // enclosing array afterwards
+    let data = cbor(r#"[[1, "x"]]"#);
+    let pattern = Pattern::parse("[child(@n(number))]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @n
+            [[1, "x"]]
+                [1, "x"]
+                    1
+        [[1, "x"]]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_none_pattern_contrasted_with_not_under_search() {
+    let data = cbor(r#"[1, ["a", 2], ["b", ["c"]], "d"]"#);
+
+    // `!number` only looks at each node itself, so every container is
+    // reported, even those holding numbers
+    let paths = Pattern::parse("search(!number)").unwrap().paths(&data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [1, ["a", 2], ["b", ["c"]], "d"]
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["a", 2]
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["a", 2]
+                "a"
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["b", ["c"]]
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["b", ["c"]]
+                "b"
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["b", ["c"]]
+                ["c"]
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["b", ["c"]]
+                ["c"]
+                    "c"
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            "d"
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // `none(number)` rejects any node with a number anywhere beneath it
+    let paths = Pattern::parse("search(none(number))").unwrap().paths(&data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["a", 2]
+                "a"
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["b", ["c"]]
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["b", ["c"]]
+                "b"
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["b", ["c"]]
+                ["c"]
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            ["b", ["c"]]
+                ["c"]
+                    "c"
+        [1, ["a", 2], ["b", ["c"]], "d"]
+            "d"
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_none_pattern() {
+    let pattern = Pattern::parse("none(number)").unwrap();
+
+    // Map keys, map values, and tagged content are all part of the subtree
+    assert!(pattern.matches(&cbor(r#"{"a": "b"}"#)));
+    assert!(!pattern.matches(&cbor(r#"{1: "b"}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"a": [2]}"#)));
+    assert!(!pattern.matches(&cbor("100([3])")));
+
+    // Unlike `!`, which only looks at the node itself
+    let not = Pattern::parse("!number").unwrap();
+    assert!(not.matches(&cbor("[1]")));
+    assert!(!pattern.matches(&cbor("[1]")));
+
+    // Inside arrays, `none` applies to each element's subtree
+    let pattern = Pattern::parse("[(none(text))*]").unwrap();
+    assert!(pattern.matches(&cbor("[1, [2], {3: 4}]")));
+    assert!(!pattern.matches(&cbor(r#"[1, [2, "x"]]"#)));
+}
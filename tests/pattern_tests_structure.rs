@@ -3,7 +3,8 @@ mod common;
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
 use dcbor_pattern::{
-    ArrayPattern, MapPattern, Matcher, Pattern, TaggedPattern, format_paths,
+    ArrayPattern, FormatPathsOpts, MapPattern, Matcher, Pattern, TaggedPattern,
+    format_paths, format_paths_with_captures,
 };
 use indoc::indoc;
 
@@ -140,6 +141,74 @@ fn test_array_pattern_with_elements() {
     assert!(!pattern.matches(&empty));
 }
 
+/// Test that `[~ ...]` matches arrays with one element per pattern, in any
+/// order
+#[test]
+fn test_array_pattern_unordered() {
+    let pattern = parse("[~ number, text]");
+    assert_eq!(pattern.to_string(), "[~ number, text]");
+    assert_eq!(
+        pattern,
+        Pattern::Structure(dcbor_pattern::StructurePattern::Array(
+            ArrayPattern::with_elements_unordered(vec![
+                parse("number"),
+                parse("text"),
+            ])
+        ))
+    );
+    assert!(pattern.matches(&cbor(r#"[1, "a"]"#)));
+    assert!(pattern.matches(&cbor(r#"["a", 1]"#)));
+
+    // Exactly one element per pattern
+    assert!(!pattern.matches(&cbor(r#"["a", "b"]"#)));
+    assert!(!pattern.matches(&cbor(r#"[1, "a", 2]"#)));
+    assert!(!pattern.matches(&cbor("[1]")));
+    assert!(!pattern.matches(&cbor(r#"{"a": 1}"#)));
+
+    // An element matched by several patterns goes to the one that needs it
+    let pattern = parse("[~ number, 1, 1 | 2]");
+    assert!(pattern.matches(&cbor("[2, 1, 3]")));
+    assert!(pattern.matches(&cbor("[1, 3, 2]")));
+    assert!(!pattern.matches(&cbor("[1, 3, 3]")));
+
+    // Alternatives bind tighter than the commas, and `[~]` is the empty array
+    assert_eq!(parse("[~ 1 | 2, text]").to_string(), "[~ 1 | 2, text]");
+    assert!(parse("[~]").matches(&cbor("[]")));
+    assert!(!parse("[~]").matches(&cbor("[1]")));
+
+    // Repeats would match runs of elements, so they are rejected
+    for (source, span) in
+        [("[~ (number)*]", 3..12), ("[~ text, (number){2}]", 9..20)]
+    {
+        let error = Pattern::parse(source).unwrap_err();
+        assert!(
+            matches!(error, dcbor_pattern::Error::RepeatInUnorderedArray(_, _)),
+            "unexpected error {:?} for {}",
+            error,
+            source
+        );
+        assert_eq!(error.span(), Some(span));
+    }
+
+    // Built in code, the error has no source to point into
+    let error = ArrayPattern::try_with_elements_unordered(vec![
+        parse("text"),
+        parse("(number)*"),
+    ])
+    .unwrap_err();
+    assert_eq!(
+        error,
+        dcbor_pattern::Error::RepeatInUnorderedArray("(number)*".into(), None)
+    );
+    assert_eq!(error.span(), None);
+    assert_eq!(error.lexeme(), None);
+    assert_eq!(
+        error.to_string(),
+        "Repeat in unordered array pattern; each of its patterns must match \
+         a single element"
+    );
+}
+
 /// Test that `map` keyword matches any map
 #[test]
 fn test_map_pattern_any() {
@@ -203,6 +272,7 @@ fn test_tagged_pattern_with_tag_and_any() {
     #[rustfmt::skip]
     let expected = indoc! {r#"
         1234("content")
+            "content"
     "#}.trim();
     assert_actual_expected!(format_paths(&paths), expected);
 
@@ -215,6 +285,155 @@ fn test_tagged_pattern_with_tag_and_any() {
     assert!(!pattern.matches(&not_tagged));
 }
 
+/// Test tagged patterns that select a range or set of tag numbers
+#[test]
+fn test_tagged_pattern_tag_range_and_set() {
+    let range = parse("tagged(100...199, *)");
+    assert_eq!(range, Pattern::tagged_range(100..=199, Pattern::any()));
+    assert!(range.matches(&cbor("100(1)")));
+    assert!(range.matches(&cbor(r#"199("x")"#)));
+    assert!(!range.matches(&cbor("99(1)")));
+    assert!(!range.matches(&cbor("200(1)")));
+    assert!(!range.matches(&cbor("150")));
+
+    let set = parse("tagged(1 | 100 | 200, *)");
+    assert_eq!(set, Pattern::tagged_set([200, 1, 100], Pattern::any()));
+    assert!(set.matches(&cbor("1(0)")));
+    assert!(set.matches(&cbor("200(0)")));
+    assert!(!set.matches(&cbor("150(0)")));
+
+    // The content must match too
+    let range = parse("tagged(100...199, number)");
+    assert!(range.matches(&cbor("150(1)")));
+    assert!(!range.matches(&cbor(r#"150("one")"#)));
+    let set = parse("tagged(1 | 100, [text, *])");
+    assert!(set.matches(&cbor(r#"100(["a", 1])"#)));
+    assert!(!set.matches(&cbor("100([1, 1])")));
+
+    // Only the outer tag is selected; nested tags are part of the content
+    let nested = parse("tagged(100...199, tagged(1 | 2, text))");
+    assert!(nested.matches(&cbor(r#"150(2("a"))"#)));
+    assert!(!nested.matches(&cbor(r#"2(150("a"))"#)));
+    assert!(!nested.matches(&cbor(r#"150(3("a"))"#)));
+    let paths = parse("search(tagged(100...199, *))")
+        .paths(&cbor(r#"150([120(1), 99(2)])"#));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        150([120(1), 99(2)])
+        150([120(1), 99(2)])
+            [120(1), 99(2)]
+                120(1)
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Both forms display as they parse
+    for source in ["tagged(100...199, *)", "tagged(1 | 100 | 200, text)"] {
+        assert_eq!(parse(source).to_string(), source);
+    }
+    // A set of one number is a plain tag
+    assert_eq!(parse("tagged(7 | 7, *)"), parse("tagged(7, *)"));
+}
+
+/// Test captures in the content of tagged patterns selecting many tags
+#[test]
+fn test_tagged_pattern_tag_range_captures() {
+    let pattern =
+        parse("[tagged(100...199, @n(number)), tagged(1 | 2, @t(text))]");
+    let haystack = cbor(r#"[150(42), 2("x")]"#);
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_path_chains!(&haystack, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @n
+            [150(42), 2("x")]
+                150(42)
+                    42
+        @t
+            [150(42), 2("x")]
+                2("x")
+                    "x"
+        [150(42), 2("x")]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+
+    // A tag outside the selection captures nothing
+    let (paths, captures) =
+        pattern.paths_with_captures(&cbor(r#"[200(42), 2("x")]"#));
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+}
+
+/// Test tagged patterns that accept any tag and match the content
+#[test]
+fn test_tagged_pattern_any_tag_with_content() {
+    let pattern = parse("tagged(*, text)");
+    assert_eq!(
+        pattern,
+        Pattern::Structure(dcbor_pattern::StructurePattern::Tagged(
+            TaggedPattern::any_with_content(Pattern::any_text())
+        ))
+    );
+    assert_eq!(
+        pattern,
+        Pattern::any_tagged_with_content(Pattern::any_text())
+    );
+    assert!(pattern.matches(&cbor(r#"1("a")"#)));
+    assert!(pattern.matches(&cbor(r#"40000("a")"#)));
+    assert!(!pattern.matches(&cbor("1(1)")));
+    assert!(!pattern.matches(&cbor(r#""a""#)));
+    assert_eq!(pattern.to_string(), "tagged(*, text)");
+    assert_eq!(parse("tagged( * ,text)"), pattern);
+
+    // Nested tags match at each wrapper whose content matches, so the outer
+    // and inner wrappers each give a path
+    let haystack = cbor(r#"[100(200("a")), 300(1)]"#);
+    let paths = parse("search(tagged(*, untag(text)))").paths(&haystack);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [100(200("a")), 300(1)]
+            100(200("a"))
+        [100(200("a")), 300(1)]
+            100(200("a"))
+                200("a")
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Captures continue from the tagged value into its content
+    let pattern = parse("search(tagged(*, @t(text)))");
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_path_chains!(&haystack, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @t
+            [100(200("a")), 300(1)]
+                100(200("a"))
+                    200("a")
+                        "a"
+        [100(200("a")), 300(1)]
+            100(200("a"))
+                200("a")
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
 /// Test structure pattern display formatting
 #[test]
 fn test_structure_pattern_display() {
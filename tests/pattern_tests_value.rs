@@ -1,5 +1,7 @@
 mod common;
 
+use std::ops::Bound;
+
 use dcbor::prelude::*;
 use dcbor_parse::parse_dcbor_item;
 use dcbor_pattern::{Matcher, Pattern, format_paths};
@@ -295,6 +297,77 @@ fn test_number_pattern_range() {
     assert!(!range_pattern.matches(&text_cbor));
 }
 
+#[test]
+fn test_number_pattern_exclusive_ranges() {
+    // Whether 10 and 20 are in each form, with a `<` marking an exclusive
+    // bound
+    for (source, has_10, has_20) in [
+        ("10...20", true, true),
+        ("10...<20", true, false),
+        ("10<...20", false, true),
+        ("10<...<20", false, false),
+    ] {
+        let pattern = parse(source);
+        assert_eq!(pattern.to_string(), source);
+        for (haystack, expected) in [
+            ("10", has_10),
+            ("10.0", has_10),
+            ("20", has_20),
+            ("20.0", has_20),
+            ("10.000001", true),
+            ("15", true),
+            ("19.999999", true),
+            ("9.999999", false),
+            ("20.000001", false),
+            ("NaN", false),
+            (r#""15""#, false),
+        ] {
+            assert_eq!(
+                pattern.matches(&cbor(haystack)),
+                expected,
+                "{} on {}",
+                source,
+                haystack
+            );
+        }
+    }
+
+    // Float bounds are the same numbers as integer bounds
+    assert_eq!(parse("10.0<...<20.0"), parse("10<...<20"));
+    assert_eq!(parse("10.0<...<20.0").to_string(), "10<...<20");
+    assert!(parse("-0.5<...<0.5").matches(&cbor("0")));
+    assert!(!parse("-0.5<...<0.5").matches(&cbor("0.5")));
+}
+
+/// Ranges open at one end are the comparison patterns
+#[test]
+fn test_number_pattern_open_ranges() {
+    for (source, canonical) in [
+        ("10...", ">=10"),
+        ("10<...", ">10"),
+        ("...20", "<=20"),
+        ("...<20", "<20"),
+    ] {
+        let pattern = parse(source);
+        assert_eq!(pattern, parse(canonical), "{}", source);
+        assert_eq!(pattern.to_string(), canonical);
+    }
+    assert!(parse("[10..., ...<0]").matches(&cbor("[10, -1]")));
+    assert!(!parse("[10<..., ...<0]").matches(&cbor("[10, -1]")));
+
+    assert_eq!(Pattern::number_range_exclusive(10..20), parse("10...<20"));
+    assert_eq!(
+        Pattern::number_bounded((Bound::Excluded(10.0), Bound::Excluded(20.0))),
+        parse("10<...<20")
+    );
+    assert_eq!(Pattern::number_bounded(10.0..=20.0), parse("10...20"));
+    assert_eq!(Pattern::number_bounded(..20.0), parse("<20"));
+
+    for source in ["...", "...<", "10...<", "10<", "10<...text", "<...20"] {
+        assert!(Pattern::parse(source).is_err(), "{}", source);
+    }
+}
+
 #[test]
 fn test_number_pattern_comparisons() {
     let gt_pattern = parse(">10");
@@ -830,6 +903,48 @@ fn test_date_pattern_display() {
     assert_eq!(Pattern::date_regex(regex).to_string(), "date'/^2023-/'");
 }
 
+#[test]
+fn test_date_pattern_any_encoding() {
+    // The same instant as tag 1 with a timestamp and as tag 0 with text
+    let numeric = cbor("2023-12-25T12:00:00Z");
+    let text = CBOR::to_tagged_value(0, "2023-12-25T12:00:00Z");
+
+    let pattern = parse("date-any'2023-12-20...2023-12-30'");
+    assert!(pattern.matches(&numeric));
+    assert!(pattern.matches(&text));
+    let paths = pattern.paths(&text);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        0("2023-12-25T12:00:00Z")
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Out of range in either encoding
+    let pattern = parse("date-any'2024-01-01...2024-12-31'");
+    assert!(!pattern.matches(&numeric));
+    assert!(!pattern.matches(&text));
+
+    // `date` only matches the preferred encoding
+    let pattern = parse("date'2023-12-20...2023-12-30'");
+    assert!(pattern.matches(&numeric));
+    assert!(!pattern.matches(&text));
+
+    // An invalid ISO-8601 string under tag 0 is not a date
+    let pattern = parse("date-any");
+    for invalid in ["2023-13-45", "yesterday", ""] {
+        assert!(!pattern.matches(&CBOR::to_tagged_value(0, invalid)));
+    }
+    assert!(!pattern.matches(&CBOR::to_tagged_value(0, 1703505600)));
+
+    // Regexes see the date's normalized ISO-8601 string
+    let pattern = parse(r"[(date-any'/^(?<year>\d{4})-/')*]");
+    let haystack = CBOR::from(vec![numeric.clone(), text.clone()]);
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_eq!(paths.len(), 1);
+    assert_eq!(captures["year"].len(), 2);
+}
+
 #[test]
 fn test_null_pattern() {
     let pattern = parse("null");
@@ -1070,6 +1185,69 @@ fn test_known_value_pattern_regex() {
     assert!(!no_match_pattern.matches(&text_cbor));
 }
 
+#[test]
+fn test_known_value_pattern_set() {
+    let pattern = parse("known('isA' | 'date' | 'note')");
+
+    let is_a_cbor = cbor("'1'");
+    let paths = pattern.paths(&is_a_cbor);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        40000(1)
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    assert!(pattern.matches(&cbor("'16'")));
+    assert!(pattern.matches(&cbor("'4'")));
+    assert!(!pattern.matches(&cbor("'2'")));
+    assert!(!pattern.matches(&cbor("'12345'")));
+    assert!(!pattern.matches(&cbor("1"))); // Should NOT match plain integers
+
+    // Members may also be given by number, including unregistered ones
+    let pattern = parse("known('1' | '12345')");
+    assert!(pattern.matches(&cbor("'1'")));
+    assert!(pattern.matches(&cbor("'12345'")));
+    assert!(!pattern.matches(&cbor("'12346'")));
+
+    // Names must be registered, since the set is resolved when parsed
+    assert!(matches!(
+        Pattern::parse("known('isA' | 'noSuchValue')"),
+        Err(dcbor_pattern::Error::UnknownKnownValue(name, _)) if name == "noSuchValue"
+    ));
+}
+
+#[test]
+fn test_known_value_pattern_range() {
+    let pattern = parse("known(1...100)");
+
+    let paths = pattern.paths(&cbor("'16'"));
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        40000(16)
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Range bounds are inclusive, and ids need not be registered
+    assert!(pattern.matches(&cbor("'1'")));
+    assert!(pattern.matches(&cbor("'100'")));
+    assert!(pattern.matches(&cbor("'50'")));
+    assert!(!pattern.matches(&cbor("'0'")));
+    assert!(!pattern.matches(&cbor("'101'")));
+    assert!(!pattern.matches(&cbor("'12345'")));
+    assert!(!pattern.matches(&cbor("50"))); // Should NOT match plain integers
+    assert!(!pattern.matches(&cbor("100(50)")));
+
+    for source in ["known(10...1)", "known(-1...5)", "known(1.5...5)"] {
+        assert!(
+            Pattern::parse(source).is_err(),
+            "{} should not parse",
+            source
+        );
+    }
+}
+
 #[test]
 fn test_known_value_pattern_display() {
     let any_pattern = parse("known");
@@ -1087,6 +1265,15 @@ fn test_known_value_pattern_display() {
     let regex_pattern =
         Pattern::known_value_regex(regex::Regex::new(r"^is.*").unwrap());
     assert_eq!(regex_pattern.to_string(), "'/^is.*/'");
+
+    let set_pattern = Pattern::known_value_set([
+        known_values::IS_A,
+        known_values::KnownValue::new(12345),
+    ]);
+    assert_eq!(set_pattern.to_string(), "known('isA' | '12345')");
+
+    let range_pattern = Pattern::known_value_range(1..=100);
+    assert_eq!(range_pattern.to_string(), "known(1...100)");
 }
 
 #[test]
@@ -1250,3 +1437,33 @@ fn test_array_or_precedence_issue() {
     );
     assert!(!pattern.matches(&cbor_1_only), "[1] should NOT match");
 }
+
+#[test]
+fn test_value_set_patterns() {
+    let colors = parse(r#"text("red", "green", "blue")"#);
+    assert!(colors.matches(&cbor(r#""green""#)));
+    assert!(!colors.matches(&cbor(r#""Green""#)));
+    assert!(!colors.matches(&cbor("1")));
+
+    let numbers = parse("number(1, 2.5, -Infinity)");
+    assert!(numbers.matches(&cbor("1")));
+    assert!(numbers.matches(&cbor("1.0")));
+    assert!(numbers.matches(&cbor("2.5")));
+    assert!(numbers.matches(&CBOR::from(f64::NEG_INFINITY)));
+    assert!(!numbers.matches(&cbor("2")));
+    assert!(!numbers.matches(&cbor(r#""1""#)));
+
+    // Zero and negative zero are the same member
+    let zero = Pattern::number_in([-0.0, 7.0]);
+    assert!(zero.matches(&CBOR::from(0.0)));
+    assert!(zero.matches(&CBOR::from(-0.0)));
+
+    let bytes = parse("bstr(h'00', h'0102')");
+    assert!(bytes.matches(&cbor("h'0102'")));
+    assert!(!bytes.matches(&cbor("h'01'")));
+
+    // A set matches within larger patterns like any other value
+    let pattern = parse(r#"{"color": text("red", "green")}"#);
+    assert!(pattern.matches(&cbor(r#"{"color": "red"}"#)));
+    assert!(!pattern.matches(&cbor(r#"{"color": "blue"}"#)));
+}
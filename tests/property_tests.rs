@@ -0,0 +1,108 @@
+#![cfg(feature = "arbitrary")]
+
+//! The invariants the fuzz targets in `fuzz/` check, run over a fixed set of
+//! generated inputs so that they hold on every test run.
+
+use arbitrary::{Arbitrary, Unstructured};
+use dcbor_pattern::{ArbitraryCBOR, Matcher, Pattern, Program};
+
+/// Fragments of pattern syntax, for building text that gets further into the
+/// parser than random characters do.
+#[rustfmt::skip]
+const FRAGMENTS: &[&str] = &[
+    "[", "]", "{", "}", "(", ")", ",", ":", "|", "&", "!", "*", "+", "?",
+    "{2,}", "{0}", "*?", "++", " ",
+    "number", "text", "bool", "null", "map", "array", "tagged", "bstr",
+    "known", "date", "digest", "search", "child", "untag", "none", "at",
+    "entry", "cbor", "keys", "values",
+    "@x", "@y", "=x", "$x",
+    "1", "-2.5", "1...3", ">=0", "NaN", "\"a\"", "'isA'", "'/i/'", "/a+/",
+    "h'00'", "h'/0/'", "date'2023-01-01'", "1(2)", "#",
+];
+
+/// Returns `count` pseudo-random byte buffers, the same on every run.
+fn inputs(count: usize, len: usize) -> impl Iterator<Item = Vec<u8>> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    (0..count).map(move |_| {
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    })
+}
+
+fn soup(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| FRAGMENTS[*byte as usize % FRAGMENTS.len()])
+        .collect()
+}
+
+/// Parsing never panics, and a pattern that parses displays as text that
+/// parses to the same pattern.
+fn check_parse(source: &str) {
+    if let Ok(pattern) = Pattern::parse(source) {
+        let displayed = pattern.to_string();
+        assert_eq!(
+            Pattern::parse(&displayed).as_ref(),
+            Ok(&pattern),
+            "{} displayed as {}",
+            source,
+            displayed
+        );
+    }
+}
+
+/// Matching directly and through the VM agree on whether a value matches.
+fn check_matching(pattern: &Pattern, haystack: &ArbitraryCBOR) {
+    let haystack = &haystack.0;
+    let matches = pattern.matches(haystack);
+    let (paths, _) = pattern.paths_with_captures(haystack);
+    assert_eq!(
+        pattern.paths(haystack),
+        paths,
+        "{} on {}",
+        pattern,
+        haystack
+    );
+    assert_eq!(matches, !paths.is_empty(), "{} on {}", pattern, haystack);
+    let (vm_paths, _) = Program::compile(pattern).run(haystack);
+    assert_eq!(matches, !vm_paths.is_empty(), "{} on {}", pattern, haystack);
+}
+
+#[test]
+fn test_parse_fragments() {
+    for data in inputs(5000, 12) {
+        check_parse(&soup(&data));
+    }
+}
+
+#[test]
+fn test_parse_bytes() {
+    for data in inputs(5000, 16) {
+        check_parse(&String::from_utf8_lossy(&data));
+    }
+}
+
+#[test]
+fn test_arbitrary_pattern_round_trip() {
+    for data in inputs(2000, 256) {
+        let pattern = Pattern::arbitrary(&mut Unstructured::new(&data));
+        let pattern = pattern.unwrap();
+        assert_eq!(Pattern::parse(&pattern.to_string()), Ok(pattern.clone()));
+        check_parse(&pattern.to_string());
+    }
+}
+
+#[test]
+fn test_arbitrary_matching() {
+    for data in inputs(2000, 256) {
+        let mut u = Unstructured::new(&data);
+        let pattern = Pattern::arbitrary(&mut u).unwrap();
+        let haystack = ArbitraryCBOR::arbitrary(&mut u).unwrap();
+        check_matching(&pattern, &haystack);
+    }
+}
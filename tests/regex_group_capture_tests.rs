@@ -0,0 +1,146 @@
+mod common;
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    FormatPathsOpts, Matcher, Pattern, format_paths_with_captures,
+};
+use indoc::indoc;
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+#[test]
+fn test_regex_groups_captured_in_search() {
+    let pattern = parse(r"search(/^(?P<prefix>[a-z]+)-(?P<num>\d+)$/)");
+    let ids = cbor(r#"{"user": "abc-12", "group": ["xy-7", "none"]}"#);
+    assert_eq!(pattern.capture_names(), ["prefix", "num"]);
+
+    // Each matching id has its own group captures, ending in the part of
+    // the id each group matched
+    let (paths, captures) = pattern.paths_with_captures(&ids);
+    assert_path_chains!(&ids, &paths, &captures);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @num
+            {"user": "abc-12", "group": ["xy-7", "none"]}
+                "abc-12"
+                    "12"
+            {"user": "abc-12", "group": ["xy-7", "none"]}
+                ["xy-7", "none"]
+                    "xy-7"
+                        "7"
+        @prefix
+            {"user": "abc-12", "group": ["xy-7", "none"]}
+                "abc-12"
+                    "abc"
+            {"user": "abc-12", "group": ["xy-7", "none"]}
+                ["xy-7", "none"]
+                    "xy-7"
+                        "xy"
+        {"user": "abc-12", "group": ["xy-7", "none"]}
+            "abc-12"
+        {"user": "abc-12", "group": ["xy-7", "none"]}
+            ["xy-7", "none"]
+                "xy-7"
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_regex_groups_with_outer_captures() {
+    let text = cbor(r#""abc-12""#);
+
+    // An outer capture and the groups inside it are both reported
+    let pattern = parse(r"@id(/^(?P<prefix>[a-z]+)-(?P<num>\d+)$/)");
+    let (paths, captures) = pattern.paths_with_captures(&text);
+    assert_path_chains!(&text, &paths, &captures);
+    assert_eq!(captures["id"], vec![vec![text.clone()]]);
+    assert_eq!(
+        captures["prefix"],
+        vec![vec![text.clone(), cbor(r#""abc""#)]]
+    );
+    assert_eq!(captures["num"], vec![vec![text.clone(), cbor(r#""12""#)]]);
+
+    // A group that takes no part in the match captures nothing
+    let pattern = parse(r"/^(?P<prefix>[a-z]+)-(?P<num>\d+)?$/");
+    let (_, captures) = pattern.paths_with_captures(&cbor(r#""abc-""#));
+    assert!(captures.contains_key("prefix"));
+    assert!(!captures.contains_key("num"));
+
+    // Unnamed groups are not captures, and nor are groups when the pattern
+    // doesn't match
+    let pattern = parse(r"/^([a-z]+)-(?P<num>\d+)$/");
+    assert_eq!(pattern.capture_names(), ["num"]);
+    let (paths, captures) = pattern.paths_with_captures(&cbor(r#""abc""#));
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+
+    // Groups can be referred back to
+    let pattern = parse(r"[/^(?P<word>\w+)-/, =word]");
+    assert!(pattern.matches(&cbor(r#"["ab-", "ab"]"#)));
+    assert!(!pattern.matches(&cbor(r#"["ab-", "ab-"]"#)));
+}
+
+#[test]
+fn test_regex_groups_in_arrays_and_maps() {
+    let pattern = parse(r"[(/^(?P<initial>\w)/)*]");
+    let names = cbor(r#"["Alice", "Bob"]"#);
+    let (paths, captures) = pattern.paths_with_captures(&names);
+    assert_path_chains!(&names, &paths, &captures);
+    assert_eq!(
+        captures["initial"],
+        vec![
+            vec![names.clone(), cbor(r#""Alice""#), cbor(r#""A""#)],
+            vec![names.clone(), cbor(r#""Bob""#), cbor(r#""B""#)],
+        ]
+    );
+
+    let pattern = parse(r#"{"email": /@(?P<domain>.+)$/}"#);
+    let user = cbor(r#"{"email": "alice@example.com"}"#);
+    let (paths, captures) = pattern.paths_with_captures(&user);
+    assert_path_chains!(&user, &paths, &captures);
+    assert_eq!(
+        captures["domain"],
+        vec![vec![
+            user.clone(),
+            cbor(r#""alice@example.com""#),
+            cbor(r#""example.com""#)
+        ]]
+    );
+}
+
+#[test]
+fn test_regex_groups_in_byte_strings_and_dates() {
+    // Byte string groups capture the bytes they matched
+    let pattern = parse(r"h'/^\x00(?P<payload>.+)$/'");
+    let bytes = cbor("h'000102'");
+    let (paths, captures) = pattern.paths_with_captures(&bytes);
+    assert_path_chains!(&bytes, &paths, &captures);
+    assert_eq!(
+        captures["payload"],
+        vec![vec![bytes.clone(), cbor("h'0102'")]]
+    );
+
+    // Date groups capture part of the date's ISO-8601 string
+    let pattern = parse(r"date'/^(?P<year>\d{4})-(?P<month>\d{2})/'");
+    let date = cbor("1(1700000000)");
+    let (paths, captures) = pattern.paths_with_captures(&date);
+    assert_path_chains!(&date, &paths, &captures);
+    assert_eq!(
+        captures["year"],
+        vec![vec![date.clone(), cbor(r#""2023""#)]]
+    );
+    assert_eq!(captures["month"], vec![vec![date.clone(), cbor(r#""11""#)]]);
+}
@@ -31,6 +31,7 @@ fn test_search_capture_basic() {
 
     // Test with captures using the proper rubric
     let (capture_paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &capture_paths, &captures);
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -70,6 +71,8 @@ fn test_search_capture_multiple_matches() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -108,6 +111,8 @@ fn test_search_capture_nested_structure() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -138,6 +143,8 @@ fn test_search_capture_with_array_elements() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -185,6 +192,8 @@ fn test_search_capture_no_match() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // Should have no paths or captures when no match is found
     assert!(
         paths.is_empty(),
@@ -205,6 +214,8 @@ fn test_search_capture_complex_pattern() {
 
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_with_captures = indoc! {r#"
@@ -219,9 +230,11 @@ fn test_search_capture_complex_pattern() {
             {"users": [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]}
                 [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]
                     {"id": 1, "name": "Alice"}
+                        1
             {"users": [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]}
                 [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]
                     {"id": 2, "name": "Bob"}
+                        2
         {"users": [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]}
             [{"id": 1, "name": "Alice"}, {"id": 2, "name": "Bob"}]
                 {"id": 1, "name": "Alice"}
@@ -246,8 +259,10 @@ fn test_search_capture_api_consistency() {
 
     // Test that both direct and API methods give same results
     let (api_paths, api_captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &api_paths, &api_captures);
     let (direct_paths, direct_captures) =
         pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &direct_paths, &direct_captures);
 
     assert_eq!(
         api_paths, direct_paths,
@@ -258,3 +273,87 @@ fn test_search_capture_api_consistency() {
         "API and direct captures should be identical"
     );
 }
+
+#[test]
+fn test_search_capture_inside_repeat() {
+    let pattern = parse("search((@n(number))+)");
+    let cbor_data = cbor(r#"[1, [2, "a", 3]]"#);
+
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    assert_eq!(paths, pattern.paths(&cbor_data));
+
+    // One capture per matched number, each with its full path
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [1, [2, "a", 3]]
+            1
+        [1, [2, "a", 3]]
+            [2, "a", 3]
+                2
+        [1, [2, "a", 3]]
+            [2, "a", 3]
+                3
+    "#}.trim();
+    assert_actual_expected!(format_paths(&captures["n"]), expected);
+}
+
+#[test]
+fn test_search_capture_inside_repeat_reluctance() {
+    let cbor_data = cbor(r#"[1, "a"]"#);
+
+    // Greedy and lazy repeats find the same nodes, and every node a path
+    // reaches through an iteration has its capture
+    for source in ["search((@n(number))*)", "search((@n(number))*?)"] {
+        let pattern = parse(source);
+        let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+        assert_path_chains!(&cbor_data, &paths, &captures);
+        assert_eq!(paths, pattern.paths(&cbor_data), "{}", source);
+        assert_eq!(captures["n"], vec![vec![cbor_data.clone(), cbor("1")]]);
+    }
+}
+
+#[test]
+fn test_search_capture_in_sequence_keeps_every_container() {
+    let cbor_data = cbor(r#"{"a": [[1, [42, 100]]]}"#);
+
+    // The capture path passes through every container between the root
+    // and the captured element, including the array holding the match
+    for source in [
+        "search([(*)*, @item(42), (*)*])",
+        "search([@item(42), (*)*])",
+        "search([@rest((*)*), 100])",
+    ] {
+        let pattern = parse(source);
+        let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+        assert_path_chains!(&cbor_data, &paths, &captures);
+        assert_eq!(paths.len(), 1, "{}", source);
+    }
+
+    let pattern = parse("search([(*)*, @item(42), (*)*])");
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @item
+            {"a": [[1, [42, 100]]]}
+                [[1, [42, 100]]]
+                    [1, [42, 100]]
+                        [42, 100]
+                            42
+        {"a": [[1, [42, 100]]]}
+            [[1, [42, 100]]]
+                [1, [42, 100]]
+                    [42, 100]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            dcbor_pattern::FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
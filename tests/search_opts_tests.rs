@@ -0,0 +1,151 @@
+mod common;
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    FormatPathsOpts, Matcher, Pattern, SearchOpts, format_paths,
+    format_paths_with_captures,
+};
+use indoc::indoc;
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+#[test]
+fn test_search_max_depth() {
+    // Ten levels of nesting, with a number at every level
+    let cbor_data = cbor("[1, [2, [3, [4, [5, [6, [7, [8, [9, [10]]]]]]]]]]");
+
+    let unlimited = parse("search(number)");
+    assert_eq!(unlimited.paths(&cbor_data).len(), 10);
+
+    let pattern = Pattern::search_with(
+        Pattern::any_number(),
+        SearchOpts { max_depth: Some(2), ..Default::default() },
+    );
+    assert_eq!(pattern, parse("search(number, depth: 2)"));
+
+    let paths = pattern.paths(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [1, [2, [3, [4, [5, [6, [7, [8, [9, [10]]]]]]]]]]
+            1
+        [1, [2, [3, [4, [5, [6, [7, [8, [9, [10]]]]]]]]]]
+            [2, [3, [4, [5, [6, [7, [8, [9, [10]]]]]]]]]
+                2
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Depth 0 only tests the root
+    let pattern = parse("search(array, depth: 0)");
+    assert_eq!(pattern.paths(&cbor_data).len(), 1);
+    assert!(
+        parse("search(number, depth: 0)")
+            .paths(&cbor_data)
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_search_without_map_keys() {
+    let cbor_data = cbor(r#"{"a": "b", 1: {"c": 2}}"#);
+
+    let pattern = parse("search(text)");
+    let paths = pattern.paths(&cbor_data);
+    assert_eq!(paths.len(), 3);
+
+    let pattern = parse("search(text, keys: false)");
+    let paths = pattern.paths(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {1: {"c": 2}, "a": "b"}
+            "b"
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+
+    // Values below a skipped key are still visited
+    let pattern = parse("search(number, keys: false)");
+    let paths = pattern.paths(&cbor_data);
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        {1: {"c": 2}, "a": "b"}
+            {"c": 2}
+                2
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_search_without_tagged_content() {
+    let cbor_data = cbor(r#"[42, 100([42])]"#);
+
+    let pattern = parse("search(42)");
+    assert_eq!(pattern.paths(&cbor_data).len(), 2);
+
+    let pattern = parse("search(42, tagged: false)");
+    let paths = pattern.paths(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        [42, 100([42])]
+            42
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}
+
+#[test]
+fn test_search_opts_with_captures() {
+    let cbor_data = cbor(r#"{"n": 1, "m": {"n": 2}}"#);
+
+    let pattern = parse("search(@num(number), depth: 1)");
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @num
+            {"m": {"n": 2}, "n": 1}
+                1
+        {"m": {"n": 2}, "n": 1}
+            1
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_search_opts_inside_array_captures() {
+    // Captures inside an array pattern are matched by the VM, which must
+    // honor the same options as direct matching
+    let cbor_data = cbor("[[1, [2]]]");
+
+    let pattern = parse("[search(@num(number), depth: 1)]");
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @num
+            [[1, [2]]]
+                [1, [2]]
+                    1
+        [[1, [2]]]
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
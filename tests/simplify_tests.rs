@@ -0,0 +1,243 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Matcher, Pattern};
+
+/// Patterns with redundant structure, and some without, to check that
+/// simplification never changes what a pattern matches.
+const PATTERNS: &[&str] = &[
+    "42",
+    "(42)",
+    "(((42)))",
+    "((42))*",
+    "42 | 42",
+    "(42 | 42) | text",
+    "number | (text | (number | bool))",
+    "(number & (>= 0)) & (<= 100)",
+    "number & (1 | 2 | 3)",
+    "((1 | 2)) & number",
+    "!((42))",
+    "!(1 | 1)",
+    "search(42)",
+    "search(search(42))",
+    "search(search(search(42)))",
+    "!search(search(42))",
+    "number & search(search(42))",
+    "search(search(@x(42)))",
+    "tagged(100, search(search(text)))",
+    "search((text))",
+    "[(42)]",
+    "[(number), (text)]",
+    "[((number)*), text]",
+    "[(number)*, ((text)), (*)*]",
+    "[(1 | 2), (1 | 2)*]",
+    "[(1 | 1), text]",
+    "[@x((number)), (text)]",
+    "@x((42 | 42))",
+    "@x(((number)))",
+    "{(text): ((number))}",
+    "{text: (1 | 1)}",
+    "tagged(100, ((number)))",
+    "tagged(100, [(number)*])",
+    "search([(42), (*)*])",
+    "((number){2,3})",
+    "[((number){2,3}), text]",
+    "child((number))",
+    "none((42))",
+];
+
+/// Haystacks covering each kind of value and some nesting.
+const HAYSTACKS: &[&str] = &[
+    "42",
+    "1",
+    "-5",
+    "150",
+    r#""hello""#,
+    "true",
+    "null",
+    "[]",
+    "[42]",
+    r#"[1, "a"]"#,
+    r#"[1, 2, "a"]"#,
+    r#"[1, 2, 3, "a"]"#,
+    r#"[42, "x", true]"#,
+    r#"[[42], ["a"]]"#,
+    r#"{"a": 1}"#,
+    r#"{"a": [42], "b": "c"}"#,
+    "100(42)",
+    r#"100(["a"])"#,
+    "100([1, 2])",
+    "100(200(42))",
+];
+
+fn haystacks() -> Vec<CBOR> {
+    HAYSTACKS
+        .iter()
+        .map(|source| parse_dcbor_item(source).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_simplify_preserves_paths() {
+    let haystacks = haystacks();
+    for source in PATTERNS {
+        let pattern = Pattern::parse(source).unwrap();
+        let simplified = pattern.simplify();
+        for haystack in &haystacks {
+            assert_eq!(
+                simplified.paths(haystack),
+                pattern.paths(haystack),
+                "{} simplified to {} on {}",
+                source,
+                simplified,
+                haystack
+            );
+            assert_eq!(
+                simplified.paths_with_captures(haystack),
+                pattern.paths_with_captures(haystack),
+                "{} simplified to {} on {}",
+                source,
+                simplified,
+                haystack
+            );
+        }
+    }
+}
+
+#[test]
+fn test_simplify_is_idempotent_and_round_trips() {
+    for source in PATTERNS {
+        let simplified = Pattern::parse(source).unwrap().simplify();
+        assert_eq!(simplified.simplify(), simplified, "{}", source);
+
+        // The simplified display parses back to the same pattern
+        let reparsed = Pattern::parse(&simplified.to_string()).unwrap();
+        assert_eq!(reparsed.simplify(), simplified, "{}", source);
+    }
+}
+
+fn simplified(source: &str) -> String {
+    Pattern::parse(source).unwrap().simplify().to_string()
+}
+
+#[test]
+fn test_simplify_removes_groups() {
+    // A repeat matching exactly once, `(p){1}`, only groups its pattern
+    assert_eq!(simplified("(((42)){1}){1}"), "42");
+    assert_eq!(simplified("((42){1})*"), "(42)*");
+    assert_eq!(simplified("[(number){1}, (text){1}]"), "[number, text]");
+    assert_eq!(simplified("!((42){1})"), "!42");
+    assert_eq!(
+        simplified("tagged(100, (number){1})"),
+        "tagged(100, number)"
+    );
+
+    // Groups inside a capture are removed, but groups around one are kept
+    assert_eq!(simplified("@x((number){1})"), "@x(number)");
+    assert_eq!(simplified("(@x(number)){1}"), "(@x(number)){1}");
+    // Inside an array a captured group captures its elements as an array
+    assert_eq!(simplified("[@x((number){1})]"), "[@x((number){1})]");
+
+    // Removing a group keeps precedence
+    assert_eq!(simplified("(1 | 2){1} & number"), "(1 | 2) & number");
+
+    // A group makes a repeat match a single array element
+    assert_eq!(
+        simplified("[((number)*){1}, text]"),
+        "[((number)*){1}, text]"
+    );
+    assert_eq!(simplified("((number)*){1}"), "(number)*");
+}
+
+#[test]
+fn test_simplify_parentheses_only_group() {
+    // Parentheses leave no group behind to remove
+    assert_eq!(simplified("(((42)))"), "42");
+    assert_eq!(simplified("[(number), (text)]"), "[number, text]");
+    assert_eq!(simplified("((1 | 2)) & number"), "(1 | 2) & number");
+    assert_eq!(simplified("[((number)*), text]"), "[(number)*, text]");
+}
+
+#[test]
+fn test_simplify_flattens_and_or() {
+    assert_eq!(
+        simplified("number | (text | (number | bool))"),
+        "number | text | bool"
+    );
+    assert_eq!(
+        simplified("(number & (>= 0)) & (<= 100)"),
+        "number & >=0 & <=100"
+    );
+
+    let nested = Pattern::and(vec![
+        Pattern::any_number(),
+        Pattern::and(vec![Pattern::number(1), Pattern::any()]),
+    ]);
+    assert_eq!(
+        nested.simplify(),
+        Pattern::and(vec![
+            Pattern::any_number(),
+            Pattern::number(1),
+            Pattern::any()
+        ])
+    );
+}
+
+#[test]
+fn test_simplify_collapses_duplicate_alternatives() {
+    assert_eq!(simplified("42 | 42"), "42");
+    assert_eq!(simplified("(42 | 42) | text"), "42 | text");
+    assert_eq!(simplified("[(1 | 1), text]"), "[1, text]");
+    assert_eq!(simplified("@x((42 | 42))"), "@x(42)");
+}
+
+#[test]
+fn test_simplify_empty_and_or() {
+    assert_eq!(Pattern::and(vec![]).simplify(), Pattern::any());
+    assert_eq!(
+        Pattern::or(vec![]).simplify(),
+        Pattern::not_matching(Pattern::any())
+    );
+
+    let haystack = CBOR::from(1);
+    assert!(Pattern::and(vec![]).matches(&haystack));
+    assert!(!Pattern::or(vec![]).matches(&haystack));
+}
+
+#[test]
+fn test_simplify_single_operand_and() {
+    use dcbor_pattern::dsl::*;
+
+    // `&` of one pattern is that pattern when its paths end where it matched
+    let captured = capture("x", number());
+    assert_eq!(Pattern::and(vec![captured.clone()]).simplify(), captured);
+
+    // `&` matches in place, so it is kept around a pattern whose paths lead
+    // into the value, with or without a capture around it
+    let haystack = parse_dcbor_item(r#"{"k": 1}"#).unwrap();
+    for operand in [
+        map_value(text_value("k"), number()),
+        capture("x", map_value(text_value("k"), number())),
+    ] {
+        let and = Pattern::and(vec![operand.clone()]);
+        assert_eq!(and.simplify(), and);
+        assert_eq!(and.paths(&haystack), vec![vec![haystack.clone()]]);
+        assert_eq!(operand.paths(&haystack)[0].len(), 2);
+    }
+}
+
+#[test]
+fn test_simplify_merges_nested_searches() {
+    assert_eq!(simplified("!search(search(42))"), "!search(42)");
+    assert_eq!(
+        simplified("number & search(search(42))"),
+        "number & search(42)"
+    );
+    assert_eq!(
+        simplified("search(search(search(42)))"),
+        "search(search(42))"
+    );
+
+    // At the top level the outer search reports the ancestors of each match,
+    // so the nested searches are kept
+    assert_eq!(simplified("search(search(42))"), "search(search(42))");
+}
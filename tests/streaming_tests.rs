@@ -0,0 +1,282 @@
+#![cfg(feature = "streaming")]
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+};
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Error, Matcher, Pattern, StreamOpts};
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+fn record(i: u64) -> CBOR {
+    let mut map = Map::new();
+    map.insert("id", i);
+    map.insert("name", format!("item-{}", i));
+    map.insert("scores", vec![i, i * 2 + 1]);
+    CBOR::from(map)
+}
+
+/// Writes the encoding of an array of `count` records, one record at a
+/// time.
+fn write_records(w: &mut impl Write, count: u64) -> io::Result<()> {
+    // An array head is an unsigned integer head with the major type changed
+    let mut head = CBOR::from(count).to_cbor_data();
+    head[0] |= 0x80;
+    w.write_all(&head)?;
+    for i in 0..count {
+        w.write_all(&record(i).to_cbor_data())?;
+    }
+    Ok(())
+}
+
+/// Returns what the stream should report for each match of `pattern` in
+/// `haystack`: the matched value, or `None` if it holds an array or map.
+fn in_memory(pattern: &Pattern, haystack: &CBOR) -> Vec<Option<CBOR>> {
+    fn holds_container(cbor: &CBOR) -> bool {
+        match cbor.as_case() {
+            CBORCase::Array(_) | CBORCase::Map(_) => true,
+            CBORCase::Tagged(_, content) => holds_container(content),
+            _ => false,
+        }
+    }
+    pattern
+        .paths(haystack)
+        .into_iter()
+        .map(|path| {
+            let last = path.last().unwrap();
+            (!holds_container(last)).then(|| last.clone())
+        })
+        .collect()
+}
+
+fn streamed(pattern: &Pattern, data: &[u8]) -> Vec<Option<CBOR>> {
+    pattern
+        .match_from_reader(data, StreamOpts::default())
+        .unwrap()
+        .matches
+        .into_iter()
+        .map(|m| m.value)
+        .collect()
+}
+
+#[test]
+fn test_stream_matches_in_memory() {
+    let mut data = Vec::new();
+    write_records(&mut data, 40).unwrap();
+    let haystack = CBOR::try_from_data(&data).unwrap();
+    for source in [
+        "search(number)",
+        "search(>=30 & <35)",
+        "search(text)",
+        "search(/^item-1/)",
+        "search(cbor'\"item-7\"')",
+        "search(array)",
+        "search([{2}] | map)",
+        "search(!number)",
+        "search(*)",
+        "search(number, depth: 2)",
+        "search(text, keys: false)",
+        "array",
+        "[{40}]",
+        "map",
+    ] {
+        let pattern = parse(source);
+        assert_eq!(
+            streamed(&pattern, &data),
+            in_memory(&pattern, &haystack),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn test_stream_tagged_values() {
+    let haystack = cbor(
+        r#"{"a": [1, 1(1700000000)], "b": 100(200(["x"])), "c": 40000(1)}"#,
+    );
+    let data = haystack.to_cbor_data();
+    for source in [
+        "search(tagged)",
+        "search(tagged(100, *))",
+        "search(tagged(100, tagged(200, [{1}])))",
+        "search(date)",
+        "search(cbor'1(1700000000)')",
+        "search(known)",
+        "search(text)",
+        "search(text, tagged: false)",
+        "search(number, tagged: false)",
+        "tagged",
+        "map",
+    ] {
+        let pattern = parse(source);
+        assert_eq!(
+            streamed(&pattern, &data),
+            in_memory(&pattern, &haystack),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn test_stream_reports_every_position() {
+    // Equal values at different positions are each a match, where paths
+    // merges equal paths
+    let data = cbor("[1, 1, [1]]").to_cbor_data();
+    let summary = parse("search(1)")
+        .match_from_reader(data.as_slice(), StreamOpts::default())
+        .unwrap();
+    let positions: Vec<_> = summary
+        .matches
+        .iter()
+        .map(|m| (m.offset, m.depth))
+        .collect();
+    assert_eq!(positions, [(1, 1), (2, 1), (4, 2)]);
+    assert!(summary.is_match());
+    assert_eq!(summary.items, 5);
+}
+
+#[test]
+fn test_stream_large_file() {
+    const COUNT: u64 = 100_000;
+    let path = std::env::temp_dir()
+        .join(format!("dcbor-pattern-stream-{}.cbor", std::process::id()));
+    {
+        let mut file = BufWriter::new(File::create(&path).unwrap());
+        write_records(&mut file, COUNT).unwrap();
+        file.flush().unwrap();
+    }
+    let stream = |source: &str| {
+        parse(source)
+            .match_from_reader(
+                File::open(&path).unwrap(),
+                StreamOpts::default(),
+            )
+            .unwrap()
+    };
+
+    // Each record is a map with three entries, one of them an array of two
+    let summary = stream("search(/^item-1999/)");
+    assert_eq!(summary.items, 1 + 9 * COUNT);
+    let names: Vec<_> = summary
+        .matches
+        .iter()
+        .map(|m| m.value.clone().unwrap())
+        .collect();
+    assert_eq!(names.len(), 1 + 10);
+    assert_eq!(names[0], CBOR::from("item-1999"));
+    assert_eq!(names[10], CBOR::from("item-19999"));
+
+    let summary = stream("search([{2}])");
+    assert_eq!(summary.matches.len() as u64, COUNT);
+    assert!(
+        summary
+            .matches
+            .iter()
+            .all(|m| m.depth == 2 && m.value.is_none())
+    );
+
+    assert!(stream(&format!("[{{{}}}]", COUNT)).is_match());
+    assert!(!stream("search(-1)").is_match());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// A reader that fails the test if it is read from.
+struct Unread;
+
+impl Read for Unread {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        panic!("the stream was read")
+    }
+}
+
+#[test]
+fn test_stream_unsupported_patterns() {
+    for (source, unsupported) in [
+        ("search(@n(number))", "@n"),
+        ("[number]", "[number]"),
+        (r#"search({"id": number})"#, r#"{"id": number}"#),
+        ("search(search(text))", "search(text)"),
+        ("search(number | [text])", "[text]"),
+        ("search(tagged(100, [text]))", "[text]"),
+        ("cbor'[1]'", "cbor'[1]'"),
+        ("search(encoded-digest'0011')", "encoded-digest'0011'"),
+        ("search(untag(number))", "untag(number)"),
+    ] {
+        assert_eq!(
+            parse(source).match_from_reader(Unread, StreamOpts::default()),
+            Err(Error::UnsupportedForStreaming(unsupported.to_string())),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn test_stream_invalid_data() {
+    let pattern = parse("search(*)");
+    let stream = |data: &[u8], opts: StreamOpts| {
+        pattern
+            .match_from_reader(data, opts)
+            .map(|summary| summary.items)
+    };
+    let opts = StreamOpts::default();
+    assert_eq!(stream(&cbor(r#"{"a": [1]}"#).to_cbor_data(), opts), Ok(4));
+
+    for data in [
+        // Empty and truncated
+        &[][..],
+        &[0x82, 0x01],
+        &[0x62, 0x61],
+        // 1 in two bytes, as an argument and as an array length
+        &[0x18, 0x01],
+        &[0x98, 0x01, 0x01],
+        // An indefinite-length array
+        &[0x9f, 0x01, 0xff],
+        // Keys out of order, and duplicated: {"b": 1, "a": 2}, {"a": 1, "a":
+        // 2}
+        &[0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02],
+        &[0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02],
+        // The simple value undefined, which dCBOR excludes
+        &[0xf7],
+        // Extra data after the item
+        &[0x01, 0x02],
+    ] {
+        assert!(
+            matches!(stream(data, opts), Err(Error::InvalidCborData(_))),
+            "{}",
+            hex::encode(data)
+        );
+    }
+
+    // Keys are compared by their whole encoding, including nested keys
+    let nested = cbor(r#"{[1]: {"a": 1, "b": 2}, [2]: 3}"#).to_cbor_data();
+    assert_eq!(stream(&nested, opts), Ok(11));
+
+    let deep = cbor("[[[[1]]]]").to_cbor_data();
+    let shallow = StreamOpts { max_nesting_depth: 3, ..opts };
+    assert!(matches!(
+        stream(&deep, shallow),
+        Err(Error::InvalidCborData(_))
+    ));
+    assert_eq!(
+        stream(&deep, StreamOpts { max_nesting_depth: 4, ..opts }),
+        Ok(5)
+    );
+
+    let long = CBOR::from("abcdef").to_cbor_data();
+    let short = StreamOpts { max_value_len: 5, ..opts };
+    assert!(matches!(
+        stream(&long, short),
+        Err(Error::InvalidCborData(_))
+    ));
+}
@@ -0,0 +1,73 @@
+use dcbor_pattern::{Pattern, syntax::grammar_summary};
+
+#[test]
+fn test_examples_parse_to_built_patterns() {
+    for rule in grammar_summary() {
+        let parsed = Pattern::parse(rule.example()).unwrap_or_else(|error| {
+            panic!("{}: {:?} fails: {}", rule.name(), rule.example(), error)
+        });
+        assert_eq!(parsed, rule.build(), "{}: {}", rule.name(), rule.example());
+    }
+}
+
+#[test]
+fn test_built_patterns_round_trip_through_display() {
+    for rule in grammar_summary() {
+        let built = rule.build();
+        let displayed = built.to_string();
+        let reparsed = Pattern::parse(&displayed).unwrap_or_else(|error| {
+            panic!("{}: {:?} fails: {}", rule.name(), displayed, error)
+        });
+        assert_eq!(reparsed, built, "{}: {}", rule.name(), displayed);
+    }
+}
+
+#[test]
+fn test_examples_are_distinct() {
+    let rules = grammar_summary();
+    for (i, rule) in rules.iter().enumerate() {
+        assert!(
+            rules[..i]
+                .iter()
+                .all(|earlier| earlier.example() != rule.example()),
+            "{} is listed twice",
+            rule.example()
+        );
+    }
+}
+
+#[test]
+fn test_keywords_in_either_case() {
+    for upper in [
+        "BOOL",
+        "TRUE | FALSE",
+        "BSTR({2,4})",
+        "DATE",
+        "DATE'2023-12-25'",
+        "DATE-ANY'2023-12-25...'",
+        "KNOWN",
+        "NULL",
+        "NUMBER",
+        "TEXT(^\"AB\")",
+        "DIGEST'BA7816'",
+        "ENCODED-DIGEST'BA7816'",
+        "CBOR'[1, 2]'",
+        "TAGGED(100, NUMBER)",
+        "ARRAY | MAP",
+        "[NUMBER, (TEXT)*]",
+        "{TEXT: NUMBER}",
+        "SEARCH(TEXT)",
+        "CHILD(NUMBER)",
+        "UNTAG(NUMBER)",
+        "ENTRY(TEXT: NUMBER)",
+        "NONE(TEXT)",
+    ] {
+        // Only the keywords change case, not the text literal
+        let lower = upper.to_lowercase().replace("\"ab\"", "\"AB\"");
+        let pattern = Pattern::parse(upper).unwrap();
+        assert_eq!(pattern, Pattern::parse(&lower).unwrap(), "{}", upper);
+
+        // Display always uses lowercase keywords
+        assert_eq!(pattern.to_string(), lower, "{}", upper);
+    }
+}
@@ -0,0 +1,138 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Matcher, Pattern, SearchOpts};
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Patterns of every kind, and searches for them.
+const PATTERNS: &[&str] = &[
+    "*",
+    "number",
+    "42",
+    "text",
+    "\"Alice\"",
+    "/^A/",
+    "bstr",
+    "null",
+    "bool",
+    "array",
+    "map",
+    "tagged",
+    "[*]",
+    "[(*)*]",
+    "[number, (*)*]",
+    "[(*)*, @n(number), (*)*]",
+    "[~ number, text]",
+    "{text: number}",
+    "{\"name\": @name(text)}",
+    "{(text: *){2,}}",
+    "{keys: text}",
+    "{\"people\"}: [(*)*]",
+    "{\"people\"}: search({\"name\"}: text)",
+    "tagged(1, *)",
+    "untag(number)",
+    "child(number)",
+    "entry(text: number)",
+    "number | text",
+    "number & 1...50",
+    "!number",
+    "@v(*)",
+    "[@a(*), =a]",
+    "search(*)",
+    "search(number)",
+    "search(text | number)",
+    "search(array)",
+    "search(map)",
+    "search({(*): (*)})",
+    "search({\"name\": @n(text)})",
+    "search([(*)*, 3, (*)*])",
+    "search(search(number))",
+    "search(tagged)",
+    "search(untag(text))",
+    "search(1) | search(2)",
+];
+
+/// Haystacks of every shape, including ones with equal siblings.
+fn haystacks() -> Vec<CBOR> {
+    let people = std::fs::read_to_string(format!(
+        "{}/tests/fixtures/people.diag",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    [
+        "42",
+        r#""Alice""#,
+        "h'0102'",
+        "null",
+        "[1, 2, 3]",
+        "[1, [1, 2], [1, 2], 3]",
+        r#"[1, "a", [2, "b", [3, "c"]]]"#,
+        r#"{"a": 1, "b": "a", 1: [1, 1]}"#,
+        r#"{"a": {"x": 1}, "b": [1, {"y": 2, "z": 3}], "c": 3}"#,
+        r#"1("Alice")"#,
+        r#"[1(2), 1(2), {3: 1([4, "x"])}]"#,
+        &people,
+    ]
+    .iter()
+    .map(|s| cbor(s))
+    .collect()
+}
+
+/// Returns the last node of each of the pattern's paths.
+fn last_nodes(pattern: &Pattern, haystack: &CBOR) -> Vec<CBOR> {
+    pattern
+        .paths(haystack)
+        .iter()
+        .map(|path| path.last().unwrap().clone())
+        .collect()
+}
+
+#[test]
+fn test_terminal_matches_equal_last_nodes_of_paths() {
+    let haystacks = haystacks();
+    for source in PATTERNS {
+        let pattern = Pattern::parse(source).unwrap();
+        for haystack in &haystacks {
+            assert_eq!(
+                pattern.terminal_matches(haystack),
+                last_nodes(&pattern, haystack),
+                "{} against {}",
+                source,
+                haystack.diagnostic_flat()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_terminal_matches_with_search_opts() {
+    let haystack = cbor(r#"{"a": [1, {"b": 2}], 3: 1(4)}"#);
+    for opts in [
+        SearchOpts::default(),
+        SearchOpts { max_depth: Some(1), ..SearchOpts::default() },
+        SearchOpts { visit_map_keys: false, ..SearchOpts::default() },
+        SearchOpts {
+            visit_tagged_content: false,
+            ..SearchOpts::default()
+        },
+    ] {
+        let pattern = Pattern::search_with(Pattern::any_number(), opts);
+        assert_eq!(
+            pattern.terminal_matches(&haystack),
+            last_nodes(&pattern, &haystack),
+            "{:?}",
+            opts
+        );
+    }
+}
+
+#[test]
+fn test_terminal_matches_keeps_equal_nodes() {
+    // Equal nodes at different places are each returned, while a node
+    // equal to an earlier sibling shares its path and is returned once
+    let haystack = cbor("[1, [1], 1]");
+    let pattern = Pattern::parse("search(1)").unwrap();
+    assert_eq!(pattern.terminal_matches(&haystack), [cbor("1"), cbor("1")]);
+    assert!(pattern.terminal_matches(&cbor("[]")).is_empty());
+}
@@ -23,6 +23,7 @@ fn test_simple_nested_tagged_array() {
     #[rustfmt::skip]
     let expected = indoc! {r#"
         100(["target"])
+            ["target"]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths), expected);
 
@@ -63,6 +64,7 @@ fn test_complex_nested_tagged_array_with_repeat() {
     #[rustfmt::skip]
     let expected1 = indoc! {r#"
         100(["target"])
+            ["target"]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths1), expected1);
 
@@ -77,6 +79,7 @@ fn test_complex_nested_tagged_array_with_repeat() {
     #[rustfmt::skip]
     let expected2 = indoc! {r#"
         100([1, "target"])
+            [1, "target"]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths2), expected2);
 
@@ -91,6 +94,7 @@ fn test_complex_nested_tagged_array_with_repeat() {
     #[rustfmt::skip]
     let expected3 = indoc! {r#"
         100(["target", 2])
+            ["target", 2]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths3), expected3);
 
@@ -105,6 +109,7 @@ fn test_complex_nested_tagged_array_with_repeat() {
     #[rustfmt::skip]
     let expected4 = indoc! {r#"
         100([1, "target", 2])
+            [1, "target", 2]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths4), expected4);
 
@@ -262,6 +267,7 @@ fn test_deeply_nested_structures() {
     #[rustfmt::skip]
     let expected1 = indoc! {r#"
         200({"data": [{"value": 42}]})
+            {"data": [{"value": 42}]}
     "#}.trim();
     assert_actual_expected!(format_paths(&paths1), expected1);
 
@@ -297,6 +303,7 @@ fn test_deeply_nested_structures_with_multiple_maps() {
     #[rustfmt::skip]
     let expected0 = indoc! {r#"
         200({"data": []})
+            {"data": []}
     "#}.trim();
     assert_actual_expected!(format_paths(&paths0), expected0);
 
@@ -308,6 +315,7 @@ fn test_deeply_nested_structures_with_multiple_maps() {
     #[rustfmt::skip]
     let expected1 = indoc! {r#"
         200({"data": [{"value": 42}]})
+            {"data": [{"value": 42}]}
     "#}.trim();
     assert_actual_expected!(format_paths(&paths1), expected1);
 
@@ -321,6 +329,7 @@ fn test_deeply_nested_structures_with_multiple_maps() {
     #[rustfmt::skip]
     let expected2 = indoc! {r#"
         200({"data": [{"value": 1}, {"value": 2}]})
+            {"data": [{"value": 1}, {"value": 2}]}
     "#}.trim();
     assert_actual_expected!(format_paths(&paths2), expected2);
 
@@ -353,6 +362,7 @@ fn test_multiple_levels_of_nesting_with_any() {
     #[rustfmt::skip]
     let expected1 = indoc! {r#"
         300([{"key": "value"}])
+            [{"key": "value"}]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths1), expected1);
 
@@ -367,6 +377,7 @@ fn test_multiple_levels_of_nesting_with_any() {
     #[rustfmt::skip]
     let expected2 = indoc! {r#"
         300([{42: true}, "extra", 123])
+            [{42: true}, "extra", 123]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths2), expected2);
 
@@ -398,6 +409,7 @@ fn test_extreme_nesting_depth() {
     #[rustfmt::skip]
     let expected = indoc! {r#"
         400({"level1": {"level2": {"level3": [42]}}})
+            {"level1": {"level2": {"level3": [42]}}}
     "#}.trim();
     assert_actual_expected!(format_paths(&paths), expected);
 
@@ -436,6 +448,7 @@ fn test_complex_combined_patterns() {
     #[rustfmt::skip]
     let expected1 = indoc! {r#"
         500([{"type": "user"}, {"id": 123}])
+            [{"type": "user"}, {"id": 123}]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths1), expected1);
 
@@ -453,6 +466,7 @@ fn test_complex_combined_patterns() {
     #[rustfmt::skip]
     let expected2 = indoc! {r#"
         500([{"type": "user"}, {"id": 123}, {"name": "John"}])
+            [{"type": "user"}, {"id": 123}, {"name": "John"}]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths2), expected2);
 
@@ -467,6 +481,7 @@ fn test_complex_combined_patterns() {
     #[rustfmt::skip]
     let expected3 = indoc! {r#"
         500([{"type": "user"}, {"id": 123}, {"email": "john@example.com"}])
+            [{"type": "user"}, {"id": 123}, {"email": "john@example.com"}]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths3), expected3);
 
@@ -481,6 +496,7 @@ fn test_complex_combined_patterns() {
     #[rustfmt::skip]
     let expected4 = indoc! {r#"
         500([{"type": "user"}, {"id": 123}, {"name": "John"}, {"email": "john@example.com"}])
+            [{"type": "user"}, {"id": 123}, {"name": "John"}, {"email": "john@example.com"}]
     "#}.trim();
     assert_actual_expected!(format_paths(&paths4), expected4);
 }
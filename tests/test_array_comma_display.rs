@@ -1,6 +1,20 @@
 #[cfg(test)]
 mod test_array_comma_display {
-    use dcbor_pattern::Pattern;
+    use dcbor::prelude::*;
+    use dcbor_pattern::{
+        ArrayPattern, Error, Matcher, Pattern, Quantifier, Reluctance,
+        StructurePattern,
+    };
+
+    fn array(elements: Pattern) -> Pattern {
+        Pattern::Structure(StructurePattern::Array(
+            ArrayPattern::with_elements(elements),
+        ))
+    }
+
+    fn numbers(values: &[i32]) -> Pattern {
+        Pattern::sequence(values.iter().map(|&v| Pattern::number(v)).collect())
+    }
 
     #[test]
     fn test_array_sequence_display_format() {
@@ -15,4 +29,125 @@ mod test_array_comma_display {
         let display = pattern.to_string();
         assert_eq!(display, "[(*)*, 42, (*)*]");
     }
+
+    #[test]
+    fn test_sequence_display_round_trip() {
+        let cases = [
+            (numbers(&[1, 2]), "(1, 2)"),
+            (
+                Pattern::or(vec![numbers(&[1, 2]), Pattern::number(3)]),
+                "(1, 2) | 3",
+            ),
+            (Pattern::capture("a", numbers(&[1, 2])), "@a((1, 2))"),
+            (Pattern::search(numbers(&[1, 2])), "search((1, 2))"),
+            (array(numbers(&[1, 2])), "[1, 2]"),
+            (
+                array(Pattern::or(vec![
+                    numbers(&[0, 1, 2]),
+                    Pattern::number(3),
+                ])),
+                "[(0, 1, 2) | 3]",
+            ),
+            (
+                array(Pattern::sequence(vec![
+                    Pattern::number(0),
+                    Pattern::or(vec![numbers(&[1, 2]), Pattern::number(9)]),
+                ])),
+                "[0, (1, 2) | 9]",
+            ),
+            (
+                array(Pattern::sequence(vec![
+                    Pattern::number(0),
+                    Pattern::capture("a", numbers(&[1, 2])),
+                ])),
+                "[0, @a((1, 2))]",
+            ),
+            (
+                array(Pattern::repeat(
+                    numbers(&[0, 1]),
+                    Quantifier::new(2..=2, Reluctance::Greedy),
+                )),
+                "[(0, 1){2}]",
+            ),
+            (array(Pattern::not_matching(numbers(&[1, 2]))), "[!(1, 2)]"),
+            (
+                array(Pattern::and(vec![
+                    numbers(&[1, 2]),
+                    Pattern::any_array(),
+                ])),
+                "[(1, 2) & array]",
+            ),
+        ];
+        for (pattern, display) in cases {
+            assert_eq!(pattern.to_string(), display);
+            assert_eq!(
+                Pattern::parse(display).unwrap(),
+                pattern,
+                "{}",
+                display
+            );
+        }
+    }
+
+    #[test]
+    fn test_sequence_alternatives_in_array() {
+        let pattern = Pattern::parse("[(0, 1, 2) | 3]").unwrap();
+        assert!(pattern.matches(&CBOR::from(vec![0, 1, 2])));
+        assert!(pattern.matches(&CBOR::from(vec![3])));
+        assert!(!pattern.matches(&CBOR::from(vec![0, 1, 3])));
+
+        // Without parentheses, `|` binds more tightly than `,`
+        let pattern = Pattern::parse("[0, 1, 2 | 3]").unwrap();
+        assert!(pattern.matches(&CBOR::from(vec![0, 1, 3])));
+        assert!(!pattern.matches(&CBOR::from(vec![3])));
+    }
+
+    #[test]
+    fn test_nested_sequences_flatten() {
+        let nested =
+            Pattern::sequence(vec![Pattern::number(0), numbers(&[1, 2])]);
+        assert_eq!(nested, numbers(&[0, 1, 2]));
+
+        let pattern = Pattern::parse("[0, (1, 2)]").unwrap();
+        assert_eq!(pattern, array(numbers(&[0, 1, 2])));
+        assert_eq!(pattern.to_string(), "[0, 1, 2]");
+        assert!(pattern.matches(&CBOR::from(vec![0, 1, 2])));
+    }
+
+    #[test]
+    fn test_parenthesized_sequence_outside_array() {
+        // Parentheses let a sequence stand on its own, for matching the items
+        // of a dCBOR sequence
+        let pattern = Pattern::parse("(number, text)").unwrap();
+        let items = [CBOR::from(1), CBOR::from("a")];
+        assert_eq!(pattern.match_sequence(&items), vec![items.to_vec()]);
+
+        // A bare sequence is still rejected
+        assert_eq!(
+            Pattern::parse("number, text"),
+            Err(Error::SequenceOutsideArray(",".to_string(), 6..7))
+        );
+    }
+
+    #[test]
+    fn test_negated_first_element_display() {
+        // A leading `!` negates all of the array content, so a negated first
+        // element is parenthesized to keep the negation to itself
+        let first = array(Pattern::sequence(vec![
+            Pattern::not_matching(Pattern::any_number()),
+            Pattern::any_text(),
+        ]));
+        assert_eq!(first.to_string(), "[(!number), text]");
+        assert_eq!(Pattern::parse("[(!number), text]").unwrap(), first);
+        assert!(first.matches(&CBOR::from(vec![CBOR::from("a"), "b".into()])));
+        assert!(!first.matches(&CBOR::from(vec![CBOR::from(1), "b".into()])));
+
+        let whole = Pattern::parse("[!number, text]").unwrap();
+        assert_ne!(whole, first);
+        assert_eq!(whole.to_string(), "[!(number, text)]");
+
+        let single = array(Pattern::not_matching(Pattern::any_number()));
+        assert_eq!(single.to_string(), "[!number]");
+        assert_eq!(Pattern::parse("[!number]").unwrap(), single);
+    }
 }
@@ -0,0 +1,114 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Matcher, Pattern};
+
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+fn items() -> Vec<CBOR> {
+    [
+        "42",
+        "-1",
+        "3.5",
+        r#""hello""#,
+        r#""world""#,
+        "true",
+        "false",
+        "null",
+        "h'0102'",
+        "[]",
+        "[1, 2, 3]",
+        r#"[1, "a", [42]]"#,
+        "{}",
+        r#"{"a": 1, "b": [42]}"#,
+        "1(1703462400)",
+        r#"100(["target"])"#,
+        "'1'",
+    ]
+    .iter()
+    .map(|s| cbor(s))
+    .collect()
+}
+
+const PATTERNS: &[&str] = &[
+    "*",
+    "number",
+    "42",
+    ">10",
+    "text",
+    "/h.*/",
+    "bool | null",
+    "!number",
+    "number & 42",
+    "[*]",
+    "[{3}]",
+    "[number, *]",
+    "[(*)*, 42, (*)*]",
+    "map",
+    r#"{"a": number}"#,
+    "tagged",
+    "tagged(100, [*])",
+    "search(42)",
+    "search(text)",
+    "@n(number)",
+    "[@x(number), *]",
+    "known",
+    "date",
+    "bstr",
+];
+
+#[test]
+fn test_filter_matches_per_item_results() {
+    let items = items();
+    for src in PATTERNS {
+        let pattern = Pattern::parse(src).unwrap();
+        let expected: Vec<bool> =
+            items.iter().map(|item| pattern.matches(item)).collect();
+        assert_eq!(
+            pattern.filter(&items),
+            expected,
+            "filter disagreed with matches for pattern {}",
+            src
+        );
+    }
+}
+
+#[test]
+fn test_matches_any_of() {
+    let items = items();
+    for src in PATTERNS {
+        let pattern = Pattern::parse(src).unwrap();
+        let expected = items.iter().any(|item| pattern.matches(item));
+        assert_eq!(
+            pattern.matches_any_of(&items),
+            expected,
+            "matches_any_of disagreed for pattern {}",
+            src
+        );
+    }
+
+    let pattern = Pattern::parse("text").unwrap();
+    assert!(!pattern.matches_any_of(&[]));
+    assert!(!pattern.matches_any_of(&[cbor("1"), cbor("[]")]));
+    assert!(pattern.matches_any_of(&[cbor("1"), cbor(r#""x""#)]));
+}
+
+#[test]
+fn test_filter_empty_input() {
+    let pattern = Pattern::any_number();
+    assert!(pattern.filter(&[]).is_empty());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_par_filter_matches_filter() {
+    let items = items();
+    for src in PATTERNS {
+        let pattern = Pattern::parse(src).unwrap();
+        assert_eq!(
+            pattern.par_filter(&items),
+            pattern.filter(&items),
+            "par_filter disagreed with filter for pattern {}",
+            src
+        );
+    }
+}
@@ -1,10 +1,8 @@
 mod common;
 
-use std::collections::HashMap;
-
 use dcbor_parse::parse_dcbor_item;
 use dcbor_pattern::{
-    FormatPathsOpts, Matcher, Pattern, format_paths_with_captures,
+    Captures, FormatPathsOpts, Matcher, Pattern, format_paths_with_captures,
 };
 use indoc::indoc;
 
@@ -462,7 +460,7 @@ mod test_comprehensive_variadic_sequences {
         assert_actual_expected!(
             format_paths_with_captures(
                 &paths,
-                &HashMap::new(),
+                &Captures::new(),
                 FormatPathsOpts::default()
             ),
             expected_three
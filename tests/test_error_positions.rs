@@ -15,7 +15,7 @@ mod test_error_positions {
                 println!("Error: {:?}", e);
 
                 // Check if this is an UnrecognizedToken error
-                if let dcbor_pattern::Error::UnrecognizedToken(span) = &e {
+                if let dcbor_pattern::Error::UnrecognizedToken(_, span) = &e {
                     println!("Error span: {:?}", span);
                     println!("Pattern: {}", pattern_str);
 
@@ -66,7 +66,7 @@ mod test_error_positions {
             Err(e) => {
                 println!("Error: {:?}", e);
 
-                if let dcbor_pattern::Error::UnrecognizedToken(span) = &e {
+                if let dcbor_pattern::Error::UnrecognizedToken(_, span) = &e {
                     println!("Error span: {:?}", span);
                     println!("Pattern: {}", pattern_str);
 
@@ -107,7 +107,7 @@ mod test_error_positions {
             Err(e) => {
                 println!("Error: {:?}", e);
 
-                if let dcbor_pattern::Error::UnrecognizedToken(span) = &e {
+                if let dcbor_pattern::Error::UnrecognizedToken(_, span) = &e {
                     println!("Error span: {:?}", span);
                     println!("Pattern: {}", pattern_str);
 
@@ -152,7 +152,7 @@ mod test_error_positions {
             Err(e) => {
                 println!("Error: {:?}", e);
 
-                if let dcbor_pattern::Error::UnrecognizedToken(span) = &e {
+                if let dcbor_pattern::Error::UnrecognizedToken(_, span) = &e {
                     println!("Error span: {:?}", span);
                     println!("Pattern: {}", pattern_str);
 
@@ -198,7 +198,7 @@ mod test_error_positions {
             Err(e) => {
                 println!("Error: {:?}", e);
 
-                if let dcbor_pattern::Error::UnrecognizedToken(span) = &e {
+                if let dcbor_pattern::Error::UnrecognizedToken(_, span) = &e {
                     println!("Error span: {:?}", span);
                     println!("Pattern: {}", pattern_str);
 
@@ -247,7 +247,7 @@ mod test_error_positions {
             Err(e) => {
                 println!("Error: {:?}", e);
 
-                if let dcbor_pattern::Error::UnrecognizedToken(span) = &e {
+                if let dcbor_pattern::Error::UnrecognizedToken(_, span) = &e {
                     println!("Error span: {:?}", span);
                     println!("Pattern: {}", pattern_str);
 
@@ -296,7 +296,7 @@ mod test_error_positions {
             Err(e) => {
                 println!("Error: {:?}", e);
 
-                if let dcbor_pattern::Error::UnrecognizedToken(span) = &e {
+                if let dcbor_pattern::Error::UnrecognizedToken(_, span) = &e {
                     println!("Error span: {:?}", span);
                     println!("Pattern: {}", pattern_str);
 
@@ -342,7 +342,7 @@ mod test_error_positions {
             Err(e) => {
                 println!("Error: {:?}", e);
 
-                if let dcbor_pattern::Error::UnrecognizedToken(span) = &e {
+                if let dcbor_pattern::Error::UnrecognizedToken(_, span) = &e {
                     println!("Error span: {:?}", span);
                     println!("Pattern: {}", pattern_str);
 
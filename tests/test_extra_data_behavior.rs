@@ -12,10 +12,10 @@ mod extra_data_tests {
         let result = Pattern::parse("true extra");
         match result {
             Ok(_) => panic!("Expected error for 'true extra', but got success"),
-            Err(Error::ExtraData(_)) => {
+            Err(Error::ExtraData(_, _)) => {
                 println!("✓ 'true extra' correctly returns ExtraData error")
             }
-            Err(Error::UnrecognizedToken(_)) => println!(
+            Err(Error::UnrecognizedToken(_, _)) => println!(
                 "✓ 'true extra' returns UnrecognizedToken error (not ExtraData)"
             ),
             Err(e) => panic!(
@@ -28,10 +28,10 @@ mod extra_data_tests {
         let result = Pattern::parse("true false");
         match result {
             Ok(_) => panic!("Expected error for 'true false', but got success"),
-            Err(Error::ExtraData(_)) => {
+            Err(Error::ExtraData(_, _)) => {
                 println!("✓ 'true false' correctly returns ExtraData error")
             }
-            Err(Error::UnrecognizedToken(_)) => println!(
+            Err(Error::UnrecognizedToken(_, _)) => println!(
                 "✓ 'true false' returns UnrecognizedToken error (not ExtraData)"
             ),
             Err(e) => panic!(
@@ -46,10 +46,10 @@ mod extra_data_tests {
             Ok(_) => {
                 panic!("Expected error for '42    more stuff', but got success")
             }
-            Err(Error::ExtraData(_)) => println!(
+            Err(Error::ExtraData(_, _)) => println!(
                 "✓ '42    more stuff' correctly returns ExtraData error"
             ),
-            Err(Error::UnrecognizedToken(_)) => println!(
+            Err(Error::UnrecognizedToken(_, _)) => println!(
                 "✓ '42    more stuff' returns UnrecognizedToken error (not ExtraData)"
             ),
             Err(e) => panic!(
@@ -62,10 +62,10 @@ mod extra_data_tests {
         let result = Pattern::parse("42 |");
         match result {
             Ok(_) => panic!("Expected error for '42 |', but got success"),
-            Err(Error::ExtraData(_)) => {
+            Err(Error::ExtraData(_, _)) => {
                 println!("✓ '42 |' correctly returns ExtraData error")
             }
-            Err(Error::UnrecognizedToken(_)) => println!(
+            Err(Error::UnrecognizedToken(_, _)) => println!(
                 "✓ '42 |' returns UnrecognizedToken error (not ExtraData)"
             ),
             Err(e) => println!("? '42 |' returns: {}", e),
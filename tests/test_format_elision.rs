@@ -0,0 +1,69 @@
+use dcbor::prelude::*;
+use dcbor_pattern::{
+    FormatPathsOpts, Matcher, Pattern, format_paths_opt,
+    format_paths_with_captures,
+};
+
+/// An array of 10,000 numbers with a map holding the needle in the middle.
+fn large_haystack() -> CBOR {
+    let mut needle = Map::new();
+    needle.insert("needle", "found");
+    let mut elements: Vec<CBOR> = (0..10_000).map(CBOR::from).collect();
+    elements[5_000] = CBOR::from(needle);
+    CBOR::from(elements)
+}
+
+#[test]
+fn test_summarized_large_array() {
+    let haystack = large_haystack();
+    let pattern = Pattern::parse(r#"search({"needle": text})"#).unwrap();
+    let paths = pattern.paths(&haystack);
+
+    let full = format_paths_opt(&paths, FormatPathsOpts::default());
+    assert!(full.len() > 40_000);
+
+    let output = format_paths_opt(
+        &paths,
+        FormatPathsOpts::new().summarize_containers(true),
+    );
+    assert_eq!(output, "[… 10000 elements]\n    {\"needle\": \"found\"}");
+}
+
+#[test]
+fn test_truncated_large_array_with_captures() {
+    let haystack = large_haystack();
+    let pattern =
+        Pattern::parse(r#"search({"needle": @found(text)})"#).unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+
+    let output = format_paths_with_captures(
+        &paths,
+        &captures,
+        FormatPathsOpts::new()
+            .max_node_length(Some(20))
+            .exempt_last_element(true),
+    );
+    assert!(output.len() < 200, "{}", output);
+    for line in output.lines() {
+        assert!(line.trim_start().chars().count() <= 20, "{}", line);
+    }
+
+    // The captured leaf is still shown in full
+    assert!(output.contains("\"found\""), "{}", output);
+}
+
+#[test]
+fn test_summarized_containers_in_json() {
+    let haystack = large_haystack();
+    let pattern = Pattern::parse("search(9999)").unwrap();
+    let paths = pattern.paths(&haystack);
+
+    let output = format_paths_opt(
+        &paths,
+        FormatPathsOpts::new().json(true).summarize_containers(true),
+    );
+    assert_eq!(
+        output,
+        r#"{"paths": [["[… 10000 elements]", "9999"]], "captures": {}}"#
+    );
+}
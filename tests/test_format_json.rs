@@ -0,0 +1,92 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    FormatPathsOpts, Matcher, PathElementFormat, Pattern, format_paths_opt,
+    format_paths_with_captures,
+};
+use serde_json::{Value, json};
+
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+fn json_output(pattern: &str, data: &str, opts: FormatPathsOpts) -> Value {
+    let pattern = Pattern::parse(pattern).unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&cbor(data));
+    let output = format_paths_with_captures(&paths, &captures, opts.json(true));
+    serde_json::from_str(&output).unwrap()
+}
+
+#[test]
+fn test_json_nested_captures() {
+    let output = json_output(
+        "@outer([@inner(number), *])",
+        r#"[42, "a"]"#,
+        FormatPathsOpts::new(),
+    );
+    assert_eq!(
+        output,
+        json!({
+            "paths": [[r#"[42, "a"]"#]],
+            "captures": {
+                "inner": [[r#"[42, "a"]"#, "42"]],
+                "outer": [[r#"[42, "a"]"#]],
+            }
+        })
+    );
+}
+
+#[test]
+fn test_json_empty_captures() {
+    let output = json_output("number", "42", FormatPathsOpts::new());
+    assert_eq!(output, json!({ "paths": [["42"]], "captures": {} }));
+
+    let output = json_output("text", "42", FormatPathsOpts::new());
+    assert_eq!(output, json!({ "paths": [], "captures": {} }));
+}
+
+#[test]
+fn test_json_non_string_map_keys() {
+    let output = json_output(
+        "search(@value(text))",
+        r#"{1: "one", [2]: "two"}"#,
+        FormatPathsOpts::new().last_element_only(true),
+    );
+    assert_eq!(
+        output,
+        json!({
+            "paths": [[r#""one""#], [r#""two""#]],
+            "captures": {
+                "value": [[r#""one""#], [r#""two""#]],
+            }
+        })
+    );
+}
+
+#[test]
+fn test_json_hex_elements() {
+    let output = json_output(
+        "[@item(number)]",
+        "[1000]",
+        FormatPathsOpts::new().element_format(PathElementFormat::Hex(None)),
+    );
+    assert_eq!(
+        output,
+        json!({
+            "paths": [["811903e8"]],
+            "captures": {
+                "item": [["811903e8", "1903e8"]],
+            }
+        })
+    );
+}
+
+#[test]
+fn test_json_special_characters() {
+    let paths = vec![vec![CBOR::from("quote \" and \\ and\nnewline")]];
+    let output = format_paths_opt(&paths, FormatPathsOpts::new().json(true));
+    let output: Value = serde_json::from_str(&output).unwrap();
+    // The element is the diagnostic text, which escapes only the quote
+    assert_eq!(
+        output["paths"][0][0],
+        json!("\"quote \\\" and \\ and\nnewline\"")
+    );
+}
@@ -0,0 +1,122 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    FormatPathsOpts, Matcher, PathElementFormat, Pattern, format_path_element,
+    format_paths, format_paths_opt, format_paths_with,
+    format_paths_with_captures, format_paths_with_captures_with,
+};
+use indoc::indoc;
+
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Shortens text values to their first three characters, and renders every
+/// other node as the crate does.
+fn truncate_text(cbor: &CBOR) -> String {
+    match cbor.as_text() {
+        Some(text) if text.chars().count() > 3 => {
+            let kept: String = text.chars().take(3).collect();
+            format!("\"{}...\"", kept)
+        }
+        _ => format_path_element(cbor, PathElementFormat::default()),
+    }
+}
+
+/// Hides the content of byte strings behind their length.
+fn redact_bytes(cbor: &CBOR) -> String {
+    match cbor.as_byte_string() {
+        Some(bytes) => format!("<{} bytes>", bytes.len()),
+        None => format_path_element(cbor, PathElementFormat::default()),
+    }
+}
+
+#[test]
+fn test_renderer_truncating_text() {
+    let haystack = cbor(r#"{"name": "Alexander", "id": 7}"#);
+    let pattern = Pattern::parse("search(@t(text))").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+
+    let output = format_paths_with_captures_with(
+        FormatPathsOpts::default(),
+        &paths,
+        &captures,
+        &truncate_text,
+    );
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @t
+            {"id": 7, "name": "Alexander"}
+                "id"
+            {"id": 7, "name": "Alexander"}
+                "nam..."
+            {"id": 7, "name": "Alexander"}
+                "Ale..."
+        {"id": 7, "name": "Alexander"}
+            "id"
+        {"id": 7, "name": "Alexander"}
+            "nam..."
+        {"id": 7, "name": "Alexander"}
+            "Ale..."
+    "#}.trim();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_renderer_redacting_bytes() {
+    let haystack = cbor("[h'deadbeef', h'', 1]");
+    let pattern = Pattern::parse("search(bstr)").unwrap();
+    let paths = pattern.paths(&haystack);
+
+    // The bytes are hidden where they are matched, but not inside the
+    // array, which the renderer sees as a whole
+    assert_eq!(
+        format_paths_with(FormatPathsOpts::default(), &paths, &redact_bytes),
+        "[h'deadbeef', h'', 1]\n    <4 bytes>\n[h'deadbeef', h'', 1]\n    <0 bytes>"
+    );
+
+    // Options other than the element format still apply
+    let opts = FormatPathsOpts::new().last_element_only(true);
+    assert_eq!(
+        format_paths_with(&opts, &paths, &redact_bytes),
+        "<4 bytes>\n<0 bytes>"
+    );
+    let opts = FormatPathsOpts::new()
+        .summarize_containers(true)
+        .container_threshold(2);
+    assert_eq!(
+        format_paths_with(opts, &paths, &redact_bytes),
+        "[… 3 elements]\n    <4 bytes>\n[… 3 elements]\n    <0 bytes>"
+    );
+    let opts = FormatPathsOpts::new().json(true).last_element_only(true);
+    assert_eq!(
+        format_paths_with(opts, &paths, &redact_bytes),
+        r#"{"paths": [["<4 bytes>"], ["<0 bytes>"]], "captures": {}}"#
+    );
+}
+
+#[test]
+fn test_default_renderer_matches_element_format() {
+    let haystack = cbor(r#"[1, "two", h'03']"#);
+    let pattern = Pattern::parse("search(@n(!array))").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+
+    assert_eq!(
+        format_paths_with(FormatPathsOpts::default(), &paths, &|cbor| {
+            format_path_element(cbor, PathElementFormat::default())
+        }),
+        format_paths(&paths)
+    );
+
+    let format = PathElementFormat::Hex(None);
+    let opts = FormatPathsOpts::new().element_format(format);
+    assert_eq!(
+        format_paths_with_captures_with(&opts, &paths, &captures, &|cbor| {
+            format_path_element(cbor, format)
+        }),
+        format_paths_with_captures(&paths, &captures, &opts)
+    );
+    assert_eq!(
+        format_paths_opt(&paths, &opts).lines().last(),
+        Some("    4103")
+    );
+}
@@ -22,6 +22,31 @@ fn test_sequence_pattern_new_api() {
     assert!(sequence.is_complex());
 }
 
+#[test]
+fn test_sequence_pattern_standalone_matches_nothing() {
+    // A sequence describes array elements or sequence items, so on its own
+    // it matches no value, whether matched directly, with captures, or
+    // through the VM inside another pattern
+    let sequence = Pattern::sequence(vec![
+        Pattern::any_number(),
+        Pattern::capture("t", Pattern::any_text()),
+    ]);
+    let items = [CBOR::from(1), CBOR::from("a")];
+    for haystack in [items.to_vec().to_cbor(), CBOR::from(1)] {
+        assert!(sequence.paths(&haystack).is_empty());
+        let (paths, captures) = sequence.paths_with_captures(&haystack);
+        assert!(paths.is_empty());
+        assert!(captures.is_empty());
+        let wrapped = Pattern::capture("s", sequence.clone());
+        assert!(!wrapped.matches(&haystack));
+    }
+
+    // Where it belongs, the same sequence matches
+    assert_eq!(sequence.match_sequence(&items), vec![items.to_vec()]);
+    let array = Pattern::parse(&format!("[{}]", sequence)).unwrap();
+    assert!(array.matches(&items.to_vec().to_cbor()));
+}
+
 #[test]
 fn test_structure_convenience_methods() {
     // Test new structure pattern convenience methods
@@ -8,7 +8,7 @@ mod test_order_stability {
         // Create a scenario that would generate multiple paths in a predictable
         // order
         let cbor_data = parse_dcbor_item(r#"[[1], [2], [3], [1]]"#).unwrap();
-        let pattern = Pattern::parse("[@outer([@inner(number)])]").unwrap();
+        let pattern = Pattern::parse("[(@outer([@inner(number)]))*]").unwrap();
 
         let (paths, captures) = pattern.paths_with_captures(&cbor_data);
 
@@ -42,22 +42,14 @@ mod test_order_stability {
         );
 
         if let Some(outer_captures) = captures.get("outer") {
-            // Should have [1], [2], [3] captured (deduplicated, [1] appears
-            // twice but creates same path)
-            assert_eq!(
-                outer_captures.len(),
-                3,
-                "Should have 3 unique outer captures"
-            );
+            // Should have [1], [2], [3], [1] captured, [1] once for each
+            // position it appears at
+            assert_eq!(outer_captures.len(), 4, "Should have 4 outer captures");
         }
 
         if let Some(inner_captures) = captures.get("inner") {
-            // Should have 1, 2, 3 captured (deduplicated)
-            assert_eq!(
-                inner_captures.len(),
-                3,
-                "Should have 3 unique inner captures"
-            );
+            // Should have 1, 2, 3, 1 captured
+            assert_eq!(inner_captures.len(), 4, "Should have 4 inner captures");
         }
     }
 
@@ -66,7 +58,7 @@ mod test_order_stability {
         // Test with values that are likely to hash differently
         let cbor_data =
             parse_dcbor_item(r#"[1, 1000000, 2, 1000000, 3]"#).unwrap();
-        let pattern = Pattern::parse("[@item(number)]").unwrap();
+        let pattern = Pattern::parse("[(@item(number))*]").unwrap();
 
         let first_run = pattern.paths_with_captures(&cbor_data);
 
@@ -85,15 +77,55 @@ mod test_order_stability {
             );
         }
 
-        // Verify we get the expected deduplication
+        // Verify each position is captured, including both 1000000s
         if let Some(item_captures) = first_run.1.get("item") {
-            // Should capture: 1, 1000000, 2, 3 (in order of first appearance,
-            // duplicates removed)
-            assert_eq!(
-                item_captures.len(),
-                4,
-                "Should have 4 unique captured values"
-            );
+            // Should capture: 1, 1000000, 2, 1000000, 3
+            assert_eq!(item_captures.len(), 5, "Should have 5 captured values");
+        }
+    }
+
+    #[test]
+    fn test_capture_names_in_pattern_order() {
+        let cbor_data =
+            parse_dcbor_item(r#"{"z": [1, "a"], "b": {"c": true}, "m": 2}"#)
+                .unwrap();
+
+        // Names are listed as they appear in the pattern, regardless of how
+        // they sort or which structure captured them
+        let pattern = Pattern::parse(
+            r#"{"z": [@zeta(number), @alpha(text)], "b": {@mid(text): @beta(bool)}, @key("m"): @last(number)}"#,
+        )
+        .unwrap();
+        let (_, captures) = pattern.paths_with_captures(&cbor_data);
+        let names: Vec<&str> = captures.keys().map(String::as_str).collect();
+        assert_eq!(names, ["zeta", "alpha", "mid", "beta", "key", "last"]);
+
+        let pattern =
+            Pattern::parse("search(@y(text) | @x(number) | @w(bool))").unwrap();
+        let (_, captures) = pattern.paths_with_captures(&cbor_data);
+        let names: Vec<&str> = captures.keys().map(String::as_str).collect();
+        assert_eq!(names, ["y", "x", "w"]);
+    }
+
+    #[test]
+    fn test_capture_paths_in_document_order() {
+        let cbor_data = parse_dcbor_item(r#"[3, [2, 1], 0]"#).unwrap();
+
+        // Each name's paths follow a pre-order traversal of the document,
+        // whatever order the matcher found them in
+        for (source, expected) in [
+            ("[(@n(number) | *)*]", vec![3, 0]),
+            ("search(@n(number))", vec![3, 2, 1, 0]),
+        ] {
+            let pattern = Pattern::parse(source).unwrap();
+            let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+            assert_eq!(paths, pattern.paths(&cbor_data), "{}", source);
+
+            let captured: Vec<u64> = captures["n"]
+                .iter()
+                .map(|path| path.last().unwrap().clone().try_into().unwrap())
+                .collect();
+            assert_eq!(captured, expected, "{}", source);
         }
     }
 }
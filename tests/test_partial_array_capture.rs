@@ -28,6 +28,8 @@ fn test_debug_array_pattern_directly() {
 
         let (paths_with_caps, captures) =
             array_pattern.paths_with_captures(&cbor_data);
+
+        assert_path_chains!(&cbor_data, &paths_with_caps, &captures);
         println!(
             "Direct ArrayPattern::paths_with_captures result: {:?}",
             paths_with_caps
@@ -40,6 +42,8 @@ fn test_debug_array_pattern_directly() {
     println!("Full pattern paths: {:?}", paths);
 
     let (paths_with_caps, captures) = pattern.paths_with_captures(&cbor_data);
+
+    assert_path_chains!(&cbor_data, &paths_with_caps, &captures);
     println!("Full pattern paths_with_captures: {:?}", paths_with_caps);
     println!("Full pattern captures: {:?}", captures);
 }
@@ -52,6 +56,7 @@ fn test_desired_partial_array_capture_behavior() {
     let pattern = parse("search([@a(*), @rest((*)*)])");
     let cbor_data = cbor("[1, 2, 3]");
     let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
 
     // Expected output according to user:
     // @a
@@ -73,6 +78,7 @@ fn test_desired_partial_array_capture_behavior() {
     // Test case 2: [1] with [@a(*), @rest((*)*)]
     let cbor_data2 = cbor("[1]");
     let (paths2, captures2) = pattern.paths_with_captures(&cbor_data2);
+    assert_path_chains!(&cbor_data2, &paths2, &captures2);
 
     // Expected output according to user:
     // @a
@@ -3,7 +3,9 @@ mod common;
 use std::time::Instant;
 
 use dcbor_parse::parse_dcbor_item;
-use dcbor_pattern::{Matcher, Pattern, format_paths};
+use dcbor_pattern::{
+    ArrayPattern, Matcher, Pattern, Program, StructurePattern, format_paths,
+};
 use indoc::indoc;
 
 #[test]
@@ -38,6 +40,7 @@ fn test_deeply_nested_performance() {
     #[rustfmt::skip]
     let expected = indoc! {r#"
         100({"a": {"b": {"c": {"d": [42]}}}})
+            {"a": {"b": {"c": {"d": [42]}}}}
     "#}.trim();
     assert_actual_expected!(format_paths(&paths), expected);
 
@@ -335,3 +338,38 @@ fn test_edge_case_performance() {
         elapsed, paths_time
     );
 }
+
+#[test]
+fn test_repeated_captures_reuse_compiled_program() {
+    // Nested captures inside arrays are matched with a VM program of their
+    // own, compiled the first time the array is matched and then reused,
+    // which the unit tests of `ArrayPattern` count.
+    let pattern = Pattern::parse("[(@outer([@inner(number)]))*]").unwrap();
+    let data = parse_dcbor_item(r#"[[1], [2], [3]]"#).unwrap();
+
+    let program = Program::compile(&pattern);
+    assert!(matches!(
+        program.literals[0],
+        Pattern::Structure(StructurePattern::Array(ArrayPattern::Elements(_)))
+    ));
+
+    let (first_paths, first_captures) = pattern.paths_with_captures(&data);
+    assert_eq!(
+        program.run(&data),
+        (first_paths.clone(), first_captures.clone())
+    );
+    assert!(!first_paths.is_empty());
+    assert!(first_captures.contains_key("outer"));
+    assert!(first_captures.contains_key("inner"));
+
+    for i in 0..1000 {
+        let (paths, captures) = pattern.paths_with_captures(&data);
+        assert_eq!(paths, first_paths, "Paths differed on iteration {}", i);
+        assert_eq!(
+            captures, first_captures,
+            "Captures differed on iteration {}",
+            i
+        );
+        assert_eq!(program.run(&data), (paths, captures));
+    }
+}
@@ -38,3 +38,51 @@ fn test_search_with_partial_array_capture() {
     // @rest should capture empty array, so it might or might not be in the
     // captures map depending on implementation
 }
+
+#[test]
+fn test_exists_agrees_with_search() {
+    let haystacks = [
+        "1",
+        r#""hello""#,
+        "[1, [2, [3, \"x\"]]]",
+        r#"{"a": {"b": [true, null]}, 1: h'0102'}"#,
+        r#"1(["deep", {"k": 5([42])}])"#,
+        "[]",
+        "{}",
+    ];
+    let patterns = [
+        "number",
+        "42",
+        "text",
+        r#""x" | null"#,
+        "[number, text]",
+        "{\"b\": array}",
+        "tagged(5, [*])",
+        "bstr",
+        "!number",
+        "number & text",
+        "@n(3)",
+        "array",
+    ];
+    for source in patterns {
+        let pattern = parse(source);
+        let search = Pattern::search(pattern.clone());
+        for haystack in haystacks {
+            let haystack = dcbor_parse::parse_dcbor_item(haystack).unwrap();
+            assert_eq!(
+                pattern.exists(&haystack),
+                search.matches(&haystack),
+                "{source} on {}",
+                haystack.diagnostic_flat()
+            );
+            assert_eq!(
+                pattern.exists(&haystack),
+                !search.paths(&haystack).is_empty()
+            );
+            assert_eq!(
+                pattern.matches_root(&haystack),
+                pattern.matches(&haystack)
+            );
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{ArrayPattern, Pattern, StructurePattern};
+
+/// Parses the element pattern of `[source]`, the same pattern a dCBOR
+/// sequence is matched with.
+fn sequence_pattern(source: &str) -> Pattern {
+    match Pattern::parse(&format!("[{}]", source)).unwrap() {
+        Pattern::Structure(StructurePattern::Array(
+            ArrayPattern::Elements(pattern),
+        )) => *pattern,
+        pattern => panic!("not an element pattern: {}", pattern),
+    }
+}
+
+/// Parses each item of a sequence from diagnostic notation.
+fn items(sources: &[&str]) -> Vec<CBOR> {
+    sources
+        .iter()
+        .map(|s| parse_dcbor_item(s).unwrap())
+        .collect()
+}
+
+/// The single path returned by a successful match.
+fn item_paths(items: &[CBOR]) -> Vec<Vec<CBOR>> { vec![items.to_vec()] }
+
+#[test]
+fn test_sequence_with_trailing_repeat() {
+    let pattern = sequence_pattern("number, text, (*)*");
+
+    let matching = [
+        items(&["1", r#""a""#]),
+        items(&["1", r#""a""#, "true"]),
+        items(&["1", r#""a""#, "[2]", "null"]),
+        items(&["1", r#""a""#, "2", "3", r#"{"k": 4}"#]),
+    ];
+    for sequence in matching {
+        assert_eq!(
+            pattern.match_sequence(&sequence),
+            item_paths(&sequence),
+            "{:?}",
+            sequence
+        );
+    }
+
+    let mismatching = [
+        items(&[]),
+        items(&["1"]),
+        items(&[r#""a""#, "1"]),
+        items(&["1", "2", r#""a""#]),
+        items(&["[1]", r#""a""#, "2"]),
+    ];
+    for sequence in mismatching {
+        assert!(
+            pattern.match_sequence(&sequence).is_empty(),
+            "{:?}",
+            sequence
+        );
+    }
+}
+
+#[test]
+fn test_sequence_must_account_for_every_item() {
+    let pattern = sequence_pattern("number, text");
+    assert!(!pattern.match_sequence(&items(&["1", r#""a""#])).is_empty());
+    assert!(
+        pattern
+            .match_sequence(&items(&["1", r#""a""#, "2"]))
+            .is_empty()
+    );
+
+    // A single pattern is a sequence of one item
+    let pattern = sequence_pattern("number");
+    assert!(!pattern.match_sequence(&items(&["1"])).is_empty());
+    assert!(pattern.match_sequence(&items(&["1", "2"])).is_empty());
+
+    let pattern = sequence_pattern("(number)*");
+    assert_eq!(pattern.match_sequence(&[]), vec![Vec::<CBOR>::new()]);
+    assert!(!pattern.match_sequence(&items(&["1", "2", "3"])).is_empty());
+    assert!(pattern.match_sequence(&items(&["1", r#""a""#])).is_empty());
+}
+
+#[test]
+fn test_sequence_captures() {
+    let pattern = sequence_pattern("@id(number), @rest((*)*)");
+    let sequence = items(&["1", r#""a""#, "[2]"]);
+    let (paths, captures) = pattern.paths_with_captures_sequence(&sequence);
+    assert_eq!(paths, item_paths(&sequence));
+    assert_eq!(captures["id"], vec![vec![sequence[0].clone()]]);
+    assert_eq!(
+        captures["rest"],
+        vec![vec![sequence[1..].to_vec().to_cbor()]]
+    );
+
+    // Captures inside an item keep the path from that item
+    let pattern = sequence_pattern("text, [@n(number)]");
+    let sequence = items(&[r#""a""#, "[5]"]);
+    let (_, captures) = pattern.paths_with_captures_sequence(&sequence);
+    assert_eq!(
+        captures["n"],
+        vec![vec![sequence[1].clone(), CBOR::from(5)]]
+    );
+
+    // Captures inside a repeat get one path per item
+    let pattern = sequence_pattern("(@n(number))*, text");
+    let sequence = items(&["1", "2", r#""a""#]);
+    let (_, captures) = pattern.paths_with_captures_sequence(&sequence);
+    assert_eq!(
+        captures["n"],
+        vec![vec![CBOR::from(1)], vec![CBOR::from(2)]]
+    );
+
+    let (paths, captures) =
+        pattern.paths_with_captures_sequence(&items(&["1", "2"]));
+    assert!(paths.is_empty());
+    assert!(captures.is_empty());
+}
+
+#[test]
+fn test_sequence_quantifiers_and_backrefs() {
+    let pattern = sequence_pattern("(number){2,3}, (text)*?");
+    assert!(!pattern.match_sequence(&items(&["1", "2"])).is_empty());
+    assert!(
+        !pattern
+            .match_sequence(&items(&["1", "2", "3", r#""a""#]))
+            .is_empty()
+    );
+    assert!(pattern.match_sequence(&items(&["1"])).is_empty());
+    assert!(
+        pattern
+            .match_sequence(&items(&["1", "2", "3", "4"]))
+            .is_empty()
+    );
+
+    let pattern = sequence_pattern("@x(*), (*)*, =x");
+    assert!(!pattern.match_sequence(&items(&["1", "2", "1"])).is_empty());
+    assert!(pattern.match_sequence(&items(&["1", "2", "3"])).is_empty());
+}
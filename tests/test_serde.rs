@@ -0,0 +1,206 @@
+#![cfg(feature = "serde")]
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{DynMatcher, MatchResult, Matcher, Pattern};
+
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+const PATTERNS: &[&str] = &[
+    "*",
+    "bool",
+    "true",
+    "null",
+    "number",
+    "42",
+    "1...10",
+    ">=5",
+    "<3.5",
+    "NaN",
+    "-Infinity",
+    "number(1, 2.5, Infinity)",
+    "text",
+    r#""hello""#,
+    "/h.*o/",
+    r#"text(^"he")"#,
+    r#"text($"lo")"#,
+    r#"text(~"HELLO")"#,
+    r#"text("red", "green")"#,
+    "bstr",
+    "h'0102ff'",
+    r"h'/^\x01/'",
+    "bstr({3})",
+    "bstr(prefix: h'01')",
+    "bstr(h'00', h'0102')",
+    r#"cbor'{"a": [1, 2, 100(h\'00\')]}'"#,
+    "date",
+    "date'2023-12-25'",
+    "date'2023-12-24...2023-12-26'",
+    "date'2023-12-24...'",
+    "date'...2023-12-26'",
+    "date'/2023-.*/'",
+    "date'past 30d'",
+    "date'future'",
+    "date-any",
+    "date-any'2023-12-24...2023-12-26'",
+    "digest",
+    "digest'a1b2c3'",
+    "digest'/^[0-9a-f]+$/'",
+    "known",
+    "'1'",
+    "'isA'",
+    "'/i.*/'",
+    "known('isA' | '12345')",
+    "known(1...100)",
+    "array",
+    "[{2,4}]",
+    "[number, text]",
+    "[~ number, @t(text)]",
+    "{{2,}, (text: @n(number)){2}, @rest(*)}",
+    "[(*)*, 42, (*)*]",
+    "[(number){2,3}?, text]",
+    "map",
+    "{{1,}}",
+    r#"{"a": number, text: *}"#,
+    r#"{"a"}: {text}: @v(number)"#,
+    "tagged",
+    "tagged(100, text)",
+    "tagged(date, *)",
+    "tagged(/da.*/, *)",
+    "number | text",
+    "number & >10",
+    "!null",
+    "@name(text)",
+    "search(@n(number))",
+    "[@head(*), @rest((*)*)]",
+    "[@x(*), (*)*, =x]",
+    "search(none(number))",
+];
+
+#[test]
+fn test_serde_round_trip() {
+    for src in PATTERNS {
+        let pattern = Pattern::parse(src).unwrap();
+        let json = serde_json::to_string(&pattern).unwrap();
+        let decoded: Pattern = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, pattern, "round trip changed pattern {}", src);
+        assert_eq!(
+            decoded.to_string(),
+            pattern.to_string(),
+            "round trip changed display of {}",
+            src
+        );
+    }
+}
+
+#[test]
+fn test_serde_round_trip_preserves_matching() {
+    let items = [
+        "42",
+        "7",
+        r#""hello""#,
+        "h'0102ff'",
+        "null",
+        r#"[1, 2, 42, "x"]"#,
+        r#"{"a": 1, "b": "c"}"#,
+        r#"100("text")"#,
+        "1(1703462400)",
+        "40000(1)",
+    ];
+    for src in PATTERNS {
+        let pattern = Pattern::parse(src).unwrap();
+        let json = serde_json::to_string(&pattern).unwrap();
+        let decoded: Pattern = serde_json::from_str(&json).unwrap();
+        for item in items {
+            let item = cbor(item);
+            assert_eq!(
+                decoded.paths_with_captures(&item),
+                pattern.paths_with_captures(&item),
+                "round trip changed results of {} on {}",
+                src,
+                item
+            );
+        }
+    }
+}
+
+#[test]
+fn test_serde_regex_source_is_stored() {
+    let pattern = Pattern::parse("/h.*o/").unwrap();
+    let json = serde_json::to_string(&pattern).unwrap();
+    assert!(json.contains(r#""h.*o""#), "unexpected encoding {}", json);
+}
+
+#[test]
+fn test_serde_invalid_regex_is_an_error() {
+    let pattern = Pattern::parse("/abc/").unwrap();
+    let json = serde_json::to_string(&pattern).unwrap();
+    let invalid = json.replace("abc", "(abc");
+    let result: Result<Pattern, _> = serde_json::from_str(&invalid);
+    assert!(result.is_err());
+
+    let pattern = Pattern::parse("h'/abc/'").unwrap();
+    let json = serde_json::to_string(&pattern).unwrap();
+    let invalid = json.replace("abc", "[abc");
+    let result: Result<Pattern, _> = serde_json::from_str(&invalid);
+    assert!(result.is_err());
+}
+
+#[derive(Debug)]
+struct Negative;
+
+impl DynMatcher for Negative {
+    fn matches(&self, haystack: &CBOR) -> bool {
+        f64::try_from(haystack.clone()).is_ok_and(|n| n < 0.0)
+    }
+}
+
+#[test]
+fn test_serde_custom_matcher_is_stored_by_name() {
+    Pattern::register_custom("negative", || Negative).unwrap();
+    let pattern = Pattern::parse("[custom(negative)*]").unwrap();
+    let json = serde_json::to_string(&pattern).unwrap();
+    assert!(
+        json.contains(r#""negative""#),
+        "unexpected encoding {}",
+        json
+    );
+    let decoded: Pattern = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, pattern);
+    assert!(decoded.matches(&cbor("[-1, -2.5]")));
+    assert!(!decoded.matches(&cbor("[-1, 2]")));
+
+    // A name with no registered matcher can't be decoded
+    let unregistered = json.replace("negative", "positive");
+    let result: Result<Pattern, _> = serde_json::from_str(&unregistered);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_serde_match_result_round_trip() {
+    let haystack = cbor(r#"{"b": [1, 2], "a": h'00ff'}"#);
+    let pattern =
+        Pattern::parse("search(@num(number)) | @bytes(bstr)").unwrap();
+    let result =
+        Pattern::parse(r#"{"b": [(@num(number))*], "a": @bytes(bstr)}"#)
+            .unwrap()
+            .run(&haystack);
+    assert!(!result.is_empty());
+    let json = serde_json::to_string(&result).unwrap();
+    let decoded: MatchResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, result);
+    assert_eq!(decoded.to_string(), result.to_string());
+
+    // Capture names keep the order they were first captured in
+    let names: Vec<_> = decoded.captures().keys().collect();
+    assert_eq!(names, ["num", "bytes"]);
+    assert!(json.find(r#""num""#) < json.find(r#""bytes""#));
+
+    // Nodes are stored as the hex of their encodings
+    assert!(json.contains(r#""4200ff""#), "unexpected encoding {}", json);
+
+    let result = pattern.run(&haystack);
+    let decoded: MatchResult =
+        serde_json::from_str(&serde_json::to_string(&result).unwrap()).unwrap();
+    assert_eq!(decoded, result);
+}
@@ -0,0 +1,126 @@
+use std::{sync::Arc, thread};
+
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{
+    Captures, Error, Instr, Matcher, Path, Pattern, PatternLibrary, Program,
+};
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_types_are_send_and_sync() {
+    assert_send_sync::<Pattern>();
+    assert_send_sync::<Program>();
+    assert_send_sync::<Instr>();
+    assert_send_sync::<PatternLibrary>();
+    assert_send_sync::<Error>();
+    assert_send_sync::<Captures>();
+}
+
+/// Patterns that touch each kind of state matching uses: the known values
+/// registry, by name and by regex, nested searches, which remember what they
+/// found for the search enclosing them, relative dates, which read the clock
+/// once per match, and compiled programs, which are cached in the pattern.
+const PATTERNS: &[&str] = &[
+    "search('isA')",
+    "search(@kv('/^(is|note)/'))",
+    r#"search({"items": search(@n(number))})"#,
+    "search(date'past')",
+    "search([(*)*, @last('note')])",
+    r#"{"kinds": [@first(known), (*)*]}"#,
+];
+
+fn haystack() -> CBOR {
+    cbor(
+        r#"{
+            "items": [1, [2, 3], {"items": [4]}],
+            "kinds": [40000(1), 40000(4)],
+            "when": 1(1700000000)
+        }"#,
+    )
+}
+
+#[test]
+fn test_concurrent_matching() {
+    let haystack = Arc::new(haystack());
+    let patterns: Arc<Vec<Pattern>> = Arc::new(
+        PATTERNS
+            .iter()
+            .map(|source| Pattern::parse(source).unwrap())
+            .collect(),
+    );
+    let expected: Vec<(Vec<Path>, Captures)> = patterns
+        .iter()
+        .map(|pattern| pattern.paths_with_captures(&haystack))
+        .collect();
+    assert!(expected.iter().all(|(paths, _)| !paths.is_empty()));
+
+    let workers: Vec<_> = (0..8)
+        .map(|_| {
+            let haystack = Arc::clone(&haystack);
+            let patterns = Arc::clone(&patterns);
+            thread::spawn(move || {
+                (0..100)
+                    .flat_map(|_| {
+                        patterns
+                            .iter()
+                            .map(|pattern| {
+                                pattern.paths_with_captures(&haystack)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    for worker in workers {
+        for (i, result) in worker.join().unwrap().into_iter().enumerate() {
+            assert_eq!(result, expected[i % PATTERNS.len()], "{}", i);
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_parsing_and_programs() {
+    let haystack = haystack();
+    let programs: Vec<(Program, (Vec<Path>, Captures))> = PATTERNS
+        .iter()
+        .map(|source| {
+            let pattern = Pattern::parse(source).unwrap();
+            let program = Program::compile(&pattern);
+            let expected = program.run(&haystack);
+            (program, expected)
+        })
+        .collect();
+
+    // Threads share the programs by reference while each parses its own
+    // patterns, with a library of its own
+    thread::scope(|scope| {
+        for worker in 0..8 {
+            let haystack = &haystack;
+            let programs = &programs;
+            scope.spawn(move || {
+                let mut library = PatternLibrary::new();
+                library.define("kind", "known").unwrap();
+                for _ in 0..50 {
+                    for (program, expected) in programs {
+                        assert_eq!(&program.run(haystack), expected);
+                    }
+                    let pattern = library
+                        .parse(&format!("[(*){{{}}}, $kind, (*)*]", worker % 2))
+                        .unwrap();
+                    let kinds = haystack
+                        .as_map()
+                        .unwrap()
+                        .get::<_, CBOR>("kinds")
+                        .unwrap();
+                    assert!(pattern.matches(&kinds));
+                }
+            });
+        }
+    });
+}
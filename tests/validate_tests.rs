@@ -0,0 +1,185 @@
+use dcbor::prelude::*;
+use dcbor_parse::parse_dcbor_item;
+use dcbor_pattern::{Pattern, ViolationKind};
+
+/// Helper function to parse CBOR diagnostic notation into CBOR objects
+fn cbor(s: &str) -> CBOR { parse_dcbor_item(s).unwrap() }
+
+/// Helper function to parse pattern text into Pattern objects
+fn parse(s: &str) -> Pattern { Pattern::parse(s).unwrap() }
+
+/// Validates `data` against `pattern` and returns the kind, failing
+/// sub-pattern, and failing node of each violation.
+fn violations(pattern: &str, data: &str) -> Vec<(ViolationKind, String, CBOR)> {
+    parse(pattern)
+        .validate(&cbor(data))
+        .into_iter()
+        .map(|violation| {
+            (
+                violation.kind(),
+                violation.pattern().to_string(),
+                violation.node().clone(),
+            )
+        })
+        .collect()
+}
+
+const USER: &str = r#"{
+    "name": text,
+    "age": 0...150,
+    "email": text,
+    "roles": [text, text],
+    "status": "active" | "suspended"
+}"#;
+
+#[test]
+fn test_validate_valid_record() {
+    let record = r#"{"name": "Alice", "age": 30, "email": "a@example.com",
+        "roles": ["admin", "dev"], "status": "active"}"#;
+    assert!(parse(USER).validate(&cbor(record)).is_empty());
+}
+
+#[test]
+fn test_validate_reports_every_violation() {
+    // No email, an age that isn't a number, and one role too few
+    let record = r#"{"name": "Alice", "age": "thirty", "roles": ["admin"],
+        "status": "active"}"#;
+    assert_eq!(
+        violations(USER, record),
+        vec![
+            (
+                ViolationKind::WrongType,
+                "0...150".into(),
+                cbor(r#""thirty""#)
+            ),
+            (
+                ViolationKind::MissingEntry,
+                r#""email""#.into(),
+                cbor(record)
+            ),
+            (
+                ViolationKind::WrongArity,
+                "[text, text]".into(),
+                cbor(r#"["admin"]"#)
+            ),
+        ]
+    );
+
+    let violation = &parse(USER).validate(&cbor(record))[0];
+    assert_eq!(violation.path(), &vec![cbor(record), cbor(r#""thirty""#)]);
+    assert_eq!(violation.depth(), 1);
+    assert_eq!(
+        violation.to_string(),
+        r#"wrong type: 0...150 did not match "thirty" at depth 1"#
+    );
+}
+
+#[test]
+fn test_validate_kinds() {
+    // A value of the right type that the pattern doesn't accept
+    let record = r#"{"name": "Bob", "age": 200, "email": "b@example.com",
+        "roles": ["dev", 7], "status": "deleted"}"#;
+    assert_eq!(
+        violations(USER, record),
+        vec![
+            (ViolationKind::WrongValue, "0...150".into(), cbor("200")),
+            (ViolationKind::WrongType, "text".into(), cbor("7")),
+            (
+                ViolationKind::NoAlternativeMatched,
+                r#""active" | "suspended""#.into(),
+                cbor(r#""deleted""#)
+            ),
+        ]
+    );
+
+    // Not a map at all
+    assert_eq!(violations(USER, "[1]")[0].0, ViolationKind::WrongType);
+
+    // Length constraints, alongside the constraints on entries
+    assert_eq!(
+        violations(r#"{{2}} & {"a": number}"#, r#"{"a": "x"}"#),
+        vec![
+            (
+                ViolationKind::LengthOutOfRange,
+                "{{2}}".into(),
+                cbor(r#"{"a": "x"}"#)
+            ),
+            (ViolationKind::WrongType, "number".into(), cbor(r#""x""#)),
+        ]
+    );
+    assert_eq!(
+        violations("[{2,} & number, text]", r#"["a"]"#)[0].0,
+        ViolationKind::LengthOutOfRange
+    );
+
+    // Every operand of `&` is checked, and tags are types
+    assert_eq!(
+        violations("tagged(1, number & 1...5)", "2(7)"),
+        vec![(
+            ViolationKind::WrongType,
+            "tagged(1, number & 1...5)".into(),
+            cbor("2(7)")
+        )]
+    );
+    assert_eq!(
+        violations("[number & 1...5, text & \"a\"]", r#"[7, "b"]"#)
+            .iter()
+            .map(|(kind, _, _)| *kind)
+            .collect::<Vec<_>>(),
+        [ViolationKind::WrongValue, ViolationKind::WrongValue]
+    );
+}
+
+#[test]
+fn test_validate_all_entries() {
+    // Every entry that breaks the constraint is reported
+    assert_eq!(
+        violations(
+            "{keys: text, values: number}",
+            r#"{"a": 1, "b": "x", 2: 3}"#
+        ),
+        vec![
+            (ViolationKind::WrongType, "text".into(), cbor("2")),
+            (ViolationKind::WrongType, "number".into(), cbor(r#""x""#)),
+        ]
+    );
+}
+
+#[test]
+fn test_validate_counted_entries() {
+    // Too many entries, or none at all
+    assert_eq!(
+        violations(
+            r#"{(text: number){,1}, (number: text){1,}}"#,
+            r#"{"a": 1, "b": 2}"#
+        ),
+        vec![
+            (
+                ViolationKind::WrongArity,
+                "{(text: number){,1}}".into(),
+                cbor(r#"{"a": 1, "b": 2}"#)
+            ),
+            (
+                ViolationKind::MissingEntry,
+                "number".into(),
+                cbor(r#"{"a": 1, "b": 2}"#)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_validate_falls_back_to_single_violation() {
+    // Arrays with repeats are explained rather than validated element by
+    // element
+    assert_eq!(
+        violations("[number, (text)+]", "[1, 2, 3]"),
+        vec![(ViolationKind::Other, "text".into(), cbor("2"))]
+    );
+
+    // As are patterns validation doesn't look inside
+    assert_eq!(
+        violations("!number", "1"),
+        vec![(ViolationKind::Other, "!number".into(), cbor("1"))]
+    );
+}
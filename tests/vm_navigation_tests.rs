@@ -162,14 +162,14 @@ fn test_vm_multiple_captures() {
 
     let (vm_paths, vm_captures) = run(&program, &cbor_data);
 
-    // Should capture both elements
+    // Should capture both elements, in order
     // expected-text-output-rubric:
     #[rustfmt::skip]
     let expected_paths = indoc! {r#"
-        [42, 100]
-            100
         [42, 100]
             42
+        [42, 100]
+            100
     "#}.trim();
     assert_actual_expected!(format_paths(&vm_paths), expected_paths);
 
@@ -211,3 +211,207 @@ fn test_vm_no_match_navigation() {
         "No captures should be returned for non-matching pattern"
     );
 }
+
+#[test]
+fn test_vm_tagged_sequence_captures_under_search() {
+    let pattern =
+        Pattern::parse("search(tagged(5, [@a(number), @b(text)]))").unwrap();
+    let cbor_data = cbor(r#"{"x": 5([1, "y"])}"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        @a
+            {"x": 5([1, "y"])}
+                5([1, "y"])
+                    [1, "y"]
+                        1
+        @b
+            {"x": 5([1, "y"])}
+                5([1, "y"])
+                    [1, "y"]
+                        "y"
+        {"x": 5([1, "y"])}
+            5([1, "y"])
+    "#}.trim();
+    assert_actual_expected!(
+        format_paths_with_captures(
+            &paths,
+            &captures,
+            FormatPathsOpts::default()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn test_vm_nested_array_sequence_captures() {
+    // The inner array is matched as a whole by the outer array's program,
+    // so its sequence isn't matched one element at a time
+    let pattern = Pattern::parse("[[@a(number), @b(text)]]").unwrap();
+    let cbor_data = cbor(r#"[[1, "y"]]"#);
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_path_chains!(&cbor_data, &paths, &captures);
+    assert_eq!(paths, vec![vec![cbor_data.clone()]]);
+    assert_eq!(
+        captures["b"],
+        vec![vec![cbor_data.clone(), cbor(r#"[1, "y"]"#), cbor(r#""y""#)]]
+    );
+
+    // A `*` inside the program doesn't add the element as a match of its own
+    let pattern = Pattern::parse("@a([@b(*)])").unwrap();
+    let cbor_data = cbor("[1]");
+    let (paths, captures) = pattern.paths_with_captures(&cbor_data);
+    assert_eq!(paths, vec![vec![cbor_data.clone()]]);
+    assert_eq!(captures["a"], vec![vec![cbor_data.clone()]]);
+}
+
+#[test]
+fn test_vm_wide_array_captures_in_order() {
+    // Every element of a wide array is captured, in document order
+    let elements: Vec<CBOR> = (0..2_000u64).map(CBOR::from).collect();
+    let haystack: CBOR = elements.clone().into();
+    let pattern = Pattern::parse("[(@n(number))*]").unwrap();
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_eq!(paths, vec![vec![haystack.clone()]]);
+    let expected: Vec<Path> = elements
+        .into_iter()
+        .map(|element| vec![haystack.clone(), element])
+        .collect();
+    assert!(captures["n"] == expected);
+
+    // Search results through a wide map are sorted by key, then value
+    let map = parse_dcbor_item(
+        r#"{1: "a", 2: "b", 3: "c", 4: "d", 5: "e", 6: "f", 7: "g", 8: "h",
+            9: "i", 10: "j"}"#,
+    )
+    .unwrap();
+    let pattern = Pattern::parse("search(@x(number | text))").unwrap();
+    let (paths, _) = pattern.paths_with_captures(&map);
+    let found: Vec<String> = paths
+        .iter()
+        .map(|path| path.last().unwrap().diagnostic_flat())
+        .collect();
+    assert_eq!(found[..4], [r#"1"#, r#""a""#, r#"2"#, r#""b""#]);
+    assert_eq!(found.len(), 20);
+}
+
+#[test]
+fn test_vm_and_with_paths_that_move() {
+    // Every operand of `&` matches the value itself, even when one of them
+    // finds its paths deeper inside it, and the match is the value
+    for (source, haystack, expected) in [
+        ("search(1) & number", "[1]", ""),
+        ("search(1) & array", "[1]", "[1]"),
+        ("bstr(cbor: null) & bstr", "h'f6'", "h'f6'"),
+    ] {
+        let pattern = Pattern::parse(source).unwrap();
+        let haystack = cbor(haystack);
+        let program = Program::compile(&pattern);
+        let (paths, _) = program.run(&haystack);
+        assert_eq!(paths, pattern.paths(&haystack), "{}", source);
+        assert_actual_expected!(format_paths(&paths), expected);
+    }
+}
+
+#[test]
+fn test_program_compile_and_run() {
+    // A compiled program finds what the pattern finds, and can be run
+    // against many haystacks
+    let haystacks = [
+        cbor(r#"{"name": "Alice", "tags": ["a", "b"]}"#),
+        cbor(r#"[1, "x", [2, "y"]]"#),
+        cbor("42"),
+    ];
+    for source in [
+        "search(@t(text))",
+        "[@first(*), (*)*]",
+        r#"{"tags": [@tag(text)*]}"#,
+        "@n(number) | @s(text)",
+        "search([(*)*, @last(array)])",
+    ] {
+        let pattern = Pattern::parse(source).unwrap();
+        let program = Program::compile(&pattern);
+        assert!(matches!(program.code.last(), Some(Instr::Accept)));
+        for haystack in &haystacks {
+            assert_eq!(
+                program.run(haystack),
+                pattern.paths_with_captures(haystack),
+                "{} on {}",
+                source,
+                haystack
+            );
+
+            // `run` finds the same paths, in the order the VM finds them
+            let (paths, _) = run(&program, haystack);
+            let (expected, _) = program.run(haystack);
+            assert_eq!(paths.len(), expected.len());
+            assert!(paths.iter().all(|path| expected.contains(path)));
+        }
+    }
+}
+
+#[test]
+fn test_vm_tagged_content_matches_direct_paths() {
+    // A hand-built program that navigates into the content finds the same
+    // paths and captures as the tagged pattern, and as its compiled program
+    let code = vec![
+        Instr::MatchPredicate(0), // Match `tagged(100, *)`
+        Instr::PushAxis(Axis::TaggedContent), // Navigate to the content
+        Instr::CaptureStart(0),   // Start capture
+        Instr::MatchPredicate(1), // Match `number`
+        Instr::CaptureEnd(0),     // End capture
+        Instr::Accept,            // Accept the match
+    ];
+    let literals =
+        vec![Pattern::tagged(100, Pattern::any()), Pattern::any_number()];
+    let program = Program {
+        code,
+        literals,
+        capture_names: vec!["n".to_string()],
+    };
+    let pattern = Pattern::parse("tagged(100, @n(number))").unwrap();
+    for haystack in ["100(42)", "100(\"x\")", "200(42)", "42"] {
+        let haystack = cbor(haystack);
+        let direct = pattern.paths_with_captures(&haystack);
+        assert_eq!(run(&program, &haystack), direct, "{}", haystack);
+        assert_eq!(Program::compile(&pattern).run(&haystack), direct);
+    }
+
+    let haystack = cbor("100(42)");
+    let (paths, captures) = pattern.paths_with_captures(&haystack);
+    assert_eq!(paths, vec![vec![haystack.clone(), cbor("42")]]);
+    assert_eq!(captures["n"], paths);
+
+    // Nested tags continue through each layer
+    let code = vec![
+        Instr::MatchPredicate(0),
+        Instr::PushAxis(Axis::TaggedContent),
+        Instr::MatchPredicate(1),
+        Instr::PushAxis(Axis::TaggedContent),
+        Instr::MatchPredicate(2),
+        Instr::Accept,
+    ];
+    let literals = vec![
+        Pattern::tagged(100, Pattern::any()),
+        Pattern::tagged(200, Pattern::any()),
+        Pattern::any_number(),
+    ];
+    let program = Program { code, literals, capture_names: vec![] };
+    let pattern = Pattern::parse("tagged(100, tagged(200, number))").unwrap();
+    let haystack = cbor("100(200(42))");
+    let (paths, _) = run(&program, &haystack);
+    assert_eq!(paths, pattern.paths(&haystack));
+    assert_eq!(Program::compile(&pattern).run(&haystack).0, paths);
+
+    // expected-text-output-rubric:
+    #[rustfmt::skip]
+    let expected = indoc! {r#"
+        100(200(42))
+            200(42)
+                42
+    "#}.trim();
+    assert_actual_expected!(format_paths(&paths), expected);
+}